@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::{ItemId, ItemKind};
+use controller::prelude::chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Message {
+    Add(Add),
+    Remove(Remove),
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Add {
+    pub kind: ItemKind,
+    pub text: String,
+    /// Free text, e.g. the name of a participant
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Remove {
+    pub id: ItemId,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use controller::prelude::*;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    #[test]
+    fn add() {
+        let json = r#"
+        {
+            "action": "add",
+            "kind": "action_item",
+            "text": "Send the slides",
+            "assignee": "Alice",
+            "due_date": "2023-02-01"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Add(Add {
+            kind,
+            text,
+            assignee,
+            due_date,
+        }) = msg
+        {
+            assert_eq!(kind, ItemKind::ActionItem);
+            assert_eq!(text, "Send the slides");
+            assert_eq!(assignee.as_deref(), Some("Alice"));
+            assert_eq!(due_date, NaiveDate::from_ymd_opt(2023, 2, 1));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let json = r#"
+        {
+            "action": "remove",
+            "id": "00000000-0000-0000-0000-000000000000"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Remove(Remove { id }) = msg {
+            assert_eq!(id, ItemId(Uuid::nil()));
+        } else {
+            panic!()
+        }
+    }
+}
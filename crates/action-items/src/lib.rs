@@ -0,0 +1,341 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! # Action Items Module
+//!
+//! ## Functionality
+//!
+//! Participants collect the decisions and action items of a meeting in a shared list. The items are kept in redis
+//! while the room is alive and moved into the database when the room gets destroyed, where they appear in the summary
+//! of the session.
+//!
+//! The protocol module appends the items which have not been exported yet to the protocol pad before generating a PDF,
+//! see [`take_protocol_text`].
+use anyhow::Result;
+use controller::i18n::{Locale, Message};
+use controller::prelude::chrono::NaiveDate;
+use controller::prelude::*;
+use database::Db;
+use db_storage::action_items::{insert_batch, ActionItemId, ActionItemKind, NewActionItem};
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::{from_utf8, FromStr};
+use std::sync::Arc;
+use types::core::{ParticipantId, Timestamp};
+use uuid::Uuid;
+
+pub mod incoming;
+pub mod outgoing;
+mod storage;
+
+#[derive(
+    Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToRedisArgs, JsonSchema,
+)]
+#[to_redis_args(fmt)]
+pub struct ItemId(pub Uuid);
+
+impl redis::FromRedisValue for ItemId {
+    fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
+        match v {
+            redis::Value::Data(bytes) => {
+                Uuid::from_str(from_utf8(bytes)?).map(Self).map_err(|_| {
+                    redis::RedisError::from((
+                        redis::ErrorKind::TypeError,
+                        "invalid data for ItemId",
+                    ))
+                })
+            }
+            _ => redis::RedisResult::Err(redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "invalid data type for ItemId",
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Decision,
+    ActionItem,
+}
+
+impl From<ItemKind> for ActionItemKind {
+    fn from(kind: ItemKind) -> Self {
+        match kind {
+            ItemKind::Decision => Self::Decision,
+            ItemKind::ActionItem => Self::ActionItem,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToRedisArgs, FromRedisValue, JsonSchema,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct Item {
+    pub id: ItemId,
+    pub kind: ItemKind,
+    pub text: String,
+    pub assignee: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub created_by: ParticipantId,
+    pub created_at: Timestamp,
+}
+
+pub struct ActionItems {
+    room: SignalingRoomId,
+    db: Arc<Db>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl SignalingModule for ActionItems {
+    const NAMESPACE: &'static str = "action_items";
+
+    type Params = ();
+
+    type Incoming = incoming::Message;
+    type Outgoing = outgoing::Message;
+    type RabbitMqMessage = ();
+
+    type ExtEvent = ();
+
+    type FrontendData = Vec<Item>;
+    type PeerFrontendData = ();
+
+    async fn init(
+        ctx: InitContext<'_, Self>,
+        _params: &Self::Params,
+        _protocol: &'static str,
+    ) -> Result<Option<Self>> {
+        Ok(Some(Self {
+            room: ctx.room_id(),
+            db: ctx.db().clone(),
+        }))
+    }
+
+    async fn on_event(
+        &mut self,
+        mut ctx: ModuleContext<'_, Self>,
+        event: Event<'_, Self>,
+    ) -> Result<()> {
+        match event {
+            Event::Joined {
+                control_data: _,
+                frontend_data,
+                participants: _,
+            } => {
+                *frontend_data = Some(storage::get_all(ctx.redis_conn(), self.room).await?);
+            }
+            Event::WsMessage(msg) => self.on_ws_message(&mut ctx, msg).await?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn on_destroy(self, mut ctx: DestroyContext<'_>) {
+        if ctx.destroy_room() {
+            if let Err(e) = self.persist(ctx.redis_conn()).await {
+                log::error!(
+                    "Failed to persist action items of room {}: {:?}",
+                    self.room,
+                    e
+                );
+            }
+
+            if let Err(e) = storage::cleanup(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Failed to cleanup action items of room {} in redis: {:?}",
+                    self.room,
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl ActionItems {
+    async fn on_ws_message(
+        &mut self,
+        ctx: &mut ModuleContext<'_, Self>,
+        msg: incoming::Message,
+    ) -> Result<()> {
+        match msg {
+            incoming::Message::Add(incoming::Add {
+                kind,
+                text,
+                assignee,
+                due_date,
+            }) => {
+                if !matches!(text.chars().count(), 1..=500) {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidText.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                if assignee
+                    .as_ref()
+                    .map(|assignee| !matches!(assignee.chars().count(), 1..=100))
+                    .unwrap_or_default()
+                {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidAssignee.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                let item = Item {
+                    id: ItemId(Uuid::new_v4()),
+                    kind,
+                    text,
+                    assignee,
+                    due_date,
+                    created_by: ctx.participant_id(),
+                    created_at: ctx.timestamp(),
+                };
+
+                storage::add(ctx.redis_conn(), self.room, &item).await?;
+
+                // Sent unchanged to everyone in the room, serialize it only once
+                ctx.rabbitmq_publish_shared(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    outgoing::Message::Added(item),
+                );
+            }
+            incoming::Message::Remove(incoming::Remove { id }) => {
+                let item = match storage::get(ctx.redis_conn(), self.room, id).await? {
+                    Some(item) => item,
+                    None => {
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::UnknownItem.into(),
+                        ));
+
+                        return Ok(());
+                    }
+                };
+
+                // Items can only be removed by their creator or a moderator
+                if item.created_by != ctx.participant_id() && ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                if storage::remove(ctx.redis_conn(), self.room, id).await? {
+                    ctx.rabbitmq_publish_shared(
+                        control::rabbitmq::current_room_exchange_name(self.room),
+                        control::rabbitmq::room_all_routing_key().into(),
+                        outgoing::Message::Removed(outgoing::Removed { id }),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move the items of the room from redis into the database
+    async fn persist(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        let items: Vec<NewActionItem> = storage::get_all(redis_conn, self.room)
+            .await?
+            .into_iter()
+            .map(|item| NewActionItem {
+                id: ActionItemId::from(item.id.0),
+                room_id: self.room.room_id(),
+                breakout_room_id: self.room.breakout_room_id(),
+                kind: item.kind.into(),
+                text: item.text,
+                assignee: item.assignee,
+                due_date: item.due_date,
+                created_at: *item.created_at,
+            })
+            .collect();
+
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let db = self.db.clone();
+        controller::block(move || {
+            let mut conn = db.get_conn()?;
+
+            insert_batch(&mut conn, &items)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+/// Get the text of the items which have not been exported into the protocol yet and mark them as exported
+///
+/// Returns `None` when there are no new items.
+pub async fn take_protocol_text(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    locale: Locale,
+) -> Result<Option<String>> {
+    let items = storage::take_unexported(redis_conn, room).await?;
+
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    let mut text = String::new();
+
+    for (kind, heading) in [
+        (ItemKind::Decision, Message::ProtocolDecisions),
+        (ItemKind::ActionItem, Message::ProtocolActionItems),
+    ] {
+        let mut items = items.iter().filter(|item| item.kind == kind).peekable();
+
+        if items.peek().is_none() {
+            continue;
+        }
+
+        text.push('\n');
+        text.push_str(locale.message(heading));
+        text.push('\n');
+
+        for item in items {
+            text.push_str("- ");
+            text.push_str(&item.text);
+
+            let details: Vec<String> = item
+                .assignee
+                .iter()
+                .cloned()
+                .chain(item.due_date.map(|due_date| due_date.to_string()))
+                .collect();
+
+            if !details.is_empty() {
+                text.push_str(&format!(" ({})", details.join(", ")));
+            }
+
+            text.push('\n');
+        }
+    }
+
+    Ok(Some(text))
+}
+
+pub fn register(controller: &mut controller::Controller) {
+    controller.signaling.add_module::<ActionItems>(());
+}
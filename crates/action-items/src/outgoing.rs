@@ -0,0 +1,96 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::{Item, ItemId};
+use schemars::JsonSchema;
+use serde::Serialize;
+use types::signaling::{ErrorEnvelope, ModuleError};
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "message", rename_all = "snake_case")]
+pub enum Message {
+    Added(Item),
+    Removed(Removed),
+    Error(ErrorEnvelope<Error>),
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct Removed {
+    pub id: ItemId,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "error")]
+pub enum Error {
+    InsufficientPermissions,
+    InvalidText,
+    InvalidAssignee,
+    UnknownItem,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "action_items";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::InvalidText => "The text must contain between 1 and 500 characters",
+            Self::InvalidAssignee => "The assignee must contain between 1 and 100 characters",
+            Self::UnknownItem => "The item does not exist",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ItemKind;
+    use controller::prelude::chrono::NaiveDate;
+    use controller::prelude::*;
+    use test_util::assert_eq_json;
+    use types::core::{ParticipantId, Timestamp};
+    use uuid::Uuid;
+
+    #[test]
+    fn added() {
+        let added = Message::Added(Item {
+            id: ItemId(Uuid::nil()),
+            kind: ItemKind::ActionItem,
+            text: "Send the slides".into(),
+            assignee: Some("Alice".into()),
+            due_date: NaiveDate::from_ymd_opt(2023, 2, 1),
+            created_by: ParticipantId::nil(),
+            created_at: Timestamp::unix_epoch(),
+        });
+
+        assert_eq_json!(
+          added,
+          {
+              "message": "added",
+              "id": "00000000-0000-0000-0000-000000000000",
+              "kind": "action_item",
+              "text": "Send the slides",
+              "assignee": "Alice",
+              "due_date": "2023-02-01",
+              "created_by": "00000000-0000-0000-0000-000000000000",
+              "created_at": "1970-01-01T00:00:00Z"
+          }
+        );
+    }
+
+    #[test]
+    fn removed() {
+        let removed = Message::Removed(Removed {
+            id: ItemId(Uuid::nil()),
+        });
+
+        assert_eq_json!(
+          removed,
+          {
+              "message": "removed",
+              "id": "00000000-0000-0000-0000-000000000000"
+          }
+        );
+    }
+}
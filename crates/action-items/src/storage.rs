@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::{Item, ItemId};
+use anyhow::{Context, Result};
+use controller::prelude::*;
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
+use std::collections::HashSet;
+
+/// Key to the hash of all items of the room, mapping the id of an item to the item
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:action_items:items")]
+struct Items {
+    room: SignalingRoomId,
+}
+
+/// Key to the set of ids of the items which have already been exported into the protocol
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:action_items:exported")]
+struct ExportedItems {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn, item))]
+pub(super) async fn add(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    item: &Item,
+) -> Result<()> {
+    redis_conn
+        .hset(Items { room }, item.id, Encrypted(item))
+        .await
+        .with_context(|| format!("Failed to add action item, room={room}"))
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn get(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: ItemId,
+) -> Result<Option<Item>> {
+    let item: Option<Encrypted<Item>> = redis_conn
+        .hget(Items { room }, id)
+        .await
+        .with_context(|| format!("Failed to get action item, room={room}"))?;
+
+    Ok(item.map(Encrypted::into_inner))
+}
+
+/// Get all items of the room, ordered by their creation time
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn get_all(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<Item>> {
+    let items: Vec<Encrypted<Item>> = redis_conn
+        .hvals(Items { room })
+        .await
+        .with_context(|| format!("Failed to get action items, room={room}"))?;
+
+    let mut items: Vec<Item> = items.into_iter().map(Encrypted::into_inner).collect();
+    items.sort_by_key(|item| item.created_at);
+
+    Ok(items)
+}
+
+/// Remove an item, returns false if the item did not exist
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn remove(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: ItemId,
+) -> Result<bool> {
+    redis_conn
+        .hdel(Items { room }, id)
+        .await
+        .with_context(|| format!("Failed to remove action item, room={room}"))
+}
+
+/// Get the items which have not been exported yet and mark them as exported
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn take_unexported(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<Item>> {
+    let exported: HashSet<String> = redis_conn
+        .smembers(ExportedItems { room })
+        .await
+        .with_context(|| format!("Failed to get exported action items, room={room}"))?;
+
+    let items: Vec<Item> = get_all(redis_conn, room)
+        .await?
+        .into_iter()
+        .filter(|item| !exported.contains(&item.id.to_string()))
+        .collect();
+
+    if !items.is_empty() {
+        let ids: Vec<ItemId> = items.iter().map(|item| item.id).collect();
+
+        redis_conn
+            .sadd(ExportedItems { room }, ids)
+            .await
+            .with_context(|| format!("Failed to mark action items as exported, room={room}"))?;
+    }
+
+    Ok(items)
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn cleanup(redis_conn: &mut RedisConnection, room: SignalingRoomId) -> Result<()> {
+    redis_conn
+        .del(Items { room })
+        .await
+        .with_context(|| format!("Failed to remove action items, room={room}"))?;
+
+    redis_conn
+        .del(ExportedItems { room })
+        .await
+        .with_context(|| format!("Failed to remove exported action items, room={room}"))
+}
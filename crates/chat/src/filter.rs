@@ -0,0 +1,263 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Filter pipeline for chat messages
+//!
+//! Every message passes the configured filters before it is stored or sent. A filter can block the message, redact
+//! parts of it or flag it for review by the moderators of the room. Failing filters are skipped, so an unavailable
+//! moderation service does not break the chat.
+use anyhow::{Context, Result};
+use controller::prelude::*;
+use controller_shared::settings::{ChatFilter, ChatFilterAction, ChatModerationApi};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Result of a single [`MessageFilter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Block { reason: String },
+    Redact { content: String },
+    Flag { reason: String },
+}
+
+/// A filter which checks chat messages before they are sent
+#[async_trait::async_trait]
+pub trait MessageFilter: Send + Sync {
+    async fn check(&self, content: &str) -> Result<Verdict>;
+}
+
+/// Result of the [`FilterPipeline`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterOutcome {
+    /// Send the message with the possibly redacted content, flagged with the reasons if not empty
+    Send { content: String, flags: Vec<String> },
+    /// The message must not be sent
+    Blocked { reason: String },
+}
+
+/// Ordered list of filters applied to every chat message
+#[derive(Default)]
+pub struct FilterPipeline {
+    filters: Vec<Box<dyn MessageFilter>>,
+}
+
+impl FilterPipeline {
+    /// Creates the pipeline from the `chat_filter` settings
+    pub fn from_settings(settings: &ChatFilter) -> Result<Self> {
+        let mut pipeline = Self::default();
+
+        for rule in &settings.rules {
+            pipeline = pipeline.with_filter(RegexFilter::new(&rule.pattern, rule.action)?);
+        }
+
+        if let Some(moderation_api) = &settings.moderation_api {
+            pipeline = pipeline.with_filter(ModerationApiFilter::new(moderation_api)?);
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Append a filter to the pipeline
+    pub fn with_filter(mut self, filter: impl MessageFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Pass the message through all filters, stops at the first filter blocking the message
+    pub async fn apply(&self, mut content: String) -> FilterOutcome {
+        let mut flags = vec![];
+
+        for filter in &self.filters {
+            match filter.check(&content).await {
+                Ok(Verdict::Allow) => {}
+                Ok(Verdict::Block { reason }) => return FilterOutcome::Blocked { reason },
+                Ok(Verdict::Redact { content: redacted }) => content = redacted,
+                Ok(Verdict::Flag { reason }) => flags.push(reason),
+                Err(e) => log::warn!("Chat filter failed, skipping it, {:?}", e),
+            }
+        }
+
+        FilterOutcome::Send { content, flags }
+    }
+}
+
+/// Replace every character of the text with an asterisk
+fn redact(text: &str) -> String {
+    "*".repeat(text.chars().count())
+}
+
+/// Filter which matches the message against a regular expression
+pub struct RegexFilter {
+    regex: Regex,
+    action: ChatFilterAction,
+}
+
+impl RegexFilter {
+    pub fn new(pattern: &str, action: ChatFilterAction) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid chat filter pattern {pattern:?}"))?;
+
+        Ok(Self { regex, action })
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageFilter for RegexFilter {
+    async fn check(&self, content: &str) -> Result<Verdict> {
+        if !self.regex.is_match(content) {
+            return Ok(Verdict::Allow);
+        }
+
+        let reason = format!("matched filter rule {:?}", self.regex.as_str());
+
+        let verdict = match self.action {
+            ChatFilterAction::Block => Verdict::Block { reason },
+            ChatFilterAction::Redact => Verdict::Redact {
+                content: self
+                    .regex
+                    .replace_all(content, |captures: &regex::Captures| redact(&captures[0]))
+                    .into_owned(),
+            },
+            ChatFilterAction::Flag => Verdict::Flag { reason },
+        };
+
+        Ok(verdict)
+    }
+}
+
+#[derive(Serialize)]
+struct ModerationRequest<'s> {
+    content: &'s str,
+}
+
+#[derive(Deserialize)]
+struct ModerationResponse {
+    flagged: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Filter which lets an external service classify the message
+pub struct ModerationApiFilter {
+    client: reqwest::Client,
+    url: url::Url,
+    action: ChatFilterAction,
+}
+
+impl ModerationApiFilter {
+    pub fn new(settings: &ChatModerationApi) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .build()
+            .context("Failed to build http client for the chat moderation api")?;
+
+        Ok(Self {
+            client,
+            url: settings.url.clone(),
+            action: settings.action,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageFilter for ModerationApiFilter {
+    async fn check(&self, content: &str) -> Result<Verdict> {
+        let response: ModerationResponse = self
+            .client
+            .post(self.url.clone())
+            .json(&ModerationRequest { content })
+            .send()
+            .await
+            .context("Failed to send message to the chat moderation api")?
+            .error_for_status()
+            .context("Chat moderation api returned an error")?
+            .json()
+            .await
+            .context("Invalid response of the chat moderation api")?;
+
+        if !response.flagged {
+            return Ok(Verdict::Allow);
+        }
+
+        let reason = response
+            .reason
+            .unwrap_or_else(|| "flagged by moderation api".into());
+
+        let verdict = match self.action {
+            ChatFilterAction::Block => Verdict::Block { reason },
+            ChatFilterAction::Redact => Verdict::Redact {
+                content: redact(content),
+            },
+            ChatFilterAction::Flag => Verdict::Flag { reason },
+        };
+
+        Ok(verdict)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct FailingFilter;
+
+    #[async_trait::async_trait]
+    impl MessageFilter for FailingFilter {
+        async fn check(&self, _: &str) -> Result<Verdict> {
+            anyhow::bail!("service unavailable")
+        }
+    }
+
+    #[actix_rt::test]
+    async fn redact_matches() {
+        let pipeline = FilterPipeline::default()
+            .with_filter(RegexFilter::new(r"(?i)\bdarn\b", ChatFilterAction::Redact).unwrap());
+
+        assert_eq!(
+            pipeline.apply("Darn it, darnation".into()).await,
+            FilterOutcome::Send {
+                content: "**** it, darnation".into(),
+                flags: vec![]
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn block_stops_pipeline() {
+        let pipeline = FilterPipeline::default()
+            .with_filter(RegexFilter::new("spam", ChatFilterAction::Block).unwrap())
+            .with_filter(RegexFilter::new("spam", ChatFilterAction::Flag).unwrap());
+
+        assert!(matches!(
+            pipeline.apply("buy spam".into()).await,
+            FilterOutcome::Blocked { .. }
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn flag_and_skip_failing_filter() {
+        let pipeline = FilterPipeline::default()
+            .with_filter(FailingFilter)
+            .with_filter(RegexFilter::new("meet me", ChatFilterAction::Flag).unwrap());
+
+        assert_eq!(
+            pipeline.apply("meet me outside".into()).await,
+            FilterOutcome::Send {
+                content: "meet me outside".into(),
+                flags: vec![r#"matched filter rule "meet me""#.into()]
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(RegexFilter::new("(unclosed", ChatFilterAction::Block).is_err());
+    }
+}
@@ -2,11 +2,12 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use crate::Scope;
+use crate::{MessageId, Scope};
+use schemars::JsonSchema;
 use serde::Deserialize;
-use types::core::Timestamp;
+use types::core::{ParticipantId, Timestamp};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Message {
     EnableChat,
@@ -18,9 +19,37 @@ pub enum Message {
         scope: Scope,
         timestamp: Timestamp,
     },
+    /// Acknowledge that private messages of the `source` have been delivered to the client
+    AckDelivered {
+        source: ParticipantId,
+        ids: Vec<MessageId>,
+    },
+    /// Acknowledge that private messages of the `source` have been read
+    AckRead {
+        source: ParticipantId,
+        ids: Vec<MessageId>,
+    },
+    /// Moderator only, pin a message of the global chat
+    PinMessage {
+        id: MessageId,
+    },
+    /// Moderator only, unpin a message of the global chat
+    UnpinMessage {
+        id: MessageId,
+    },
+    /// Moderator only, remove a pinned announcement
+    UnpinAnnouncement {
+        id: MessageId,
+    },
+    /// Moderator only, get the messages flagged by the chat filter
+    GetFlaggedMessages,
+    /// Moderator only, remove a message from the flagged messages after reviewing it
+    DismissFlaggedMessage {
+        id: MessageId,
+    },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct SendMessage {
     pub content: String,
     #[serde(flatten)]
@@ -33,7 +62,7 @@ mod test {
     use controller::prelude::serde_json;
     use pretty_assertions::assert_eq;
     use serde_json::json;
-    use types::core::{GroupName, ParticipantId};
+    use types::core::GroupName;
 
     #[test]
     fn user_private_message() {
@@ -93,4 +122,72 @@ mod test {
             panic!()
         }
     }
+
+    #[test]
+    fn announcement_message() {
+        let json = json!({
+            "action": "send_message",
+            "scope": "announcement",
+            "content": "Break until 11:00"
+        });
+
+        let msg: Message = serde_json::from_value(json).unwrap();
+
+        if let Message::SendMessage(SendMessage { content, scope }) = msg {
+            assert_eq!(scope, Scope::Announcement);
+            assert_eq!(content, "Break until 11:00");
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn ack_read() {
+        let json = json!({
+            "action": "ack_read",
+            "source": "00000000-0000-0000-0000-000000000000",
+            "ids": ["00000000-0000-0000-0000-000000000000"]
+        });
+
+        let msg: Message = serde_json::from_value(json).unwrap();
+
+        if let Message::AckRead { source, ids } = msg {
+            assert_eq!(source, ParticipantId::nil());
+            assert_eq!(ids, vec![MessageId::nil()]);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn pin_message() {
+        let json = json!({
+            "action": "pin_message",
+            "id": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let msg: Message = serde_json::from_value(json).unwrap();
+
+        if let Message::PinMessage { id } = msg {
+            assert_eq!(id, MessageId::nil());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn dismiss_flagged_message() {
+        let json = json!({
+            "action": "dismiss_flagged_message",
+            "id": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let msg: Message = serde_json::from_value(json).unwrap();
+
+        if let Message::DismissFlaggedMessage { id } = msg {
+            assert_eq!(id, MessageId::nil());
+        } else {
+            panic!()
+        }
+    }
 }
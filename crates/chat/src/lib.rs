@@ -8,14 +8,22 @@
 //!
 //! Issues timestamp and messageIds to incoming chat messages and forwards them to other participants in the room or group.
 //! For this the rabbitmq room exchange or target group exchange is used.
+//!
+//! Messages pass the configured [`filter`] pipeline first, which can block, redact or flag them. Flagged messages are
+//! kept in a queue which moderators can review.
 use anyhow::{Context, Result};
 use control::rabbitmq;
 use controller::prelude::*;
 use database::Db;
 use db_storage::groups::Group;
-use outgoing::{ChatDisabled, ChatEnabled, HistoryCleared, MessageSent};
+use filter::{FilterOutcome, FilterPipeline};
+use outgoing::{
+    AnnouncementUnpinned, ChatDisabled, ChatEnabled, FlaggedMessage, FlaggedMessages,
+    HistoryCleared, MessageSent, PinUpdate, Receipt,
+};
 use r3dlock::Mutex;
 use redis_args::ToRedisArgs;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
@@ -24,6 +32,7 @@ use std::sync::Arc;
 use storage::StoredMessage;
 use types::core::{GroupId, GroupName, ParticipantId, Timestamp, UserId};
 
+pub mod filter;
 pub mod incoming;
 pub mod outgoing;
 mod storage;
@@ -34,15 +43,17 @@ fn group_routing_key(group_id: GroupId) -> String {
     format!("group.{group_id}")
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "scope", content = "target", rename_all = "snake_case")]
 pub enum Scope {
     Global,
     Group(GroupName),
     Private(ParticipantId),
+    /// Message of a moderator to all participants, which is pinned until it gets unpinned
+    Announcement,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, ToRedisArgs)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, ToRedisArgs, JsonSchema)]
 #[to_redis_args(fmt)]
 pub struct MessageId(uuid::Uuid);
 
@@ -102,22 +113,85 @@ pub struct Chat {
     last_seen_timestamps_group: HashMap<GroupName, Timestamp>,
     db: Arc<Db>,
     groups: Vec<Group>,
+    filter: Arc<FilterPipeline>,
+    webinar_mode: bool,
 }
 
 impl Chat {
     fn get_group(&self, name: &GroupName) -> Option<&Group> {
         self.groups.iter().find(|group| group.name == *name)
     }
+
+    /// Attendees of a room in webinar mode can only send private messages to moderators and panelists
+    async fn may_send(&self, ctx: &mut ModuleContext<'_, Self>, scope: &Scope) -> Result<bool> {
+        if !self.webinar_mode
+            || ctx.role() == Role::Moderator
+            || moderation::storage::is_panelist(ctx.redis_conn(), self.room.room_id(), self.id)
+                .await?
+        {
+            return Ok(true);
+        }
+
+        let target = if let Scope::Private(target) = scope {
+            *target
+        } else {
+            return Ok(false);
+        };
+
+        let role: Option<Role> =
+            control::storage::get_attribute(ctx.redis_conn(), self.room, target, "role").await?;
+
+        if role == Some(Role::Moderator) {
+            return Ok(true);
+        }
+
+        moderation::storage::is_panelist(ctx.redis_conn(), self.room.room_id(), target).await
+    }
+
+    /// Add the message to the flagged messages of the room and notify the moderators
+    async fn flag_message(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        message: &MessageSent,
+        reasons: Vec<String>,
+    ) -> Result<()> {
+        if reasons.is_empty() {
+            return Ok(());
+        }
+
+        let flagged = FlaggedMessage {
+            id: message.id,
+            source: message.source,
+            timestamp: ctx.timestamp(),
+            content: message.content.clone(),
+            scope: message.scope.clone(),
+            reasons,
+        };
+
+        storage::add_flagged_message(ctx.redis_conn(), self.room, &flagged).await?;
+
+        ctx.rabbitmq_publish(
+            rabbitmq::current_room_exchange_name(self.room),
+            rabbitmq::room_all_routing_key().into(),
+            outgoing::Message::MessageFlagged(flagged),
+        );
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct ChatState {
     enabled: bool,
     room_history: Vec<StoredMessage>,
+    announcements: Vec<StoredMessage>,
+    pinned_messages: Vec<MessageId>,
     groups_history: Vec<GroupHistory>,
     last_seen_timestamp_global: Option<Timestamp>,
     last_seen_timestamps_private: HashMap<ParticipantId, Timestamp>,
     last_seen_timestamps_group: HashMap<GroupName, Timestamp>,
+    /// Number of unread private messages per sender
+    unread_private: HashMap<ParticipantId, u32>,
 }
 
 impl ChatState {
@@ -129,6 +203,8 @@ impl ChatState {
     ) -> Result<Self> {
         let enabled = storage::is_chat_enabled(redis_conn, room.room_id()).await?;
         let room_history = storage::get_room_chat_history(redis_conn, room).await?;
+        let announcements = storage::get_announcements(redis_conn, room).await?;
+        let pinned_messages = storage::get_pinned_messages(redis_conn, room).await?;
         let mut groups_history = Vec::new();
         for group in groups {
             storage::add_participant_to_set(redis_conn, room, group.id, participant).await?;
@@ -147,14 +223,18 @@ impl ChatState {
             storage::get_last_seen_timestamps_private(redis_conn, room, participant).await?;
         let last_seen_timestamps_group =
             storage::get_last_seen_timestamps_group(redis_conn, room, participant).await?;
+        let unread_private = storage::get_unread_private(redis_conn, room, participant).await?;
 
         Ok(Self {
             room_history,
+            announcements,
+            pinned_messages,
             enabled,
             groups_history,
             last_seen_timestamp_global,
             last_seen_timestamps_private,
             last_seen_timestamps_group,
+            unread_private,
         })
     }
 }
@@ -174,7 +254,10 @@ pub struct PeerFrontendData {
 impl SignalingModule for Chat {
     const NAMESPACE: &'static str = "chat";
 
-    type Params = ();
+    /// Version 2 fixed the swapped keys of the private and global last-seen timestamps
+    const KEY_VERSION: u32 = 2;
+
+    type Params = Arc<FilterPipeline>;
 
     type Incoming = incoming::Message;
     type Outgoing = outgoing::Message;
@@ -187,7 +270,7 @@ impl SignalingModule for Chat {
 
     async fn init(
         mut ctx: InitContext<'_, Self>,
-        _params: &Self::Params,
+        filter: &Self::Params,
         _protocol: &'static str,
     ) -> Result<Option<Self>> {
         let id = ctx.participant_id();
@@ -224,6 +307,8 @@ impl SignalingModule for Chat {
             room,
             db: ctx.db().clone(),
             groups,
+            filter: filter.clone(),
+            webinar_mode: ctx.room().webinar_mode,
             last_seen_timestamp_global: None,
             last_seen_timestamps_private: HashMap::new(),
             last_seen_timestamps_group: HashMap::new(),
@@ -366,7 +451,7 @@ impl SignalingModule for Chat {
             Event::WsMessage(incoming::Message::EnableChat) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -382,7 +467,7 @@ impl SignalingModule for Chat {
             Event::WsMessage(incoming::Message::DisableChat) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -404,11 +489,30 @@ impl SignalingModule for Chat {
                     return Ok(());
                 }
 
-                let chat_enabled =
-                    storage::is_chat_enabled(ctx.redis_conn(), self.room.room_id()).await?;
+                let is_announcement = scope == Scope::Announcement;
+
+                if is_announcement && ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if !self.may_send(&mut ctx, &scope).await? {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                // Announcements are sent even if the chat is disabled
+                let chat_enabled = is_announcement
+                    || storage::is_chat_enabled(ctx.redis_conn(), self.room.room_id()).await?;
 
                 if !chat_enabled {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::ChatDisabled));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::ChatDisabled.into(),
+                    ));
                     return Ok(());
                 }
 
@@ -427,6 +531,18 @@ impl SignalingModule for Chat {
                     content.truncate(last_idx);
                 }
 
+                let (content, flags) = match self.filter.apply(content).await {
+                    FilterOutcome::Send { content, flags } => (content, flags),
+                    FilterOutcome::Blocked { reason } => {
+                        log::debug!("Blocked chat message of {}, {}", self.id, reason);
+
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::MessageBlocked.into(),
+                        ));
+                        return Ok(());
+                    }
+                };
+
                 let source = self.id;
 
                 match scope {
@@ -438,6 +554,12 @@ impl SignalingModule for Chat {
                             scope: Scope::Private(target),
                         };
 
+                        self.flag_message(&mut ctx, &out_message_contents, flags)
+                            .await?;
+
+                        storage::add_unread_private(ctx.redis_conn(), self.room, target, source)
+                            .await?;
+
                         let out_message = outgoing::Message::MessageSent(out_message_contents);
 
                         ctx.rabbitmq_publish(
@@ -457,6 +579,9 @@ impl SignalingModule for Chat {
                                 scope: Scope::Group(group_name),
                             };
 
+                            self.flag_message(&mut ctx, &out_message_contents, flags)
+                                .await?;
+
                             let stored_msg = StoredMessage {
                                 id: out_message_contents.id,
                                 source: out_message_contents.source,
@@ -490,6 +615,9 @@ impl SignalingModule for Chat {
                             scope: Scope::Global,
                         };
 
+                        self.flag_message(&mut ctx, &out_message_contents, flags)
+                            .await?;
+
                         let stored_msg = StoredMessage {
                             id: out_message_contents.id,
                             source: out_message_contents.source,
@@ -507,18 +635,152 @@ impl SignalingModule for Chat {
 
                         let out_message = outgoing::Message::MessageSent(out_message_contents);
 
-                        ctx.rabbitmq_publish(
+                        // Sent unchanged to everyone in the room, serialize it only once
+                        ctx.rabbitmq_publish_shared(
                             rabbitmq::current_room_exchange_name(self.room),
                             rabbitmq::room_all_routing_key().into(),
                             out_message,
                         );
                     }
+                    Scope::Announcement => {
+                        let out_message_contents = MessageSent {
+                            id: MessageId::new(),
+                            source,
+                            content,
+                            scope: Scope::Announcement,
+                        };
+
+                        self.flag_message(&mut ctx, &out_message_contents, flags)
+                            .await?;
+
+                        let stored_msg = StoredMessage {
+                            id: out_message_contents.id,
+                            source: out_message_contents.source,
+                            content: out_message_contents.content.clone(),
+                            scope: out_message_contents.scope.clone(),
+                            timestamp: ctx.timestamp(),
+                        };
+
+                        storage::add_announcement(ctx.redis_conn(), self.room, &stored_msg).await?;
+
+                        let out_message = outgoing::Message::MessageSent(out_message_contents);
+
+                        // Sent unchanged to everyone in the room, serialize it only once
+                        ctx.rabbitmq_publish_shared(
+                            rabbitmq::current_room_exchange_name(self.room),
+                            rabbitmq::room_all_routing_key().into(),
+                            out_message,
+                        );
+                    }
+                }
+            }
+            Event::WsMessage(incoming::Message::AckDelivered { source, ids }) => {
+                ctx.rabbitmq_publish(
+                    rabbitmq::current_room_exchange_name(self.room),
+                    rabbitmq::room_participant_routing_key(source),
+                    outgoing::Message::MessageDelivered(Receipt {
+                        ids,
+                        recipient: self.id,
+                    }),
+                );
+            }
+            Event::WsMessage(incoming::Message::AckRead { source, ids }) => {
+                if ids.is_empty() {
+                    return Ok(());
+                }
+
+                storage::mark_private_read(ctx.redis_conn(), self.room, self.id, source, ids.len())
+                    .await?;
+
+                ctx.rabbitmq_publish(
+                    rabbitmq::current_room_exchange_name(self.room),
+                    rabbitmq::room_participant_routing_key(source),
+                    outgoing::Message::MessageRead(Receipt {
+                        ids,
+                        recipient: self.id,
+                    }),
+                );
+            }
+            Event::WsMessage(incoming::Message::PinMessage { id }) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let history = storage::get_room_chat_history(ctx.redis_conn(), self.room).await?;
+
+                if !history.iter().any(|message| message.id == id) {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownMessage.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if storage::pin_message(ctx.redis_conn(), self.room, id, ctx.timestamp()).await? {
+                    ctx.rabbitmq_publish(
+                        rabbitmq::current_room_exchange_name(self.room),
+                        rabbitmq::room_all_routing_key().into(),
+                        outgoing::Message::MessagePinned(PinUpdate {
+                            id,
+                            issued_by: self.id,
+                        }),
+                    );
                 }
             }
+            Event::WsMessage(incoming::Message::UnpinMessage { id }) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if !storage::unpin_message(ctx.redis_conn(), self.room, id).await? {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownMessage.into(),
+                    ));
+                    return Ok(());
+                }
+
+                ctx.rabbitmq_publish(
+                    rabbitmq::current_room_exchange_name(self.room),
+                    rabbitmq::room_all_routing_key().into(),
+                    outgoing::Message::MessageUnpinned(PinUpdate {
+                        id,
+                        issued_by: self.id,
+                    }),
+                );
+            }
+            Event::WsMessage(incoming::Message::UnpinAnnouncement { id }) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if !storage::remove_announcement(ctx.redis_conn(), self.room, id).await? {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownAnnouncement.into(),
+                    ));
+                    return Ok(());
+                }
+
+                ctx.rabbitmq_publish(
+                    rabbitmq::current_room_exchange_name(self.room),
+                    rabbitmq::room_all_routing_key().into(),
+                    outgoing::Message::AnnouncementUnpinned(AnnouncementUnpinned {
+                        id,
+                        issued_by: self.id,
+                    }),
+                );
+            }
             Event::WsMessage(incoming::Message::ClearHistory) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -527,6 +789,9 @@ impl SignalingModule for Chat {
                 {
                     log::error!("Failed to clear room chat history, {}", e);
                 }
+                if let Err(e) = storage::delete_pinned_messages(ctx.redis_conn(), self.room).await {
+                    log::error!("Failed to clear pinned messages, {}", e);
+                }
 
                 ctx.rabbitmq_publish(
                     rabbitmq::current_room_exchange_name(self.room),
@@ -548,8 +813,51 @@ impl SignalingModule for Chat {
                     Scope::Global => {
                         self.last_seen_timestamp_global = Some(timestamp);
                     }
+                    // Announcements stay visible until unpinned, nothing to track
+                    Scope::Announcement => {}
                 };
             }
+            Event::WsMessage(incoming::Message::GetFlaggedMessages) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let messages = storage::get_flagged_messages(ctx.redis_conn(), self.room).await?;
+
+                ctx.ws_send(outgoing::Message::FlaggedMessages(FlaggedMessages {
+                    messages,
+                }));
+            }
+            Event::WsMessage(incoming::Message::DismissFlaggedMessage { id }) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if !storage::remove_flagged_message(ctx.redis_conn(), self.room, id).await? {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownFlaggedMessage.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let messages = storage::get_flagged_messages(ctx.redis_conn(), self.room).await?;
+
+                ctx.ws_send(outgoing::Message::FlaggedMessages(FlaggedMessages {
+                    messages,
+                }));
+            }
+            Event::RabbitMq(outgoing::Message::MessageFlagged(flagged)) => {
+                // Only moderators review flagged messages
+                if ctx.role() == Role::Moderator {
+                    ctx.ws_send(outgoing::Message::MessageFlagged(flagged));
+                }
+            }
             Event::RabbitMq(msg) => {
                 ctx.ws_send(msg);
             }
@@ -570,6 +878,18 @@ impl SignalingModule for Chat {
             {
                 log::error!("Failed to clean up chat enabled flag {}", e);
             }
+            if let Err(e) = storage::delete_pinned_messages(ctx.redis_conn(), self.room).await {
+                log::error!("Failed to remove pinned messages on room destroy, {}", e);
+            }
+            if let Err(e) = storage::delete_announcements(ctx.redis_conn(), self.room).await {
+                log::error!("Failed to remove announcements on room destroy, {}", e);
+            }
+            if let Err(e) = storage::delete_flagged_messages(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Failed to remove flagged chat messages on room destroy, {}",
+                    e
+                );
+            }
 
             let participants = control::storage::get_all_participants(ctx.redis_conn(), self.room)
                 .await
@@ -614,6 +934,11 @@ impl SignalingModule for Chat {
                         e
                     );
                 }
+                if let Err(e) =
+                    storage::delete_unread_private(ctx.redis_conn(), self.room, participant).await
+                {
+                    log::error!("Failed to clean up unread private messages, {}", e);
+                }
             }
         } else {
             if let Some(timestamp) = self.last_seen_timestamp_global {
@@ -717,10 +1042,31 @@ impl SignalingModule for Chat {
             }
         }
     }
+
+    async fn migrate_keys(
+        _: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        from_version: u32,
+    ) -> Result<()> {
+        if from_version < 2 {
+            storage::migrate_last_seen_timestamps(redis_conn).await?;
+        }
+
+        Ok(())
+    }
 }
 
-pub fn register(controller: &mut controller::Controller) {
-    controller.signaling.add_module::<Chat>(());
+pub fn register(controller: &mut controller::Controller) -> Result<()> {
+    let filter = match &controller.shared_settings.load_full().chat_filter {
+        Some(settings) => {
+            FilterPipeline::from_settings(settings).context("Invalid chat_filter configuration")?
+        }
+        None => FilterPipeline::default(),
+    };
+
+    controller.signaling.add_module::<Chat>(Arc::new(filter));
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -2,32 +2,46 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use controller::prelude::serde_json;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use types::core::ParticipantId;
+use types::core::{ParticipantId, Timestamp};
+use types::signaling::{ErrorEnvelope, ModuleError};
 
 use crate::{MessageId, Scope};
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     ChatEnabled(ChatEnabled),
     ChatDisabled(ChatDisabled),
     MessageSent(MessageSent),
     HistoryCleared(HistoryCleared),
-    Error(Error),
+    /// Sent to the source of private messages once the recipient acknowledged the delivery
+    MessageDelivered(Receipt),
+    /// Sent to the source of private messages once the recipient read them
+    MessageRead(Receipt),
+    MessagePinned(PinUpdate),
+    MessageUnpinned(PinUpdate),
+    AnnouncementUnpinned(AnnouncementUnpinned),
+    /// Sent to the moderators when a message has been flagged by the chat filter
+    MessageFlagged(FlaggedMessage),
+    FlaggedMessages(FlaggedMessages),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct ChatEnabled {
     pub issued_by: ParticipantId,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct ChatDisabled {
     pub issued_by: ParticipantId,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct MessageSent {
     pub id: MessageId,
     pub source: ParticipantId,
@@ -36,16 +50,76 @@ pub struct MessageSent {
     pub scope: Scope,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct HistoryCleared {
     pub issued_by: ParticipantId,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct Receipt {
+    pub ids: Vec<MessageId>,
+    pub recipient: ParticipantId,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct PinUpdate {
+    pub id: MessageId,
+    pub issued_by: ParticipantId,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct AnnouncementUnpinned {
+    pub id: MessageId,
+    pub issued_by: ParticipantId,
+}
+
+/// A message flagged by the chat filter, waiting for review by a moderator
+#[derive(
+    Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema, ToRedisArgs, FromRedisValue,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct FlaggedMessage {
+    pub id: MessageId,
+    pub source: ParticipantId,
+    pub timestamp: Timestamp,
+    pub content: String,
+    #[serde(flatten)]
+    pub scope: Scope,
+    /// Why the filters flagged the message
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct FlaggedMessages {
+    /// Oldest message first
+    pub messages: Vec<FlaggedMessage>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "error", rename_all = "snake_case")]
 pub enum Error {
     ChatDisabled,
     InsufficientPermissions,
+    MessageBlocked,
+    UnknownFlaggedMessage,
+    UnknownAnnouncement,
+    UnknownMessage,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "chat";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::ChatDisabled => "The chat is disabled",
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::MessageBlocked => "The message was blocked by the chat filter",
+            Self::UnknownFlaggedMessage => "There is no flagged message with the given id",
+            Self::UnknownAnnouncement => "There is no pinned announcement with the given id",
+            Self::UnknownMessage => "There is no message with the given id in the global chat",
+        }
+    }
 }
 
 #[cfg(test)]
@@ -118,12 +192,55 @@ mod test {
         assert_eq!(expected, produced);
     }
 
+    #[test]
+    fn message_pinned_serialize() {
+        let produced = serde_json::to_value(&Message::MessagePinned(PinUpdate {
+            id: MessageId::nil(),
+            issued_by: ParticipantId::nil(),
+        }))
+        .unwrap();
+
+        let expected = json!({
+            "message": "message_pinned",
+            "id": "00000000-0000-0000-0000-000000000000",
+            "issued_by": "00000000-0000-0000-0000-000000000000",
+        });
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn message_flagged_serialize() {
+        let produced = serde_json::to_value(&Message::MessageFlagged(FlaggedMessage {
+            id: MessageId::nil(),
+            source: ParticipantId::nil(),
+            timestamp: Timestamp::unix_epoch(),
+            content: "Hello All!".to_string(),
+            scope: Scope::Global,
+            reasons: vec!["insult".into()],
+        }))
+        .unwrap();
+
+        let expected = json!({
+            "message": "message_flagged",
+            "id": "00000000-0000-0000-0000-000000000000",
+            "source": "00000000-0000-0000-0000-000000000000",
+            "timestamp": "1970-01-01T00:00:00Z",
+            "content": "Hello All!",
+            "scope": "global",
+            "reasons": ["insult"],
+        });
+        assert_eq!(expected, produced);
+    }
+
     #[test]
     fn error_serialize() {
-        let produced = serde_json::to_value(&Message::Error(Error::ChatDisabled)).unwrap();
+        let produced = serde_json::to_value(&Message::Error(Error::ChatDisabled.into())).unwrap();
         let expected = json!({
             "message": "error",
+            "module": "chat",
             "error": "chat_disabled",
+            "text": "The chat is disabled",
+            "retryable": false,
         });
         assert_eq!(expected, produced);
     }
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::outgoing::FlaggedMessage;
 use crate::{MessageId, Scope};
 
 use anyhow::{Context, Result};
@@ -40,12 +41,12 @@ pub async fn get_room_chat_history(
     redis_conn: &mut RedisConnection,
     room: SignalingRoomId,
 ) -> Result<Vec<StoredMessage>> {
-    let messages = redis_conn
+    let messages: Vec<Encrypted<StoredMessage>> = redis_conn
         .lrange(RoomChatHistory { room }, 0, -1)
         .await
         .with_context(|| format!("Failed to get chat history: room={room}"))?;
 
-    Ok(messages)
+    Ok(messages.into_iter().map(Encrypted::into_inner).collect())
 }
 
 #[tracing::instrument(level = "debug", skip(redis_conn, message))]
@@ -55,7 +56,7 @@ pub async fn add_message_to_room_chat_history(
     message: &StoredMessage,
 ) -> Result<()> {
     redis_conn
-        .lpush(RoomChatHistory { room }, message)
+        .lpush(RoomChatHistory { room }, Encrypted(message))
         .await
         .with_context(|| format!("Failed to add message to room chat history, room={room}"))?;
 
@@ -75,6 +76,191 @@ pub async fn delete_room_chat_history(
     Ok(())
 }
 
+/// Key to the sorted set of pinned message ids of the global chat, scored by the time they were pinned
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:chat:pinned")]
+struct RoomPinnedMessages {
+    room: SignalingRoomId,
+}
+
+/// Pin a message, returns false if it was already pinned
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn pin_message(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: MessageId,
+    timestamp: Timestamp,
+) -> Result<bool> {
+    redis::cmd("ZADD")
+        .arg(RoomPinnedMessages { room })
+        .arg("NX")
+        .arg(timestamp)
+        .arg(id)
+        .query_async(redis_conn)
+        .await
+        .with_context(|| format!("Failed to pin message, room={room}"))
+}
+
+/// Unpin a message, returns false if the message was not pinned
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn unpin_message(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: MessageId,
+) -> Result<bool> {
+    redis_conn
+        .zrem(RoomPinnedMessages { room }, id)
+        .await
+        .with_context(|| format!("Failed to unpin message, room={room}"))
+}
+
+/// Get the ids of the pinned messages, pinned first comes first
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_pinned_messages(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<MessageId>> {
+    redis_conn
+        .zrange(RoomPinnedMessages { room }, 0, -1)
+        .await
+        .with_context(|| format!("Failed to get pinned messages, room={room}"))
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_pinned_messages(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RoomPinnedMessages { room })
+        .await
+        .with_context(|| format!("Failed to delete pinned messages, room={room}"))
+}
+
+/// Key to the hash of pinned announcements inside a room, indexed by the message id
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:chat:announcements")]
+struct RoomAnnouncements {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn, message))]
+pub async fn add_announcement(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    message: &StoredMessage,
+) -> Result<()> {
+    redis_conn
+        .hset(RoomAnnouncements { room }, message.id, Encrypted(message))
+        .await
+        .with_context(|| format!("Failed to add announcement, room={room}"))
+}
+
+/// Get the pinned announcements of the room, oldest first
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_announcements(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<StoredMessage>> {
+    let announcements: Vec<Encrypted<StoredMessage>> = redis_conn
+        .hvals(RoomAnnouncements { room })
+        .await
+        .with_context(|| format!("Failed to get announcements, room={room}"))?;
+
+    let mut announcements: Vec<StoredMessage> = announcements
+        .into_iter()
+        .map(Encrypted::into_inner)
+        .collect();
+    announcements.sort_by_key(|announcement| announcement.timestamp);
+
+    Ok(announcements)
+}
+
+/// Unpin an announcement, returns false if the announcement was not pinned
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn remove_announcement(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: MessageId,
+) -> Result<bool> {
+    redis_conn
+        .hdel(RoomAnnouncements { room }, id)
+        .await
+        .with_context(|| format!("Failed to remove announcement, room={room}"))
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_announcements(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RoomAnnouncements { room })
+        .await
+        .with_context(|| format!("Failed to delete announcements, room={room}"))
+}
+
+/// Key to the hash of messages flagged by the chat filter inside a room, indexed by the message id
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:chat:flagged")]
+struct RoomFlaggedMessages {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn, message))]
+pub async fn add_flagged_message(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    message: &FlaggedMessage,
+) -> Result<()> {
+    redis_conn
+        .hset(RoomFlaggedMessages { room }, message.id, Encrypted(message))
+        .await
+        .with_context(|| format!("Failed to add flagged message, room={room}"))
+}
+
+/// Get the flagged messages of the room, oldest first
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_flagged_messages(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<FlaggedMessage>> {
+    let messages: Vec<Encrypted<FlaggedMessage>> = redis_conn
+        .hvals(RoomFlaggedMessages { room })
+        .await
+        .with_context(|| format!("Failed to get flagged messages, room={room}"))?;
+
+    let mut messages: Vec<FlaggedMessage> =
+        messages.into_iter().map(Encrypted::into_inner).collect();
+    messages.sort_by_key(|message| message.timestamp);
+
+    Ok(messages)
+}
+
+/// Remove a flagged message, returns false if the message was not flagged
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn remove_flagged_message(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    id: MessageId,
+) -> Result<bool> {
+    redis_conn
+        .hdel(RoomFlaggedMessages { room }, id)
+        .await
+        .with_context(|| format!("Failed to remove flagged message, room={room}"))
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_flagged_messages(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RoomFlaggedMessages { room })
+        .await
+        .with_context(|| format!("Failed to delete flagged messages, room={room}"))
+}
+
 /// If set to true the chat is enabled
 #[derive(ToRedisArgs)]
 #[to_redis_args(fmt = "k3k-signaling:room={room}:chat_enabled")]
@@ -111,14 +297,101 @@ pub async fn delete_chat_enabled(redis_conn: &mut RedisConnection, room: RoomId)
         .context("Failed to DEL chat_enabled")
 }
 
+/// A hash of the number of unread private messages of a participant, indexed by the sender
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:participant={participant}:chat:unread:private")]
+struct RoomParticipantUnreadPrivate {
+    room: SignalingRoomId,
+    participant: ParticipantId,
+}
+
+/// Decrements the unread count of a sender, removing the sender once everything has been read
+const MARK_PRIVATE_READ: &str = r#"
+local unread = redis.call("HINCRBY", KEYS[1], ARGV[1], -tonumber(ARGV[2]))
+if unread <= 0 then
+    redis.call("HDEL", KEYS[1], ARGV[1])
+end
+"#;
+
+/// Count a private message from `source` as unread for the `participant`
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_unread_private(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    source: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .hincr(
+            RoomParticipantUnreadPrivate { room, participant },
+            source,
+            1,
+        )
+        .await
+        .context("Failed to HINCRBY unread private messages")
+}
+
+/// Mark `count` private messages from `source` as read by the `participant`
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn mark_private_read(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    source: ParticipantId,
+    count: usize,
+) -> Result<()> {
+    redis::Script::new(MARK_PRIVATE_READ)
+        .key(RoomParticipantUnreadPrivate { room, participant })
+        .arg(source)
+        .arg(count)
+        .invoke_async(redis_conn)
+        .await
+        .context("Failed to mark private messages as read")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_unread_private(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<HashMap<ParticipantId, u32>> {
+    redis_conn
+        .hgetall(RoomParticipantUnreadPrivate { room, participant })
+        .await
+        .context("Failed to HGETALL unread private messages")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_unread_private(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .del(RoomParticipantUnreadPrivate { room, participant })
+        .await
+        .context("Failed to DEL unread private messages")
+}
+
 /// A hash of last-seen timestamps
 #[derive(ToRedisArgs)]
-#[to_redis_args(fmt = "k3k-signaling:room={room}:participant={participant}:chat:last_seen:global")]
+#[to_redis_args(
+    fmt = "k3k-signaling:room={room}:participant={participant}:chat:v2:last_seen:private"
+)]
 struct RoomParticipantLastSeenTimestampPrivate {
     room: SignalingRoomId,
     participant: ParticipantId,
 }
 
+/// Key of [`RoomParticipantLastSeenTimestampPrivate`] before key version 2, still written for controllers of the
+/// previous release
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:participant={participant}:chat:last_seen:global")]
+struct LegacyRoomParticipantLastSeenTimestampPrivate {
+    room: SignalingRoomId,
+    participant: ParticipantId,
+}
+
 #[tracing::instrument(level = "debug", skip(redis_conn))]
 pub async fn set_last_seen_timestamps_private(
     redis_conn: &mut RedisConnection,
@@ -126,11 +399,19 @@ pub async fn set_last_seen_timestamps_private(
     participant: ParticipantId,
     timestamps: &[(ParticipantId, Timestamp)],
 ) -> Result<()> {
-    redis_conn
+    redis::pipe()
+        .atomic()
         .hset_multiple(
             RoomParticipantLastSeenTimestampPrivate { room, participant },
             timestamps,
         )
+        .ignore()
+        .hset_multiple(
+            LegacyRoomParticipantLastSeenTimestampPrivate { room, participant },
+            timestamps,
+        )
+        .ignore()
+        .query_async(redis_conn)
         .await
         .context("Failed to HSET messages last seen timestamp for private chat")
 }
@@ -141,10 +422,23 @@ pub async fn get_last_seen_timestamps_private(
     room: SignalingRoomId,
     participant: ParticipantId,
 ) -> Result<HashMap<ParticipantId, Timestamp>> {
-    redis_conn
+    let (mut timestamps, legacy_timestamps): (
+        HashMap<ParticipantId, Timestamp>,
+        HashMap<ParticipantId, Timestamp>,
+    ) = redis::pipe()
         .hgetall(RoomParticipantLastSeenTimestampPrivate { room, participant })
+        .hgetall(LegacyRoomParticipantLastSeenTimestampPrivate { room, participant })
+        .query_async(redis_conn)
         .await
-        .context("Failed to HGETALL messages last seen timestamps for private chats")
+        .context("Failed to HGETALL messages last seen timestamps for private chats")?;
+
+    // Controllers of the previous release only update the legacy key
+    for (peer, legacy_timestamp) in legacy_timestamps {
+        let timestamp = timestamps.entry(peer).or_insert(legacy_timestamp);
+        *timestamp = (*timestamp).max(legacy_timestamp);
+    }
+
+    Ok(timestamps)
 }
 
 #[tracing::instrument(level = "debug", skip(redis_conn))]
@@ -153,8 +447,13 @@ pub async fn delete_last_seen_timestamps_private(
     room: SignalingRoomId,
     participant: ParticipantId,
 ) -> Result<()> {
-    redis_conn
+    redis::pipe()
+        .atomic()
         .del(RoomParticipantLastSeenTimestampPrivate { room, participant })
+        .ignore()
+        .del(LegacyRoomParticipantLastSeenTimestampPrivate { room, participant })
+        .ignore()
+        .query_async(redis_conn)
         .await
         .context("Failed to DEL messages last seen timestamps for private chats")
 }
@@ -207,14 +506,25 @@ pub async fn delete_last_seen_timestamps_group(
         .context("Failed to DEL last seen timestamp for group chats")
 }
 
-/// A hash of last-seen timestamps
+/// The last-seen timestamp of the global chat
 #[derive(ToRedisArgs)]
-#[to_redis_args(fmt = "k3k-signaling:room={room}:participant={participant}:chat:last_seen:private")]
+#[to_redis_args(
+    fmt = "k3k-signaling:room={room}:participant={participant}:chat:v2:last_seen:global"
+)]
 struct RoomParticipantLastSeenTimestampGlobal {
     room: SignalingRoomId,
     participant: ParticipantId,
 }
 
+/// Key of [`RoomParticipantLastSeenTimestampGlobal`] before key version 2, still written for controllers of the
+/// previous release
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:participant={participant}:chat:last_seen:private")]
+struct LegacyRoomParticipantLastSeenTimestampGlobal {
+    room: SignalingRoomId,
+    participant: ParticipantId,
+}
+
 #[tracing::instrument(level = "debug", skip(redis_conn))]
 pub async fn set_last_seen_timestamp_global(
     redis_conn: &mut RedisConnection,
@@ -222,13 +532,21 @@ pub async fn set_last_seen_timestamp_global(
     participant: ParticipantId,
     timestamp: Timestamp,
 ) -> Result<()> {
-    redis_conn
+    redis::pipe()
+        .atomic()
         .set(
             RoomParticipantLastSeenTimestampGlobal { room, participant },
             timestamp,
         )
+        .ignore()
+        .set(
+            LegacyRoomParticipantLastSeenTimestampGlobal { room, participant },
+            timestamp,
+        )
+        .ignore()
+        .query_async(redis_conn)
         .await
-        .context("Failed to HSET messages last seen timestamp for global chat")
+        .context("Failed to SET messages last seen timestamp for global chat")
 }
 
 #[tracing::instrument(level = "debug", skip(redis_conn))]
@@ -237,11 +555,15 @@ pub async fn get_last_seen_timestamp_global(
     room: SignalingRoomId,
     participant: ParticipantId,
 ) -> Result<Option<Timestamp>> {
-    let key = RoomParticipantLastSeenTimestampGlobal { room, participant };
-    redis_conn
-        .get(&key)
+    let (timestamp, legacy_timestamp): (Option<Timestamp>, Option<Timestamp>) = redis::pipe()
+        .get(RoomParticipantLastSeenTimestampGlobal { room, participant })
+        .get(LegacyRoomParticipantLastSeenTimestampGlobal { room, participant })
+        .query_async(redis_conn)
         .await
-        .context("Failed to GET messages last seen timestamp for global chat")
+        .context("Failed to GET messages last seen timestamp for global chat")?;
+
+    // Controllers of the previous release only update the legacy key
+    Ok(timestamp.max(legacy_timestamp))
 }
 
 #[tracing::instrument(level = "debug", skip(redis_conn))]
@@ -250,12 +572,51 @@ pub async fn delete_last_seen_timestamp_global(
     room: SignalingRoomId,
     participant: ParticipantId,
 ) -> Result<()> {
-    redis_conn
+    redis::pipe()
+        .atomic()
         .del(RoomParticipantLastSeenTimestampGlobal { room, participant })
+        .ignore()
+        .del(LegacyRoomParticipantLastSeenTimestampGlobal { room, participant })
+        .ignore()
+        .query_async(redis_conn)
         .await
         .context("Failed to DEL messages last seen timestamp for global chat")
 }
 
+/// Migrate the last-seen timestamps to key version 2
+///
+/// Before version 2 the timestamps of the private chats were stored at `chat:last_seen:global` and the timestamp of
+/// the global chat at `chat:last_seen:private`. The keys are copied to the correctly named keys, the legacy keys are
+/// kept for controllers of the previous release.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn migrate_last_seen_timestamps(redis_conn: &mut RedisConnection) -> Result<()> {
+    let renames = [
+        (":chat:last_seen:global", ":chat:v2:last_seen:private"),
+        (":chat:last_seen:private", ":chat:v2:last_seen:global"),
+    ];
+
+    for (legacy_suffix, suffix) in renames {
+        let copied = key_versions::copy_keys(
+            redis_conn,
+            &format!("k3k-signaling:room=*:participant=*{legacy_suffix}"),
+            |key| {
+                key.strip_suffix(legacy_suffix)
+                    .map(|prefix| format!("{prefix}{suffix}"))
+            },
+        )
+        .await?;
+
+        log::debug!(
+            "Copied {} keys from *{} to *{}",
+            copied,
+            legacy_suffix,
+            suffix
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -291,6 +652,46 @@ mod test {
         DateTime::from(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn unread_private() {
+        let mut redis_conn = setup().await;
+
+        add_unread_private(&mut redis_conn, ROOM, SELF, BOB)
+            .await
+            .unwrap();
+        add_unread_private(&mut redis_conn, ROOM, SELF, BOB)
+            .await
+            .unwrap();
+        add_unread_private(&mut redis_conn, ROOM, SELF, ALICE)
+            .await
+            .unwrap();
+
+        mark_private_read(&mut redis_conn, ROOM, SELF, ALICE, 3)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_unread_private(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap(),
+            HashMap::from([(BOB, 2)])
+        );
+        assert!(get_unread_private(&mut redis_conn, ROOM, BOB)
+            .await
+            .unwrap()
+            .is_empty());
+
+        delete_unread_private(&mut redis_conn, ROOM, SELF)
+            .await
+            .unwrap();
+
+        assert!(get_unread_private(&mut redis_conn, ROOM, SELF)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     #[serial]
     async fn last_seen_global() {
@@ -448,6 +849,165 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn migrate_last_seen_timestamps_to_v2() {
+        let mut redis_conn = setup().await;
+
+        // Keys written by a controller of the previous release
+        redis_conn
+            .set::<_, _, ()>(
+                LegacyRoomParticipantLastSeenTimestampGlobal {
+                    room: ROOM,
+                    participant: SELF,
+                },
+                Timestamp::from(unix_epoch(1000)),
+            )
+            .await
+            .unwrap();
+        redis_conn
+            .hset::<_, _, _, ()>(
+                LegacyRoomParticipantLastSeenTimestampPrivate {
+                    room: ROOM,
+                    participant: SELF,
+                },
+                BOB,
+                Timestamp::from(unix_epoch(2000)),
+            )
+            .await
+            .unwrap();
+
+        migrate_last_seen_timestamps(&mut redis_conn).await.unwrap();
+
+        // The legacy keys are kept for controllers of the previous release
+        let legacy_global: Option<Timestamp> = redis_conn
+            .get(LegacyRoomParticipantLastSeenTimestampGlobal {
+                room: ROOM,
+                participant: SELF,
+            })
+            .await
+            .unwrap();
+        assert_eq!(legacy_global, Some(unix_epoch(1000).into()));
+
+        redis_conn
+            .del::<_, ()>(LegacyRoomParticipantLastSeenTimestampGlobal {
+                room: ROOM,
+                participant: SELF,
+            })
+            .await
+            .unwrap();
+        redis_conn
+            .del::<_, ()>(LegacyRoomParticipantLastSeenTimestampPrivate {
+                room: ROOM,
+                participant: SELF,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_last_seen_timestamp_global(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap(),
+            Some(unix_epoch(1000).into())
+        );
+        assert_eq!(
+            get_last_seen_timestamps_private(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap(),
+            HashMap::from([(BOB, unix_epoch(2000).into())])
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn last_seen_bridges_legacy_keys() {
+        let mut redis_conn = setup().await;
+
+        set_last_seen_timestamp_global(&mut redis_conn, ROOM, SELF, unix_epoch(1000).into())
+            .await
+            .unwrap();
+        set_last_seen_timestamps_private(
+            &mut redis_conn,
+            ROOM,
+            SELF,
+            &[
+                (BOB, unix_epoch(1000).into()),
+                (ALICE, unix_epoch(3000).into()),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // Controllers of the previous release read the legacy keys
+        let legacy_private: HashMap<ParticipantId, Timestamp> = redis_conn
+            .hgetall(LegacyRoomParticipantLastSeenTimestampPrivate {
+                room: ROOM,
+                participant: SELF,
+            })
+            .await
+            .unwrap();
+        assert_eq!(legacy_private.len(), 2);
+
+        // and only update the legacy keys
+        redis_conn
+            .set::<_, _, ()>(
+                LegacyRoomParticipantLastSeenTimestampGlobal {
+                    room: ROOM,
+                    participant: SELF,
+                },
+                Timestamp::from(unix_epoch(2000)),
+            )
+            .await
+            .unwrap();
+        redis_conn
+            .hset_multiple::<_, _, _, ()>(
+                LegacyRoomParticipantLastSeenTimestampPrivate {
+                    room: ROOM,
+                    participant: SELF,
+                },
+                &[
+                    (BOB, Timestamp::from(unix_epoch(2000))),
+                    (ALICE, Timestamp::from(unix_epoch(2000))),
+                ],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_last_seen_timestamp_global(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap(),
+            Some(unix_epoch(2000).into())
+        );
+        assert_eq!(
+            get_last_seen_timestamps_private(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap(),
+            HashMap::from([
+                (BOB, unix_epoch(2000).into()),
+                (ALICE, unix_epoch(3000).into())
+            ])
+        );
+
+        delete_last_seen_timestamp_global(&mut redis_conn, ROOM, SELF)
+            .await
+            .unwrap();
+        delete_last_seen_timestamps_private(&mut redis_conn, ROOM, SELF)
+            .await
+            .unwrap();
+
+        assert!(get_last_seen_timestamp_global(&mut redis_conn, ROOM, SELF)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(
+            get_last_seen_timestamps_private(&mut redis_conn, ROOM, SELF)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn redis_args() {
         let room_id = RoomId::from(uuid!("ecead1b3-eed0-4cb9-912e-4bb31a3914bd"));
@@ -547,10 +1107,12 @@ pub async fn get_group_chat_history(
     room: SignalingRoomId,
     group: GroupId,
 ) -> Result<Vec<StoredMessage>> {
-    redis_conn
+    let messages: Vec<Encrypted<StoredMessage>> = redis_conn
         .lrange(RoomGroupChatHistory { room, group }, 0, -1)
         .await
-        .with_context(|| format!("Failed to get chat history, {room}, group={group}"))
+        .with_context(|| format!("Failed to get chat history, {room}, group={group}"))?;
+
+    Ok(messages.into_iter().map(Encrypted::into_inner).collect())
 }
 
 #[tracing::instrument(level = "debug", skip(redis_conn, message))]
@@ -561,7 +1123,7 @@ pub async fn add_message_to_group_chat_history(
     message: &StoredMessage,
 ) -> Result<()> {
     redis_conn
-        .lpush(RoomGroupChatHistory { room, group }, message)
+        .lpush(RoomGroupChatHistory { room, group }, Encrypted(message))
         .await
         .with_context(|| {
             format!("Failed to add message to room chat history, {room}, group={group}",)
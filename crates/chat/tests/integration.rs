@@ -52,7 +52,7 @@ async fn last_seen_timestamps() {
                 user1.clone(),
                 Role::User,
                 USER_1.name,
-                (),
+                Default::default(),
             )
             .await
             .unwrap();
@@ -88,6 +88,9 @@ async fn last_seen_timestamps() {
                         "last_seen_timestamps_private": {},
                         "last_seen_timestamps_group": {},
                         "room_history": [],
+                        "announcements": [],
+                        "pinned_messages": [],
+                        "unread_private": {},
                     })
                 );
             }
@@ -99,7 +102,13 @@ async fn last_seen_timestamps() {
         // join another user in order to keep the room alive when the first
         // user leaves and joins the room
         module_tester
-            .join_user(USER_2.participant_id, user2, Role::User, USER_2.name, ())
+            .join_user(
+                USER_2.participant_id,
+                user2,
+                Role::User,
+                USER_2.name,
+                Default::default(),
+            )
             .await
             .unwrap();
         // discard the received ws join success message, no need to test it here
@@ -158,7 +167,13 @@ async fn last_seen_timestamps() {
     // leave and join again with the first user
     module_tester.leave(&USER_1.participant_id).await.unwrap();
     module_tester
-        .join_user(USER_1.participant_id, user1, Role::User, USER_1.name, ())
+        .join_user(
+            USER_1.participant_id,
+            user1,
+            Role::User,
+            USER_1.name,
+            Default::default(),
+        )
         .await
         .unwrap();
 
@@ -183,6 +198,9 @@ async fn last_seen_timestamps() {
                 json!({
                     "enabled": true,
                     "room_history": [],
+                    "announcements": [],
+                    "pinned_messages": [],
+                    "unread_private": {},
                     "groups_history": [
                         {
                             "history": [],
@@ -244,7 +262,13 @@ async fn common_groups_on_join() {
     );
 
     module_tester
-        .join_user(USER_1.participant_id, user1, Role::User, USER_1.name, ())
+        .join_user(
+            USER_1.participant_id,
+            user1,
+            Role::User,
+            USER_1.name,
+            Default::default(),
+        )
         .await
         .unwrap();
 
@@ -281,6 +305,9 @@ async fn common_groups_on_join() {
                         }
                     ],
                     "room_history": [],
+                    "announcements": [],
+                    "pinned_messages": [],
+                    "unread_private": {},
                     "last_seen_timestamp_global": null,
                     "last_seen_timestamps_group": {},
                     "last_seen_timestamps_private": {},
@@ -291,7 +318,13 @@ async fn common_groups_on_join() {
     }
 
     module_tester
-        .join_user(USER_2.participant_id, user2, Role::User, USER_2.name, ())
+        .join_user(
+            USER_2.participant_id,
+            user2,
+            Role::User,
+            USER_2.name,
+            Default::default(),
+        )
         .await
         .unwrap();
 
@@ -323,6 +356,9 @@ async fn common_groups_on_join() {
                 json!({
                     "enabled": true,
                     "room_history": [],
+                    "announcements": [],
+                    "pinned_messages": [],
+                    "unread_private": {},
                     "groups_history": [
                         {
                             "history": [],
@@ -344,3 +380,97 @@ async fn common_groups_on_join() {
 
     module_tester.shutdown().await.unwrap();
 }
+
+#[actix_rt::test]
+#[serial]
+async fn global_messages_are_shared() {
+    let test_ctx = TestContext::new().await;
+
+    let user1 = test_ctx
+        .db_ctx
+        .create_test_user(USER_1.n, Vec::new())
+        .unwrap();
+    let user2 = test_ctx
+        .db_ctx
+        .create_test_user(USER_2.n, Vec::new())
+        .unwrap();
+
+    let waiting_room = false;
+    let room = test_ctx
+        .db_ctx
+        .create_test_room(ROOM_ID, user1.id, waiting_room)
+        .unwrap();
+
+    let mut module_tester = ModuleTester::<Chat>::new(
+        test_ctx.db_ctx.db.clone(),
+        test_ctx.authz,
+        test_ctx.redis_conn,
+        room,
+    );
+
+    for (user, test_user) in [(user1, USER_1), (user2, USER_2)] {
+        module_tester
+            .join_user(
+                test_user.participant_id,
+                user,
+                Role::User,
+                test_user.name,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        // discard the join success message
+        module_tester
+            .receive_ws_message(&test_user.participant_id)
+            .await
+            .unwrap();
+    }
+
+    // discard the joined message of the second user
+    module_tester
+        .receive_ws_message(&USER_1.participant_id)
+        .await
+        .unwrap();
+
+    module_tester
+        .send_ws_message(
+            &USER_1.participant_id,
+            incoming::Message::SendMessage(incoming::SendMessage {
+                content: "Hello all!".into(),
+                scope: Scope::Global,
+            }),
+        )
+        .unwrap();
+
+    // Both participants receive the same pre-serialized message
+    let received1 = module_tester
+        .receive_ws_message(&USER_1.participant_id)
+        .await
+        .unwrap();
+    let received2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(received1, received2);
+
+    let payload = match received1 {
+        WsMessageOutgoing::Shared(payload) => payload,
+        other => panic!("expected a shared payload, got {other:?}"),
+    };
+
+    let json: serde_json::Value =
+        serde_json::from_str(payload.for_version(ProtocolVersion::LATEST)).unwrap();
+
+    assert_eq!(json["namespace"], "chat");
+    assert_eq!(json["payload"]["message"], "message_sent");
+    assert_eq!(json["payload"]["content"], "Hello all!");
+    assert_eq!(json["payload"]["scope"], "global");
+    assert_eq!(
+        json["payload"]["source"],
+        json!(USER_1.participant_id.to_string())
+    );
+
+    module_tester.shutdown().await.unwrap();
+}
@@ -3,14 +3,28 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use anyhow::Result;
+use controller::prelude::SignalingSchemas;
 use controller::Controller;
 
 pub async fn register(controller: &mut Controller) -> Result<()> {
-    chat::register(controller);
+    action_items::register(controller);
+    chat::register(controller)?;
     janus_media::register(controller).await?;
+    layout::register(controller);
     polls::register(controller);
     protocol::register(controller);
     timer::register(controller);
     whiteboard::register(controller);
     Ok(())
 }
+
+pub fn register_schemas(schemas: &mut SignalingSchemas) {
+    schemas.add_module::<action_items::ActionItems>();
+    schemas.add_module::<chat::Chat>();
+    schemas.add_module::<janus_media::Media>();
+    schemas.add_module::<layout::Layout>();
+    schemas.add_module::<polls::Polls>();
+    schemas.add_module::<protocol::Protocol>();
+    schemas.add_module::<timer::Timer>();
+    schemas.add_module::<whiteboard::Whiteboard>();
+}
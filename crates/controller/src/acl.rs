@@ -26,6 +26,13 @@ pub(crate) async fn check_or_create_kustos_default_permissions(authz: &Authz) ->
     check_or_create_kustos_role_policy(authz, "user", "/users/me/tariff", [AccessMethod::Get])
         .await?;
     check_or_create_kustos_role_policy(authz, "user", "/users/find", [AccessMethod::Get]).await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/users/me/data-export",
+        [AccessMethod::Post],
+    )
+    .await?;
     check_or_create_kustos_role_policy(
         authz,
         "user",
@@ -40,6 +47,45 @@ pub(crate) async fn check_or_create_kustos_default_permissions(authz: &Authz) ->
         [AccessMethod::Post, AccessMethod::Get],
     )
     .await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/events/check-conflicts",
+        [AccessMethod::Post],
+    )
+    .await?;
+    check_or_create_kustos_role_policy(authz, "user", "/trash", [AccessMethod::Get]).await?;
+    check_or_create_kustos_role_policy(authz, "user", "/trash/*", [AccessMethod::Post]).await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/users/me/contacts",
+        [AccessMethod::Get, AccessMethod::Put],
+    )
+    .await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/users/me/calendar_links",
+        [AccessMethod::Get],
+    )
+    .await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/users/me/calendar_links/*",
+        [AccessMethod::Get, AccessMethod::Put, AccessMethod::Delete],
+    )
+    .await?;
+    check_or_create_kustos_role_policy(
+        authz,
+        "user",
+        "/users/me/polls",
+        [AccessMethod::Get, AccessMethod::Post],
+    )
+    .await?;
+    check_or_create_kustos_role_policy(authz, "user", "/users/me/polls/*", [AccessMethod::Delete])
+        .await?;
 
     Ok(())
 }
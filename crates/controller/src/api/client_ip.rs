@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Address of the client sending a request
+//!
+//! The `X-Forwarded-For` header can be set by any client, so it is only read when the request was forwarded by one of
+//! the configured trusted proxies (`http.trusted_proxies`). Connections through the unix socket listener are always
+//! considered to be forwarded by a local proxy. The header is read from right to left, the first address which is not
+//! a trusted proxy is the address of the client.
+use actix_http::header::HeaderMap;
+use actix_web::HttpRequest;
+use cidr::IpInet;
+use std::net::{IpAddr, SocketAddr};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Returns the address of the client, `None` if it cannot be determined
+pub fn client_ip(request: &HttpRequest, trusted_proxies: &[IpInet]) -> Option<IpAddr> {
+    resolve(request.peer_addr(), request.headers(), trusted_proxies)
+}
+
+fn resolve(
+    peer_addr: Option<SocketAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpInet],
+) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    let mut client = peer_addr.map(|addr| canonical_ip(addr.ip()));
+
+    if client.as_ref().map(is_trusted) == Some(false) {
+        return client;
+    }
+
+    // Later proxies append to the header, either by adding a value or by adding another header
+    let mut forwarded: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    while let Some(hop) = forwarded.pop() {
+        // Stop at the first invalid hop, everything before it may have been set by the client
+        let ip = match hop.parse() {
+            Ok(ip) => canonical_ip(ip),
+            Err(_) => break,
+        };
+
+        client = Some(ip);
+
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+
+    client
+}
+
+/// IPv4 clients of the dual-stack server have IPv4-mapped IPv6 addresses, configured networks should contain IPv4
+/// networks
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => ip
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(ip)),
+        ip => ip,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_http::header::{HeaderName, HeaderValue};
+    use pretty_assertions::assert_eq;
+
+    fn headers(forwarded_for: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for value in forwarded_for {
+            headers.append(
+                HeaderName::from_static(X_FORWARDED_FOR),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+
+        headers
+    }
+
+    fn peer(ip: &str) -> Option<SocketAddr> {
+        Some(SocketAddr::new(ip.parse().unwrap(), 4711))
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_cannot_spoof() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+
+        assert_eq!(
+            resolve(peer("192.0.2.1"), &headers(&["198.51.100.7"]), &trusted),
+            ip("192.0.2.1")
+        );
+        assert_eq!(
+            resolve(peer("::ffff:192.0.2.1"), &headers(&[]), &[]),
+            ip("192.0.2.1")
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+
+        // The client prepended a spoofed address, the first untrusted hop from the right is the client
+        assert_eq!(
+            resolve(
+                peer("10.0.0.1"),
+                &headers(&["203.0.113.9, 198.51.100.7", "10.0.0.2"]),
+                &trusted
+            ),
+            ip("198.51.100.7")
+        );
+
+        // Requests from the proxy itself
+        assert_eq!(
+            resolve(peer("10.0.0.1"), &headers(&[]), &trusted),
+            ip("10.0.0.1")
+        );
+
+        // Invalid hops end the search
+        assert_eq!(
+            resolve(
+                peer("10.0.0.1"),
+                &headers(&["198.51.100.7, garbage"]),
+                &trusted
+            ),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn unix_socket_is_a_proxy() {
+        assert_eq!(
+            resolve(None, &headers(&["198.51.100.7"]), &[]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(resolve(None, &headers(&[]), &[]), None);
+    }
+}
@@ -5,7 +5,7 @@
 use crate::api::internal::NoContent;
 use crate::api::v1::events::associated_resource_ids;
 use crate::api::v1::response::ApiError;
-use crate::storage::assets::asset_key;
+use crate::storage::assets::delete_asset_objects;
 use crate::storage::ObjectStorage;
 use actix_web::delete;
 use actix_web::web::{Data, Path, ReqData};
@@ -117,7 +117,7 @@ pub async fn delete(
     .await??;
 
     for asset_id in assets {
-        storage.delete(asset_key(&asset_id)).await?;
+        delete_asset_objects(&storage, &asset_id).await?;
     }
 
     authz.remove_explicit_resources(resources).await?;
@@ -133,5 +133,20 @@ pub(crate) fn associated_room_resource_ids(
         ResourceId::from(format!("/rooms/{room_id}/invites")),
         ResourceId::from(format!("/rooms/{room_id}/invites/*")),
         ResourceId::from(format!("/rooms/{room_id}/start")),
+        ResourceId::from(format!("/rooms/{room_id}/tariff")),
+        ResourceId::from(format!("/rooms/{room_id}/summary")),
+        ResourceId::from(format!("/rooms/{room_id}/legal_votes/scheduled")),
+        ResourceId::from(format!("/rooms/{room_id}/legal_votes/scheduled/*")),
+        ResourceId::from(format!("/rooms/{room_id}/assets")),
+        ResourceId::from(format!("/rooms/{room_id}/assets/*")),
+        ResourceId::from(format!("/rooms/{room_id}/assets/uploads")),
+        ResourceId::from(format!("/rooms/{room_id}/assets/uploads/*")),
+        ResourceId::from(format!("/rooms/{room_id}/owners")),
+        ResourceId::from(format!("/rooms/{room_id}/owners/*")),
+        ResourceId::from(format!("/rooms/{room_id}/transfer_ownership")),
+        ResourceId::from(format!("/rooms/{room_id}/media_settings")),
+        ResourceId::from(format!("/rooms/{room_id}/branding")),
+        ResourceId::from(format!("/rooms/{room_id}/directory_entry")),
+        ResourceId::from(format!("/rooms/{room_id}/media-stats")),
     ]
 }
@@ -12,6 +12,7 @@ use db_storage::users::User;
 use serde::{Deserialize, Serialize};
 use types::core::UserId;
 
+pub mod client_ip;
 pub mod internal;
 mod util;
 #[macro_use]
@@ -24,6 +25,7 @@ pub enum Participant<U> {
     Guest,
     Sip,
     Recorder,
+    Bot,
 }
 
 impl<U> Participant<U> {
@@ -33,6 +35,7 @@ impl<U> Participant<U> {
             Participant::Guest => "guest",
             Participant::Sip => "sip",
             Participant::Recorder => "recorder",
+            Participant::Bot => "bot",
         }
     }
 }
@@ -57,6 +60,7 @@ impl Participant<User> {
             Participant::Guest => None,
             Participant::Sip => None,
             Participant::Recorder => None,
+            Participant::Bot => None,
         }
     }
 }
@@ -0,0 +1,289 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Capture of the events received by the signaling modules
+//!
+//! When `event_capture` is configured, the runners of a captured room append every event they dispatch to their modules
+//! to a redis stream of the room, before the modules handle it. The stream is shared by all controllers, so it contains
+//! the events of all participants in the order the runners dispatched them. It is not part of the room's keys and
+//! outlives the room until its retention time elapsed. The records contain meeting content like chat messages and are
+//! therefore [`Encrypted`] with the `redis.encryption_key`.
+//!
+//! Captured events are exported with the `event-capture` command and fed back into a module offline with the
+//! [`EventReplay`](super::prelude::EventReplay). Ext events are only captured with their payload if the module
+//! implements [`SignalingModule::capture_ext_event`](super::prelude::SignalingModule::capture_ext_event).
+use crate::api;
+use crate::api::signaling::ws_modules::control::ControlData;
+use crate::api::signaling::{Role, SignalingRoomId};
+use crate::redis_encryption::Encrypted;
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use controller_shared::settings;
+use db_storage::users::User;
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use types::core::{BreakoutRoomId, ParticipantId, ParticipationKind, RoomId, Timestamp, UserId};
+
+/// Stream of the events captured in a room and its breakout rooms
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:capture:room={room}")]
+struct CapturedEvents {
+    room: RoomId,
+}
+
+/// Field of the stream entries containing the serialized [`CaptureRecord`]
+const RECORD_FIELD: &str = "record";
+
+/// An event dispatched to the modules of a participant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    pub timestamp: Timestamp,
+    pub participant: ParticipantId,
+    pub participation_kind: ParticipationKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
+    /// Role of the participant when the event was dispatched
+    pub role: Role,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakout_room: Option<BreakoutRoomId>,
+    #[serde(flatten)]
+    pub event: CapturedEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "event")]
+pub enum CapturedEvent {
+    WsMessage {
+        namespace: String,
+        payload: Value,
+    },
+    RabbitMq {
+        namespace: String,
+        payload: Value,
+    },
+    /// Ext events of modules which do not implement the capture have no payload
+    Ext {
+        namespace: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload: Option<Value>,
+    },
+    Joined {
+        control_data: ControlData,
+        participants: Vec<ParticipantId>,
+    },
+    Leaving,
+    RaiseHand,
+    LowerHand,
+    ParticipantJoined {
+        peer: ParticipantId,
+    },
+    ParticipantLeft {
+        peer: ParticipantId,
+    },
+    ParticipantUpdated {
+        peer: ParticipantId,
+    },
+    /// The modules of the participant have been destroyed
+    Destroyed {
+        destroy_room: bool,
+    },
+}
+
+impl CapturedEvent {
+    /// Namespace of the module the event is dispatched to, `None` for events dispatched to all modules
+    pub fn namespace(&self) -> Option<&str> {
+        match self {
+            Self::WsMessage { namespace, .. }
+            | Self::RabbitMq { namespace, .. }
+            | Self::Ext { namespace, .. } => Some(namespace),
+            _ => None,
+        }
+    }
+}
+
+/// Records the events dispatched to the modules of a single participant
+pub(crate) struct EventRecorder {
+    room_id: SignalingRoomId,
+    participant: ParticipantId,
+    participation_kind: ParticipationKind,
+    user_id: Option<UserId>,
+    role: Role,
+    max_events: usize,
+    retention: Duration,
+}
+
+impl EventRecorder {
+    /// Returns a recorder if the events of the room are captured
+    pub(crate) fn new(
+        settings: &settings::Settings,
+        room_id: SignalingRoomId,
+        participant: ParticipantId,
+        kind: &api::Participant<User>,
+        role: Role,
+    ) -> Option<Self> {
+        let capture = settings.event_capture.as_ref()?;
+
+        if !capture.captures_room(room_id.room_id().into_inner()) {
+            return None;
+        }
+
+        let (participation_kind, user_id) = match kind {
+            api::Participant::User(user) => (ParticipationKind::User, Some(user.id)),
+            api::Participant::Guest => (ParticipationKind::Guest, None),
+            api::Participant::Sip => (ParticipationKind::Sip, None),
+            api::Participant::Recorder => (ParticipationKind::Recorder, None),
+            api::Participant::Bot => (ParticipationKind::Bot, None),
+        };
+
+        Some(Self {
+            room_id,
+            participant,
+            participation_kind,
+            user_id,
+            role,
+            max_events: capture.max_events,
+            retention: capture.retention,
+        })
+    }
+
+    /// Append the event to the capture of the room
+    ///
+    /// Failures are only logged, the capture must never interrupt the session.
+    pub(crate) async fn record(
+        &mut self,
+        redis_conn: &mut RedisConnection,
+        role: Role,
+        timestamp: Timestamp,
+        event: CapturedEvent,
+    ) {
+        self.role = role;
+
+        let record = CaptureRecord {
+            timestamp,
+            participant: self.participant,
+            participation_kind: self.participation_kind,
+            user_id: self.user_id,
+            role,
+            breakout_room: self.room_id.breakout_room_id(),
+            event,
+        };
+
+        if let Err(e) = self.append(redis_conn, &record).await {
+            log::warn!("Failed to capture event, {:?}", e);
+        }
+    }
+
+    /// Record that the modules have been destroyed, with the role of the last recorded event
+    pub(crate) async fn record_destroyed(
+        &mut self,
+        redis_conn: &mut RedisConnection,
+        destroy_room: bool,
+    ) {
+        self.record(
+            redis_conn,
+            self.role,
+            Timestamp::now(),
+            CapturedEvent::Destroyed { destroy_room },
+        )
+        .await
+    }
+
+    async fn append(&self, redis_conn: &mut RedisConnection, record: &CaptureRecord) -> Result<()> {
+        let key = CapturedEvents {
+            room: self.room_id.room_id(),
+        };
+
+        let record = serde_json::to_string(record).context("Failed to serialize record")?;
+
+        redis::pipe()
+            .cmd("XADD")
+            .arg(&key)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(self.max_events)
+            .arg("*")
+            .arg(RECORD_FIELD)
+            .arg(Encrypted(record))
+            .ignore()
+            .expire(&key, self.retention.as_secs() as usize)
+            .ignore()
+            .query_async(redis_conn)
+            .await
+            .context("Failed to XADD the captured event")
+    }
+}
+
+/// Returns the captured events of the room in the order they have been dispatched
+pub async fn read(redis_conn: &mut RedisConnection, room: RoomId) -> Result<Vec<CaptureRecord>> {
+    let entries: Vec<(String, Vec<Encrypted<String>>)> = redis::cmd("XRANGE")
+        .arg(CapturedEvents { room })
+        .arg("-")
+        .arg("+")
+        .query_async(redis_conn)
+        .await
+        .context("Failed to XRANGE the captured events")?;
+
+    entries
+        .into_iter()
+        .filter_map(|(id, fields)| {
+            let position = fields.iter().position(|field| field.0 == RECORD_FIELD)?;
+
+            Some((id, fields.into_iter().nth(position + 1)?.into_inner()))
+        })
+        .map(|(id, record)| {
+            serde_json::from_str(&record).with_context(|| format!("Invalid captured event {id}"))
+        })
+        .collect()
+}
+
+/// Delete the captured events of the room
+pub async fn clear(redis_conn: &mut RedisConnection, room: RoomId) -> Result<()> {
+    redis_conn
+        .del(CapturedEvents { room })
+        .await
+        .context("Failed to DEL the captured events")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn records() {
+        let record = json!({
+            "timestamp": "2023-01-01T12:00:00Z",
+            "participant": "00000000-0000-0000-0000-000000000001",
+            "participation_kind": "guest",
+            "role": "guest",
+            "event": "ws_message",
+            "namespace": "chat",
+            "payload": {"action": "send_message", "content": "hi", "scope": "global"}
+        });
+
+        let parsed: CaptureRecord = serde_json::from_value(record.clone()).unwrap();
+
+        assert_eq!(parsed.event.namespace(), Some("chat"));
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), record);
+
+        let record = json!({
+            "timestamp": "2023-01-01T12:00:00Z",
+            "participant": "00000000-0000-0000-0000-000000000001",
+            "participation_kind": "user",
+            "user_id": "00000000-0000-0000-0000-000000000002",
+            "role": "moderator",
+            "event": "participant_left",
+            "peer": "00000000-0000-0000-0000-000000000003"
+        });
+
+        let parsed: CaptureRecord = serde_json::from_value(record.clone()).unwrap();
+
+        assert_eq!(parsed.event.namespace(), None);
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), record);
+    }
+}
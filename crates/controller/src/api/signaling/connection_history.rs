@@ -0,0 +1,143 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! History of connection related events of participants
+//!
+//! The latest events of every participant, like websocket connects, broken media connections or failing signaling
+//! modules, are kept in redis for a day. Moderators can fetch the history of a participant to diagnose reports like
+//! broken video after the fact.
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use types::core::{ParticipantId, Timestamp};
+
+/// Number of events kept per participant
+const CONNECTION_HISTORY_LEN: isize = 50;
+
+/// Time in seconds after which the history of a participant expires
+const CONNECTION_HISTORY_EXPIRY: usize = 24 * 60 * 60;
+
+/// List of the latest connection events of a participant, newest first
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:participant={participant}:connection_history")]
+struct ConnectionHistoryKey {
+    participant: ParticipantId,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToRedisArgs, FromRedisValue,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct ConnectionEvent {
+    pub timestamp: Timestamp,
+    #[serde(flatten)]
+    pub kind: ConnectionEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ConnectionEventKind {
+    /// The websocket connection has been established
+    Connected { resuming: bool },
+    /// The websocket connection has been closed
+    Disconnected,
+    /// A media connection went down
+    #[serde(rename = "webrtc_down")]
+    WebRtcDown {
+        media_session_type: String,
+        source: ParticipantId,
+        /// Reason reported by the media server, e.g. `ICE failed`
+        reason: Option<String>,
+    },
+    /// The media server detected lost packets on a media connection
+    SlowLink {
+        media_session_type: String,
+        source: ParticipantId,
+        direction: String,
+        lost: u64,
+    },
+    /// A signaling module failed to handle an event
+    ModuleError { namespace: String, error: String },
+}
+
+/// Add the event to the history of the participant, dropping the oldest events
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record(
+    redis_conn: &mut RedisConnection,
+    participant: ParticipantId,
+    kind: ConnectionEventKind,
+) -> Result<()> {
+    let key = ConnectionHistoryKey { participant };
+
+    let event = ConnectionEvent {
+        timestamp: Timestamp::now(),
+        kind,
+    };
+
+    // The history is most interesting during redis outages, so the event is buffered until redis is available again
+    let pipe = redis::pipe()
+        .atomic()
+        .lpush(&key, event)
+        .ignore()
+        .ltrim(&key, 0, CONNECTION_HISTORY_LEN - 1)
+        .ignore()
+        .expire(&key, CONNECTION_HISTORY_EXPIRY)
+        .ignore()
+        .clone();
+
+    redis_conn
+        .write_buffered(pipe)
+        .await
+        .context("Failed to record connection event")
+}
+
+/// Get the history of the participant, oldest event first
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get(
+    redis_conn: &mut RedisConnection,
+    participant: ParticipantId,
+) -> Result<Vec<ConnectionEvent>> {
+    let mut events: Vec<ConnectionEvent> = redis_conn
+        .lrange(ConnectionHistoryKey { participant }, 0, -1)
+        .await
+        .context("Failed to get connection history")?;
+
+    events.reverse();
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn serialize_event() {
+        let event = ConnectionEvent {
+            timestamp: Timestamp::unix_epoch(),
+            kind: ConnectionEventKind::WebRtcDown {
+                media_session_type: "video".into(),
+                source: ParticipantId::nil(),
+                reason: Some("ICE failed".into()),
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "timestamp": "1970-01-01T00:00:00Z",
+                "event": "webrtc_down",
+                "media_session_type": "video",
+                "source": "00000000-0000-0000-0000-000000000000",
+                "reason": "ICE failed"
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Connectivity pre-checks of clients
+//!
+//! Clients gather ICE candidates using the configured STUN and TURN servers and report the types of the gathered
+//! candidates, either via the REST API before joining or via the signaling of the media module. The controller
+//! evaluates the report, stores the latest result of the client and counts the outcomes in the signaling metrics, which
+//! surfaces NAT traversal problems to operators.
+use super::metrics::SignalingMetrics;
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use types::core::Timestamp;
+
+/// Time in seconds after which a stored connectivity check expires
+const CONNECTIVITY_CHECK_EXPIRY: usize = 24 * 60 * 60;
+
+/// Latest connectivity check of a subject, e.g. `user={user_id}`
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:connectivity_check={subject}")]
+struct ConnectivityCheckKey<'s> {
+    subject: &'s str,
+}
+
+/// Type of an ICE candidate as defined in RFC 8445
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateType {
+    /// Candidate of a local interface
+    Host,
+    /// Server reflexive candidate, discovered using a STUN server
+    Srflx,
+    /// Peer reflexive candidate, discovered during the connectivity checks
+    Prflx,
+    /// Relayed candidate, allocated on a TURN server
+    Relay,
+}
+
+/// Report of the candidates gathered by the client
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ConnectivityReport {
+    /// Types of the ICE candidates the client gathered
+    pub candidate_types: Vec<CandidateType>,
+}
+
+/// How the client is able to establish media connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityOutcome {
+    /// The client discovered its public address, media can be sent without a relay
+    Direct,
+    /// The client can only send media relayed by a TURN server
+    Relayed,
+    /// Neither the STUN nor the TURN servers were reachable, the client likely cannot send any media
+    Failed,
+}
+
+impl ConnectivityOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectivityOutcome::Direct => "direct",
+            ConnectivityOutcome::Relayed => "relayed",
+            ConnectivityOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Evaluated connectivity report
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToRedisArgs, FromRedisValue,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct ConnectivityCheck {
+    pub outcome: ConnectivityOutcome,
+    /// A server reflexive or peer reflexive candidate has been gathered
+    pub stun_reachable: bool,
+    /// A relay candidate has been gathered
+    pub turn_reachable: bool,
+    pub checked_at: Timestamp,
+}
+
+impl ConnectivityCheck {
+    pub fn evaluate(report: &ConnectivityReport, checked_at: Timestamp) -> Self {
+        let stun_reachable = report
+            .candidate_types
+            .iter()
+            .any(|ty| matches!(ty, CandidateType::Srflx | CandidateType::Prflx));
+        let turn_reachable = report.candidate_types.contains(&CandidateType::Relay);
+
+        let outcome = if stun_reachable {
+            ConnectivityOutcome::Direct
+        } else if turn_reachable {
+            ConnectivityOutcome::Relayed
+        } else {
+            ConnectivityOutcome::Failed
+        };
+
+        Self {
+            outcome,
+            stun_reachable,
+            turn_reachable,
+            checked_at,
+        }
+    }
+
+    pub fn record_metrics(&self, metrics: &SignalingMetrics) {
+        metrics.increment_connectivity_checks_count(self.outcome.as_str(), self.turn_reachable);
+    }
+}
+
+/// Store the connectivity check as the latest check of the subject
+#[tracing::instrument(level = "debug", skip(redis_conn, check))]
+pub async fn set_last_check(
+    redis_conn: &mut RedisConnection,
+    subject: &str,
+    check: &ConnectivityCheck,
+) -> Result<()> {
+    redis_conn
+        .set_ex(
+            ConnectivityCheckKey { subject },
+            check,
+            CONNECTIVITY_CHECK_EXPIRY,
+        )
+        .await
+        .context("Failed to set last connectivity check")
+}
+
+/// Get the latest connectivity check of the subject
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_last_check(
+    redis_conn: &mut RedisConnection,
+    subject: &str,
+) -> Result<Option<ConnectivityCheck>> {
+    redis_conn
+        .get(ConnectivityCheckKey { subject })
+        .await
+        .context("Failed to get last connectivity check")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn evaluate(candidate_types: &[CandidateType]) -> ConnectivityCheck {
+        ConnectivityCheck::evaluate(
+            &ConnectivityReport {
+                candidate_types: candidate_types.to_vec(),
+            },
+            Timestamp::unix_epoch(),
+        )
+    }
+
+    #[test]
+    fn evaluate_outcome() {
+        use CandidateType::*;
+
+        let check = evaluate(&[Host, Srflx, Relay]);
+        assert_eq!(check.outcome, ConnectivityOutcome::Direct);
+        assert!(check.stun_reachable);
+        assert!(check.turn_reachable);
+
+        let check = evaluate(&[Host, Prflx]);
+        assert_eq!(check.outcome, ConnectivityOutcome::Direct);
+        assert!(!check.turn_reachable);
+
+        let check = evaluate(&[Host, Relay]);
+        assert_eq!(check.outcome, ConnectivityOutcome::Relayed);
+        assert!(!check.stun_reachable);
+
+        assert_eq!(evaluate(&[Host]).outcome, ConnectivityOutcome::Failed);
+        assert_eq!(evaluate(&[]).outcome, ConnectivityOutcome::Failed);
+    }
+}
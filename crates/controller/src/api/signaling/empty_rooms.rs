@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Background task destroying rooms which stayed empty for the configured grace period
+//!
+//! When the last participant leaves a room and an empty room grace period is configured, the room is not destroyed
+//! right away. Instead the point in time it is to be destroyed is stored in redis, which allows any controller
+//! instance to pick it up, even after the instance the last participant was connected to restarted. Rejoining the room
+//! within the grace period cancels its destruction.
+use super::metrics::SignalingMetrics;
+use super::prelude::*;
+use crate::redis_wrapper::RedisConnection;
+use anyhow::Result;
+use database::Db;
+use redis::AsyncCommands;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::{RoomId, Timestamp};
+
+/// Interval in which the empty rooms are checked for an elapsed grace period
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Periodically destroy all empty rooms whose grace period has elapsed
+///
+/// Runs until the shutdown signal is received.
+pub(crate) async fn sweeper_task(
+    mut redis_conn: RedisConnection,
+    db: Arc<Db>,
+    modules: Weak<SignalingModules>,
+    metrics: Arc<SignalingMetrics>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let modules = match modules.upgrade() {
+                    Some(modules) => modules,
+                    None => return,
+                };
+
+                if let Err(e) = sweep(&mut redis_conn, &db, &modules, &metrics).await {
+                    log::error!("Failed to destroy empty rooms, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Empty room sweeper received shutdown signal");
+                return;
+            }
+        }
+    }
+}
+
+async fn sweep(
+    redis_conn: &mut RedisConnection,
+    db: &Arc<Db>,
+    modules: &SignalingModules,
+    metrics: &SignalingMetrics,
+) -> Result<()> {
+    let room_ids = control::storage::get_expired_empty_rooms(redis_conn, Timestamp::now()).await?;
+
+    for room_id in room_ids {
+        match destroy_room(redis_conn, db, modules, room_id).await {
+            Ok(true) => metrics.increment_destroyed_rooms_count(),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to destroy empty room {}, {:?}", room_id, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Destroy the room unless it has been taken by another instance or a participant rejoined in the meantime
+///
+/// Returns true if the room has been destroyed.
+async fn destroy_room(
+    redis_conn: &mut RedisConnection,
+    db: &Arc<Db>,
+    modules: &SignalingModules,
+    room_id: RoomId,
+) -> Result<bool> {
+    let room = SignalingRoomId(room_id, None);
+
+    let mut room_mutex = control::storage::room_mutex(room);
+    let guard = room_mutex.lock(redis_conn).await?;
+
+    // Another instance might have taken the room or a participant rejoined in the meantime
+    let destroy = control::storage::cancel_empty_room_destroy(redis_conn, room_id).await?
+        && control::storage::get_participant_count(redis_conn, room_id)
+            .await?
+            .unwrap_or_default()
+            <= 0;
+
+    let res = if destroy {
+        log::debug!("Destroying empty room {}", room_id);
+
+        destroy_room_state(redis_conn, db, modules, room).await
+    } else {
+        Ok(())
+    };
+
+    guard.unlock(redis_conn).await?;
+
+    res.map(|()| destroy)
+}
+
+/// Remove all state of the room, like the runner of the last participant does when no grace period is configured
+async fn destroy_room_state(
+    redis_conn: &mut RedisConnection,
+    db: &Arc<Db>,
+    modules: &SignalingModules,
+    room: SignalingRoomId,
+) -> Result<()> {
+    modules.cleanup_empty_room(redis_conn, room).await;
+
+    if let Err(e) = super::room_statistics::persist(redis_conn, db, room.room_id()).await {
+        log::error!(
+            "Failed to persist statistics of room {}, {:?}",
+            room.room_id(),
+            e
+        );
+    }
+
+    control::storage::delete_participant_count(redis_conn, room.room_id()).await?;
+    control::storage::delete_tariff(redis_conn, room.room_id()).await?;
+    super::sharding::release_room(redis_conn, room.room_id()).await?;
+
+    remove_room_keys(redis_conn, room).await
+}
+
+/// Remove all redis keys of the room except its lock, which is released by the sweeper
+async fn remove_room_keys(redis_conn: &mut RedisConnection, room: SignalingRoomId) -> Result<()> {
+    let lock_key = format!("k3k-signaling:room={room}:participants.lock");
+
+    let keys: Vec<String> = control::storage::get_room_keys(redis_conn, room.room_id())
+        .await?
+        .into_iter()
+        .filter(|key| *key != lock_key)
+        .collect();
+
+    if !keys.is_empty() {
+        redis_conn.del::<_, ()>(keys).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use serial_test::serial;
+    use uuid::Uuid;
+
+    const ROOM_ID: RoomId = RoomId::from(Uuid::nil());
+
+    /// Leave the room behind like the runner of the last participant does with a grace period
+    async fn leave_empty_room(redis_conn: &mut RedisConnection, participant_count: isize) {
+        redis_conn
+            .set::<_, _, ()>(
+                format!("k3k-signaling:room={ROOM_ID}:participant-count"),
+                participant_count,
+            )
+            .await
+            .unwrap();
+        redis_conn
+            .set::<_, _, ()>(format!("k3k-signaling:room={ROOM_ID}:tariff"), "{}")
+            .await
+            .unwrap();
+        redis_conn
+            .set::<_, _, ()>(format!("k3k-signaling:room={ROOM_ID}:chat:history"), "[]")
+            .await
+            .unwrap();
+
+        control::storage::schedule_empty_room_destroy(redis_conn, ROOM_ID, Timestamp::now())
+            .await
+            .unwrap();
+    }
+
+    async fn room_keys(redis_conn: &mut RedisConnection) -> Vec<String> {
+        let mut keys = control::storage::get_room_keys(redis_conn, ROOM_ID)
+            .await
+            .unwrap();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn destroy_expired_empty_room() {
+        let db_ctx = test_util::database::DatabaseContext::new(true).await;
+        let mut redis_conn = setup().await;
+
+        leave_empty_room(&mut redis_conn, 0).await;
+
+        assert_eq!(
+            control::storage::get_expired_empty_rooms(&mut redis_conn, Timestamp::now())
+                .await
+                .unwrap(),
+            vec![ROOM_ID]
+        );
+
+        let destroyed = destroy_room(
+            &mut redis_conn,
+            &db_ctx.db,
+            &SignalingModules::default(),
+            ROOM_ID,
+        )
+        .await
+        .unwrap();
+
+        assert!(destroyed);
+        assert!(room_keys(&mut redis_conn).await.is_empty());
+        assert!(
+            control::storage::get_expired_empty_rooms(&mut redis_conn, Timestamp::now())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn keep_rejoined_room() {
+        let db_ctx = test_util::database::DatabaseContext::new(true).await;
+        let mut redis_conn = setup().await;
+
+        leave_empty_room(&mut redis_conn, 1).await;
+
+        let destroyed = destroy_room(
+            &mut redis_conn,
+            &db_ctx.db,
+            &SignalingModules::default(),
+            ROOM_ID,
+        )
+        .await
+        .unwrap();
+
+        assert!(!destroyed);
+        assert_eq!(
+            room_keys(&mut redis_conn).await,
+            vec![
+                format!("k3k-signaling:room={ROOM_ID}:chat:history"),
+                format!("k3k-signaling:room={ROOM_ID}:participant-count"),
+                format!("k3k-signaling:room={ROOM_ID}:tariff"),
+            ]
+        );
+    }
+}
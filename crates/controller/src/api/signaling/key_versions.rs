@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Versioning of the redis keys of signaling modules
+//!
+//! Every module declares the version of its key format with [`SignalingModule::KEY_VERSION`]. The version the keys
+//! stored in redis have been migrated to is recorded per module namespace. When a controller starts, it migrates the
+//! keys of all registered modules with an older recorded version with [`SignalingModule::migrate_keys`]. Only one
+//! controller of a deployment migrates at a time.
+//!
+//! Keys of version 2 and later carry the version after the namespace of the module, e.g.
+//! `k3k-signaling:room={room}:chat:v2:history`. Controllers of the previous release keep using the old keys during a
+//! rolling upgrade, so migrations should copy the keys with [`copy_keys`] instead of renaming them. Controllers which
+//! find a newer recorded version than they support leave the keys untouched.
+//!
+//! The copy is only a snapshot, controllers of the previous release continue to write the old keys after the
+//! migration. Until the following release modules bridge both key versions:
+//! - writes go to the new and the old keys (dual-write), so controllers of the previous release see them
+//! - reads merge the new and the old keys (dual-read), e.g. by keeping the newest value, so changes of controllers of
+//!   the previous release are not lost
+//!
+//! See the last-seen timestamps of the chat module for an example.
+//!
+//! [`SignalingModule::KEY_VERSION`]: super::prelude::SignalingModule::KEY_VERSION
+//! [`SignalingModule::migrate_keys`]: super::prelude::SignalingModule::migrate_keys
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use r3dlock::Mutex;
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Version of the keys of modules without a recorded version, i.e. the key format before versioning was introduced
+pub const INITIAL_KEY_VERSION: u32 = 1;
+
+/// Hash of the key versions of the modules, keyed by their namespace
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:key_versions")]
+struct KeyVersions;
+
+/// Lock taken while migrating the keys of the modules
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:key_versions.lock")]
+pub(crate) struct KeyVersionsLock;
+
+/// Must be taken while migrating keys
+///
+/// Controllers starting at the same time wait for the migration of the first one instead of failing.
+pub(crate) fn migration_mutex() -> Mutex<KeyVersionsLock> {
+    Mutex::new(KeyVersionsLock)
+        .with_wait_time(Duration::from_millis(200)..Duration::from_millis(500))
+        .with_retries(100)
+}
+
+/// Returns the recorded key versions of all modules, keyed by their namespace
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_all(redis_conn: &mut RedisConnection) -> Result<BTreeMap<String, u32>> {
+    redis_conn
+        .hgetall(KeyVersions)
+        .await
+        .context("Failed to HGETALL the key versions")
+}
+
+/// Returns the recorded key version of the module, [`INITIAL_KEY_VERSION`] if none has been recorded yet
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get(redis_conn: &mut RedisConnection, namespace: &str) -> Result<u32> {
+    let version: Option<u32> = redis_conn
+        .hget(KeyVersions, namespace)
+        .await
+        .context("Failed to HGET the key version")?;
+
+    Ok(version.unwrap_or(INITIAL_KEY_VERSION))
+}
+
+/// Record the key version of the module
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set(redis_conn: &mut RedisConnection, namespace: &str, version: u32) -> Result<()> {
+    redis_conn
+        .hset(KeyVersions, namespace, version)
+        .await
+        .context("Failed to HSET the key version")
+}
+
+/// Copy all keys matching `pattern` to the key returned by `new_key`, keeping their values and expiry
+///
+/// Keys for which `new_key` returns `None` are skipped, existing target keys are overwritten. The old keys are kept,
+/// so controllers of the previous release can continue to use them during a rolling upgrade.
+///
+/// Returns the number of copied keys.
+#[tracing::instrument(level = "debug", skip(redis_conn, new_key))]
+pub async fn copy_keys(
+    redis_conn: &mut RedisConnection,
+    pattern: &str,
+    new_key: impl Fn(&str) -> Option<String>,
+) -> Result<usize> {
+    let keys = {
+        let mut iter = redis_conn
+            .scan_match::<_, String>(pattern)
+            .await
+            .context("Failed to scan the keys to copy")?;
+
+        let mut keys = Vec::new();
+
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+
+        keys
+    };
+
+    let mut copied = 0;
+
+    for key in keys {
+        let target = match new_key(&key) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let payload: Option<Vec<u8>> = redis::cmd("DUMP")
+            .arg(&key)
+            .query_async(redis_conn)
+            .await
+            .context("Failed to DUMP key")?;
+
+        // The key expired while copying
+        let payload = match payload {
+            Some(payload) => payload,
+            None => continue,
+        };
+
+        let ttl: i64 = redis_conn.pttl(&key).await.context("Failed to get PTTL")?;
+
+        redis::cmd("RESTORE")
+            .arg(&target)
+            .arg(ttl.max(0))
+            .arg(payload)
+            .arg("REPLACE")
+            .query_async::<_, ()>(redis_conn)
+            .await
+            .context("Failed to RESTORE key")?;
+
+        copied += 1;
+    }
+
+    Ok(copied)
+}
@@ -2,14 +2,64 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+//! Metrics of the signaling
+//!
+//! Metrics describing rooms must never be labeled with the id of the room or participant, as every room would create
+//! a new time series. Instead they are labeled with a bounded set of values like the [`RoomSize`] bucket and the module
+//! namespace. Measurements are recorded with the context of the current tracing span, so exporters supporting exemplars
+//! can link them to the trace of the room.
 use crate::api;
 use opentelemetry::metrics::{Counter, Histogram, UpDownCounter};
 use opentelemetry::{Context, Key};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 const STARTUP_SUCCESSFUL: Key = Key::from_static_str("successful");
 const DESTROY_SUCCESSFUL: Key = Key::from_static_str("successful");
 const PARTICIPATION_KIND: Key = Key::from_static_str("participation_kind");
 const MEDIA_SESSION_TYPE: Key = Key::from_static_str("media_session_type");
+const CONNECTIVITY_OUTCOME: Key = Key::from_static_str("outcome");
+const TURN_REACHABLE: Key = Key::from_static_str("turn_reachable");
+const ROOM_SIZE: Key = Key::from_static_str("room_size");
+const MODULE: Key = Key::from_static_str("module");
+const REJECTION_REASON: Key = Key::from_static_str("reason");
+const FAILURE_KIND: Key = Key::from_static_str("kind");
+
+/// Bucket of the number of participants inside a room, used as metric label instead of the room id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RoomSize {
+    /// Up to 2 participants
+    Tiny,
+    /// Up to 10 participants
+    Small,
+    /// Up to 50 participants
+    Medium,
+    /// Up to 250 participants
+    Large,
+    /// More than 250 participants
+    Huge,
+}
+
+impl RoomSize {
+    pub fn from_participant_count(count: usize) -> Self {
+        match count {
+            0..=2 => Self::Tiny,
+            3..=10 => Self::Small,
+            11..=50 => Self::Medium,
+            51..=250 => Self::Large,
+            _ => Self::Huge,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tiny => "tiny",
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+            Self::Huge => "huge",
+        }
+    }
+}
 
 pub struct SignalingMetrics {
     pub(crate) runner_startup_time: Histogram<f64>,
@@ -19,6 +69,12 @@ pub struct SignalingMetrics {
     pub(crate) participants_count: UpDownCounter<i64>,
     pub(crate) participants_with_audio_count: UpDownCounter<i64>,
     pub(crate) participants_with_video_count: UpDownCounter<i64>,
+    pub(crate) inactivity_disconnects_count: Counter<u64>,
+    pub(crate) connectivity_checks_count: Counter<u64>,
+    pub(crate) module_event_duration: Histogram<f64>,
+    pub(crate) module_failures_count: Counter<u64>,
+    pub(crate) ws_queue_depth: UpDownCounter<i64>,
+    pub(crate) ticket_rejections_count: Counter<u64>,
 }
 
 impl SignalingMetrics {
@@ -93,4 +149,82 @@ impl SignalingMetrics {
             &[MEDIA_SESSION_TYPE.string(session_type.to_owned())],
         );
     }
+
+    /// Count a rejected signaling ticket, `reason` is one of `invalid`, `replayed` or `fingerprint_mismatch`
+    pub fn increment_ticket_rejections_count(&self, reason: &'static str) {
+        self.ticket_rejections_count.add(
+            &Context::current(),
+            1,
+            &[REJECTION_REASON.string(reason)],
+        );
+    }
+
+    pub fn increment_inactivity_disconnects_count(&self) {
+        self.inactivity_disconnects_count
+            .add(&Context::current(), 1, &[]);
+    }
+
+    pub fn increment_connectivity_checks_count(&self, outcome: &'static str, turn_reachable: bool) {
+        self.connectivity_checks_count.add(
+            &Context::current(),
+            1,
+            &[
+                CONNECTIVITY_OUTCOME.string(outcome),
+                TURN_REACHABLE.bool(turn_reachable),
+            ],
+        );
+    }
+
+    /// Record the time a module took to handle an event
+    ///
+    /// `module` must be the namespace of a registered module, never a value received from the client.
+    pub fn record_module_event_duration(
+        &self,
+        module: &'static str,
+        room_size: RoomSize,
+        secs: f64,
+    ) {
+        self.module_event_duration.record(
+            &tracing::Span::current().context(),
+            secs,
+            &[MODULE.string(module), ROOM_SIZE.string(room_size.as_str())],
+        );
+    }
+
+    /// Count a module which failed to handle an event, `kind` is either `error` or `panic`
+    ///
+    /// `module` must be the namespace of a registered module, never a value received from the client.
+    pub fn increment_module_failures_count(&self, module: &'static str, kind: &'static str) {
+        self.module_failures_count.add(
+            &Context::current(),
+            1,
+            &[MODULE.string(module), FAILURE_KIND.string(kind)],
+        );
+    }
+
+    /// A websocket message was queued for the runner
+    pub fn increment_ws_queue_depth(&self) {
+        self.ws_queue_depth.add(&Context::current(), 1, &[]);
+    }
+
+    /// A queued websocket message was taken by the runner
+    pub fn decrement_ws_queue_depth(&self) {
+        self.ws_queue_depth.add(&Context::current(), -1, &[]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn room_size_buckets() {
+        assert_eq!(RoomSize::from_participant_count(0), RoomSize::Tiny);
+        assert_eq!(RoomSize::from_participant_count(2), RoomSize::Tiny);
+        assert_eq!(RoomSize::from_participant_count(3), RoomSize::Small);
+        assert_eq!(RoomSize::from_participant_count(50), RoomSize::Medium);
+        assert_eq!(RoomSize::from_participant_count(51), RoomSize::Large);
+        assert_eq!(RoomSize::from_participant_count(10_000), RoomSize::Huge);
+    }
 }
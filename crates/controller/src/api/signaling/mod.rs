@@ -3,12 +3,22 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use types::core::{BreakoutRoomId, RoomId};
 
+pub mod capture;
+pub mod connection_history;
+pub mod connectivity;
+pub(crate) mod empty_rooms;
+pub mod key_versions;
 pub(crate) mod metrics;
+pub(crate) mod prewarm;
 pub(crate) mod resumption;
+pub(crate) mod room_statistics;
+pub(crate) mod sharding;
+pub(crate) mod snapshot;
 pub(crate) mod ticket;
 
 mod ws;
@@ -17,18 +27,32 @@ mod ws_modules;
 pub(crate) use ws::ws_service;
 
 pub mod prelude {
+    pub use super::capture;
+    pub use super::connection_history;
+    pub use super::connectivity;
+    pub use super::key_versions;
     pub use super::ws::module_tester::*;
+    pub use super::ws::replay::*;
     pub use super::ws::{
-        DestroyContext, Event, InitContext, ModuleContext, SignalingModule, SignalingModules,
-        SignalingProtocols,
+        BusEvent, DestroyContext, Event, InitContext, ModuleContext, ProtocolVersion,
+        SharedPayload, SignalingModule, SignalingModules, SignalingProtocols, SignalingSchemas,
     };
-    pub use super::ws_modules::{breakout, control, moderation, recording};
+    pub use super::ws_modules::{breakout, control, moderation, plugins, recording};
     pub use super::{Role, SignalingRoomId};
 }
 
 /// Role of the participant inside a room
 #[derive(
-    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToRedisArgs, FromRedisValue,
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    ToRedisArgs,
+    FromRedisValue,
+    JsonSchema,
 )]
 #[serde(rename_all = "lowercase")]
 #[to_redis_args(serde)]
@@ -0,0 +1,235 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Background task preparing the rooms of upcoming events
+//!
+//! When a pre-warm lead time is configured, the signaling modules get the chance to provision the resources of a room
+//! shortly before an event in the room starts, e.g. the etherpad of the protocol or the space of the whiteboard. This
+//! way the first participants do not have to wait for them to be created. The prepared room is scheduled to be
+//! destroyed like an empty room, joining it cancels the destruction. A marker in redis makes sure every meeting is only
+//! prepared by a single controller instance.
+use super::prelude::*;
+use crate::api::v1::events::conflicts::{occurrences_between, MAX_EXPANDED_OCCURRENCES};
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettings;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::events::Event;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::RoomId;
+
+/// Interval in which the upcoming events are checked for rooms to prepare
+const PREWARM_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Periodically prepare the rooms of all events starting within the configured lead time
+///
+/// Runs until the shutdown signal is received.
+pub(crate) async fn prewarm_task(
+    settings: SharedSettings,
+    mut redis_conn: RedisConnection,
+    db: Arc<Db>,
+    modules: Weak<SignalingModules>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(PREWARM_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let rooms = settings.load().rooms.clone();
+
+                if rooms.prewarm_lead_time.is_zero() {
+                    continue;
+                }
+
+                let modules = match modules.upgrade() {
+                    Some(modules) => modules,
+                    None => return,
+                };
+
+                // Prepared rooms nobody joins are kept at least as long as rooms left empty
+                let keep_for = rooms.prewarm_lead_time.max(rooms.empty_room_grace_period);
+
+                if let Err(e) =
+                    prewarm(&mut redis_conn, &db, &modules, rooms.prewarm_lead_time, keep_for).await
+                {
+                    log::error!("Failed to prepare the rooms of upcoming events, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Room pre-warm task received shutdown signal");
+                return;
+            }
+        }
+    }
+}
+
+async fn prewarm(
+    redis_conn: &mut RedisConnection,
+    db: &Arc<Db>,
+    modules: &SignalingModules,
+    lead_time: Duration,
+    keep_for: Duration,
+) -> Result<()> {
+    let now = Utc::now();
+    let time_max =
+        now + chrono::Duration::from_std(lead_time).context("invalid pre-warm lead time")?;
+    let keep_for =
+        chrono::Duration::from_std(keep_for).context("invalid empty room grace period")?;
+
+    let db = db.clone();
+    let upcoming = crate::block(move || -> database::Result<Vec<(RoomId, DateTime<Utc>)>> {
+        let mut conn = db.get_conn()?;
+
+        let events = Event::get_all_between(&mut conn, now, time_max)?;
+
+        let mut upcoming = Vec::new();
+        let mut budget = MAX_EXPANDED_OCCURRENCES;
+
+        for event in events {
+            for occurrence in occurrences_between(&mut conn, &event, now, time_max, &mut budget)? {
+                // Meetings which already started are prepared by their participants
+                if occurrence.starts_at > now && occurrence.starts_at <= time_max {
+                    upcoming.push((event.room, occurrence.starts_at));
+                }
+            }
+        }
+
+        Ok(upcoming)
+    })
+    .await??;
+
+    for (room_id, starts_at) in upcoming {
+        let expiry = (starts_at - now).to_std().unwrap_or_default() + PREWARM_INTERVAL;
+
+        if !control::storage::try_mark_room_prepared(redis_conn, room_id, starts_at.into(), expiry)
+            .await?
+        {
+            continue;
+        }
+
+        if let Err(e) = prepare_room(redis_conn, modules, room_id, starts_at + keep_for).await {
+            log::error!("Failed to prepare room {}, {:?}", room_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn prepare_room(
+    redis_conn: &mut RedisConnection,
+    modules: &SignalingModules,
+    room_id: RoomId,
+    destroy_at: DateTime<Utc>,
+) -> Result<()> {
+    let room = SignalingRoomId(room_id, None);
+
+    let mut room_mutex = control::storage::room_mutex(room);
+    let guard = room_mutex.lock(redis_conn).await?;
+
+    // Rooms which are already in use have been initialized by their participants
+    let in_use = control::storage::get_participant_count(redis_conn, room_id)
+        .await?
+        .unwrap_or_default()
+        > 0;
+
+    let res = if in_use {
+        Ok(())
+    } else {
+        log::debug!("Preparing room {}", room_id);
+
+        modules.prepare_room(redis_conn, room).await;
+
+        // Release the prepared resources if nobody joins the room
+        control::storage::schedule_empty_room_destroy(redis_conn, room_id, destroy_at.into()).await
+    };
+
+    guard.unlock(redis_conn).await?;
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use redis::AsyncCommands;
+    use serial_test::serial;
+    use types::core::Timestamp;
+    use uuid::Uuid;
+
+    const ROOM_ID: RoomId = RoomId::from(Uuid::nil());
+
+    async fn expired_empty_rooms(redis_conn: &mut RedisConnection) -> Vec<RoomId> {
+        control::storage::get_expired_empty_rooms(redis_conn, Timestamp::now())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn prepared_room_is_destroyed_when_unused() {
+        let mut redis_conn = setup().await;
+
+        prepare_room(
+            &mut redis_conn,
+            &SignalingModules::default(),
+            ROOM_ID,
+            Utc::now() - chrono::Duration::seconds(1),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(expired_empty_rooms(&mut redis_conn).await, vec![ROOM_ID]);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn room_in_use_is_not_prepared() {
+        let mut redis_conn = setup().await;
+
+        redis_conn
+            .set::<_, _, ()>(format!("k3k-signaling:room={ROOM_ID}:participant-count"), 2)
+            .await
+            .unwrap();
+
+        prepare_room(
+            &mut redis_conn,
+            &SignalingModules::default(),
+            ROOM_ID,
+            Utc::now() - chrono::Duration::seconds(1),
+        )
+        .await
+        .unwrap();
+
+        assert!(expired_empty_rooms(&mut redis_conn).await.is_empty());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn meeting_is_prepared_once() {
+        let mut redis_conn = setup().await;
+        let starts_at = Timestamp::now();
+
+        assert!(control::storage::try_mark_room_prepared(
+            &mut redis_conn,
+            ROOM_ID,
+            starts_at,
+            Duration::from_secs(60)
+        )
+        .await
+        .unwrap());
+        assert!(!control::storage::try_mark_room_prepared(
+            &mut redis_conn,
+            ROOM_ID,
+            starts_at,
+            Duration::from_secs(60)
+        )
+        .await
+        .unwrap());
+    }
+}
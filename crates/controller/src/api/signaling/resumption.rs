@@ -10,7 +10,7 @@
 //! to receive the same participant when reconnecting to the room. This enables all participant id
 //! based features to recognize the reconnected client as the previously disconnected one.
 
-use crate::{api::Participant, redis_wrapper::RedisConnection};
+use crate::{api::Participant, redis_encryption::Encrypted, redis_wrapper::RedisConnection};
 use anyhow::{bail, Context, Result};
 use redis_args::{FromRedisValue, ToRedisArgs};
 use serde::{Deserialize, Serialize};
@@ -57,7 +57,7 @@ impl ResumptionTokenKeepAlive {
     pub async fn set_initial(&mut self, redis_conn: &mut RedisConnection) -> Result<()> {
         redis::cmd("SET")
             .arg(&self.redis_key)
-            .arg(&self.data)
+            .arg(Encrypted(&self.data))
             .arg("EX")
             .arg(120)
             .arg("NX")
@@ -66,6 +66,11 @@ impl ResumptionTokenKeepAlive {
             .context("failed to set initial resumption token")
     }
 
+    /// Change the breakout room the participant resumes into, takes effect on the next refresh
+    pub fn set_breakout_room(&mut self, breakout_room: Option<BreakoutRoomId>) {
+        self.data.breakout_room = breakout_room;
+    }
+
     pub async fn wait(&mut self) {
         sleep_until(self.next_refresh.into()).await;
     }
@@ -77,7 +82,7 @@ impl ResumptionTokenKeepAlive {
         // and only if it already exists
         let value: redis::Value = redis::cmd("SET")
             .arg(&self.redis_key)
-            .arg(&self.data)
+            .arg(Encrypted(&self.data))
             .arg("EX")
             .arg(120)
             .arg("XX")
@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Persistence of the usage statistics of room sessions
+//!
+//! While a room is alive its statistics are collected in redis by the runners of its participants. When the room
+//! gets destroyed they are moved into the database, where they are available for reporting. The registered users which
+//! took part in the session are recorded as well, to suggest recently met users as contacts. The markers set during the
+//! session are linked to its statistics.
+use super::prelude::*;
+use crate::redis_wrapper::RedisConnection;
+use anyhow::Result;
+use database::Db;
+use db_storage::room_markers;
+use db_storage::room_statistics::{insert_participants, NewRoomStatistics};
+use diesel::Connection;
+use std::sync::Arc;
+use types::core::{RoomId, Timestamp};
+
+/// Move the statistics of the room's session from redis into the database
+///
+/// Must be called when the room gets destroyed, while holding the room lock.
+pub(crate) async fn persist(
+    redis_conn: &mut RedisConnection,
+    db: &Arc<Db>,
+    room_id: RoomId,
+) -> Result<()> {
+    let statistics = match control::storage::take_room_statistics(redis_conn, room_id).await? {
+        Some(statistics) => statistics,
+        None => return Ok(()),
+    };
+
+    let new_statistics = NewRoomStatistics {
+        room_id,
+        tenant_id: statistics.tenant_id,
+        started_at: *statistics.started_at,
+        ended_at: *Timestamp::now(),
+        peak_participants: statistics.peak_participants.try_into().unwrap_or(i32::MAX),
+        participant_minutes: statistics.participant_seconds / 60,
+        modules: statistics.modules,
+    };
+
+    let users = statistics.users;
+
+    let db = db.clone();
+    crate::block(move || -> database::Result<()> {
+        let mut conn = db.get_conn()?;
+
+        conn.transaction(|conn| {
+            let statistics = new_statistics.insert(conn)?;
+
+            // The markers of the session appear in its summary
+            room_markers::link_to_session(conn, room_id, statistics.id)?;
+
+            insert_participants(conn, statistics.id, &users)
+        })
+    })
+    .await??;
+
+    Ok(())
+}
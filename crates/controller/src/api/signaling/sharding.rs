@@ -0,0 +1,385 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Assignment of rooms to controller instances
+//!
+//! Without sharding the participants of a room may be connected to any controller instance, which makes every event
+//! inside the room cross instance boundaries through rabbitmq. When sharding is configured, each instance announces
+//! itself in redis together with the URL of its signaling endpoint and refreshes the announcement periodically. The
+//! first participant starting a room assigns it to the instance handling the request, all following participants are
+//! directed to the signaling endpoint of that instance. Once an instance stops refreshing its announcement, e.g.
+//! because it crashed, its rooms are taken over by the next instance a participant of the room starts the room on.
+//!
+//! The assignment of a room expires after the instance timeout as well, the instance refreshes the assignments of its
+//! rooms together with its announcement. This way no assignments are left behind when the room state is lost.
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use controller_shared::settings::Sharding;
+use parking_lot::Mutex;
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::RoomId;
+use uuid::Uuid;
+
+/// Prefix of the [`InstanceKey`], used to build the key inside the [`ASSIGN_ROOM`] script
+const INSTANCE_KEY_PREFIX: &str = "k3k-signaling:instance=";
+
+/// Announcement of a running controller instance, containing the URL of its signaling endpoint
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:instance={instance_id}")]
+struct InstanceKey {
+    instance_id: Uuid,
+}
+
+/// The controller instance the room is assigned to
+///
+/// Notice that this key only contains the [`RoomId`] as it applies to all breakout rooms as well
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room_id}:instance")]
+struct RoomInstance {
+    room_id: RoomId,
+}
+
+/// Returns the instance the room is assigned to and the URL of its signaling endpoint
+///
+/// Assigns the room to the given instance if it is not assigned yet or its instance is gone. The assignment expires
+/// after the given number of milliseconds unless it is refreshed with [`REFRESH_ROOMS`].
+const ASSIGN_ROOM: &str = r#"
+local instance = redis.call("GET", KEYS[1])
+if instance then
+    local url = redis.call("GET", ARGV[3] .. instance)
+    if url then
+        return {instance, url}
+    end
+end
+redis.call("SET", KEYS[1], ARGV[1], "PX", ARGV[4])
+return {ARGV[1], ARGV[2]}
+"#;
+
+/// Refreshes the expiry of all given room assignments which are still assigned to the given instance
+///
+/// Returns a list containing `1` for every refreshed assignment and `0` for every assignment which was released or
+/// taken over by another instance.
+const REFRESH_ROOMS: &str = r#"
+local refreshed = {}
+for i, key in ipairs(KEYS) do
+    if redis.call("GET", key) == ARGV[1] then
+        redis.call("PEXPIRE", key, ARGV[2])
+        refreshed[i] = 1
+    else
+        refreshed[i] = 0
+    end
+end
+return refreshed
+"#;
+
+/// The controller instance a room is assigned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    /// URL of the signaling endpoint of the instance
+    pub signaling_url: String,
+    /// Whether the room is assigned to this instance
+    pub local: bool,
+}
+
+/// Assigns rooms to this controller instance, does nothing if sharding is not configured
+pub struct RoomAffinity {
+    instance: Option<Instance>,
+}
+
+struct Instance {
+    id: Uuid,
+    signaling_url: String,
+    timeout: Duration,
+    /// Rooms assigned to this instance, their assignments are refreshed by the [`announce_task`]
+    rooms: Mutex<HashSet<RoomId>>,
+}
+
+impl RoomAffinity {
+    pub fn new(settings: Option<&Sharding>) -> Self {
+        Self {
+            instance: settings.map(|settings| Instance {
+                id: Uuid::new_v4(),
+                signaling_url: settings.signaling_url.to_string(),
+                timeout: settings.instance_timeout,
+                rooms: Mutex::new(HashSet::new()),
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.instance.is_some()
+    }
+
+    /// Returns the instance the room is assigned to, or `None` if sharding is not configured
+    ///
+    /// Assigns the room to this instance if it is not assigned to a running instance.
+    pub async fn assign(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room_id: RoomId,
+    ) -> Result<Option<Assignment>> {
+        let instance = match &self.instance {
+            Some(instance) => instance,
+            None => return Ok(None),
+        };
+
+        let (instance_id, signaling_url): (String, String) = redis::Script::new(ASSIGN_ROOM)
+            .key(RoomInstance { room_id })
+            .arg(instance.id.to_string())
+            .arg(&instance.signaling_url)
+            .arg(INSTANCE_KEY_PREFIX)
+            .arg(instance.timeout_ms())
+            .invoke_async(redis_conn)
+            .await
+            .context("Failed to assign the room to a controller instance")?;
+
+        let local = instance_id == instance.id.to_string();
+
+        if local {
+            instance.rooms.lock().insert(room_id);
+        }
+
+        Ok(Some(Assignment {
+            signaling_url,
+            local,
+        }))
+    }
+}
+
+impl Instance {
+    fn timeout_ms(&self) -> u64 {
+        (self.timeout.as_millis() as u64).max(1000)
+    }
+
+    /// Announce this instance, the announcement expires after the instance timeout
+    async fn announce(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        redis_conn
+            .set_ex(
+                InstanceKey {
+                    instance_id: self.id,
+                },
+                &self.signaling_url,
+                self.timeout.as_secs().max(1) as usize,
+            )
+            .await
+            .context("Failed to SET the instance announcement")
+    }
+
+    /// Refresh the assignments of the rooms of this instance
+    ///
+    /// Rooms which have been released or taken over by another instance are forgotten.
+    async fn refresh_rooms(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        let rooms: Vec<RoomId> = self.rooms.lock().iter().copied().collect();
+
+        if rooms.is_empty() {
+            return Ok(());
+        }
+
+        let mut script = redis::Script::new(REFRESH_ROOMS).prepare_invoke();
+
+        for &room_id in &rooms {
+            script.key(RoomInstance { room_id });
+        }
+
+        let refreshed: Vec<bool> = script
+            .arg(self.id.to_string())
+            .arg(self.timeout_ms())
+            .invoke_async(redis_conn)
+            .await
+            .context("Failed to refresh the room assignments")?;
+
+        let mut assigned_rooms = self.rooms.lock();
+
+        for (room_id, refreshed) in rooms.into_iter().zip(refreshed) {
+            if !refreshed {
+                assigned_rooms.remove(&room_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the announcement of this instance, so its rooms are taken over right away
+    async fn withdraw(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        redis_conn
+            .del(InstanceKey {
+                instance_id: self.id,
+            })
+            .await
+            .context("Failed to DEL the instance announcement")
+    }
+}
+
+/// Remove the assignment of a destroyed room, so it can be assigned to any instance when it is started again
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn release_room(redis_conn: &mut RedisConnection, room_id: RoomId) -> Result<()> {
+    redis_conn
+        .del(RoomInstance { room_id })
+        .await
+        .context("Failed to DEL the room's instance")
+}
+
+/// Periodically refresh the announcement of this instance
+///
+/// Runs until the shutdown signal is received, withdrawing the announcement. Returns right away if sharding is not
+/// configured.
+pub(crate) async fn announce_task(
+    affinity: Arc<RoomAffinity>,
+    mut redis_conn: RedisConnection,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let instance = match &affinity.instance {
+        Some(instance) => instance,
+        None => return,
+    };
+
+    // Refresh well before the announcement expires, so a single failed attempt does not hand over the rooms
+    let mut interval = tokio::time::interval((instance.timeout / 3).max(Duration::from_secs(1)));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(e) = instance.announce(&mut redis_conn).await {
+                    log::error!("Failed to announce the controller instance, {:?}", e);
+                }
+
+                if let Err(e) = instance.refresh_rooms(&mut redis_conn).await {
+                    log::error!("Failed to refresh the rooms of the controller instance, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Instance announcement task received shutdown signal");
+
+                if let Err(e) = instance.withdraw(&mut redis_conn).await {
+                    log::error!("Failed to withdraw the controller instance, {:?}", e);
+                }
+
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use serial_test::serial;
+
+    fn affinity(signaling_url: &str) -> RoomAffinity {
+        RoomAffinity::new(Some(&Sharding {
+            signaling_url: signaling_url.parse().unwrap(),
+            instance_timeout: Duration::from_secs(30),
+        }))
+    }
+
+    const ROOM: RoomId = RoomId::from(Uuid::from_u128(1));
+
+    #[tokio::test]
+    #[serial]
+    async fn rooms_are_assigned_to_the_first_instance() {
+        let mut redis_conn = setup().await;
+
+        let first = affinity("wss://controller-1.example.org/signaling");
+        let second = affinity("wss://controller-2.example.org/signaling");
+
+        for instance in [&first, &second] {
+            instance
+                .instance
+                .as_ref()
+                .unwrap()
+                .announce(&mut redis_conn)
+                .await
+                .unwrap();
+        }
+
+        let expected = Assignment {
+            signaling_url: "wss://controller-1.example.org/signaling".into(),
+            local: true,
+        };
+        assert_eq!(
+            first.assign(&mut redis_conn, ROOM).await.unwrap(),
+            Some(expected.clone())
+        );
+
+        let expected = Assignment {
+            local: false,
+            ..expected
+        };
+        assert_eq!(
+            second.assign(&mut redis_conn, ROOM).await.unwrap(),
+            Some(expected)
+        );
+
+        // The assignment expires unless it is refreshed
+        let ttl: i64 = redis_conn
+            .pttl(RoomInstance { room_id: ROOM })
+            .await
+            .unwrap();
+        assert!(ttl > 0 && ttl <= 30_000);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn rooms_of_gone_instances_are_taken_over() {
+        let mut redis_conn = setup().await;
+
+        let first = affinity("wss://controller-1.example.org/signaling");
+        let second = affinity("wss://controller-2.example.org/signaling");
+        let first_instance = first.instance.as_ref().unwrap();
+        let second_instance = second.instance.as_ref().unwrap();
+
+        first_instance.announce(&mut redis_conn).await.unwrap();
+        second_instance.announce(&mut redis_conn).await.unwrap();
+
+        assert!(
+            first
+                .assign(&mut redis_conn, ROOM)
+                .await
+                .unwrap()
+                .unwrap()
+                .local
+        );
+
+        first_instance.withdraw(&mut redis_conn).await.unwrap();
+
+        assert!(
+            second
+                .assign(&mut redis_conn, ROOM)
+                .await
+                .unwrap()
+                .unwrap()
+                .local
+        );
+
+        // The first instance stops refreshing the room once it has been taken over
+        first_instance.refresh_rooms(&mut redis_conn).await.unwrap();
+        assert!(first_instance.rooms.lock().is_empty());
+
+        second_instance
+            .refresh_rooms(&mut redis_conn)
+            .await
+            .unwrap();
+        assert!(second_instance.rooms.lock().contains(&ROOM));
+
+        // Released rooms are forgotten as well
+        release_room(&mut redis_conn, ROOM).await.unwrap();
+        second_instance
+            .refresh_rooms(&mut redis_conn)
+            .await
+            .unwrap();
+        assert!(second_instance.rooms.lock().is_empty());
+
+        let exists: bool = redis_conn
+            .exists(RoomInstance { room_id: ROOM })
+            .await
+            .unwrap();
+        assert!(!exists);
+    }
+}
@@ -0,0 +1,341 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Snapshots of the redis state of a room
+//!
+//! A snapshot contains all redis keys of a room and its breakout rooms, used to debug incidents locally and for
+//! disaster recovery drills. The keys are read one after another, so a snapshot of a room with participants inside
+//! is not necessarily consistent. Values encrypted with `redis.encryption_key` can only be read by controllers using
+//! the same key.
+use crate::api::signaling::key_versions;
+use crate::api::signaling::ws_modules::control;
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use types::core::RoomId;
+
+/// Namespace of the keys which do not belong to a module
+const CORE_NAMESPACE: &str = control::NAMESPACE;
+
+/// The redis state of a room at the time the snapshot was taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomSnapshot {
+    pub room_id: RoomId,
+    pub created_at: DateTime<Utc>,
+    /// Key versions of the modules when the snapshot was taken
+    pub key_versions: BTreeMap<String, u32>,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// A single redis key of the room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub key: String,
+    /// Namespace of the module the key belongs to, `control` for keys of the controller core
+    pub namespace: String,
+    /// Remaining time to live in milliseconds, unset for keys without expiry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+    pub data: SnapshotData,
+}
+
+/// The value of a redis key by its type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+pub enum SnapshotData {
+    String(SnapshotValue),
+    List(Vec<SnapshotValue>),
+    Set(Vec<SnapshotValue>),
+    SortedSet(Vec<(SnapshotValue, f64)>),
+    Hash(Vec<(SnapshotValue, SnapshotValue)>),
+}
+
+/// A redis value, stored as text if it is valid UTF-8 and base64 encoded otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotValue {
+    Text(String),
+    Base64(String),
+}
+
+impl From<Vec<u8>> for SnapshotValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::Text(text),
+            Err(e) => Self::Base64(base64::encode(e.into_bytes())),
+        }
+    }
+}
+
+impl SnapshotValue {
+    fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            Self::Text(text) => Ok(text.into_bytes()),
+            Self::Base64(encoded) => base64::decode(encoded).context("Invalid base64 value"),
+        }
+    }
+}
+
+/// Take a snapshot of all redis keys of the room
+///
+/// `namespaces` are the namespaces of the registered modules, used to assign the keys to the modules.
+pub async fn take(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    namespaces: &[&str],
+) -> Result<RoomSnapshot> {
+    let created_at = Utc::now();
+    let keys = control::storage::get_room_keys(redis_conn, room_id).await?;
+
+    let mut entries = Vec::with_capacity(keys.len());
+
+    for key in keys {
+        let data = match read_data(redis_conn, &key).await? {
+            Some(data) => data,
+            // The key expired or has a type which is not used by the modules
+            None => continue,
+        };
+
+        let ttl: i64 = redis_conn.pttl(&key).await.context("Failed to get PTTL")?;
+
+        entries.push(SnapshotEntry {
+            namespace: namespace_of_key(&key, namespaces).into(),
+            ttl_ms: u64::try_from(ttl).ok(),
+            data,
+            key,
+        });
+    }
+
+    entries.sort_by(|a, b| (&a.namespace, &a.key).cmp(&(&b.namespace, &b.key)));
+
+    Ok(RoomSnapshot {
+        room_id,
+        created_at,
+        key_versions: key_versions::get_all(redis_conn).await?,
+        entries,
+    })
+}
+
+/// Returns true if the snapshot has been taken with the current key versions of the modules
+///
+/// Snapshots taken with other key versions must not be restored, the modules would misinterpret the keys.
+pub async fn has_current_key_versions(
+    redis_conn: &mut RedisConnection,
+    snapshot: &RoomSnapshot,
+) -> Result<bool> {
+    Ok(key_versions::get_all(redis_conn).await? == snapshot.key_versions)
+}
+
+/// Restore the snapshot into the room `room_id`, which may differ from the room the snapshot was taken of
+///
+/// All current redis keys of the room are replaced. Returns the number of restored keys.
+pub async fn restore(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    snapshot: RoomSnapshot,
+) -> Result<usize> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    let current_keys = control::storage::get_room_keys(redis_conn, room_id).await?;
+
+    if !current_keys.is_empty() {
+        pipe.del(current_keys).ignore();
+    }
+
+    let restored = snapshot.entries.len();
+
+    for entry in snapshot.entries {
+        let key = rebase_key(&entry.key, snapshot.room_id, room_id)?;
+
+        match entry.data {
+            SnapshotData::String(value) => {
+                pipe.set(&key, value.into_bytes()?).ignore();
+            }
+            SnapshotData::List(values) => {
+                pipe.rpush(&key, into_bytes(values)?).ignore();
+            }
+            SnapshotData::Set(values) => {
+                pipe.sadd(&key, into_bytes(values)?).ignore();
+            }
+            SnapshotData::SortedSet(values) => {
+                let items = values
+                    .into_iter()
+                    .map(|(member, score)| Ok((score, member.into_bytes()?)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                pipe.zadd_multiple(&key, &items).ignore();
+            }
+            SnapshotData::Hash(fields) => {
+                let items = fields
+                    .into_iter()
+                    .map(|(field, value)| Ok((field.into_bytes()?, value.into_bytes()?)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                pipe.hset_multiple(&key, &items).ignore();
+            }
+        }
+
+        if let Some(ttl_ms) = entry.ttl_ms {
+            pipe.pexpire(&key, ttl_ms.max(1) as usize).ignore();
+        }
+    }
+
+    pipe.query_async::<_, ()>(redis_conn)
+        .await
+        .context("Failed to restore the redis keys of the room")?;
+
+    Ok(restored)
+}
+
+async fn read_data(redis_conn: &mut RedisConnection, key: &str) -> Result<Option<SnapshotData>> {
+    let key_type: String = redis::cmd("TYPE")
+        .arg(key)
+        .query_async(redis_conn)
+        .await
+        .context("Failed to get TYPE of key")?;
+
+    let data = match key_type.as_str() {
+        "string" => {
+            let value: Option<Vec<u8>> = redis_conn.get(key).await.context("Failed to GET")?;
+
+            value.map(|value| SnapshotData::String(value.into()))
+        }
+        "list" => {
+            let values: Vec<Vec<u8>> = redis_conn
+                .lrange(key, 0, -1)
+                .await
+                .context("Failed to LRANGE")?;
+
+            Some(SnapshotData::List(from_bytes(values)))
+        }
+        "set" => {
+            let values: Vec<Vec<u8>> = redis_conn
+                .smembers(key)
+                .await
+                .context("Failed to SMEMBERS")?;
+
+            Some(SnapshotData::Set(from_bytes(values)))
+        }
+        "zset" => {
+            let values: Vec<(Vec<u8>, f64)> = redis_conn
+                .zrange_withscores(key, 0, -1)
+                .await
+                .context("Failed to ZRANGE")?;
+
+            Some(SnapshotData::SortedSet(
+                values
+                    .into_iter()
+                    .map(|(member, score)| (member.into(), score))
+                    .collect(),
+            ))
+        }
+        "hash" => {
+            let fields: Vec<(Vec<u8>, Vec<u8>)> =
+                redis_conn.hgetall(key).await.context("Failed to HGETALL")?;
+
+            Some(SnapshotData::Hash(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field.into(), value.into()))
+                    .collect(),
+            ))
+        }
+        "none" => None,
+        unknown => {
+            log::warn!("Skipping key {} of unsupported type {}", key, unknown);
+            None
+        }
+    };
+
+    Ok(data)
+}
+
+fn from_bytes(values: Vec<Vec<u8>>) -> Vec<SnapshotValue> {
+    values.into_iter().map(Into::into).collect()
+}
+
+fn into_bytes(values: Vec<SnapshotValue>) -> Result<Vec<Vec<u8>>> {
+    values.into_iter().map(SnapshotValue::into_bytes).collect()
+}
+
+/// Returns the namespace of the module the key belongs to
+///
+/// Keys contain the namespace of their module as a segment (e.g. `k3k-signaling:room={room}:chat:history`) or as
+/// `namespace={namespace}` segment.
+fn namespace_of_key<'n>(key: &str, namespaces: &[&'n str]) -> &'n str {
+    key.split(':')
+        .find_map(|segment| {
+            let segment = segment.strip_prefix("namespace=").unwrap_or(segment);
+
+            namespaces
+                .iter()
+                .find(|namespace| **namespace == segment)
+                .copied()
+        })
+        .unwrap_or(CORE_NAMESPACE)
+}
+
+/// Replace the room of the key taken of room `from` with room `to`
+fn rebase_key(key: &str, from: RoomId, to: RoomId) -> Result<String> {
+    let prefix = format!("k3k-signaling:room={from}");
+
+    match key.strip_prefix(&prefix) {
+        Some(rest) => Ok(format!("k3k-signaling:room={to}{rest}")),
+        None => bail!("Key {key} does not belong to room {from}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    #[test]
+    fn namespaces() {
+        let namespaces = ["chat", "media", "polls"];
+
+        assert_eq!(
+            namespace_of_key("k3k-signaling:room=abc:chat:history", &namespaces),
+            "chat"
+        );
+        assert_eq!(
+            namespace_of_key(
+                "k3k-signaling:room=abc:namespace=media:presenters",
+                &namespaces
+            ),
+            "media"
+        );
+        assert_eq!(
+            namespace_of_key("k3k-signaling:room=abc:participants", &namespaces),
+            "control"
+        );
+    }
+
+    #[test]
+    fn rebase() {
+        let from = RoomId::from(Uuid::from_u128(1));
+        let to = RoomId::from(Uuid::from_u128(2));
+
+        assert_eq!(
+            rebase_key(&format!("k3k-signaling:room={from}:chat:history"), from, to).unwrap(),
+            format!("k3k-signaling:room={to}:chat:history")
+        );
+        assert!(rebase_key(&format!("k3k-signaling:room={to}:chat:history"), from, to).is_err());
+    }
+
+    #[test]
+    fn values() {
+        let text = SnapshotValue::from(b"hello".to_vec());
+        let binary = SnapshotValue::from(vec![0xff, 0x00]);
+
+        assert_eq!(text, SnapshotValue::Text("hello".into()));
+        assert_eq!(binary, SnapshotValue::Base64("/wA=".into()));
+        assert_eq!(binary.into_bytes().unwrap(), vec![0xff, 0x00]);
+    }
+}
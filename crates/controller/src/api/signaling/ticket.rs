@@ -3,10 +3,16 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use super::resumption::{ResumptionData, ResumptionRedisKey};
+use crate::api::client_ip::client_ip;
+use crate::settings::TicketBinding;
 use crate::{api::v1::response::ApiError, prelude::*};
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
 use anyhow::Context;
+use cidr::IpInet;
 use redis::AsyncCommands;
 use redis_args::{FromRedisValue, ToRedisArgs};
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use types::core::{BreakoutRoomId, ParticipantId, ResumptionToken, RoomId, TicketToken, UserId};
 
@@ -17,6 +23,62 @@ pub struct TicketRedisKey<'s> {
     pub ticket: &'s str,
 }
 
+/// Typed redis key marking a signaling ticket as used, to detect replayed tickets
+#[derive(Debug, Copy, Clone, ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:ticket={ticket}:used")]
+pub struct UsedTicketRedisKey<'s> {
+    pub ticket: &'s str,
+}
+
+/// Hash of the properties of the client a ticket is bound to
+///
+/// Remembers the binding it was created with, so changing the binding setting does not invalidate issued tickets.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClientFingerprint {
+    binding: TicketBinding,
+    hash: String,
+}
+
+impl ClientFingerprint {
+    /// Create the fingerprint of the client sending the request, `None` if tickets are not bound to clients
+    pub fn of_request(
+        binding: TicketBinding,
+        request: &HttpRequest,
+        trusted_proxies: &[IpInet],
+    ) -> Option<Self> {
+        let ip = match binding {
+            TicketBinding::None => return None,
+            TicketBinding::UserAgent => None,
+            TicketBinding::IpAndUserAgent => client_ip(request, trusted_proxies),
+        };
+
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .map(|user_agent| user_agent.as_bytes())
+            .unwrap_or_default();
+
+        let mut context = digest::Context::new(&digest::SHA256);
+
+        if binding == TicketBinding::IpAndUserAgent {
+            context.update(ip.map(|ip| ip.to_string()).unwrap_or_default().as_bytes());
+            context.update(b"\n");
+        }
+
+        context.update(user_agent);
+
+        Some(Self {
+            binding,
+            hash: base64::encode_config(context.finish(), base64::URL_SAFE_NO_PAD),
+        })
+    }
+
+    /// Returns true if the request was sent by the client the fingerprint was created for
+    pub fn matches(&self, request: &HttpRequest, trusted_proxies: &[IpInet]) -> bool {
+        Self::of_request(self.binding, request, trusted_proxies).as_ref() == Some(self)
+    }
+}
+
 /// Data stored behind the [`Ticket`] key.
 #[derive(Debug, Clone, Deserialize, Serialize, ToRedisArgs, FromRedisValue)]
 #[to_redis_args(serde)]
@@ -28,14 +90,19 @@ pub struct TicketData {
     pub room: RoomId,
     pub breakout_room: Option<BreakoutRoomId>,
     pub resumption: ResumptionToken,
+    /// The ticket can only be used by a client with this fingerprint
+    #[serde(default)]
+    pub fingerprint: Option<ClientFingerprint>,
 }
 
+/// Create a single-use signaling ticket, bound to the client with the `fingerprint` if given
 pub async fn start_or_continue_signaling_session(
     redis_conn: &mut RedisConnection,
     participant: Participant<UserId>,
     room: RoomId,
     breakout_room: Option<BreakoutRoomId>,
     resumption: Option<ResumptionToken>,
+    fingerprint: Option<ClientFingerprint>,
 ) -> Result<(TicketToken, ResumptionToken), ApiError> {
     let mut resuming = false;
 
@@ -63,6 +130,7 @@ pub async fn start_or_continue_signaling_session(
         room,
         breakout_room,
         resumption: resumption.clone(),
+        fingerprint,
     };
 
     // let the ticket expire in 30 seconds
@@ -71,7 +139,7 @@ pub async fn start_or_continue_signaling_session(
             TicketRedisKey {
                 ticket: ticket.as_str(),
             },
-            &ticket_data,
+            Encrypted(&ticket_data),
             30,
         )
         .await
@@ -83,6 +151,26 @@ pub async fn start_or_continue_signaling_session(
     Ok((ticket, resumption))
 }
 
+/// Returns true if the resumption token can be used to continue a session of the participant in the room
+pub async fn is_valid_resumption_token(
+    redis_conn: &mut RedisConnection,
+    participant: Participant<UserId>,
+    room: RoomId,
+    token: &ResumptionToken,
+) -> Result<bool, ApiError> {
+    let resumption_data: Option<Encrypted<ResumptionData>> = redis_conn
+        .get(ResumptionRedisKey(token.clone()))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch resumption token from redis, {}", e);
+            ApiError::internal()
+        })?;
+
+    Ok(resumption_data
+        .map(|Encrypted(data)| data.room == room && data.participant == participant)
+        .unwrap_or_default())
+}
+
 async fn use_resumption_token(
     redis_conn: &mut RedisConnection,
     participant: Participant<UserId>,
@@ -92,13 +180,13 @@ async fn use_resumption_token(
     let resumption_redis_key = ResumptionRedisKey(token);
 
     // Check for resumption data behind resumption token
-    let resumption_data: Option<ResumptionData> =
+    let resumption_data: Option<Encrypted<ResumptionData>> =
         redis_conn.get(&resumption_redis_key).await.map_err(|e| {
             log::error!("Failed to fetch resumption token from redis, {}", e);
             ApiError::internal()
         })?;
 
-    let data = if let Some(data) = resumption_data {
+    let data = if let Some(Encrypted(data)) = resumption_data {
         data
     } else {
         return Ok(None);
@@ -127,3 +215,78 @@ async fn use_resumption_token(
         Err(ApiError::internal())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use std::net::SocketAddr;
+
+    fn request(peer_addr: &str, user_agent: &str) -> HttpRequest {
+        TestRequest::default()
+            .peer_addr(peer_addr.parse::<SocketAddr>().unwrap())
+            .insert_header((USER_AGENT, user_agent))
+            .insert_header(("x-forwarded-for", "198.51.100.7"))
+            .to_http_request()
+    }
+
+    #[test]
+    fn unbound_tickets_have_no_fingerprint() {
+        let request = request("192.0.2.1:4000", "Firefox");
+
+        assert_eq!(
+            ClientFingerprint::of_request(TicketBinding::None, &request, &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn fingerprint_keeps_the_binding_it_was_created_with() {
+        let fingerprint = ClientFingerprint::of_request(
+            TicketBinding::IpAndUserAgent,
+            &request("192.0.2.1:4000", "Firefox"),
+            &[],
+        )
+        .unwrap();
+
+        // Another port of the same address is the same client
+        assert!(fingerprint.matches(&request("192.0.2.1:5000", "Firefox"), &[]));
+        assert!(!fingerprint.matches(&request("192.0.2.2:4000", "Firefox"), &[]));
+        assert!(!fingerprint.matches(&request("192.0.2.1:4000", "Chrome"), &[]));
+
+        let fingerprint = ClientFingerprint::of_request(
+            TicketBinding::UserAgent,
+            &request("192.0.2.1:4000", "Firefox"),
+            &[],
+        )
+        .unwrap();
+
+        assert!(fingerprint.matches(&request("192.0.2.2:4000", "Firefox"), &[]));
+        assert!(!fingerprint.matches(&request("192.0.2.1:4000", "Chrome"), &[]));
+    }
+
+    #[test]
+    fn fingerprint_uses_the_forwarded_address_of_trusted_proxies() {
+        let trusted_proxies = ["192.0.2.0/24".parse().unwrap()];
+
+        let fingerprint = ClientFingerprint::of_request(
+            TicketBinding::IpAndUserAgent,
+            &request("192.0.2.1:4000", "Firefox"),
+            &trusted_proxies,
+        )
+        .unwrap();
+
+        // The same client forwarded by another proxy
+        assert!(fingerprint.matches(&request("192.0.2.2:4000", "Firefox"), &trusted_proxies));
+
+        // Without trusted proxies the forwarded address is ignored
+        let fingerprint = ClientFingerprint::of_request(
+            TicketBinding::IpAndUserAgent,
+            &request("192.0.2.1:4000", "Firefox"),
+            &[],
+        )
+        .unwrap();
+
+        assert!(!fingerprint.matches(&request("192.0.2.2:4000", "Firefox"), &[]));
+    }
+}
@@ -2,12 +2,14 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::api::signaling::metrics::SignalingMetrics;
 use actix::{Actor, ActorContext, AsyncContext, Handler, StreamHandler};
 use actix_http::ws::{CloseCode, CloseReason, Item, ProtocolError};
 use actix_web_actors::ws::{Message, WebsocketContext};
 use bytes::BytesMut;
 use bytestring::ByteString;
 use std::convert::TryFrom;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc::UnboundedSender;
 
@@ -21,6 +23,9 @@ pub struct WebSocketActor {
     /// Sender to signaling runner
     sender: UnboundedSender<Message>,
 
+    /// Tracks the number of messages queued for the runner
+    metrics: Arc<SignalingMetrics>,
+
     /// Timestamp of last pong received
     last_pong: Instant,
 
@@ -34,9 +39,10 @@ struct Continuation {
 }
 
 impl WebSocketActor {
-    pub fn new(sender: UnboundedSender<Message>) -> Self {
+    pub fn new(sender: UnboundedSender<Message>, metrics: Arc<SignalingMetrics>) -> Self {
         Self {
             sender,
+            metrics,
             last_pong: Instant::now(),
             continuation: None,
         }
@@ -51,6 +57,8 @@ impl WebSocketActor {
                 code: CloseCode::Abnormal,
                 description: None,
             }));
+        } else {
+            self.metrics.increment_ws_queue_depth();
         }
     }
 
@@ -133,7 +141,12 @@ impl Actor for WebSocketActor {
 impl StreamHandler<Result<Message, ProtocolError>> for WebSocketActor {
     fn handle(&mut self, msg: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
         match msg {
-            Ok(Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(Message::Ping(msg)) => {
+                ctx.pong(&msg);
+
+                // Let the runner know that the client is still active
+                self.forward_to_runner(ctx, Message::Ping(msg));
+            }
             Ok(Message::Pong(msg)) => {
                 if msg == b"heartbeat"[..] {
                     self.last_pong = Instant::now();
@@ -0,0 +1,108 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Typed events exchanged between the modules of a participant
+//!
+//! Modules subscribe to the events of other modules in their `init` function with
+//! [`InitContext::subscribe`](super::InitContext::subscribe) and publish events with
+//! [`ModuleContext::publish`](super::ModuleContext::publish). Events are delivered to the subscribed modules of the
+//! same participant as `Event::Ext`, after the handler of the publishing module returned.
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::Stream;
+
+/// Event which can be published on the [`ModuleBus`]
+///
+/// Implemented for all types which are `Clone + 'static`. Modules should still define dedicated types for the
+/// events they publish, as subscriptions are made by type.
+pub trait BusEvent: Any + Clone {}
+
+impl<T> BusEvent for T where T: Any + Clone {}
+
+type Subscriber = Box<dyn Fn(&dyn Any)>;
+
+/// Event bus connecting the modules of a single participant
+#[derive(Default)]
+pub struct ModuleBus {
+    subscribers: HashMap<TypeId, Vec<Subscriber>>,
+}
+
+impl ModuleBus {
+    /// Subscribe to all events of type `E`, returning a stream of the events mapped with `map`
+    pub(super) fn subscribe<E, T, F>(&mut self, map: F) -> impl Stream<Item = T>
+    where
+        E: BusEvent,
+        T: 'static,
+        F: Fn(E) -> T + 'static,
+    {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        self.subscribers
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(Box::new(move |event| {
+                if let Some(event) = event.downcast_ref::<E>() {
+                    // The module has been destroyed if the receiver is gone
+                    let _ = sender.send(map(event.clone()));
+                }
+            }));
+
+        UnboundedReceiverStream::new(receiver)
+    }
+
+    /// Send the event to all subscribers of its type
+    pub(super) fn publish<E>(&self, event: E)
+    where
+        E: BusEvent,
+    {
+        if let Some(subscribers) = self.subscribers.get(&TypeId::of::<E>()) {
+            for subscriber in subscribers {
+                subscriber(&event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio_stream::StreamExt;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct TimerExpired(u32);
+
+    #[derive(Debug, Clone)]
+    struct RecordingStarted;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum ExtEvent {
+        TimerExpired(u32),
+    }
+
+    #[tokio::test]
+    async fn delivers_events_by_type() {
+        let mut bus = ModuleBus::default();
+
+        let mut first = Box::pin(bus.subscribe(|TimerExpired(id)| ExtEvent::TimerExpired(id)));
+        let mut second = Box::pin(bus.subscribe(|event: TimerExpired| event));
+
+        bus.publish(RecordingStarted);
+        bus.publish(TimerExpired(1));
+
+        assert_eq!(first.next().await, Some(ExtEvent::TimerExpired(1)));
+        assert_eq!(second.next().await, Some(TimerExpired(1)));
+    }
+
+    #[test]
+    fn ignores_dropped_subscribers() {
+        let mut bus = ModuleBus::default();
+
+        drop(bus.subscribe(|event: TimerExpired| event));
+
+        bus.publish(TimerExpired(1));
+    }
+}
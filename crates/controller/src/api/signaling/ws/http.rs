@@ -3,32 +3,50 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use super::modules::{ModuleBuilder, ModuleBuilderImpl};
+use super::protocol;
 use super::runner::Runner;
-use super::SignalingModule;
+use super::{NamespacedEvent, SignalingModule, Timestamp};
+use crate::api::signaling::key_versions;
 use crate::api::signaling::metrics::SignalingMetrics;
 use crate::api::signaling::resumption::{ResumptionData, ResumptionTokenKeepAlive};
-use crate::api::signaling::ticket::{TicketData, TicketRedisKey};
-use crate::api::signaling::ws::actor::WebSocketActor;
+use crate::api::signaling::sharding::RoomAffinity;
+use crate::api::signaling::ticket::{TicketData, TicketRedisKey, UsedTicketRedisKey};
+use crate::api::signaling::ws::actor::{WebSocketActor, WsCommand};
+use crate::api::signaling::ws_modules::control;
+use crate::api::signaling::SignalingRoomId;
 use crate::api::v1::response::ApiError;
 use crate::api::Participant;
+use crate::redis_encryption::Encrypted;
 use crate::redis_wrapper::RedisConnection;
+use crate::services::{error_reporting, ErrorReportingService, NotificationService};
 use crate::settings::SharedSettingsActix;
 use crate::storage::ObjectStorage;
+use actix::Addr;
+use actix_http::ws::{CloseCode, CloseReason};
 use actix_web::http::header;
 use actix_web::web::Data;
 use actix_web::{get, HttpMessage};
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use anyhow::{bail, Context, Result};
+use cidr::IpInet;
 use database::Db;
+use db_storage::room_owners::RoomOwner;
 use db_storage::rooms::Room;
 use db_storage::users::User;
 use kustos::Authz;
 use lapin_pool::RabbitMqPool;
+use redis::AsyncCommands;
+use std::collections::BTreeMap;
 use std::marker::PhantomData;
 use std::time::Instant;
 use tokio::sync::{broadcast, mpsc};
 use tokio::task;
 use tracing_actix_web::RequestId;
+use types::core::UserId;
+
+/// Time in seconds a used ticket is remembered, longer than a ticket is valid
+const USED_TICKET_EXPIRY: usize = 60;
 
 #[derive(Default)]
 pub struct SignalingModules(Vec<Box<dyn ModuleBuilder>>);
@@ -47,13 +65,140 @@ impl SignalingModules {
     pub fn get_module_names(&self) -> Vec<&'static str> {
         self.0.iter().map(|m| m.namespace()).collect()
     }
+
+    /// Let all modules release the state of an empty room which is about to be destroyed
+    pub(crate) async fn cleanup_empty_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) {
+        for module in &self.0 {
+            if let Err(e) = module.cleanup_empty_room(redis_conn, room).await {
+                log::error!(
+                    "Module {} failed to clean up empty room {}, {:?}",
+                    module.namespace(),
+                    room,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Let all modules provision the resources of a room ahead of a scheduled meeting
+    pub(crate) async fn prepare_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) {
+        for module in &self.0 {
+            if let Err(e) = module.prepare_room(redis_conn, room).await {
+                log::error!(
+                    "Module {} failed to prepare room {}, {:?}",
+                    module.namespace(),
+                    room,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Migrate the redis keys of all modules to the key versions of this controller
+    ///
+    /// Modules whose keys have been migrated to a newer version by a controller of a later release are left untouched.
+    pub(crate) async fn migrate_keys(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        let mut mutex = key_versions::migration_mutex();
+
+        let guard = match mutex.lock(redis_conn).await {
+            Ok(guard) => guard,
+            Err(r3dlock::Error::Redis(e)) => bail!("Failed to acquire r3dlock, {}", e),
+            Err(r3dlock::Error::CouldNotAcquireLock) => {
+                bail!("Failed to acquire r3dlock, another controller is still migrating")
+            }
+            Err(r3dlock::Error::FailedToUnlock | r3dlock::Error::AlreadyExpired) => {
+                unreachable!()
+            }
+        };
+
+        let result = self.migrate_keys_locked(redis_conn).await;
+
+        if let Err(e) = guard.unlock(redis_conn).await {
+            log::error!("Failed to unlock the key versions, {}", e);
+        }
+
+        result
+    }
+
+    async fn migrate_keys_locked(&self, redis_conn: &mut RedisConnection) -> Result<()> {
+        for module in &self.0 {
+            let namespace = module.namespace();
+            let version = module.key_version();
+            let recorded = key_versions::get(redis_conn, namespace).await?;
+
+            if recorded > version {
+                log::warn!(
+                    "Keys of module {} have version {}, this controller uses version {}",
+                    namespace,
+                    recorded,
+                    version
+                );
+                continue;
+            }
+
+            if recorded < version {
+                log::info!(
+                    "Migrating keys of module {} from version {} to {}",
+                    namespace,
+                    recorded,
+                    version
+                );
+
+                module
+                    .migrate_keys(redis_conn, recorded)
+                    .await
+                    .with_context(|| format!("Failed to migrate keys of module {namespace}"))?;
+            }
+
+            key_versions::set(redis_conn, namespace, version).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Collect the statistics of all modules about a running room, keyed by the namespace of the module
+    pub(crate) async fn room_stats(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> BTreeMap<&'static str, serde_json::Value> {
+        let mut stats = BTreeMap::new();
+
+        for module in &self.0 {
+            match module.room_stats(redis_conn, room).await {
+                Ok(Some(module_stats)) => {
+                    stats.insert(module.namespace(), module_stats);
+                }
+                Ok(None) => {}
+                Err(e) => log::error!(
+                    "Module {} failed to collect the stats of room {}, {:?}",
+                    module.namespace(),
+                    room,
+                    e
+                ),
+            }
+        }
+
+        stats
+    }
 }
 
+/// Websocket subprotocols supported by the signaling endpoint
+///
+/// Each protocol is a version of the JSON signaling protocol, see [`ProtocolVersion`](super::ProtocolVersion).
 pub struct SignalingProtocols(&'static [&'static str]);
 
 impl SignalingProtocols {
     pub fn data() -> Data<Self> {
-        Data::new(Self(&["k3k-signaling-json-v1.0"]))
+        Data::new(Self(protocol::SUPPORTED_PROTOCOLS))
     }
 }
 
@@ -67,8 +212,11 @@ pub(crate) async fn ws_service(
     redis_conn: Data<RedisConnection>,
     rabbitmq_pool: Data<RabbitMqPool>,
     metrics: Data<SignalingMetrics>,
+    notifications: Data<NotificationService>,
+    error_reporting: Data<ErrorReportingService>,
     protocols: Data<SignalingProtocols>,
     modules: Data<SignalingModules>,
+    room_affinity: Data<RoomAffinity>,
     request: HttpRequest,
     stream: web::Payload,
     settings: SharedSettingsActix,
@@ -85,11 +233,50 @@ pub(crate) async fn ws_service(
     // Read ticket and protocol from protocol header
     let (ticket, protocol) = read_request_header(&request, protocols.0)?;
 
-    // Read ticket data from redis
-    let ticket_data = get_ticket_data_from_redis(&mut redis_conn, ticket).await?;
+    // Send the participant to the controller instance the room is assigned to, the ticket stays valid.
+    // Browsers do not follow redirects of websocket handshakes, so the handshake is finished to tell the participant
+    // about the other instance.
+    if let Some(signaling_url) =
+        get_remote_instance_of_ticket(&mut redis_conn, &room_affinity, ticket).await?
+    {
+        let (sender, _) = mpsc::unbounded_channel();
+        let (addr, response) = ws::WsResponseBuilder::new(
+            WebSocketActor::new(sender, metrics.clone().into_inner()),
+            &request,
+            stream,
+        )
+        .protocols(&[protocol])
+        .start_with_addr()?;
+
+        redirect_to_instance(&addr, signaling_url);
 
-    // Get user & room from database using the ticket data
-    let (participant, room) = get_user_and_room_from_ticket_data(db.clone(), &ticket_data).await?;
+        return Ok(response);
+    }
+
+    // Read ticket data from redis
+    let trusted_proxies = settings.load().http.trusted_proxies.clone();
+    let ticket_data = get_ticket_data_from_redis(
+        &mut redis_conn,
+        ticket,
+        &trusted_proxies,
+        &request,
+        &metrics,
+    )
+    .await?;
+
+    // Get user, room and room owners from database using the ticket data
+    let (participant, room, room_owners) =
+        get_user_and_room_from_ticket_data(db.clone(), &ticket_data).await?;
+
+    // Bots only get the modules enabled for them in the settings
+    let bot_modules = matches!(participant, Participant::Bot).then(|| {
+        settings
+            .load()
+            .bots
+            .as_ref()
+            .map(|bots| bots.modules.clone())
+            .unwrap_or_default()
+    });
 
     // Create resumption data to be refreshed by the runner in redis
     let resumption_data = ResumptionData {
@@ -99,6 +286,7 @@ pub(crate) async fn ws_service(
             Participant::Guest => Participant::Guest,
             Participant::Sip => Participant::Sip,
             Participant::Recorder => Participant::Recorder,
+            Participant::Bot => Participant::Bot,
         },
         room: ticket_data.room,
         breakout_room: ticket_data.breakout_room,
@@ -110,10 +298,13 @@ pub(crate) async fn ws_service(
 
     // Finish websocket handshake
     let (sender, recv) = mpsc::unbounded_channel();
-    let (addr, response) =
-        ws::WsResponseBuilder::new(WebSocketActor::new(sender), &request, stream)
-            .protocols(protocols.0)
-            .start_with_addr()?;
+    let (addr, response) = ws::WsResponseBuilder::new(
+        WebSocketActor::new(sender, metrics.clone().into_inner()),
+        &request,
+        stream,
+    )
+    .protocols(&[protocol])
+    .start_with_addr()?;
 
     let rabbitmq_channel = match rabbitmq_pool.create_channel().await {
         Ok(rabbitmq_channel) => rabbitmq_channel,
@@ -128,10 +319,13 @@ pub(crate) async fn ws_service(
         ticket_data.participant_id,
         ticket_data.resuming,
         room,
+        room_owners,
         ticket_data.breakout_room,
         participant,
         protocol,
         metrics.clone().into_inner(),
+        notifications.get_ref().clone(),
+        error_reporting.get_ref().clone(),
         db.into_inner(),
         storage.into_inner(),
         authz.into_inner(),
@@ -140,11 +334,29 @@ pub(crate) async fn ws_service(
         resumption_keep_alive,
     );
 
+    // The region of the participant is determined by the load balancer in front of the controller
+    if let Some(region_header) = &settings.load().rooms.region_header {
+        if let Some(region) = request
+            .headers()
+            .get(region_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .filter(|region| !region.is_empty())
+        {
+            builder.set_region(region.to_owned());
+        }
+    }
+
     let startup_start_time = Instant::now();
 
     // add all modules
     for module in modules.0.iter() {
-        if let Err(e) = module.build(&mut builder).await {
+        if let Some(bot_modules) = &bot_modules {
+            if !bot_modules.iter().any(|name| name == module.namespace()) {
+                continue;
+            }
+        }
+
+        if let Err(e) = builder.build_module(module.as_ref()).await {
             log::error!("Failed to initialize module, {:?}", e);
 
             metrics.record_startup_time(startup_start_time.elapsed().as_secs_f64(), false);
@@ -174,8 +386,11 @@ pub(crate) async fn ws_service(
         }
     };
 
-    // Spawn the runner task
-    task::spawn_local(runner.run());
+    // Spawn the runner task, reports of its errors are enriched with its context
+    task::spawn_local(error_reporting::scope(
+        error_reporting.max_breadcrumbs(),
+        runner.run(),
+    ));
 
     metrics.record_startup_time(startup_start_time.elapsed().as_secs_f64(), true);
 
@@ -186,7 +401,7 @@ fn read_request_header<'t>(
     request: &'t HttpRequest,
     allowed_protocols: &'static [&'static str],
 ) -> Result<(TicketRedisKey<'t>, &'static str), ApiError> {
-    let mut protocol = None;
+    let mut offered_protocols = vec![];
     let mut ticket = None;
 
     // read the SEC_WEBSOCKET_PROTOCOL header
@@ -201,17 +416,14 @@ fn read_request_header<'t>(
             if value.starts_with("ticket#") {
                 let (_, tmp_ticket) = value.split_once('#').unwrap();
                 ticket = Some(tmp_ticket);
-                continue;
-            } else if protocol.is_some() {
-                continue;
-            } else if let Some(value) = allowed_protocols.iter().find(|&&v| v == value) {
-                protocol = Some(value);
+            } else {
+                offered_protocols.push(value);
             }
         }
     }
 
     // look if valid protocol exists
-    let protocol = match protocol {
+    let protocol = match protocol::negotiate(allowed_protocols, offered_protocols) {
         Some(protocol) => protocol,
         None => {
             log::debug!("Rejecting websocket request, missing valid protocol");
@@ -249,13 +461,32 @@ fn read_request_header<'t>(
     Ok((TicketRedisKey { ticket }, protocol))
 }
 
+/// Consume the ticket, it must be used by the client it is bound to
 async fn get_ticket_data_from_redis(
     redis_conn: &mut RedisConnection,
     ticket: TicketRedisKey<'_>,
+    trusted_proxies: &[IpInet],
+    request: &HttpRequest,
+    metrics: &SignalingMetrics,
 ) -> Result<TicketData, ApiError> {
+    let invalid_ticket = || {
+        ApiError::unauthorized()
+            .with_code("invalid_ticket")
+            .with_message(
+            "Invalid or expired ticket. Please request a new ticket from /v1/rooms/<room_id>/start",
+        )
+    };
+
+    let used_ticket = UsedTicketRedisKey {
+        ticket: ticket.ticket,
+    };
+
     // GETDEL available since redis 6.2.0, missing direct support by redis crate
-    let ticket_data: Option<TicketData> = redis::cmd("GETDEL")
+    let (ticket_data, used): (Option<Encrypted<TicketData>>, bool) = redis::pipe()
+        .atomic()
+        .cmd("GETDEL")
         .arg(ticket)
+        .exists(used_ticket)
         .query_async(redis_conn)
         .await
         .map_err(|e| {
@@ -263,21 +494,98 @@ async fn get_ticket_data_from_redis(
             ApiError::internal()
         })?;
 
-    let ticket_data = ticket_data.ok_or_else(|| {
-        ApiError::unauthorized()
-            .with_code("invalid_ticket")
-            .with_message(
-            "Invalid or expired ticket. Please request a new ticket from /v1/rooms/<room_id>/start",
-        )
-    })?;
+    let ticket_data = match ticket_data {
+        Some(ticket_data) => ticket_data.into_inner(),
+        None if used => {
+            log::warn!("Rejecting websocket request, ticket was already used");
+            metrics.increment_ticket_rejections_count("replayed");
+            return Err(invalid_ticket());
+        }
+        None => {
+            metrics.increment_ticket_rejections_count("invalid");
+            return Err(invalid_ticket());
+        }
+    };
+
+    // Remember the ticket for longer than it is valid to tell replayed tickets apart from expired ones
+    redis_conn
+        .set_ex(used_ticket, true, USED_TICKET_EXPIRY)
+        .await
+        .map_err(|e| {
+            log::warn!("Unable to mark ticket as used in redis: {}", e);
+            ApiError::internal()
+        })?;
+
+    if let Some(fingerprint) = &ticket_data.fingerprint {
+        if !fingerprint.matches(request, trusted_proxies) {
+            log::warn!("Rejecting websocket request, ticket was requested by another client");
+            metrics.increment_ticket_rejections_count("fingerprint_mismatch");
+            return Err(invalid_ticket());
+        }
+    }
 
     Ok(ticket_data)
 }
 
+/// Returns the signaling URL of the controller instance the ticket's room is assigned to, if it is not this instance
+///
+/// Does not consume the ticket, so it can be used with the other instance.
+async fn get_remote_instance_of_ticket(
+    redis_conn: &mut RedisConnection,
+    room_affinity: &RoomAffinity,
+    ticket: TicketRedisKey<'_>,
+) -> Result<Option<String>, ApiError> {
+    if !room_affinity.is_enabled() {
+        return Ok(None);
+    }
+
+    let ticket_data: Option<Encrypted<TicketData>> = redis_conn.get(ticket).await.map_err(|e| {
+        log::warn!("Unable to get ticket data in redis: {}", e);
+        ApiError::internal()
+    })?;
+
+    // Invalid tickets are rejected when they are consumed
+    let ticket_data = match ticket_data {
+        Some(ticket_data) => ticket_data.into_inner(),
+        None => return Ok(None),
+    };
+
+    let assignment = room_affinity
+        .assign(redis_conn, ticket_data.room)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Unable to assign the room to a controller instance, {:?}",
+                e
+            );
+            ApiError::internal()
+        })?;
+
+    Ok(assignment
+        .filter(|assignment| !assignment.local)
+        .map(|assignment| assignment.signaling_url))
+}
+
+/// Send the signaling URL of the instance the room is assigned to and close the websocket
+fn redirect_to_instance(addr: &Addr<WebSocketActor>, signaling_url: String) {
+    let redirect = serde_json::to_string(&NamespacedEvent {
+        namespace: control::NAMESPACE,
+        timestamp: Timestamp::now(),
+        payload: control::outgoing::Message::Redirect { signaling_url },
+    })
+    .expect("Failed to convert namespaced to json");
+
+    addr.do_send(WsCommand::Ws(ws::Message::Text(redirect.into())));
+    addr.do_send(WsCommand::Close(CloseReason {
+        code: CloseCode::Normal,
+        description: Some("redirect".into()),
+    }));
+}
+
 async fn get_user_and_room_from_ticket_data(
     db: Data<Db>,
     ticket_data: &TicketData,
-) -> Result<(Participant<User>, Room), ApiError> {
+) -> Result<(Participant<User>, Room, Vec<UserId>), ApiError> {
     let participant = ticket_data.participant;
     let room_id = ticket_data.room;
 
@@ -293,11 +601,13 @@ async fn get_user_and_room_from_ticket_data(
             Participant::Guest => Participant::Guest,
             Participant::Sip => Participant::Sip,
             Participant::Recorder => Participant::Recorder,
+            Participant::Bot => Participant::Bot,
         };
 
         let room = Room::get(&mut conn, room_id)?;
+        let room_owners = RoomOwner::get_ids_for_room(&mut conn, room_id)?;
 
-        Ok((participant, room))
+        Ok((participant, room, room_owners))
     })
     .await?
 }
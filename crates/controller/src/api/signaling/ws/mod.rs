@@ -10,7 +10,8 @@ use crate::api::Participant;
 use crate::redis_wrapper::RedisConnection;
 use crate::storage::ObjectStorage;
 use actix_http::ws::CloseCode;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use bus::ModuleBus;
 use database::Db;
 use db_storage::rooms::Room;
 use db_storage::users::User;
@@ -19,6 +20,7 @@ use kustos::Authz;
 use lapin::options::{ExchangeDeclareOptions, QueueBindOptions};
 use lapin::ExchangeKind;
 use modules::{any_stream, AnyStream};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -31,16 +33,25 @@ use types::{
 };
 
 mod actor;
+mod bus;
 mod echo;
 mod http;
 pub mod module_tester;
 mod modules;
+mod protocol;
+pub mod replay;
 mod runner;
+mod schema;
+mod shared_payload;
 
+pub use bus::BusEvent;
 pub use echo::Echo;
 pub use http::ws_service;
 pub use http::SignalingModules;
 pub use http::SignalingProtocols;
+pub use protocol::ProtocolVersion;
+pub use schema::SignalingSchemas;
+pub use shared_payload::SharedPayload;
 
 /// Event passed to [`SignalingModule::on_event`]
 pub enum Event<'evt, M>
@@ -109,12 +120,14 @@ where
     breakout_room: Option<BreakoutRoomId>,
     participant: &'ctx Participant<User>,
     role: Role,
+    region: Option<&'ctx str>,
     db: &'ctx Arc<Db>,
     storage: &'ctx Arc<ObjectStorage>,
     authz: &'ctx Arc<Authz>,
     rabbitmq_exchanges: &'ctx mut Vec<RabbitMqExchange>,
     rabbitmq_bindings: &'ctx mut Vec<RabbitMqBinding>,
     events: &'ctx mut SelectAll<AnyStream>,
+    bus: &'ctx mut ModuleBus,
     redis_conn: &'ctx mut RedisConnection,
     m: PhantomData<fn() -> M>,
 }
@@ -125,6 +138,7 @@ struct RabbitMqExchange {
     options: ExchangeDeclareOptions,
 }
 
+#[derive(Clone)]
 struct RabbitMqBinding {
     routing_key: String,
     exchange: String,
@@ -169,6 +183,11 @@ where
         self.role
     }
 
+    /// Returns the region of the participant if it has been provided by the load balancer
+    pub fn region(&self) -> Option<&str> {
+        self.region
+    }
+
     /// Returns a reference to the controllers database interface
     pub fn db(&self) -> &Arc<Db> {
         self.db
@@ -223,6 +242,19 @@ where
     {
         self.events.push(any_stream(M::NAMESPACE, stream));
     }
+
+    /// Subscribe to the events of type `E` published by other modules of the participant
+    ///
+    /// The events are converted with `map` and received as [`Event::Ext`].
+    pub fn subscribe<E, F>(&mut self, map: F)
+    where
+        E: BusEvent,
+        F: Fn(E) -> M::ExtEvent + 'static,
+        M::ExtEvent: 'static,
+    {
+        let stream = self.bus.subscribe(map);
+        self.events.push(any_stream(M::NAMESPACE, stream));
+    }
 }
 
 /// Context passed to the module
@@ -233,22 +265,33 @@ where
     M: SignalingModule,
 {
     role: Role,
-    ws_messages: &'ctx mut Vec<NamespacedEvent<'static, M::Outgoing>>,
+    ws_messages: &'ctx mut Vec<QueuedWsMessage<M::Outgoing>>,
     timestamp: Timestamp,
     rabbitmq_publish: &'ctx mut Vec<RabbitMqPublish>,
     redis_conn: &'ctx mut RedisConnection,
     events: &'ctx mut SelectAll<AnyStream>,
+    bus: &'ctx ModuleBus,
     invalidate_data: &'ctx mut bool,
     exit: &'ctx mut Option<CloseCode>,
     metrics: Option<Arc<SignalingMetrics>>,
     m: PhantomData<fn() -> M>,
 }
 
+/// Websocket message queued by a module
+enum QueuedWsMessage<O> {
+    /// Message serialized for the protocol version of the participant
+    Event(NamespacedEvent<'static, O>),
+    /// Message which has already been serialized
+    Shared(SharedPayload),
+}
+
 #[derive(Debug, Clone)]
 struct RabbitMqPublish {
     exchange: Option<String>,
     routing_key: String,
     message: String,
+    /// The message is a [`SharedPayload`] forwarded to the websocket by the receiving runners
+    shared: bool,
 }
 
 impl<M> ModuleContext<'_, M>
@@ -267,11 +310,20 @@ where
 
     /// Similar to `ws_send` but sets an explicit timestamp
     pub fn ws_send_overwrite_timestamp(&mut self, message: M::Outgoing, timestamp: Timestamp) {
-        self.ws_messages.push(NamespacedEvent {
-            namespace: M::NAMESPACE,
-            timestamp,
-            payload: message,
-        });
+        self.ws_messages
+            .push(QueuedWsMessage::Event(NamespacedEvent {
+                namespace: M::NAMESPACE,
+                timestamp,
+                payload: message,
+            }));
+    }
+
+    /// Queue a pre-serialized message to be sent via the websocket
+    /// after exiting the `on_event` function
+    ///
+    /// The payload is sent as is, see [`SharedPayload::new`].
+    pub fn ws_send_raw(&mut self, payload: SharedPayload) {
+        self.ws_messages.push(QueuedWsMessage::Shared(payload));
     }
 
     /// Queue a outgoing message to be sent via rabbitmq
@@ -302,6 +354,27 @@ where
             exchange,
             routing_key,
             message: serde_json::to_string(&message).expect("value must be serializable to json"),
+            shared: false,
+        });
+    }
+
+    /// Queue a outgoing message to be sent via rabbitmq and forwarded to the websocket of every receiving participant
+    ///
+    /// The message is serialized once per protocol version as [`SharedPayload`]. Receiving runners send it without
+    /// passing it to their modules, so it must be the same for every recipient.
+    pub fn rabbitmq_publish_shared(
+        &mut self,
+        exchange: String,
+        routing_key: String,
+        message: M::Outgoing,
+    ) {
+        let payload = SharedPayload::new::<M>(self.timestamp, &message);
+
+        self.rabbitmq_publish.push(RabbitMqPublish {
+            exchange: Some(exchange),
+            routing_key,
+            message: payload.to_delivery(),
+            shared: true,
         });
     }
 
@@ -337,6 +410,16 @@ where
         self.events.push(any_stream(M::NAMESPACE, stream));
     }
 
+    /// Publish an event to the modules of the participant which subscribed to its type
+    ///
+    /// See [`InitContext::subscribe`].
+    pub fn publish<E>(&self, event: E)
+    where
+        E: BusEvent,
+    {
+        self.bus.publish(event);
+    }
+
     /// Signals that the data related to the participant has changed
     pub fn invalidate_data(&mut self) {
         *self.invalidate_data = true;
@@ -382,16 +465,35 @@ pub trait SignalingModule: Sized + 'static {
     /// Must be unique between all registered modules.
     const NAMESPACE: &'static str;
 
+    /// Version of the format of the module's redis keys and values
+    ///
+    /// Must be increased whenever the keys or stored values change incompatibly, see [`key_versions`].
+    ///
+    /// [`key_versions`]: crate::api::signaling::key_versions
+    const KEY_VERSION: u32 = 1;
+
+    /// The [`PeerFrontendData`](Self::PeerFrontendData) only depends on the state of the peer
+    ///
+    /// If set, the peer frontend data fetched for a joining participant is cached in the participant snapshot of the
+    /// room and handed to other joining participants without asking the module again, see `ControlData::snapshot`.
+    /// Every change of the data must be followed by [`ModuleContext::invalidate_data`], which discards the cached
+    /// entry of the participant.
+    const CACHEABLE_PEER_FRONTEND_DATA: bool = false;
+
     /// The module params, can be any type that is `Clone` + `Send` + `Sync`
     ///
     /// Will get passed to `init` as parameter
     type Params: Clone + Send + Sync;
 
     /// The websocket incoming message type
-    type Incoming: for<'de> Deserialize<'de>;
+    ///
+    /// Its JSON schema is part of the schemas exported by [`SignalingSchemas`]
+    type Incoming: for<'de> Deserialize<'de> + JsonSchema;
 
     /// The websocket outgoing message type
-    type Outgoing: Serialize + PartialEq + Debug;
+    ///
+    /// Its JSON schema is part of the schemas exported by [`SignalingSchemas`]
+    type Outgoing: Serialize + PartialEq + Debug + JsonSchema;
 
     /// Message type sent over rabbitmq to other participant's modules
     type RabbitMqMessage: for<'de> Deserialize<'de> + Serialize;
@@ -428,4 +530,96 @@ pub trait SignalingModule: Sized + 'static {
 
     /// Before dropping the module this function will be called
     async fn on_destroy(self, ctx: DestroyContext<'_>);
+
+    /// Release the state of a room which was destroyed after staying empty for the configured grace period
+    ///
+    /// Such rooms are destroyed by the controller without any module instance, so `on_destroy` is never called with
+    /// `destroy_room` set for them. Modules holding resources outside of the room's redis keys must release them here,
+    /// all redis keys of the room are removed afterwards.
+    async fn cleanup_empty_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        let _ = (params, redis_conn, room);
+
+        Ok(())
+    }
+
+    /// Provision the resources of a room ahead of a scheduled meeting
+    ///
+    /// Called by the pre-warm scheduler shortly before an event in the room starts, without any participant being
+    /// connected. Modules which lazily create external resources on first use can create them here, so the first
+    /// participants do not have to wait for them. Resources created here are released by `cleanup_empty_room` if
+    /// nobody joins the room.
+    async fn prepare_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        let _ = (params, redis_conn, room);
+
+        Ok(())
+    }
+
+    /// Collect statistics about the current state of a running room
+    ///
+    /// Used by support to inspect a room without joining it, e.g. the media sessions of the participants. Returns
+    /// `None` if the module has nothing to report.
+    async fn room_stats(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<Option<serde_json::Value>> {
+        let _ = (params, redis_conn, room);
+
+        Ok(None)
+    }
+
+    /// Migrate the module's redis keys from `from_version` to [`Self::KEY_VERSION`]
+    ///
+    /// Called once per deployment when a controller with a newer key version starts, before it accepts signaling
+    /// connections. Rooms may still be running on controllers of the previous release, so the old keys should be
+    /// copied instead of renamed.
+    async fn migrate_keys(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        from_version: u32,
+    ) -> Result<()> {
+        let _ = (params, redis_conn, from_version);
+
+        Ok(())
+    }
+
+    /// Convert an outgoing message into the schema of the negotiated protocol version
+    ///
+    /// Called for every websocket message sent by the module. Modules which change the schema of a message in a newer
+    /// protocol version override this to keep serving participants which negotiated an older version.
+    /// By default the message is serialized unchanged.
+    fn adapt_outgoing(message: &Self::Outgoing, version: ProtocolVersion) -> serde_json::Value {
+        let _ = version;
+
+        serde_json::to_value(message).expect("value must be serializable to json")
+    }
+
+    /// Serialize an ext event for the [`capture`](crate::api::signaling::capture) of the room
+    ///
+    /// Modules with ext events affecting their state override this together with
+    /// [`SignalingModule::replay_ext_event`]. By default ext events are captured without payload and skipped when
+    /// replaying the capture.
+    fn capture_ext_event(event: &Self::ExtEvent) -> Option<serde_json::Value> {
+        let _ = event;
+
+        None
+    }
+
+    /// Restore an ext event serialized by [`SignalingModule::capture_ext_event`] to replay it
+    fn replay_ext_event(value: serde_json::Value) -> Result<Self::ExtEvent> {
+        let _ = value;
+
+        bail!(
+            "Ext events of module {} cannot be replayed",
+            Self::NAMESPACE
+        )
+    }
 }
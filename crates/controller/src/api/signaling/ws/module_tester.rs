@@ -9,9 +9,11 @@
 //! visibility restriction of those types, this module is located in the same folder.
 //!
 //! The idea is to simulate a frontend websocket connection. See the LegalVote integration tests for examples.
+use super::bus::ModuleBus;
 use super::modules::AnyStream;
 use super::{
-    DestroyContext, Event, NamespacedCommand, NamespacedEvent, RabbitMqPublish, SignalingModule,
+    DestroyContext, Event, NamespacedCommand, QueuedWsMessage, RabbitMqPublish, SharedPayload,
+    SignalingModule,
 };
 use crate::api::signaling::prelude::control::incoming::Join;
 use crate::api::signaling::prelude::control::{self, outgoing, storage, ControlData, NAMESPACE};
@@ -122,6 +124,7 @@ where
         runner_interface.ws.send(WsMessageIncoming::Control(
             control::incoming::Message::Join(Join {
                 display_name: display_name.into(),
+                client_timestamp: None,
             }),
         ))?;
 
@@ -359,6 +362,7 @@ where
         rabbitmq_sender: broadcast::Sender<RabbitMqPublish>,
     ) -> Result<Self> {
         let mut events = SelectAll::new();
+        let mut bus = ModuleBus::default();
 
         let init_context = InitContext {
             id: participant_id,
@@ -366,12 +370,14 @@ where
             breakout_room,
             participant: &mut participant,
             role,
+            region: None,
             db: &db,
             storage: &storage,
             authz: &authz,
             rabbitmq_exchanges: &mut vec![],
             rabbitmq_bindings: &mut vec![],
             events: &mut events,
+            bus: &mut bus,
             redis_conn: &mut redis_conn,
             m: PhantomData::<fn() -> M>,
         };
@@ -385,6 +391,7 @@ where
             Participant::Guest => Participant::Guest,
             Participant::Sip => Participant::Sip,
             Participant::Recorder => Participant::Recorder,
+            Participant::Bot => Participant::Bot,
         };
 
         Ok(Self {
@@ -412,6 +419,7 @@ where
             let mut rabbitmq_publish = vec![];
             let mut invalidate_data = false;
             let mut events = SelectAll::new();
+            let bus = ModuleBus::default();
             let mut exit = None;
 
             let ctx = ModuleContext {
@@ -422,6 +430,7 @@ where
                 redis_conn: &mut self.redis_conn.clone(),
                 invalidate_data: &mut invalidate_data,
                 events: &mut events,
+                bus: &bus,
                 exit: &mut exit,
                 metrics: None,
                 m: PhantomData::<fn() -> M>,
@@ -498,6 +507,9 @@ where
                     Participant::Recorder => {
                         attr_pipe.set("kind", ParticipationKind::Recorder);
                     }
+                    Participant::Bot => {
+                        attr_pipe.set("kind", ParticipationKind::Bot);
+                    }
                 }
 
                 attr_pipe
@@ -551,11 +563,13 @@ where
                         Participant::Guest => ParticipationKind::Guest,
                         Participant::Sip => ParticipationKind::Sip,
                         Participant::Recorder => ParticipationKind::Recorder,
+                        Participant::Bot => ParticipationKind::Bot,
                     },
                     hand_is_up: false,
                     joined_at: ctx.timestamp,
                     left_at: None,
                     hand_updated_at: ctx.timestamp,
+                    metadata: Default::default(),
                 };
 
                 self.module
@@ -603,6 +617,8 @@ where
                         enabled_modules: HashSet::from([M::NAMESPACE.into()]),
                     }
                     .into(),
+                    branding: None,
+                    clock_offset_ms: None,
                     module_data,
                     participants,
                 };
@@ -646,6 +662,11 @@ where
             }
             control::incoming::Message::GrantModeratorRole(_) => unimplemented!(),
             control::incoming::Message::RevokeModeratorRole(_) => unimplemented!(),
+            control::incoming::Message::SwitchBreakout(_)
+            | control::incoming::Message::SetMetadata(_)
+            | control::incoming::Message::GetRaisedHands(_)
+            | control::incoming::Message::SubmitFeedback(_)
+            | control::incoming::Message::TimeSync(_) => unimplemented!(),
         }
     }
 
@@ -751,6 +772,7 @@ where
             )),
             routing_key: control::rabbitmq::room_all_routing_key().into(),
             message,
+            shared: false,
         };
 
         self.rabbitmq_sender
@@ -778,7 +800,7 @@ where
                     return Ok(());
                 }
             }
-            Participant::Guest | Participant::Sip | Participant::Recorder => {
+            Participant::Guest | Participant::Sip | Participant::Recorder | Participant::Bot => {
                 if !(rabbitmq_publish.routing_key == "participant.all"
                     || rabbitmq_publish.routing_key == participant_routing_key)
                 {
@@ -787,6 +809,16 @@ where
             }
         }
 
+        if rabbitmq_publish.shared {
+            let payload = SharedPayload::from_delivery(rabbitmq_publish.message.into_bytes())
+                .context("Got invalid shared rabbitmq payload")?;
+
+            let mut ctx = ctx;
+            ctx.ws_send_raw(payload);
+
+            return Ok(());
+        }
+
         let namespaced =
             serde_json::from_str::<NamespacedCommand<Value>>(&rabbitmq_publish.message)
                 .context("Failed to read incoming rabbitmq message")?;
@@ -818,16 +850,21 @@ where
 
     async fn handle_module_requested_actions(
         &mut self,
-        ws_messages: Vec<NamespacedEvent<'_, M::Outgoing>>,
+        ws_messages: Vec<QueuedWsMessage<M::Outgoing>>,
         rabbitmq_publish: Vec<RabbitMqPublish>,
         invalidate_data: bool,
         events: SelectAll<AnyStream>,
         exit: Option<CloseCode>,
     ) {
         for ws_message in ws_messages {
+            let ws_message = match ws_message {
+                QueuedWsMessage::Event(event) => WsMessageOutgoing::Module(event.payload),
+                QueuedWsMessage::Shared(payload) => WsMessageOutgoing::Shared(payload),
+            };
+
             self.interface
                 .ws
-                .send(WsMessageOutgoing::Module(ws_message.payload))
+                .send(ws_message)
                 .expect("Error sending outgoing module message");
         }
 
@@ -858,6 +895,7 @@ where
         let mut rabbitmq_publish = vec![];
         let mut invalidate_data = false;
         let mut events = SelectAll::new();
+        let bus = ModuleBus::default();
         let mut exit = None;
 
         let ctx = ModuleContext {
@@ -868,6 +906,7 @@ where
             redis_conn: &mut self.redis_conn,
             invalidate_data: &mut invalidate_data,
             events: &mut events,
+            bus: &bus,
             exit: &mut exit,
             metrics: None,
             m: PhantomData::<fn() -> M>,
@@ -973,6 +1012,8 @@ where
 {
     Module(M::Outgoing),
     Control(control::outgoing::Message),
+    /// Pre-serialized message of the module, see [`SharedPayload`]
+    Shared(SharedPayload),
 }
 
 impl<M> Clone for WsMessageOutgoing<M>
@@ -984,6 +1025,7 @@ where
         match self {
             Self::Module(outgoing) => Self::Module(outgoing.clone()),
             Self::Control(outgoing) => Self::Control(outgoing.clone()),
+            Self::Shared(payload) => Self::Shared(payload.clone()),
         }
     }
 }
@@ -996,6 +1038,7 @@ where
         match self {
             Self::Module(arg0) => f.debug_tuple("Module").field(arg0).finish(),
             Self::Control(arg0) => f.debug_tuple("Control").field(arg0).finish(),
+            Self::Shared(arg0) => f.debug_tuple("Shared").field(arg0).finish(),
         }
     }
 }
@@ -1008,6 +1051,7 @@ where
         match (self, other) {
             (Self::Module(l0), Self::Module(r0)) => l0 == r0,
             (Self::Control(l0), Self::Control(r0)) => l0 == r0,
+            (Self::Shared(l0), Self::Shared(r0)) => l0 == r0,
             _ => false,
         }
     }
@@ -2,26 +2,35 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use super::{Event, ModuleContext};
-use super::{SignalingModule, Timestamp};
-use crate::api::signaling::metrics::SignalingMetrics;
-use crate::api::signaling::ws::runner::Builder;
+use super::bus::ModuleBus;
+use super::{Event, ModuleContext, QueuedWsMessage};
+use super::{ProtocolVersion, SignalingModule, Timestamp};
+use crate::api::signaling::capture::{CapturedEvent, EventRecorder};
+use crate::api::signaling::connection_history;
+use crate::api::signaling::metrics::{RoomSize, SignalingMetrics};
+use crate::api::signaling::ws::runner::ModuleInit;
 use crate::api::signaling::ws::{DestroyContext, InitContext, RabbitMqPublish};
 use crate::api::signaling::ws_modules::control::outgoing::Participant;
 use crate::api::signaling::ws_modules::control::ControlData;
-use crate::api::signaling::Role;
+use crate::api::signaling::{Role, SignalingRoomId};
 use crate::redis_wrapper::RedisConnection;
+use crate::services::{error_reporting, ErrorReportingService};
 use actix_http::ws::{CloseCode, Message};
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use controller_shared::settings::ModuleFailurePolicy;
 use futures::stream::SelectAll;
+use futures::FutureExt;
 use serde_json::Value;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio_stream::{Stream, StreamExt};
 use types::core::ParticipantId;
+use types::signaling::NamespacedEvent;
 
 pub type AnyStream = Pin<Box<dyn Stream<Item = (&'static str, Box<dyn Any + 'static>)>>>;
 
@@ -39,13 +48,30 @@ pub struct NoSuchModuleError(pub ());
 #[derive(Default)]
 pub(super) struct Modules {
     modules: HashMap<&'static str, Box<dyn ModuleCaller>>,
+    /// Modules disabled after a failure, they no longer receive events but are still destroyed with the others
+    disabled: HashMap<&'static str, Box<dyn ModuleCaller>>,
+    /// Set if the events of the room are captured
+    recorder: Option<EventRecorder>,
 }
 
 impl Modules {
+    pub fn set_recorder(&mut self, recorder: Option<EventRecorder>) {
+        self.recorder = recorder;
+    }
+
     pub fn get_module_names(&self) -> Vec<&'static str> {
         self.modules.keys().copied().collect()
     }
 
+    /// Namespaces of the modules whose peer frontend data can be cached in the participant snapshot
+    pub fn get_cacheable_module_names(&self) -> Vec<&'static str> {
+        self.modules
+            .iter()
+            .filter(|(_, module)| module.has_cacheable_peer_frontend_data())
+            .map(|(namespace, _)| *namespace)
+            .collect()
+    }
+
     pub async fn add_module<M>(&mut self, module: M)
     where
         M: SignalingModule,
@@ -58,54 +84,206 @@ impl Modules {
 
     pub async fn on_event_targeted(
         &mut self,
-        ctx: DynEventCtx<'_>,
+        mut ctx: DynEventCtx<'_>,
         module: &str,
         dyn_event: DynTargetedEvent,
     ) -> Result<(), NoSuchModuleError> {
-        let module = self.modules.get_mut(module).ok_or(NoSuchModuleError(()))?;
+        if self.disabled.contains_key(module) {
+            log::debug!("Dropping event of disabled module {}", module);
+            return Ok(());
+        }
+
+        let namespace = *self
+            .modules
+            .get_key_value(module)
+            .ok_or(NoSuchModuleError(()))?
+            .0;
+        let module_caller = self
+            .modules
+            .get_mut(namespace)
+            .ok_or(NoSuchModuleError(()))?;
+
+        if let Some(recorder) = &mut self.recorder {
+            let event = match &dyn_event {
+                DynTargetedEvent::WsMessage(payload) => CapturedEvent::WsMessage {
+                    namespace: namespace.into(),
+                    payload: payload.clone(),
+                },
+                DynTargetedEvent::RabbitMqMessage(payload) => CapturedEvent::RabbitMq {
+                    namespace: namespace.into(),
+                    payload: payload.clone(),
+                },
+                DynTargetedEvent::Ext(ext) => CapturedEvent::Ext {
+                    namespace: namespace.into(),
+                    payload: module_caller.capture_ext_event(ext.as_ref()),
+                },
+            };
 
-        if let Err(e) = module.on_event_targeted(ctx, dyn_event).await {
-            log::error!("Failed to handle event {:?}", e);
+            recorder
+                .record(ctx.redis_conn, ctx.role, ctx.timestamp, event)
+                .await;
         }
 
+        error_reporting::breadcrumb(dyn_event.as_str(), namespace);
+        error_reporting::set_module(Some(namespace));
+
+        let result = AssertUnwindSafe(module_caller.on_event_targeted(ctx.reborrow(), dyn_event))
+            .catch_unwind()
+            .await;
+
+        if let Some(failure) = ModuleFailure::from_result(result) {
+            if handle_module_failure(&mut ctx, namespace, failure).await {
+                self.disable(&mut ctx, namespace);
+            }
+        }
+
+        error_reporting::set_module(None);
+
         Ok(())
     }
 
     pub async fn on_event_broadcast(
         &mut self,
-        ctx: DynEventCtx<'_>,
+        mut ctx: DynEventCtx<'_>,
         mut dyn_event: DynBroadcastEvent<'_>,
     ) {
-        for module in self.modules.values_mut() {
-            let ctx = DynEventCtx {
-                id: ctx.id,
-                role: ctx.role,
-                ws_messages: ctx.ws_messages,
-                rabbitmq_publish: ctx.rabbitmq_publish,
-                redis_conn: ctx.redis_conn,
-                events: ctx.events,
-                invalidate_data: ctx.invalidate_data,
-                timestamp: ctx.timestamp,
-                exit: ctx.exit,
-                metrics: ctx.metrics.clone(),
-            };
+        error_reporting::breadcrumb("broadcast", dyn_event.as_str());
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .record(ctx.redis_conn, ctx.role, ctx.timestamp, dyn_event.capture())
+                .await;
+        }
+
+        let mut failed = vec![];
 
-            if let Err(e) = module.on_event_broadcast(ctx, &mut dyn_event).await {
-                log::error!("Failed to handle event, {:?}", e);
+        for (namespace, module) in self.modules.iter_mut() {
+            error_reporting::set_module(Some(*namespace));
+
+            let result =
+                AssertUnwindSafe(module.on_event_broadcast(ctx.reborrow(), &mut dyn_event))
+                    .catch_unwind()
+                    .await;
+
+            if let Some(failure) = ModuleFailure::from_result(result) {
+                if handle_module_failure(&mut ctx, *namespace, failure).await {
+                    failed.push(*namespace);
+                }
             }
         }
+
+        error_reporting::set_module(None);
+
+        for namespace in failed {
+            self.disable(&mut ctx, namespace);
+        }
+    }
+
+    /// Stop dispatching events to the module and let the runner notify the client
+    fn disable(&mut self, ctx: &mut DynEventCtx<'_>, namespace: &'static str) {
+        if let Some(module) = self.modules.remove(namespace) {
+            log::warn!("Disabling module {} for the rest of the session", namespace);
+
+            self.disabled.insert(namespace, module);
+            ctx.disabled_modules.push(namespace);
+        }
     }
 
     pub async fn destroy(&mut self, ctx: DestroyContext<'_>) {
-        for (namespace, module) in self.modules.drain() {
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .record_destroyed(ctx.redis_conn, ctx.destroy_room)
+                .await;
+        }
+
+        for (namespace, module) in self.modules.drain().chain(self.disabled.drain()) {
             log::debug!("Destroying module {}", namespace);
 
-            module
-                .destroy(DestroyContext {
-                    redis_conn: ctx.redis_conn,
-                    destroy_room: ctx.destroy_room,
-                })
-                .await;
+            let result = AssertUnwindSafe(module.destroy(DestroyContext {
+                redis_conn: ctx.redis_conn,
+                destroy_room: ctx.destroy_room,
+            }))
+            .catch_unwind()
+            .await;
+
+            if result.is_err() {
+                log::error!("Module {} panicked while being destroyed", namespace);
+            }
+        }
+    }
+}
+
+/// Failure of a module while handling an event
+enum ModuleFailure {
+    Error(anyhow::Error),
+    Panic(String),
+}
+
+impl ModuleFailure {
+    fn from_result(result: std::thread::Result<Result<()>>) -> Option<Self> {
+        match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Self::Error(e)),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic payload".into());
+
+                Some(Self::Panic(message))
+            }
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Error(_) => "error",
+            Self::Panic(_) => "panic",
+        }
+    }
+}
+
+/// Report the failure of the module and apply the configured [`ModuleFailurePolicy`]
+///
+/// Returns true if the module must be disabled.
+async fn handle_module_failure(
+    ctx: &mut DynEventCtx<'_>,
+    namespace: &'static str,
+    failure: ModuleFailure,
+) -> bool {
+    ctx.metrics
+        .increment_module_failures_count(namespace, failure.kind());
+
+    let (error, panicked) = match failure {
+        ModuleFailure::Error(e) => {
+            log::error!("Failed to handle event, {:?}", e);
+
+            ctx.error_reporting.report_module_error(namespace, &e);
+
+            (e, false)
+        }
+        ModuleFailure::Panic(message) => {
+            // Panics are reported by the panic hook already
+            log::error!(
+                "Module {} panicked while handling event, {}",
+                namespace,
+                message
+            );
+
+            (anyhow!("panicked: {}", message), true)
+        }
+    };
+
+    record_module_error(ctx.redis_conn, ctx.id, namespace, &error).await;
+
+    match ctx.failure_policy {
+        // The state of a module which panicked might be inconsistent
+        ModuleFailurePolicy::Continue => panicked,
+        ModuleFailurePolicy::Disable => true,
+        ModuleFailurePolicy::Disconnect => {
+            *ctx.exit = Some(CloseCode::Error);
+            false
         }
     }
 }
@@ -118,13 +296,27 @@ pub enum DynTargetedEvent {
     Ext(Box<dyn Any + 'static>),
 }
 
+impl DynTargetedEvent {
+    /// Source of the event, used as category of the error reporting breadcrumbs
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::WsMessage(_) => "websocket",
+            Self::RabbitMqMessage(_) => "rabbitmq",
+            Self::Ext(_) => "ext",
+        }
+    }
+}
+
 /// Events that can dispatched to all modules
 #[derive(Debug)]
 pub enum DynBroadcastEvent<'evt> {
+    /// The participant joined the room, the set contains the participants whose cacheable peer frontend data was
+    /// served from the participant snapshot
     Joined(
         &'evt ControlData,
         &'evt mut HashMap<&'static str, Value>,
         &'evt mut Vec<Participant>,
+        &'evt HashSet<ParticipantId>,
     ),
     Leaving,
     RaiseHand,
@@ -134,6 +326,44 @@ pub enum DynBroadcastEvent<'evt> {
     ParticipantUpdated(&'evt mut Participant),
 }
 
+impl DynBroadcastEvent<'_> {
+    /// Name of the event, used in the error reporting breadcrumbs
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Joined(..) => "joined",
+            Self::Leaving => "leaving",
+            Self::RaiseHand => "raise_hand",
+            Self::LowerHand => "lower_hand",
+            Self::ParticipantJoined(_) => "participant_joined",
+            Self::ParticipantLeft(_) => "participant_left",
+            Self::ParticipantUpdated(_) => "participant_updated",
+        }
+    }
+
+    /// The event as recorded in the capture of the room
+    fn capture(&self) -> CapturedEvent {
+        match self {
+            Self::Joined(control_data, _, participants, _) => CapturedEvent::Joined {
+                control_data: (*control_data).clone(),
+                participants: participants
+                    .iter()
+                    .map(|participant| participant.id)
+                    .collect(),
+            },
+            Self::Leaving => CapturedEvent::Leaving,
+            Self::RaiseHand => CapturedEvent::RaiseHand,
+            Self::LowerHand => CapturedEvent::LowerHand,
+            Self::ParticipantJoined(participant) => CapturedEvent::ParticipantJoined {
+                peer: participant.id,
+            },
+            Self::ParticipantLeft(peer) => CapturedEvent::ParticipantLeft { peer: *peer },
+            Self::ParticipantUpdated(participant) => CapturedEvent::ParticipantUpdated {
+                peer: participant.id,
+            },
+        }
+    }
+}
+
 /// Untyped version of a ModuleContext which is used in `on_event`
 pub(super) struct DynEventCtx<'ctx> {
     pub id: ParticipantId,
@@ -143,9 +373,60 @@ pub(super) struct DynEventCtx<'ctx> {
     pub rabbitmq_publish: &'ctx mut Vec<RabbitMqPublish>,
     pub redis_conn: &'ctx mut RedisConnection,
     pub events: &'ctx mut SelectAll<AnyStream>,
+    pub bus: &'ctx ModuleBus,
     pub invalidate_data: &'ctx mut bool,
     pub exit: &'ctx mut Option<CloseCode>,
     pub metrics: Arc<SignalingMetrics>,
+    pub error_reporting: &'ctx ErrorReportingService,
+    pub protocol_version: ProtocolVersion,
+    pub room_size: RoomSize,
+    pub failure_policy: ModuleFailurePolicy,
+    /// Namespaces of the modules disabled while handling the event
+    pub disabled_modules: &'ctx mut Vec<&'static str>,
+}
+
+impl DynEventCtx<'_> {
+    /// Reborrow the context to pass it to a single module
+    fn reborrow(&mut self) -> DynEventCtx<'_> {
+        DynEventCtx {
+            id: self.id,
+            role: self.role,
+            timestamp: self.timestamp,
+            ws_messages: self.ws_messages,
+            rabbitmq_publish: self.rabbitmq_publish,
+            redis_conn: self.redis_conn,
+            events: self.events,
+            bus: self.bus,
+            invalidate_data: self.invalidate_data,
+            exit: self.exit,
+            metrics: self.metrics.clone(),
+            error_reporting: self.error_reporting,
+            protocol_version: self.protocol_version,
+            room_size: self.room_size,
+            failure_policy: self.failure_policy,
+            disabled_modules: self.disabled_modules,
+        }
+    }
+}
+
+/// Add the error of the module to the connection history of the participant
+async fn record_module_error(
+    redis_conn: &mut RedisConnection,
+    id: ParticipantId,
+    namespace: &str,
+    error: &anyhow::Error,
+) {
+    let kind = connection_history::ConnectionEventKind::ModuleError {
+        namespace: namespace.into(),
+        error: error.to_string(),
+    };
+
+    if let Err(e) = connection_history::record(redis_conn, id, kind).await {
+        log::warn!(
+            "Failed to record module error in connection history, {:?}",
+            e
+        );
+    }
 }
 
 #[async_trait::async_trait(?Send)]
@@ -161,6 +442,8 @@ trait ModuleCaller {
         dyn_event: &mut DynBroadcastEvent<'_>,
     ) -> Result<()>;
     async fn destroy(self: Box<Self>, ctx: DestroyContext<'_>);
+    fn capture_ext_event(&self, event: &dyn Any) -> Option<Value>;
+    fn has_cacheable_peer_frontend_data(&self) -> bool;
 }
 
 struct ModuleCallerImpl<M> {
@@ -182,6 +465,7 @@ where
             rabbitmq_publish: ctx.rabbitmq_publish,
             redis_conn: ctx.redis_conn,
             events: ctx.events,
+            bus: ctx.bus,
             invalidate_data: ctx.invalidate_data,
             exit: ctx.exit,
             timestamp: ctx.timestamp,
@@ -220,6 +504,7 @@ where
             rabbitmq_publish: ctx.rabbitmq_publish,
             redis_conn: ctx.redis_conn,
             events: ctx.events,
+            bus: ctx.bus,
             invalidate_data: ctx.invalidate_data,
             exit: ctx.exit,
             timestamp: ctx.timestamp,
@@ -228,9 +513,15 @@ where
         };
 
         match dyn_event {
-            DynBroadcastEvent::Joined(control_data, module_data, participants) => {
+            DynBroadcastEvent::Joined(control_data, module_data, participants, cached) => {
                 let mut frontend_data = None;
-                let mut participants_data = participants.iter().map(|p| (p.id, None)).collect();
+
+                // The cached peer frontend data has already been added from the participant snapshot
+                let mut participants_data = participants
+                    .iter()
+                    .filter(|p| !(M::CACHEABLE_PEER_FRONTEND_DATA && cached.contains(&p.id)))
+                    .map(|p| (p.id, None))
+                    .collect();
 
                 self.module
                     .on_event(
@@ -318,6 +609,7 @@ where
         dyn_ctx: DynEventCtx<'_>,
         dyn_event: DynTargetedEvent,
     ) -> Result<()> {
+        let start = Instant::now();
         let mut ws_messages = vec![];
 
         let ctx = ModuleContext {
@@ -327,6 +619,7 @@ where
             rabbitmq_publish: dyn_ctx.rabbitmq_publish,
             redis_conn: dyn_ctx.redis_conn,
             events: dyn_ctx.events,
+            bus: dyn_ctx.bus,
             invalidate_data: dyn_ctx.invalidate_data,
             exit: dyn_ctx.exit,
             metrics: Some(dyn_ctx.metrics.clone()),
@@ -335,19 +628,17 @@ where
 
         let result = self.handle_dyn_targeted_event(ctx, dyn_event).await;
 
-        let mut ws_messages_serialized = ws_messages
-            .into_iter()
-            .map(|message| {
-                Message::Text(
-                    serde_json::to_string(&message)
-                        .expect("Failed to convert namespaced to json")
-                        .into(),
-                )
-            })
-            .collect();
+        let mut ws_messages_serialized =
+            serialize_ws_messages::<M>(ws_messages, dyn_ctx.protocol_version);
 
         dyn_ctx.ws_messages.append(&mut ws_messages_serialized);
 
+        dyn_ctx.metrics.record_module_event_duration(
+            M::NAMESPACE,
+            dyn_ctx.room_size,
+            start.elapsed().as_secs_f64(),
+        );
+
         result
     }
 
@@ -357,6 +648,7 @@ where
         dyn_ctx: DynEventCtx<'_>,
         dyn_event: &mut DynBroadcastEvent<'_>,
     ) -> Result<()> {
+        let start = Instant::now();
         let mut ws_messages = vec![];
 
         let ctx = ModuleContext {
@@ -366,6 +658,7 @@ where
             rabbitmq_publish: dyn_ctx.rabbitmq_publish,
             redis_conn: dyn_ctx.redis_conn,
             events: dyn_ctx.events,
+            bus: dyn_ctx.bus,
             invalidate_data: dyn_ctx.invalidate_data,
             exit: dyn_ctx.exit,
             metrics: Some(dyn_ctx.metrics.clone()),
@@ -374,19 +667,17 @@ where
 
         let result = self.handle_dyn_broadcast_event(ctx, dyn_event).await;
 
-        let mut ws_messages_serialized = ws_messages
-            .into_iter()
-            .map(|message| {
-                Message::Text(
-                    serde_json::to_string(&message)
-                        .expect("Failed to convert namespaced to json")
-                        .into(),
-                )
-            })
-            .collect();
+        let mut ws_messages_serialized =
+            serialize_ws_messages::<M>(ws_messages, dyn_ctx.protocol_version);
 
         dyn_ctx.ws_messages.append(&mut ws_messages_serialized);
 
+        dyn_ctx.metrics.record_module_event_duration(
+            M::NAMESPACE,
+            dyn_ctx.room_size,
+            start.elapsed().as_secs_f64(),
+        );
+
         result
     }
 
@@ -394,15 +685,81 @@ where
     async fn destroy(self: Box<Self>, ctx: DestroyContext<'_>) {
         self.module.on_destroy(ctx).await
     }
+
+    fn capture_ext_event(&self, event: &dyn Any) -> Option<Value> {
+        event
+            .downcast_ref::<M::ExtEvent>()
+            .and_then(M::capture_ext_event)
+    }
+
+    fn has_cacheable_peer_frontend_data(&self) -> bool {
+        M::CACHEABLE_PEER_FRONTEND_DATA
+    }
+}
+
+/// Serialize the websocket messages of a module using the schema of the negotiated protocol version
+///
+/// Shared payloads have been serialized for every version already, the matching serialization is sent as is.
+fn serialize_ws_messages<M>(
+    messages: Vec<QueuedWsMessage<M::Outgoing>>,
+    version: ProtocolVersion,
+) -> Vec<Message>
+where
+    M: SignalingModule,
+{
+    messages
+        .into_iter()
+        .map(|message| {
+            let message = match message {
+                QueuedWsMessage::Event(message) => message,
+                QueuedWsMessage::Shared(payload) => return payload.into_message(version),
+            };
+
+            let message = NamespacedEvent {
+                namespace: message.namespace,
+                timestamp: message.timestamp,
+                payload: M::adapt_outgoing(&message.payload, version),
+            };
+
+            Message::Text(
+                serde_json::to_string(&message)
+                    .expect("Failed to convert namespaced to json")
+                    .into(),
+            )
+        })
+        .collect()
 }
 
 #[async_trait::async_trait(?Send)]
 pub trait ModuleBuilder: Send + Sync {
-    async fn build(&self, builder: &mut Builder) -> Result<()>;
+    async fn build(&self, init: ModuleInit<'_>) -> Result<()>;
+
+    async fn cleanup_empty_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()>;
+
+    async fn prepare_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()>;
+
+    async fn room_stats(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<Option<serde_json::Value>>;
+
+    async fn migrate_keys(&self, redis_conn: &mut RedisConnection, from_version: u32)
+        -> Result<()>;
 
     fn clone_boxed(&self) -> Box<dyn ModuleBuilder>;
 
     fn namespace(&self) -> &'static str;
+
+    fn key_version(&self) -> u32;
 }
 
 pub struct ModuleBuilderImpl<M>
@@ -418,30 +775,64 @@ impl<M> ModuleBuilder for ModuleBuilderImpl<M>
 where
     M: SignalingModule,
 {
-    async fn build(&self, builder: &mut Builder) -> Result<()> {
+    async fn build(&self, init: ModuleInit<'_>) -> Result<()> {
         let ctx = InitContext {
-            id: builder.id,
-            room: &builder.room,
-            breakout_room: builder.breakout_room,
-            participant: &builder.participant,
-            role: builder.role,
-            db: &builder.db,
-            storage: &builder.storage,
-            authz: &builder.authz,
-            rabbitmq_exchanges: &mut builder.rabbitmq_exchanges,
-            rabbitmq_bindings: &mut builder.rabbitmq_bindings,
-            events: &mut builder.events,
-            redis_conn: &mut builder.redis_conn,
+            id: init.id,
+            room: init.room,
+            breakout_room: init.breakout_room,
+            participant: init.participant,
+            role: init.role,
+            region: init.region,
+            db: init.db,
+            storage: init.storage,
+            authz: init.authz,
+            rabbitmq_exchanges: init.rabbitmq_exchanges,
+            rabbitmq_bindings: init.rabbitmq_bindings,
+            events: init.events,
+            bus: init.bus,
+            redis_conn: init.redis_conn,
             m: PhantomData::<fn() -> M>,
         };
 
-        if let Some(module) = M::init(ctx, &self.params, builder.protocol).await? {
-            builder.modules.add_module(module).await;
+        if let Some(module) = M::init(ctx, &self.params, init.protocol).await? {
+            init.modules.add_module(module).await;
         }
 
         Ok(())
     }
 
+    async fn cleanup_empty_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        M::cleanup_empty_room(&self.params, redis_conn, room).await
+    }
+
+    async fn prepare_room(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        M::prepare_room(&self.params, redis_conn, room).await
+    }
+
+    async fn room_stats(
+        &self,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<Option<serde_json::Value>> {
+        M::room_stats(&self.params, redis_conn, room).await
+    }
+
+    async fn migrate_keys(
+        &self,
+        redis_conn: &mut RedisConnection,
+        from_version: u32,
+    ) -> Result<()> {
+        M::migrate_keys(&self.params, redis_conn, from_version).await
+    }
+
     fn clone_boxed(&self) -> Box<dyn ModuleBuilder> {
         Box::new(Self {
             m: self.m,
@@ -452,6 +843,10 @@ where
     fn namespace(&self) -> &'static str {
         M::NAMESPACE
     }
+
+    fn key_version(&self) -> u32 {
+        M::KEY_VERSION
+    }
 }
 
 impl Clone for Box<dyn ModuleBuilder> {
@@ -459,3 +854,95 @@ impl Clone for Box<dyn ModuleBuilder> {
         (**self).clone_boxed()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::signaling::ws::{DestroyContext, Event, InitContext, ModuleContext};
+    use pretty_assertions::assert_eq;
+    use schemars::JsonSchema;
+    use serde::Serialize;
+
+    /// Module which renamed the `name` field of its message to `display_name` in the (hypothetical) protocol v1.1
+    struct Versioned;
+
+    #[derive(Debug, PartialEq, Serialize, JsonSchema)]
+    struct Renamed {
+        display_name: String,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl SignalingModule for Versioned {
+        const NAMESPACE: &'static str = "versioned";
+        type Params = ();
+        type Incoming = Value;
+        type Outgoing = Renamed;
+        type RabbitMqMessage = ();
+        type ExtEvent = ();
+        type FrontendData = ();
+        type PeerFrontendData = ();
+
+        async fn init(
+            _: InitContext<'_, Self>,
+            _: &Self::Params,
+            _: &'static str,
+        ) -> Result<Option<Self>> {
+            Ok(Some(Self))
+        }
+
+        async fn on_event(&mut self, _: ModuleContext<'_, Self>, _: Event<'_, Self>) -> Result<()> {
+            Ok(())
+        }
+
+        async fn on_destroy(self, _: DestroyContext<'_>) {}
+
+        fn adapt_outgoing(message: &Self::Outgoing, version: ProtocolVersion) -> Value {
+            if version < ProtocolVersion::new(1, 1) {
+                serde_json::json!({ "name": message.display_name })
+            } else {
+                serde_json::to_value(message).unwrap()
+            }
+        }
+    }
+
+    fn serialize_for(version: ProtocolVersion) -> Value {
+        let messages = vec![QueuedWsMessage::Event(NamespacedEvent {
+            namespace: Versioned::NAMESPACE,
+            timestamp: Timestamp::unix_epoch(),
+            payload: Renamed {
+                display_name: "Alice".into(),
+            },
+        })];
+
+        match serialize_ws_messages::<Versioned>(messages, version).as_slice() {
+            [Message::Text(text)] => serde_json::from_str(text).unwrap(),
+            messages => panic!("expected a single text message, got {messages:?}"),
+        }
+    }
+
+    #[test]
+    fn ws_messages_are_adapted_to_the_protocol_version() {
+        assert_eq!(
+            serialize_for(ProtocolVersion::V1_0)["payload"],
+            serde_json::json!({ "name": "Alice" })
+        );
+        assert_eq!(
+            serialize_for(ProtocolVersion::new(1, 1))["payload"],
+            serde_json::json!({ "display_name": "Alice" })
+        );
+    }
+
+    #[test]
+    fn module_failures() {
+        assert!(ModuleFailure::from_result(Ok(Ok(()))).is_none());
+
+        let failure = ModuleFailure::from_result(Ok(Err(anyhow!("failed")))).unwrap();
+        assert_eq!(failure.kind(), "error");
+
+        let payload = std::panic::catch_unwind(|| -> Result<()> { panic!("module {}", "chat") });
+        match ModuleFailure::from_result(payload) {
+            Some(ModuleFailure::Panic(message)) => assert_eq!(message, "module chat"),
+            _ => panic!("expected a panic"),
+        }
+    }
+}
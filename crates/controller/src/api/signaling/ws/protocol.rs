@@ -0,0 +1,127 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Versioning of the signaling protocol
+//!
+//! The protocol is negotiated with the websocket subprotocol header on upgrade. Clients list all protocol versions
+//! they understand and the controller picks the newest one it supports as well. Modules can adapt their outgoing
+//! messages to older versions with [`SignalingModule::adapt_outgoing`](super::SignalingModule::adapt_outgoing).
+use std::fmt;
+
+const PROTOCOL_PREFIX: &str = "k3k-signaling-json-v";
+
+/// Websocket subprotocols of all protocol versions supported by the controller
+pub(super) const SUPPORTED_PROTOCOLS: &[&str] = &["k3k-signaling-json-v1.0"];
+
+/// Version of the JSON signaling protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    /// `k3k-signaling-json-v1.0`
+    pub const V1_0: Self = Self::new(1, 0);
+
+    /// The newest version supported by the controller
+    pub const LATEST: Self = Self::V1_0;
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// All protocol versions supported by the controller, oldest first
+    pub fn supported() -> impl Iterator<Item = Self> {
+        SUPPORTED_PROTOCOLS
+            .iter()
+            .filter_map(|protocol| Self::from_protocol(protocol))
+    }
+
+    /// Parse the version from a websocket subprotocol, e.g. `k3k-signaling-json-v1.0`
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        let (major, minor) = protocol.strip_prefix(PROTOCOL_PREFIX)?.split_once('.')?;
+
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}.{}", PROTOCOL_PREFIX, self.major, self.minor)
+    }
+}
+
+/// Select the newest of the `supported` protocols which is also `offered` by the client
+pub(super) fn negotiate<'o>(
+    supported: &'static [&'static str],
+    offered: impl IntoIterator<Item = &'o str>,
+) -> Option<&'static str> {
+    let offered: Vec<&str> = offered.into_iter().collect();
+
+    supported
+        .iter()
+        .copied()
+        .filter(|protocol| offered.contains(protocol))
+        .max_by_key(|protocol| ProtocolVersion::from_protocol(protocol))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SUPPORTED: &[&str] = &["k3k-signaling-json-v1.0", "k3k-signaling-json-v1.1"];
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(
+            ProtocolVersion::from_protocol("k3k-signaling-json-v1.0"),
+            Some(ProtocolVersion::V1_0)
+        );
+        assert_eq!(
+            ProtocolVersion::from_protocol("k3k-signaling-json-v2.13"),
+            Some(ProtocolVersion::new(2, 13))
+        );
+        assert_eq!(
+            ProtocolVersion::from_protocol("k3k-signaling-json-v1"),
+            None
+        );
+        assert_eq!(ProtocolVersion::from_protocol("ticket#abc"), None);
+        assert_eq!(ProtocolVersion::V1_0.to_string(), "k3k-signaling-json-v1.0");
+    }
+
+    #[test]
+    fn supported_versions() {
+        assert!(ProtocolVersion::supported().eq(SUPPORTED_PROTOCOLS
+            .iter()
+            .map(|protocol| ProtocolVersion::from_protocol(protocol).unwrap())));
+        assert_eq!(
+            ProtocolVersion::supported().max(),
+            Some(ProtocolVersion::LATEST)
+        );
+    }
+
+    #[test]
+    fn negotiate_newest_common_version() {
+        assert_eq!(
+            negotiate(
+                SUPPORTED,
+                ["k3k-signaling-json-v1.0", "k3k-signaling-json-v1.1"]
+            ),
+            Some("k3k-signaling-json-v1.1")
+        );
+        assert_eq!(
+            negotiate(
+                SUPPORTED,
+                ["k3k-signaling-json-v1.0", "k3k-signaling-json-v2.0"]
+            ),
+            Some("k3k-signaling-json-v1.0")
+        );
+        assert_eq!(negotiate(SUPPORTED, ["k3k-signaling-json-v2.0"]), None);
+    }
+}
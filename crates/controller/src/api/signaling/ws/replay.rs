@@ -0,0 +1,356 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Offline replay of the events captured in a room
+//!
+//! The [`EventReplay`] feeds the [captured events](crate::api::signaling::capture) of a room into a single module,
+//! with one instance of the module for every captured participant, in the order the runners dispatched them. Only the
+//! captured events are dispatched: rabbitmq messages published and ext event streams added by the replayed modules are
+//! not delivered, so every replay of the same capture dispatches the same events with the same timestamps. This allows
+//! to reproduce races between participants, e.g. a vote arriving while the poll is finished, as often as needed.
+//!
+//! The modules keep their state in redis, replays must use a dedicated redis database. The state of the room at the
+//! start of the capture can be restored from a [snapshot](crate::api::signaling::snapshot) before replaying.
+use super::bus::ModuleBus;
+use super::modules::AnyStream;
+use super::{
+    DestroyContext, Event, InitContext, ModuleContext, ProtocolVersion, QueuedWsMessage,
+    SignalingModule,
+};
+use crate::api::signaling::capture::{CaptureRecord, CapturedEvent};
+use crate::api::Participant;
+use crate::redis_wrapper::RedisConnection;
+use crate::storage::ObjectStorage;
+use actix_http::ws::CloseCode;
+use anyhow::{Context, Result};
+use database::Db;
+use db_storage::rooms::Room;
+use db_storage::users::User;
+use futures::stream::SelectAll;
+use kustos::Authz;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use types::core::{ParticipantId, ParticipationKind};
+use types::signaling::NamespacedEvent;
+
+/// Replays captured events into the module `M`
+pub struct EventReplay<M>
+where
+    M: SignalingModule,
+{
+    db: Arc<Db>,
+    authz: Arc<Authz>,
+    storage: Arc<ObjectStorage>,
+    redis_conn: RedisConnection,
+    room: Room,
+    params: M::Params,
+    /// Protocol version the websocket messages of the modules are serialized for
+    protocol_version: ProtocolVersion,
+    /// The module of every participant, `None` if the module did not initialize for the participant
+    participants: HashMap<ParticipantId, Option<ReplayedModule<M>>>,
+}
+
+struct ReplayedModule<M> {
+    module: M,
+    bus: ModuleBus,
+    /// Keeps the event streams added by the module alive, they are never polled
+    _events: SelectAll<AnyStream>,
+}
+
+/// The outcome of a single replayed event
+#[derive(Debug)]
+pub struct ReplayStep {
+    pub record: CaptureRecord,
+    /// Websocket messages sent by the module, serialized for the protocol version of the replay
+    pub ws_messages: Vec<Value>,
+    /// Rabbitmq messages published by the module
+    pub rabbitmq_publish: Vec<ReplayedPublish>,
+    /// Set if the module requested to close the connection of the participant
+    pub exit: Option<CloseCode>,
+    /// The result returned by the module
+    pub result: Result<()>,
+}
+
+/// A rabbitmq message published by a replayed module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayedPublish {
+    pub exchange: Option<String>,
+    pub routing_key: String,
+    pub message: String,
+}
+
+impl<M> EventReplay<M>
+where
+    M: SignalingModule,
+{
+    /// Create a replay of events captured in `room`
+    ///
+    /// Users of captured participants must exist in the database, participants without a user are replayed as guests.
+    pub fn new(
+        db: Arc<Db>,
+        authz: Arc<Authz>,
+        redis_conn: RedisConnection,
+        room: Room,
+        params: M::Params,
+    ) -> Self {
+        Self {
+            db,
+            authz,
+            storage: Arc::new(ObjectStorage::broken()),
+            redis_conn,
+            room,
+            params,
+            protocol_version: ProtocolVersion::LATEST,
+            participants: HashMap::new(),
+        }
+    }
+
+    /// Serialize the websocket messages for the protocol `version` instead of [`ProtocolVersion::LATEST`]
+    ///
+    /// Captures do not contain the protocol versions negotiated by the participants, replays use the same version for
+    /// all of them.
+    pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Replay all records in order, returns the steps of the records dispatched to the module
+    pub async fn replay_all(
+        &mut self,
+        records: impl IntoIterator<Item = CaptureRecord>,
+    ) -> Result<Vec<ReplayStep>> {
+        let mut steps = vec![];
+
+        for record in records {
+            if let Some(step) = self.replay(record).await? {
+                steps.push(step);
+            }
+        }
+
+        Ok(steps)
+    }
+
+    /// Replay a single record
+    ///
+    /// Returns `None` if the record is not dispatched to the module, e.g. events of other modules and ext events
+    /// captured without payload. The module of a participant is initialized with its first record.
+    pub async fn replay(&mut self, record: CaptureRecord) -> Result<Option<ReplayStep>> {
+        match record.event.namespace() {
+            Some(namespace) if namespace != M::NAMESPACE => return Ok(None),
+            _ => {}
+        }
+
+        if let CapturedEvent::Ext { payload: None, .. } = &record.event {
+            log::warn!(
+                "Skipping ext event of participant {} captured without payload",
+                record.participant
+            );
+
+            return Ok(None);
+        }
+
+        if let CapturedEvent::Destroyed { destroy_room } = record.event {
+            self.destroy(record.participant, destroy_room).await;
+
+            return Ok(None);
+        }
+
+        if !self.participants.contains_key(&record.participant) {
+            let module = self.init(&record).await?;
+
+            self.participants.insert(record.participant, module);
+        }
+
+        let replayed = match self.participants.get_mut(&record.participant) {
+            Some(Some(replayed)) => replayed,
+            _ => return Ok(None),
+        };
+
+        let mut ws_messages = vec![];
+        let mut rabbitmq_publish = vec![];
+        let mut events = SelectAll::new();
+        let mut invalidate_data = false;
+        let mut exit = None;
+
+        let ctx = ModuleContext {
+            role: record.role,
+            timestamp: record.timestamp,
+            ws_messages: &mut ws_messages,
+            rabbitmq_publish: &mut rabbitmq_publish,
+            redis_conn: &mut self.redis_conn,
+            events: &mut events,
+            bus: &replayed.bus,
+            invalidate_data: &mut invalidate_data,
+            exit: &mut exit,
+            metrics: None,
+            m: PhantomData::<fn() -> M>,
+        };
+
+        let result = dispatch(&mut replayed.module, ctx, record.event.clone()).await;
+        let protocol_version = self.protocol_version;
+
+        Ok(Some(ReplayStep {
+            record,
+            ws_messages: ws_messages
+                .into_iter()
+                .map(|message| serialize_ws_message::<M>(message, protocol_version))
+                .collect(),
+            rabbitmq_publish: rabbitmq_publish
+                .into_iter()
+                .map(|publish| ReplayedPublish {
+                    exchange: publish.exchange,
+                    routing_key: publish.routing_key,
+                    message: publish.message,
+                })
+                .collect(),
+            exit,
+            result,
+        }))
+    }
+
+    /// Destroy the modules of all participants which are still inside the room
+    pub async fn finish(mut self) {
+        let participants: Vec<_> = self.participants.keys().copied().collect();
+
+        for participant in participants {
+            self.destroy(participant, false).await;
+        }
+    }
+
+    async fn init(&mut self, record: &CaptureRecord) -> Result<Option<ReplayedModule<M>>> {
+        let participant = match (record.participation_kind, record.user_id) {
+            (ParticipationKind::User, Some(user_id)) => {
+                let mut conn = self.db.get_conn()?;
+
+                Participant::User(
+                    User::get(&mut conn, user_id)
+                        .with_context(|| format!("Unknown user {user_id} of captured event"))?,
+                )
+            }
+            (ParticipationKind::User | ParticipationKind::Guest, _) => Participant::Guest,
+            (ParticipationKind::Sip, _) => Participant::Sip,
+            (ParticipationKind::Recorder, _) => Participant::Recorder,
+            (ParticipationKind::Bot, _) => Participant::Bot,
+        };
+
+        let mut events = SelectAll::new();
+        let mut bus = ModuleBus::default();
+
+        let ctx = InitContext {
+            id: record.participant,
+            room: &self.room,
+            breakout_room: record.breakout_room,
+            participant: &participant,
+            role: record.role,
+            region: None,
+            db: &self.db,
+            storage: &self.storage,
+            authz: &self.authz,
+            rabbitmq_exchanges: &mut vec![],
+            rabbitmq_bindings: &mut vec![],
+            events: &mut events,
+            bus: &mut bus,
+            redis_conn: &mut self.redis_conn,
+            m: PhantomData::<fn() -> M>,
+        };
+
+        let module = M::init(ctx, &self.params, "")
+            .await
+            .context("Failed to initialize module")?;
+
+        Ok(module.map(|module| ReplayedModule {
+            module,
+            bus,
+            _events: events,
+        }))
+    }
+
+    async fn destroy(&mut self, participant: ParticipantId, destroy_room: bool) {
+        if let Some(Some(replayed)) = self.participants.remove(&participant) {
+            replayed
+                .module
+                .on_destroy(DestroyContext {
+                    redis_conn: &mut self.redis_conn,
+                    destroy_room,
+                })
+                .await;
+        }
+    }
+}
+
+async fn dispatch<M>(module: &mut M, ctx: ModuleContext<'_, M>, event: CapturedEvent) -> Result<()>
+where
+    M: SignalingModule,
+{
+    match event {
+        CapturedEvent::WsMessage { payload, .. } => {
+            let message = serde_json::from_value(payload).context("Failed to parse WS message")?;
+
+            module.on_event(ctx, Event::WsMessage(message)).await
+        }
+        CapturedEvent::RabbitMq { payload, .. } => {
+            let message =
+                serde_json::from_value(payload).context("Failed to parse RabbitMq message")?;
+
+            module.on_event(ctx, Event::RabbitMq(message)).await
+        }
+        CapturedEvent::Ext { payload, .. } => {
+            let payload = payload.context("Ext event has no payload")?;
+
+            module
+                .on_event(ctx, Event::Ext(M::replay_ext_event(payload)?))
+                .await
+        }
+        CapturedEvent::Joined {
+            control_data,
+            participants,
+        } => {
+            module
+                .on_event(
+                    ctx,
+                    Event::Joined {
+                        control_data: &control_data,
+                        frontend_data: &mut None,
+                        participants: &mut participants.into_iter().map(|id| (id, None)).collect(),
+                    },
+                )
+                .await
+        }
+        CapturedEvent::Leaving => module.on_event(ctx, Event::Leaving).await,
+        CapturedEvent::RaiseHand => module.on_event(ctx, Event::RaiseHand).await,
+        CapturedEvent::LowerHand => module.on_event(ctx, Event::LowerHand).await,
+        CapturedEvent::ParticipantJoined { peer } => {
+            module
+                .on_event(ctx, Event::ParticipantJoined(peer, &mut None))
+                .await
+        }
+        CapturedEvent::ParticipantLeft { peer } => {
+            module.on_event(ctx, Event::ParticipantLeft(peer)).await
+        }
+        CapturedEvent::ParticipantUpdated { peer } => {
+            module
+                .on_event(ctx, Event::ParticipantUpdated(peer, &mut None))
+                .await
+        }
+        CapturedEvent::Destroyed { .. } => unreachable!("destroyed records are not dispatched"),
+    }
+}
+
+fn serialize_ws_message<M>(message: QueuedWsMessage<M::Outgoing>, version: ProtocolVersion) -> Value
+where
+    M: SignalingModule,
+{
+    match message {
+        QueuedWsMessage::Event(message) => serde_json::to_value(NamespacedEvent {
+            namespace: message.namespace,
+            timestamp: message.timestamp,
+            payload: M::adapt_outgoing(&message.payload, version),
+        })
+        .expect("Failed to convert namespaced to json"),
+        QueuedWsMessage::Shared(payload) => serde_json::from_str(payload.for_version(version))
+            .expect("Shared payloads contain valid json"),
+    }
+}
@@ -3,40 +3,52 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use super::actor::WebSocketActor;
+use super::bus::ModuleBus;
 use super::modules::{
-    AnyStream, DynBroadcastEvent, DynEventCtx, DynTargetedEvent, Modules, NoSuchModuleError,
+    AnyStream, DynBroadcastEvent, DynEventCtx, DynTargetedEvent, ModuleBuilder, Modules,
+    NoSuchModuleError,
 };
+use super::shared_payload::SHARED_PAYLOAD_KIND;
 use super::{
     DestroyContext, NamespacedCommand, NamespacedEvent, RabbitMqBinding, RabbitMqExchange,
-    RabbitMqPublish, Timestamp,
+    RabbitMqPublish, SharedPayload, Timestamp,
 };
 use crate::api;
-use crate::api::signaling::metrics::SignalingMetrics;
+use crate::api::signaling::capture::EventRecorder;
+use crate::api::signaling::metrics::{RoomSize, SignalingMetrics};
 use crate::api::signaling::prelude::control::outgoing::JoinBlockedReason;
 use crate::api::signaling::prelude::*;
 use crate::api::signaling::resumption::{ResumptionTokenKeepAlive, ResumptionTokenUsed};
+use crate::api::signaling::room_statistics;
+use crate::api::signaling::sharding;
 use crate::api::signaling::ws::actor::WsCommand;
 use crate::api::signaling::ws_modules::control::outgoing::Participant;
 use crate::api::signaling::ws_modules::control::storage::ParticipantIdRunnerLock;
 use crate::api::signaling::ws_modules::control::{
-    incoming, outgoing, rabbitmq, storage, ControlData, NAMESPACE,
+    incoming, outgoing, rabbitmq, storage, ControlData, ParticipantMetadata, SnapshotData,
+    SnapshotEntry, NAMESPACE,
 };
 use crate::api::signaling::{Role, SignalingRoomId};
+use crate::api::v1::room_branding;
 use crate::api::v1::tariffs::TariffResource;
-use crate::redis_wrapper::RedisConnection;
+use crate::redis_wrapper::{self, RedisConnection};
+use crate::services::{error_reporting, ErrorReportingService, NotificationService};
 use crate::storage::ObjectStorage;
 use actix::Addr;
 use actix_http::ws::{CloseCode, CloseReason, Message};
 use actix_web_actors::ws;
 use anyhow::{bail, Context, Result};
+use batching::ParticipantEventBatch;
 use chrono::TimeZone;
-use controller_shared::settings::SharedSettings;
+use controller_shared::settings::{NotificationEvent, SharedSettings};
 use database::Db;
+use db_storage::call_feedback::NewCallFeedback;
 use db_storage::rooms::Room;
 use db_storage::tariffs::Tariff;
 use db_storage::users::User;
 use futures::stream::SelectAll;
 use futures::Future;
+use inactivity::{InactivityEvent, InactivityTimer};
 use itertools::Itertools;
 use kustos::Authz;
 use lapin::message::DeliveryResult;
@@ -45,9 +57,9 @@ use lapin::{BasicProperties, ExchangeKind};
 use lapin_pool::RabbitMqChannel;
 use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future;
-use std::mem::replace;
+use std::mem::{replace, take};
 use std::ops::ControlFlow;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -58,6 +70,8 @@ use tokio_stream::StreamExt;
 use types::core::{BreakoutRoomId, ParticipantId, ParticipationKind, UserId};
 use uuid::Uuid;
 
+mod batching;
+mod inactivity;
 mod sip;
 
 // The expiry in seconds for the `skip_waiting_room` key in Redis
@@ -66,30 +80,91 @@ const SKIP_WAITING_ROOM_KEY_REFRESH_INTERVAL: u64 = 60;
 
 /// Builder to the runner type.
 ///
-/// Passed into [`ModuleBuilder::build`](super::modules::ModuleBuilder::build) function to create an [`InitContext`](super::InitContext).
+/// Modules are added using [`Builder::build_module`].
 pub struct Builder {
     runner_id: Uuid,
     pub(super) id: ParticipantId,
     resuming: bool,
     pub(super) room: Room,
+    room_owners: Vec<UserId>,
     pub(super) breakout_room: Option<BreakoutRoomId>,
     pub(super) participant: api::Participant<User>,
     pub(super) role: Role,
+    pub(super) region: Option<String>,
     pub(super) protocol: &'static str,
     pub(super) metrics: Arc<SignalingMetrics>,
+    notifications: NotificationService,
+    error_reporting: ErrorReportingService,
     pub(super) modules: Modules,
     pub(super) rabbitmq_exchanges: Vec<RabbitMqExchange>,
     pub(super) rabbitmq_bindings: Vec<RabbitMqBinding>,
     pub(super) events: SelectAll<AnyStream>,
+    pub(super) bus: ModuleBus,
     pub(super) db: Arc<Db>,
     pub(super) storage: Arc<ObjectStorage>,
     pub(super) authz: Arc<Authz>,
     pub(super) redis_conn: RedisConnection,
     pub(super) rabbitmq_channel: RabbitMqChannel,
     resumption_keep_alive: ResumptionTokenKeepAlive,
+    module_builders: Vec<Box<dyn ModuleBuilder>>,
+}
+
+/// Passed into [`ModuleBuilder::build`] to create an [`InitContext`](super::InitContext)
+pub struct ModuleInit<'a> {
+    pub(super) id: ParticipantId,
+    pub(super) room: &'a Room,
+    pub(super) breakout_room: Option<BreakoutRoomId>,
+    pub(super) participant: &'a api::Participant<User>,
+    pub(super) role: Role,
+    pub(super) region: Option<&'a str>,
+    pub(super) protocol: &'static str,
+    pub(super) db: &'a Arc<Db>,
+    pub(super) storage: &'a Arc<ObjectStorage>,
+    pub(super) authz: &'a Arc<Authz>,
+    pub(super) modules: &'a mut Modules,
+    pub(super) rabbitmq_exchanges: &'a mut Vec<RabbitMqExchange>,
+    pub(super) rabbitmq_bindings: &'a mut Vec<RabbitMqBinding>,
+    pub(super) events: &'a mut SelectAll<AnyStream>,
+    pub(super) bus: &'a mut ModuleBus,
+    pub(super) redis_conn: &'a mut RedisConnection,
 }
 
 impl Builder {
+    /// Set the region of the participant, passed to the modules on initialization
+    pub fn set_region(&mut self, region: String) {
+        self.region = Some(region);
+    }
+
+    /// Initialize the module for the participant
+    ///
+    /// The module is initialized again if the participant switches into another breakout room.
+    pub async fn build_module(&mut self, module: &dyn ModuleBuilder) -> Result<()> {
+        module
+            .build(ModuleInit {
+                id: self.id,
+                room: &self.room,
+                breakout_room: self.breakout_room,
+                participant: &self.participant,
+                role: self.role,
+                region: self.region.as_deref(),
+                protocol: self.protocol,
+                db: &self.db,
+                storage: &self.storage,
+                authz: &self.authz,
+                modules: &mut self.modules,
+                rabbitmq_exchanges: &mut self.rabbitmq_exchanges,
+                rabbitmq_bindings: &mut self.rabbitmq_bindings,
+                events: &mut self.events,
+                bus: &mut self.bus,
+                redis_conn: &mut self.redis_conn,
+            })
+            .await?;
+
+        self.module_builders.push(module.clone_boxed());
+
+        Ok(())
+    }
+
     /// Abort the building process and destroy all already built modules
     #[tracing::instrument(skip(self))]
     pub async fn abort(mut self) {
@@ -142,6 +217,10 @@ impl Builder {
 
         let room_id = SignalingRoomId(self.room.id, self.breakout_room);
 
+        // All supported protocols carry a version, fall back to the latest one for unversioned test protocols
+        let protocol_version =
+            ProtocolVersion::from_protocol(self.protocol).unwrap_or(ProtocolVersion::LATEST);
+
         // The name of the room exchange
         let room_exchange = rabbitmq::current_room_exchange_name(room_id);
 
@@ -208,6 +287,14 @@ impl Builder {
             options: Default::default(),
         });
 
+        // Bindings to the global room exchange are kept when switching into another breakout room
+        let room_bindings = self
+            .rabbitmq_bindings
+            .iter()
+            .filter(|binding| binding.exchange != global_room_exchange)
+            .cloned()
+            .collect();
+
         // ==== BEGIN GENERIC SETUP ====
 
         // Create the queue for this participant
@@ -308,34 +395,77 @@ impl Builder {
             .set_initial(&mut self.redis_conn)
             .await?;
 
+        // Recorder, SIP and bot participants have no client which could show activity
+        let inactivity = match &self.participant {
+            api::Participant::User(_) | api::Participant::Guest => settings
+                .load()
+                .inactivity
+                .as_ref()
+                .map(InactivityTimer::new),
+            api::Participant::Sip | api::Participant::Recorder | api::Participant::Bot => None,
+        };
+
+        let participant_event_batch_delay = settings.load().rooms.participant_event_batch_delay;
+        let participant_events = if participant_event_batch_delay.is_zero() {
+            None
+        } else {
+            Some(ParticipantEventBatch::new(participant_event_batch_delay))
+        };
+
+        self.modules.set_recorder(EventRecorder::new(
+            &settings.load(),
+            room_id,
+            self.id,
+            &self.participant,
+            self.role,
+        ));
+
         Ok(Runner {
             runner_id: self.runner_id,
             id: self.id,
             resuming: self.resuming,
             room: self.room,
+            room_owners: self.room_owners,
             room_id,
             participant: self.participant,
             role: self.role,
+            region: self.region,
+            protocol: self.protocol,
             state: RunnerState::None,
             ws: Ws {
                 to_actor: to_ws_actor,
                 from_actor: from_ws_actor,
+                metrics: self.metrics.clone(),
                 state: State::Open,
             },
             modules: self.modules,
+            module_builders: self.module_builders,
             events: self.events,
+            bus: self.bus,
             metrics: self.metrics,
+            notifications: self.notifications,
+            error_reporting: self.error_reporting,
+            protocol_version,
             db: self.db,
+            storage: self.storage,
+            authz: self.authz,
             redis_conn: self.redis_conn,
+            queue_name: queue.name().as_str().to_owned(),
             consumer,
             consumer_delegated: false,
             rabbitmq_channel: self.rabbitmq_channel,
             room_exchange,
+            room_bindings,
             resumption_keep_alive: self.resumption_keep_alive,
             shutdown_sig,
             exit: false,
             settings,
             time_limit_future: Box::pin(future::pending()),
+            inactivity,
+            participant_events,
+            room_participant_count: 0,
+            feedback_submitted: false,
+            clock_offset_ms: None,
         })
     }
 }
@@ -359,6 +489,9 @@ pub struct Runner {
     /// The database repr of the current room at the time of joining
     room: Room,
 
+    /// Owners of the room at the time of joining, they are always moderators
+    room_owners: Vec<UserId>,
+
     /// Full signaling room id
     room_id: SignalingRoomId,
 
@@ -368,6 +501,12 @@ pub struct Runner {
     /// The role of the participant inside the room
     role: Role,
 
+    /// Region of the participant as reported by the load balancer
+    region: Option<String>,
+
+    /// Negotiated websocket subprotocol, passed to the modules on initialization
+    protocol: &'static str,
+
     /// The control data. Initialized when frontend send join
     state: RunnerState,
 
@@ -376,17 +515,35 @@ pub struct Runner {
 
     /// All registered and initialized modules
     modules: Modules,
+    /// Builders of the modules, used to initialize the modules again when switching into another breakout room
+    module_builders: Vec<Box<dyn ModuleBuilder>>,
     events: SelectAll<AnyStream>,
+    bus: ModuleBus,
 
     /// Signaling metrics for this runner
     metrics: Arc<SignalingMetrics>,
 
+    /// Notifies chat services and webhooks about the start of the meeting
+    notifications: NotificationService,
+
+    /// Reports errors of the modules
+    error_reporting: ErrorReportingService,
+
+    /// Signaling protocol version negotiated on the websocket upgrade
+    protocol_version: ProtocolVersion,
+
     /// Database connection pool
     db: Arc<Db>,
 
+    storage: Arc<ObjectStorage>,
+    authz: Arc<Authz>,
+
     /// Redis connection manager
     redis_conn: RedisConnection,
 
+    /// Name of the RabbitMQ queue of this participant
+    queue_name: String,
+
     /// RabbitMQ queue consumer for this participant, will contain any events about room and
     /// participant changes
     consumer: lapin::Consumer,
@@ -398,6 +555,9 @@ pub struct Runner {
     /// Name of the rabbitmq room exchange
     room_exchange: String,
 
+    /// Bindings of the queue which are specific to the current room, excluding the binding of the user routing key
+    room_bindings: Vec<RabbitMqBinding>,
+
     /// Util to keep the resumption token alive
     resumption_keep_alive: ResumptionTokenKeepAlive,
 
@@ -411,6 +571,21 @@ pub struct Runner {
     settings: SharedSettings,
 
     time_limit_future: Pin<Box<dyn Future<Output = ()>>>,
+
+    /// Disconnects the participant after a configured time of inactivity
+    inactivity: Option<InactivityTimer>,
+
+    /// Collects participant events in large rooms, processing them in batches
+    participant_events: Option<ParticipantEventBatch>,
+
+    /// Number of participants inside the room as seen by this runner, only used to label metrics
+    room_participant_count: usize,
+
+    /// Set when the participant submitted feedback on the call, only one feedback is accepted per connection
+    feedback_submitted: bool,
+
+    /// Offset of the client clock estimated from the join message, sent with the join success
+    clock_offset_ms: Option<i64>,
 }
 
 impl Drop for Runner {
@@ -448,10 +623,13 @@ impl Runner {
         id: ParticipantId,
         resuming: bool,
         room: Room,
+        room_owners: Vec<UserId>,
         breakout_room: Option<BreakoutRoomId>,
         participant: api::Participant<User>,
         protocol: &'static str,
         metrics: Arc<SignalingMetrics>,
+        notifications: NotificationService,
+        error_reporting: ErrorReportingService,
         db: Arc<Db>,
         storage: Arc<ObjectStorage>,
         authz: Arc<Authz>,
@@ -462,15 +640,16 @@ impl Runner {
         // TODO(r.floren) Change this when the permissions system gets introduced
         let role = match &participant {
             api::Participant::User(user) => {
-                if user.id == room.created_by {
+                if room_owners.contains(&user.id) {
                     Role::Moderator
                 } else {
                     Role::User
                 }
             }
-            api::Participant::Guest | api::Participant::Sip | api::Participant::Recorder => {
-                Role::Guest
-            }
+            api::Participant::Guest
+            | api::Participant::Sip
+            | api::Participant::Recorder
+            | api::Participant::Bot => Role::Guest,
         };
 
         Builder {
@@ -478,21 +657,27 @@ impl Runner {
             id,
             resuming,
             room,
+            room_owners,
             breakout_room,
             participant,
             role,
+            region: None,
             protocol,
             metrics,
+            notifications,
+            error_reporting,
             modules: Default::default(),
             rabbitmq_exchanges: vec![],
             rabbitmq_bindings: vec![],
             events: SelectAll::new(),
+            bus: Default::default(),
             db,
             storage,
             authz,
             redis_conn,
             rabbitmq_channel,
             resumption_keep_alive,
+            module_builders: vec![],
         }
     }
 
@@ -527,98 +712,190 @@ impl Runner {
 
         self.delegate_consumer();
 
+        self.record_connection_event(connection_history::ConnectionEventKind::Disconnected)
+            .await;
+
         if let RunnerState::Joined | RunnerState::Waiting { .. } = &self.state {
-            // The retry/wait_time values are set extra high
-            // since a lot of operations are being done while holding the lock
-            let mut room_mutex = storage::room_mutex(self.room_id);
-
-            let room_guard = match room_mutex.lock(&mut self.redis_conn).await {
-                Ok(guard) => guard,
-                Err(r3dlock::Error::Redis(e)) => {
-                    log::error!("Failed to acquire r3dlock, {}", e);
+            match self.leave_room(false).await {
+                Ok(leave_error) => encountered_error |= leave_error,
+                Err(e) => {
                     // There is a problem when accessing redis which could
                     // mean either the network or redis is broken.
                     // Both cases cannot be handled here, abort the cleanup
+                    log::error!("{:?}", e);
 
                     self.metrics
                         .record_destroy_time(destroy_start_time.elapsed().as_secs_f64(), false);
 
                     return;
                 }
-                Err(r3dlock::Error::CouldNotAcquireLock) => {
-                    log::error!("Failed to acquire r3dlock, contention too high");
+            }
+        } else {
+            // Not joined, just destroy modules normal
+            let ctx = DestroyContext {
+                redis_conn: &mut self.redis_conn,
+                destroy_room: false,
+            };
 
-                    self.metrics
-                        .record_destroy_time(destroy_start_time.elapsed().as_secs_f64(), false);
+            self.modules.destroy(ctx).await;
+        }
 
-                    return;
-                }
-                Err(r3dlock::Error::FailedToUnlock | r3dlock::Error::AlreadyExpired) => {
-                    unreachable!()
-                }
-            };
+        // Cancel subscription to not poison the rabbitmq channel with unacknowledged messages
+        if let Err(e) = self
+            .rabbitmq_channel
+            .basic_cancel(self.consumer.tag().as_str(), Default::default())
+            .await
+        {
+            log::error!("Failed to cancel consumer, {}", e);
+            encountered_error = true;
+        }
 
-            if let RunnerState::Joined = &self.state {
-                // first check if the list of joined participant is empty
-                if let Err(e) = storage::set_attribute(
-                    &mut self.redis_conn,
-                    self.room_id,
-                    self.id,
-                    "left_at",
-                    Timestamp::now(),
-                )
-                .await
-                {
-                    log::error!("failed to mark participant as left, {:?}", e);
-                    encountered_error = true;
+        // release participant id
+        match redis::cmd("GETDEL")
+            .arg(ParticipantIdRunnerLock { id: self.id })
+            .query_async::<_, String>(&mut self.redis_conn)
+            .await
+        {
+            Ok(runner_id) => {
+                if runner_id != self.runner_id.to_string() {
+                    log::warn!("removed runner id does not match the id of the runner");
                 }
-            } else if let RunnerState::Waiting { .. } = &self.state {
-                if let Err(e) = moderation::storage::waiting_room_remove(
-                    &mut self.redis_conn,
-                    self.room_id.room_id(),
-                    self.id,
-                )
-                .await
-                {
+            }
+            Err(e) => {
+                log::error!("failed to remove participant id, {}", e);
+                encountered_error = true;
+            }
+        }
+
+        self.metrics.record_destroy_time(
+            destroy_start_time.elapsed().as_secs_f64(),
+            !encountered_error,
+        );
+
+        // If a Close frame is received from the websocket actor, manually return a close command
+        if close_ws {
+            self.ws.close(CloseCode::Normal).await;
+        }
+    }
+
+    /// Leave the current room or waiting room, destroying the modules
+    ///
+    /// When `switching` into another breakout room the participant is not removed from the room's participant count.
+    ///
+    /// Returns an error if the room lock could not be acquired, otherwise if any error was encountered during the cleanup.
+    async fn leave_room(&mut self, switching: bool) -> Result<bool> {
+        let mut encountered_error = false;
+
+        // The retry/wait_time values are set extra high
+        // since a lot of operations are being done while holding the lock
+        let mut room_mutex = storage::room_mutex(self.room_id);
+
+        let room_guard = match room_mutex.lock(&mut self.redis_conn).await {
+            Ok(guard) => guard,
+            Err(r3dlock::Error::Redis(e)) => bail!("Failed to acquire r3dlock, {}", e),
+            Err(r3dlock::Error::CouldNotAcquireLock) => {
+                bail!("Failed to acquire r3dlock, contention too high")
+            }
+            Err(r3dlock::Error::FailedToUnlock | r3dlock::Error::AlreadyExpired) => {
+                unreachable!()
+            }
+        };
+
+        let mut lowered_hand = false;
+
+        if let RunnerState::Joined = &self.state {
+            if let Err(e) = self.record_participant_time().await {
+                log::error!(
+                    "Failed to record participant time in room statistics, {:?}",
+                    e
+                );
+            }
+
+            // first check if the list of joined participant is empty
+            if let Err(e) = storage::set_attribute(
+                &mut self.redis_conn,
+                self.room_id,
+                self.id,
+                "left_at",
+                Timestamp::now(),
+            )
+            .await
+            {
+                log::error!("failed to mark participant as left, {:?}", e);
+                encountered_error = true;
+            }
+
+            match storage::remove_raised_hand(&mut self.redis_conn, self.room_id, self.id).await {
+                Ok(removed) => lowered_hand = removed,
+                Err(e) => {
                     log::error!(
-                        "failed to remove participant from waiting_room list, {:?}",
+                        "failed to remove participant from the raised hands, {:?}",
                         e
                     );
                     encountered_error = true;
                 }
-                if let Err(e) = moderation::storage::waiting_room_accepted_remove(
+            }
+        } else if let RunnerState::Waiting { .. } = &self.state {
+            if let Err(e) = moderation::storage::waiting_room_remove(
+                &mut self.redis_conn,
+                self.room_id.room_id(),
+                self.id,
+            )
+            .await
+            {
+                log::error!(
+                    "failed to remove participant from waiting_room list, {:?}",
+                    e
+                );
+                encountered_error = true;
+            }
+            if let Err(e) = moderation::storage::waiting_room_accepted_remove(
+                &mut self.redis_conn,
+                self.room_id.room_id(),
+                self.id,
+            )
+            .await
+            {
+                log::error!(
+                    "failed to remove participant from waiting_room_accepted list, {:?}",
+                    e
+                );
+                encountered_error = true;
+            }
+        };
+
+        let room_is_empty =
+            match storage::participants_all_left(&mut self.redis_conn, self.room_id).await {
+                Ok(room_is_empty) => room_is_empty,
+                Err(e) => {
+                    log::error!("Failed to check if room is empty {:?}", e);
+                    encountered_error = true;
+                    false
+                }
+            };
+
+        // if the room is empty check that the waiting room is empty
+        let destroy_room = if room_is_empty {
+            if self.room_id.1.is_some() {
+                // Breakout rooms are destroyed even with participants inside the waiting room
+                true
+            } else {
+                // destroy room only if waiting room is empty
+                let waiting_room_is_empty = match moderation::storage::waiting_room_len(
                     &mut self.redis_conn,
                     self.room_id.room_id(),
-                    self.id,
                 )
                 .await
                 {
-                    log::error!(
-                        "failed to remove participant from waiting_room_accepted list, {:?}",
-                        e
-                    );
-                    encountered_error = true;
-                }
-            };
-
-            let room_is_empty =
-                match storage::participants_all_left(&mut self.redis_conn, self.room_id).await {
-                    Ok(room_is_empty) => room_is_empty,
+                    Ok(waiting_room_len) => waiting_room_len == 0,
                     Err(e) => {
-                        log::error!("Failed to check if room is empty {:?}", e);
+                        log::error!("failed to get waiting room len, {:?}", e);
                         encountered_error = true;
                         false
                     }
                 };
-
-            // if the room is empty check that the waiting room is empty
-            let destroy_room = if room_is_empty {
-                if self.room_id.1.is_some() {
-                    // Breakout rooms are destroyed even with participants inside the waiting room
-                    true
-                } else {
-                    // destroy room only if waiting room is empty
-                    let waiting_room_is_empty = match moderation::storage::waiting_room_len(
+                let waiting_room_accepted_is_empty =
+                    match moderation::storage::waiting_room_accepted_len(
                         &mut self.redis_conn,
                         self.room_id.room_id(),
                     )
@@ -626,36 +903,44 @@ impl Runner {
                     {
                         Ok(waiting_room_len) => waiting_room_len == 0,
                         Err(e) => {
-                            log::error!("failed to get waiting room len, {:?}", e);
+                            log::error!("failed to get accepted waiting room len, {:?}", e);
                             encountered_error = true;
                             false
                         }
                     };
-                    let waiting_room_accepted_is_empty =
-                        match moderation::storage::waiting_room_accepted_len(
-                            &mut self.redis_conn,
-                            self.room_id.room_id(),
-                        )
-                        .await
-                        {
-                            Ok(waiting_room_len) => waiting_room_len == 0,
-                            Err(e) => {
-                                log::error!("failed to get accepted waiting room len, {:?}", e);
-                                encountered_error = true;
-                                false
-                            }
-                        };
-                    waiting_room_is_empty && waiting_room_accepted_is_empty
+                waiting_room_is_empty && waiting_room_accepted_is_empty
+            }
+        } else {
+            false
+        };
+
+        // Empty main rooms are kept for the configured grace period and destroyed by the empty room sweeper
+        let grace_period = self.settings.load().rooms.empty_room_grace_period;
+
+        let destroy_room =
+            destroy_room && (self.room_id.breakout_room_id().is_some() || grace_period.is_zero());
+
+        // The participant stays inside the room when switching into another breakout room
+        if !switching {
+            if self.is_recorded_in_statistics() {
+                if let Err(e) =
+                    storage::record_room_statistics_leave(&mut self.redis_conn, self.room.id).await
+                {
+                    log::error!("Failed to record the leave in room statistics, {:?}", e);
                 }
-            } else {
-                false
-            };
+            }
 
             match storage::decrement_participant_count(&mut self.redis_conn, self.room.id).await {
                 Ok(remaining_participant_count) => {
                     if remaining_participant_count == 0 {
-                        if let Err(e) = self.cleanup_redis_for_global_room().await {
-                            log::error!("failed to mark participant as left, {:?}", e);
+                        if grace_period.is_zero() {
+                            if let Err(e) = self.cleanup_redis_for_global_room().await {
+                                log::error!("failed to mark participant as left, {:?}", e);
+                                encountered_error = true;
+                            }
+                        } else if let Err(e) = self.schedule_empty_room_destroy(grace_period).await
+                        {
+                            log::error!("Failed to schedule empty room destroy, {:?}", e);
                             encountered_error = true;
                         }
                     }
@@ -665,117 +950,380 @@ impl Runner {
                     encountered_error = true;
                 }
             }
+        }
 
-            let ctx = DestroyContext {
-                redis_conn: &mut self.redis_conn,
-                destroy_room,
-            };
-
-            self.modules.destroy(ctx).await;
+        let ctx = DestroyContext {
+            redis_conn: &mut self.redis_conn,
+            destroy_room,
+        };
 
-            if destroy_room {
-                if let Err(e) = self.cleanup_redis_keys_for_current_room().await {
-                    log::error!("Failed to remove all control attributes, {}", e);
-                    encountered_error = true;
-                }
+        self.modules.destroy(ctx).await;
 
-                self.metrics.increment_destroyed_rooms_count();
+        if destroy_room {
+            if let Err(e) = self.cleanup_redis_keys_for_current_room().await {
+                log::error!("Failed to remove all control attributes, {}", e);
+                encountered_error = true;
             }
 
+            self.metrics.increment_destroyed_rooms_count();
+        }
+
+        if !switching {
             self.metrics.decrement_participants_count(&self.participant);
+        }
 
-            if let Err(e) = room_guard.unlock(&mut self.redis_conn).await {
-                log::error!("Failed to unlock set_guard r3dlock, {}", e);
-                encountered_error = true;
-            }
+        if let Err(e) = room_guard.unlock(&mut self.redis_conn).await {
+            log::error!("Failed to unlock set_guard r3dlock, {}", e);
+            encountered_error = true;
+        }
 
-            if !destroy_room {
-                match &self.state {
-                    RunnerState::None => unreachable!("state was checked before"),
-                    RunnerState::Waiting { .. } => {
-                        self.rabbitmq_publish(
+        if !destroy_room {
+            match &self.state {
+                RunnerState::None => unreachable!("state was checked before"),
+                RunnerState::Waiting { .. } => {
+                    self.rabbitmq_publish(
+                        Timestamp::now(),
+                        Some(&breakout::rabbitmq::global_exchange_name(
+                            self.room_id.room_id(),
+                        )),
+                        control::rabbitmq::room_all_routing_key(),
+                        serde_json::to_string(&NamespacedCommand {
+                            namespace: moderation::NAMESPACE,
+                            payload: moderation::rabbitmq::Message::LeftWaitingRoom(self.id),
+                        })
+                        .expect("Failed to convert namespaced to json"),
+                    )
+                    .await;
+                }
+                RunnerState::Joined => {
+                    // Skip sending the left message.
+                    // TODO:(kbalt): The left message is the only message not sent by the recorder, all other
+                    // messages are currently ignored by filtering in the `build_participant` function
+                    // It'd might be nicer to have a "visibility" check before sending any "joined"/"updated"/"left"
+                    // message
+                    if !matches!(&self.participant, api::Participant::Recorder) {
+                        self.rabbitmq_publish_control(
                             Timestamp::now(),
-                            Some(&breakout::rabbitmq::global_exchange_name(
-                                self.room_id.room_id(),
-                            )),
-                            control::rabbitmq::room_all_routing_key(),
-                            serde_json::to_string(&NamespacedCommand {
-                                namespace: moderation::NAMESPACE,
-                                payload: moderation::rabbitmq::Message::LeftWaitingRoom(self.id),
-                            })
-                            .expect("Failed to convert namespaced to json"),
+                            None,
+                            rabbitmq::Message::Left(self.id),
                         )
                         .await;
                     }
-                    RunnerState::Joined => {
-                        // Skip sending the left message.
-                        // TODO:(kbalt): The left message is the only message not sent by the recorder, all other
-                        // messages are currently ignored by filtering in the `build_participant` function
-                        // It'd might be nicer to have a "visibility" check before sending any "joined"/"updated"/"left"
-                        // message
-                        if !matches!(&self.participant, api::Participant::Recorder) {
-                            self.rabbitmq_publish_control(
-                                Timestamp::now(),
-                                None,
-                                rabbitmq::Message::Left(self.id),
-                            )
-                            .await;
-                        }
+
+                    if self.room.webinar_mode && lowered_hand {
+                        self.rabbitmq_publish_control(
+                            Timestamp::now(),
+                            None,
+                            rabbitmq::Message::RaisedHandsUpdated,
+                        )
+                        .await;
                     }
                 }
             }
-        } else {
-            // Not joined, just destroy modules normal
-            let ctx = DestroyContext {
-                redis_conn: &mut self.redis_conn,
-                destroy_room: false,
-            };
+        }
 
-            self.modules.destroy(ctx).await;
+        Ok(encountered_error)
+    }
+
+    /// Move the participant from the current room into the given breakout room, or the main room if `None`
+    ///
+    /// The participant leaves the current room like when disconnecting and joins the other room without having to
+    /// reconnect. The modules are initialized again for the other room, media sessions have to be published again by
+    /// the client.
+    async fn switch_breakout_room(
+        &mut self,
+        timestamp: Timestamp,
+        breakout_room: Option<BreakoutRoomId>,
+    ) -> Result<()> {
+        let control_data =
+            ControlData::from_redis(&mut self.redis_conn, self.room_id, self.id).await?;
+
+        let actions = self
+            .handle_module_broadcast_event(timestamp, DynBroadcastEvent::Leaving, false)
+            .await;
+
+        self.handle_module_requested_actions(timestamp, actions)
+            .await;
+
+        if self.leave_room(true).await? {
+            log::warn!("Encountered errors while leaving room {}", self.room_id);
         }
 
-        // Cancel subscription to not poison the rabbitmq channel with unacknowledged messages
+        // Events of the previous room must not be processed in the new room
+        if let Some(participant_events) = &mut self.participant_events {
+            participant_events.clear();
+        }
+
+        self.state = RunnerState::None;
+
+        self.unbind_room().await?;
+
+        self.room_id = SignalingRoomId(self.room.id, breakout_room);
+        error_reporting::set_participant(self.room_id, self.id);
+        self.room_exchange = rabbitmq::current_room_exchange_name(self.room_id);
+
+        self.resumption_keep_alive.set_breakout_room(breakout_room);
+
         if let Err(e) = self
-            .rabbitmq_channel
-            .basic_cancel(self.consumer.tag().as_str(), Default::default())
+            .resumption_keep_alive
+            .refresh(&mut self.redis_conn)
             .await
         {
-            log::error!("Failed to cancel consumer, {}", e);
-            encountered_error = true;
+            log::warn!(
+                "Failed to refresh resumption token after switching room, {:?}",
+                e
+            );
         }
 
-        // release participant id
-        match redis::cmd("GETDEL")
-            .arg(ParticipantIdRunnerLock { id: self.id })
-            .query_async::<_, String>(&mut self.redis_conn)
-            .await
+        self.init_modules().await?;
+
+        self.set_control_attributes(
+            timestamp,
+            &control_data.display_name,
+            control_data.avatar_url.as_deref(),
+        )
+        .await?;
+
+        if !control_data.metadata.is_empty() {
+            storage::set_attribute(
+                &mut self.redis_conn,
+                self.room_id,
+                self.id,
+                "metadata",
+                &control_data.metadata,
+            )
+            .await?;
+        }
+
+        let control_data = ControlData {
+            role: self.role,
+            joined_at: timestamp,
+            hand_is_up: false,
+            hand_updated_at: timestamp,
+            left_at: None,
+            ..control_data
+        };
+
+        // The tariff of the room has already been enforced when joining the first room
+        self.join_room(timestamp, control_data, true).await
+    }
+
+    /// Remove all queue bindings specific to the current room
+    async fn unbind_room(&mut self) -> Result<()> {
+        for RabbitMqBinding {
+            routing_key,
+            exchange,
+            ..
+        } in take(&mut self.room_bindings)
         {
-            Ok(runner_id) => {
-                if runner_id != self.runner_id.to_string() {
-                    log::warn!("removed runner id does not match the id of the runner");
-                }
-            }
-            Err(e) => {
-                log::error!("failed to remove participant id, {}", e);
-                encountered_error = true;
+            log::debug!(
+                "Removing queue binding: name={:?} routing_key={:?} exchange={:?}",
+                self.queue_name,
+                routing_key,
+                exchange
+            );
+
+            self.rabbitmq_channel
+                .queue_unbind(
+                    &self.queue_name,
+                    &exchange,
+                    &routing_key,
+                    Default::default(),
+                )
+                .await?;
+        }
+
+        if let api::Participant::User(user) = &self.participant {
+            self.rabbitmq_channel
+                .queue_unbind(
+                    &self.queue_name,
+                    &self.room_exchange,
+                    &rabbitmq::room_user_routing_key(user.id),
+                    Default::default(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Initialize all modules for the current room and bind the queue to the room exchange
+    ///
+    /// Counterpart of the module initialization done by the [`Builder`].
+    async fn init_modules(&mut self) -> Result<()> {
+        let mut modules = Modules::default();
+        let mut rabbitmq_exchanges = vec![];
+        let mut rabbitmq_bindings = vec![];
+        let mut events = SelectAll::new();
+        let mut bus = ModuleBus::default();
+
+        for module in &self.module_builders {
+            let res = module
+                .build(ModuleInit {
+                    id: self.id,
+                    room: &self.room,
+                    breakout_room: self.room_id.breakout_room_id(),
+                    participant: &self.participant,
+                    role: self.role,
+                    region: self.region.as_deref(),
+                    protocol: self.protocol,
+                    db: &self.db,
+                    storage: &self.storage,
+                    authz: &self.authz,
+                    modules: &mut modules,
+                    rabbitmq_exchanges: &mut rabbitmq_exchanges,
+                    rabbitmq_bindings: &mut rabbitmq_bindings,
+                    events: &mut events,
+                    bus: &mut bus,
+                    redis_conn: &mut self.redis_conn,
+                })
+                .await;
+
+            if let Err(e) = res {
+                let ctx = DestroyContext {
+                    redis_conn: &mut self.redis_conn,
+                    destroy_room: false,
+                };
+
+                modules.destroy(ctx).await;
+
+                return Err(e);
             }
         }
 
-        self.metrics.record_destroy_time(
-            destroy_start_time.elapsed().as_secs_f64(),
-            !encountered_error,
+        let global_room_exchange = breakout::rabbitmq::global_exchange_name(self.room.id);
+
+        rabbitmq_exchanges.insert(
+            0,
+            RabbitMqExchange {
+                name: self.room_exchange.clone(),
+                kind: ExchangeKind::Topic,
+                options: Default::default(),
+            },
         );
 
-        // If a Close frame is received from the websocket actor, manually return a close command
-        if close_ws {
-            self.ws.close(CloseCode::Normal).await;
+        rabbitmq_bindings.push(RabbitMqBinding {
+            routing_key: rabbitmq::room_all_routing_key().into(),
+            exchange: self.room_exchange.clone(),
+            options: Default::default(),
+        });
+
+        rabbitmq_bindings.push(RabbitMqBinding {
+            routing_key: rabbitmq::room_participant_routing_key(self.id),
+            exchange: self.room_exchange.clone(),
+            options: Default::default(),
+        });
+
+        for RabbitMqExchange {
+            name,
+            kind,
+            options,
+        } in rabbitmq_exchanges
+        {
+            self.rabbitmq_channel
+                .exchange_declare(&name, kind, options, Default::default())
+                .await?;
+        }
+
+        // The global room exchange is bound for the whole lifetime of the runner
+        let rabbitmq_bindings: Vec<_> = rabbitmq_bindings
+            .into_iter()
+            .filter(|binding| binding.exchange != global_room_exchange)
+            .collect();
+
+        for RabbitMqBinding {
+            routing_key,
+            exchange,
+            options,
+        } in rabbitmq_bindings.clone()
+        {
+            log::debug!(
+                "Creating queue binding: name={:?} routing_key={:?} exchange={:?}",
+                self.queue_name,
+                routing_key,
+                exchange
+            );
+
+            self.rabbitmq_channel
+                .queue_bind(
+                    &self.queue_name,
+                    &exchange,
+                    &routing_key,
+                    options,
+                    Default::default(),
+                )
+                .await?;
+        }
+
+        if let api::Participant::User(user) = &self.participant {
+            self.rabbitmq_channel
+                .queue_bind(
+                    &self.queue_name,
+                    &self.room_exchange,
+                    &rabbitmq::room_user_routing_key(user.id),
+                    Default::default(),
+                    Default::default(),
+                )
+                .await?;
         }
+
+        modules.set_recorder(EventRecorder::new(
+            &self.settings.load(),
+            self.room_id,
+            self.id,
+            &self.participant,
+            self.role,
+        ));
+
+        self.room_bindings = rabbitmq_bindings;
+        self.modules = modules;
+        self.events = events;
+        self.bus = bus;
+
+        Ok(())
+    }
+
+    /// Services like the recorder and bots are no participants of the room's session
+    fn is_recorded_in_statistics(&self) -> bool {
+        !matches!(
+            self.participant,
+            api::Participant::Recorder | api::Participant::Bot
+        )
+    }
+
+    /// Add the time the participant spent inside the room to the room statistics
+    async fn record_participant_time(&mut self) -> Result<()> {
+        if !self.is_recorded_in_statistics() {
+            return Ok(());
+        }
+
+        let joined_at: Option<Timestamp> =
+            storage::get_attribute(&mut self.redis_conn, self.room_id, self.id, "joined_at")
+                .await?;
+
+        if let Some(joined_at) = joined_at {
+            let seconds = Timestamp::now()
+                .signed_duration_since(*joined_at)
+                .num_seconds()
+                .max(0);
+
+            storage::record_room_statistics_participant_time(
+                &mut self.redis_conn,
+                self.room.id,
+                seconds,
+            )
+            .await?;
+        }
+
+        Ok(())
     }
 
     /// Remove all room and control module related data from redis for the current 'local' room/breakout-room. Does not
     /// touch any keys that contain 'global' data that is used across all 'sub'-rooms (main & breakout rooms).
     async fn cleanup_redis_keys_for_current_room(&mut self) -> Result<()> {
         storage::remove_room_closes_at(&mut self.redis_conn, self.room_id).await?;
+        storage::delete_raised_hands(&mut self.redis_conn, self.room_id).await?;
+        storage::delete_participant_snapshot(&mut self.redis_conn, self.room_id).await?;
         storage::remove_participant_set(&mut self.redis_conn, self.room_id).await?;
         storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "display_name").await?;
         storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "role").await?;
@@ -786,20 +1334,39 @@ impl Runner {
             .await?;
         storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "kind").await?;
         storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "user_id").await?;
+        storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "metadata").await?;
         storage::remove_attribute_key(&mut self.redis_conn, self.room_id, "avatar_url").await
     }
 
     /// Remove all room and control module related redis keys that are used across all 'sub'-rooms. This must only be
     /// called once the main and all breakout rooms are empty.
     async fn cleanup_redis_for_global_room(&mut self) -> Result<()> {
+        if let Err(e) = room_statistics::persist(&mut self.redis_conn, &self.db, self.room.id).await
+        {
+            log::error!("Failed to persist room statistics, {:?}", e);
+        }
+
         storage::delete_participant_count(&mut self.redis_conn, self.room.id).await?;
+        sharding::release_room(&mut self.redis_conn, self.room.id).await?;
         storage::delete_tariff(&mut self.redis_conn, self.room.id).await
     }
 
+    /// Schedule the destruction of the room by the empty room sweeper, once the grace period elapsed
+    async fn schedule_empty_room_destroy(&mut self, grace_period: Duration) -> Result<()> {
+        let destroy_at = Timestamp::now()
+            .checked_add_signed(chrono::Duration::from_std(grace_period)?)
+            .context("empty room grace period is out of range")?;
+
+        storage::schedule_empty_room_destroy(&mut self.redis_conn, self.room.id, destroy_at.into())
+            .await
+    }
+
     /// Runs the runner until the peer closes its websocket connection or a fatal error occurres.
     pub async fn run(mut self) {
         let mut manual_close_ws = false;
 
+        error_reporting::set_participant(self.room_id, self.id);
+
         // Set default `skip_waiting_room` key value with the default expiration time in seconds
         _ = storage::set_skip_waiting_room_with_expiry_nx(
             &mut self.redis_conn,
@@ -808,6 +1375,12 @@ impl Runner {
             SKIP_WAITING_ROOM_KEY_EXPIRY,
         )
         .await;
+
+        self.record_connection_event(connection_history::ConnectionEventKind::Connected {
+            resuming: self.resuming,
+        })
+        .await;
+
         let mut skip_waiting_room_refresh_interval =
             interval(Duration::from_secs(SKIP_WAITING_ROOM_KEY_REFRESH_INTERVAL));
 
@@ -825,7 +1398,14 @@ impl Runner {
                             manual_close_ws = true;
                             break;
                         }
-                        Ok(Some(msg)) => self.handle_ws_message(msg).await,
+                        Ok(Some(Message::Ping(_))) => {
+                            // Pings are only forwarded to keep track of the participants activity
+                            self.record_activity();
+                        }
+                        Ok(Some(msg)) => {
+                            self.record_activity();
+                            self.handle_ws_message(msg).await;
+                        }
                         Ok(None) => {
                             // Ws was in closing state, runner will now exit gracefully
                         }
@@ -861,6 +1441,30 @@ impl Runner {
                     )
                     .await;
                 }
+                event = inactivity::wait(&mut self.inactivity) => {
+                    match event {
+                        InactivityEvent::Warn(remaining) => {
+                            let now = Timestamp::now();
+                            let remaining = chrono::Duration::from_std(remaining)
+                                .unwrap_or_else(|_| chrono::Duration::zero());
+
+                            self.ws_send_control(now, outgoing::Message::InactivityWarning {
+                                disconnects_at: Timestamp::from(*now + remaining),
+                            }).await;
+                        }
+                        InactivityEvent::Disconnect => {
+                            log::debug!("Disconnecting participant {} due to inactivity", self.id);
+
+                            self.metrics.increment_inactivity_disconnects_count();
+                            self.ws_send_control(Timestamp::now(), outgoing::Message::InactivityTimeout).await;
+                            self.ws.close(CloseCode::Normal).await;
+                            break;
+                        }
+                    }
+                }
+                events = batching::wait(&mut self.participant_events) => {
+                    self.handle_participant_events(events).await;
+                }
                 _ = &mut self.time_limit_future => {
                     self.ws_send_control(Timestamp::now(), outgoing::Message::TimeLimitQuotaElapsed).await;
                     self.ws.close(CloseCode::Normal).await;
@@ -896,6 +1500,20 @@ impl Runner {
         self.destroy(manual_close_ws).await;
     }
 
+    /// Add the event to the connection history of the participant
+    async fn record_connection_event(&mut self, kind: connection_history::ConnectionEventKind) {
+        if let Err(e) = connection_history::record(&mut self.redis_conn, self.id, kind).await {
+            log::warn!("Failed to record connection event, {:?}", e);
+        }
+    }
+
+    /// Reset the inactivity timer of the participant
+    fn record_activity(&mut self) {
+        if let Some(inactivity) = &mut self.inactivity {
+            inactivity.record_activity();
+        }
+    }
+
     #[tracing::instrument(skip(self, message), fields(id = %self.id))]
     async fn handle_ws_message(&mut self, message: Message) {
         log::trace!("Received websocket message {:?}", message);
@@ -924,8 +1542,22 @@ impl Runner {
             match serde_json::from_value(namespaced.payload) {
                 Ok(msg) => {
                     if let Err(e) = self.handle_control_msg(timestamp, msg).await {
-                        log::error!("Failed to handle control msg, {}", e);
-                        self.exit = true;
+                        if redis_wrapper::is_transient_error(&e) {
+                            // Keep the participant inside the room, the request can be repeated once redis is back
+                            log::warn!(
+                                "Failed to handle control msg while redis is unavailable, {}",
+                                e
+                            );
+
+                            self.ws_send_control_error(
+                                timestamp,
+                                outgoing::Error::StorageUnavailable,
+                            )
+                            .await;
+                        } else {
+                            log::error!("Failed to handle control msg, {}", e);
+                            self.exit = true;
+                        }
                     }
                 }
                 Err(e) => {
@@ -971,6 +1603,10 @@ impl Runner {
                     return Ok(());
                 }
 
+                self.clock_offset_ms = join
+                    .client_timestamp
+                    .map(|client_timestamp| (*timestamp - *client_timestamp).num_milliseconds());
+
                 let (display_name, avatar_url) = match &self.participant {
                     api::Participant::User(user) => {
                         let avatar_url = Some(format!(
@@ -979,9 +1615,23 @@ impl Runner {
                             md5::compute(&user.email)
                         ));
 
-                        (trim_display_name(join.display_name), avatar_url)
+                        let real_names_required = moderation::storage::is_real_names_required(
+                            &mut self.redis_conn,
+                            self.room.id,
+                        )
+                        .await?;
+
+                        let display_name = if real_names_required {
+                            user.display_name.clone()
+                        } else {
+                            join.display_name
+                        };
+
+                        (trim_display_name(display_name), avatar_url)
+                    }
+                    api::Participant::Guest | api::Participant::Bot => {
+                        (trim_display_name(join.display_name), None)
                     }
-                    api::Participant::Guest => (trim_display_name(join.display_name), None),
                     api::Participant::Recorder => (join.display_name, None),
                     api::Participant::Sip => {
                         if let Some(call_in) = self.settings.load().call_in.as_ref() {
@@ -1000,6 +1650,21 @@ impl Runner {
                     }
                 };
 
+                let display_name_owner = match &self.participant {
+                    api::Participant::User(user) => {
+                        moderation::storage::DisplayNameOwner::User(user.id)
+                    }
+                    _ => moderation::storage::DisplayNameOwner::Participant(self.id),
+                };
+
+                let display_name = moderation::storage::get_forced_display_name(
+                    &mut self.redis_conn,
+                    self.room.id,
+                    display_name_owner,
+                )
+                .await?
+                .unwrap_or(display_name);
+
                 if display_name.is_empty() || display_name.len() > 100 {
                     self.ws_send_control_error(timestamp, outgoing::Error::InvalidUsername)
                         .await;
@@ -1017,21 +1682,24 @@ impl Runner {
                         api::Participant::Guest => ParticipationKind::Guest,
                         api::Participant::Sip => ParticipationKind::Sip,
                         api::Participant::Recorder => ParticipationKind::Recorder,
+                        api::Participant::Bot => ParticipationKind::Bot,
                     },
                     joined_at: timestamp,
                     hand_is_up: false,
                     hand_updated_at: timestamp,
                     left_at: None,
+                    metadata: Default::default(),
                 };
 
                 self.metrics.increment_participants_count(&self.participant);
 
-                // Allow moderators, invisible services, and already accepted participants to skip the waiting room
+                // Allow moderators, services, and already accepted participants to skip the waiting room
                 let can_skip_waiting_room: bool =
                     storage::get_skip_waiting_room(&mut self.redis_conn, self.id).await?;
 
                 let skip_waiting_room = matches!(self.role, Role::Moderator)
                     || !control_data.participation_kind.is_visible()
+                    || matches!(self.participant, api::Participant::Bot)
                     || can_skip_waiting_room;
 
                 let waiting_room_enabled = moderation::storage::init_waiting_room_key(
@@ -1106,6 +1774,54 @@ impl Runner {
             incoming::Message::LowerHand => {
                 self.handle_raise_hand_change(timestamp, false).await?;
             }
+            incoming::Message::GetRaisedHands(incoming::GetRaisedHands { offset, limit }) => {
+                if !matches!(self.state, RunnerState::Joined) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::NotYetJoined)
+                        .await;
+
+                    return Ok(());
+                }
+
+                if !matches!(self.role, Role::Moderator) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::InsufficientPermissions)
+                        .await;
+
+                    return Ok(());
+                }
+
+                self.handle_get_raised_hands(timestamp, offset, limit)
+                    .await?;
+            }
+            incoming::Message::SetMetadata(incoming::SetMetadata { key, value }) => {
+                if !matches!(self.state, RunnerState::Joined) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::NotYetJoined)
+                        .await;
+
+                    return Ok(());
+                }
+
+                self.handle_set_metadata(timestamp, key, value).await?;
+            }
+            incoming::Message::TimeSync(incoming::TimeSync { client_timestamp }) => {
+                self.ws_send_control(
+                    timestamp,
+                    outgoing::Message::TimeSync(outgoing::TimeSync {
+                        client_timestamp,
+                        server_timestamp: Timestamp::now(),
+                    }),
+                )
+                .await;
+            }
+            incoming::Message::SubmitFeedback(feedback) => {
+                if !matches!(self.state, RunnerState::Joined) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::NotYetJoined)
+                        .await;
+
+                    return Ok(());
+                }
+
+                self.handle_submit_feedback(timestamp, feedback).await?;
+            }
             incoming::Message::GrantModeratorRole(incoming::Target { target }) => {
                 if !matches!(self.state, RunnerState::Joined) {
                     self.ws_send_control_error(timestamp, outgoing::Error::NotYetJoined)
@@ -1128,6 +1844,42 @@ impl Runner {
                 self.handle_grant_moderator_msg(timestamp, target, false)
                     .await?;
             }
+            incoming::Message::SwitchBreakout(incoming::SwitchBreakout { breakout_room }) => {
+                if !matches!(self.state, RunnerState::Joined) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::NotYetJoined)
+                        .await;
+
+                    return Ok(());
+                }
+
+                if !matches!(self.role, Role::Moderator) {
+                    self.ws_send_control_error(timestamp, outgoing::Error::InsufficientPermissions)
+                        .await;
+
+                    return Ok(());
+                }
+
+                if breakout_room == self.room_id.breakout_room_id() {
+                    self.ws_send_control_error(timestamp, outgoing::Error::NothingToDo)
+                        .await;
+
+                    return Ok(());
+                }
+
+                if let Some(breakout_room) = breakout_room {
+                    let config =
+                        breakout::storage::get_config(&mut self.redis_conn, self.room.id).await?;
+
+                    if !config.map_or(false, |config| config.is_valid_id(breakout_room)) {
+                        self.ws_send_control_error(timestamp, outgoing::Error::InvalidBreakoutRoom)
+                            .await;
+
+                        return Ok(());
+                    }
+                }
+
+                self.switch_breakout_room(timestamp, breakout_room).await?;
+            }
         }
 
         Ok(())
@@ -1162,7 +1914,7 @@ impl Runner {
             storage::get_attribute(&mut self.redis_conn, self.room_id, target, "user_id").await?;
 
         if let Some(user_id) = user_id {
-            if user_id == self.room.created_by {
+            if self.room_owners.contains(&user_id) {
                 self.ws_send_control_error(timestamp, outgoing::Error::TargetIsRoomOwner)
                     .await;
 
@@ -1180,6 +1932,149 @@ impl Runner {
         Ok(())
     }
 
+    async fn handle_set_metadata(
+        &mut self,
+        timestamp: Timestamp,
+        key: String,
+        value: Option<String>,
+    ) -> Result<()> {
+        let max_value_length = self
+            .settings
+            .load()
+            .participant_metadata
+            .as_ref()
+            .filter(|settings| settings.allowed_keys.contains(&key))
+            .map(|settings| settings.max_value_length);
+
+        let max_value_length = if let Some(max_value_length) = max_value_length {
+            max_value_length
+        } else {
+            self.ws_send_control_error(timestamp, outgoing::Error::MetadataKeyNotAllowed)
+                .await;
+
+            return Ok(());
+        };
+
+        let value = value
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty());
+
+        if matches!(&value, Some(value) if value.chars().count() > max_value_length) {
+            self.ws_send_control_error(timestamp, outgoing::Error::MetadataValueTooLong)
+                .await;
+
+            return Ok(());
+        }
+
+        let metadata: Option<ParticipantMetadata> =
+            storage::get_attribute(&mut self.redis_conn, self.room_id, self.id, "metadata").await?;
+        let mut metadata = metadata.unwrap_or_default();
+
+        if let Some(value) = value {
+            metadata.0.insert(key, value);
+        } else {
+            metadata.0.remove(&key);
+        }
+
+        storage::set_attribute(
+            &mut self.redis_conn,
+            self.room_id,
+            self.id,
+            "metadata",
+            &metadata,
+        )
+        .await?;
+
+        self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::Update(self.id))
+            .await;
+
+        Ok(())
+    }
+
+    async fn handle_submit_feedback(
+        &mut self,
+        timestamp: Timestamp,
+        feedback: incoming::SubmitFeedback,
+    ) -> Result<()> {
+        if self.feedback_submitted {
+            self.ws_send_control_error(timestamp, outgoing::Error::FeedbackAlreadySubmitted)
+                .await;
+
+            return Ok(());
+        }
+
+        let comment = feedback
+            .comment
+            .map(|comment| comment.trim().to_owned())
+            .filter(|comment| !comment.is_empty());
+
+        let comment_too_long = comment.as_ref().map_or(false, |comment| {
+            comment.chars().count() > incoming::MAX_FEEDBACK_COMMENT_LENGTH
+        });
+
+        if !(1..=5).contains(&feedback.rating) || comment_too_long {
+            self.ws_send_control_error(timestamp, outgoing::Error::InvalidFeedback)
+                .await;
+
+            return Ok(());
+        }
+
+        let mut issues = feedback.issues;
+        issues.sort();
+        issues.dedup();
+
+        // Context of the call to relate the rating to the media problems of the participant
+        let joined_at: Option<Timestamp> =
+            storage::get_attribute(&mut self.redis_conn, self.room_id, self.id, "joined_at")
+                .await?;
+        let duration_secs = joined_at
+            .map(|joined_at| timestamp.signed_duration_since(*joined_at).num_seconds())
+            .unwrap_or_default()
+            .max(0);
+
+        let mut webrtc_down_count = 0;
+        let mut slow_link_count = 0;
+
+        for event in connection_history::get(&mut self.redis_conn, self.id).await? {
+            match event.kind {
+                connection_history::ConnectionEventKind::WebRtcDown { .. } => {
+                    webrtc_down_count += 1
+                }
+                connection_history::ConnectionEventKind::SlowLink { .. } => slow_link_count += 1,
+                _ => {}
+            }
+        }
+
+        let new_feedback = NewCallFeedback {
+            room_id: self.room.id,
+            tenant_id: self.room.tenant_id,
+            user_id: match &self.participant {
+                api::Participant::User(user) => Some(user.id),
+                _ => None,
+            },
+            rating: feedback.rating.into(),
+            issues: issues
+                .iter()
+                .map(|issue| issue.as_str().to_owned())
+                .collect(),
+            comment,
+            participants: self.room_participant_count.try_into().unwrap_or(i32::MAX),
+            duration_secs,
+            webrtc_down_count,
+            slow_link_count,
+        };
+
+        let db = self.db.clone();
+        crate::block(move || new_feedback.insert(&mut db.get_conn()?)).await??;
+
+        self.feedback_submitted = true;
+
+        self.ws_send_control(timestamp, outgoing::Message::FeedbackSubmitted)
+            .await;
+
+        Ok(())
+    }
+
     async fn handle_raise_hand_change(
         &mut self,
         timestamp: Timestamp,
@@ -1191,32 +2086,116 @@ impl Runner {
             .query_async(&mut self.redis_conn)
             .await?;
 
+        let queue_changed = if hand_raised {
+            storage::add_raised_hand(&mut self.redis_conn, self.room_id, self.id, timestamp).await?
+        } else {
+            storage::remove_raised_hand(&mut self.redis_conn, self.room_id, self.id).await?
+        };
+
+        // Broadcasting the hand raises of thousands of webinar attendees to every participant does not scale,
+        // they are only published as aggregated count instead. Moderators page through the queue on demand.
+        let is_attendee = self.is_webinar_attendee().await?;
+
         let broadcast_event = if hand_raised {
             DynBroadcastEvent::RaiseHand
         } else {
             DynBroadcastEvent::LowerHand
         };
         let actions = self
-            .handle_module_broadcast_event(timestamp, broadcast_event, true)
+            .handle_module_broadcast_event(timestamp, broadcast_event, !is_attendee)
             .await;
 
         self.handle_module_requested_actions(timestamp, actions)
             .await;
 
+        if self.room.webinar_mode && queue_changed {
+            self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::RaisedHandsUpdated)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the participant is a webinar attendee, being neither moderator nor panelist
+    async fn is_webinar_attendee(&mut self) -> Result<bool> {
+        Ok(self.room.webinar_mode
+            && self.role != Role::Moderator
+            && !moderation::storage::is_panelist(&mut self.redis_conn, self.room.id, self.id)
+                .await?)
+    }
+
+    async fn send_raised_hands_count(&mut self, timestamp: Timestamp) -> Result<()> {
+        let count = storage::get_raised_hands_count(&mut self.redis_conn, self.room_id).await?;
+
+        self.ws_send_control(timestamp, outgoing::Message::RaisedHandsCount { count })
+            .await;
+
+        Ok(())
+    }
+
+    async fn handle_get_raised_hands(
+        &mut self,
+        timestamp: Timestamp,
+        offset: usize,
+        limit: usize,
+    ) -> Result<()> {
+        let limit = limit.min(incoming::MAX_RAISED_HANDS_LIMIT);
+
+        let total = storage::get_raised_hands_count(&mut self.redis_conn, self.room_id).await?;
+        let participants =
+            storage::get_raised_hands(&mut self.redis_conn, self.room_id, offset, limit)
+                .await?
+                .into_iter()
+                .map(|(id, raised_at)| outgoing::RaisedHand { id, raised_at })
+                .collect();
+
+        self.ws_send_control(
+            timestamp,
+            outgoing::Message::RaisedHands(outgoing::RaisedHands {
+                total,
+                participants,
+            }),
+        )
+        .await;
+
         Ok(())
     }
 
-    /// Enforces the given tariff.
+    /// Rejects new participants of rooms locked by a moderator and enforces the given tariff.
     ///
     /// Requires the room lock to be taken before calling
     async fn enforce_tariff(
         &mut self,
         tariff: Tariff,
     ) -> Result<ControlFlow<JoinBlockedReason, Tariff>> {
+        // Resumed sessions belong to participants which were already inside the room, services are requested by the
+        // moderators (e.g. the recorder) or provided by the operator of the deployment. Moderators must always be able
+        // to get back into their room, otherwise a locked room could not be unlocked anymore.
+        let is_new_participant = !self.resuming
+            && self.role != Role::Moderator
+            && !matches!(
+                self.participant,
+                api::Participant::Recorder | api::Participant::Bot
+            );
+
+        if is_new_participant
+            && moderation::storage::is_room_locked(&mut self.redis_conn, self.room.id).await?
+        {
+            return Ok(ControlFlow::Break(JoinBlockedReason::RoomLocked));
+        }
+
         let tariff =
             control::storage::try_init_tariff(&mut self.redis_conn, self.room.id, tariff).await?;
 
-        if let Some(participant_limit) = tariff.quotas.0.get("room_participant_limit") {
+        // Bots are provided by the operator of the deployment and are not subject to the participant limit
+        let counts_towards_limit = !matches!(self.participant, api::Participant::Bot);
+
+        if let Some(participant_limit) = tariff
+            .quotas
+            .0
+            .get("room_participant_limit")
+            .filter(|_| counts_towards_limit)
+        {
             if let Some(count) =
                 control::storage::get_participant_count(&mut self.redis_conn, self.room.id).await?
             {
@@ -1228,7 +2207,36 @@ impl Runner {
             }
         }
 
-        control::storage::increment_participant_count(&mut self.redis_conn, self.room.id).await?;
+        let participant_count =
+            control::storage::increment_participant_count(&mut self.redis_conn, self.room.id)
+                .await?;
+
+        self.room_participant_count = participant_count.max(0) as usize;
+
+        if !self.is_recorded_in_statistics() {
+            return Ok(ControlFlow::Continue(tariff));
+        }
+
+        let session_started = control::storage::record_room_statistics_join(
+            &mut self.redis_conn,
+            self.room.id,
+            self.room.tenant_id,
+        )
+        .await?;
+
+        if let api::Participant::User(user) = &self.participant {
+            control::storage::record_room_statistics_user(
+                &mut self.redis_conn,
+                self.room.id,
+                user.id,
+            )
+            .await?;
+        }
+
+        if session_started {
+            self.notifications
+                .notify(NotificationEvent::MeetingStarted, self.room.id, vec![]);
+        }
 
         Ok(ControlFlow::Continue(tariff))
     }
@@ -1280,22 +2288,17 @@ impl Runner {
             bail!("participant-id is already taken inside waiting-room set");
         }
 
-        self.state = RunnerState::Waiting {
-            accepted: false,
-            control_data,
-        };
-
-        self.ws
-            .send(Message::Text(
-                serde_json::to_string(&NamespacedEvent {
-                    namespace: moderation::NAMESPACE,
-                    timestamp,
-                    payload: moderation::outgoing::Message::InWaitingRoom,
-                })?
-                .into(),
-            ))
-            .await;
-
+        self.state = RunnerState::Waiting {
+            accepted: false,
+            control_data,
+        };
+
+        self.ws_send_module::<moderation::ModerationModule>(
+            timestamp,
+            moderation::outgoing::Message::InWaitingRoom,
+        )
+        .await;
+
         self.rabbitmq_publish(
             timestamp,
             Some(breakout::rabbitmq::global_exchange_name(self.room_id.room_id()).as_str()),
@@ -1315,13 +2318,13 @@ impl Runner {
         &mut self,
         timestamp: Timestamp,
         control_data: ControlData,
-        joining_from_waiting_room: bool,
+        tariff_enforced: bool,
     ) -> Result<()> {
         let mut lock = storage::room_mutex(self.room_id);
 
-        // If we haven't joined the waiting room yet, fetch, set and enforce the tariff for the room.
-        // When in waiting-room this logic was already executed in `join_waiting_room`.
-        let (guard, tariff) = if !joining_from_waiting_room {
+        // If we haven't joined the waiting room or another breakout room yet, fetch, set and enforce the tariff for
+        // the room. Otherwise this logic was already executed, e.g. in `join_waiting_room`.
+        let (guard, tariff) = if !tariff_enforced {
             let db = self.db.clone();
             let creator_id = self.room.created_by;
 
@@ -1369,15 +2372,60 @@ impl Runner {
 
         unlock_res?;
 
+        let snapshot_ttl = self.settings.load().rooms.participant_snapshot_ttl;
+        let snapshot = ControlData::snapshot(
+            &mut self.redis_conn,
+            self.room_id,
+            &participant_ids,
+            snapshot_ttl,
+        )
+        .await?;
+
+        let cacheable_modules = self.modules.get_cacheable_module_names();
+
         let mut participants = vec![];
+        // Participants whose cacheable peer frontend data was served from the snapshot
+        let mut cached_participants = HashSet::new();
+        // Participants to add to the snapshot, with the generation of their data
+        let mut fetched_participants = HashMap::new();
 
-        for id in participant_ids {
+        for (id, data) in participant_ids.into_iter().zip(snapshot) {
             if self.id == id {
                 continue;
             }
 
-            match self.build_participant(id).await {
-                Ok(Some(participant)) => participants.push(participant),
+            let (participant_control_data, cached_module_data) = match data {
+                SnapshotData::Cached(entry) => (entry.control_data, Some(entry.module_data)),
+                SnapshotData::Fetched {
+                    control_data,
+                    generation,
+                } => {
+                    fetched_participants.insert(id, (generation, control_data.clone()));
+
+                    (control_data, None)
+                }
+            };
+
+            match self
+                .build_participant_from_control_data(id, participant_control_data)
+                .await
+            {
+                Ok(Some(mut participant)) => {
+                    if let Some(cached_module_data) = cached_module_data {
+                        for (namespace, value) in cached_module_data {
+                            if let Some(namespace) = cacheable_modules
+                                .iter()
+                                .find(|cacheable| **cacheable == namespace)
+                            {
+                                participant.module_data.insert(*namespace, value);
+                            }
+                        }
+
+                        cached_participants.insert(id);
+                    }
+
+                    participants.push(participant)
+                }
                 Ok(None) => { /* ignore invisible participants */ }
                 Err(e) => log::error!("Failed to build participant {}, {}", id, e),
             };
@@ -1388,15 +2436,41 @@ impl Runner {
         let actions = self
             .handle_module_broadcast_event(
                 timestamp,
-                DynBroadcastEvent::Joined(&control_data, &mut module_data, &mut participants),
+                DynBroadcastEvent::Joined(
+                    &control_data,
+                    &mut module_data,
+                    &mut participants,
+                    &cached_participants,
+                ),
                 false,
             )
             .await;
 
+        // Modules which failed did not add their peer frontend data, which must not be cached as missing
+        if !snapshot_ttl.is_zero() && actions.disabled_modules.is_empty() {
+            self.cache_participant_snapshot(
+                &participants,
+                fetched_participants,
+                &cacheable_modules,
+                snapshot_ttl,
+            )
+            .await;
+        }
+
         let available_modules = self.modules.get_module_names();
         let closes_at =
             control::storage::get_room_closes_at(&mut self.redis_conn, self.room_id).await?;
 
+        // The branding is only cosmetic, failing to load it must not prevent joining
+        let branding =
+            match room_branding::load_branding(self.db.clone(), &self.storage, &self.room).await {
+                Ok(branding) => branding,
+                Err(e) => {
+                    log::warn!("Failed to load branding of room {}, {:?}", self.room.id, e);
+                    None
+                }
+            };
+
         self.ws_send_control(
             timestamp,
             outgoing::Message::JoinSuccess(outgoing::JoinSuccess {
@@ -1406,6 +2480,8 @@ impl Runner {
                 role: self.role,
                 closes_at,
                 tariff: TariffResource::from_tariff(tariff, &available_modules).into(),
+                branding,
+                clock_offset_ms: self.clock_offset_ms,
                 module_data,
                 participants,
             }),
@@ -1414,6 +2490,10 @@ impl Runner {
 
         self.state = RunnerState::Joined;
 
+        if self.room.webinar_mode {
+            self.send_raised_hands_count(timestamp).await?;
+        }
+
         self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::Joined(self.id))
             .await;
 
@@ -1424,6 +2504,9 @@ impl Runner {
     }
 
     async fn join_room_locked(&mut self) -> Result<Vec<ParticipantId>> {
+        // Keep the room alive if it was left empty before
+        storage::cancel_empty_room_destroy(&mut self.redis_conn, self.room_id.room_id()).await?;
+
         let participant_set_exists =
             control::storage::participant_set_exists(&mut self.redis_conn, self.room_id).await?;
 
@@ -1433,6 +2516,13 @@ impl Runner {
         }
         self.activate_room_time_limit().await?;
 
+        storage::record_room_statistics_modules(
+            &mut self.redis_conn,
+            self.room.id,
+            &self.modules.get_module_names(),
+        )
+        .await?;
+
         let participants = storage::get_all_participants(&mut self.redis_conn, self.room_id)
             .await
             .context("Failed to get all active participants")?;
@@ -1518,6 +2608,9 @@ impl Runner {
             api::Participant::Recorder => {
                 pipe_attrs.set("kind", ParticipationKind::Recorder);
             }
+            api::Participant::Bot => {
+                pipe_attrs.set("kind", ParticipationKind::Bot);
+            }
         }
 
         pipe_attrs
@@ -1527,6 +2620,7 @@ impl Runner {
             .set("display_name", display_name)
             .set("joined_at", timestamp)
             .del("left_at")
+            .del("metadata")
             .query_async(&mut self.redis_conn)
             .await?;
 
@@ -1538,18 +2632,79 @@ impl Runner {
     /// If the participant is an invisible service (like the recorder) and shouldn't be shown to other participants
     /// this function will return Ok(None)
     async fn build_participant(&mut self, id: ParticipantId) -> Result<Option<Participant>> {
+        let control_data = ControlData::from_redis(&mut self.redis_conn, self.room_id, id).await?;
+
+        self.build_participant_from_control_data(id, control_data)
+            .await
+    }
+
+    /// Add the fetched participants with the peer frontend data of the cacheable modules to the participant snapshot
+    async fn cache_participant_snapshot(
+        &mut self,
+        participants: &[Participant],
+        mut fetched_participants: HashMap<ParticipantId, (u64, ControlData)>,
+        cacheable_modules: &[&'static str],
+        snapshot_ttl: Duration,
+    ) {
+        let entries: Vec<_> = participants
+            .iter()
+            .filter_map(|participant| {
+                let (generation, control_data) = fetched_participants.remove(&participant.id)?;
+
+                let module_data = participant
+                    .module_data
+                    .iter()
+                    .filter(|(namespace, _)| cacheable_modules.contains(namespace))
+                    .map(|(namespace, value)| (namespace.to_string(), value.clone()))
+                    .collect();
+
+                Some((
+                    participant.id,
+                    generation,
+                    SnapshotEntry {
+                        control_data,
+                        module_data,
+                    },
+                ))
+            })
+            .collect();
+
+        if let Err(e) = storage::set_participant_snapshot(
+            &mut self.redis_conn,
+            self.room_id,
+            &entries,
+            snapshot_ttl,
+        )
+        .await
+        {
+            log::warn!("Failed to cache the participant snapshot, {:?}", e);
+        }
+    }
+
+    async fn build_participant_from_control_data(
+        &mut self,
+        id: ParticipantId,
+        control_data: ControlData,
+    ) -> Result<Option<Participant>> {
         let mut participant = outgoing::Participant {
             id,
             module_data: Default::default(),
         };
 
-        let control_data = ControlData::from_redis(&mut self.redis_conn, self.room_id, id).await?;
-
         // Do not build participants for invisible services
         if !control_data.participation_kind.is_visible() {
             return Ok(None);
         };
 
+        // In webinar mode participants only see the moderators and panelists of the room
+        if self.room.webinar_mode
+            && self.role != Role::Moderator
+            && control_data.role != Role::Moderator
+            && !moderation::storage::is_panelist(&mut self.redis_conn, self.room.id, id).await?
+        {
+            return Ok(None);
+        }
+
         participant.module_data.insert(
             NAMESPACE,
             serde_json::to_value(control_data)
@@ -1580,6 +2735,28 @@ impl Runner {
             }
             ).into();
 
+            let is_shared_payload = delivery
+                .properties
+                .kind()
+                .as_ref()
+                .map_or(false, |kind| kind.as_str() == SHARED_PAYLOAD_KIND);
+
+            if is_shared_payload {
+                // Shared payloads are forwarded to the websocket without involving the modules
+                if let RunnerState::Joined = &self.state {
+                    match SharedPayload::from_delivery(delivery.data) {
+                        Some(payload) => {
+                            self.ws
+                                .send(payload.into_message(self.protocol_version))
+                                .await
+                        }
+                        None => log::error!("Got invalid shared rabbit-mq payload"),
+                    }
+                }
+
+                return;
+            }
+
             let namespaced =
                 match serde_json::from_slice::<NamespacedCommand<Value>>(&delivery.data) {
                     Ok(namespaced) => namespaced,
@@ -1640,25 +2817,14 @@ impl Runner {
                     return Ok(());
                 }
 
-                let mut participant = if let Some(participant) = self.build_participant(id).await? {
-                    participant
-                } else {
-                    return Ok(());
-                };
-
-                let actions = self
-                    .handle_module_broadcast_event(
-                        timestamp,
-                        DynBroadcastEvent::ParticipantJoined(&mut participant),
-                        false,
-                    )
-                    .await;
+                self.room_participant_count += 1;
 
-                self.ws_send_control(timestamp, outgoing::Message::Joined(participant))
-                    .await;
+                if let Some(participant_events) = &mut self.participant_events {
+                    participant_events.joined(id);
+                    return Ok(());
+                }
 
-                self.handle_module_requested_actions(timestamp, actions)
-                    .await;
+                self.handle_participant_joined(timestamp, id).await?;
             }
             rabbitmq::Message::Left(id) => {
                 // Ignore events of self and only if runner is joined
@@ -1666,6 +2832,20 @@ impl Runner {
                     return Ok(());
                 }
 
+                self.room_participant_count = self.room_participant_count.saturating_sub(1);
+
+                if let Some(participant_events) = &mut self.participant_events {
+                    if participant_events.left(id) {
+                        // The participant has not been announced yet
+                        return Ok(());
+                    }
+                }
+
+                // Participants hidden by the webinar mode never joined from the view of this participant
+                if self.room.webinar_mode && self.build_participant(id).await?.is_none() {
+                    return Ok(());
+                }
+
                 let actions = self
                     .handle_module_broadcast_event(
                         timestamp,
@@ -1689,26 +2869,24 @@ impl Runner {
                     return Ok(());
                 }
 
-                let mut participant = if let Some(participant) = self.build_participant(id).await? {
-                    participant
-                } else {
-                    log::warn!("ignoring update of invisible participant");
+                if let Some(participant_events) = &mut self.participant_events {
+                    participant_events.updated(id);
                     return Ok(());
-                };
+                }
 
-                let actions = self
-                    .handle_module_broadcast_event(
-                        timestamp,
-                        DynBroadcastEvent::ParticipantUpdated(&mut participant),
-                        false,
-                    )
-                    .await;
+                self.handle_participant_updated(timestamp, id).await?;
+            }
+            rabbitmq::Message::RaisedHandsUpdated => {
+                if !matches!(&self.state, RunnerState::Joined) {
+                    return Ok(());
+                }
 
-                self.ws_send_control(timestamp, outgoing::Message::Update(participant))
-                    .await;
+                if let Some(participant_events) = &mut self.participant_events {
+                    participant_events.raised_hands_updated();
+                    return Ok(());
+                }
 
-                self.handle_module_requested_actions(timestamp, actions)
-                    .await;
+                self.send_raised_hands_count(timestamp).await?;
             }
             rabbitmq::Message::Accepted(id) => {
                 if self.id != id {
@@ -1734,27 +2912,22 @@ impl Runner {
                         )
                         .await?;
 
-                        self.ws
-                            .send(Message::Text(
-                                serde_json::to_string(&NamespacedEvent {
-                                    namespace: moderation::NAMESPACE,
-                                    timestamp,
-                                    payload: moderation::outgoing::Message::Accepted,
-                                })?
-                                .into(),
-                            ))
-                            .await;
+                        self.ws_send_module::<moderation::ModerationModule>(
+                            timestamp,
+                            moderation::outgoing::Message::Accepted,
+                        )
+                        .await;
                     }
                 }
             }
             rabbitmq::Message::SetModeratorStatus(grant_moderator) => {
-                let created_room = if let api::Participant::User(user) = &self.participant {
-                    self.room.created_by == user.id
+                let owns_room = if let api::Participant::User(user) = &self.participant {
+                    self.room_owners.contains(&user.id)
                 } else {
                     false
                 };
 
-                if created_room {
+                if owns_room {
                     return Ok(());
                 }
 
@@ -1765,7 +2938,8 @@ impl Runner {
                         api::Participant::User(_) => Role::User,
                         api::Participant::Guest
                         | api::Participant::Sip
-                        | api::Participant::Recorder => Role::Guest,
+                        | api::Participant::Recorder
+                        | api::Participant::Bot => Role::Guest,
                     }
                 };
 
@@ -1802,30 +2976,23 @@ impl Runner {
                     self.handle_raise_hand_change(timestamp, false).await?;
                 }
 
-                self.ws
-                    .send(Message::Text(
-                        serde_json::to_string(&NamespacedEvent {
-                            namespace: moderation::NAMESPACE,
-                            timestamp,
-                            payload: moderation::outgoing::Message::RaisedHandResetByModerator {
-                                issued_by,
-                            },
-                        })?
-                        .into(),
-                    ))
-                    .await;
+                // The moderator cleared the queue of raised hands before publishing this message
+                if self.room.webinar_mode {
+                    self.send_raised_hands_count(timestamp).await?;
+                }
+
+                self.ws_send_module::<moderation::ModerationModule>(
+                    timestamp,
+                    moderation::outgoing::Message::RaisedHandResetByModerator { issued_by },
+                )
+                .await;
             }
             rabbitmq::Message::EnableRaiseHands { issued_by } => {
-                self.ws
-                    .send(Message::Text(
-                        serde_json::to_string(&NamespacedEvent {
-                            namespace: moderation::NAMESPACE,
-                            timestamp,
-                            payload: moderation::outgoing::Message::RaiseHandsEnabled { issued_by },
-                        })?
-                        .into(),
-                    ))
-                    .await;
+                self.ws_send_module::<moderation::ModerationModule>(
+                    timestamp,
+                    moderation::outgoing::Message::RaiseHandsEnabled { issued_by },
+                )
+                .await;
             }
             rabbitmq::Message::DisableRaiseHands { issued_by } => {
                 let raised: Option<bool> = storage::get_attribute(
@@ -1839,18 +3006,36 @@ impl Runner {
                     self.handle_raise_hand_change(timestamp, false).await?;
                 }
 
-                self.ws
-                    .send(Message::Text(
-                        serde_json::to_string(&NamespacedEvent {
-                            namespace: moderation::NAMESPACE,
-                            timestamp,
-                            payload: moderation::outgoing::Message::RaiseHandsDisabled {
-                                issued_by,
-                            },
-                        })?
-                        .into(),
-                    ))
+                // The moderator cleared the queue of raised hands before publishing this message
+                if self.room.webinar_mode {
+                    self.send_raised_hands_count(timestamp).await?;
+                }
+
+                self.ws_send_module::<moderation::ModerationModule>(
+                    timestamp,
+                    moderation::outgoing::Message::RaiseHandsDisabled { issued_by },
+                )
+                .await;
+            }
+            rabbitmq::Message::CloseRoom => {
+                self.ws_send_control(timestamp, outgoing::Message::RoomClosed)
                     .await;
+
+                self.exit = true;
+                self.ws.close(CloseCode::Normal).await;
+            }
+            rabbitmq::Message::TranscriptionUpdated {
+                recording_id,
+                status,
+            } => {
+                self.ws_send_control(
+                    timestamp,
+                    outgoing::Message::TranscriptionUpdated {
+                        recording_id,
+                        status: status.into(),
+                    },
+                )
+                .await;
             }
         }
 
@@ -1889,19 +3074,38 @@ impl Runner {
     /// Publish a rabbitmq message
     ///
     /// If exchange is `None`, `self.room_exchange` will be used.
+    async fn rabbitmq_publish(
+        &mut self,
+        timestamp: Timestamp,
+        exchange: Option<&str>,
+        routing_key: &str,
+        message: String,
+    ) {
+        self.rabbitmq_publish_with_kind(timestamp, exchange, routing_key, message, None)
+            .await;
+    }
+
+    /// Publish a rabbitmq message with the given `type` property
+    ///
+    /// If exchange is `None`, `self.room_exchange` will be used.
     #[tracing::instrument(
         skip(self, exchange, message),
         fields(id = %self.id, exchange = %exchange.unwrap_or(&self.room_exchange))
     )]
-    async fn rabbitmq_publish(
+    async fn rabbitmq_publish_with_kind(
         &mut self,
         timestamp: Timestamp,
         exchange: Option<&str>,
         routing_key: &str,
         message: String,
+        kind: Option<&str>,
     ) {
         log::trace!("publish {}", message);
-        let properties = BasicProperties::default().with_timestamp(timestamp.timestamp() as u64);
+        let mut properties =
+            BasicProperties::default().with_timestamp(timestamp.timestamp() as u64);
+        if let Some(kind) = kind {
+            properties = properties.with_kind(kind.into());
+        }
         if let Err(e) = self
             .rabbitmq_channel
             .basic_publish(
@@ -1918,6 +3122,95 @@ impl Runner {
         }
     }
 
+    /// Process a batch of participant events collected by the [`ParticipantEventBatch`]
+    async fn handle_participant_events(&mut self, events: batching::ParticipantEvents) {
+        let timestamp = Timestamp::now();
+
+        if matches!(&self.state, RunnerState::Joined) {
+            for id in events.joined {
+                if let Err(e) = self.handle_participant_joined(timestamp, id).await {
+                    log::error!("Failed to handle joined participant {}, {:?}", id, e);
+                }
+            }
+
+            for id in events.updated {
+                if let Err(e) = self.handle_participant_updated(timestamp, id).await {
+                    log::error!("Failed to handle updated participant {}, {:?}", id, e);
+                }
+            }
+
+            if events.raised_hands_updated {
+                if let Err(e) = self.send_raised_hands_count(timestamp).await {
+                    log::error!("Failed to send the count of raised hands, {:?}", e);
+                }
+            }
+        }
+
+        if events.publish_update {
+            self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::Update(self.id))
+                .await;
+        }
+    }
+
+    /// Announce a participant which joined the room to the modules and the frontend
+    async fn handle_participant_joined(
+        &mut self,
+        timestamp: Timestamp,
+        id: ParticipantId,
+    ) -> Result<()> {
+        let mut participant = if let Some(participant) = self.build_participant(id).await? {
+            participant
+        } else {
+            return Ok(());
+        };
+
+        let actions = self
+            .handle_module_broadcast_event(
+                timestamp,
+                DynBroadcastEvent::ParticipantJoined(&mut participant),
+                false,
+            )
+            .await;
+
+        self.ws_send_control(timestamp, outgoing::Message::Joined(participant))
+            .await;
+
+        self.handle_module_requested_actions(timestamp, actions)
+            .await;
+
+        Ok(())
+    }
+
+    /// Pass the updated data of a participant to the modules and the frontend
+    async fn handle_participant_updated(
+        &mut self,
+        timestamp: Timestamp,
+        id: ParticipantId,
+    ) -> Result<()> {
+        let mut participant = if let Some(participant) = self.build_participant(id).await? {
+            participant
+        } else {
+            log::warn!("ignoring update of invisible participant");
+            return Ok(());
+        };
+
+        let actions = self
+            .handle_module_broadcast_event(
+                timestamp,
+                DynBroadcastEvent::ParticipantUpdated(&mut participant),
+                false,
+            )
+            .await;
+
+        self.ws_send_control(timestamp, outgoing::Message::Update(participant))
+            .await;
+
+        self.handle_module_requested_actions(timestamp, actions)
+            .await;
+
+        Ok(())
+    }
+
     /// Dispatch owned event to a single module
     async fn handle_module_targeted_event(
         &mut self,
@@ -1929,6 +3222,7 @@ impl Runner {
         let mut rabbitmq_publish = vec![];
         let mut invalidate_data = false;
         let mut exit = None;
+        let mut disabled_modules = vec![];
 
         let ctx = DynEventCtx {
             id: self.id,
@@ -1938,9 +3232,15 @@ impl Runner {
             rabbitmq_publish: &mut rabbitmq_publish,
             redis_conn: &mut self.redis_conn,
             events: &mut self.events,
+            bus: &self.bus,
             invalidate_data: &mut invalidate_data,
             exit: &mut exit,
             metrics: self.metrics.clone(),
+            error_reporting: &self.error_reporting,
+            protocol_version: self.protocol_version,
+            room_size: RoomSize::from_participant_count(self.room_participant_count),
+            failure_policy: self.settings.load().rooms.module_failure_policy,
+            disabled_modules: &mut disabled_modules,
         };
 
         self.modules
@@ -1952,6 +3252,7 @@ impl Runner {
             rabbitmq_publish,
             invalidate_data,
             exit,
+            disabled_modules,
         })
     }
 
@@ -1965,6 +3266,7 @@ impl Runner {
         let mut ws_messages = vec![];
         let mut rabbitmq_publish = vec![];
         let mut exit = None;
+        let mut disabled_modules = vec![];
 
         let ctx = DynEventCtx {
             id: self.id,
@@ -1974,9 +3276,15 @@ impl Runner {
             rabbitmq_publish: &mut rabbitmq_publish,
             redis_conn: &mut self.redis_conn,
             events: &mut self.events,
+            bus: &self.bus,
             invalidate_data: &mut invalidate_data,
             exit: &mut exit,
             metrics: self.metrics.clone(),
+            error_reporting: &self.error_reporting,
+            protocol_version: self.protocol_version,
+            room_size: RoomSize::from_participant_count(self.room_participant_count),
+            failure_policy: self.settings.load().rooms.module_failure_policy,
+            disabled_modules: &mut disabled_modules,
         };
 
         self.modules.on_event_broadcast(ctx, dyn_event).await;
@@ -1986,6 +3294,7 @@ impl Runner {
             rabbitmq_publish,
             invalidate_data,
             exit,
+            disabled_modules,
         }
     }
 
@@ -1999,25 +3308,47 @@ impl Runner {
             rabbitmq_publish,
             invalidate_data,
             exit,
+            disabled_modules,
         }: ModuleRequestedActions,
     ) {
         for ws_message in ws_messages {
             self.ws.send(ws_message).await;
         }
 
+        for module in disabled_modules {
+            self.ws_send_control(timestamp, outgoing::Message::ModuleDisabled { module })
+                .await;
+        }
+
         for publish in rabbitmq_publish {
-            self.rabbitmq_publish(
+            self.rabbitmq_publish_with_kind(
                 timestamp,
                 publish.exchange.as_deref(),
                 &publish.routing_key,
                 publish.message,
+                publish.shared.then_some(SHARED_PAYLOAD_KIND),
             )
             .await;
         }
 
         if invalidate_data {
-            self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::Update(self.id))
-                .await;
+            // The cached module data of the participant is outdated now
+            if let Err(e) = storage::invalidate_participant_snapshot(
+                &mut self.redis_conn,
+                self.room_id,
+                self.id,
+            )
+            .await
+            {
+                log::warn!("Failed to invalidate the participant snapshot, {:?}", e);
+            }
+
+            if let Some(participant_events) = &mut self.participant_events {
+                participant_events.publish_update();
+            } else {
+                self.rabbitmq_publish_control(timestamp, None, rabbitmq::Message::Update(self.id))
+                    .await;
+            }
         }
 
         if let Some(exit) = exit {
@@ -2028,15 +3359,38 @@ impl Runner {
     }
 
     async fn ws_send_control_error(&mut self, timestamp: Timestamp, error: outgoing::Error) {
-        self.ws_send_control(timestamp, outgoing::Message::Error(error))
+        self.ws_send_control(timestamp, outgoing::Message::Error(error.into()))
             .await;
     }
 
     async fn ws_send_control(&mut self, timestamp: Timestamp, payload: outgoing::Message) {
+        let payload = outgoing::adapt_outgoing(&payload, self.protocol_version);
+
+        self.ws_send_namespaced(NAMESPACE, timestamp, payload).await;
+    }
+
+    /// Send a message of the module `M` which is sent by the runner itself, e.g. for the waiting room
+    async fn ws_send_module<M>(&mut self, timestamp: Timestamp, payload: M::Outgoing)
+    where
+        M: SignalingModule,
+    {
+        let payload = M::adapt_outgoing(&payload, self.protocol_version);
+
+        self.ws_send_namespaced(M::NAMESPACE, timestamp, payload)
+            .await;
+    }
+
+    /// Send a message which has been adapted to the negotiated protocol version already
+    async fn ws_send_namespaced(
+        &mut self,
+        namespace: &'static str,
+        timestamp: Timestamp,
+        payload: Value,
+    ) {
         self.ws
             .send(Message::Text(
                 serde_json::to_string(&NamespacedEvent {
-                    namespace: NAMESPACE,
+                    namespace,
                     timestamp,
                     payload,
                 })
@@ -2053,11 +3407,13 @@ struct ModuleRequestedActions {
     rabbitmq_publish: Vec<RabbitMqPublish>,
     invalidate_data: bool,
     exit: Option<CloseCode>,
+    disabled_modules: Vec<&'static str>,
 }
 
 struct Ws {
     to_actor: Addr<WebSocketActor>,
     from_actor: mpsc::UnboundedReceiver<ws::Message>,
+    metrics: Arc<SignalingMetrics>,
 
     state: State,
 }
@@ -2107,7 +3463,10 @@ impl Ws {
     /// Sends a health check ping message every WS_TIMEOUT.
     async fn receive(&mut self) -> Result<Option<Message>> {
         match self.from_actor.recv().await {
-            Some(msg) => Ok(Some(msg)),
+            Some(msg) => {
+                self.metrics.decrement_ws_queue_depth();
+                Ok(Some(msg))
+            }
             None => {
                 self.state = State::Closed;
                 Ok(None)
@@ -2116,6 +3475,17 @@ impl Ws {
     }
 }
 
+impl Drop for Ws {
+    fn drop(&mut self) {
+        // Messages left in the queue are never handled, remove them from the queue depth
+        self.from_actor.close();
+
+        while self.from_actor.try_recv().is_ok() {
+            self.metrics.decrement_ws_queue_depth();
+        }
+    }
+}
+
 /// Trim leading, trailing, and extra whitespaces between a given display name.
 fn trim_display_name(display_name: String) -> String {
     display_name.split_whitespace().join(" ")
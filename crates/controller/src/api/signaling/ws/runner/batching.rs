@@ -0,0 +1,169 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Batching of participant events in large rooms
+//!
+//! Every participant joining or updating its data causes all other participants to fetch its data from redis and to
+//! pass it through all of their modules. When hundreds of participants join a webinar at once, these events are
+//! collected for a short time instead, so that each participant is only processed once per batch. Likewise the
+//! participant's own update notification is only published once per batch, however often its modules invalidate
+//! their data, and the aggregated count of raised hands in a webinar is only refreshed once per batch.
+
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+use types::core::ParticipantId;
+
+/// Participant events collected by the [`ParticipantEventBatch`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct ParticipantEvents {
+    /// Participants which joined the room, in order of their arrival
+    pub(super) joined: Vec<ParticipantId>,
+    /// Participants which updated their data, excluding those which joined in the same batch
+    pub(super) updated: Vec<ParticipantId>,
+    /// Whether the data of this participant was invalidated and must be published
+    pub(super) publish_update: bool,
+    /// Whether the queue of raised hands changed and the aggregated count must be refreshed
+    pub(super) raised_hands_updated: bool,
+}
+
+/// Collects participant events until the batch delay elapsed
+pub(super) struct ParticipantEventBatch {
+    delay: Duration,
+    deadline: Option<Instant>,
+    events: ParticipantEvents,
+}
+
+impl ParticipantEventBatch {
+    pub(super) fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            deadline: None,
+            events: ParticipantEvents::default(),
+        }
+    }
+
+    /// Start the batch if it is not already running
+    fn schedule(&mut self) {
+        if self.deadline.is_none() {
+            self.deadline = Some(Instant::now() + self.delay);
+        }
+    }
+
+    pub(super) fn joined(&mut self, id: ParticipantId) {
+        self.events.updated.retain(|updated| *updated != id);
+
+        if !self.events.joined.contains(&id) {
+            self.events.joined.push(id);
+        }
+
+        self.schedule();
+    }
+
+    pub(super) fn updated(&mut self, id: ParticipantId) {
+        // The data of joining participants is fetched when the batch is processed
+        if !self.events.joined.contains(&id) && !self.events.updated.contains(&id) {
+            self.events.updated.push(id);
+        }
+
+        self.schedule();
+    }
+
+    /// Drop all pending events of the participant which left the room
+    ///
+    /// Returns true if the participant joined in the current batch, in which case it has never been announced and its
+    /// left event must be skipped as well.
+    pub(super) fn left(&mut self, id: ParticipantId) -> bool {
+        self.events.updated.retain(|updated| *updated != id);
+
+        let len = self.events.joined.len();
+        self.events.joined.retain(|joined| *joined != id);
+
+        len != self.events.joined.len()
+    }
+
+    pub(super) fn publish_update(&mut self) {
+        self.events.publish_update = true;
+
+        self.schedule();
+    }
+
+    pub(super) fn raised_hands_updated(&mut self) {
+        self.events.raised_hands_updated = true;
+
+        self.schedule();
+    }
+
+    /// Drop all pending events, e.g. when leaving the room
+    pub(super) fn clear(&mut self) {
+        self.deadline = None;
+        self.events = ParticipantEvents::default();
+    }
+
+    /// Wait until the batch delay elapsed and take the collected events
+    ///
+    /// Cancel safe, the events are only taken when the returned future completes.
+    pub(super) async fn wait(&mut self) -> ParticipantEvents {
+        match self.deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+
+        self.deadline = None;
+
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Wait for the next batch of participant events, or forever if batching is disabled
+pub(super) async fn wait(batch: &mut Option<ParticipantEventBatch>) -> ParticipantEvents {
+    match batch {
+        Some(batch) => batch.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test(start_paused = true)]
+    async fn collect_events() {
+        let start = Instant::now();
+        let mut batch = ParticipantEventBatch::new(Duration::from_millis(100));
+
+        batch.updated(ParticipantId::from_u128(1));
+        batch.updated(ParticipantId::from_u128(1));
+        batch.joined(ParticipantId::from_u128(2));
+        batch.updated(ParticipantId::from_u128(2));
+        batch.joined(ParticipantId::from_u128(1));
+        batch.publish_update();
+        batch.raised_hands_updated();
+        batch.raised_hands_updated();
+
+        assert_eq!(
+            batch.wait().await,
+            ParticipantEvents {
+                joined: vec![ParticipantId::from_u128(2), ParticipantId::from_u128(1)],
+                updated: vec![],
+                publish_update: true,
+                raised_hands_updated: true,
+            }
+        );
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn left_drops_pending_events() {
+        let mut batch = ParticipantEventBatch::new(Duration::from_millis(100));
+
+        batch.joined(ParticipantId::from_u128(1));
+        batch.updated(ParticipantId::from_u128(2));
+
+        assert!(batch.left(ParticipantId::from_u128(1)));
+        assert!(!batch.left(ParticipantId::from_u128(2)));
+
+        assert_eq!(batch.wait().await, ParticipantEvents::default());
+    }
+}
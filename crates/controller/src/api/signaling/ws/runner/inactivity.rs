@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Detection of participants which show no activity on their signaling connection
+
+use controller_shared::settings::Inactivity;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+/// Event emitted by the [`InactivityTimer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum InactivityEvent {
+    /// The participant will be disconnected after the given duration if it stays inactive
+    Warn(Duration),
+    /// The participant has been inactive for the configured timeout
+    Disconnect,
+}
+
+/// Tracks the last activity of a participant
+pub(super) struct InactivityTimer {
+    timeout: Duration,
+    warning: Duration,
+    last_activity: Instant,
+    warned: bool,
+}
+
+impl InactivityTimer {
+    pub(super) fn new(settings: &Inactivity) -> Self {
+        Self {
+            timeout: settings.timeout,
+            warning: settings.warning.min(settings.timeout),
+            last_activity: Instant::now(),
+            warned: false,
+        }
+    }
+
+    /// Reset the timer, must be called on every activity of the participant
+    pub(super) fn record_activity(&mut self) {
+        self.last_activity = Instant::now();
+        self.warned = false;
+    }
+
+    /// Wait for the next inactivity event
+    ///
+    /// Cancel safe, the timer only advances when the returned future completes.
+    pub(super) async fn wait(&mut self) -> InactivityEvent {
+        let disconnect_at = self.last_activity + self.timeout;
+
+        if !self.warned && !self.warning.is_zero() {
+            sleep_until(disconnect_at - self.warning).await;
+
+            self.warned = true;
+
+            InactivityEvent::Warn(self.warning)
+        } else {
+            sleep_until(disconnect_at).await;
+
+            InactivityEvent::Disconnect
+        }
+    }
+}
+
+/// Wait for the next inactivity event of the timer, or forever if inactivity detection is disabled
+pub(super) async fn wait(timer: &mut Option<InactivityTimer>) -> InactivityEvent {
+    match timer {
+        Some(timer) => timer.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn timer() -> InactivityTimer {
+        InactivityTimer::new(&Inactivity {
+            timeout: Duration::from_secs(60),
+            warning: Duration::from_secs(10),
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn warn_then_disconnect() {
+        let start = Instant::now();
+        let mut timer = timer();
+
+        assert_eq!(
+            timer.wait().await,
+            InactivityEvent::Warn(Duration::from_secs(10))
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(50));
+
+        assert_eq!(timer.wait().await, InactivityEvent::Disconnect);
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn activity_resets_timer() {
+        let mut timer = timer();
+
+        assert_eq!(
+            timer.wait().await,
+            InactivityEvent::Warn(Duration::from_secs(10))
+        );
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        let start = Instant::now();
+        timer.record_activity();
+
+        assert_eq!(
+            timer.wait().await,
+            InactivityEvent::Warn(Duration::from_secs(10))
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(50));
+    }
+}
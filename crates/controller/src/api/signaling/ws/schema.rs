@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! JSON schemas of the signaling messages
+//!
+//! The schemas are exported with the `export-schema` subcommand, allowing the frontend and bots to generate typed
+//! clients for the signaling API.
+use super::SignalingModule;
+use crate::api::signaling::ws_modules::{breakout, control, moderation, plugins, recording};
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Schemas of the messages of a single namespace
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceSchemas {
+    /// Messages sent by the client, tagged with `action`
+    pub incoming: RootSchema,
+
+    /// Messages sent by the controller, tagged with `message`
+    pub outgoing: RootSchema,
+}
+
+/// Schemas of all signaling messages, keyed by namespace
+///
+/// Contains the control namespace and the modules built into the controller. Other modules are added with
+/// [`SignalingSchemas::add_module`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct SignalingSchemas {
+    namespaces: BTreeMap<&'static str, NamespaceSchemas>,
+}
+
+impl Default for SignalingSchemas {
+    fn default() -> Self {
+        let mut schemas = Self {
+            namespaces: BTreeMap::new(),
+        };
+
+        schemas.add::<control::incoming::Message, control::outgoing::Message>(control::NAMESPACE);
+        schemas.add_module::<breakout::BreakoutRooms>();
+        schemas.add_module::<moderation::ModerationModule>();
+        schemas.add_module::<plugins::Plugins>();
+        schemas.add_module::<recording::Recording>();
+
+        schemas
+    }
+}
+
+impl SignalingSchemas {
+    /// Add the schemas of the messages of the given module
+    pub fn add_module<M>(&mut self)
+    where
+        M: SignalingModule,
+    {
+        self.add::<M::Incoming, M::Outgoing>(M::NAMESPACE);
+    }
+
+    fn add<I, O>(&mut self, namespace: &'static str)
+    where
+        I: JsonSchema,
+        O: JsonSchema,
+    {
+        self.namespaces.insert(
+            namespace,
+            NamespaceSchemas {
+                incoming: schema_for!(I),
+                outgoing: schema_for!(O),
+            },
+        );
+    }
+
+    /// Returns the namespaces schemas are registered for
+    pub fn namespaces(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.namespaces.keys().copied()
+    }
+
+    /// Returns the schemas of the given namespace
+    pub fn get(&self, namespace: &str) -> Option<&NamespaceSchemas> {
+        self.namespaces.get(namespace)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn builtin_namespaces() {
+        let schemas = SignalingSchemas::default();
+
+        assert_eq!(
+            schemas.namespaces().collect::<Vec<_>>(),
+            ["breakout", "control", "moderation", "recording"]
+        );
+    }
+
+    #[test]
+    fn control_join_schema() {
+        let schemas = SignalingSchemas::default();
+        let control = serde_json::to_value(schemas.get("control").unwrap()).unwrap();
+
+        assert_eq!(
+            control["incoming"]["definitions"]["Join"]["properties"]["display_name"],
+            json!({ "description": "The users display name", "type": "string" })
+        );
+        assert_eq!(
+            control["outgoing"]["definitions"]["Role"],
+            json!({
+                "description": "Role of the participant inside a room",
+                "type": "string",
+                "enum": ["guest", "user", "moderator"]
+            })
+        );
+    }
+}
@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Websocket messages serialized once and shared between their recipients
+//!
+//! Room-wide broadcasts are usually published over rabbitmq, deserialized by every receiving runner and serialized
+//! again for the websocket of each participant. For large rooms this repeated serde work dominates the CPU time of
+//! the controller. A [`SharedPayload`] contains the final websocket text of a message for every supported protocol
+//! version, so receiving runners forward the one matching the negotiated version to the websocket as is.
+use super::{ProtocolVersion, SignalingModule};
+use actix_http::ws::Message;
+use bytestring::ByteString;
+use std::collections::BTreeMap;
+use types::core::Timestamp;
+use types::signaling::NamespacedEvent;
+
+/// Value of the `type` property of rabbitmq messages carrying a [`SharedPayload`]
+pub(super) const SHARED_PAYLOAD_KIND: &str = "k3k-signaling-shared-payload";
+
+/// A pre-serialized websocket message of a module
+///
+/// Contains one serialization per protocol version, ordered from the oldest to the newest version. Cloning is cheap
+/// as the serialized messages are reference counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedPayload(Vec<(ProtocolVersion, ByteString)>);
+
+impl SharedPayload {
+    /// Serialize an outgoing message of the module `M`
+    ///
+    /// The message is adapted to every [supported](ProtocolVersion::supported) protocol version, recipients receive
+    /// the serialization of the version they negotiated.
+    pub fn new<M>(timestamp: Timestamp, message: &M::Outgoing) -> Self
+    where
+        M: SignalingModule,
+    {
+        let mut serialized: Vec<(ProtocolVersion, ByteString)> = vec![];
+
+        for version in ProtocolVersion::supported() {
+            let event = NamespacedEvent {
+                namespace: M::NAMESPACE,
+                timestamp,
+                payload: M::adapt_outgoing(message, version),
+            };
+
+            let text = serde_json::to_string(&event).expect("Failed to convert namespaced to json");
+
+            // Share the buffer with the previous version if the message did not change
+            let text = match serialized.last() {
+                Some((_, previous)) if *previous == text => previous.clone(),
+                _ => text.into(),
+            };
+
+            serialized.push((version, text));
+        }
+
+        Self(serialized)
+    }
+
+    /// Read a payload received over rabbitmq, see [`SharedPayload::to_delivery`]
+    ///
+    /// Returns `None` if the data is not a payload or contains no known protocol version.
+    pub(super) fn from_delivery(data: Vec<u8>) -> Option<Self> {
+        let serialized: BTreeMap<String, String> = serde_json::from_slice(&data).ok()?;
+
+        let mut serialized: Vec<(ProtocolVersion, ByteString)> = serialized
+            .into_iter()
+            .filter_map(|(protocol, text)| {
+                Some((ProtocolVersion::from_protocol(&protocol)?, text.into()))
+            })
+            .collect();
+
+        if serialized.is_empty() {
+            return None;
+        }
+
+        serialized.sort_by_key(|(version, _)| *version);
+
+        Some(Self(serialized))
+    }
+
+    /// Encode the payload to be published over rabbitmq
+    ///
+    /// The serializations are sent as JSON object keyed by the websocket subprotocol of their version.
+    pub(super) fn to_delivery(&self) -> String {
+        let serialized: BTreeMap<String, &str> = self
+            .0
+            .iter()
+            .map(|(version, text)| (version.to_string(), &**text))
+            .collect();
+
+        serde_json::to_string(&serialized).expect("value must be serializable to json")
+    }
+
+    /// The serialized websocket message for the protocol `version`
+    pub fn for_version(&self, version: ProtocolVersion) -> &str {
+        self.serialized(version)
+    }
+
+    pub(super) fn into_message(self, version: ProtocolVersion) -> Message {
+        Message::Text(self.serialized(version).clone())
+    }
+
+    /// Select the serialization for the protocol `version`
+    ///
+    /// Payloads published by controllers supporting other versions may lack the requested version. In this case the
+    /// newest older version is used, or the oldest version if all are newer.
+    fn serialized(&self, version: ProtocolVersion) -> &ByteString {
+        self.0
+            .iter()
+            .rev()
+            .find(|(serialized_version, _)| *serialized_version <= version)
+            .or_else(|| self.0.first())
+            .map(|(_, text)| text)
+            .expect("shared payloads contain at least one serialization")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::signaling::ws::Echo;
+    use serde_json::json;
+
+    #[test]
+    fn serialized_as_namespaced_event() {
+        let payload =
+            SharedPayload::new::<Echo>(Timestamp::unix_epoch(), &json!({ "message": "hello" }));
+
+        let expected = json!({
+            "namespace": "echo",
+            "timestamp": "1970-01-01T00:00:00Z",
+            "payload": {
+                "message": "hello"
+            }
+        });
+
+        for version in ProtocolVersion::supported() {
+            let produced: serde_json::Value =
+                serde_json::from_str(payload.for_version(version)).unwrap();
+
+            assert_eq!(expected, produced);
+            assert_eq!(
+                payload.clone().into_message(version),
+                Message::Text(payload.for_version(version).into())
+            );
+        }
+    }
+
+    #[test]
+    fn select_version() {
+        let payload = SharedPayload(vec![
+            (ProtocolVersion::V1_0, "v1.0".into()),
+            (ProtocolVersion::new(1, 2), "v1.2".into()),
+        ]);
+
+        assert_eq!(payload.for_version(ProtocolVersion::new(0, 9)), "v1.0");
+        assert_eq!(payload.for_version(ProtocolVersion::V1_0), "v1.0");
+        assert_eq!(payload.for_version(ProtocolVersion::new(1, 1)), "v1.0");
+        assert_eq!(payload.for_version(ProtocolVersion::new(1, 2)), "v1.2");
+        assert_eq!(payload.for_version(ProtocolVersion::new(2, 0)), "v1.2");
+    }
+
+    #[test]
+    fn delivery_roundtrip() {
+        let payload = SharedPayload(vec![
+            (
+                ProtocolVersion::new(1, 2),
+                "{\"namespace\":\"echo\"}".into(),
+            ),
+            (
+                ProtocolVersion::V1_0,
+                "{\"namespace\":\"echo\",\"old\":true}".into(),
+            ),
+        ]);
+
+        let delivery = payload.to_delivery();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&delivery).unwrap(),
+            json!({
+                "k3k-signaling-json-v1.0": "{\"namespace\":\"echo\",\"old\":true}",
+                "k3k-signaling-json-v1.2": "{\"namespace\":\"echo\"}",
+            })
+        );
+
+        let received = SharedPayload::from_delivery(delivery.into_bytes()).unwrap();
+
+        assert_eq!(
+            received.for_version(ProtocolVersion::V1_0),
+            "{\"namespace\":\"echo\",\"old\":true}"
+        );
+        assert_eq!(
+            received.for_version(ProtocolVersion::new(1, 2)),
+            "{\"namespace\":\"echo\"}"
+        );
+    }
+
+    #[test]
+    fn invalid_delivery() {
+        assert!(SharedPayload::from_delivery(vec![0xff, 0xfe]).is_none());
+        assert!(SharedPayload::from_delivery(b"{\"namespace\":\"echo\"}".to_vec()).is_none());
+        assert!(SharedPayload::from_delivery(b"{}".to_vec()).is_none());
+    }
+}
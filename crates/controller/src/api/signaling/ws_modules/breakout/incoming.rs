@@ -2,25 +2,27 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::time::Duration;
 use types::core::ParticipantId;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Message {
     Start(Start),
     Stop,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Start {
     pub rooms: Vec<RoomParameter>,
     #[serde(default, with = "time")]
+    #[schemars(with = "Option<u64>")]
     pub duration: Option<Duration>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct RoomParameter {
     pub name: String,
     pub assignments: Vec<ParticipantId>,
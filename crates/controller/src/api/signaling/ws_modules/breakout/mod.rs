@@ -11,6 +11,7 @@ use actix_http::ws::CloseCode;
 use anyhow::{bail, Result};
 use chrono::{DateTime, Utc};
 use futures::FutureExt;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::{Duration, SystemTime};
@@ -22,7 +23,7 @@ pub mod outgoing;
 pub mod rabbitmq;
 pub mod storage;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct ParticipantInOtherRoom {
     pub breakout_room: Option<BreakoutRoomId>,
     pub id: ParticipantId,
@@ -34,7 +35,7 @@ pub struct ParticipantInOtherRoom {
     pub left_at: Option<Timestamp>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct AssocParticipantInOtherRoom {
     pub breakout_room: Option<BreakoutRoomId>,
     pub id: ParticipantId,
@@ -47,7 +48,7 @@ pub struct BreakoutRooms {
     breakout_room: Option<BreakoutRoomId>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct BreakoutRoom {
     id: BreakoutRoomId,
     name: String,
@@ -294,7 +295,7 @@ impl BreakoutRooms {
     ) -> Result<()> {
         if ctx.role() != Role::Moderator {
             ctx.ws_send(outgoing::Message::Error(
-                outgoing::Error::InsufficientPermissions,
+                outgoing::Error::InsufficientPermissions.into(),
             ));
             return Ok(());
         }
@@ -350,7 +351,7 @@ impl BreakoutRooms {
                         rabbitmq::Message::Stop,
                     );
                 } else {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::Inactive));
+                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::Inactive.into()));
                 }
             }
         }
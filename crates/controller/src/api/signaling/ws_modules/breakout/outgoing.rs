@@ -4,9 +4,11 @@
 
 use super::{AssocParticipantInOtherRoom, BreakoutRoom, BreakoutRoomId, ParticipantInOtherRoom};
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::Serialize;
+use types::signaling::{ErrorEnvelope, ModuleError};
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     Started(Started),
@@ -16,23 +18,34 @@ pub enum Message {
     Joined(ParticipantInOtherRoom),
     Left(AssocParticipantInOtherRoom),
 
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Started {
     pub rooms: Vec<BreakoutRoom>,
     pub expires: Option<DateTime<Utc>>,
     pub assignment: Option<BreakoutRoomId>,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "error", rename_all = "snake_case")]
 pub enum Error {
     Inactive,
     InsufficientPermissions,
 }
 
+impl ModuleError for Error {
+    const MODULE: &'static str = "breakout";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::Inactive => "No breakout session is running",
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -134,10 +147,16 @@ mod test {
 
     #[test]
     fn error() {
-        let expected = json!({"message": "error", "error": "insufficient_permissions"});
+        let expected = json!({
+            "message": "error",
+            "module": "breakout",
+            "error": "insufficient_permissions",
+            "text": "Insufficient permissions for the operation",
+            "retryable": false
+        });
 
         let produced =
-            serde_json::to_value(&Message::Error(Error::InsufficientPermissions)).unwrap();
+            serde_json::to_value(&Message::Error(Error::InsufficientPermissions.into())).unwrap();
 
         assert_eq!(expected, produced);
     }
@@ -2,10 +2,11 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Deserialize;
-use types::core::ParticipantId;
+use types::core::{BreakoutRoomId, ParticipantId, Timestamp};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Message {
     Join(Join),
@@ -16,19 +17,110 @@ pub enum Message {
     LowerHand,
     GrantModeratorRole(Target),
     RevokeModeratorRole(Target),
+    /// Move into another breakout room without reconnecting, only available to moderators
+    SwitchBreakout(SwitchBreakout),
+    /// Set or remove a custom metadata entry of the participant
+    SetMetadata(SetMetadata),
+    /// Request a page of the queue of raised hands, only available to moderators
+    GetRaisedHands(GetRaisedHands),
+    /// Rate the quality of the call, sent when leaving the room
+    SubmitFeedback(SubmitFeedback),
+    /// Request the time of the server to synchronize the clock of the client
+    TimeSync(TimeSync),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Join {
     /// The users display name
     pub display_name: String,
+    /// The time of the client when sending the join, used to estimate the clock offset of the client
+    #[serde(default)]
+    pub client_timestamp: Option<Timestamp>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TimeSync {
+    /// The time of the client when sending the request, echoed in the response
+    pub client_timestamp: Timestamp,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Target {
     pub target: ParticipantId,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SwitchBreakout {
+    /// The breakout room to move into, the main room if missing
+    #[serde(default)]
+    pub breakout_room: Option<BreakoutRoomId>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetMetadata {
+    /// One of the keys configured in `participant_metadata.allowed_keys`
+    pub key: String,
+    /// The new value, removes the entry if missing or empty
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// The maximum number of raised hands returned by a single [`GetRaisedHands`] request
+pub const MAX_RAISED_HANDS_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetRaisedHands {
+    /// The number of raised hands to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// The maximum number of raised hands to return, capped at [`MAX_RAISED_HANDS_LIMIT`]
+    #[serde(default = "default_raised_hands_limit")]
+    pub limit: usize,
+}
+
+fn default_raised_hands_limit() -> usize {
+    50
+}
+
+/// The maximum number of characters of the comment of a [`SubmitFeedback`] request
+pub const MAX_FEEDBACK_COMMENT_LENGTH: usize = 4096;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SubmitFeedback {
+    /// Star rating between 1 and 5
+    pub rating: u8,
+    /// Issues the participant experienced during the call
+    #[serde(default)]
+    pub issues: Vec<FeedbackIssue>,
+    /// Free text, limited to [`MAX_FEEDBACK_COMMENT_LENGTH`] characters
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackIssue {
+    Audio,
+    Video,
+    ScreenShare,
+    Echo,
+    Connection,
+    Other,
+}
+
+impl FeedbackIssue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Audio => "audio",
+            Self::Video => "video",
+            Self::ScreenShare => "screen_share",
+            Self::Echo => "echo",
+            Self::Connection => "connection",
+            Self::Other => "other",
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -45,8 +137,31 @@ mod test {
 
         let msg: Message = serde_json::from_str(json).unwrap();
 
-        if let Message::Join(Join { display_name }) = msg {
+        if let Message::Join(Join {
+            display_name,
+            client_timestamp,
+        }) = msg
+        {
             assert_eq!(display_name, "Test!");
+            assert!(client_timestamp.is_none());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn time_sync() {
+        let json = r#"
+        {
+            "action": "time_sync",
+            "client_timestamp": "2023-01-01T10:30:00.250Z"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::TimeSync(TimeSync { client_timestamp }) = msg {
+            assert_eq!(client_timestamp.timestamp_millis(), 1672569000250);
         } else {
             panic!()
         }
@@ -77,4 +192,106 @@ mod test {
 
         assert!(matches!(msg, Message::LowerHand));
     }
+
+    #[test]
+    fn switch_breakout() {
+        let json = r#"
+        {
+            "action": "switch_breakout",
+            "breakout_room": "00000000-0000-0000-0000-000000000001"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SwitchBreakout(SwitchBreakout { breakout_room }) = msg {
+            assert_eq!(breakout_room, Some(BreakoutRoomId::from_u128(1)));
+        } else {
+            panic!()
+        }
+
+        let json = r#"
+        {
+            "action": "switch_breakout"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        assert!(matches!(
+            msg,
+            Message::SwitchBreakout(SwitchBreakout {
+                breakout_room: None
+            })
+        ));
+    }
+
+    #[test]
+    fn set_metadata() {
+        let json = r#"
+        {
+            "action": "set_metadata",
+            "key": "pronouns",
+            "value": "they/them"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SetMetadata(SetMetadata { key, value }) = msg {
+            assert_eq!(key, "pronouns");
+            assert_eq!(value.as_deref(), Some("they/them"));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn get_raised_hands() {
+        let json = r#"
+        {
+            "action": "get_raised_hands",
+            "offset": 100
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::GetRaisedHands(GetRaisedHands { offset, limit }) = msg {
+            assert_eq!(offset, 100);
+            assert_eq!(limit, 50);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn submit_feedback() {
+        let json = r#"
+        {
+            "action": "submit_feedback",
+            "rating": 2,
+            "issues": ["audio", "screen_share"],
+            "comment": "Choppy audio"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SubmitFeedback(SubmitFeedback {
+            rating,
+            issues,
+            comment,
+        }) = msg
+        {
+            assert_eq!(rating, 2);
+            assert_eq!(
+                issues,
+                vec![FeedbackIssue::Audio, FeedbackIssue::ScreenShare]
+            );
+            assert_eq!(comment.as_deref(), Some("Choppy audio"));
+        } else {
+            panic!()
+        }
+    }
 }
@@ -6,8 +6,12 @@
 //!
 //! Actual control 'module' code can be found inside `crate::api::signaling::ws::runner`
 use crate::prelude::*;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use itertools::izip;
+use redis_args::{FromRedisValue, ToRedisArgs};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 use types::core::{ParticipantId, ParticipationKind, Timestamp};
 
 pub mod incoming;
@@ -29,16 +33,133 @@ pub struct ControlData {
     pub joined_at: Timestamp,
     pub left_at: Option<Timestamp>,
     pub hand_updated_at: Timestamp,
+    #[serde(default, skip_serializing_if = "ParticipantMetadata::is_empty")]
+    pub metadata: ParticipantMetadata,
 }
 
+/// Custom key-value metadata a participant attached to itself, e.g. pronouns or department
+///
+/// Only the keys configured in `participant_metadata.allowed_keys` can be set.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToRedisArgs, FromRedisValue,
+)]
+#[serde(transparent)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct ParticipantMetadata(pub BTreeMap<String, String>);
+
+impl ParticipantMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The participant attributes making up the [`ControlData`]
+type ControlAttributes = (
+    Option<String>,
+    Option<Role>,
+    Option<String>,
+    Option<Timestamp>,
+    Option<Timestamp>,
+    Option<bool>,
+    Option<Timestamp>,
+    Option<ParticipationKind>,
+    Option<ParticipantMetadata>,
+);
+
+const CONTROL_ATTRIBUTES: [&str; 9] = [
+    "display_name",
+    "role",
+    "avatar_url",
+    "joined_at",
+    "left_at",
+    "hand_is_up",
+    "hand_updated_at",
+    "kind",
+    "metadata",
+];
+
 impl ControlData {
     pub async fn from_redis(
         redis_conn: &mut RedisConnection,
         room_id: SignalingRoomId,
         participant_id: ParticipantId,
     ) -> Result<Self> {
+        let mut pipe = storage::AttrPipeline::new(room_id, participant_id);
+
+        for name in CONTROL_ATTRIBUTES {
+            pipe.get(name);
+        }
+
+        let attributes: ControlAttributes = pipe.query_async(redis_conn).await?;
+
+        Ok(Self::from_attributes(attributes))
+    }
+
+    /// Fetch the control data of multiple participants in a single pipeline
+    ///
+    /// The index of the entries in the returned vector is a direct mapping to the provided list of participants.
+    pub async fn from_redis_for_participants(
+        redis_conn: &mut RedisConnection,
+        room_id: SignalingRoomId,
+        participants: &[ParticipantId],
+    ) -> Result<Vec<Self>> {
+        // Special case: HMGET cannot handle empty arrays (missing arguments)
+        if participants.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut pipe = redis::pipe();
+
+        for name in CONTROL_ATTRIBUTES {
+            pipe.cmd("HMGET")
+                .arg(storage::attribute_key(room_id, name))
+                .arg(participants);
+        }
+
         #[allow(clippy::type_complexity)]
         let (
+            display_names,
+            roles,
+            avatar_urls,
+            joined_ats,
+            left_ats,
+            hands_are_up,
+            hands_updated_at,
+            participation_kinds,
+            metadata,
+        ): (
+            Vec<Option<String>>,
+            Vec<Option<Role>>,
+            Vec<Option<String>>,
+            Vec<Option<Timestamp>>,
+            Vec<Option<Timestamp>>,
+            Vec<Option<bool>>,
+            Vec<Option<Timestamp>>,
+            Vec<Option<ParticipationKind>>,
+            Vec<Option<ParticipantMetadata>>,
+        ) = pipe
+            .query_async(redis_conn)
+            .await
+            .context("Failed to get the control data of the participants")?;
+
+        Ok(izip!(
+            display_names,
+            roles,
+            avatar_urls,
+            joined_ats,
+            left_ats,
+            hands_are_up,
+            hands_updated_at,
+            participation_kinds,
+            metadata
+        )
+        .map(Self::from_attributes)
+        .collect())
+    }
+
+    fn from_attributes(
+        (
             display_name,
             role,
             avatar_url,
@@ -47,27 +168,9 @@ impl ControlData {
             hand_is_up,
             hand_updated_at,
             participation_kind,
-        ): (
-            Option<String>,
-            Option<Role>,
-            Option<String>,
-            Option<Timestamp>,
-            Option<Timestamp>,
-            Option<bool>,
-            Option<Timestamp>,
-            Option<ParticipationKind>,
-        ) = storage::AttrPipeline::new(room_id, participant_id)
-            .get("display_name")
-            .get("role")
-            .get("avatar_url")
-            .get("joined_at")
-            .get("left_at")
-            .get("hand_is_up")
-            .get("hand_updated_at")
-            .get("kind")
-            .query_async(redis_conn)
-            .await?;
-
+            metadata,
+        ): ControlAttributes,
+    ) -> Self {
         if display_name.is_none()
             || joined_at.is_none()
             || hand_is_up.is_none()
@@ -76,7 +179,7 @@ impl ControlData {
             log::error!("failed to fetch some attribute, using fallback defaults");
         }
 
-        Ok(Self {
+        Self {
             display_name: display_name.unwrap_or_else(|| "Participant".into()),
             role: role.unwrap_or(Role::Guest),
             avatar_url,
@@ -87,6 +190,75 @@ impl ControlData {
             // no default for left_at. If its not found by error,
             // worst case we have a ghost participant,
             left_at,
-        })
+            metadata: metadata.unwrap_or_default(),
+        }
     }
+
+    /// Returns the data of the given participants, served from the participant snapshot where possible
+    ///
+    /// When hundreds of participants join at once, each of them needs the data of all other participants. The data
+    /// fetched by one joining participant is cached for `snapshot_ttl`, so the following participants only fetch the
+    /// data of participants which joined or changed in the meantime. Changing the attributes of a participant or
+    /// invalidating its module data discards its cached entry. The cache is skipped if `snapshot_ttl` is zero.
+    ///
+    /// The fetched data is cached by the caller with [`storage::set_participant_snapshot`], once the peer frontend
+    /// data of the modules has been added.
+    pub async fn snapshot(
+        redis_conn: &mut RedisConnection,
+        room_id: SignalingRoomId,
+        participants: &[ParticipantId],
+        snapshot_ttl: Duration,
+    ) -> Result<Vec<SnapshotData>> {
+        let snapshot = if snapshot_ttl.is_zero() {
+            vec![(None, 0); participants.len()]
+        } else {
+            storage::get_participant_snapshot(redis_conn, room_id, participants).await?
+        };
+
+        let missing: Vec<ParticipantId> = participants
+            .iter()
+            .zip(&snapshot)
+            .filter(|(_, (entry, _))| entry.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut fetched = Self::from_redis_for_participants(redis_conn, room_id, &missing)
+            .await?
+            .into_iter();
+
+        snapshot
+            .into_iter()
+            .map(|(entry, generation)| match entry {
+                Some(entry) => Ok(SnapshotData::Cached(entry)),
+                None => Ok(SnapshotData::Fetched {
+                    control_data: fetched
+                        .next()
+                        .context("Missing control data of participant")?,
+                    generation,
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Data of a participant cached in the participant snapshot of the room, see [`ControlData::snapshot`]
+#[derive(Debug, Clone, Serialize, Deserialize, ToRedisArgs, FromRedisValue)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct SnapshotEntry {
+    pub control_data: ControlData,
+    /// Peer frontend data of the modules with cacheable peer frontend data, keyed by their namespace
+    pub module_data: HashMap<String, serde_json::Value>,
+}
+
+/// Data of a participant returned by [`ControlData::snapshot`]
+#[derive(Debug)]
+pub enum SnapshotData {
+    /// Served from the participant snapshot, including the cached peer frontend data of the modules
+    Cached(SnapshotEntry),
+    /// Fetched from the participant attributes, to be cached with the given generation
+    Fetched {
+        control_data: ControlData,
+        generation: u64,
+    },
 }
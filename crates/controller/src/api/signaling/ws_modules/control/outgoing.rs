@@ -2,13 +2,27 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use crate::api::signaling::Role;
+use crate::api::signaling::{ProtocolVersion, Role};
+use crate::api::v1::room_branding::BrandingResource;
 use crate::api::v1::tariffs::TariffResource;
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::collections::HashMap;
-use types::core::{ParticipantId, Timestamp};
+use types::core::{AssetId, ParticipantId, Timestamp};
+use types::signaling::{ErrorEnvelope, ModuleError};
+
+/// Convert an outgoing control message into the schema of the negotiated protocol version
+///
+/// Counterpart of [`SignalingModule::adapt_outgoing`](crate::api::signaling::SignalingModule::adapt_outgoing) for the
+/// control namespace, which is sent by the runner. No control message changed its schema yet, so the message is
+/// serialized unchanged for all versions.
+pub fn adapt_outgoing(message: &Message, version: ProtocolVersion) -> serde_json::Value {
+    let _ = version;
+
+    serde_json::to_value(message).expect("value must be serializable to json")
+}
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     JoinSuccess(JoinSuccess),
@@ -22,15 +36,49 @@ pub enum Message {
     Left(AssociatedParticipant),
     /// The quota's time limit has elapsed
     TimeLimitQuotaElapsed,
+    /// The room was closed by an administrator
+    RoomClosed,
+    /// The participant will be disconnected due to inactivity unless it shows some activity
+    InactivityWarning {
+        disconnects_at: Timestamp,
+    },
+    /// The participant was disconnected due to inactivity
+    InactivityTimeout,
 
     RoleUpdated {
         new_role: Role,
     },
 
-    Error(Error),
+    /// The number of raised hands in a webinar changed
+    RaisedHandsCount {
+        count: usize,
+    },
+    /// A page of the queue of raised hands, response to `get_raised_hands`
+    RaisedHands(RaisedHands),
+    /// The feedback of the participant has been stored, response to `submit_feedback`
+    FeedbackSubmitted,
+    /// The transcription of a recording of the room changed its status, only sent to the owners of the room
+    TranscriptionUpdated {
+        recording_id: AssetId,
+        status: TranscriptionStatus,
+    },
+    /// Response to `time_sync`
+    TimeSync(TimeSync),
+    /// The room is assigned to another controller instance, sent before the websocket is closed
+    ///
+    /// The ticket stays valid and is used to connect to the signaling endpoint of the other instance.
+    Redirect {
+        signaling_url: String,
+    },
+    /// A module failed and has been disabled for the rest of the session
+    ModuleDisabled {
+        module: &'static str,
+    },
+
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct JoinSuccess {
     pub id: ParticipantId,
 
@@ -46,24 +94,59 @@ pub struct JoinSuccess {
 
     pub tariff: Box<TariffResource>,
 
+    /// Branding of the room, unset if neither the room nor its tenant have a branding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branding: Option<BrandingResource>,
+
+    /// Estimated offset of the client clock in milliseconds, set if the join contained the time of the client
+    ///
+    /// Positive if the clock of the server is ahead. The estimation includes the latency of the join message, clients
+    /// refine it with `time_sync`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<i64>,
+
     #[serde(flatten)]
     pub module_data: HashMap<&'static str, serde_json::Value>,
 
     pub participants: Vec<Participant>,
 }
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct TimeSync {
+    /// The time of the client sent with the request
+    pub client_timestamp: Timestamp,
+    /// The time of the server when sending the response
+    pub server_timestamp: Timestamp,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct RaisedHands {
+    /// The total number of raised hands in the room
+    pub total: usize,
+    /// The requested page of raised hands, ordered by the time they were raised
+    pub participants: Vec<RaisedHand>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct RaisedHand {
+    pub id: ParticipantId,
+    pub raised_at: Timestamp,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "reason", rename_all = "snake_case")]
 pub enum JoinBlockedReason {
     ParticipantLimitReached,
+    /// A moderator locked the room, new participants cannot join
+    RoomLocked,
 }
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct AssociatedParticipant {
     pub id: ParticipantId,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "error", rename_all = "snake_case")]
 pub enum Error {
     InvalidJson,
@@ -76,16 +159,77 @@ pub enum Error {
     InsufficientPermissions,
     TargetIsRoomOwner,
     NothingToDo,
+    InvalidBreakoutRoom,
+    MetadataKeyNotAllowed,
+    MetadataValueTooLong,
+    /// The rating is not between 1 and 5 or the comment is too long
+    InvalidFeedback,
+    FeedbackAlreadySubmitted,
+    /// The request could not be handled as the storage of the controller is temporarily unavailable
+    StorageUnavailable,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = super::NAMESPACE;
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InvalidJson => "The message is not valid JSON",
+            Self::InvalidNamespace => "The message targets an unknown module",
+            Self::InvalidUsername => "The display name is invalid",
+            Self::AlreadyJoined => "The participant has already joined the room",
+            Self::NotYetJoined => "The participant has not joined the room yet",
+            Self::NotAcceptedOrNotInWaitingRoom => {
+                "The participant has not been accepted or is not in the waiting room"
+            }
+            Self::RaiseHandsDisabled => "Raising hands is disabled",
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::TargetIsRoomOwner => "The operation cannot target the owner of the room",
+            Self::NothingToDo => "The request does not change anything",
+            Self::InvalidBreakoutRoom => "The breakout room does not exist",
+            Self::MetadataKeyNotAllowed => "The metadata key is not allowed",
+            Self::MetadataValueTooLong => "The metadata value is too long",
+            Self::InvalidFeedback => "The rating or comment of the feedback is invalid",
+            Self::FeedbackAlreadySubmitted => "The participant has already submitted feedback",
+            Self::StorageUnavailable => "The storage is temporarily unavailable",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(self, Self::StorageUnavailable)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum WaitingRoomState {
     Waiting,
     Accepted,
 }
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionStatus {
+    Queued,
+    Processing,
+    Done,
+    Failed,
+}
+
+impl From<db_storage::recording_transcriptions::TranscriptionStatus> for TranscriptionStatus {
+    fn from(status: db_storage::recording_transcriptions::TranscriptionStatus) -> Self {
+        use db_storage::recording_transcriptions::TranscriptionStatus as DbStatus;
+
+        match status {
+            DbStatus::Queued => Self::Queued,
+            DbStatus::Processing => Self::Processing,
+            DbStatus::Done => Self::Done,
+            DbStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Participant {
     pub id: ParticipantId,
 
@@ -153,6 +297,11 @@ mod test {
             "role": "user",
             "closes_at":"2021-06-24T14:00:11.873753715Z",
             "tariff": serde_json::to_value(participant_tariff()).unwrap(),
+            "branding": {
+                "logo_url": "https://example.org/logo.svg",
+                "primary_color": "#1a2b3c",
+            },
+            "clock_offset_ms": -250,
             "participants": [],
         });
 
@@ -167,6 +316,12 @@ mod test {
                     .into(),
             ),
             tariff: participant_tariff().into(),
+            branding: Some(BrandingResource {
+                logo_url: Some("https://example.org/logo.svg".into()),
+                primary_color: Some("#1a2b3c".into()),
+                secondary_color: None,
+            }),
+            clock_offset_ms: Some(-250),
             module_data: Default::default(),
             participants: vec![],
         }))
@@ -193,6 +348,8 @@ mod test {
             role: Role::Guest,
             closes_at: None,
             tariff: participant_tariff().into(),
+            branding: None,
+            clock_offset_ms: None,
             module_data: Default::default(),
             participants: vec![],
         }))
@@ -239,11 +396,128 @@ mod test {
         assert_eq!(expected, produced);
     }
 
+    #[test]
+    fn transcription_updated() {
+        let expected = json!({
+            "message": "transcription_updated",
+            "recording_id": "00000000-0000-0000-0000-000000000000",
+            "status": "done",
+        });
+
+        let produced = serde_json::to_value(&Message::TranscriptionUpdated {
+            recording_id: AssetId::from(uuid::Uuid::nil()),
+            status: TranscriptionStatus::Done,
+        })
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn room_closed() {
+        let expected = json!({"message": "room_closed"});
+
+        let produced = serde_json::to_value(&Message::RoomClosed).unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn inactivity_warning() {
+        let expected = json!({
+            "message": "inactivity_warning",
+            "disconnects_at": "1970-01-01T00:00:00Z"
+        });
+
+        let produced = serde_json::to_value(&Message::InactivityWarning {
+            disconnects_at: Timestamp::unix_epoch(),
+        })
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn raised_hands() {
+        let expected = json!({
+            "message": "raised_hands",
+            "total": 2,
+            "participants": [
+                {
+                    "id": "00000000-0000-0000-0000-000000000001",
+                    "raised_at": "1970-01-01T00:00:00Z"
+                }
+            ]
+        });
+
+        let produced = serde_json::to_value(&Message::RaisedHands(RaisedHands {
+            total: 2,
+            participants: vec![RaisedHand {
+                id: ParticipantId::from_u128(1),
+                raised_at: Timestamp::unix_epoch(),
+            }],
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
     #[test]
     fn error() {
-        let expected = json!({"message": "error", "error": "raise_hands_disabled"});
+        let expected = json!({
+            "message": "error",
+            "module": "control",
+            "error": "raise_hands_disabled",
+            "text": "Raising hands is disabled",
+            "retryable": false
+        });
+
+        let produced =
+            serde_json::to_value(&Message::Error(Error::RaiseHandsDisabled.into())).unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn time_sync() {
+        let expected = json!({
+            "message": "time_sync",
+            "client_timestamp": "1970-01-01T00:00:00Z",
+            "server_timestamp": "1970-01-01T00:00:00Z",
+        });
+
+        let produced = serde_json::to_value(&Message::TimeSync(TimeSync {
+            client_timestamp: Timestamp::unix_epoch(),
+            server_timestamp: Timestamp::unix_epoch(),
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn redirect() {
+        let expected = json!({
+            "message": "redirect",
+            "signaling_url": "wss://controller-2.example.org/signaling",
+        });
+
+        let produced = serde_json::to_value(&Message::Redirect {
+            signaling_url: "wss://controller-2.example.org/signaling".into(),
+        })
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn module_disabled() {
+        let expected = json!({
+            "message": "module_disabled",
+            "module": "chat",
+        });
 
-        let produced = serde_json::to_value(&Message::Error(Error::RaiseHandsDisabled)).unwrap();
+        let produced = serde_json::to_value(&Message::ModuleDisabled { module: "chat" }).unwrap();
 
         assert_eq!(expected, produced);
     }
@@ -3,8 +3,9 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::api::signaling::SignalingRoomId;
+use db_storage::recording_transcriptions::TranscriptionStatus;
 use serde::{Deserialize, Serialize};
-use types::core::{ParticipantId, UserId};
+use types::core::{AssetId, ParticipantId, UserId};
 
 /// Control messages sent between controller modules to communicate changes inside a room
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,6 +38,24 @@ pub enum Message {
     ResetRaisedHands {
         issued_by: ParticipantId,
     },
+
+    /// The queue of raised hands changed, the aggregated count must be refreshed
+    ///
+    /// Sent instead of [`Message::Update`] when attendees of a webinar raise or lower their hand.
+    RaisedHandsUpdated,
+
+    /// The room was closed by an administrator, all participants must leave
+    ///
+    /// Published on the global room exchange by the `close-room` subcommand of the controller cli.
+    CloseRoom,
+
+    /// The transcription of a recording of the room changed its status
+    ///
+    /// Published on the global room exchange to the owners of the room by the recording service endpoints.
+    TranscriptionUpdated {
+        recording_id: AssetId,
+        status: TranscriptionStatus,
+    },
 }
 
 /// Returns the name of the RabbitMQ topic exchange used inside the current room.
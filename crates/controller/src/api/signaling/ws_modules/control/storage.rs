@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use super::incoming::MAX_RAISED_HANDS_LIMIT;
+use super::SnapshotEntry;
 use crate::api::signaling::SignalingRoomId;
 use crate::redis_wrapper::RedisConnection;
 use anyhow::{Context, Result};
@@ -12,7 +14,8 @@ use redis_args::ToRedisArgs;
 use std::convert::identity;
 use std::fmt::Debug;
 use std::time::Duration;
-use types::core::{ParticipantId, RoomId, Timestamp};
+use types::core::{ParticipantId, ParticipationKind, RoomId, TenantId, Timestamp, UserId};
+use uuid::Uuid;
 
 /// Describes a set of participants inside a room.
 /// This MUST always be locked before accessing it
@@ -32,11 +35,57 @@ pub struct RoomLock {
 /// Key used for the lock over the room participants set
 #[derive(ToRedisArgs)]
 #[to_redis_args(fmt = "k3k-signaling:room={room}:participants:attributes:{attribute_name}")]
-struct RoomParticipantAttributes<'s> {
+pub(super) struct RoomParticipantAttributes<'s> {
     room: SignalingRoomId,
     attribute_name: &'s str,
 }
 
+/// Key of the hash containing the given attribute of all participants in the room
+pub(super) fn attribute_key(room: SignalingRoomId, name: &str) -> RoomParticipantAttributes<'_> {
+    RoomParticipantAttributes {
+        room,
+        attribute_name: name,
+    }
+}
+
+/// Hash of the cached [`SnapshotEntry`] of the participants in the room, see [`get_participant_snapshot`]
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:participants:snapshot")]
+struct RoomParticipantSnapshot {
+    room: SignalingRoomId,
+}
+
+/// Hash of counters increased whenever the data of a participant changes
+///
+/// Entries of the participant snapshot are only written if the counter of the participant did not change since the
+/// data was read, see [`set_participant_snapshot`].
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:participants:snapshot_generations")]
+struct RoomParticipantSnapshotGenerations {
+    room: SignalingRoomId,
+}
+
+/// Caches the given entries of the participant snapshot unless the data of the participant changed since it was read
+///
+/// KEYS[1] is the snapshot, KEYS[2] the generations, ARGV[1] the TTL of the snapshot in milliseconds followed by
+/// triples of participant, generation at the time the data was read and the entry.
+///
+/// Returns the number of cached entries
+const SET_PARTICIPANT_SNAPSHOT: &str = r#"
+local cached = 0
+for i = 2, #ARGV, 3 do
+    local generation = redis.call("HGET", KEYS[2], ARGV[i]) or "0"
+    if generation == ARGV[i + 1] then
+        redis.call("HSET", KEYS[1], ARGV[i], ARGV[i + 2])
+        cached = cached + 1
+    end
+end
+if cached > 0 then
+    redis.call("PEXPIRE", KEYS[1], ARGV[1])
+end
+return cached
+"#;
+
 /// The total count of all participants in the room, also considers participants in breakout rooms and the waiting room
 ///
 /// Notice that this key only contains the [`RoomId`] as it applies to all breakout rooms as well
@@ -62,6 +111,64 @@ struct RoomClosesAt {
     room: SignalingRoomId,
 }
 
+/// Sorted set of the participants in the room with a raised hand, scored by the time they raised it
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:raised_hands")]
+struct RoomRaisedHands {
+    room: SignalingRoomId,
+}
+
+/// Usage statistics of the current session of the room, stored as hash
+///
+/// Notice that this key only contains the [`RoomId`] as it applies to all breakout rooms as well
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room_id}:statistics")]
+struct RoomStatisticsKey {
+    room_id: RoomId,
+}
+
+/// Set of the module namespaces used in the current session of the room
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room_id}:statistics:modules")]
+struct RoomStatisticsModules {
+    room_id: RoomId,
+}
+
+/// Set of the registered users which joined the current session of the room
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room_id}:statistics:users")]
+struct RoomStatisticsUsers {
+    room_id: RoomId,
+}
+
+/// Sets the start of the session if it isn't set yet, increments the number of participants inside the room and
+/// raises the peak participant count to it
+///
+/// Returns 1 if the start of the session has been set
+const RECORD_ROOM_STATISTICS_JOIN: &str = r#"
+local started = redis.call("HSETNX", KEYS[1], "started_at", ARGV[1])
+redis.call("HSETNX", KEYS[1], "tenant_id", ARGV[2])
+local participants = redis.call("HINCRBY", KEYS[1], "participants", 1)
+local peak = tonumber(redis.call("HGET", KEYS[1], "peak_participants") or "0")
+if participants > peak then
+    redis.call("HSET", KEYS[1], "peak_participants", participants)
+end
+return started
+"#;
+
+/// Sorted set of empty rooms, scored by the unix timestamp at which they get destroyed
+const EMPTY_ROOMS: &str = "k3k-signaling:empty_rooms";
+
+/// Marker of a meeting in the room which has been prepared by the pre-warm scheduler
+///
+/// Not prefixed with the room key, so it is kept when the prepared room gets destroyed before the meeting started.
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:prepared_room={room_id}:starts_at={starts_at}")]
+struct PreparedRoom {
+    room_id: RoomId,
+    starts_at: i64,
+}
+
 /// The room's mutex
 ///
 /// Must be taken when joining and leaving the room.
@@ -160,6 +267,28 @@ pub async fn participants_all_left(
     Ok(left_at_attrs.iter().all(Option::is_some))
 }
 
+/// Returns true if participants other than services like bots and the recorder are inside the room
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn has_present_participants(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<bool> {
+    let participants = get_all_participants(redis_conn, room).await?;
+
+    let kinds: Vec<Option<ParticipationKind>> =
+        get_attribute_for_participants(redis_conn, room, "kind", &participants).await?;
+    let left_at_attrs: Vec<Option<Timestamp>> =
+        get_attribute_for_participants(redis_conn, room, "left_at", &participants).await?;
+
+    Ok(kinds.iter().zip(left_at_attrs).any(|(kind, left_at)| {
+        left_at.is_none()
+            && matches!(
+                kind,
+                Some(ParticipationKind::User | ParticipationKind::Guest | ParticipationKind::Sip)
+            )
+    }))
+}
+
 #[tracing::instrument(level = "debug", skip(redis_conn))]
 pub async fn remove_attribute_key(
     redis_conn: &mut RedisConnection,
@@ -182,7 +311,9 @@ pub async fn remove_attribute(
     participant: ParticipantId,
     name: &str,
 ) -> Result<()> {
-    redis_conn
+    let mut pipe = redis::pipe();
+
+    pipe.atomic()
         .hdel(
             RoomParticipantAttributes {
                 room,
@@ -190,6 +321,11 @@ pub async fn remove_attribute(
             },
             participant,
         )
+        .ignore();
+
+    invalidate_snapshot_entry(&mut pipe, room, participant);
+
+    pipe.query_async(redis_conn)
         .await
         .with_context(|| format!("Failed to remove participant attribute key, {name}"))
 }
@@ -205,7 +341,9 @@ pub async fn set_attribute<V>(
 where
     V: Debug + ToRedisArgs + Send + Sync,
 {
-    redis_conn
+    let mut pipe = redis::pipe();
+
+    pipe.atomic()
         .hset(
             RoomParticipantAttributes {
                 room,
@@ -214,6 +352,11 @@ where
             participant,
             value,
         )
+        .ignore();
+
+    invalidate_snapshot_entry(&mut pipe, room, participant);
+
+    pipe.query_async::<_, ()>(redis_conn)
         .await
         .with_context(|| format!("Failed to set attribute {name}"))?;
 
@@ -251,7 +394,7 @@ impl AttrPipeline {
             )
             .ignore();
 
-        self
+        self.invalidate_snapshot()
     }
 
     pub fn get(&mut self, name: &str) -> &mut Self {
@@ -277,6 +420,13 @@ impl AttrPipeline {
             )
             .ignore();
 
+        self.invalidate_snapshot()
+    }
+
+    /// Discard the participant's cached entry of the participant snapshot, as its attributes are changed
+    fn invalidate_snapshot(&mut self) -> &mut Self {
+        invalidate_snapshot_entry(&mut self.pipe, self.room, self.participant);
+
         self
     }
 
@@ -341,6 +491,120 @@ where
     }
 }
 
+/// Get the cached [`SnapshotEntry`] of multiple participants
+///
+/// The index of the entries in the returned vector is a direct mapping to the provided list of participants. Entries
+/// are missing if they were not cached yet or the data of the participant changed since they were cached. Each entry
+/// comes with the generation of the participant's data, which must be passed to [`set_participant_snapshot`] when
+/// caching data read after this call.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_participant_snapshot(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participants: &[ParticipantId],
+) -> Result<Vec<(Option<SnapshotEntry>, u64)>> {
+    // Special case: HMGET cannot handle empty arrays (missing arguments)
+    if participants.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let (entries, generations): (Vec<Option<SnapshotEntry>>, Vec<Option<u64>>) = redis::pipe()
+        .atomic()
+        .cmd("HMGET")
+        .arg(RoomParticipantSnapshot { room })
+        .arg(participants)
+        .cmd("HMGET")
+        .arg(RoomParticipantSnapshotGenerations { room })
+        .arg(participants)
+        .query_async(redis_conn)
+        .await
+        .context("Failed to HMGET the participant snapshot")?;
+
+    Ok(entries
+        .into_iter()
+        .zip(generations.into_iter().map(Option::unwrap_or_default))
+        .collect())
+}
+
+/// Cache the [`SnapshotEntry`] of the given participants, the whole snapshot expires after `ttl`
+///
+/// Each entry is only cached if the generation of the participant still matches the one returned by
+/// [`get_participant_snapshot`] before the data was read, otherwise the data may be outdated already.
+#[tracing::instrument(level = "debug", skip(redis_conn, entries))]
+pub async fn set_participant_snapshot(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    entries: &[(ParticipantId, u64, SnapshotEntry)],
+    ttl: Duration,
+) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let script = redis::Script::new(SET_PARTICIPANT_SNAPSHOT);
+    let mut invocation = script.key(RoomParticipantSnapshot { room });
+
+    invocation
+        .key(RoomParticipantSnapshotGenerations { room })
+        .arg(ttl.as_millis() as u64);
+
+    for (participant, generation, entry) in entries {
+        invocation.arg(participant).arg(generation).arg(entry);
+    }
+
+    invocation
+        .invoke_async::<_, ()>(redis_conn)
+        .await
+        .context("Failed to set the participant snapshot")
+}
+
+/// Discard the cached entry of the participant from the participant snapshot
+///
+/// Must be called after the data of the participant changed, e.g. when a module invalidates its data.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn invalidate_participant_snapshot(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    invalidate_snapshot_entry(&mut pipe, room, participant);
+
+    pipe.query_async(redis_conn)
+        .await
+        .context("Failed to invalidate the participant snapshot")
+}
+
+/// Add the commands discarding the cached entry of the participant to the pipeline
+fn invalidate_snapshot_entry(
+    pipe: &mut redis::Pipeline,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) {
+    pipe.hincr(RoomParticipantSnapshotGenerations { room }, participant, 1)
+        .ignore()
+        .hdel(RoomParticipantSnapshot { room }, participant)
+        .ignore();
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_participant_snapshot(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis::pipe()
+        .atomic()
+        .del(RoomParticipantSnapshot { room })
+        .ignore()
+        .del(RoomParticipantSnapshotGenerations { room })
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .context("Failed to DEL the participant snapshot")
+}
+
 #[derive(Debug, ToRedisArgs)]
 #[to_redis_args(fmt = "k3k-signaling:runner:{id}")]
 pub struct ParticipantIdRunnerLock {
@@ -553,3 +817,485 @@ pub async fn remove_room_closes_at(
         .await
         .context("Failed to DEL the point in time the room closes")
 }
+
+/// Add the participant to the queue of raised hands
+///
+/// Returns false if the hand of the participant was already raised
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_raised_hand(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    raised_at: Timestamp,
+) -> Result<bool> {
+    let added: usize = redis::cmd("ZADD")
+        .arg(RoomRaisedHands { room })
+        .arg("NX")
+        .arg(raised_at)
+        .arg(participant)
+        .query_async(redis_conn)
+        .await
+        .context("Failed to ZADD the raised hand")?;
+
+    Ok(added > 0)
+}
+
+/// Remove the participant from the queue of raised hands
+///
+/// Returns false if the hand of the participant was not raised
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn remove_raised_hand(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<bool> {
+    let removed: usize = redis_conn
+        .zrem(RoomRaisedHands { room }, participant)
+        .await
+        .context("Failed to ZREM the raised hand")?;
+
+    Ok(removed > 0)
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_raised_hands_count(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<usize> {
+    redis_conn
+        .zcard(RoomRaisedHands { room })
+        .await
+        .context("Failed to ZCARD the raised hands")
+}
+
+/// Returns a page of the queue of raised hands, ordered by the time the hands were raised
+///
+/// The `limit` is capped at [`MAX_RAISED_HANDS_LIMIT`]
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_raised_hands(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<(ParticipantId, Timestamp)>> {
+    let limit = limit.min(MAX_RAISED_HANDS_LIMIT);
+
+    // Offsets beyond the index range of redis cannot contain any raised hand, negative indices would count from the
+    // end of the queue instead
+    let start = match isize::try_from(offset) {
+        Ok(start) if limit > 0 => start,
+        _ => return Ok(vec![]),
+    };
+    let stop = isize::try_from(offset.saturating_add(limit - 1)).unwrap_or(isize::MAX);
+
+    redis_conn
+        .zrange_withscores(RoomRaisedHands { room }, start, stop)
+        .await
+        .context("Failed to ZRANGE the raised hands")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_raised_hands(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RoomRaisedHands { room })
+        .await
+        .context("Failed to DEL the raised hands")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn schedule_empty_room_destroy(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    destroy_at: Timestamp,
+) -> Result<()> {
+    redis_conn
+        .zadd(EMPTY_ROOMS, room_id.to_string(), destroy_at.timestamp())
+        .await
+        .context("Failed to ZADD the empty room")
+}
+
+/// Remove the room from the set of empty rooms
+///
+/// Returns false if the room was not scheduled to be destroyed (anymore)
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn cancel_empty_room_destroy(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+) -> Result<bool> {
+    let removed: usize = redis_conn
+        .zrem(EMPTY_ROOMS, room_id.to_string())
+        .await
+        .context("Failed to ZREM the empty room")?;
+
+    Ok(removed > 0)
+}
+
+/// Returns all empty rooms whose grace period has elapsed at the given point in time
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_expired_empty_rooms(
+    redis_conn: &mut RedisConnection,
+    now: Timestamp,
+) -> Result<Vec<RoomId>> {
+    let room_ids: Vec<String> = redis_conn
+        .zrangebyscore(EMPTY_ROOMS, "-inf", now.timestamp())
+        .await
+        .context("Failed to ZRANGEBYSCORE the empty rooms")?;
+
+    Ok(room_ids
+        .into_iter()
+        .filter_map(|room_id| room_id.parse::<Uuid>().ok())
+        .map(RoomId::from)
+        .collect())
+}
+
+/// Mark the meeting starting at the given point in time as prepared
+///
+/// The marker expires after `expiry`. Returns false if the meeting has already been marked by another instance.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn try_mark_room_prepared(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    starts_at: Timestamp,
+    expiry: Duration,
+) -> Result<bool> {
+    let set: Option<String> = redis::cmd("SET")
+        .arg(PreparedRoom {
+            room_id,
+            starts_at: starts_at.timestamp(),
+        })
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(expiry.as_secs().max(1))
+        .query_async(redis_conn)
+        .await
+        .context("Failed to SET the prepared room marker")?;
+
+    Ok(set.is_some())
+}
+
+/// Returns the keys of all redis entries of the room, including the entries of its breakout rooms
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_room_keys(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+) -> Result<Vec<String>> {
+    let mut iter = redis_conn
+        .scan_match::<_, String>(format!("k3k-signaling:room={room_id}*"))
+        .await
+        .context("Failed to scan the redis keys of the room")?;
+
+    let mut keys = Vec::new();
+
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+/// Usage statistics of a room session, collected while the room is alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomStatistics {
+    pub tenant_id: TenantId,
+    pub started_at: Timestamp,
+    pub peak_participants: isize,
+    pub participant_seconds: i64,
+    pub modules: Vec<String>,
+    /// Registered users which joined the session
+    pub users: Vec<UserId>,
+}
+
+/// Record that a participant entered the room
+///
+/// The statistics count the participants separately from the participant count of the room, as services like bots
+/// are not recorded. Returns true if the participant started a new session of the room.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record_room_statistics_join(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    tenant_id: TenantId,
+) -> Result<bool> {
+    redis::Script::new(RECORD_ROOM_STATISTICS_JOIN)
+        .key(RoomStatisticsKey { room_id })
+        .arg(Timestamp::now())
+        .arg(tenant_id.to_string())
+        .invoke_async(redis_conn)
+        .await
+        .context("Failed to record the join in the room statistics")
+}
+
+/// Add the namespaces of the modules to the set of modules used in the room
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record_room_statistics_modules(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    modules: &[&str],
+) -> Result<()> {
+    if modules.is_empty() {
+        return Ok(());
+    }
+
+    redis_conn
+        .sadd(RoomStatisticsModules { room_id }, modules)
+        .await
+        .context("Failed to SADD the modules to the room statistics")
+}
+
+/// Add the registered user to the set of users which joined the room
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record_room_statistics_user(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    user_id: UserId,
+) -> Result<()> {
+    redis_conn
+        .sadd(RoomStatisticsUsers { room_id }, user_id.to_string())
+        .await
+        .context("Failed to SADD the user to the room statistics")
+}
+
+/// Add the time a participant spent in the room to the room statistics
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record_room_statistics_participant_time(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    seconds: i64,
+) -> Result<()> {
+    redis_conn
+        .hincr(
+            RoomStatisticsKey { room_id },
+            "participant_seconds",
+            seconds,
+        )
+        .await
+        .context("Failed to HINCRBY the participant time of the room statistics")
+}
+
+/// Record that a participant recorded with [`record_room_statistics_join`] left the room
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn record_room_statistics_leave(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+) -> Result<()> {
+    redis_conn
+        .hincr(RoomStatisticsKey { room_id }, "participants", -1)
+        .await
+        .context("Failed to HINCRBY the participants of the room statistics")
+}
+
+/// Remove the statistics of the room session from redis and return them
+///
+/// Returns `None` if no statistics were recorded for the room.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn take_room_statistics(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+) -> Result<Option<RoomStatistics>> {
+    let (tenant_id, started_at, peak_participants, participant_seconds, modules, users): (
+        Option<String>,
+        Option<Timestamp>,
+        Option<isize>,
+        Option<i64>,
+        Vec<String>,
+        Vec<String>,
+    ) = redis::pipe()
+        .atomic()
+        .hget(RoomStatisticsKey { room_id }, "tenant_id")
+        .hget(RoomStatisticsKey { room_id }, "started_at")
+        .hget(RoomStatisticsKey { room_id }, "peak_participants")
+        .hget(RoomStatisticsKey { room_id }, "participant_seconds")
+        .smembers(RoomStatisticsModules { room_id })
+        .smembers(RoomStatisticsUsers { room_id })
+        .del(RoomStatisticsKey { room_id })
+        .ignore()
+        .del(RoomStatisticsModules { room_id })
+        .ignore()
+        .del(RoomStatisticsUsers { room_id })
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .context("Failed to take the room statistics")?;
+
+    let tenant_id = tenant_id.and_then(|tenant_id| tenant_id.parse::<Uuid>().ok());
+
+    let (tenant_id, started_at) = match (tenant_id, started_at) {
+        (Some(tenant_id), Some(started_at)) => (TenantId::from(tenant_id), started_at),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(RoomStatistics {
+        tenant_id,
+        started_at,
+        peak_participants: peak_participants.unwrap_or_default(),
+        participant_seconds: participant_seconds.unwrap_or_default(),
+        modules,
+        users: users
+            .into_iter()
+            .filter_map(|user_id| user_id.parse::<Uuid>().ok())
+            .map(UserId::from)
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::signaling::ws_modules::control::{ControlData, ParticipantMetadata};
+    use crate::api::signaling::Role;
+    use crate::redis_wrapper::testing::setup;
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+    use serial_test::serial;
+
+    const ROOM: SignalingRoomId = SignalingRoomId::new_test(RoomId::from(Uuid::nil()));
+
+    #[tokio::test]
+    #[serial]
+    async fn raised_hands_pages() {
+        let mut redis_conn = setup().await;
+
+        let participants: Vec<_> = (0..3).map(ParticipantId::from_u128).collect();
+
+        for (secs, participant) in participants.iter().enumerate() {
+            let raised_at = Utc.timestamp_opt(secs as i64, 0).unwrap().into();
+
+            add_raised_hand(&mut redis_conn, ROOM, *participant, raised_at)
+                .await
+                .unwrap();
+        }
+
+        let page = |raised_hands: Vec<(ParticipantId, Timestamp)>| -> Vec<ParticipantId> {
+            raised_hands.into_iter().map(|(id, _)| id).collect()
+        };
+
+        assert_eq!(
+            page(get_raised_hands(&mut redis_conn, ROOM, 1, 5).await.unwrap()),
+            participants[1..]
+        );
+        assert!(get_raised_hands(&mut redis_conn, ROOM, 0, 0)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Must neither overflow nor wrap around to the end of the queue
+        assert!(
+            get_raised_hands(&mut redis_conn, ROOM, usize::MAX, usize::MAX)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            get_raised_hands(&mut redis_conn, ROOM, isize::MAX as usize, usize::MAX)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            page(
+                get_raised_hands(&mut redis_conn, ROOM, 0, usize::MAX)
+                    .await
+                    .unwrap()
+            ),
+            participants
+        );
+    }
+
+    fn snapshot_entry(display_name: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            control_data: ControlData {
+                display_name: display_name.into(),
+                role: Role::User,
+                avatar_url: None,
+                participation_kind: ParticipationKind::User,
+                hand_is_up: false,
+                joined_at: Timestamp::unix_epoch(),
+                left_at: None,
+                hand_updated_at: Timestamp::unix_epoch(),
+                metadata: ParticipantMetadata::default(),
+            },
+            module_data: Default::default(),
+        }
+    }
+
+    fn cached_names(snapshot: &[(Option<SnapshotEntry>, u64)]) -> Vec<Option<String>> {
+        snapshot
+            .iter()
+            .map(|(entry, _)| {
+                entry
+                    .as_ref()
+                    .map(|entry| entry.control_data.display_name.clone())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn outdated_data_is_not_cached() {
+        let mut redis_conn = setup().await;
+
+        let alice = ParticipantId::from_u128(1);
+        let bob = ParticipantId::from_u128(2);
+        let ttl = Duration::from_secs(10);
+
+        let snapshot = get_participant_snapshot(&mut redis_conn, ROOM, &[alice, bob])
+            .await
+            .unwrap();
+        assert_eq!(cached_names(&snapshot), vec![None, None]);
+
+        // Alice changes her name while the data read above is still being processed
+        set_attribute(&mut redis_conn, ROOM, alice, "display_name", "Alice")
+            .await
+            .unwrap();
+
+        set_participant_snapshot(
+            &mut redis_conn,
+            ROOM,
+            &[
+                (alice, snapshot[0].1, snapshot_entry("Participant")),
+                (bob, snapshot[1].1, snapshot_entry("Bob")),
+            ],
+            ttl,
+        )
+        .await
+        .unwrap();
+
+        let snapshot = get_participant_snapshot(&mut redis_conn, ROOM, &[alice, bob])
+            .await
+            .unwrap();
+        assert_eq!(cached_names(&snapshot), vec![None, Some("Bob".into())]);
+
+        // Data read after the change is cached
+        set_participant_snapshot(
+            &mut redis_conn,
+            ROOM,
+            &[(alice, snapshot[0].1, snapshot_entry("Alice"))],
+            ttl,
+        )
+        .await
+        .unwrap();
+
+        // Invalidating the module data of bob discards his entry
+        invalidate_participant_snapshot(&mut redis_conn, ROOM, bob)
+            .await
+            .unwrap();
+
+        let snapshot = get_participant_snapshot(&mut redis_conn, ROOM, &[alice, bob])
+            .await
+            .unwrap();
+        assert_eq!(cached_names(&snapshot), vec![Some("Alice".into()), None]);
+
+        delete_participant_snapshot(&mut redis_conn, ROOM)
+            .await
+            .unwrap();
+
+        let snapshot = get_participant_snapshot(&mut redis_conn, ROOM, &[alice, bob])
+            .await
+            .unwrap();
+        assert_eq!(cached_names(&snapshot), vec![None, None]);
+        assert!(snapshot.iter().all(|(_, generation)| *generation == 0));
+    }
+}
@@ -5,4 +5,5 @@
 pub mod breakout;
 pub mod control;
 pub mod moderation;
+pub mod plugins;
 pub mod recording;
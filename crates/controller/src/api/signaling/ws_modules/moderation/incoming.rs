@@ -2,10 +2,11 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Deserialize;
-use types::core::ParticipantId;
+use types::core::{ParticipantId, Timestamp};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Message {
     Kick(Target),
@@ -20,14 +21,62 @@ pub enum Message {
     Accept(Target),
 
     ResetRaisedHands,
+
+    GetConnectionHistory(Target),
+
+    ChangeDisplayName(ChangeDisplayName),
+
+    EnableRealNames,
+    DisableRealNames,
+
+    /// Allow an attendee of a room in webinar mode to publish media and to write to everyone
+    PromoteToPanelist(Target),
+    /// Make a panelist an attendee again
+    DemoteToAttendee(Target),
+
+    /// Mark the beginning of a chapter of the meeting
+    SetMarker(SetMarker),
+
+    /// Show the participants prominently on the stage of everyone
+    Spotlight(Targets),
+    /// Remove the participants from the spotlights
+    Unspotlight(Targets),
+
+    /// Prevent new participants from joining the room, participants inside the room stay
+    LockRoom,
+    /// Allow new participants to join the room again
+    UnlockRoom,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Target {
-    /// The participant to ban/kick from the room
+    /// The participant to ban/kick from the room or get the connection history of
     pub target: ParticipantId,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Targets {
+    /// The participants to spotlight or unspotlight
+    pub targets: Vec<ParticipantId>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ChangeDisplayName {
+    /// The participant to rename
+    pub target: ParticipantId,
+    /// The new display name of the participant
+    pub new_name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetMarker {
+    /// Title of the chapter
+    pub title: String,
+    /// Beginning of the chapter, defaults to the time the message is received
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -86,4 +135,101 @@ mod test {
             panic!()
         }
     }
+
+    #[test]
+    fn get_connection_history() {
+        let json = r#"
+        {
+            "action": "get_connection_history",
+            "target": "00000000-0000-0000-0000-000000000000"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::GetConnectionHistory(Target { target }) = msg {
+            assert_eq!(target, ParticipantId::nil());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn change_display_name() {
+        let json = r#"
+        {
+            "action": "change_display_name",
+            "target": "00000000-0000-0000-0000-000000000000",
+            "new_name": "Jane Doe"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::ChangeDisplayName(ChangeDisplayName { target, new_name }) = msg {
+            assert_eq!(target, ParticipantId::nil());
+            assert_eq!(new_name, "Jane Doe");
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn promote_to_panelist() {
+        let json = r#"
+        {
+            "action": "promote_to_panelist",
+            "target": "00000000-0000-0000-0000-000000000000"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::PromoteToPanelist(Target { target }) = msg {
+            assert_eq!(target, ParticipantId::nil());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn set_marker() {
+        let json = r#"
+        {
+            "action": "set_marker",
+            "title": "Q&A",
+            "timestamp": "2023-01-01T10:30:00Z"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SetMarker(SetMarker { title, timestamp }) = msg {
+            assert_eq!(title, "Q&A");
+            assert_eq!(
+                timestamp.map(|timestamp| timestamp.to_rfc3339()),
+                Some("2023-01-01T10:30:00+00:00".into())
+            );
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn spotlight() {
+        let json = r#"
+        {
+            "action": "spotlight",
+            "targets": ["00000000-0000-0000-0000-000000000000"]
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Spotlight(Targets { targets }) = msg {
+            assert_eq!(targets, vec![ParticipantId::nil()]);
+        } else {
+            panic!()
+        }
+    }
 }
@@ -2,11 +2,16 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::api::Participant;
 use crate::{api::signaling::prelude::*, redis_wrapper::RedisConnection};
 use actix_http::ws::CloseCode;
 use anyhow::Result;
+use database::Db;
+use db_storage::room_markers::{NewRoomMarker, RoomMarker};
+use itertools::Itertools;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::Arc;
 use types::core::{ParticipantId, RoomId, UserId};
 
 pub mod incoming;
@@ -16,16 +21,49 @@ pub mod storage;
 
 pub const NAMESPACE: &str = "moderation";
 
+/// Maximum number of participants which can be spotlighted at the same time
+pub const MAX_SPOTLIGHTS: usize = 16;
+
 pub struct ModerationModule {
     room: SignalingRoomId,
     id: ParticipantId,
+    user_id: Option<UserId>,
+    webinar_mode: bool,
+    db: Arc<Db>,
+}
+
+/// Published on the module bus of a participant in webinar mode when it got promoted to a panelist or demoted to an
+/// attendee
+#[derive(Debug, Clone, Copy)]
+pub struct PanelistStatusChanged {
+    pub is_panelist: bool,
+}
+
+/// Published on the module bus of every participant when the spotlighted participants of the room changed
+#[derive(Debug, Clone)]
+pub struct SpotlightsChanged {
+    pub spotlights: Vec<ParticipantId>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ModerationModuleFrontendData {
+    /// Participants spotlighted by a moderator, sent to every participant
+    spotlights: Vec<ParticipantId>,
+    /// Only sent to moderators
+    #[serde(flatten)]
+    moderator_data: Option<ModeratorFrontendData>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModeratorFrontendData {
     waiting_room_enabled: bool,
     waiting_room_participants: Vec<control::outgoing::Participant>,
     raise_hands_enabled: bool,
+    real_names_required: bool,
+    /// If set new participants cannot join the room
+    room_locked: bool,
+    /// Markers set in the running session of the room
+    markers: Vec<outgoing::Marker>,
 }
 
 async fn build_waiting_room_participants(
@@ -75,9 +113,17 @@ impl SignalingModule for ModerationModule {
         _params: &Self::Params,
         _protocol: &'static str,
     ) -> Result<Option<Self>> {
+        let user_id = match ctx.participant() {
+            Participant::User(user) => Some(user.id),
+            _ => None,
+        };
+
         Ok(Some(Self {
             room: ctx.room_id(),
             id: ctx.participant_id(),
+            user_id,
+            webinar_mode: ctx.room().webinar_mode,
+            db: ctx.db().clone(),
         }))
     }
 
@@ -92,6 +138,8 @@ impl SignalingModule for ModerationModule {
                 frontend_data,
                 participants: _,
             } => {
+                let mut moderator_data = None;
+
                 if ctx.role() == Role::Moderator {
                     let waiting_room_enabled =
                         storage::is_waiting_room_enabled(ctx.redis_conn(), self.room.room_id())
@@ -101,6 +149,13 @@ impl SignalingModule for ModerationModule {
                         storage::is_raise_hands_enabled(ctx.redis_conn(), self.room.room_id())
                             .await?;
 
+                    let real_names_required =
+                        storage::is_real_names_required(ctx.redis_conn(), self.room.room_id())
+                            .await?;
+
+                    let room_locked =
+                        storage::is_room_locked(ctx.redis_conn(), self.room.room_id()).await?;
+
                     let list =
                         storage::waiting_room_all(ctx.redis_conn(), self.room.room_id()).await?;
                     let mut waiting_room_participants = build_waiting_room_participants(
@@ -124,14 +179,35 @@ impl SignalingModule for ModerationModule {
 
                     waiting_room_participants.append(&mut accepted_waiting_room_participants);
 
-                    *frontend_data = Some(ModerationModuleFrontendData {
+                    let markers = self.get_markers().await?;
+
+                    moderator_data = Some(ModeratorFrontendData {
                         waiting_room_enabled,
                         waiting_room_participants,
                         raise_hands_enabled,
+                        real_names_required,
+                        room_locked,
+                        markers,
                     });
                 }
+
+                let spotlights = storage::get_spotlights(ctx.redis_conn(), self.room).await?;
+
+                *frontend_data = Some(ModerationModuleFrontendData {
+                    spotlights,
+                    moderator_data,
+                });
+            }
+            Event::Leaving => {
+                // Spotlights of participants which left the room are removed
+                if storage::remove_spotlights(ctx.redis_conn(), self.room, &[self.id]).await? > 0 {
+                    ctx.rabbitmq_publish(
+                        control::rabbitmq::current_room_exchange_name(self.room),
+                        control::rabbitmq::room_all_routing_key().into(),
+                        rabbitmq::Message::SpotlightsUpdated { issued_by: None },
+                    );
+                }
             }
-            Event::Leaving => {}
             Event::RaiseHand => {}
             Event::LowerHand => {}
             Event::ParticipantJoined(_, _) => {}
@@ -149,7 +225,9 @@ impl SignalingModule for ModerationModule {
                 if let Some(user_id) = user_id {
                     storage::ban_user(ctx.redis_conn(), self.room.room_id(), user_id).await?;
                 } else {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::CannotBanGuest));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::CannotBanGuest.into(),
+                    ));
                     return Ok(());
                 }
 
@@ -225,6 +303,10 @@ impl SignalingModule for ModerationModule {
                     return Ok(());
                 }
 
+                // Every participant lowers its own hand, the queue is cleared at once to refresh the aggregated
+                // count of raised hands without a notification per lowered hand
+                control::storage::delete_raised_hands(ctx.redis_conn(), self.room).await?;
+
                 ctx.rabbitmq_publish_control(
                     control::rabbitmq::current_room_exchange_name(self.room),
                     control::rabbitmq::room_all_routing_key().to_string(),
@@ -254,6 +336,7 @@ impl SignalingModule for ModerationModule {
 
                 storage::set_raise_hands_enabled(ctx.redis_conn(), self.room.room_id(), false)
                     .await?;
+                control::storage::delete_raised_hands(ctx.redis_conn(), self.room).await?;
 
                 ctx.rabbitmq_publish_control(
                     control::rabbitmq::current_room_exchange_name(self.room),
@@ -262,6 +345,302 @@ impl SignalingModule for ModerationModule {
                 );
             }
 
+            Event::WsMessage(incoming::Message::GetConnectionHistory(incoming::Target {
+                target,
+            })) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                if !control::storage::participants_contains(ctx.redis_conn(), self.room, target)
+                    .await?
+                {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownParticipant.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let events = connection_history::get(ctx.redis_conn(), target).await?;
+
+                ctx.ws_send(outgoing::Message::ConnectionHistory(
+                    outgoing::ConnectionHistory {
+                        participant_id: target,
+                        events,
+                    },
+                ));
+            }
+
+            Event::WsMessage(incoming::Message::ChangeDisplayName(
+                incoming::ChangeDisplayName { target, new_name },
+            )) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                if !control::storage::participants_contains(ctx.redis_conn(), self.room, target)
+                    .await?
+                {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownParticipant.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let new_name = new_name.split_whitespace().join(" ");
+
+                if new_name.is_empty() || new_name.chars().count() > 100 {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidDisplayName.into(),
+                    ));
+                    return Ok(());
+                }
+
+                let user_id: Option<UserId> =
+                    control::storage::get_attribute(ctx.redis_conn(), self.room, target, "user_id")
+                        .await?;
+
+                let owner = match user_id {
+                    Some(user_id) => storage::DisplayNameOwner::User(user_id),
+                    None => storage::DisplayNameOwner::Participant(target),
+                };
+
+                storage::set_forced_display_name(
+                    ctx.redis_conn(),
+                    self.room.room_id(),
+                    owner,
+                    &new_name,
+                )
+                .await?;
+
+                control::storage::set_attribute(
+                    ctx.redis_conn(),
+                    self.room,
+                    target,
+                    "display_name",
+                    &new_name,
+                )
+                .await?;
+
+                log::info!(
+                    "Moderator {} changed the display name of participant {} in room {}",
+                    self.id,
+                    target,
+                    self.room
+                );
+
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_participant_routing_key(target),
+                    rabbitmq::Message::DisplayNameChanged {
+                        target,
+                        new_name,
+                        issued_by: self.id,
+                    },
+                );
+
+                ctx.rabbitmq_publish_control(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().to_string(),
+                    control::rabbitmq::Message::Update(target),
+                );
+            }
+            Event::WsMessage(incoming::Message::EnableRealNames) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                storage::set_real_names_required(ctx.redis_conn(), self.room.room_id(), true)
+                    .await?;
+
+                ctx.rabbitmq_publish(
+                    breakout::rabbitmq::global_exchange_name(self.room.room_id()),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::RealNamesEnableUpdated { issued_by: self.id },
+                );
+            }
+            Event::WsMessage(incoming::Message::DisableRealNames) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                storage::set_real_names_required(ctx.redis_conn(), self.room.room_id(), false)
+                    .await?;
+
+                ctx.rabbitmq_publish(
+                    breakout::rabbitmq::global_exchange_name(self.room.room_id()),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::RealNamesEnableUpdated { issued_by: self.id },
+                );
+            }
+            Event::WsMessage(incoming::Message::LockRoom) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                storage::set_room_locked(ctx.redis_conn(), self.room.room_id(), true).await?;
+
+                log::info!("Moderator {} locked room {}", self.id, self.room);
+
+                ctx.rabbitmq_publish(
+                    breakout::rabbitmq::global_exchange_name(self.room.room_id()),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::RoomLockUpdated { issued_by: self.id },
+                );
+            }
+            Event::WsMessage(incoming::Message::UnlockRoom) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                storage::set_room_locked(ctx.redis_conn(), self.room.room_id(), false).await?;
+
+                log::info!("Moderator {} unlocked room {}", self.id, self.room);
+
+                ctx.rabbitmq_publish(
+                    breakout::rabbitmq::global_exchange_name(self.room.room_id()),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::RoomLockUpdated { issued_by: self.id },
+                );
+            }
+
+            Event::WsMessage(incoming::Message::PromoteToPanelist(incoming::Target { target })) => {
+                if !self.check_panelist_change(&mut ctx, target).await? {
+                    return Ok(());
+                }
+
+                storage::add_panelist(ctx.redis_conn(), self.room.room_id(), target).await?;
+
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::PanelistPromoted {
+                        target,
+                        issued_by: self.id,
+                    },
+                );
+            }
+            Event::WsMessage(incoming::Message::DemoteToAttendee(incoming::Target { target })) => {
+                if !self.check_panelist_change(&mut ctx, target).await? {
+                    return Ok(());
+                }
+
+                storage::remove_panelist(ctx.redis_conn(), self.room.room_id(), target).await?;
+
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::PanelistDemoted {
+                        target,
+                        issued_by: self.id,
+                    },
+                );
+            }
+            Event::WsMessage(incoming::Message::SetMarker(incoming::SetMarker {
+                title,
+                timestamp,
+            })) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                let title = title.trim().to_string();
+
+                if title.is_empty() || title.chars().count() > 200 {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidMarker.into(),
+                    ));
+                    return Ok(());
+                }
+
+                // Chapters cannot begin in the future
+                let now = ctx.timestamp();
+                let timestamp = timestamp.map_or(now, |timestamp| timestamp.min(now));
+
+                let new_marker = NewRoomMarker {
+                    room_id: self.room.room_id(),
+                    breakout_room_id: self.room.breakout_room_id(),
+                    created_by: self.user_id,
+                    title: title.clone(),
+                    marked_at: *timestamp,
+                };
+
+                let db = self.db.clone();
+                crate::block(move || {
+                    let mut conn = db.get_conn()?;
+
+                    new_marker.insert(&mut conn)
+                })
+                .await??;
+
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::MarkerSet {
+                        title,
+                        timestamp,
+                        issued_by: self.id,
+                    },
+                );
+            }
+            Event::WsMessage(incoming::Message::Spotlight(incoming::Targets { targets })) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                let targets: Vec<ParticipantId> = targets.into_iter().unique().collect();
+
+                for &target in &targets {
+                    if !control::storage::participants_contains(ctx.redis_conn(), self.room, target)
+                        .await?
+                    {
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::UnknownParticipant.into(),
+                        ));
+                        return Ok(());
+                    }
+                }
+
+                let spotlights = storage::get_spotlights(ctx.redis_conn(), self.room).await?;
+
+                let new_spotlights = targets
+                    .iter()
+                    .filter(|target| !spotlights.contains(target))
+                    .count();
+
+                if spotlights.len() + new_spotlights > MAX_SPOTLIGHTS {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::TooManySpotlights.into(),
+                    ));
+                    return Ok(());
+                }
+
+                storage::add_spotlights(ctx.redis_conn(), self.room, &targets, ctx.timestamp())
+                    .await?;
+
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::SpotlightsUpdated {
+                        issued_by: Some(self.id),
+                    },
+                );
+            }
+            Event::WsMessage(incoming::Message::Unspotlight(incoming::Targets { targets })) => {
+                if ctx.role() != Role::Moderator {
+                    return Ok(());
+                }
+
+                if storage::remove_spotlights(ctx.redis_conn(), self.room, &targets).await? > 0 {
+                    ctx.rabbitmq_publish(
+                        control::rabbitmq::current_room_exchange_name(self.room),
+                        control::rabbitmq::room_all_routing_key().into(),
+                        rabbitmq::Message::SpotlightsUpdated {
+                            issued_by: Some(self.id),
+                        },
+                    );
+                }
+            }
             Event::RabbitMq(rabbitmq::Message::Banned(participant)) => {
                 if self.id == participant {
                     ctx.ws_send(outgoing::Message::Banned);
@@ -308,6 +687,89 @@ impl SignalingModule for ModerationModule {
                     ctx.ws_send(outgoing::Message::WaitingRoomDisabled);
                 }
             }
+            Event::RabbitMq(rabbitmq::Message::DisplayNameChanged {
+                target,
+                new_name,
+                issued_by,
+            }) => {
+                if self.id == target {
+                    ctx.ws_send(outgoing::Message::DisplayNameChanged(
+                        outgoing::DisplayNameChanged {
+                            new_name,
+                            issued_by,
+                        },
+                    ));
+                }
+            }
+            Event::RabbitMq(rabbitmq::Message::RealNamesEnableUpdated { issued_by }) => {
+                let required =
+                    storage::is_real_names_required(ctx.redis_conn(), self.room.room_id()).await?;
+
+                if required {
+                    ctx.ws_send(outgoing::Message::RealNamesEnabled { issued_by });
+                } else {
+                    ctx.ws_send(outgoing::Message::RealNamesDisabled { issued_by });
+                }
+            }
+            Event::RabbitMq(rabbitmq::Message::PanelistPromoted { target, issued_by }) => {
+                if self.id == target {
+                    ctx.publish(PanelistStatusChanged { is_panelist: true });
+                    ctx.invalidate_data();
+                }
+
+                ctx.ws_send(outgoing::Message::PanelistPromoted(
+                    outgoing::PanelistUpdate {
+                        participant_id: target,
+                        issued_by,
+                    },
+                ));
+            }
+            Event::RabbitMq(rabbitmq::Message::PanelistDemoted { target, issued_by }) => {
+                if self.id == target {
+                    ctx.publish(PanelistStatusChanged { is_panelist: false });
+                    ctx.invalidate_data();
+                }
+
+                ctx.ws_send(outgoing::Message::PanelistDemoted(
+                    outgoing::PanelistUpdate {
+                        participant_id: target,
+                        issued_by,
+                    },
+                ));
+            }
+            Event::RabbitMq(rabbitmq::Message::MarkerSet {
+                title,
+                timestamp,
+                issued_by,
+            }) => {
+                ctx.ws_send(outgoing::Message::MarkerSet(outgoing::MarkerSet {
+                    marker: outgoing::Marker { title, timestamp },
+                    issued_by,
+                }));
+            }
+            Event::RabbitMq(rabbitmq::Message::SpotlightsUpdated { issued_by }) => {
+                let spotlights = storage::get_spotlights(ctx.redis_conn(), self.room).await?;
+
+                ctx.publish(SpotlightsChanged {
+                    spotlights: spotlights.clone(),
+                });
+
+                ctx.ws_send(outgoing::Message::SpotlightsUpdated(
+                    outgoing::SpotlightsUpdated {
+                        spotlights,
+                        issued_by,
+                    },
+                ));
+            }
+            Event::RabbitMq(rabbitmq::Message::RoomLockUpdated { issued_by }) => {
+                let locked = storage::is_room_locked(ctx.redis_conn(), self.room.room_id()).await?;
+
+                if locked {
+                    ctx.ws_send(outgoing::Message::RoomLocked { issued_by });
+                } else {
+                    ctx.ws_send(outgoing::Message::RoomUnlocked { issued_by });
+                }
+            }
             Event::Ext(_) => unreachable!(),
         }
 
@@ -332,6 +794,31 @@ impl SignalingModule for ModerationModule {
                 log::error!("Failed to clean up raise hands enabled flag {}", e);
             }
 
+            if let Err(e) =
+                storage::delete_real_names_required(ctx.redis_conn(), self.room.room_id()).await
+            {
+                log::error!("Failed to clean up real names required flag {}", e);
+            }
+
+            if let Err(e) = storage::delete_room_locked(ctx.redis_conn(), self.room.room_id()).await
+            {
+                log::error!("Failed to clean up room locked flag {}", e);
+            }
+
+            if let Err(e) =
+                storage::delete_forced_display_names(ctx.redis_conn(), self.room.room_id()).await
+            {
+                log::error!("Failed to clean up forced display names {}", e);
+            }
+
+            if let Err(e) = storage::delete_panelists(ctx.redis_conn(), self.room.room_id()).await {
+                log::error!("Failed to clean up panelists {}", e);
+            }
+
+            if let Err(e) = storage::delete_spotlights(ctx.redis_conn(), self.room).await {
+                log::error!("Failed to clean up spotlights {}", e);
+            }
+
             if let Err(e) =
                 storage::delete_waiting_room(ctx.redis_conn(), self.room.room_id()).await
             {
@@ -346,3 +833,53 @@ impl SignalingModule for ModerationModule {
         }
     }
 }
+
+impl ModerationModule {
+    /// Get the markers set in the running session of the room
+    async fn get_markers(&self) -> Result<Vec<outgoing::Marker>> {
+        let db = self.db.clone();
+        let room_id = self.room.room_id();
+
+        let markers = crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            RoomMarker::get_all_for_running_session(&mut conn, room_id)
+        })
+        .await??;
+
+        Ok(markers
+            .into_iter()
+            .map(|marker| outgoing::Marker {
+                title: marker.title,
+                timestamp: marker.marked_at.into(),
+            })
+            .collect())
+    }
+
+    /// Check if the participant may promote or demote the target, sending an error to the participant if not
+    async fn check_panelist_change(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        target: ParticipantId,
+    ) -> Result<bool> {
+        if ctx.role() != Role::Moderator {
+            return Ok(false);
+        }
+
+        if !self.webinar_mode {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::NotInWebinarMode.into(),
+            ));
+            return Ok(false);
+        }
+
+        if !control::storage::participants_contains(ctx.redis_conn(), self.room, target).await? {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::UnknownParticipant.into(),
+            ));
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+}
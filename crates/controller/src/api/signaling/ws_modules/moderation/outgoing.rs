@@ -3,10 +3,12 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::api::signaling::prelude::*;
+use schemars::JsonSchema;
 use serde::Serialize;
-use types::core::ParticipantId;
+use types::core::{ParticipantId, Timestamp};
+use types::signaling::{ErrorEnvelope, ModuleError};
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     Kicked,
@@ -25,15 +27,99 @@ pub enum Message {
 
     Accepted,
 
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 
     RaisedHandResetByModerator { issued_by: ParticipantId },
+
+    ConnectionHistory(ConnectionHistory),
+
+    DisplayNameChanged(DisplayNameChanged),
+
+    RealNamesEnabled { issued_by: ParticipantId },
+    RealNamesDisabled { issued_by: ParticipantId },
+
+    PanelistPromoted(PanelistUpdate),
+    PanelistDemoted(PanelistUpdate),
+
+    MarkerSet(MarkerSet),
+
+    SpotlightsUpdated(SpotlightsUpdated),
+
+    RoomLocked { issued_by: ParticipantId },
+    RoomUnlocked { issued_by: ParticipantId },
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct SpotlightsUpdated {
+    /// The spotlighted participants, spotlighted first comes first
+    pub spotlights: Vec<ParticipantId>,
+    /// Id of the issuing moderator, `None` if a spotlighted participant left the room
+    pub issued_by: Option<ParticipantId>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct PanelistUpdate {
+    /// The promoted or demoted participant
+    pub participant_id: ParticipantId,
+    /// Id of the issuing moderator
+    pub issued_by: ParticipantId,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct MarkerSet {
+    #[serde(flatten)]
+    pub marker: Marker,
+    /// Id of the moderator who set the marker
+    pub issued_by: ParticipantId,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct Marker {
+    /// Title of the chapter
+    pub title: String,
+    /// Beginning of the chapter
+    pub timestamp: Timestamp,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct DisplayNameChanged {
+    /// The new display name of the participant
+    pub new_name: String,
+    /// Id of the moderator who changed the display name
+    pub issued_by: ParticipantId,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct ConnectionHistory {
+    pub participant_id: ParticipantId,
+    /// Latest connection events of the participant, oldest first
+    pub events: Vec<connection_history::ConnectionEvent>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "error", rename_all = "snake_case")]
 pub enum Error {
     CannotBanGuest,
+    UnknownParticipant,
+    InvalidDisplayName,
+    NotInWebinarMode,
+    InvalidMarker,
+    TooManySpotlights,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = super::NAMESPACE;
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::CannotBanGuest => "Guests cannot be banned",
+            Self::UnknownParticipant => "The participant is not part of the room",
+            Self::InvalidDisplayName => "The display name must contain 1 to 100 characters",
+            Self::NotInWebinarMode => "The room is not in webinar mode",
+            Self::InvalidMarker => "The title of a marker must contain 1 to 200 characters",
+            Self::TooManySpotlights => "At most 16 participants can be spotlighted",
+        }
+    }
 }
 
 #[cfg(test)]
@@ -68,4 +154,127 @@ mod test {
 
         assert_eq!(expected, produced);
     }
+
+    #[test]
+    fn connection_history() {
+        let expected = json!({
+            "message": "connection_history",
+            "participant_id": "00000000-0000-0000-0000-000000000000",
+            "events": [
+                {
+                    "timestamp": "1970-01-01T00:00:00Z",
+                    "event": "connected",
+                    "resuming": false
+                },
+                {
+                    "timestamp": "1970-01-01T00:00:00Z",
+                    "event": "disconnected"
+                }
+            ]
+        });
+
+        let produced = serde_json::to_value(&Message::ConnectionHistory(ConnectionHistory {
+            participant_id: ParticipantId::nil(),
+            events: vec![
+                connection_history::ConnectionEvent {
+                    timestamp: Timestamp::unix_epoch(),
+                    kind: connection_history::ConnectionEventKind::Connected { resuming: false },
+                },
+                connection_history::ConnectionEvent {
+                    timestamp: Timestamp::unix_epoch(),
+                    kind: connection_history::ConnectionEventKind::Disconnected,
+                },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn display_name_changed() {
+        let expected = json!({
+            "message": "display_name_changed",
+            "new_name": "Jane Doe",
+            "issued_by": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let produced = serde_json::to_value(&Message::DisplayNameChanged(DisplayNameChanged {
+            new_name: "Jane Doe".into(),
+            issued_by: ParticipantId::nil(),
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn panelist_promoted() {
+        let expected = json!({
+            "message": "panelist_promoted",
+            "participant_id": "00000000-0000-0000-0000-000000000000",
+            "issued_by": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let produced = serde_json::to_value(&Message::PanelistPromoted(PanelistUpdate {
+            participant_id: ParticipantId::nil(),
+            issued_by: ParticipantId::nil(),
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn marker_set() {
+        let expected = json!({
+            "message": "marker_set",
+            "title": "Q&A",
+            "timestamp": "1970-01-01T00:00:00Z",
+            "issued_by": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let produced = serde_json::to_value(&Message::MarkerSet(MarkerSet {
+            marker: Marker {
+                title: "Q&A".into(),
+                timestamp: Timestamp::unix_epoch(),
+            },
+            issued_by: ParticipantId::nil(),
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn spotlights_updated() {
+        let expected = json!({
+            "message": "spotlights_updated",
+            "spotlights": ["00000000-0000-0000-0000-000000000000"],
+            "issued_by": null
+        });
+
+        let produced = serde_json::to_value(&Message::SpotlightsUpdated(SpotlightsUpdated {
+            spotlights: vec![ParticipantId::nil()],
+            issued_by: None,
+        }))
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn room_locked() {
+        let expected = json!({
+            "message": "room_locked",
+            "issued_by": "00000000-0000-0000-0000-000000000000"
+        });
+
+        let produced = serde_json::to_value(&Message::RoomLocked {
+            issued_by: ParticipantId::nil(),
+        })
+        .unwrap();
+
+        assert_eq!(expected, produced);
+    }
 }
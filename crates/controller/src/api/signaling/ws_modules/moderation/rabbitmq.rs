@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use serde::{Deserialize, Serialize};
-use types::core::ParticipantId;
+use types::core::{ParticipantId, Timestamp};
 
 /// Control messages sent between controller modules to communicate changes inside a room
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,4 +14,32 @@ pub enum Message {
     JoinedWaitingRoom(ParticipantId),
     LeftWaitingRoom(ParticipantId),
     WaitingRoomEnableUpdated,
+    DisplayNameChanged {
+        target: ParticipantId,
+        new_name: String,
+        issued_by: ParticipantId,
+    },
+    RealNamesEnableUpdated {
+        issued_by: ParticipantId,
+    },
+    PanelistPromoted {
+        target: ParticipantId,
+        issued_by: ParticipantId,
+    },
+    PanelistDemoted {
+        target: ParticipantId,
+        issued_by: ParticipantId,
+    },
+    MarkerSet {
+        title: String,
+        timestamp: Timestamp,
+        issued_by: ParticipantId,
+    },
+    /// The spotlights changed, `issued_by` is `None` if a spotlighted participant left
+    SpotlightsUpdated {
+        issued_by: Option<ParticipantId>,
+    },
+    RoomLockUpdated {
+        issued_by: ParticipantId,
+    },
 }
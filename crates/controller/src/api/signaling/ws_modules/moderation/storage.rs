@@ -2,11 +2,12 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::api::signaling::SignalingRoomId;
 use crate::redis_wrapper::RedisConnection;
 use anyhow::{Context, Result};
 use redis::AsyncCommands;
 use redis_args::ToRedisArgs;
-use types::core::{ParticipantId, RoomId, UserId};
+use types::core::{ParticipantId, RoomId, Timestamp, UserId};
 
 /// Set of user-ids banned in a room
 #[derive(ToRedisArgs)]
@@ -59,6 +60,131 @@ pub async fn delete_bans(redis_conn: &mut RedisConnection, room: RoomId) -> Resu
         .context("Failed to DEL bans")
 }
 
+/// Set of participants promoted to panelists in a room in webinar mode
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:panelists")]
+struct Panelists {
+    room: RoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_panelist(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .sadd(Panelists { room }, participant)
+        .await
+        .context("Failed to SADD participant to panelists")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn remove_panelist(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .srem(Panelists { room }, participant)
+        .await
+        .context("Failed to SREM participant from panelists")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn is_panelist(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    participant: ParticipantId,
+) -> Result<bool> {
+    redis_conn
+        .sismember(Panelists { room }, participant)
+        .await
+        .context("Failed to SISMEMBER participant on panelists")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_panelists(redis_conn: &mut RedisConnection, room: RoomId) -> Result<()> {
+    redis_conn
+        .del(Panelists { room })
+        .await
+        .context("Failed to DEL panelists")
+}
+
+/// Sorted set of the participants spotlighted by a moderator, scored by the time they were spotlighted
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:spotlights")]
+struct Spotlights {
+    room: SignalingRoomId,
+}
+
+/// Add the participants to the spotlights, keeping the given order
+///
+/// Participants which are already spotlighted keep their position.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_spotlights(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participants: &[ParticipantId],
+    timestamp: Timestamp,
+) -> Result<()> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    for (i, participant) in participants.iter().enumerate() {
+        pipe.cmd("ZADD")
+            .arg(Spotlights { room })
+            .arg("NX")
+            .arg(timestamp.timestamp_millis() + i as i64)
+            .arg(participant)
+            .ignore();
+    }
+
+    pipe.query_async(redis_conn)
+        .await
+        .context("Failed to ZADD participants to spotlights")
+}
+
+/// Remove the participants from the spotlights, returns the number of removed participants
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn remove_spotlights(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participants: &[ParticipantId],
+) -> Result<usize> {
+    if participants.is_empty() {
+        return Ok(0);
+    }
+
+    redis_conn
+        .zrem(Spotlights { room }, participants)
+        .await
+        .context("Failed to ZREM participants from spotlights")
+}
+
+/// Get the spotlighted participants, spotlighted first comes first
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_spotlights(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<ParticipantId>> {
+    redis_conn
+        .zrange(Spotlights { room }, 0, -1)
+        .await
+        .context("Failed to ZRANGE spotlights")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_spotlights(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(Spotlights { room })
+        .await
+        .context("Failed to DEL spotlights")
+}
+
 /// If set to true the waiting room is enabled
 #[derive(ToRedisArgs)]
 #[to_redis_args(fmt = "k3k-signaling:room={room}:waiting_room_enabled")]
@@ -161,6 +287,143 @@ pub async fn delete_raise_hands_enabled(
         .context("Failed to DEL raise_hands_enabled")
 }
 
+/// If set to true authenticated users must use the display name of their account
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:real_names_required")]
+struct RealNamesRequired {
+    room: RoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_real_names_required(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    required: bool,
+) -> Result<()> {
+    redis_conn
+        .set(RealNamesRequired { room }, required)
+        .await
+        .context("Failed to SET real_names_required")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn is_real_names_required(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+) -> Result<bool> {
+    redis_conn
+        .get(RealNamesRequired { room })
+        .await
+        .context("Failed to GET real_names_required")
+        .map(|result: Option<bool>| result.unwrap_or_default())
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_real_names_required(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RealNamesRequired { room })
+        .await
+        .context("Failed to DEL real_names_required")
+}
+
+/// Display names set by moderators, keyed by the user id of registered users or the participant id of everyone else
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:forced_display_names")]
+struct ForcedDisplayNames {
+    room: RoomId,
+}
+
+/// Owner of a display name set by a moderator
+#[derive(Debug, Clone, Copy)]
+pub enum DisplayNameOwner {
+    User(UserId),
+    Participant(ParticipantId),
+}
+
+impl DisplayNameOwner {
+    fn field(self) -> String {
+        match self {
+            Self::User(user_id) => format!("user={user_id}"),
+            Self::Participant(participant_id) => format!("participant={participant_id}"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_forced_display_name(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    owner: DisplayNameOwner,
+    display_name: &str,
+) -> Result<()> {
+    redis_conn
+        .hset(ForcedDisplayNames { room }, owner.field(), display_name)
+        .await
+        .context("Failed to HSET forced display name")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_forced_display_name(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    owner: DisplayNameOwner,
+) -> Result<Option<String>> {
+    redis_conn
+        .hget(ForcedDisplayNames { room }, owner.field())
+        .await
+        .context("Failed to HGET forced display name")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_forced_display_names(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+) -> Result<()> {
+    redis_conn
+        .del(ForcedDisplayNames { room })
+        .await
+        .context("Failed to DEL forced display names")
+}
+
+/// If set to true no new participants may join the room
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:locked")]
+struct RoomLocked {
+    room: RoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_room_locked(
+    redis_conn: &mut RedisConnection,
+    room: RoomId,
+    locked: bool,
+) -> Result<()> {
+    redis_conn
+        .set(RoomLocked { room }, locked)
+        .await
+        .context("Failed to SET room locked")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn is_room_locked(redis_conn: &mut RedisConnection, room: RoomId) -> Result<bool> {
+    redis_conn
+        .get(RoomLocked { room })
+        .await
+        .context("Failed to GET room locked")
+        .map(|result: Option<bool>| result.unwrap_or_default())
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_room_locked(redis_conn: &mut RedisConnection, room: RoomId) -> Result<()> {
+    redis_conn
+        .del(RoomLocked { room })
+        .await
+        .context("Failed to DEL room locked")
+}
+
 /// Set of participant ids inside the waiting room
 #[derive(ToRedisArgs)]
 #[to_redis_args(fmt = "k3k-signaling:room={room}:waiting_room_list")]
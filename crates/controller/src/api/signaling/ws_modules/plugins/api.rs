@@ -0,0 +1,175 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! HTTP API implemented by plugin sidecars
+//!
+//! For every event of a participant the controller sends a [`Request`] as JSON to `POST {url}/events` of the plugin.
+//! The plugin answers with a [`Response`] containing the actions to execute, or with `204 No Content`.
+//!
+//! Requests and responses carry the [`VERSION`] of the API. It is only incremented for incompatible changes, responses
+//! with a different version are rejected.
+use crate::api::signaling::Role;
+use anyhow::{bail, Context, Result};
+use controller_shared::settings::Plugin;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use types::core::{BreakoutRoomId, ParticipantId, RoomId};
+
+/// Version of the plugin API
+pub const VERSION: u32 = 1;
+
+/// Event of a participant sent to a plugin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Request {
+    pub version: u32,
+    pub room: RoomId,
+    pub breakout_room: Option<BreakoutRoomId>,
+    pub participant_id: ParticipantId,
+    pub role: Role,
+    #[serde(flatten)]
+    pub event: RequestEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "event")]
+pub enum RequestEvent {
+    /// The participant joined the room
+    Joined,
+    /// The participant left the room, actions returned for this event are ignored
+    Left,
+    /// The participant sent a message to the plugin
+    Message { payload: Value },
+}
+
+/// Actions a plugin requests in response to an event
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum Action {
+    /// Send the payload to the participant
+    Send { payload: Value },
+    /// Send the payload to all participants of the room
+    Broadcast { payload: Value },
+}
+
+/// Send the request to the plugin and return the requested actions
+pub async fn send(
+    client: &reqwest::Client,
+    plugin: &Plugin,
+    request: &Request,
+) -> Result<Vec<Action>> {
+    let url = plugin.url.join("events").context("Invalid plugin url")?;
+
+    let mut builder = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(request)?)
+        .timeout(plugin.timeout);
+
+    if let Some(api_key) = &plugin.api_key {
+        builder = builder.bearer_auth(api_key);
+    }
+
+    let response = builder
+        .send()
+        .await
+        .context("Failed to send request")?
+        .error_for_status()?;
+
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(vec![]);
+    }
+
+    let body = response.bytes().await.context("Failed to read response")?;
+
+    parse_response(&body)
+}
+
+fn parse_response(body: &[u8]) -> Result<Vec<Action>> {
+    let response: Response = serde_json::from_slice(body).context("Invalid response")?;
+
+    if response.version != VERSION {
+        bail!(
+            "Unsupported plugin API version {}, expected {}",
+            response.version,
+            VERSION
+        );
+    }
+
+    Ok(response.actions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn request() {
+        let request = Request {
+            version: VERSION,
+            room: RoomId::from(Uuid::nil()),
+            breakout_room: None,
+            participant_id: ParticipantId::nil(),
+            role: Role::Moderator,
+            event: RequestEvent::Message {
+                payload: json!({ "language": "de" }),
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "version": 1,
+                "room": "00000000-0000-0000-0000-000000000000",
+                "breakout_room": null,
+                "participant_id": "00000000-0000-0000-0000-000000000000",
+                "role": "moderator",
+                "event": "message",
+                "payload": { "language": "de" }
+            })
+        );
+    }
+
+    #[test]
+    fn response() {
+        let actions = parse_response(
+            br#"{
+                "version": 1,
+                "actions": [
+                    { "action": "send", "payload": "only for you" },
+                    { "action": "broadcast", "payload": { "text": "for everyone" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::Send {
+                    payload: json!("only for you")
+                },
+                Action::Broadcast {
+                    payload: json!({ "text": "for everyone" })
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn response_with_other_version() {
+        assert!(parse_response(br#"{ "version": 2, "actions": [] }"#).is_err());
+    }
+}
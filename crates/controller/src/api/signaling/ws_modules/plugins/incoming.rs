@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum Message {
+    /// Send a message to a plugin
+    Message(PluginMessage),
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PluginMessage {
+    /// Name of the plugin
+    pub plugin: String,
+    /// Plugin specific payload, passed as is
+    pub payload: Value,
+}
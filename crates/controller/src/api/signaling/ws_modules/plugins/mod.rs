@@ -0,0 +1,207 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Bridge to signaling modules running out of tree
+//!
+//! Plugins are sidecar services configured in the `plugins` settings. Participants address a plugin by its name
+//! inside the `plugins` namespace. The module forwards the events of the participant to the plugin using the
+//! versioned HTTP API described in [`api`] and executes the actions the plugin responds with. This allows third
+//! parties to add functionality to the signaling without forking the controller.
+use crate::api::signaling::prelude::*;
+use anyhow::Result;
+use controller_shared::settings::Plugin;
+use std::sync::Arc;
+use types::core::ParticipantId;
+
+pub mod api;
+mod incoming;
+mod outgoing;
+mod rabbitmq;
+
+pub struct Plugins {
+    id: ParticipantId,
+    room: SignalingRoomId,
+    params: PluginsParams,
+}
+
+#[derive(Clone)]
+pub struct PluginsParams {
+    client: reqwest::Client,
+    plugins: Arc<[Plugin]>,
+}
+
+impl PluginsParams {
+    pub fn new(plugins: Vec<Plugin>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            plugins: plugins.into(),
+        }
+    }
+}
+
+/// Response of a plugin to a forwarded event
+pub struct PluginResponse {
+    plugin: String,
+    result: Result<Vec<api::Action>>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl SignalingModule for Plugins {
+    const NAMESPACE: &'static str = "plugins";
+
+    type Params = PluginsParams;
+
+    type Incoming = incoming::Message;
+    type Outgoing = outgoing::Message;
+    type RabbitMqMessage = rabbitmq::Message;
+
+    type ExtEvent = PluginResponse;
+
+    type FrontendData = ();
+    type PeerFrontendData = ();
+
+    async fn init(
+        ctx: InitContext<'_, Self>,
+        params: &Self::Params,
+        _protocol: &'static str,
+    ) -> Result<Option<Self>> {
+        if params.plugins.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            id: ctx.participant_id(),
+            room: ctx.room_id(),
+            params: params.clone(),
+        }))
+    }
+
+    async fn on_event(
+        &mut self,
+        mut ctx: ModuleContext<'_, Self>,
+        event: Event<'_, Self>,
+    ) -> Result<()> {
+        match event {
+            Event::Joined { .. } => {
+                for plugin in self.params.plugins.iter() {
+                    self.forward(&mut ctx, plugin, api::RequestEvent::Joined);
+                }
+            }
+            Event::Leaving => {
+                // The participant is gone before any response could be delivered, send without waiting for it
+                for plugin in self.params.plugins.iter() {
+                    let client = self.params.client.clone();
+                    let plugin = plugin.clone();
+                    let request = self.request(ctx.role(), api::RequestEvent::Left);
+
+                    actix_rt::spawn(async move {
+                        if let Err(e) = api::send(&client, &plugin, &request).await {
+                            log::warn!(
+                                "Plugin {} failed to handle left event, {:?}",
+                                plugin.name,
+                                e
+                            );
+                        }
+                    });
+                }
+            }
+            Event::WsMessage(incoming::Message::Message(message)) => {
+                let plugin = self
+                    .params
+                    .plugins
+                    .iter()
+                    .find(|plugin| plugin.name == message.plugin);
+
+                match plugin {
+                    Some(plugin) => self.forward(
+                        &mut ctx,
+                        plugin,
+                        api::RequestEvent::Message {
+                            payload: message.payload,
+                        },
+                    ),
+                    None => ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownPlugin.into(),
+                    )),
+                }
+            }
+            Event::RabbitMq(rabbitmq::Message::Broadcast { plugin, payload }) => {
+                ctx.ws_send(outgoing::Message::Message(outgoing::PluginMessage {
+                    plugin,
+                    payload,
+                }));
+            }
+            Event::Ext(PluginResponse { plugin, result }) => match result {
+                Ok(actions) => {
+                    for action in actions {
+                        match action {
+                            api::Action::Send { payload } => {
+                                ctx.ws_send(outgoing::Message::Message(outgoing::PluginMessage {
+                                    plugin: plugin.clone(),
+                                    payload,
+                                }));
+                            }
+                            api::Action::Broadcast { payload } => {
+                                ctx.rabbitmq_publish(
+                                    control::rabbitmq::current_room_exchange_name(self.room),
+                                    control::rabbitmq::room_all_routing_key().into(),
+                                    rabbitmq::Message::Broadcast {
+                                        plugin: plugin.clone(),
+                                        payload,
+                                    },
+                                );
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Plugin {} failed to handle event, {:?}", plugin, e);
+                }
+            },
+            Event::RaiseHand
+            | Event::LowerHand
+            | Event::ParticipantJoined(..)
+            | Event::ParticipantLeft(_)
+            | Event::ParticipantUpdated(..) => {}
+        }
+
+        Ok(())
+    }
+
+    async fn on_destroy(self, _: DestroyContext<'_>) {}
+}
+
+impl Plugins {
+    fn request(&self, role: Role, event: api::RequestEvent) -> api::Request {
+        api::Request {
+            version: api::VERSION,
+            room: self.room.room_id(),
+            breakout_room: self.room.breakout_room_id(),
+            participant_id: self.id,
+            role,
+            event,
+        }
+    }
+
+    /// Send the event to the plugin, its response is received as [`Event::Ext`]
+    fn forward(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        plugin: &Plugin,
+        event: api::RequestEvent,
+    ) {
+        let client = self.params.client.clone();
+        let plugin = plugin.clone();
+        let request = self.request(ctx.role(), event);
+
+        ctx.add_event_stream(futures::stream::once(async move {
+            let result = api::send(&client, &plugin, &request).await;
+
+            PluginResponse {
+                plugin: plugin.name,
+                result,
+            }
+        }));
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use types::signaling::{ErrorEnvelope, ModuleError};
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "message")]
+pub enum Message {
+    /// Message sent by a plugin
+    Message(PluginMessage),
+    Error(ErrorEnvelope<Error>),
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct PluginMessage {
+    /// Name of the plugin
+    pub plugin: String,
+    /// Plugin specific payload, passed as is
+    pub payload: Value,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "error")]
+pub enum Error {
+    /// No plugin with the given name is configured
+    UnknownPlugin,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "plugins";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::UnknownPlugin => "No plugin with the given name is configured",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn plugin_message() {
+        let message = Message::Message(PluginMessage {
+            plugin: "captions".into(),
+            payload: json!({ "text": "Hello" }),
+        });
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!({
+                "message": "message",
+                "plugin": "captions",
+                "payload": { "text": "Hello" }
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_plugin() {
+        let message = Message::Error(Error::UnknownPlugin.into());
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!({
+                "message": "error",
+                "module": "plugins",
+                "error": "unknown_plugin",
+                "text": "No plugin with the given name is configured",
+                "retryable": false
+            })
+        );
+    }
+}
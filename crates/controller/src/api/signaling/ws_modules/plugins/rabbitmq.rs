@@ -0,0 +1,12 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// Payload a plugin broadcasts to all participants of the room
+    Broadcast { plugin: String, payload: Value },
+}
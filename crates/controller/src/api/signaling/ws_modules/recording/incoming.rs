@@ -2,11 +2,12 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use super::RecordingId;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "action")]
 pub enum Message {
     Start,
@@ -14,12 +15,12 @@ pub enum Message {
     SetConsent(SetConsent),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Stop {
     pub recording_id: RecordingId,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct SetConsent {
     pub consent: bool,
 }
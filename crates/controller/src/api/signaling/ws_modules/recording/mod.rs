@@ -5,14 +5,19 @@
 use crate::api::signaling::prelude::*;
 use crate::api::Participant;
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::ParticipantId;
+use types::signaling::NamespacedCommand;
 
 mod incoming;
 mod outgoing;
+pub mod overlay;
 mod rabbitmq;
 mod storage;
 
+pub use overlay::Overlay;
+
 pub struct Recording {
     id: ParticipantId,
     room: SignalingRoomId,
@@ -24,7 +29,7 @@ pub struct Recording {
 ///
 /// Is used for subsequent requests to a specific recording session
 // TODO(kbalt): currently hacky workaround using the participant id as recording id
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct RecordingId(ParticipantId);
 
 #[derive(Debug, Serialize)]
@@ -44,6 +49,8 @@ pub struct RecordingParams {
 impl SignalingModule for Recording {
     const NAMESPACE: &'static str = "recording";
 
+    const CACHEABLE_PEER_FRONTEND_DATA: bool = true;
+
     type Params = RecordingParams;
 
     type Incoming = incoming::Message;
@@ -148,14 +155,14 @@ impl SignalingModule for Recording {
                     incoming::Message::Start => {
                         if ctx.role() != Role::Moderator {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InsufficientPermissions,
+                                outgoing::Error::InsufficientPermissions.into(),
                             ));
                             return Ok(());
                         }
 
                         if !storage::try_init(ctx.redis_conn(), self.room).await? {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::AlreadyRecording,
+                                outgoing::Error::AlreadyRecording.into(),
                             ));
                             return Ok(());
                         }
@@ -172,7 +179,7 @@ impl SignalingModule for Recording {
                     incoming::Message::Stop(incoming::Stop { recording_id }) => {
                         if ctx.role() != Role::Moderator {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InsufficientPermissions,
+                                outgoing::Error::InsufficientPermissions.into(),
                             ));
                             return Ok(());
                         }
@@ -182,7 +189,7 @@ impl SignalingModule for Recording {
                             Some(storage::RecordingState::Recording(id)) if id == recording_id
                         ) {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InvalidRecordingId,
+                                outgoing::Error::InvalidRecordingId.into(),
                             ));
                             return Ok(());
                         }
@@ -214,6 +221,20 @@ impl SignalingModule for Recording {
                         ctx.exit(None);
                     }
                 }
+                rabbitmq::Message::Overlay(overlay) => {
+                    if self.i_am_the_recorder {
+                        ctx.rabbitmq_publish_any(
+                            Some(String::new()), // empty string to send to the default rmq exchange
+                            self.params.queue.clone(),
+                            rabbitmq::OverlayEvent {
+                                room: self.room.room_id(),
+                                breakout: self.room.breakout_room_id(),
+                                timestamp: ctx.timestamp(),
+                                overlay,
+                            },
+                        );
+                    }
+                }
                 rabbitmq::Message::Started(recording_id) => {
                     if !self.i_am_the_recorder {
                         ctx.ws_send(outgoing::Message::Started(outgoing::Started {
@@ -243,3 +264,27 @@ impl SignalingModule for Recording {
         }
     }
 }
+
+/// Publish an overlay event to the recording service if the room is currently being recorded
+///
+/// The event is routed to the recorder participant of the room, which forwards it to the recording service queue.
+pub async fn publish_overlay<M: SignalingModule>(
+    ctx: &mut ModuleContext<'_, M>,
+    room: SignalingRoomId,
+    overlay: Overlay,
+) -> Result<()> {
+    if let Some(storage::RecordingState::Recording(recording_id)) =
+        storage::get_state(ctx.redis_conn(), room).await?
+    {
+        ctx.rabbitmq_publish_any(
+            Some(control::rabbitmq::current_room_exchange_name(room)),
+            control::rabbitmq::room_participant_routing_key(recording_id.0),
+            NamespacedCommand {
+                namespace: Recording::NAMESPACE,
+                payload: rabbitmq::Message::Overlay(overlay),
+            },
+        );
+    }
+
+    Ok(())
+}
@@ -2,32 +2,46 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Serialize;
+use types::signaling::{ErrorEnvelope, ModuleError};
 
 use super::RecordingId;
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     Started(Started),
     Stopped(Stopped),
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Started {
     pub recording_id: RecordingId,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Stopped {
     pub recording_id: RecordingId,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "error", rename_all = "snake_case")]
 pub enum Error {
     InsufficientPermissions,
     AlreadyRecording,
     InvalidRecordingId,
 }
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "recording";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::AlreadyRecording => "The room is already being recorded",
+            Self::InvalidRecordingId => "The recording does not exist",
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Overlay events for the recording service
+//!
+//! The modules of the recorder participant publish the state shown in a room (poll results, timers, the current
+//! speaker, the stage layout) with [`publish_overlay`](super::publish_overlay). The events are forwarded to the
+//! recording service queue, where they can be burned into the recording or attached as metadata track.
+use serde::{Deserialize, Serialize};
+use types::core::{ParticipantId, Timestamp};
+use uuid::Uuid;
+
+/// Structured information about the state of a room to show in a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "overlay", rename_all = "snake_case")]
+pub enum Overlay {
+    /// The current results of a poll
+    PollResults(PollResults),
+    /// A timer has been started
+    Timer(Timer),
+    /// The timer has been stopped
+    TimerStopped { id: Uuid },
+    /// The participant in focus changed
+    Speaker(Speaker),
+    /// The stage layout defined by the moderators changed
+    Layout(Layout),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollResults {
+    pub id: Uuid,
+    pub topic: String,
+    pub choices: Vec<PollChoice>,
+    /// The poll is over and the results are final
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollChoice {
+    pub content: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timer {
+    pub id: Uuid,
+    pub started_at: Timestamp,
+    /// Point in time a countdown ends, `None` for stopwatches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<Timestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Speaker {
+    /// The participant in focus, `None` if nobody is speaking
+    pub participant_id: Option<ParticipantId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    /// Arrangement of the stage, e.g. `grid` or `speaker`
+    pub view: String,
+    pub spotlights: Vec<ParticipantId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_screen_share: Option<ParticipantId>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn poll_results() {
+        let overlay = Overlay::PollResults(PollResults {
+            id: Uuid::nil(),
+            topic: "Lunch?".into(),
+            choices: vec![PollChoice {
+                content: "Yes".into(),
+                count: 3,
+            }],
+            finished: false,
+        });
+
+        assert_eq!(
+            serde_json::to_value(&overlay).unwrap(),
+            json!({
+                "overlay": "poll_results",
+                "id": "00000000-0000-0000-0000-000000000000",
+                "topic": "Lunch?",
+                "choices": [{ "content": "Yes", "count": 3 }],
+                "finished": false
+            })
+        );
+    }
+
+    #[test]
+    fn timer_stopped() {
+        let overlay = Overlay::TimerStopped { id: Uuid::nil() };
+
+        assert_eq!(
+            serde_json::to_value(&overlay).unwrap(),
+            json!({
+                "overlay": "timer_stopped",
+                "id": "00000000-0000-0000-0000-000000000000"
+            })
+        );
+    }
+}
@@ -2,14 +2,17 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use super::overlay::Overlay;
 use super::RecordingId;
 use serde::{Deserialize, Serialize};
-use types::core::{BreakoutRoomId, RoomId};
+use types::core::{BreakoutRoomId, RoomId, Timestamp};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
     /// Signals for the recording "participant"
     Stop,
+    /// Overlay event to forward to the recording service
+    Overlay(Overlay),
 
     /// Messages sent to participants to signal changes in the recording
     Started(RecordingId),
@@ -22,3 +25,13 @@ pub struct StartRecording {
     pub room: RoomId,
     pub breakout: Option<BreakoutRoomId>,
 }
+
+/// Message sent to the recording service containing an overlay event for the given room
+#[derive(Debug, Serialize)]
+pub struct OverlayEvent {
+    pub room: RoomId,
+    pub breakout: Option<BreakoutRoomId>,
+    pub timestamp: Timestamp,
+    #[serde(flatten)]
+    pub overlay: Overlay,
+}
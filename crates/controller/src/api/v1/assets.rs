@@ -4,15 +4,23 @@
 
 use super::response::{ApiError, NoContent};
 use super::{ApiResponse, PagePaginationQuery};
+use crate::redis_wrapper::RedisConnection;
+use crate::storage::assets::AssetInfected;
+use crate::storage::uploads::{self, UploadSession, UploadedPart};
 use crate::storage::{self, ObjectStorage};
 use actix_http::StatusCode;
-use actix_web::web::{Data, Path, Query};
-use actix_web::{delete, get, HttpResponse};
+use actix_web::web::{Data, Json, Path, Payload, Query, ReqData};
+use actix_web::{delete, get, post, put, HttpResponse};
+use bytes::BytesMut;
 use chrono::{DateTime, Utc};
 use database::Db;
-use db_storage::assets::Asset;
-use serde::Serialize;
-use types::core::{AssetId, RoomId};
+use db_storage::assets::{Asset, AssetScanStatus};
+use db_storage::room_markers::RoomMarker;
+use db_storage::users::User;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use types::core::{AssetId, BreakoutRoomId, RoomId, Timestamp};
+use validator::Validate;
 
 #[derive(Debug, Serialize)]
 pub struct AssetResource {
@@ -20,7 +28,11 @@ pub struct AssetResource {
     filename: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     namespace: Option<String>,
+    /// The breakout room the asset has been created in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    breakout_room_id: Option<BreakoutRoomId>,
     created_at: DateTime<Utc>,
+    scan_status: AssetScanStatus,
 }
 
 impl From<Asset> for AssetResource {
@@ -29,11 +41,26 @@ impl From<Asset> for AssetResource {
             id: asset.id,
             filename: asset.filename,
             namespace: asset.namespace,
+            breakout_room_id: None,
             created_at: asset.created_at,
+            scan_status: asset.scan_status,
         }
     }
 }
 
+/// Map errors of storing an asset, reporting infected assets to the uploader
+pub(crate) fn map_store_asset_error(e: anyhow::Error) -> ApiError {
+    match e.downcast::<AssetInfected>() {
+        Ok(infected) => ApiError::bad_request()
+            .with_code("asset_infected")
+            .with_message(format!(
+                "The asset has been quarantined, it is infected with {}",
+                infected.signature
+            )),
+        Err(e) => e.into(),
+    }
+}
+
 #[get("/rooms/{room_id}/assets")]
 pub async fn room_assets(
     db: Data<Db>,
@@ -44,13 +71,19 @@ pub async fn room_assets(
     let PagePaginationQuery { per_page, page } = pagination.into_inner();
 
     let (assets, asset_count) = crate::block(move || {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         Asset::get_all_for_room_paginated(&mut conn, room_id, per_page, page)
     })
     .await??;
 
-    let asset_data = assets.into_iter().map(Into::into).collect();
+    let asset_data = assets
+        .into_iter()
+        .map(|(asset, breakout_room_id)| AssetResource {
+            breakout_room_id,
+            ..asset.into()
+        })
+        .collect();
 
     Ok(ApiResponse::new(asset_data).with_page_pagination(per_page, page, asset_count))
 }
@@ -70,11 +103,97 @@ pub async fn room_asset(
     })
     .await??;
 
+    if asset.scan_status == AssetScanStatus::Infected {
+        return Err(ApiError::forbidden()
+            .with_code("asset_infected")
+            .with_message("The asset is infected and has been quarantined"));
+    }
+
     let data = storage::assets::get_asset(&storage, &asset.id).await?;
 
     Ok(HttpResponse::build(StatusCode::OK).streaming(data))
 }
 
+/// A pre-signed URL to download an asset directly from the object storage
+#[derive(Debug, Serialize)]
+pub struct AssetUrlResource {
+    url: String,
+    expires_at: Timestamp,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/assets/{asset_id}/url*
+///
+/// Returns a pre-signed URL which allows downloading the asset without routing the download through the
+/// controller. The URL is valid for the configured lifetime.
+#[get("/rooms/{room_id}/assets/{asset_id}/url")]
+pub async fn room_asset_url(
+    db: Data<Db>,
+    storage: Data<ObjectStorage>,
+    path: Path<(RoomId, AssetId)>,
+) -> Result<ApiResponse<AssetUrlResource>, ApiError> {
+    let (room_id, asset_id) = path.into_inner();
+
+    let asset = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Asset::get(&mut conn, asset_id, room_id)
+    })
+    .await??;
+
+    if asset.scan_status == AssetScanStatus::Infected {
+        return Err(ApiError::forbidden()
+            .with_code("asset_infected")
+            .with_message("The asset is infected and has been quarantined"));
+    }
+
+    let (url, lifetime) = storage::assets::get_asset_url(&storage, &asset.id).await?;
+
+    let expires_at = Timestamp::from(
+        Utc::now() + chrono::Duration::from_std(lifetime).map_err(anyhow::Error::from)?,
+    );
+
+    Ok(ApiResponse::new(AssetUrlResource { url, expires_at }))
+}
+
+/// A chapter of a recording
+#[derive(Debug, Serialize)]
+pub struct ChapterResource {
+    title: String,
+    /// Beginning of the chapter in the meeting, the recording starts with the first chapter at the earliest
+    marked_at: DateTime<Utc>,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/assets/{asset_id}/chapters*
+///
+/// Returns the markers the moderators set while the recording was running, ordered by their time.
+#[get("/rooms/{room_id}/assets/{asset_id}/chapters")]
+pub async fn room_asset_chapters(
+    db: Data<Db>,
+    path: Path<(RoomId, AssetId)>,
+) -> Result<ApiResponse<Vec<ChapterResource>>, ApiError> {
+    let (room_id, asset_id) = path.into_inner();
+
+    let markers = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        // Fails with 404 Not Found if the asset is not part of the room
+        Asset::get(&mut conn, asset_id, room_id)?;
+
+        RoomMarker::get_all_for_recording(&mut conn, asset_id)
+    })
+    .await??;
+
+    let chapters = markers
+        .into_iter()
+        .map(|marker| ChapterResource {
+            title: marker.title,
+            marked_at: marker.marked_at,
+        })
+        .collect();
+
+    Ok(ApiResponse::new(chapters))
+}
+
 #[delete("/rooms/{room_id}/assets/{asset_id}")]
 pub async fn delete(
     db: Data<Db>,
@@ -87,3 +206,235 @@ pub async fn delete(
 
     Ok(NoContent)
 }
+
+/// An upload session of a resumable asset upload
+#[derive(Debug, Serialize)]
+pub struct UploadResource {
+    /// The id of the asset that is created when completing the upload
+    asset_id: AssetId,
+    filename: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    /// The minimum size in bytes of every part except the last one
+    min_part_size: usize,
+    /// The maximum size in bytes of a single part
+    max_part_size: usize,
+    expires_at: Timestamp,
+    /// All parts which have already been uploaded
+    uploaded_parts: Vec<UploadedPart>,
+}
+
+impl UploadResource {
+    fn new(session: UploadSession, uploaded_parts: Vec<UploadedPart>) -> Self {
+        Self {
+            asset_id: session.asset_id,
+            filename: session.filename,
+            namespace: session.namespace,
+            min_part_size: uploads::MIN_PART_SIZE,
+            max_part_size: uploads::MAX_PART_SIZE,
+            expires_at: session.expires_at,
+            uploaded_parts,
+        }
+    }
+}
+
+/// The JSON body expected when making a *POST /rooms/{room_id}/assets/uploads*
+#[derive(Debug, Deserialize, Validate)]
+pub struct PostUploadBody {
+    #[validate(length(min = 1, max = 255))]
+    filename: String,
+    #[validate(length(min = 1, max = 255))]
+    kind: String,
+    #[validate(length(min = 1, max = 255))]
+    namespace: Option<String>,
+}
+
+fn upload_not_found() -> ApiError {
+    ApiError::not_found()
+        .with_code("upload_not_found")
+        .with_message("The upload does not exist or has expired")
+}
+
+/// API Endpoint *POST /rooms/{room_id}/assets/uploads*
+///
+/// Start a resumable upload of a new asset. The asset data is uploaded in parts via
+/// [`put_upload_part`] and the asset gets created with [`complete_upload`].
+#[post("/rooms/{room_id}/assets/uploads")]
+pub async fn start_upload(
+    storage: Data<ObjectStorage>,
+    redis_ctx: Data<RedisConnection>,
+    current_user: ReqData<User>,
+    room_id: Path<RoomId>,
+    body: Json<PostUploadBody>,
+) -> Result<ApiResponse<UploadResource>, ApiError> {
+    let room_id = room_id.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let session = uploads::start_upload(
+        &storage,
+        &mut redis_conn,
+        room_id,
+        current_user.id,
+        body.namespace,
+        body.filename,
+        body.kind,
+    )
+    .await?;
+
+    Ok(ApiResponse::new(UploadResource::new(session, vec![])))
+}
+
+/// API Endpoint *GET /rooms/{room_id}/assets/uploads/{asset_id}*
+///
+/// Returns the state of an upload including all parts that have already been uploaded.
+/// Used by clients to resume an interrupted upload.
+#[get("/rooms/{room_id}/assets/uploads/{asset_id}")]
+pub async fn get_upload(
+    redis_ctx: Data<RedisConnection>,
+    current_user: ReqData<User>,
+    path: Path<(RoomId, AssetId)>,
+) -> Result<ApiResponse<UploadResource>, ApiError> {
+    let (room_id, asset_id) = path.into_inner();
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let session = uploads::get_upload(&mut redis_conn, room_id, current_user.id, asset_id)
+        .await?
+        .ok_or_else(upload_not_found)?;
+
+    let uploaded_parts = uploads::get_uploaded_parts(&mut redis_conn, &session).await?;
+
+    Ok(ApiResponse::new(UploadResource::new(
+        session,
+        uploaded_parts,
+    )))
+}
+
+/// API Endpoint *PUT /rooms/{room_id}/assets/uploads/{asset_id}/parts/{part_number}*
+///
+/// Upload a single part of the asset as raw request body. Part numbers start at 1, uploading
+/// a part number again replaces the previously uploaded part.
+#[put("/rooms/{room_id}/assets/uploads/{asset_id}/parts/{part_number}")]
+pub async fn put_upload_part(
+    storage: Data<ObjectStorage>,
+    redis_ctx: Data<RedisConnection>,
+    current_user: ReqData<User>,
+    path: Path<(RoomId, AssetId, i32)>,
+    mut payload: Payload,
+) -> Result<ApiResponse<UploadedPart>, ApiError> {
+    let (room_id, asset_id, part_number) = path.into_inner();
+
+    if !(1..=uploads::MAX_PART_NUMBER).contains(&part_number) {
+        return Err(ApiError::bad_request()
+            .with_code("invalid_part_number")
+            .with_message(format!(
+                "The part number must be between 1 and {}",
+                uploads::MAX_PART_NUMBER
+            )));
+    }
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let mut session = uploads::get_upload(&mut redis_conn, room_id, current_user.id, asset_id)
+        .await?
+        .ok_or_else(upload_not_found)?;
+
+    let mut data = BytesMut::new();
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| ApiError::bad_request().with_message(e.to_string()))?;
+
+        if data.len() + chunk.len() > uploads::MAX_PART_SIZE {
+            return Err(ApiError::bad_request()
+                .with_code("part_too_large")
+                .with_message(format!(
+                    "A single part must not be larger than {} bytes",
+                    uploads::MAX_PART_SIZE
+                )));
+        }
+
+        data.extend_from_slice(&chunk);
+    }
+
+    let part = uploads::upload_part(
+        &storage,
+        &mut redis_conn,
+        &mut session,
+        part_number,
+        data.freeze(),
+    )
+    .await?;
+
+    Ok(ApiResponse::new(part))
+}
+
+/// API Endpoint *POST /rooms/{room_id}/assets/uploads/{asset_id}/complete*
+///
+/// Completes the upload by assembling all uploaded parts and creates the asset.
+/// Returns the created [`AssetResource`].
+#[post("/rooms/{room_id}/assets/uploads/{asset_id}/complete")]
+pub async fn complete_upload(
+    db: Data<Db>,
+    storage: Data<ObjectStorage>,
+    redis_ctx: Data<RedisConnection>,
+    current_user: ReqData<User>,
+    path: Path<(RoomId, AssetId)>,
+) -> Result<ApiResponse<AssetResource>, ApiError> {
+    let (room_id, asset_id) = path.into_inner();
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let session = uploads::get_upload(&mut redis_conn, room_id, current_user.id, asset_id)
+        .await?
+        .ok_or_else(upload_not_found)?;
+
+    if uploads::get_uploaded_parts(&mut redis_conn, &session)
+        .await?
+        .is_empty()
+    {
+        return Err(ApiError::bad_request()
+            .with_code("no_parts_uploaded")
+            .with_message("At least one part must be uploaded before completing the upload"));
+    }
+
+    let asset_id =
+        uploads::complete_upload(&storage, db.clone().into_inner(), &mut redis_conn, session)
+            .await
+            .map_err(map_store_asset_error)?;
+
+    let asset = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Asset::get(&mut conn, asset_id, room_id)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(asset.into()))
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/assets/uploads/{asset_id}*
+///
+/// Aborts the upload and discards all uploaded parts.
+#[delete("/rooms/{room_id}/assets/uploads/{asset_id}")]
+pub async fn abort_upload(
+    storage: Data<ObjectStorage>,
+    redis_ctx: Data<RedisConnection>,
+    current_user: ReqData<User>,
+    path: Path<(RoomId, AssetId)>,
+) -> Result<NoContent, ApiError> {
+    let (room_id, asset_id) = path.into_inner();
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let session = uploads::get_upload(&mut redis_conn, room_id, current_user.id, asset_id)
+        .await?
+        .ok_or_else(upload_not_found)?;
+
+    uploads::abort_upload(&storage, &mut redis_conn, session).await?;
+
+    Ok(NoContent)
+}
@@ -3,18 +3,22 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 //! Auth related API structs and Endpoints
+use super::brute_force;
 use super::events::EventPoliciesBuilderExt;
 use super::rooms::RoomsPoliciesBuilderExt;
 use crate::api::v1::response::error::AuthenticationError;
 use crate::api::v1::response::ApiError;
-use crate::oidc::{OidcContext, VerifyError};
+use crate::oidc::{IdTokenInfo, OidcContext, VerifyError};
+use crate::redis_wrapper::RedisConnection;
 use crate::settings::SharedSettingsActix;
 use actix_web::web::{Data, Json};
-use actix_web::{get, post};
-use controller_shared::settings::{TariffAssignment, TenantAssignment};
+use actix_web::{get, post, HttpRequest};
+use chrono::{DateTime, Utc};
+use controller_shared::settings::{Settings, TariffAssignment, TenantAssignment};
 use core::mem::take;
-use database::{Db, OptionalExt};
+use database::{Db, DbConnection, OptionalExt};
 use db_storage::groups::{get_or_create_groups_by_name, Group};
+use db_storage::ldap_sessions::{LdapSession, NewLdapSession};
 use db_storage::tariffs::{ExternalTariffId, Tariff};
 use db_storage::tenants::{get_or_create_tenant_by_oidc_id, OidcTenantId};
 use db_storage::users::User;
@@ -55,7 +59,7 @@ pub async fn login(
 ) -> Result<Json<LoginResponse>, ApiError> {
     let id_token = body.into_inner().id_token;
 
-    let mut info = match oidc_ctx.verify_id_token(&id_token) {
+    let info = match oidc_ctx.verify_id_token(&id_token) {
         Ok(info) => info,
         Err(e) => {
             return match e {
@@ -77,60 +81,119 @@ pub async fn login(
         let settings = settings.load_full();
         let mut conn = db.get_conn()?;
 
-        // Get tariff depending on the configured assignment
-        let tariff = match &settings.tariffs.assignment {
-            TariffAssignment::Static { static_tariff_name } => {
-                Tariff::get_by_name(&mut conn, static_tariff_name)?
-            }
-            TariffAssignment::ByExternalTariffId => {
-                let external_tariff_id = info.tariff_id.clone().ok_or_else(|| {
-                    ApiError::bad_request()
-                        .with_code("invalid_claims")
-                        .with_message("tariff_id missing in id_token claims")
-                })?;
-
-                Tariff::get_by_external_id(&mut conn, &ExternalTariffId::from(external_tariff_id))
-                    .optional()?
-                    .ok_or_else(|| {
-                        ApiError::internal()
-                            .with_code("invalid_tariff_id")
-                            .with_message("JWT contained unknown tariff_id")
-                    })?
-            }
-        };
+        login_user(&settings, &mut conn, info)
+    })
+    .await??;
 
-        // Get the tenant_id depending on the configured assignment
-        let tenant_id = match &settings.tenants.assignment {
-            TenantAssignment::Static { static_tenant_id } => static_tenant_id.clone(),
-            TenantAssignment::ByExternalTenantId => info.tenant_id.clone().ok_or_else(|| {
-                ApiError::bad_request()
-                    .with_code("invalid_claims")
-                    .with_message("tenant_id missing in id_token claims")
-            })?,
-        };
+    update_core_user_permissions(authz.as_ref(), db_result).await?;
 
-        let tenant = get_or_create_tenant_by_oidc_id(&mut conn, &OidcTenantId::from(tenant_id))?;
+    Ok(Json(LoginResponse {
+        // TODO calculate permissions
+        permissions: Default::default(),
+    }))
+}
 
-        let groups: Vec<(TenantId, GroupName)> = take(&mut info.x_grp)
-            .into_iter()
-            .map(|group| (tenant.id, GroupName::from(group)))
-            .collect();
+/// The JSON Body expected when making a *POST* request on `/auth/ldap/login`
+#[derive(Deserialize)]
+pub struct LdapLogin {
+    username: String,
+    password: String,
+}
 
-        let groups = get_or_create_groups_by_name(&mut conn, &groups)?;
+/// JSON Body of the response coming from the *POST* request on `/auth/ldap/login`
+#[derive(Debug, Serialize)]
+pub struct LdapLoginResponse {
+    /// Token to authenticate subsequent requests with, used like an access token of the OIDC provider
+    access_token: String,
+    expires_at: DateTime<Utc>,
+    /// Permissions is a set of strings that each define a permission a user has.
+    permissions: HashSet<String>,
+}
 
-        // Try to get the user by the `sub` field in the IdToken
-        let user = User::get_by_oidc_sub(&mut conn, tenant.id, &info.sub)?;
+/// API Endpoint *POST /auth/ldap/login*
+///
+/// Authenticates the user against the configured LDAP directory. On success the user is created or updated with the
+/// attributes of their directory entry and a new session is created. Clients guessing passwords are locked out.
+///
+/// Returns a [`LdapLoginResponse`] containing the session token.
+#[post("/auth/ldap/login")]
+pub async fn ldap_login(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    request: HttpRequest,
+    body: Json<LdapLogin>,
+    authz: Data<kustos::Authz>,
+) -> Result<Json<LdapLoginResponse>, ApiError> {
+    let settings = settings.load_full();
 
-        let login_result = match user {
-            Some(user) => {
-                // Found a matching user, update its attributes, tenancy and groups
-                update_user::update_user(&settings, &mut conn, user, info, groups, tariff)?
-            }
-            None => {
-                // No matching user, create a new one with inside the given tenants and groups
-                create_user::create_user(&settings, &mut conn, info, tenant, groups, tariff)?
+    let ldap_settings = match &settings.ldap {
+        Some(ldap_settings) => ldap_settings,
+        None => return Err(ApiError::not_found()),
+    };
+
+    let body = body.into_inner();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    // Passwords are guessed by trying different usernames and passwords, so the attempts are counted per client
+    let client = brute_force::client_subject(&request, &settings.http.trusted_proxies);
+
+    if let Some(client) = &client {
+        brute_force::check(&mut redis_conn, brute_force::Kind::LdapLogin, client).await?;
+    }
+
+    let entry = crate::ldap::authenticate(ldap_settings, &body.username, &body.password)
+        .await
+        .map_err(|e| {
+            log::error!("LDAP authentication failed, {:?}", e);
+            ApiError::internal()
+        })?;
+
+    let entry = match entry {
+        Some(entry) => entry,
+        None => {
+            if let Some(client) = &client {
+                brute_force::record_failure(
+                    &mut redis_conn,
+                    &settings.brute_force_protection,
+                    brute_force::Kind::LdapLogin,
+                    client,
+                )
+                .await?;
             }
-        };
+
+            return Err(ApiError::unauthorized()
+                .with_code("invalid_credentials")
+                .with_message("Invalid username or password"));
+        }
+    };
+
+    if let Some(client) = &client {
+        brute_force::reset(&mut redis_conn, brute_force::Kind::LdapLogin, client).await?;
+    }
+
+    let session_lifetime =
+        chrono::Duration::from_std(ldap_settings.session_lifetime).map_err(anyhow::Error::from)?;
+    let expires_at = Utc::now() + session_lifetime;
+    let info = entry.into_id_token_info(ldap_settings.url.clone(), expires_at);
+
+    let (access_token, token_hash) = crate::ldap::new_session_token()?;
+
+    let db_result = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let login_result = login_user(&settings, &mut conn, info)?;
+
+        let user_id = login_result.user().id;
+
+        LdapSession::delete_expired_for_user(&mut conn, user_id)?;
+
+        NewLdapSession {
+            token_hash,
+            user_id,
+            expires_at,
+        }
+        .insert(&mut conn)?;
 
         Ok(login_result)
     })
@@ -138,12 +201,80 @@ pub async fn login(
 
     update_core_user_permissions(authz.as_ref(), db_result).await?;
 
-    Ok(Json(LoginResponse {
+    Ok(Json(LdapLoginResponse {
+        access_token,
+        expires_at,
         // TODO calculate permissions
         permissions: Default::default(),
     }))
 }
 
+/// Create or update the user described by `info` in its tenant and groups
+///
+/// Tenant and tariff are chosen by the configured assignment, for the external assignments they are taken from `info`.
+fn login_user(
+    settings: &Settings,
+    conn: &mut DbConnection,
+    mut info: IdTokenInfo,
+) -> Result<LoginResult, ApiError> {
+    // Get tariff depending on the configured assignment
+    let tariff = match &settings.tariffs.assignment {
+        TariffAssignment::Static { static_tariff_name } => {
+            Tariff::get_by_name(conn, static_tariff_name)?
+        }
+        TariffAssignment::ByExternalTariffId => {
+            let external_tariff_id = info.tariff_id.clone().ok_or_else(|| {
+                ApiError::bad_request()
+                    .with_code("invalid_claims")
+                    .with_message("tariff_id missing in id_token claims")
+            })?;
+
+            Tariff::get_by_external_id(conn, &ExternalTariffId::from(external_tariff_id))
+                .optional()?
+                .ok_or_else(|| {
+                    ApiError::internal()
+                        .with_code("invalid_tariff_id")
+                        .with_message("JWT contained unknown tariff_id")
+                })?
+        }
+    };
+
+    // Get the tenant_id depending on the configured assignment
+    let tenant_id = match &settings.tenants.assignment {
+        TenantAssignment::Static { static_tenant_id } => static_tenant_id.clone(),
+        TenantAssignment::ByExternalTenantId => info.tenant_id.clone().ok_or_else(|| {
+            ApiError::bad_request()
+                .with_code("invalid_claims")
+                .with_message("tenant_id missing in id_token claims")
+        })?,
+    };
+
+    let tenant = get_or_create_tenant_by_oidc_id(conn, &OidcTenantId::from(tenant_id))?;
+
+    let groups: Vec<(TenantId, GroupName)> = take(&mut info.x_grp)
+        .into_iter()
+        .map(|group| (tenant.id, GroupName::from(group)))
+        .collect();
+
+    let groups = get_or_create_groups_by_name(conn, &groups)?;
+
+    // Try to get the user by the `sub` field in the IdToken
+    let user = User::get_by_oidc_sub(conn, tenant.id, &info.sub)?;
+
+    let login_result = match user {
+        Some(user) => {
+            // Found a matching user, update its attributes, tenancy and groups
+            update_user::update_user(settings, conn, user, info, groups, tariff)?
+        }
+        None => {
+            // No matching user, create a new one with inside the given tenants and groups
+            create_user::create_user(settings, conn, info, tenant, groups, tariff)?
+        }
+    };
+
+    Ok(login_result)
+}
+
 /// Wrapper struct for the oidc provider
 #[derive(Debug, Serialize, Eq, PartialEq, Hash)]
 pub struct Provider {
@@ -183,6 +314,14 @@ enum LoginResult {
     },
 }
 
+impl LoginResult {
+    fn user(&self) -> &User {
+        match self {
+            LoginResult::UserCreated { user, .. } | LoginResult::UserUpdated { user, .. } => user,
+        }
+    }
+}
+
 async fn update_core_user_permissions(
     authz: &kustos::Authz,
     db_result: LoginResult,
@@ -0,0 +1,222 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Protection of secrets which can be guessed, like invite codes, call-in PINs and passwords of LDAP users
+//!
+//! Failed attempts are counted in redis per subject. Once a subject reaches the configured number of failed attempts
+//! it is locked out, the duration of the lockout doubles with every further failed attempt.
+use super::response::ApiError;
+use crate::api::client_ip::client_ip;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::BruteForceProtection;
+use actix_web::HttpRequest;
+use cidr::IpInet;
+use redis_args::ToRedisArgs;
+use std::time::Duration;
+
+/// The kind of secret which is guessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    InviteCode,
+    CallIn,
+    LdapLogin,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::InviteCode => "invite_code",
+            Self::CallIn => "call_in",
+            Self::LdapLogin => "ldap_login",
+        }
+    }
+}
+
+/// Number of failed attempts of a subject
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-brute_force:kind={kind}:subject={subject}:attempts")]
+struct FailedAttempts<'s> {
+    kind: &'static str,
+    subject: &'s str,
+}
+
+/// Exists while the subject is locked out
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-brute_force:kind={kind}:subject={subject}:lockout")]
+struct Lockout<'s> {
+    kind: &'static str,
+    subject: &'s str,
+}
+
+/// Returns the subject of the client sending the request
+///
+/// Returns `None` if the address of the client is unknown. The attempts of these clients are not counted, as sharing
+/// them would allow a single client to lock out all of them.
+pub fn client_subject(request: &HttpRequest, trusted_proxies: &[IpInet]) -> Option<String> {
+    let subject = client_ip(request, trusted_proxies).map(|ip| ip.to_string());
+
+    if subject.is_none() {
+        log::debug!("Unknown client address, failed attempts are not counted");
+    }
+
+    subject
+}
+
+/// Returns an error if the subject is currently locked out
+pub async fn check(
+    redis_conn: &mut RedisConnection,
+    kind: Kind,
+    subject: &str,
+) -> Result<(), ApiError> {
+    let remaining: i64 = redis::cmd("TTL")
+        .arg(Lockout {
+            kind: kind.as_str(),
+            subject,
+        })
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get the lockout of {}, {}", kind.as_str(), e);
+            ApiError::internal()
+        })?;
+
+    if remaining > 0 {
+        return Err(ApiError::too_many_requests()
+            .with_code("locked_out")
+            .with_message(format!(
+                "Too many failed attempts, try again in {remaining} seconds"
+            )));
+    }
+
+    Ok(())
+}
+
+/// Count a failed attempt of the subject and lock it out if it reached the maximum number of attempts
+pub async fn record_failure(
+    redis_conn: &mut RedisConnection,
+    settings: &BruteForceProtection,
+    kind: Kind,
+    subject: &str,
+) -> Result<(), ApiError> {
+    let attempts_key = FailedAttempts {
+        kind: kind.as_str(),
+        subject,
+    };
+
+    let (attempts,): (u32,) = redis::pipe()
+        .atomic()
+        .incr(&attempts_key, 1)
+        .expire(&attempts_key, settings.window.as_secs() as usize)
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to count failed attempt of {}, {}", kind.as_str(), e);
+            ApiError::internal()
+        })?;
+
+    let lockout = match lockout_duration(settings, attempts) {
+        Some(lockout) => lockout,
+        None => return Ok(()),
+    };
+
+    log::warn!(
+        "Locking out {} {:?} for {}s after {} failed attempts",
+        kind.as_str(),
+        subject,
+        lockout.as_secs(),
+        attempts
+    );
+
+    // Keep counting the attempts until the lockout is over, so the next lockout is longer
+    redis::pipe()
+        .atomic()
+        .set_ex(
+            Lockout {
+                kind: kind.as_str(),
+                subject,
+            },
+            attempts,
+            lockout.as_secs() as usize,
+        )
+        .expire(
+            &attempts_key,
+            (settings.window + lockout).as_secs() as usize,
+        )
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to lock out {}, {}", kind.as_str(), e);
+            ApiError::internal()
+        })
+}
+
+/// Forget the failed attempts of the subject after a successful attempt
+pub async fn reset(
+    redis_conn: &mut RedisConnection,
+    kind: Kind,
+    subject: &str,
+) -> Result<(), ApiError> {
+    redis::cmd("DEL")
+        .arg(FailedAttempts {
+            kind: kind.as_str(),
+            subject,
+        })
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Failed to reset failed attempts of {}, {}",
+                kind.as_str(),
+                e
+            );
+            ApiError::internal()
+        })
+}
+
+/// Duration of the lockout after the given number of failed attempts, `None` if the subject is not locked out
+fn lockout_duration(settings: &BruteForceProtection, attempts: u32) -> Option<Duration> {
+    let exceeded = attempts.checked_sub(settings.max_attempts)?;
+
+    let lockout = settings
+        .lockout
+        .checked_mul(2u32.saturating_pow(exceeded))
+        .unwrap_or(settings.max_lockout);
+
+    Some(lockout.min(settings.max_lockout))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn exponential_lockout() {
+        let settings = BruteForceProtection {
+            max_attempts: 3,
+            window: Duration::from_secs(600),
+            lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(3600),
+        };
+
+        assert_eq!(lockout_duration(&settings, 2), None);
+        assert_eq!(
+            lockout_duration(&settings, 3),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            lockout_duration(&settings, 4),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            lockout_duration(&settings, 6),
+            Some(Duration::from_secs(480))
+        );
+        assert_eq!(
+            lockout_duration(&settings, 100),
+            Some(Duration::from_secs(3600))
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Google and Microsoft calendars linked by users
+//!
+//! Events created by a user are synchronized to their linked calendars by a background task of the controller.
+use super::response::{ApiError, NoContent};
+use crate::calendar_sync::{CalendarSync, CalendarSyncError};
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{delete, get, put};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::calendar_links::{CalendarLink, CalendarProvider};
+use db_storage::users::User;
+use serde::{Deserialize, Serialize};
+
+/// A calendar linked by the current user
+#[derive(Debug, Serialize)]
+pub struct CalendarLinkResource {
+    pub provider: CalendarProvider,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last successful synchronization
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+impl From<CalendarLink> for CalendarLinkResource {
+    fn from(link: CalendarLink) -> Self {
+        Self {
+            provider: link.provider,
+            created_at: link.created_at,
+            synced_at: link.synced_at,
+        }
+    }
+}
+
+impl From<CalendarSyncError> for ApiError {
+    fn from(e: CalendarSyncError) -> Self {
+        match e {
+            CalendarSyncError::NotConfigured => ApiError::not_found(),
+            CalendarSyncError::Link(e) => {
+                log::debug!("Failed to link calendar, {:?}", e);
+
+                ApiError::bad_request()
+                    .with_code("invalid_authorization_code")
+                    .with_message("The authorization code could not be exchanged")
+            }
+            CalendarSyncError::Other(e) => e.into(),
+        }
+    }
+}
+
+/// API Endpoint *GET /users/me/calendar_links*
+///
+/// Returns all calendars linked by the current user
+#[get("/users/me/calendar_links")]
+pub async fn get_calendar_links(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+) -> Result<Json<Vec<CalendarLinkResource>>, ApiError> {
+    let current_user = current_user.into_inner();
+
+    let links = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        CalendarLink::get_all_for_user(&mut conn, current_user.id)
+    })
+    .await??;
+
+    Ok(Json(links.into_iter().map(Into::into).collect()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeQuery {
+    redirect_uri: String,
+    state: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizeResponse {
+    /// URL of the provider the user has to be redirected to
+    pub url: String,
+}
+
+/// API Endpoint *GET /users/me/calendar_links/{provider}/authorize*
+///
+/// Returns the URL to grant access to the calendar of the provider. The provider redirects back to the given
+/// `redirect_uri` with the authorization code and the `state`.
+#[get("/users/me/calendar_links/{provider}/authorize")]
+pub async fn authorize(
+    calendar_sync: Data<CalendarSync>,
+    provider: Path<CalendarProvider>,
+    query: Query<AuthorizeQuery>,
+) -> Result<Json<AuthorizeResponse>, ApiError> {
+    let url =
+        calendar_sync.authorize_url(provider.into_inner(), &query.redirect_uri, &query.state)?;
+
+    Ok(Json(AuthorizeResponse {
+        url: url.to_string(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PutCalendarLinkBody {
+    /// Authorization code returned by the provider
+    code: String,
+    /// The `redirect_uri` used for the authorization
+    redirect_uri: String,
+}
+
+/// API Endpoint *PUT /users/me/calendar_links/{provider}*
+///
+/// Links the calendar of the provider with the authorization code, replacing an existing link to the provider
+#[put("/users/me/calendar_links/{provider}")]
+pub async fn put_calendar_link(
+    calendar_sync: Data<CalendarSync>,
+    current_user: ReqData<User>,
+    provider: Path<CalendarProvider>,
+    body: Json<PutCalendarLinkBody>,
+) -> Result<Json<CalendarLinkResource>, ApiError> {
+    let body = body.into_inner();
+
+    let link = calendar_sync
+        .link(
+            current_user.id,
+            provider.into_inner(),
+            &body.code,
+            &body.redirect_uri,
+        )
+        .await?;
+
+    Ok(Json(link.into()))
+}
+
+/// API Endpoint *DELETE /users/me/calendar_links/{provider}*
+///
+/// Unlinks the calendar of the provider. Events already synchronized remain in the calendar.
+#[delete("/users/me/calendar_links/{provider}")]
+pub async fn delete_calendar_link(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    provider: Path<CalendarProvider>,
+) -> Result<NoContent, ApiError> {
+    let current_user = current_user.into_inner();
+    let provider = provider.into_inner();
+
+    let deleted = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        CalendarLink::delete(&mut conn, current_user.id, provider)
+    })
+    .await??;
+
+    if !deleted {
+        return Err(ApiError::not_found());
+    }
+
+    Ok(NoContent)
+}
@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Contacts of users
+//!
+//! Consists of the favorites picked by the user and the users they recently met in a meeting. The frontend uses them
+//! to suggest invitees without searching through all users.
+use super::response::ApiError;
+use super::users::PublicUserProfile;
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Json, ReqData};
+use actix_web::{get, put};
+use chrono::{DateTime, Duration, Utc};
+use controller_shared::settings::Settings;
+use database::{Db, DbConnection};
+use db_storage::contacts::ContactFavorite;
+use db_storage::room_statistics::get_recently_met_users;
+use db_storage::users::User;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use types::core::UserId;
+use validator::Validate;
+
+/// Number of days in which met users are considered as recently met
+const RECENTLY_MET_DAYS: i64 = 30;
+
+/// Maximum number of recently met users returned
+const RECENTLY_MET_LIMIT: usize = 10;
+
+/// Contacts of the current user
+#[derive(Debug, Serialize)]
+pub struct ContactsResource {
+    /// Favorites, in the order they have been added
+    pub favorites: Vec<PublicUserProfile>,
+    /// Users recently met in a meeting which are not favorites, most recently met first
+    pub recently_met: Vec<RecentlyMetContact>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecentlyMetContact {
+    #[serde(flatten)]
+    pub user: PublicUserProfile,
+    /// End of the last meeting both users took part in
+    pub last_met_at: DateTime<Utc>,
+}
+
+/// Load the contacts of the user
+fn get_contacts(
+    settings: &Settings,
+    conn: &mut DbConnection,
+    user_id: UserId,
+) -> database::Result<ContactsResource> {
+    let favorites = ContactFavorite::get_all_for_user(conn, user_id)?;
+
+    let since = Utc::now() - Duration::days(RECENTLY_MET_DAYS);
+    let recently_met = get_recently_met_users(conn, user_id, since)?;
+
+    let favorite_ids: HashSet<UserId> = favorites.iter().map(|user| user.id).collect();
+
+    let recently_met = recently_met
+        .into_iter()
+        .filter(|(user, _)| !favorite_ids.contains(&user.id))
+        .take(RECENTLY_MET_LIMIT)
+        .map(|(user, last_met_at)| RecentlyMetContact {
+            user: PublicUserProfile::from_db(settings, user),
+            last_met_at,
+        })
+        .collect();
+
+    let favorites = favorites
+        .into_iter()
+        .map(|user| PublicUserProfile::from_db(settings, user))
+        .collect();
+
+    Ok(ContactsResource {
+        favorites,
+        recently_met,
+    })
+}
+
+/// API Endpoint *GET /users/me/contacts*
+///
+/// Returns the favorite and recently met contacts of the current user
+#[get("/users/me/contacts")]
+pub async fn get_me_contacts(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    current_user: ReqData<User>,
+) -> Result<Json<ContactsResource>, ApiError> {
+    let settings = settings.load_full();
+    let current_user = current_user.into_inner();
+
+    let contacts = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        get_contacts(&settings, &mut conn, current_user.id)
+    })
+    .await??;
+
+    Ok(Json(contacts))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutContactsBody {
+    /// Ids of the favorite contacts
+    #[validate(length(max = 100))]
+    pub favorites: Vec<UserId>,
+}
+
+/// API Endpoint *PUT /users/me/contacts*
+///
+/// Replaces the favorite contacts of the current user. All favorites must be users of the same tenant.
+///
+/// Returns the updated contacts.
+#[put("/users/me/contacts")]
+pub async fn put_me_contacts(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    body: Json<PutContactsBody>,
+) -> Result<Json<ContactsResource>, ApiError> {
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let settings = settings.load_full();
+    let current_user = current_user.into_inner();
+
+    // Remove duplicates and the current user, keeping the order
+    let mut seen = HashSet::new();
+    let favorite_ids: Vec<UserId> = body
+        .favorites
+        .into_iter()
+        .filter(|id| *id != current_user.id && seen.insert(*id))
+        .collect();
+
+    let contacts = crate::block(move || -> Result<ContactsResource, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let users = User::get_all_by_ids(&mut conn, &favorite_ids)?;
+
+        let all_known = users.len() == favorite_ids.len()
+            && users
+                .iter()
+                .all(|user| user.tenant_id == current_user.tenant_id);
+
+        if !all_known {
+            return Err(ApiError::bad_request()
+                .with_code("unknown_contact")
+                .with_message("The favorites contain unknown users"));
+        }
+
+        ContactFavorite::replace_for_user(&mut conn, current_user.id, &favorite_ids)?;
+
+        Ok(get_contacts(&settings, &mut conn, current_user.id)?)
+    })
+    .await??;
+
+    Ok(Json(contacts))
+}
@@ -0,0 +1,560 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Detection of scheduling conflicts
+//!
+//! A planned event conflicts with every occurrence of another event which overlaps with one of its occurrences and
+//! either blocks the time of one of the participants or takes place in the same room.
+use super::{
+    can_edit, recurrence_array_to_string, recurrence_set, validate_recurrence_pattern, ApiResponse,
+    DateTimeTzFromDb, DefaultApiResult, EventResource,
+};
+use crate::api::v1::response::ApiError;
+use actix_web::post;
+use actix_web::web::{Data, Json, ReqData};
+use chrono::{DateTime, Utc};
+use database::{Db, DbConnection};
+use db_storage::events::{Event, EventException, EventExceptionKind};
+use db_storage::room_owners::RoomOwner;
+use db_storage::users::User;
+use rrule::RRuleSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use types::core::{DateTimeTz, EventId, RoomId, UserId};
+use validator::Validate;
+
+/// Maximum number of occurrences of the planned event which are checked for conflicts
+const MAX_PLANNED_OCCURRENCES: usize = 100;
+
+/// Maximum number of occurrences of recurring events which are iterated per request to find the ones in the checked
+/// time range, shared by all events of the request
+pub(crate) const MAX_EXPANDED_OCCURRENCES: usize = 10_000;
+
+/// Time span of an occurrence of an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Occurrence {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+impl Occurrence {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.starts_at < other.ends_at && other.starts_at < self.ends_at
+    }
+}
+
+/// Returns the occurrences of the given occurrences which overlap with any of the planned occurrences
+fn overlapping(planned: &[Occurrence], occurrences: Vec<Occurrence>) -> Vec<Occurrence> {
+    occurrences
+        .into_iter()
+        .filter(|occurrence| planned.iter().any(|planned| planned.overlaps(occurrence)))
+        .collect()
+}
+
+/// Returns the occurrences of the planned event, limited to [`MAX_PLANNED_OCCURRENCES`]
+fn planned_occurrences(
+    starts_at: DateTimeTz,
+    ends_at: DateTimeTz,
+    recurrence_pattern: Option<&str>,
+) -> Result<Vec<Occurrence>, rrule::RRuleError> {
+    let duration = ends_at.datetime - starts_at.datetime;
+
+    let starts: Vec<DateTime<Utc>> = match recurrence_pattern {
+        Some(recurrence_pattern) => recurrence_set(starts_at, recurrence_pattern)?
+            .into_iter()
+            .take(MAX_PLANNED_OCCURRENCES)
+            .map(|dt| dt.with_timezone(&Utc))
+            .collect(),
+        None => vec![starts_at.datetime],
+    };
+
+    Ok(starts
+        .into_iter()
+        .map(|starts_at| Occurrence {
+            starts_at,
+            ends_at: starts_at + duration,
+        })
+        .collect())
+}
+
+/// Returns the starts of the occurrences of the recurrence set which overlap with the time range
+///
+/// Every iterated occurrence is taken from the `budget`, the iteration stops once it is used up.
+fn expand_occurrences(
+    rruleset: RRuleSet,
+    duration: chrono::Duration,
+    time_min: DateTime<Utc>,
+    time_max: DateTime<Utc>,
+    budget: &mut usize,
+) -> Vec<DateTime<Utc>> {
+    let limit = *budget;
+    let mut expanded = 0;
+
+    let datetimes = rruleset
+        .into_iter()
+        .take(limit)
+        .inspect(|_| expanded += 1)
+        .map(|dt| dt.with_timezone(&Utc))
+        .skip_while(|&dt| dt + duration <= time_min)
+        .take_while(|&dt| dt < time_max)
+        .collect();
+
+    *budget -= expanded;
+
+    datetimes
+}
+
+/// Returns the occurrences of a stored event which overlap with the time range, exceptions are already applied
+///
+/// The occurrences of recurring events are taken from the `budget`, see [`MAX_EXPANDED_OCCURRENCES`].
+pub(crate) fn occurrences_between(
+    conn: &mut DbConnection,
+    event: &Event,
+    time_min: DateTime<Utc>,
+    time_max: DateTime<Utc>,
+    budget: &mut usize,
+) -> database::Result<Vec<Occurrence>> {
+    let (starts_at, ends_at) = match (
+        DateTimeTz::starts_at_of(event),
+        DateTimeTz::ends_at_of(event),
+    ) {
+        (Some(starts_at), Some(ends_at)) => (starts_at, ends_at),
+        _ => return Ok(vec![]),
+    };
+
+    let duration = ends_at.datetime - starts_at.datetime;
+
+    let recurrence_pattern = match &event.recurrence_pattern {
+        Some(recurrence_pattern) if event.is_recurring.unwrap_or_default() => recurrence_pattern,
+        _ => {
+            return Ok(vec![Occurrence {
+                starts_at: starts_at.datetime,
+                ends_at: ends_at.datetime,
+            }])
+        }
+    };
+
+    let rruleset = match recurrence_set(starts_at, recurrence_pattern) {
+        Ok(rruleset) => rruleset,
+        Err(e) => {
+            log::error!("failed to parse rrule of event {} from db {}", event.id, e);
+            return Ok(vec![]);
+        }
+    };
+
+    if *budget == 0 {
+        log::warn!(
+            "Skipping occurrences of event {}, too many occurrences were expanded",
+            event.id
+        );
+        return Ok(vec![]);
+    }
+
+    let datetimes = expand_occurrences(rruleset, duration, time_min, time_max, budget);
+
+    let exceptions = EventException::get_all_for_event(conn, event.id, &datetimes)?;
+
+    let occurrences = datetimes
+        .into_iter()
+        .filter_map(|datetime| {
+            let exception = exceptions
+                .iter()
+                .find(|exception| exception.exception_date == datetime);
+
+            match exception {
+                Some(exception) if matches!(exception.kind, EventExceptionKind::Cancelled) => None,
+                Some(exception) => Some(Occurrence {
+                    starts_at: exception.starts_at.unwrap_or(datetime),
+                    ends_at: exception.ends_at.unwrap_or(datetime + duration),
+                }),
+                None => Some(Occurrence {
+                    starts_at: datetime,
+                    ends_at: datetime + duration,
+                }),
+            }
+        })
+        .collect();
+
+    Ok(occurrences)
+}
+
+/// Kind of a scheduling conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// A participant is busy with another event
+    Participant,
+    /// Another event takes place in the same room
+    Room,
+}
+
+/// A conflict of the planned event with an occurrence of another event
+///
+/// The conflicting event is only identified if the current user takes part in it, otherwise only the busy time of
+/// the participant is returned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventConflict {
+    #[serde(rename = "type")]
+    pub kind: ConflictKind,
+
+    /// Id of the busy participant, only set for `participant` conflicts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
+
+    /// Id of the conflicting event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<EventId>,
+
+    /// Title of the conflicting event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// Start of the conflicting occurrence
+    pub starts_at: DateTime<Utc>,
+
+    /// End of the conflicting occurrence
+    pub ends_at: DateTime<Utc>,
+}
+
+/// Finds the conflicts of the planned occurrences with the events of the given users and room
+///
+/// The occurrences of the event `exclude_event` are ignored.
+fn find_conflicts(
+    conn: &mut DbConnection,
+    current_user: &User,
+    planned: &[Occurrence],
+    user_ids: &[UserId],
+    room_id: Option<RoomId>,
+    exclude_event: Option<EventId>,
+) -> database::Result<Vec<EventConflict>> {
+    let (time_min, time_max) = match (planned.first(), planned.last()) {
+        (Some(first), Some(last)) => (first.starts_at, last.ends_at),
+        _ => return Ok(vec![]),
+    };
+
+    let blocking = Event::get_all_blocking_for_users(
+        conn,
+        current_user.tenant_id,
+        user_ids,
+        time_min,
+        time_max,
+    )?;
+
+    let visible: HashSet<EventId> = blocking
+        .iter()
+        .filter(|(_, user_id)| *user_id == current_user.id)
+        .map(|(event, _)| event.id)
+        .collect();
+
+    let mut conflicts = vec![];
+    let mut budget = MAX_EXPANDED_OCCURRENCES;
+    // The same event is returned once for every blocked user, only expand its occurrences once
+    let mut expanded: HashMap<EventId, Vec<Occurrence>> = HashMap::new();
+
+    for (event, user_id) in &blocking {
+        if Some(event.id) == exclude_event {
+            continue;
+        }
+
+        let occurrences = match expanded.get(&event.id) {
+            Some(occurrences) => occurrences.clone(),
+            None => {
+                let occurrences =
+                    occurrences_between(conn, event, time_min, time_max, &mut budget)?;
+                expanded.insert(event.id, occurrences.clone());
+                occurrences
+            }
+        };
+        let is_visible = visible.contains(&event.id);
+
+        conflicts.extend(
+            overlapping(planned, occurrences)
+                .into_iter()
+                .map(|occurrence| EventConflict {
+                    kind: ConflictKind::Participant,
+                    user_id: Some(*user_id),
+                    event_id: is_visible.then_some(event.id),
+                    title: is_visible.then(|| event.title.clone()),
+                    starts_at: occurrence.starts_at,
+                    ends_at: occurrence.ends_at,
+                }),
+        );
+    }
+
+    if let Some(room_id) = room_id {
+        for event in Event::get_all_for_room_between(conn, room_id, time_min, time_max)? {
+            if Some(event.id) == exclude_event {
+                continue;
+            }
+
+            let occurrences = match expanded.remove(&event.id) {
+                Some(occurrences) => occurrences,
+                None => occurrences_between(conn, &event, time_min, time_max, &mut budget)?,
+            };
+
+            conflicts.extend(
+                overlapping(planned, occurrences)
+                    .into_iter()
+                    .map(|occurrence| EventConflict {
+                        kind: ConflictKind::Room,
+                        user_id: None,
+                        event_id: Some(event.id),
+                        title: Some(event.title.clone()),
+                        starts_at: occurrence.starts_at,
+                        ends_at: occurrence.ends_at,
+                    }),
+            );
+        }
+    }
+
+    conflicts.sort_by(|a, b| {
+        (a.starts_at, a.kind, a.user_id, a.event_id).cmp(&(
+            b.starts_at,
+            b.kind,
+            b.user_id,
+            b.event_id,
+        ))
+    });
+    conflicts.dedup();
+
+    Ok(conflicts)
+}
+
+/// Returns the conflicts of a newly created event with the other events of its creator
+pub(super) fn conflicts_of_new_event(
+    conn: &mut DbConnection,
+    current_user: &User,
+    event: &EventResource,
+) -> Result<Vec<EventConflict>, ApiError> {
+    let (starts_at, ends_at) = match (event.starts_at, event.ends_at) {
+        (Some(starts_at), Some(ends_at)) => (starts_at, ends_at),
+        _ => return Ok(vec![]),
+    };
+
+    let recurrence_pattern = recurrence_array_to_string(event.recurrence_pattern.clone());
+
+    let planned =
+        planned_occurrences(starts_at, ends_at, recurrence_pattern.as_deref()).map_err(|e| {
+            log::error!("failed to parse rrule of new event {}", e);
+            ApiError::internal()
+        })?;
+
+    let conflicts = find_conflicts(
+        conn,
+        current_user,
+        &planned,
+        &[current_user.id],
+        None,
+        Some(event.id),
+    )?;
+
+    Ok(conflicts)
+}
+
+/// Body of the `POST /events/check-conflicts` endpoint
+#[derive(Debug, Deserialize, Validate)]
+pub struct CheckConflictsBody {
+    /// Start of the planned event
+    ///
+    /// For recurring events this must contain the datetime of the first instance
+    pub starts_at: DateTimeTz,
+
+    /// End of the planned event
+    ///
+    /// For recurring events this must contain the datetime of the first instance
+    pub ends_at: DateTimeTz,
+
+    /// List of recurrence patterns of the planned event
+    #[validate(custom = "validate_recurrence_pattern")]
+    #[serde(default)]
+    pub recurrence_pattern: Vec<String>,
+
+    /// Users to invite, whose events are checked in addition to the ones of the current user
+    #[validate(length(max = 100))]
+    #[serde(default)]
+    pub invitees: Vec<UserId>,
+
+    /// Event which is being rescheduled
+    ///
+    /// Its own occurrences are ignored and the events in its room are checked for double-bookings.
+    pub event_id: Option<EventId>,
+}
+
+/// API Endpoint `POST /events/check-conflicts`
+///
+/// Returns the conflicts of a planned event with the events of the current user and the invitees, and with other
+/// events in the room of the rescheduled event. Only the first 100 occurrences of recurring events are checked.
+#[post("/events/check-conflicts")]
+pub async fn check_conflicts(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    body: Json<CheckConflictsBody>,
+) -> DefaultApiResult<Vec<EventConflict>> {
+    let current_user = current_user.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    if body.ends_at.datetime < body.starts_at.datetime {
+        return Err(ApiError::unprocessable_entity()
+            .with_code("invalid_event")
+            .with_message("ends_at must not be before starts_at"));
+    }
+
+    let conflicts = crate::block(move || -> Result<Vec<EventConflict>, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        let room_id = match body.event_id {
+            Some(event_id) => {
+                let event = Event::get(&mut conn, event_id)?;
+
+                let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+
+                if !can_edit(&event, &current_user, &owned_rooms) {
+                    return Err(ApiError::forbidden());
+                }
+
+                Some(event.room)
+            }
+            None => None,
+        };
+
+        let recurrence_pattern = recurrence_array_to_string(body.recurrence_pattern);
+
+        let planned =
+            planned_occurrences(body.starts_at, body.ends_at, recurrence_pattern.as_deref())
+                .map_err(|e| {
+                    log::debug!("failed to parse rrule {:?}", e);
+                    ApiError::unprocessable_entity()
+                        .with_code("invalid_event")
+                        .with_message("Invalid recurrence pattern")
+                })?;
+
+        let mut user_ids = body.invitees;
+        user_ids.push(current_user.id);
+        user_ids.sort_unstable();
+        user_ids.dedup();
+
+        let conflicts = find_conflicts(
+            &mut conn,
+            &current_user,
+            &planned,
+            &user_ids,
+            room_id,
+            body.event_id,
+        )?;
+
+        Ok(conflicts)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(conflicts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+    use chrono_tz::Tz;
+    use pretty_assertions::assert_eq;
+    use types::core::TimeZone;
+
+    fn utc(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn occurrence(day: u32, start_hour: u32, end_hour: u32) -> Occurrence {
+        Occurrence {
+            starts_at: utc(day, start_hour),
+            ends_at: utc(day, end_hour),
+        }
+    }
+
+    #[test]
+    fn overlapping_occurrences() {
+        let planned = [occurrence(1, 10, 11), occurrence(8, 10, 11)];
+
+        let occurrences = vec![
+            // ends when the planned event starts
+            occurrence(1, 9, 10),
+            occurrence(1, 10, 12),
+            // contains the planned event
+            occurrence(8, 8, 12),
+            // between the planned occurrences
+            occurrence(4, 10, 11),
+        ];
+
+        assert_eq!(
+            overlapping(&planned, occurrences),
+            vec![occurrence(1, 10, 12), occurrence(8, 8, 12)]
+        );
+    }
+
+    #[test]
+    fn occurrences_of_recurring_planned_event() {
+        let berlin = TimeZone::from(Tz::Europe__Berlin);
+
+        let occurrences = planned_occurrences(
+            DateTimeTz {
+                datetime: utc(1, 9),
+                timezone: berlin,
+            },
+            DateTimeTz {
+                datetime: utc(1, 10),
+                timezone: berlin,
+            },
+            Some("RRULE:FREQ=DAILY;COUNT=2"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            occurrences,
+            vec![occurrence(1, 9, 10), occurrence(2, 9, 10)]
+        );
+    }
+
+    fn daily_series(starts_at: DateTime<Utc>) -> RRuleSet {
+        recurrence_set(
+            DateTimeTz {
+                datetime: starts_at,
+                timezone: TimeZone::from(Tz::UTC),
+            },
+            "RRULE:FREQ=DAILY",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn expand_long_occurrences() {
+        // Every occurrence lasts two days, the one starting on the 2nd still overlaps with the 4th
+        let mut budget = MAX_EXPANDED_OCCURRENCES;
+
+        let starts = expand_occurrences(
+            daily_series(utc(1, 12)),
+            chrono::Duration::hours(48),
+            utc(4, 0),
+            utc(4, 6),
+            &mut budget,
+        );
+
+        assert_eq!(starts, vec![utc(2, 12), utc(3, 12)]);
+        assert_eq!(budget, MAX_EXPANDED_OCCURRENCES - 4);
+    }
+
+    #[test]
+    fn expand_occurrences_within_budget() {
+        let mut budget = 5;
+
+        let starts = expand_occurrences(
+            daily_series(utc(1, 12)),
+            chrono::Duration::hours(1),
+            utc(20, 0),
+            utc(21, 0),
+            &mut budget,
+        );
+
+        assert!(starts.is_empty());
+        assert_eq!(budget, 0);
+    }
+}
@@ -3,8 +3,8 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use super::{
-    can_edit, ApiResponse, DateTimeTz, DefaultApiResult, EventAndInstanceId, EventInvitee,
-    EventRoomInfo, EventStatus, EventType, InstanceId, LOCAL_DT_FORMAT,
+    can_edit, recurrence_set, ApiResponse, DateTimeTz, DefaultApiResult, EventAndInstanceId,
+    EventInvitee, EventRoomInfo, EventStatus, EventType, InstanceId,
 };
 use crate::api::v1::cursor::Cursor;
 use crate::api::v1::events::{enrich_invitees_from_keycloak, DateTimeTzFromDb};
@@ -21,6 +21,7 @@ use db_storage::events::{
     Event, EventException, EventExceptionKind, EventInviteStatus, NewEventException,
     UpdateEventException,
 };
+use db_storage::room_owners::RoomOwner;
 use db_storage::tenants::Tenant;
 use db_storage::users::User;
 use keycloak_admin::KeycloakAdminClient;
@@ -129,7 +130,7 @@ pub async fn get_event_instances(
 
     let instances_data = crate::block(
         move || -> Result<GetPaginatedEventInstancesData, ApiError> {
-            let mut conn = db.get_conn()?;
+            let mut conn = db.get_read_conn()?;
 
             let (event, invite, room, sip_config, is_favorite) =
                 Event::get_with_invite_and_room(&mut conn, current_user.id, event_id)?;
@@ -173,7 +174,8 @@ pub async fn get_event_instances(
 
             let room = EventRoomInfo::from_room(&settings, room, sip_config);
 
-            let can_edit = can_edit(&event, &current_user);
+            let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+            let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
             let mut exceptions = exceptions.into_iter().peekable();
 
@@ -290,7 +292,8 @@ pub async fn get_event_instance(
 
         let room = EventRoomInfo::from_room(&settings, room, sip_config);
 
-        let can_edit = can_edit(&event, &current_user);
+        let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+        let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
         let event_instance = create_event_instance(
             &users,
@@ -500,7 +503,8 @@ pub async fn patch_event_instance(
 
         let room = EventRoomInfo::from_room(&settings, room, sip_config);
 
-        let can_edit = can_edit(&event, &current_user);
+        let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+        let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
         let event_instance = create_event_instance(
             &users,
@@ -626,16 +630,9 @@ fn build_rruleset(event: &Event) -> Result<RRuleSet, ApiError> {
         .recurrence_pattern
         .as_ref()
         .ok_or_else(ApiError::internal)?;
-    let starts_at = event.starts_at.ok_or_else(ApiError::internal)?;
-    let starts_at_tz = event.starts_at_tz.ok_or_else(ApiError::internal)?;
+    let starts_at = DateTimeTz::starts_at_of(event).ok_or_else(ApiError::internal)?;
 
-    let starts_at = starts_at
-        .with_timezone(starts_at_tz.as_ref())
-        .naive_local()
-        .format(LOCAL_DT_FORMAT);
-
-    let rruleset = format!("DTSTART;TZID={starts_at_tz}:{starts_at};\n{recurrence_pattern}");
-    let rruleset: RRuleSet = rruleset.parse().map_err(|e| {
+    let rruleset = recurrence_set(starts_at, recurrence_pattern).map_err(|e| {
         log::error!("failed to parse rrule from db {}", e);
         ApiError::internal()
     })?;
@@ -705,6 +702,7 @@ mod tests {
                 id: RoomId::from(Uuid::nil()),
                 password: None,
                 waiting_room: false,
+                locale: None,
                 sip_tel: None,
                 sip_uri: None,
                 sip_id: None,
@@ -714,6 +712,8 @@ mod tests {
             invitees: vec![EventInvitee {
                 profile: EventInviteeProfile::Registered(user_profile),
                 status: EventInviteStatus::Accepted,
+                comment: None,
+                responded_at: None,
             }],
             is_all_day: false,
             starts_at: DateTimeTz {
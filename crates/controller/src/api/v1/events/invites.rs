@@ -4,21 +4,23 @@
 
 use super::{ApiResponse, DefaultApiResult, PagePaginationQuery};
 use crate::api::v1::events::{
-    enrich_invitees_from_keycloak, EventInvitee, EventPoliciesBuilderExt,
+    enrich_from_keycloak, enrich_invitees_from_keycloak, EventInvitee, EventPoliciesBuilderExt,
 };
 use crate::api::v1::response::{ApiError, Created, NoContent};
 use crate::api::v1::rooms::RoomsPoliciesBuilderExt;
-use crate::services::MailService;
+use crate::services::{ExternalMailRecipient, MailRecipient, MailService, RegisteredMailRecipient};
 use crate::settings::SharedSettingsActix;
 use actix_web::web::{Data, Json, Path, Query, ReqData};
 use actix_web::{delete, get, patch, post, Either};
 use anyhow::Context;
+use chrono::Utc;
 use database::Db;
 use db_storage::events::email_invites::{EventEmailInvite, NewEventEmailInvite};
 use db_storage::events::{
     Event, EventFavorite, EventInvite, EventInviteStatus, NewEventInvite, UpdateEventInvite,
 };
-use db_storage::invites::NewInvite;
+use db_storage::invites::{Invite, NewInvite};
+use db_storage::room_owners::RoomOwner;
 use db_storage::rooms::Room;
 use db_storage::sip_configs::SipConfig;
 use db_storage::tenants::Tenant;
@@ -30,6 +32,7 @@ use kustos::policies_builder::PoliciesBuilder;
 use kustos::Authz;
 use serde::{Deserialize, Serialize};
 use types::core::{EventId, UserId};
+use validator::Validate;
 
 /// API Endpoint `GET /events/{event_id}/invites`
 ///
@@ -48,7 +51,7 @@ pub async fn get_invites_for_event(
     let PagePaginationQuery { per_page, page } = pagination.into_inner();
 
     let (invitees, invitees_total) = crate::block(move || -> database::Result<_> {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         // FIXME: Preliminary solution, consider using UNION when Diesel supports it.
         // As in #[get("/events")], we simply get all invitees and truncate them afterwards.
@@ -172,7 +175,9 @@ async fn create_user_event_invite(
         let (event, room, sip_config) = Event::get_with_room(&mut conn, event_id)?;
         let invitee = User::get_filtered_by_tenant(&mut conn, event.tenant_id, invitee_id)?;
 
-        if event.created_by == invitee_id {
+        // Owners of the event's room do not need to be invited
+        if event.created_by == invitee_id || RoomOwner::is_owner(&mut conn, event.room, invitee_id)?
+        {
             return Ok(Either::Right(NoContent));
         }
 
@@ -266,7 +271,9 @@ async fn create_email_event_invite(
                 User::get_by_email(&mut conn, current_user.tenant_id, email.as_ref())?;
 
             if let Some(invitee_user) = invitee_user {
-                if event.created_by == invitee_user.id {
+                if event.created_by == invitee_user.id
+                    || RoomOwner::is_owner(&mut conn, event.room, invitee_user.id)?
+                {
                     return Ok(UserState::ExistsAndIsAlreadyInvited);
                 }
 
@@ -491,9 +498,11 @@ pub async fn delete_invite_to_event(
 
     let resources = vec![
         format!("/events/{event_id}"),
+        format!("/events/{event_id}/attendance"),
         format!("/events/{event_id}/instances"),
         format!("/events/{event_id}/instances/*"),
         format!("/events/{event_id}/invites"),
+        format!("/events/{event_id}/matrix_bridge"),
         format!("/users/me/event_favorites/{event_id}"),
         format!("/events/{event_id}/invite"),
         format!("/rooms/{room_id}"),
@@ -521,7 +530,7 @@ pub async fn get_event_invites_pending(
     current_user: ReqData<User>,
 ) -> DefaultApiResult<GetEventInvitesPendingResponse> {
     let event_invites = crate::block(move || -> database::Result<_> {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         EventInvite::get_pending_for_user(&mut conn, current_user.id)
     })
@@ -532,22 +541,53 @@ pub async fn get_event_invites_pending(
     }))
 }
 
+/// Request body for the `PATCH /events/{event_id}/invite` endpoint
+#[derive(Debug, Deserialize, Validate)]
+pub struct PatchEventInviteBody {
+    /// Response to the invite, one of `accepted`, `tentative` or `declined`
+    #[serde(default = "default_response_status")]
+    status: EventInviteStatus,
+    /// Comment to the response, shown to the organizer
+    #[validate(length(max = 1024))]
+    comment: Option<String>,
+}
+
+fn default_response_status() -> EventInviteStatus {
+    EventInviteStatus::Accepted
+}
+
 /// API Endpoint `PATCH /events/{event_id}/invite`
 ///
-/// Accept an invite to an event
+/// Respond to an invite to an event with the provided [`PatchEventInviteBody`], accepts the invite if no body is
+/// provided
 #[patch("/events/{event_id}/invite")]
 pub async fn accept_event_invite(
     db: Data<Db>,
     current_user: ReqData<User>,
     event_id: Path<EventId>,
+    body: Option<Json<PatchEventInviteBody>>,
 ) -> Result<NoContent, ApiError> {
     let event_id = event_id.into_inner();
+    let body = body.map(Json::into_inner).unwrap_or(PatchEventInviteBody {
+        status: EventInviteStatus::Accepted,
+        comment: None,
+    });
+
+    body.validate()?;
+
+    if body.status == EventInviteStatus::Pending {
+        return Err(ApiError::bad_request()
+            .with_code("invalid_status")
+            .with_message("An invite cannot be responded to with `pending`"));
+    }
 
     crate::block(move || -> database::Result<_> {
         let mut conn = db.get_conn()?;
 
         let changeset = UpdateEventInvite {
-            status: EventInviteStatus::Accepted,
+            status: body.status,
+            comment: body.comment,
+            responded_at: Some(Utc::now()),
         };
 
         changeset.apply(&mut conn, current_user.id, event_id)
@@ -573,6 +613,8 @@ pub async fn decline_event_invite(
 
         let changeset = UpdateEventInvite {
             status: EventInviteStatus::Declined,
+            comment: None,
+            responded_at: Some(Utc::now()),
         };
 
         changeset.apply(&mut conn, current_user.id, event_id)
@@ -581,3 +623,203 @@ pub async fn decline_event_invite(
 
     Ok(NoContent)
 }
+
+/// Responses of the invitees of an event
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct EventAttendance {
+    pub accepted: u32,
+    pub tentative: u32,
+    pub declined: u32,
+    pub pending: u32,
+    /// Expected number of participants including the creator of the event
+    pub predicted_participants: u32,
+}
+
+impl EventAttendance {
+    fn new(statuses: impl IntoIterator<Item = EventInviteStatus>) -> Self {
+        let mut attendance = EventAttendance {
+            accepted: 0,
+            tentative: 0,
+            declined: 0,
+            pending: 0,
+            predicted_participants: 0,
+        };
+
+        for status in statuses {
+            match status {
+                EventInviteStatus::Accepted => attendance.accepted += 1,
+                EventInviteStatus::Tentative => attendance.tentative += 1,
+                EventInviteStatus::Declined => attendance.declined += 1,
+                EventInviteStatus::Pending => attendance.pending += 1,
+            }
+        }
+
+        attendance.predicted_participants = attendance.predict_participants();
+
+        attendance
+    }
+
+    /// The creator and the invitees who accepted attend, tentative invitees attend with a probability of one half
+    /// and pending invitees with the rate of acceptance of the invitees who responded
+    fn predict_participants(&self) -> u32 {
+        let responded = self.accepted + self.declined;
+
+        let acceptance_rate = if responded == 0 {
+            0.5
+        } else {
+            f64::from(self.accepted) / f64::from(responded)
+        };
+
+        let predicted = 1.0
+            + f64::from(self.accepted)
+            + f64::from(self.tentative) * 0.5
+            + f64::from(self.pending) * acceptance_rate;
+
+        predicted.round() as u32
+    }
+}
+
+/// API Endpoint `GET /events/{event_id}/attendance`
+///
+/// Get the number of invitees per response and the predicted number of participants
+#[get("/events/{event_id}/attendance")]
+pub async fn get_event_attendance(
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> DefaultApiResult<EventAttendance> {
+    let event_id = event_id.into_inner();
+
+    let attendance = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_read_conn()?;
+
+        let (invites_with_user, _) =
+            EventInvite::get_for_event_paginated(&mut conn, event_id, i64::MAX, 1)?;
+        let (email_invites, _) =
+            EventEmailInvite::get_for_event_paginated(&mut conn, event_id, i64::MAX, 1)?;
+
+        let statuses = invites_with_user
+            .into_iter()
+            .map(|(invite, _)| invite.status)
+            .chain(email_invites.into_iter().map(|invite| invite.status));
+
+        Ok(EventAttendance::new(statuses))
+    })
+    .await??;
+
+    Ok(ApiResponse::new(attendance))
+}
+
+/// API Endpoint `POST /events/{event_id}/invites/remind`
+///
+/// Send the invite mail again to all invitees who did not respond yet
+#[post("/events/{event_id}/invites/remind")]
+pub async fn remind_event_invitees(
+    db: Data<Db>,
+    kc_admin_client: Data<KeycloakAdminClient>,
+    current_tenant: ReqData<Tenant>,
+    current_user: ReqData<User>,
+    event_id: Path<EventId>,
+    mail_service: Data<MailService>,
+) -> Result<NoContent, ApiError> {
+    let event_id = event_id.into_inner();
+    let current_tenant = current_tenant.into_inner();
+    let current_user = current_user.into_inner();
+
+    let (event, room, sip_config, invitees, invite_for_room) = {
+        let current_user = current_user.clone();
+
+        crate::block(move || -> database::Result<_> {
+            let mut conn = db.get_conn()?;
+
+            let (event, room, sip_config) = Event::get_with_room(&mut conn, event_id)?;
+
+            let (invites_with_user, _) =
+                EventInvite::get_for_event_paginated(&mut conn, event_id, i64::MAX, 1)?;
+            let (email_invites, _) =
+                EventEmailInvite::get_for_event_paginated(&mut conn, event_id, i64::MAX, 1)?;
+
+            let user_invitees = invites_with_user
+                .into_iter()
+                .filter(|(invite, _)| invite.status == EventInviteStatus::Pending)
+                .map(|(_, user)| {
+                    MailRecipient::Registered(RegisteredMailRecipient {
+                        email: user.email,
+                        title: user.title,
+                        first_name: user.firstname,
+                        last_name: user.lastname,
+                        language: user.language,
+                    })
+                });
+
+            let email_invitees = email_invites
+                .into_iter()
+                .filter(|invite| invite.status == EventInviteStatus::Pending)
+                .map(|invite| {
+                    MailRecipient::External(ExternalMailRecipient {
+                        email: invite.email,
+                    })
+                });
+
+            let invitees: Vec<_> = user_invitees.chain(email_invitees).collect();
+
+            let invite_for_room = Invite::get_first_for_room(&mut conn, room.id, current_user.id)?;
+
+            Ok((event, room, sip_config, invitees, invite_for_room))
+        })
+        .await??
+    };
+
+    let mail_service = mail_service.into_inner();
+
+    for invitee in invitees {
+        let invitee = enrich_from_keycloak(invitee, &current_tenant, &kc_admin_client).await;
+
+        if let Err(e) = mail_service
+            .send_invite_reminder(
+                current_user.clone(),
+                event.clone(),
+                room.clone(),
+                sip_config.clone(),
+                invitee,
+                invite_for_room.id.to_string(),
+            )
+            .await
+        {
+            log::error!("Failed to send invite reminder with MailService, {}", e);
+        }
+    }
+
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn attendance() {
+        use EventInviteStatus::*;
+
+        assert_eq!(
+            EventAttendance::new([
+                Accepted, Accepted, Accepted, Accepted, Tentative, Tentative, Declined, Pending,
+                Pending, Pending, Pending, Pending,
+            ]),
+            EventAttendance {
+                accepted: 4,
+                tentative: 2,
+                declined: 1,
+                pending: 5,
+                // 1 + 4 + 2 * 0.5 + 5 * 0.8
+                predicted_participants: 10,
+            }
+        );
+
+        assert_eq!(EventAttendance::new([]).predicted_participants, 1);
+        assert_eq!(
+            EventAttendance::new([Pending, Pending]).predicted_participants,
+            2
+        );
+    }
+}
@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Bridge of the global chat of an event's room to a Matrix room
+//!
+//! The bridge itself is run by the `k3k-matrix-bridge` service, which joins the room as bot participant.
+use super::{ApiResponse, DefaultApiResult};
+use crate::api::v1::response::{ApiError, NoContent};
+use actix_web::web::{Data, Json, Path, ReqData};
+use actix_web::{delete, get, put};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::events::matrix_bridges::{EventMatrixBridge, NewEventMatrixBridge};
+use db_storage::events::Event;
+use db_storage::users::User;
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use types::core::{EventId, UserId};
+use validator::{Validate, ValidationError};
+
+/// Matrix bridge of an event
+#[derive(Debug, Serialize)]
+pub struct MatrixBridgeResource {
+    pub matrix_room_id: String,
+    pub updated_by: UserId,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<EventMatrixBridge> for MatrixBridgeResource {
+    fn from(bridge: EventMatrixBridge) -> Self {
+        Self {
+            matrix_room_id: bridge.matrix_room_id,
+            updated_by: bridge.updated_by,
+            updated_at: bridge.updated_at,
+        }
+    }
+}
+
+/// Request body for the `PUT /events/{event_id}/matrix_bridge` endpoint
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutMatrixBridgeBody {
+    #[validate(custom = "validate_matrix_room_id")]
+    pub matrix_room_id: String,
+}
+
+/// Accept room ids of the form `!opaque_id:domain`, aliases can not be bridged as they may change
+fn validate_matrix_room_id(room_id: &str) -> Result<(), ValidationError> {
+    let valid = room_id.len() <= 255
+        && room_id
+            .strip_prefix('!')
+            .and_then(|room_id| room_id.split_once(':'))
+            .map(|(opaque_id, domain)| !opaque_id.is_empty() && !domain.is_empty())
+            .unwrap_or_default();
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_matrix_room_id"))
+    }
+}
+
+/// API Endpoint `GET /events/{event_id}/matrix_bridge`
+///
+/// Returns the Matrix room the chat of the event is bridged to
+#[get("/events/{event_id}/matrix_bridge")]
+pub async fn get_matrix_bridge(
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> DefaultApiResult<MatrixBridgeResource> {
+    let event_id = event_id.into_inner();
+
+    let bridge = crate::block(move || EventMatrixBridge::get(&mut db.get_read_conn()?, event_id))
+        .await??
+        .ok_or_else(ApiError::not_found)?;
+
+    Ok(ApiResponse::new(bridge.into()))
+}
+
+/// API Endpoint `PUT /events/{event_id}/matrix_bridge`
+///
+/// Bridge the chat of the event to the given Matrix room, replacing an existing bridge
+#[put("/events/{event_id}/matrix_bridge")]
+pub async fn put_matrix_bridge(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    event_id: Path<EventId>,
+    body: Json<PutMatrixBridgeBody>,
+) -> DefaultApiResult<MatrixBridgeResource> {
+    let event_id = event_id.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let bridge = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        conn.transaction(|conn| {
+            // Assert that the event exists
+            let _event = Event::get(conn, event_id)?;
+
+            NewEventMatrixBridge {
+                event_id,
+                matrix_room_id: body.matrix_room_id,
+                updated_by: current_user.id,
+            }
+            .upsert(conn)
+        })
+    })
+    .await??;
+
+    Ok(ApiResponse::new(bridge.into()))
+}
+
+/// API Endpoint `DELETE /events/{event_id}/matrix_bridge`
+///
+/// Stop bridging the chat of the event
+#[delete("/events/{event_id}/matrix_bridge")]
+pub async fn delete_matrix_bridge(
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> Result<NoContent, ApiError> {
+    let event_id = event_id.into_inner();
+
+    let existed =
+        crate::block(move || EventMatrixBridge::delete_by_event_id(&mut db.get_conn()?, event_id))
+            .await??;
+
+    if existed {
+        Ok(NoContent)
+    } else {
+        Err(ApiError::not_found())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_room_id() {
+        assert!(validate_matrix_room_id("!abcdef:matrix.org").is_ok());
+        assert!(validate_matrix_room_id("#opentalk:matrix.org").is_err());
+        assert!(validate_matrix_room_id("!abcdef").is_err());
+        assert!(validate_matrix_room_id("!:matrix.org").is_err());
+        assert!(validate_matrix_room_id("!abcdef:").is_err());
+    }
+}
@@ -10,8 +10,9 @@ use super::response::error::ValidationErrorEntry;
 use super::response::{ApiError, NoContent, CODE_VALUE_REQUIRED};
 use super::users::{email_to_libravatar_url, PublicUserProfile, UnregisteredUser};
 use super::{ApiResponse, DefaultApiResult, PagePaginationQuery};
+use crate::api::v1::events::conflicts::EventConflict;
 use crate::api::v1::response::CODE_IGNORED_VALUE;
-use crate::api::v1::rooms::RoomsPoliciesBuilderExt;
+use crate::api::v1::rooms::{validate_locale, RoomsPoliciesBuilderExt};
 use crate::api::v1::util::comma_separated;
 use crate::api::v1::util::{deserialize_some, GetUserProfilesBatched};
 use crate::services::{
@@ -30,6 +31,7 @@ use db_storage::events::{
     EventInviteStatus, NewEvent, UpdateEvent,
 };
 use db_storage::invites::Invite;
+use db_storage::room_owners::RoomOwner;
 use db_storage::rooms::{NewRoom, Room, UpdateRoom};
 use db_storage::sip_configs::{NewSipConfig, SipConfig};
 use db_storage::tenants::Tenant;
@@ -44,9 +46,12 @@ use serde::{Deserialize, Serialize};
 use types::core::{DateTimeTz, EventId, RoomId, TimeZone};
 use validator::{Validate, ValidationError};
 
+pub mod conflicts;
 pub mod favorites;
 pub mod instances;
 pub mod invites;
+pub mod matrix_bridge;
+pub mod published_results;
 
 const LOCAL_DT_FORMAT: &str = "%Y%m%dT%H%M%S";
 const UTC_DT_FORMAT: &str = "%Y%m%dT%H%M%SZ";
@@ -194,6 +199,10 @@ pub struct EventResource {
     /// All information about the room the event takes place in
     pub room: EventRoomInfo,
 
+    /// URL of a meeting on an external conferencing service which takes place instead of a meeting in the room
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_meeting_url: Option<String>,
+
     /// Flag which indicates if `invitees` contains all invites as far as known to the application
     /// May also be true if there are no invitees but no invitees were requested
     pub invitees_truncated: bool,
@@ -373,6 +382,11 @@ pub struct EmailOnlyUser {
 pub struct EventInvitee {
     pub profile: EventInviteeProfile,
     pub status: EventInviteStatus,
+    /// Comment of the invitee to the response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub responded_at: Option<DateTime<Utc>>,
 }
 
 impl EventInvitee {
@@ -380,6 +394,8 @@ impl EventInvitee {
         EventInvitee {
             profile: EventInviteeProfile::Registered(PublicUserProfile::from_db(settings, user)),
             status: invite.status,
+            comment: invite.comment,
+            responded_at: invite.responded_at,
         }
     }
 
@@ -390,7 +406,9 @@ impl EventInvitee {
                 email: invite.email,
                 avatar_url,
             }),
-            status: EventInviteStatus::Pending,
+            status: invite.status,
+            comment: invite.comment,
+            responded_at: invite.responded_at,
         }
     }
 }
@@ -431,6 +449,10 @@ pub struct EventRoomInfo {
     /// Flag to check if the room has a waiting room enabled
     pub waiting_room: bool,
 
+    /// Language tag of the documents and messages generated for the room
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
     /// SIP Call-In phone number which must be used to reach the room
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sip_tel: Option<String>,
@@ -469,6 +491,7 @@ impl EventRoomInfo {
             id: room.id,
             password: room.password,
             waiting_room: room.waiting_room,
+            locale: room.locale,
             sip_tel,
             sip_uri: None, // TODO SIP URI support
             sip_id,
@@ -496,6 +519,10 @@ pub struct PostEventsBody {
     #[serde(default)]
     pub waiting_room: bool,
 
+    /// Language tag of the documents and messages generated for the event's room, e.g. `de-DE`
+    #[validate(custom = "validate_locale")]
+    pub locale: Option<String>,
+
     /// Should the created event be time independent?
     ///
     /// If true, all following fields must be null
@@ -529,6 +556,13 @@ pub struct PostEventsBody {
     /// Is this an ad-hoc chatroom?
     #[serde(default)]
     pub is_adhoc: bool,
+
+    /// URL of a meeting on an external conferencing service, e.g. Jitsi or Teams
+    ///
+    /// If set, invitees are sent to the external meeting instead of the event's room. The room is created regardless
+    /// to keep the permissions of the event.
+    #[validate(length(max = 2048), custom = "validate_external_meeting_url")]
+    pub external_meeting_url: Option<String>,
 }
 
 fn validate_recurrence_pattern(pattern: &[String]) -> Result<(), ValidationError> {
@@ -543,6 +577,27 @@ fn validate_recurrence_pattern(pattern: &[String]) -> Result<(), ValidationError
     Ok(())
 }
 
+/// Accept absolute `http` and `https` URLs
+fn validate_external_meeting_url(url: &str) -> Result<(), ValidationError> {
+    match url::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err(ValidationError::new("invalid_url")),
+    }
+}
+
+/// Response of the `POST /events` endpoint
+#[derive(Debug, Serialize)]
+pub struct NewEventResource {
+    #[serde(flatten)]
+    pub event: EventResource,
+
+    /// Conflicts of the created event with other events of the current user
+    ///
+    /// The event is created regardless, see [`conflicts::check_conflicts`] to check for conflicts beforehand
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<EventConflict>,
+}
+
 /// API Endpoint `POST /events`
 #[post("/events")]
 pub async fn new_event(
@@ -551,14 +606,14 @@ pub async fn new_event(
     authz: Data<Authz>,
     current_user: ReqData<User>,
     new_event: Json<PostEventsBody>,
-) -> DefaultApiResult<EventResource> {
+) -> DefaultApiResult<NewEventResource> {
     let settings = settings.load_full();
     let current_user = current_user.into_inner();
     let new_event = new_event.into_inner();
 
     new_event.validate()?;
 
-    let event_resource = crate::block(move || {
+    let new_event_resource = crate::block(move || -> Result<NewEventResource, ApiError> {
         let mut conn = db.get_conn()?;
 
         // simplify logic by splitting the event creation
@@ -569,12 +624,14 @@ pub async fn new_event(
                 description,
                 password,
                 waiting_room,
+                locale,
                 is_time_independent: true,
                 is_all_day: None,
                 starts_at: None,
                 ends_at: None,
                 recurrence_pattern,
                 is_adhoc,
+                external_meeting_url,
             } if recurrence_pattern.is_empty() => {
                 create_time_independent_event(
                     &settings,
@@ -584,35 +641,53 @@ pub async fn new_event(
                     description,
                     password,
                     waiting_room,
-                    is_adhoc
+                    locale,
+                    is_adhoc,
+                    external_meeting_url,
                 )
+                .map(|event| NewEventResource {
+                    event,
+                    conflicts: vec![],
+                })
             }
             PostEventsBody {
                 title,
                 description,
                 password,
                 waiting_room,
+                locale,
                 is_time_independent: false,
                 is_all_day: Some(is_all_day),
                 starts_at: Some(starts_at),
                 ends_at: Some(ends_at),
                 recurrence_pattern,
                 is_adhoc,
+                external_meeting_url,
             } => {
-                create_time_dependent_event(
+                let event_resource = create_time_dependent_event(
                     &settings,
                     &mut conn,
-                    current_user,
+                    current_user.clone(),
                     title,
                     description,
                     password,
                     waiting_room,
+                    locale,
                     is_all_day,
                     starts_at,
                     ends_at,
                     recurrence_pattern,
                     is_adhoc,
-                )
+                    external_meeting_url,
+                )?;
+
+                let conflicts =
+                    conflicts::conflicts_of_new_event(&mut conn, &current_user, &event_resource)?;
+
+                Ok(NewEventResource {
+                    event: event_resource,
+                    conflicts,
+                })
             }
             new_event => {
                 let msg = if new_event.is_time_independent {
@@ -627,6 +702,8 @@ pub async fn new_event(
     })
     .await??;
 
+    let event_resource = &new_event_resource.event;
+
     let policies = PoliciesBuilder::new()
         .grant_user_access(event_resource.created_by.id)
         .event_read_access(event_resource.id)
@@ -637,7 +714,7 @@ pub async fn new_event(
 
     authz.add_policies(policies).await?;
 
-    Ok(ApiResponse::new(event_resource))
+    Ok(ApiResponse::new(new_event_resource))
 }
 
 /// Part of `POST /events` endpoint
@@ -650,13 +727,18 @@ fn create_time_independent_event(
     description: String,
     password: Option<String>,
     waiting_room: bool,
+    locale: Option<String>,
     is_adhoc: bool,
+    external_meeting_url: Option<String>,
 ) -> Result<EventResource, ApiError> {
     let room = NewRoom {
         created_by: current_user.id,
         password,
         waiting_room,
         tenant_id: current_user.tenant_id,
+        locale,
+        region: None,
+        webinar_mode: false,
     }
     .insert(conn)?;
 
@@ -679,6 +761,7 @@ fn create_time_independent_event(
         recurrence_pattern: None,
         is_adhoc,
         tenant_id: current_user.tenant_id,
+        external_meeting_url,
     }
     .insert(conn)?;
 
@@ -687,6 +770,7 @@ fn create_time_independent_event(
         title: event.title,
         description: event.description,
         room: EventRoomInfo::from_room(settings, room, Some(sip_config)),
+        external_meeting_url: event.external_meeting_url,
         invitees_truncated: false,
         invitees: vec![],
         created_by: PublicUserProfile::from_db(settings, current_user.clone()),
@@ -716,11 +800,13 @@ fn create_time_dependent_event(
     description: String,
     password: Option<String>,
     waiting_room: bool,
+    locale: Option<String>,
     is_all_day: bool,
     starts_at: DateTimeTz,
     ends_at: DateTimeTz,
     recurrence_pattern: Vec<String>,
     is_adhoc: bool,
+    external_meeting_url: Option<String>,
 ) -> Result<EventResource, ApiError> {
     let recurrence_pattern = recurrence_array_to_string(recurrence_pattern);
 
@@ -732,6 +818,9 @@ fn create_time_dependent_event(
         password,
         waiting_room,
         tenant_id: current_user.tenant_id,
+        locale,
+        region: None,
+        webinar_mode: false,
     }
     .insert(conn)?;
 
@@ -754,6 +843,7 @@ fn create_time_dependent_event(
         recurrence_pattern,
         is_adhoc,
         tenant_id: current_user.tenant_id,
+        external_meeting_url,
     }
     .insert(conn)?;
 
@@ -762,6 +852,7 @@ fn create_time_dependent_event(
         title: event.title,
         description: event.description,
         room: EventRoomInfo::from_room(settings, room, Some(sip_config)),
+        external_meeting_url: event.external_meeting_url,
         invitees_truncated: false,
         invitees: vec![],
         created_by: PublicUserProfile::from_db(settings, current_user.clone()),
@@ -897,7 +988,7 @@ pub async fn get_events(
                 from_starts_at: cursor.event_starts_at,
             });
 
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         let events = Event::get_all_for_user_paginated(
             &mut conn,
@@ -943,6 +1034,8 @@ pub async fn get_events(
             .zip(email_invites_grouped_by_event)
             .collect();
 
+        let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+
         let mut event_resources = vec![];
 
         let mut ret_cursor_data = None;
@@ -987,7 +1080,7 @@ pub async fn get_events(
             let starts_at = DateTimeTz::starts_at_of(&event);
             let ends_at = DateTimeTz::ends_at_of(&event);
 
-            let can_edit = can_edit(&event, &current_user);
+            let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
             event_resources.push(EventOrException::Event(EventResource {
                 id: event.id,
@@ -998,6 +1091,7 @@ pub async fn get_events(
                 title: event.title,
                 description: event.description,
                 room: EventRoomInfo::from_room(&settings, room, sip_config),
+                external_meeting_url: event.external_meeting_url,
                 invitees_truncated,
                 invitees,
                 is_time_independent: event.is_time_independent,
@@ -1098,13 +1192,15 @@ pub async fn get_event(
         let starts_at = DateTimeTz::starts_at_of(&event);
         let ends_at = DateTimeTz::ends_at_of(&event);
 
-        let can_edit = can_edit(&event, &current_user);
+        let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+        let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
         let event_resource = EventResource {
             id: event.id,
             title: event.title,
             description: event.description,
             room: EventRoomInfo::from_room(&settings, room, sip_config),
+            external_meeting_url: event.external_meeting_url,
             invitees_truncated,
             invitees,
             created_by: users.get(event.created_by),
@@ -1177,6 +1273,11 @@ pub struct PatchEventBody {
     /// Patch the presence of a waiting room
     waiting_room: Option<bool>,
 
+    /// Patch the language of the documents and messages generated for the event's room
+    #[validate(custom = "validate_locale")]
+    #[serde(default, deserialize_with = "deserialize_some")]
+    locale: Option<Option<String>>,
+
     /// Patch the adhoc flag.
     is_adhoc: Option<bool>,
 
@@ -1205,6 +1306,11 @@ pub struct PatchEventBody {
     #[validate(custom = "validate_recurrence_pattern")]
     #[serde(default)]
     recurrence_pattern: Vec<String>,
+
+    /// Patch the URL of the external meeting, `null` removes it
+    #[validate(length(max = 2048), custom = "validate_external_meeting_url")]
+    #[serde(default, deserialize_with = "deserialize_some")]
+    external_meeting_url: Option<Option<String>>,
 }
 
 impl PatchEventBody {
@@ -1214,24 +1320,28 @@ impl PatchEventBody {
             description,
             password,
             waiting_room,
+            locale,
             is_adhoc,
             is_time_independent,
             is_all_day,
             starts_at,
             ends_at,
             recurrence_pattern,
+            external_meeting_url,
         } = self;
 
         title.is_none()
             && description.is_none()
             && password.is_none()
             && waiting_room.is_none()
+            && locale.is_none()
             && is_adhoc.is_none()
             && is_time_independent.is_none()
             && is_all_day.is_none()
             && starts_at.is_none()
             && ends_at.is_none()
             && recurrence_pattern.is_empty()
+            && external_meeting_url.is_none()
     }
 
     // special case to only patch the events room
@@ -1241,12 +1351,14 @@ impl PatchEventBody {
             description,
             password,
             waiting_room,
+            locale,
             is_time_independent,
             is_all_day,
             starts_at,
             ends_at,
             recurrence_pattern,
             is_adhoc,
+            external_meeting_url,
         } = self;
 
         title.is_none()
@@ -1257,7 +1369,8 @@ impl PatchEventBody {
             && ends_at.is_none()
             && recurrence_pattern.is_empty()
             && is_adhoc.is_none()
-            && (password.is_some() || waiting_room.is_some())
+            && external_meeting_url.is_none()
+            && (password.is_some() || waiting_room.is_some() || locale.is_some())
     }
 }
 
@@ -1317,11 +1430,17 @@ pub async fn patch_event(
             let (event, invite, room, sip_config, is_favorite) =
                 Event::get_with_invite_and_room(&mut conn, current_user.id, event_id)?;
 
-            let room = if patch.password.is_some() || patch.waiting_room.is_some() {
+            let room = if patch.password.is_some()
+                || patch.waiting_room.is_some()
+                || patch.locale.is_some()
+            {
                 // Update the event's room if at least one of the fields is set
                 UpdateRoom {
                     password: patch.password.clone(),
                     waiting_room: patch.waiting_room,
+                    locale: patch.locale.clone(),
+                    region: None,
+                    webinar_mode: None,
                 }
                 .apply(&mut conn, event.room)?
             } else {
@@ -1376,7 +1495,8 @@ pub async fn patch_event(
             let starts_at = DateTimeTz::starts_at_of(&event);
             let ends_at = DateTimeTz::ends_at_of(&event);
 
-            let can_edit = can_edit(&event, &current_user);
+            let owned_rooms = RoomOwner::get_room_ids_for_user(&mut conn, current_user.id)?;
+            let can_edit = can_edit(&event, &current_user, &owned_rooms);
 
             let event_resource = EventResource {
                 id: event.id,
@@ -1387,6 +1507,7 @@ pub async fn patch_event(
                 title: event.title,
                 description: event.description,
                 room: EventRoomInfo::from_room(&settings, room, sip_config),
+                external_meeting_url: event.external_meeting_url,
                 invitees_truncated,
                 invitees,
                 is_time_independent: event.is_time_independent,
@@ -1487,6 +1608,7 @@ fn patch_event_change_to_time_dependent(
             is_recurring: Some(Some(recurrence_pattern.is_some())),
             recurrence_pattern: Some(recurrence_pattern),
             is_adhoc: patch.is_adhoc,
+            external_meeting_url: patch.external_meeting_url,
         })
     } else {
         const MSG: Option<&str> = Some("Must be provided when changing to time dependent events");
@@ -1582,6 +1704,7 @@ fn patch_time_independent_event(
         is_recurring: Some(None),
         recurrence_pattern: Some(None),
         is_adhoc: patch.is_adhoc,
+        external_meeting_url: patch.external_meeting_url,
     })
 }
 
@@ -1630,6 +1753,7 @@ fn patch_time_dependent_event(
         duration_secs: Some(duration_secs),
         is_recurring: Some(Some(recurrence_pattern.is_some())),
         is_adhoc: patch.is_adhoc,
+        external_meeting_url: patch.external_meeting_url,
         recurrence_pattern: Some(recurrence_pattern),
     })
 }
@@ -1651,15 +1775,15 @@ pub struct DeleteEventQuery {
     suppress_email_notification: bool,
 }
 
-/// API Endpoint `POST /events/{event_id}`
+/// API Endpoint `DELETE /events/{event_id}`
+///
+/// Moves the event into the trash, from where it can be restored until the configured grace period has passed.
 #[delete("/events/{event_id}")]
-#[allow(clippy::too_many_arguments)]
 pub async fn delete_event(
     db: Data<Db>,
     kc_admin_client: Data<KeycloakAdminClient>,
     current_tenant: ReqData<Tenant>,
     current_user: ReqData<User>,
-    authz: Data<Authz>,
     query: Query<DeleteEventQuery>,
     event_id: Path<EventId>,
     mail_service: Data<MailService>,
@@ -1686,7 +1810,7 @@ pub async fn delete_event(
 
             let invited_users = get_invited_mail_recipients_for_event(&mut conn, event_id)?;
 
-            Event::delete_by_id(&mut conn, event_id)?;
+            Event::soft_delete_by_id(&mut conn, event_id)?;
 
             let notification_values = CancellationNotificationValues {
                 tenant: current_tenant.into_inner(),
@@ -1705,10 +1829,6 @@ pub async fn delete_event(
         notify_invitees_about_delete(notification_values, mail_service, &kc_admin_client).await;
     }
 
-    let resources = associated_resource_ids(event_id);
-
-    authz.remove_explicit_resources(resources).await?;
-
     Ok(NoContent)
 }
 
@@ -1742,12 +1862,19 @@ async fn notify_invitees_about_delete(
 pub(crate) fn associated_resource_ids(event_id: EventId) -> impl IntoIterator<Item = ResourceId> {
     [
         ResourceId::from(format!("/events/{event_id}")),
+        ResourceId::from(format!("/events/{event_id}/attendance")),
         ResourceId::from(format!("/events/{event_id}/instances")),
         ResourceId::from(format!("/events/{event_id}/instances/*")),
         ResourceId::from(format!("/events/{event_id}/invites")),
         ResourceId::from(format!("/events/{event_id}/invites/*")),
+        ResourceId::from(format!("/events/{event_id}/invites/remind")),
         ResourceId::from(format!("/events/{event_id}/invite")),
+        ResourceId::from(format!("/events/{event_id}/matrix_bridge")),
+        ResourceId::from(format!("/events/{event_id}/polls")),
+        ResourceId::from(format!("/events/{event_id}/polls/*")),
         ResourceId::from(format!("/events/{event_id}/reschedule")),
+        ResourceId::from(format!("/events/{event_id}/results")),
+        ResourceId::from(format!("/events/{event_id}/results/*")),
         ResourceId::from(format!("/users/me/event_favorites/{event_id}")),
     ]
 }
@@ -1954,12 +2081,7 @@ fn parse_event_dt_params(
     }
 
     if let Some(recurrence_pattern) = &recurrence_pattern {
-        let starts_at_tz = starts_at.timezone;
-        let starts_at_fmt = starts_at.datetime.format(LOCAL_DT_FORMAT);
-
-        let rrule_set =
-            format!("DTSTART;TZID={starts_at_tz}:{starts_at_fmt};\n{recurrence_pattern}");
-        let rrule_set = match rrule_set.parse::<RRuleSet>() {
+        let rrule_set = match recurrence_set(starts_at, recurrence_pattern) {
             Ok(rrule) => rrule,
             Err(e) => {
                 log::warn!("failed to parse rrule {:?}", e);
@@ -2028,11 +2150,29 @@ fn parse_event_dt_params(
     }
 }
 
+/// Builds the set of occurrences of a recurring event starting at `starts_at`
+///
+/// The start is passed to the recurrence rules as local time of the event's time zone, so that the occurrences keep
+/// their wall-clock time when the UTC offset changes in between, e.g. on daylight saving time changes.
+fn recurrence_set(
+    starts_at: DateTimeTz,
+    recurrence_pattern: &str,
+) -> Result<RRuleSet, rrule::RRuleError> {
+    let starts_at_tz = starts_at.timezone;
+    let starts_at = starts_at
+        .to_datetime_tz()
+        .naive_local()
+        .format(LOCAL_DT_FORMAT);
+
+    format!("DTSTART;TZID={starts_at_tz}:{starts_at};\n{recurrence_pattern}").parse()
+}
+
 /// calculate if `user` can edit `event`
-fn can_edit(event: &Event, user: &User) -> bool {
-    // Its sufficient to check if the user created the event as here isn't currently a system which allows users to
-    // grant write access to event
-    event.created_by == user.id
+///
+/// `owned_rooms` are the ids of the rooms owned by the user. Besides the creator of the event, all owners of the
+/// event's room can edit the event.
+fn can_edit(event: &Event, user: &User, owned_rooms: &[RoomId]) -> bool {
+    event.created_by == user.id || owned_rooms.contains(&event.room)
 }
 
 /// Helper trait to to reduce boilerplate in the single route handlers
@@ -2053,6 +2193,10 @@ where
     /// PUT and DELETE to the event_favorites endpoint.
     fn event_read_access(self, event_id: EventId) -> Self {
         self.add_resource(event_id.resource_id(), [AccessMethod::Get])
+            .add_resource(
+                event_id.resource_id().with_suffix("/attendance"),
+                [AccessMethod::Get],
+            )
             .add_resource(
                 event_id.resource_id().with_suffix("/instances"),
                 [AccessMethod::Get],
@@ -2065,6 +2209,10 @@ where
                 event_id.resource_id().with_suffix("/invites"),
                 [AccessMethod::Get],
             )
+            .add_resource(
+                event_id.resource_id().with_suffix("/matrix_bridge"),
+                [AccessMethod::Get],
+            )
             .add_resource(
                 format!("/users/me/event_favorites/{event_id}"),
                 [AccessMethod::Put, AccessMethod::Delete],
@@ -2072,9 +2220,12 @@ where
     }
 
     /// PATCH and DELETE to the event
-    /// POST to reschedule and invites of the event
+    /// POST to reschedule, invites and invite reminders of the event
     /// PATCH to instances
     /// DELETE to invites
+    /// PUT and DELETE to the matrix bridge
+    /// GET, POST and DELETE to the prepared polls
+    /// GET, POST and DELETE to the published results
     fn event_write_access(self, event_id: EventId) -> Self {
         self.add_resource(
             event_id.resource_id(),
@@ -2096,6 +2247,30 @@ where
             event_id.resource_id().with_suffix("/invites/*"),
             [AccessMethod::Delete],
         )
+        .add_resource(
+            event_id.resource_id().with_suffix("/invites/remind"),
+            [AccessMethod::Post],
+        )
+        .add_resource(
+            event_id.resource_id().with_suffix("/matrix_bridge"),
+            [AccessMethod::Put, AccessMethod::Delete],
+        )
+        .add_resource(
+            event_id.resource_id().with_suffix("/polls"),
+            [AccessMethod::Get, AccessMethod::Post],
+        )
+        .add_resource(
+            event_id.resource_id().with_suffix("/polls/*"),
+            [AccessMethod::Delete],
+        )
+        .add_resource(
+            event_id.resource_id().with_suffix("/results"),
+            [AccessMethod::Get, AccessMethod::Post],
+        )
+        .add_resource(
+            event_id.resource_id().with_suffix("/results/*"),
+            [AccessMethod::Delete],
+        )
     }
 
     /// PATCH and DELETE to event invite
@@ -2169,15 +2344,19 @@ mod tests {
                 id: RoomId::from(Uuid::nil()),
                 password: None,
                 waiting_room: false,
+                locale: None,
                 sip_tel: None,
                 sip_uri: None,
                 sip_id: None,
                 sip_password: None,
             },
+            external_meeting_url: None,
             invitees_truncated: false,
             invitees: vec![EventInvitee {
                 profile: EventInviteeProfile::Registered(user_profile),
                 status: EventInviteStatus::Accepted,
+                comment: None,
+                responded_at: None,
             }],
             is_time_independent: false,
             is_all_day: Some(false),
@@ -2288,15 +2467,19 @@ mod tests {
                 id: RoomId::from(Uuid::nil()),
                 password: None,
                 waiting_room: false,
+                locale: None,
                 sip_tel: None,
                 sip_uri: None,
                 sip_id: None,
                 sip_password: None,
             },
+            external_meeting_url: None,
             invitees_truncated: false,
             invitees: vec![EventInvitee {
                 profile: EventInviteeProfile::Registered(user_profile),
                 status: EventInviteStatus::Accepted,
+                comment: None,
+                responded_at: None,
             }],
             is_time_independent: true,
             is_all_day: None,
@@ -2456,4 +2639,60 @@ mod tests {
             }
         );
     }
+
+    fn berlin(year: i32, month: u32, day: u32, hour: u32) -> DateTimeTz {
+        DateTimeTz {
+            datetime: Tz::Europe__Berlin
+                .with_ymd_and_hms(year, month, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc),
+            timezone: TimeZone::from(Tz::Europe__Berlin),
+        }
+    }
+
+    #[test]
+    fn recurrence_keeps_wall_clock_time_across_dst() {
+        // The week of the change from CET to CEST
+        let occurrences: Vec<DateTime<Utc>> =
+            recurrence_set(berlin(2023, 3, 20, 10), "RRULE:FREQ=WEEKLY;COUNT=3")
+                .unwrap()
+                .into_iter()
+                .map(|dt| dt.with_timezone(&Utc))
+                .collect();
+
+        assert_eq!(
+            occurrences,
+            vec![
+                Utc.with_ymd_and_hms(2023, 3, 20, 9, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2023, 3, 27, 8, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2023, 4, 3, 8, 0, 0).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn last_occurrence_of_recurring_event() {
+        let (duration_secs, ends_at, _) = parse_event_dt_params(
+            false,
+            berlin(2023, 3, 20, 10),
+            berlin(2023, 3, 20, 11),
+            &Some("RRULE:FREQ=WEEKLY;COUNT=2".into()),
+        )
+        .unwrap();
+
+        assert_eq!(duration_secs, Some(3600));
+        assert_eq!(
+            ends_at.with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2023, 3, 27, 8, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn external_meeting_urls() {
+        assert!(validate_external_meeting_url("https://meet.jit.si/weekly-meeting").is_ok());
+        assert!(validate_external_meeting_url("http://teams.example.org/l/meetup-join/1").is_ok());
+        assert!(validate_external_meeting_url("javascript:alert(1)").is_err());
+        assert!(validate_external_meeting_url("ftp://example.org/meeting").is_err());
+        assert!(validate_external_meeting_url("meet.jit.si/weekly-meeting").is_err());
+    }
 }
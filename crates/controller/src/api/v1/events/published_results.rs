@@ -0,0 +1,377 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Final results of polls and legal votes published to the page of an event
+//!
+//! Legal votes are published by the editors of the event, polls are published by the `polls` signaling module when
+//! they were started with `publish_results`. Stakeholders who did not attend the meeting can read the tally with the
+//! public link of the event, which contains a token signed with the secret of `[published_results]`. The token is bound
+//! to a nonce of the event, the editors revoke all links issued before by replacing the nonce.
+use super::{ApiResponse, DefaultApiResult};
+use crate::api::v1::legal_vote::parse_protocol;
+use crate::api::v1::response::{ApiError, NoContent};
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{delete, get, post};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::events::published_results::{
+    EventPublishedResult, EventResultsToken, NewEventPublishedResult, PublishedResultId,
+    ResultEntry,
+};
+use db_storage::events::Event;
+use db_storage::legal_votes::{LegalVote, LegalVoteId};
+use db_storage::users::User;
+use db_storage::utils::Jsonb;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use types::core::EventId;
+use uuid::Uuid;
+
+/// Results published to an event
+#[derive(Debug, Serialize)]
+pub struct PublishedResultResource {
+    pub id: PublishedResultId,
+    pub published_at: DateTime<Utc>,
+    /// Topic of the poll or name of the legal vote
+    pub title: String,
+    pub results: Vec<ResultEntry>,
+    /// Set if the results are of a legal vote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legal_vote_id: Option<LegalVoteId>,
+}
+
+impl From<EventPublishedResult> for PublishedResultResource {
+    fn from(result: EventPublishedResult) -> Self {
+        Self {
+            id: result.id,
+            published_at: result.published_at,
+            title: result.title,
+            results: result.results.0,
+            legal_vote_id: result.legal_vote_id,
+        }
+    }
+}
+
+/// Results published to an event together with the token of the public link
+#[derive(Debug, Serialize)]
+pub struct EventResultsResource {
+    /// Token to read the results without authentication, unset if public links are not configured
+    pub public_token: Option<String>,
+    pub results: Vec<PublishedResultResource>,
+}
+
+/// Results published to an event as seen by everyone holding the public link
+#[derive(Debug, Serialize)]
+pub struct PublicEventResultsResource {
+    /// Title of the event
+    pub title: String,
+    pub results: Vec<PublishedResultResource>,
+}
+
+/// Request body for the `POST /events/{event_id}/results` endpoint
+#[derive(Debug, Deserialize)]
+pub struct PostEventResultBody {
+    /// Legal vote which has been held in the room of the event
+    pub legal_vote_id: LegalVoteId,
+}
+
+/// Query parameters of the public results endpoint
+#[derive(Debug, Deserialize)]
+pub struct PublicResultsQuery {
+    pub token: String,
+}
+
+fn token_key(secret: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())
+}
+
+fn token_subject(event_id: EventId, nonce: Uuid) -> String {
+    format!("published_results:{event_id}:{nonce}")
+}
+
+/// Returns the token of the public link to the results of the event
+fn public_token(secret: &str, event_id: EventId, nonce: Uuid) -> String {
+    let tag = hmac::sign(
+        &token_key(secret),
+        token_subject(event_id, nonce).as_bytes(),
+    );
+
+    base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+}
+
+/// Returns true if the token has been signed for the event and its current nonce with the secret
+fn verify_public_token(secret: &str, event_id: EventId, nonce: Uuid, token: &str) -> bool {
+    match base64::decode_config(token, base64::URL_SAFE_NO_PAD) {
+        Ok(tag) => hmac::verify(
+            &token_key(secret),
+            token_subject(event_id, nonce).as_bytes(),
+            &tag,
+        )
+        .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// API Endpoint `GET /events/{event_id}/results`
+///
+/// Returns the results published to the event and the token of the public link
+#[get("/events/{event_id}/results")]
+pub async fn get_event_results(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> DefaultApiResult<EventResultsResource> {
+    let settings = settings.load_full();
+    let event_id = event_id.into_inner();
+    let secret = settings
+        .published_results
+        .as_ref()
+        .map(|published_results| published_results.secret.clone());
+
+    let (results, public_token) = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_conn()?;
+
+        // Assert that the event exists
+        let _event = Event::get(&mut conn, event_id)?;
+
+        let results = EventPublishedResult::get_all_for_event(&mut conn, event_id)?;
+
+        let public_token = match secret {
+            Some(secret) => {
+                let token = EventResultsToken::get_or_create(&mut conn, event_id)?;
+
+                Some(public_token(&secret, event_id, token.nonce))
+            }
+            None => None,
+        };
+
+        Ok((results, public_token))
+    })
+    .await??;
+
+    Ok(ApiResponse::new(EventResultsResource {
+        public_token,
+        results: results.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// API Endpoint `POST /events/{event_id}/results`
+///
+/// Publish the final tally of a legal vote held in the room of the event
+///
+/// Returns 422 Unprocessable Entity if the vote has not been held in the room of the event or has no valid results
+/// and 409 Conflict if the vote has already been published.
+#[post("/events/{event_id}/results")]
+pub async fn publish_event_result(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    event_id: Path<EventId>,
+    body: Json<PostEventResultBody>,
+) -> DefaultApiResult<PublishedResultResource> {
+    let event_id = event_id.into_inner();
+    let legal_vote_id = body.into_inner().legal_vote_id;
+
+    let result = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let event = Event::get(&mut conn, event_id)?;
+        let legal_vote = LegalVote::get(&mut conn, legal_vote_id)?;
+
+        if legal_vote.room != Some(event.room) {
+            return Err(ApiError::unprocessable_entity()
+                .with_code("legal_vote_not_in_room")
+                .with_message("The legal vote has not been held in the room of the event"));
+        }
+
+        let details = parse_protocol(&mut conn, legal_vote.protocol).ok();
+
+        let (title, tally) = match details.as_ref().and_then(|details| {
+            details
+                .final_tally()
+                .map(|tally| (details.settings.name.clone(), *tally))
+        }) {
+            Some(final_tally) => final_tally,
+            None => {
+                return Err(ApiError::unprocessable_entity()
+                    .with_code("no_final_results")
+                    .with_message("The legal vote has no valid final results"))
+            }
+        };
+
+        let mut results = vec![
+            ResultEntry {
+                option: "yes".into(),
+                count: tally.yes,
+            },
+            ResultEntry {
+                option: "no".into(),
+                count: tally.no,
+            },
+        ];
+
+        if let Some(abstain) = tally.abstain {
+            results.push(ResultEntry {
+                option: "abstain".into(),
+                count: abstain,
+            });
+        }
+
+        NewEventPublishedResult {
+            event_id,
+            published_by: current_user.id,
+            title,
+            results: Jsonb(results),
+            legal_vote_id: Some(legal_vote_id),
+            tenant_id: event.tenant_id,
+        }
+        .try_insert(&mut conn)?
+        .ok_or_else(|| {
+            ApiError::conflict()
+                .with_code("already_published")
+                .with_message("The results of the legal vote have already been published")
+        })
+    })
+    .await??;
+
+    Ok(ApiResponse::new(result.into()))
+}
+
+/// API Endpoint `DELETE /events/{event_id}/results/{result_id}`
+///
+/// Remove published results from the event
+#[delete("/events/{event_id}/results/{result_id}")]
+pub async fn delete_event_result(
+    db: Data<Db>,
+    path: Path<(EventId, PublishedResultId)>,
+) -> Result<NoContent, ApiError> {
+    let (event_id, result_id) = path.into_inner();
+
+    crate::block(move || {
+        EventPublishedResult::delete_by_id(&mut db.get_conn()?, event_id, result_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+/// API Endpoint `DELETE /events/{event_id}/results/public_token`
+///
+/// Revoke the public link to the results of the event. The token of the new link is returned by
+/// `GET /events/{event_id}/results`.
+#[delete("/events/{event_id}/results/public_token")]
+pub async fn revoke_public_token(
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> Result<NoContent, ApiError> {
+    let event_id = event_id.into_inner();
+
+    crate::block(move || -> database::Result<()> {
+        let mut conn = db.get_conn()?;
+
+        // Assert that the event exists
+        let _event = Event::get(&mut conn, event_id)?;
+
+        EventResultsToken::rotate(&mut conn, event_id)?;
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+/// API Endpoint `GET /events/{event_id}/results/public?token=...`
+///
+/// Returns the results published to the event without authentication, if the token of the public link is valid.
+/// Returns 404 Not Found if public links are not configured or the token is invalid or has been revoked.
+#[get("/events/{event_id}/results/public")]
+pub async fn get_public_event_results(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    event_id: Path<EventId>,
+    query: Query<PublicResultsQuery>,
+) -> DefaultApiResult<PublicEventResultsResource> {
+    let settings = settings.load_full();
+    let event_id = event_id.into_inner();
+
+    let secret = settings
+        .published_results
+        .as_ref()
+        .ok_or_else(ApiError::not_found)?
+        .secret
+        .clone();
+    let token = query.into_inner().token;
+
+    let (event, results) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        let is_valid = EventResultsToken::get(&mut conn, event_id)?
+            .map(|results_token| {
+                verify_public_token(&secret, event_id, results_token.nonce, &token)
+            })
+            .unwrap_or_default();
+
+        if !is_valid {
+            return Err(ApiError::not_found());
+        }
+
+        let event = Event::get(&mut conn, event_id)?;
+        let results = EventPublishedResult::get_all_for_event(&mut conn, event_id)?;
+
+        Ok((event, results))
+    })
+    .await??;
+
+    Ok(ApiResponse::new(PublicEventResultsResource {
+        title: event.title,
+        results: results
+            .into_iter()
+            .map(|result| PublishedResultResource {
+                legal_vote_id: None,
+                ..result.into()
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token() {
+        let event_id = EventId::from(Uuid::from_u128(1));
+        let nonce = Uuid::from_u128(10);
+        let token = public_token("secret", event_id, nonce);
+
+        assert!(verify_public_token("secret", event_id, nonce, &token));
+        assert!(!verify_public_token("other", event_id, nonce, &token));
+        assert!(!verify_public_token(
+            "secret",
+            EventId::from(Uuid::from_u128(2)),
+            nonce,
+            &token
+        ));
+        assert!(!verify_public_token(
+            "secret",
+            event_id,
+            nonce,
+            "invalid token"
+        ));
+    }
+
+    #[test]
+    fn rotated_token() {
+        let event_id = EventId::from(Uuid::from_u128(1));
+        let token = public_token("secret", event_id, Uuid::from_u128(10));
+
+        assert!(!verify_public_token(
+            "secret",
+            event_id,
+            Uuid::from_u128(11),
+            &token
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Challenges guests have to solve before they can join a room with an invite code
+//!
+//! Configured with the `guest_challenge` settings. Guests request a challenge with *POST /invite/challenge* and
+//! pass their solution to *POST /rooms/{room_id}/start_invited*. The number of proof of work challenges a client can
+//! request is limited, so clients cannot flood redis with challenges.
+use super::response::{ApiError, NoContent};
+use super::rooms::StartRoomError;
+use crate::api::client_ip::client_ip;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::{GuestChallenge, SharedSettingsActix};
+use actix_web::web::{Data, Json};
+use actix_web::{post, Either, HttpRequest};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use redis_args::ToRedisArgs;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+/// A proof of work challenge which has not been solved yet
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-guest_challenge:challenge={challenge}")]
+struct PendingChallenge<'s> {
+    challenge: &'s str,
+}
+
+/// Number of challenges requested by a client within the lifetime of a challenge
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-guest_challenge:client={client}:challenges")]
+struct RequestedChallenges<'s> {
+    client: &'s str,
+}
+
+/// Stores a new challenge, unless the client requested the maximum number of challenges already
+///
+/// Returns 1 if the challenge has been stored, 0 otherwise.
+const STORE_CHALLENGE: &str = r#"
+local requested = redis.call("INCR", KEYS[1])
+if requested == 1 then
+    redis.call("EXPIRE", KEYS[1], ARGV[1])
+end
+if requested > tonumber(ARGV[2]) then
+    return 0
+end
+redis.call("SET", KEYS[2], ARGV[3], "EX", ARGV[1])
+return 1
+"#;
+
+/// Challenge returned by *POST /invite/challenge*
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Challenge {
+    /// Find a nonce, so the SHA-256 hash of the challenge followed by the nonce has `difficulty` leading zero bits
+    ProofOfWork { challenge: String, difficulty: u8 },
+    /// Solve the captcha with the site key
+    Captcha { site_key: String },
+}
+
+/// Solution of a [`Challenge`], passed when starting a room as guest
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeSolution {
+    ProofOfWork { challenge: String, nonce: String },
+    Captcha { token: String },
+}
+
+/// API Endpoint *POST /invite/challenge*
+///
+/// Returns the challenge guests have to solve before joining a room, or no content if guests do not have to solve a
+/// challenge.
+#[post("/invite/challenge")]
+pub async fn new_challenge(
+    settings: SharedSettingsActix,
+    redis_ctx: Data<RedisConnection>,
+    request: HttpRequest,
+) -> Result<Either<Json<Challenge>, NoContent>, ApiError> {
+    let settings = settings.load_full();
+
+    let challenge = match &settings.guest_challenge {
+        Some(GuestChallenge::ProofOfWork {
+            difficulty,
+            lifetime,
+            max_challenges,
+        }) => {
+            let challenge = base64::encode_config(
+                rand::thread_rng().gen::<[u8; 32]>(),
+                base64::URL_SAFE_NO_PAD,
+            );
+
+            let client = client_ip(&request, &settings.http.trusted_proxies)
+                .map(|ip| ip.to_string())
+                .unwrap_or_default();
+
+            let mut redis_conn = (**redis_ctx).clone();
+
+            let stored = store_challenge(
+                &mut redis_conn,
+                &client,
+                &challenge,
+                *difficulty,
+                lifetime.as_secs().max(1),
+                *max_challenges,
+            )
+            .await?;
+
+            if !stored {
+                return Err(ApiError::too_many_requests()
+                    .with_code("too_many_challenges")
+                    .with_message("Too many challenges requested, try again later"));
+            }
+
+            Challenge::ProofOfWork {
+                challenge,
+                difficulty: *difficulty,
+            }
+        }
+        Some(GuestChallenge::Captcha { site_key, .. }) => Challenge::Captcha {
+            site_key: site_key.clone(),
+        },
+        None => return Ok(Either::Right(NoContent)),
+    };
+
+    Ok(Either::Left(Json(challenge)))
+}
+
+/// Verify the solution of the guest, does nothing if guests do not have to solve a challenge
+pub async fn verify(
+    settings: Option<&GuestChallenge>,
+    redis_conn: &mut RedisConnection,
+    solution: Option<ChallengeSolution>,
+    client_ip: Option<&str>,
+) -> Result<(), ApiError> {
+    let (settings, solution) = match (settings, solution) {
+        (None, _) => return Ok(()),
+        (Some(_), None) => return Err(StartRoomError::ChallengeRequired.into()),
+        (Some(settings), Some(solution)) => (settings, solution),
+    };
+
+    let solved = match (settings, solution) {
+        (
+            GuestChallenge::ProofOfWork { difficulty, .. },
+            ChallengeSolution::ProofOfWork { challenge, nonce },
+        ) => {
+            solves(&challenge, &nonce, *difficulty)
+                && consume_challenge(redis_conn, &challenge).await?
+        }
+        (
+            GuestChallenge::Captcha {
+                verify_url, secret, ..
+            },
+            ChallengeSolution::Captcha { token },
+        ) => verify_captcha(verify_url, secret, &token, client_ip).await?,
+        _ => false,
+    };
+
+    if !solved {
+        return Err(StartRoomError::ChallengeFailed.into());
+    }
+
+    Ok(())
+}
+
+/// Store the challenge, returns false if the client requested too many challenges
+async fn store_challenge(
+    redis_conn: &mut RedisConnection,
+    client: &str,
+    challenge: &str,
+    difficulty: u8,
+    lifetime: u64,
+    max_challenges: u32,
+) -> Result<bool, ApiError> {
+    redis::Script::new(STORE_CHALLENGE)
+        .key(RequestedChallenges { client })
+        .key(PendingChallenge { challenge })
+        .arg(lifetime)
+        .arg(max_challenges)
+        .arg(difficulty)
+        .invoke_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to store guest challenge, {}", e);
+            ApiError::internal()
+        })
+}
+
+/// Remove the pending challenge, returns false if the challenge expired or was already used
+async fn consume_challenge(
+    redis_conn: &mut RedisConnection,
+    challenge: &str,
+) -> Result<bool, ApiError> {
+    let removed: u32 = redis::cmd("DEL")
+        .arg(PendingChallenge { challenge })
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to remove guest challenge, {}", e);
+            ApiError::internal()
+        })?;
+
+    Ok(removed == 1)
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+async fn verify_captcha(
+    verify_url: &Url,
+    secret: &str,
+    token: &str,
+    client_ip: Option<&str>,
+) -> Result<bool, ApiError> {
+    let mut form = vec![("secret", secret), ("response", token)];
+    if let Some(client_ip) = client_ip {
+        form.push(("remoteip", client_ip));
+    }
+
+    let response = CLIENT
+        .post(verify_url.clone())
+        .form(&form)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            log::error!("Failed to verify captcha, {}", e);
+            ApiError::internal()
+        })?;
+
+    let body = response.bytes().await.map_err(|e| {
+        log::error!("Failed to read captcha verification response, {}", e);
+        ApiError::internal()
+    })?;
+
+    let response: SiteVerifyResponse = serde_json::from_slice(&body).map_err(|e| {
+        log::error!("Invalid response when verifying captcha, {}", e);
+        ApiError::internal()
+    })?;
+
+    Ok(response.success)
+}
+
+/// Returns true if the SHA-256 hash of the challenge followed by the nonce has `difficulty` leading zero bits
+fn solves(challenge: &str, nonce: &str, difficulty: u8) -> bool {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(challenge.as_bytes());
+    context.update(nonce.as_bytes());
+
+    leading_zero_bits(context.finish().as_ref()) >= u32::from(difficulty)
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+
+    for byte in hash {
+        bits += byte.leading_zeros();
+
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    bits
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use pretty_assertions::assert_eq;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn challenges_are_limited_per_client() {
+        let mut redis_conn = setup().await;
+
+        for challenge in ["a", "b"] {
+            assert!(
+                store_challenge(&mut redis_conn, "192.0.2.1", challenge, 20, 120, 2)
+                    .await
+                    .unwrap()
+            );
+        }
+
+        assert!(
+            !store_challenge(&mut redis_conn, "192.0.2.1", "c", 20, 120, 2)
+                .await
+                .unwrap()
+        );
+        assert!(
+            store_challenge(&mut redis_conn, "192.0.2.2", "d", 20, 120, 2)
+                .await
+                .unwrap()
+        );
+
+        // Rejected challenges are not stored, stored ones can be used once
+        assert!(!consume_challenge(&mut redis_conn, "c").await.unwrap());
+        assert!(consume_challenge(&mut redis_conn, "a").await.unwrap());
+        assert!(!consume_challenge(&mut redis_conn, "a").await.unwrap());
+    }
+
+    #[test]
+    fn count_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x10, 0xff]), 20);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn proof_of_work() {
+        let challenge = "rO0ABXQAEWd1ZXN0IGNoYWxsZW5nZQ";
+
+        let nonce = (0u32..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| solves(challenge, nonce, 8))
+            .unwrap();
+
+        assert!(solves(challenge, &nonce, 8));
+        assert!(!solves(challenge, "not a solution", 255));
+    }
+}
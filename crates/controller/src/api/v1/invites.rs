@@ -3,13 +3,15 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 //! Contains invite related REST endpoints.
+use super::brute_force;
 use super::response::{ApiError, NoContent};
 use super::DefaultApiResult;
 use crate::api::v1::users::PublicUserProfile;
 use crate::api::v1::{ApiResponse, PagePaginationQuery};
+use crate::redis_wrapper::RedisConnection;
 use crate::settings::SharedSettingsActix;
 use actix_web::web::{Data, Json, Path, Query, ReqData};
-use actix_web::{delete, get, post, put};
+use actix_web::{delete, get, post, put, HttpRequest};
 use chrono::{DateTime, Utc};
 use database::{DatabaseError, Db};
 use db_storage::invites::{Invite, NewInvite, UpdateInvite};
@@ -114,7 +116,7 @@ pub async fn get_invites(
     let PagePaginationQuery { per_page, page } = pagination.into_inner();
 
     let (invites_with_users, total_invites) = crate::block(move || {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         let room = Room::get(&mut conn, room_id)?;
 
@@ -290,14 +292,26 @@ pub struct CodeVerified {
 /// As the GET request might not be Idempotent this should be the prioritized endpoint to verify invite_codes.
 #[post("/invite/verify")]
 pub async fn verify_invite_code(
+    settings: SharedSettingsActix,
     db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    request: HttpRequest,
     data: Json<VerifyBody>,
 ) -> DefaultApiResult<CodeVerified> {
+    let settings = settings.load_full();
+    let mut redis_conn = (**redis_ctx).clone();
     let data = data.into_inner();
 
     data.validate()?;
 
-    let (invite, room) = crate::block(move || -> database::Result<_> {
+    // Invite codes are guessed by trying different codes, so the attempts are counted per client
+    let client = brute_force::client_subject(&request, &settings.http.trusted_proxies);
+
+    if let Some(client) = &client {
+        brute_force::check(&mut redis_conn, brute_force::Kind::InviteCode, client).await?;
+    }
+
+    let result = crate::block(move || -> database::Result<_> {
         let mut conn = db.get_conn()?;
 
         let invite = Invite::get(&mut conn, data.invite_code)?;
@@ -305,21 +319,41 @@ pub async fn verify_invite_code(
 
         Ok((invite, room))
     })
-    .await??;
-
-    if invite.active {
-        if let Some(expiration) = invite.expiration {
-            if expiration <= Utc::now() {
-                // Do not leak the existence of the invite when it is expired
-                return Err(ApiError::not_found());
+    .await?;
+
+    let (invite, room) = match result {
+        Ok((invite, room)) if invite_is_valid(&invite) => (invite, room),
+        // Do not leak the existence of the invite when it is expired or inactive
+        Ok(_) | Err(DatabaseError::NotFound) => {
+            if let Some(client) = &client {
+                brute_force::record_failure(
+                    &mut redis_conn,
+                    &settings.brute_force_protection,
+                    brute_force::Kind::InviteCode,
+                    client,
+                )
+                .await?;
             }
+
+            return Err(ApiError::not_found());
         }
-        Ok(ApiResponse::new(CodeVerified {
-            room_id: invite.room,
-            password_required: room.password.is_some(),
-        }))
-    } else {
-        // TODO(r.floren) Do we want to return something else here?
-        Err(ApiError::not_found())
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(client) = &client {
+        brute_force::reset(&mut redis_conn, brute_force::Kind::InviteCode, client).await?;
     }
+
+    Ok(ApiResponse::new(CodeVerified {
+        room_id: invite.room,
+        password_required: room.password.is_some(),
+    }))
+}
+
+pub(crate) fn invite_is_valid(invite: &Invite) -> bool {
+    invite.active
+        && invite
+            .expiration
+            .map(|expiration| expiration > Utc::now())
+            .unwrap_or(true)
 }
@@ -3,24 +3,37 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use super::response::error::ApiError;
-use super::{ApiResponse, PagePaginationQuery};
-use actix_web::get;
+use super::response::NoContent;
+use super::{ApiResponse, DefaultApiResult, PagePaginationQuery};
+use crate::storage::{self, ObjectStorage};
+use actix_http::StatusCode;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{delete, get, post, HttpResponse};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use database::{Db, DbConnection};
+use database::{Db, DbConnection, OptionalExt};
+use db_storage::assets::{Asset, AssetScanStatus};
+use db_storage::legal_votes::scheduled::{
+    NewScheduledLegalVote, ScheduledLegalVote, ScheduledLegalVoteId,
+};
 use db_storage::legal_votes::types::protocol::v1::{self, VoteEvent};
 use db_storage::legal_votes::types::protocol::{self, Protocol};
 use db_storage::legal_votes::types::{
-    CancelReason, FinalResults, Invalid, Parameters, Tally, UserParameters, VoteKind, VoteOption,
+    CancelReason, FinalResults, Invalid, Parameters, ScheduledParameters, Tally, UserParameters,
+    VoteKind, VoteOption,
 };
 use db_storage::legal_votes::{LegalVote, LegalVoteId};
+use db_storage::rooms::Room;
 use db_storage::users::User;
+use db_storage::utils::Jsonb;
 use kustos::prelude::AccessMethod;
 use kustos::{AccessibleResources, Authz};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 use std::collections::HashMap;
-use types::core::RoomId;
+use types::core::{RoomId, UserId};
+use validator::Validate;
 
 /// Wrapper struct to display invalid protocols to the API caller
 #[derive(Debug, Serialize)]
@@ -59,10 +72,23 @@ pub struct LegalVoteDetails {
     pub settings: Settings,
     /// A list of participants that voted on the legal vote
     pub voters: Option<Vec<Voter>>,
+    /// The delegations of votes to proxies, in the order they were made
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub delegations: Vec<Delegation>,
     /// The results of the legal vote
     pub vote_result: VoteResult,
 }
 
+impl LegalVoteDetails {
+    /// The final tally of the vote, unset if the vote has been canceled or the results are invalid
+    pub(crate) fn final_tally(&self) -> Option<&Tally> {
+        match &self.vote_result {
+            VoteResult::Success(success) => Some(&success.tally),
+            VoteResult::Failed(_) => None,
+        }
+    }
+}
+
 /// Settings of a legal vote
 #[derive(Debug, Serialize)]
 pub struct Settings {
@@ -89,6 +115,12 @@ pub struct Settings {
     /// The vote will stop when the duration (in seconds) has passed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration: Option<u64>,
+    /// The time the vote expires, set if the vote has a duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    /// The time the vote actually got stopped or canceled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
 }
 
 /// Represents a participant in a legal vote
@@ -116,6 +148,18 @@ pub struct Voter {
     participant: ParticipantInfo,
     /// The chosen vote option
     vote_option: VoteOption,
+    /// The proxy that cast the vote on behalf of the participant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cast_by: Option<ParticipantInfo>,
+}
+
+/// A participant that delegated their vote to a proxy
+#[derive(Debug, Serialize)]
+pub struct Delegation {
+    /// The participant that delegated their vote
+    delegator: ParticipantInfo,
+    /// The participant that casts the vote on behalf of the delegator
+    proxy: ParticipantInfo,
 }
 
 /// The results of a legal vote
@@ -182,7 +226,7 @@ pub async fn get_all_for_room(
         .await?;
 
     let (legal_votes, count) = crate::block(move || -> database::Result<_> {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         let (legal_votes, count) = match accessible_legal_votes {
             AccessibleResources::List(vote_ids) => LegalVote::get_for_room_by_ids_paginated(
@@ -234,7 +278,7 @@ pub async fn get_all(
         .await?;
 
     let (legal_votes, count) = crate::block(move || -> database::Result<_> {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         let (legal_votes, count) = match accessible_legal_votes {
             AccessibleResources::List(vote_ids) => {
@@ -279,21 +323,10 @@ pub async fn get_specific(
 ) -> Result<Json<LegalVoteEntry>, ApiError> {
     let legal_vote_id = legal_vote_id.into_inner();
 
-    let accessible_legal_votes: AccessibleResources<LegalVoteId> = authz
-        .get_accessible_resources_for_user(current_user.id, AccessMethod::Get)
-        .await?;
-
-    match accessible_legal_votes {
-        kustos::AccessibleResources::List(vote_ids) => {
-            if !vote_ids.contains(&legal_vote_id) {
-                return Err(ApiError::forbidden());
-            }
-        }
-        kustos::AccessibleResources::All => (),
-    }
+    check_legal_vote_access(&authz, &current_user, legal_vote_id).await?;
 
     let legal_vote_detailed = crate::block(move || -> database::Result<_> {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         let legal_vote = LegalVote::get(&mut conn, legal_vote_id)?;
 
@@ -315,7 +348,224 @@ pub async fn get_specific(
     Ok(Json(legal_vote_detailed))
 }
 
-fn parse_protocol(
+/// The protocol of a legal vote as it is stored
+#[derive(Debug, Serialize)]
+pub struct LegalVoteProtocol {
+    pub legal_vote_id: LegalVoteId,
+    /// Version of the format of the entries
+    pub version: u8,
+    /// The events of the vote in the order they happened
+    pub entries: Box<RawValue>,
+}
+
+/// API Endpoint *GET /legal_votes/{legal_vote_id}/protocol*
+///
+/// Returns the stored protocol of the specified legal vote as [`LegalVoteProtocol`], for archiving the vote in
+/// external systems
+#[get("/legal_votes/{legal_vote_id}/protocol")]
+pub async fn get_protocol(
+    db: Data<Db>,
+    authz: Data<Authz>,
+    legal_vote_id: Path<LegalVoteId>,
+    current_user: ReqData<User>,
+) -> Result<Json<LegalVoteProtocol>, ApiError> {
+    let legal_vote_id = legal_vote_id.into_inner();
+
+    check_legal_vote_access(&authz, &current_user, legal_vote_id).await?;
+
+    let legal_vote =
+        crate::block(move || LegalVote::get(&mut db.get_read_conn()?, legal_vote_id)).await??;
+
+    Ok(Json(LegalVoteProtocol {
+        legal_vote_id: legal_vote.id,
+        version: legal_vote.protocol.version,
+        entries: legal_vote.protocol.entries,
+    }))
+}
+
+/// API Endpoint *GET /legal_votes/{legal_vote_id}/pdf*
+///
+/// Returns the PDF of the protocol of the specified legal vote. Returns 404 Not Found if no PDF has been stored for
+/// the vote.
+#[get("/legal_votes/{legal_vote_id}/pdf")]
+pub async fn get_protocol_pdf(
+    db: Data<Db>,
+    authz: Data<Authz>,
+    storage: Data<ObjectStorage>,
+    legal_vote_id: Path<LegalVoteId>,
+    current_user: ReqData<User>,
+) -> Result<HttpResponse, ApiError> {
+    let legal_vote_id = legal_vote_id.into_inner();
+
+    check_legal_vote_access(&authz, &current_user, legal_vote_id).await?;
+
+    let asset = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_read_conn()?;
+
+        let legal_vote = LegalVote::get(&mut conn, legal_vote_id)?;
+
+        match (legal_vote.room, legal_vote.protocol_asset_id) {
+            (Some(room_id), Some(asset_id)) => Asset::get(&mut conn, asset_id, room_id).optional(),
+            _ => Ok(None),
+        }
+    })
+    .await??
+    .ok_or_else(ApiError::not_found)?;
+
+    if asset.scan_status == AssetScanStatus::Infected {
+        return Err(ApiError::forbidden()
+            .with_code("asset_infected")
+            .with_message("The asset is infected and has been quarantined"));
+    }
+
+    let data = storage::assets::get_asset(&storage, &asset.id).await?;
+
+    Ok(HttpResponse::build(StatusCode::OK)
+        .content_type("application/pdf")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(asset.filename)],
+        })
+        .streaming(data))
+}
+
+/// Return 403 Forbidden if the user has no access to the legal vote
+async fn check_legal_vote_access(
+    authz: &Authz,
+    current_user: &User,
+    legal_vote_id: LegalVoteId,
+) -> Result<(), ApiError> {
+    let accessible_legal_votes: AccessibleResources<LegalVoteId> = authz
+        .get_accessible_resources_for_user(current_user.id, AccessMethod::Get)
+        .await?;
+
+    match accessible_legal_votes {
+        kustos::AccessibleResources::List(vote_ids) => {
+            if !vote_ids.contains(&legal_vote_id) {
+                return Err(ApiError::forbidden());
+            }
+        }
+        kustos::AccessibleResources::All => (),
+    }
+
+    Ok(())
+}
+
+/// A legal vote which was prepared before the meeting
+#[derive(Debug, Serialize)]
+pub struct ScheduledLegalVoteResource {
+    pub id: ScheduledLegalVoteId,
+    pub room_id: RoomId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// Reference to the agenda item of the meeting
+    pub agenda_item: Option<String>,
+    pub parameters: ScheduledParameters,
+    /// The legal vote which was started from this scheduled vote, contains the protocol once the vote is over
+    pub legal_vote_id: Option<LegalVoteId>,
+}
+
+impl From<ScheduledLegalVote> for ScheduledLegalVoteResource {
+    fn from(scheduled_vote: ScheduledLegalVote) -> Self {
+        Self {
+            id: scheduled_vote.id,
+            room_id: scheduled_vote.room_id,
+            created_by: scheduled_vote.created_by,
+            created_at: scheduled_vote.created_at,
+            agenda_item: scheduled_vote.agenda_item,
+            parameters: scheduled_vote.parameters.0,
+            legal_vote_id: scheduled_vote.legal_vote_id,
+        }
+    }
+}
+
+/// Body for *POST /rooms/{room_id}/legal_votes/scheduled*
+#[derive(Debug, Deserialize, Validate)]
+pub struct PostScheduledLegalVoteBody {
+    #[validate(length(max = 255))]
+    pub agenda_item: Option<String>,
+    #[validate]
+    pub parameters: ScheduledParameters,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/legal_votes/scheduled*
+///
+/// Returns a JSON array of the legal votes scheduled for this room
+#[get("/rooms/{room_id}/legal_votes/scheduled")]
+pub async fn get_scheduled_for_room(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+) -> DefaultApiResult<Vec<ScheduledLegalVoteResource>> {
+    let room_id = room_id.into_inner();
+
+    let scheduled_votes = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_read_conn()?;
+
+        ScheduledLegalVote::get_all_for_room(&mut conn, room_id)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(
+        scheduled_votes.into_iter().map(Into::into).collect(),
+    ))
+}
+
+/// API Endpoint *POST /rooms/{room_id}/legal_votes/scheduled*
+///
+/// Schedules a legal vote for this room, which can be started by its id during the meeting
+#[post("/rooms/{room_id}/legal_votes/scheduled")]
+pub async fn new_scheduled(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+    current_user: ReqData<User>,
+    body: Json<PostScheduledLegalVoteBody>,
+) -> DefaultApiResult<ScheduledLegalVoteResource> {
+    let room_id = room_id.into_inner();
+    let current_user_id = current_user.id;
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let scheduled_vote = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_conn()?;
+
+        let room = Room::get(&mut conn, room_id)?;
+
+        NewScheduledLegalVote {
+            room_id: room.id,
+            created_by: current_user_id,
+            agenda_item: body.agenda_item,
+            parameters: Jsonb(body.parameters),
+            tenant_id: room.tenant_id,
+        }
+        .insert(&mut conn)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(scheduled_vote.into()))
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/legal_votes/scheduled/{scheduled_vote_id}*
+///
+/// Deletes a scheduled legal vote. Legal votes which were already started from it are kept.
+#[delete("/rooms/{room_id}/legal_votes/scheduled/{scheduled_vote_id}")]
+pub async fn delete_scheduled(
+    db: Data<Db>,
+    path: Path<(RoomId, ScheduledLegalVoteId)>,
+) -> Result<NoContent, ApiError> {
+    let (room_id, scheduled_vote_id) = path.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        ScheduledLegalVote::delete_by_id(&mut conn, room_id, scheduled_vote_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+pub(crate) fn parse_protocol(
     conn: &mut DbConnection,
     protocol: Protocol,
 ) -> Result<LegalVoteDetails, ProtocolError> {
@@ -350,13 +600,19 @@ fn parse_v1_entries(
     let mut stop_kind = None;
     let mut final_results = None;
     let mut cancel = None;
+    let mut end_time = None;
 
     let mut raw_voters = HashMap::new();
     let mut user_ids = vec![];
 
+    let mut raw_delegations = vec![];
+    let mut proxies = HashMap::new();
+
     for entry in entries {
         match entry.event {
             VoteEvent::Start(start) => {
+                let deadline = start.parameters.deadline();
+
                 let Parameters {
                     initiator_id: _,
                     legal_vote_id: _,
@@ -399,15 +655,23 @@ fn parse_v1_entries(
                     enable_abstain,
                     auto_close,
                     duration,
+                    deadline,
+                    end_time: None,
                 });
             }
             VoteEvent::Vote(vote) => {
                 if let Some(user_info) = vote.user_info {
                     user_ids.push(user_info.issuer);
                     raw_voters.insert(user_info.issuer, vote.option);
+
+                    if let Some(proxy) = vote.proxy {
+                        proxies.insert(user_info.issuer, proxy);
+                    }
                 }
             }
+            VoteEvent::Delegation(delegation) => raw_delegations.push(delegation),
             VoteEvent::Stop(kind) => {
+                end_time = entry.timestamp;
                 stop_kind = Some(match kind {
                     protocol::v1::StopKind::Auto => StopKind::Auto,
                     protocol::v1::StopKind::Expired => StopKind::Expired,
@@ -429,7 +693,10 @@ fn parse_v1_entries(
             VoteEvent::FinalResults(results) => {
                 final_results = Some(results);
             }
-            VoteEvent::Cancel(c) => cancel = Some(c),
+            VoteEvent::Cancel(c) => {
+                end_time = entry.timestamp;
+                cancel = Some(c);
+            }
         }
     }
 
@@ -478,10 +745,39 @@ fn parse_v1_entries(
         return Err(ProtocolError::InternalError);
     }
 
-    let settings = settings.ok_or_else(|| {
+    let mut settings = settings.ok_or_else(|| {
         log::error!("Missing settings in legal vote protocol");
         ProtocolError::InvalidProtocol
     })?;
+    settings.end_time = end_time;
+
+    let delegate_ids: Vec<UserId> = raw_delegations
+        .iter()
+        .flat_map(|delegation| [delegation.delegator, delegation.proxy])
+        .chain(proxies.values().copied())
+        .collect();
+
+    let delegates: HashMap<UserId, ParticipantInfo> = User::get_all_by_ids(conn, &delegate_ids)
+        .map_err(|e| {
+            log::error!(
+                "Failed to get delegates by id while parsing legal vote protocol {}",
+                e
+            );
+            ProtocolError::InternalError
+        })?
+        .into_iter()
+        .map(|user| (user.id, user.into()))
+        .collect();
+
+    let delegations = raw_delegations
+        .into_iter()
+        .map(|delegation| {
+            Ok(Delegation {
+                delegator: resolve_delegate(&delegates, delegation.delegator)?,
+                proxy: resolve_delegate(&delegates, delegation.proxy)?,
+            })
+        })
+        .collect::<Result<Vec<_>, ProtocolError>>()?;
 
     let voters = match settings.kind {
         VoteKind::Pseudonymous => {
@@ -508,9 +804,15 @@ fn parse_v1_entries(
                     lastname: user.lastname,
                     email: user.email,
                 };
+                let cast_by = proxies
+                    .get(&user.id)
+                    .map(|proxy| resolve_delegate(&delegates, *proxy))
+                    .transpose()?;
+
                 voters.push(Voter {
                     participant,
                     vote_option,
+                    cast_by,
                 });
             }
 
@@ -521,10 +823,21 @@ fn parse_v1_entries(
     Ok(LegalVoteDetails {
         settings,
         voters,
+        delegations,
         vote_result,
     })
 }
 
+fn resolve_delegate(
+    delegates: &HashMap<UserId, ParticipantInfo>,
+    user_id: UserId,
+) -> Result<ParticipantInfo, ProtocolError> {
+    delegates.get(&user_id).cloned().ok_or_else(|| {
+        log::error!("Could not resolve delegate in legal vote protocol");
+        ProtocolError::InternalError
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -551,6 +864,8 @@ mod test {
             enable_abstain: false,
             auto_close: false,
             duration: Some(60),
+            deadline: Some(Utc.with_ymd_and_hms(1970, 1, 1, 0, 1, 0).unwrap()),
+            end_time: Some(Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 30).unwrap()),
         };
 
         assert_eq_json!(
@@ -570,6 +885,8 @@ mod test {
                 "enable_abstain": false,
                 "auto_close": false,
                 "duration": 60,
+                "deadline": "1970-01-01T00:01:00Z",
+                "end_time": "1970-01-01T00:00:30Z",
             }
         );
     }
@@ -593,6 +910,8 @@ mod test {
             enable_abstain: false,
             auto_close: false,
             duration: None,
+            deadline: None,
+            end_time: None,
         };
 
         assert_eq_json!(
@@ -635,11 +954,15 @@ mod test {
                     enable_abstain: false,
                     auto_close: false,
                     duration: Some(60),
+                    deadline: None,
+                    end_time: None,
                 },
                 voters: Some(vec![Voter {
                     participant: test_participant.clone(),
                     vote_option: VoteOption::Yes,
+                    cast_by: None,
                 }]),
+                delegations: vec![],
                 vote_result: VoteResult::Success(Success {
                     stop_kind: StopKind::ByParticipant(test_participant),
                     tally: Tally {
@@ -714,11 +1037,15 @@ mod test {
                     enable_abstain: false,
                     auto_close: false,
                     duration: None,
+                    deadline: None,
+                    end_time: None,
                 },
                 voters: Some(vec![Voter {
                     participant: test_participant.clone(),
                     vote_option: VoteOption::Yes,
+                    cast_by: None,
                 }]),
+                delegations: vec![],
                 vote_result: VoteResult::Failed(FailReason::Canceled(CancelInfo {
                     canceled_by: test_participant,
                     reason: CancelReason::Custom("Some custom reason".into()),
@@ -788,11 +1115,15 @@ mod test {
                     enable_abstain: false,
                     auto_close: false,
                     duration: Some(60),
+                    deadline: None,
+                    end_time: None,
                 },
                 voters: Some(vec![Voter {
                     participant: test_participant,
                     vote_option: VoteOption::Yes,
+                    cast_by: None,
                 }]),
+                delegations: vec![],
                 vote_result: VoteResult::Failed(FailReason::InvalidResults(
                     Invalid::VoteCountInconsistent,
                 )),
@@ -833,4 +1164,119 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn delegated_legal_vote_entry() {
+        let delegator = ParticipantInfo {
+            firstname: "test".into(),
+            lastname: "tester".into(),
+            email: "test.tester@heinlein-video.de".into(),
+        };
+        let proxy = ParticipantInfo {
+            firstname: "proxy".into(),
+            lastname: "tester".into(),
+            email: "proxy.tester@heinlein-video.de".into(),
+        };
+
+        let legal_vote_entry = LegalVoteEntry {
+            legal_vote_id: LegalVoteId::from(Uuid::from_u128(1)),
+            protocol_result: ProtocolResult::Ok(LegalVoteDetails {
+                settings: Settings {
+                    created_by: proxy.clone(),
+                    start_time: Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap(),
+                    kind: VoteKind::RollCall,
+                    max_votes: 2,
+                    name: "Test Vote".into(),
+                    subtitle: None,
+                    topic: None,
+                    enable_abstain: false,
+                    auto_close: false,
+                    duration: None,
+                    deadline: None,
+                    end_time: None,
+                },
+                voters: Some(vec![
+                    Voter {
+                        participant: delegator.clone(),
+                        vote_option: VoteOption::No,
+                        cast_by: Some(proxy.clone()),
+                    },
+                    Voter {
+                        participant: proxy.clone(),
+                        vote_option: VoteOption::Yes,
+                        cast_by: None,
+                    },
+                ]),
+                delegations: vec![Delegation {
+                    delegator,
+                    proxy: proxy.clone(),
+                }],
+                vote_result: VoteResult::Success(Success {
+                    stop_kind: StopKind::Auto,
+                    tally: Tally {
+                        yes: 1,
+                        no: 1,
+                        abstain: None,
+                    },
+                }),
+            }),
+        };
+
+        assert_eq_json!(
+            legal_vote_entry,
+            {
+                "kind": "roll_call",
+                "legal_vote_id": "00000000-0000-0000-0000-000000000001",
+                "created_by": {
+                  "firstname": "proxy",
+                  "lastname": "tester",
+                  "email": "proxy.tester@heinlein-video.de"
+                },
+                "start_time": "1970-01-01T00:00:00Z",
+                "max_votes": 2,
+                "name": "Test Vote",
+                "enable_abstain": false,
+                "auto_close": false,
+                "voters": [
+                  {
+                    "firstname": "test",
+                    "lastname": "tester",
+                    "email": "test.tester@heinlein-video.de",
+                    "vote_option": "no",
+                    "cast_by": {
+                      "firstname": "proxy",
+                      "lastname": "tester",
+                      "email": "proxy.tester@heinlein-video.de"
+                    }
+                  },
+                  {
+                    "firstname": "proxy",
+                    "lastname": "tester",
+                    "email": "proxy.tester@heinlein-video.de",
+                    "vote_option": "yes"
+                  }
+                ],
+                "delegations": [
+                  {
+                    "delegator": {
+                      "firstname": "test",
+                      "lastname": "tester",
+                      "email": "test.tester@heinlein-video.de"
+                    },
+                    "proxy": {
+                      "firstname": "proxy",
+                      "lastname": "tester",
+                      "email": "proxy.tester@heinlein-video.de"
+                    }
+                  }
+                ],
+                "vote_result": {
+                  "status": "success",
+                  "stop_kind": "auto",
+                  "yes": 1,
+                  "no": 1
+                }
+              }
+        );
+    }
 }
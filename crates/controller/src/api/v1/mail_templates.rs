@@ -0,0 +1,191 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Mail templates of tenants for administrators
+//!
+//! Tenants can replace the built-in templates of the mails sent to the invitees of events. The variables available in
+//! the templates are listed in [`crate::services::mail_templates`].
+//!
+//! The endpoints are only accessible by service accounts with the `opentalk-mail-templates` realm role.
+use super::response::{ApiError, NoContent};
+use super::services::RequiredRealmRole;
+use crate::services::mail_templates::{render_mail, sample_mail_task};
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, post, put};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::mail_templates::{MailTemplate, MailTemplateKind};
+use db_storage::tenants::Tenant;
+use mail_worker_proto::v1::CustomMail;
+use serde::{Deserialize, Serialize};
+use types::core::TenantId;
+use validator::Validate;
+
+const REQUIRED_MAIL_TEMPLATES_ROLE: &str = "opentalk-mail-templates";
+
+/// Mail template of a tenant
+#[derive(Debug, Serialize)]
+pub struct MailTemplateResource {
+    pub kind: MailTemplateKind,
+    pub subject: String,
+    pub body: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<MailTemplate> for MailTemplateResource {
+    fn from(template: MailTemplate) -> Self {
+        Self {
+            kind: template.kind,
+            subject: template.subject,
+            body: template.body,
+            updated_at: template.updated_at,
+        }
+    }
+}
+
+/// API request parameters to replace a mail template, also used to preview a template
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutMailTemplate {
+    #[validate(length(min = 1, max = 255))]
+    pub subject: String,
+    /// HTML body of the mail
+    #[validate(length(min = 1, max = 65536))]
+    pub body: String,
+}
+
+/// Mail rendered with example values
+#[derive(Debug, Serialize)]
+pub struct MailPreview {
+    pub subject: String,
+    pub body: String,
+}
+
+/// Render the template with example values, returns 400 Bad Request if it contains syntax errors
+fn preview(
+    tenant_id: TenantId,
+    kind: MailTemplateKind,
+    template: &PutMailTemplate,
+) -> Result<CustomMail, ApiError> {
+    let template = MailTemplate {
+        tenant_id,
+        kind,
+        subject: template.subject.clone(),
+        body: template.body.clone(),
+        updated_at: Utc::now(),
+    };
+
+    render_mail(&template, &sample_mail_task(kind)).map_err(|e| {
+        ApiError::bad_request()
+            .with_code("invalid_template")
+            .with_message(e.to_string())
+    })
+}
+
+/// API Endpoint *GET /mail_templates/{tenant_id}*
+///
+/// Returns the mail templates of the tenant, kinds without a template use the built-in template
+#[get("/{tenant_id}")]
+pub async fn get_all(
+    db: Data<Db>,
+    tenant_id: Path<TenantId>,
+) -> Result<Json<Vec<MailTemplateResource>>, ApiError> {
+    let tenant_id = tenant_id.into_inner();
+
+    let templates = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        Tenant::get(&mut conn, tenant_id)?;
+
+        MailTemplate::get_all_for_tenant(&mut conn, tenant_id)
+    })
+    .await??;
+
+    Ok(Json(templates.into_iter().map(Into::into).collect()))
+}
+
+/// API Endpoint *PUT /mail_templates/{tenant_id}/{kind}*
+///
+/// Replaces the mail template of the tenant with the provided [`PutMailTemplate`]
+///
+/// Returns the new template.
+#[put("/{tenant_id}/{kind}")]
+pub async fn put(
+    db: Data<Db>,
+    path: Path<(TenantId, MailTemplateKind)>,
+    body: Json<PutMailTemplate>,
+) -> Result<Json<MailTemplateResource>, ApiError> {
+    let (tenant_id, kind) = path.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    // Reject templates which cannot be rendered
+    preview(tenant_id, kind, &body)?;
+
+    let template = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Tenant::get(&mut conn, tenant_id)?;
+
+        MailTemplate {
+            tenant_id,
+            kind,
+            subject: body.subject,
+            body: body.body,
+            updated_at: Utc::now(),
+        }
+        .upsert(&mut conn)
+    })
+    .await??;
+
+    Ok(Json(template.into()))
+}
+
+/// API Endpoint *DELETE /mail_templates/{tenant_id}/{kind}*
+///
+/// Removes the mail template of the tenant, the built-in template is used again
+#[delete("/{tenant_id}/{kind}")]
+pub async fn delete(
+    db: Data<Db>,
+    path: Path<(TenantId, MailTemplateKind)>,
+) -> Result<NoContent, ApiError> {
+    let (tenant_id, kind) = path.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        MailTemplate::delete(&mut conn, tenant_id, kind)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+/// API Endpoint *POST /mail_templates/{tenant_id}/{kind}/preview*
+///
+/// Renders the provided [`PutMailTemplate`] with example values without storing it
+#[post("/{tenant_id}/{kind}/preview")]
+pub async fn post_preview(
+    path: Path<(TenantId, MailTemplateKind)>,
+    body: Json<PutMailTemplate>,
+) -> Result<Json<MailPreview>, ApiError> {
+    let (tenant_id, kind) = path.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let CustomMail { subject, body } = preview(tenant_id, kind, &body)?;
+
+    Ok(Json(MailPreview { subject, body }))
+}
+
+pub fn services() -> impl HttpServiceFactory {
+    actix_web::web::scope("")
+        .wrap(RequiredRealmRole::new(REQUIRED_MAIL_TEMPLATES_ROLE))
+        .service(get_all)
+        .service(put)
+        .service(delete)
+        .service(post_preview)
+}
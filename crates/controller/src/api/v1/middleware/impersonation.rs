@@ -0,0 +1,273 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Read-only impersonation of users for support purposes
+//!
+//! Users in the [`IMPERSONATION_ROLE`] can view the rooms, events and assets of another user of their tenant by
+//! sending the id of the user in the [`IMPERSONATE_USER_HEADER`]. The request is then handled as if it was made by the
+//! impersonated user, after it has been written to the impersonation log. Only `GET` requests to the endpoints in
+//! [`IMPERSONATION_PATHS`] are allowed, the response carries the [`IMPERSONATED_BY_HEADER`] to let the frontend show a
+//! banner.
+//!
+//! Must be wrapped by the [`OidcAuth`](super::user_auth::OidcAuth) middleware, which provides the impersonator.
+use crate::api::v1::response::ApiError;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::web::Data;
+use actix_web::{HttpMessage, ResponseError};
+use core::future::ready;
+use database::Db;
+use db_storage::impersonation_log::NewImpersonationLogEntry;
+use db_storage::users::User;
+use kustos::Authz;
+use std::future::{Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use tracing_actix_web::RequestId;
+use tracing_futures::Instrument;
+use types::core::UserId;
+
+/// Kustos role of the users allowed to impersonate other users
+pub const IMPERSONATION_ROLE: &str = "impersonator";
+
+/// Request header containing the id of the impersonated user
+pub const IMPERSONATE_USER_HEADER: &str = "x-impersonate-user";
+
+/// Response header containing the id of the impersonating user
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
+
+/// Endpoints which can be requested while impersonating a user, including their sub paths
+pub const IMPERSONATION_PATHS: &[&str] = &["/rooms", "/events", "/users/me"];
+
+/// The impersonating user, available as [`ReqData`](actix_web::web::ReqData) of impersonated requests
+#[derive(Debug, Clone)]
+pub struct Impersonation {
+    pub impersonator: User,
+}
+
+/// Middleware factory
+///
+/// Transforms into [`ImpersonationMiddleware`]
+pub struct Impersonate {
+    pub db: Data<Db>,
+    pub authz: Data<Authz>,
+}
+
+impl<S> Transform<S, ServiceRequest> for Impersonate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Transform = ImpersonationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ImpersonationMiddleware {
+            service: Rc::new(service),
+            db: self.db.clone(),
+            authz: self.authz.clone(),
+        }))
+    }
+}
+
+/// Impersonation middleware
+///
+/// Replaces the authenticated user with the impersonated user for requests carrying the
+/// [`IMPERSONATE_USER_HEADER`]. Requests without the header are passed through unchanged.
+pub struct ImpersonationMiddleware<S> {
+    service: Rc<S>,
+    db: Data<Db>,
+    authz: Data<Authz>,
+}
+
+type ResultFuture<O, E> = Pin<Box<dyn Future<Output = Result<O, E>>>>;
+
+impl<S> Service<ServiceRequest> for ImpersonationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = ResultFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        let header = match req.headers().get(IMPERSONATE_USER_HEADER) {
+            Some(header) => header,
+            None => return Box::pin(service.call(req)),
+        };
+
+        let impersonated_id = match header
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<uuid::Uuid>().ok())
+        {
+            Some(id) => UserId::from(id),
+            None => {
+                let error = ApiError::bad_request()
+                    .with_code("invalid_impersonated_user")
+                    .with_message(format!("{IMPERSONATE_USER_HEADER} must contain a user id"));
+
+                return Box::pin(ready(Ok(req.into_response(error.error_response()))));
+            }
+        };
+
+        let db = self.db.clone();
+        let authz = self.authz.clone();
+
+        Box::pin(
+            async move {
+                let impersonator = req.extensions().get::<User>().cloned();
+                let impersonator = match impersonator {
+                    Some(user) => user,
+                    None => {
+                        log::error!("Impersonation middleware used without user authentication");
+                        return Ok(req.into_response(ApiError::internal().error_response()));
+                    }
+                };
+
+                let impersonated =
+                    match check_impersonation(&db, &authz, &req, &impersonator, impersonated_id)
+                        .await
+                    {
+                        Ok(user) => user,
+                        Err(error) => return Ok(req.into_response(error.error_response())),
+                    };
+
+                let impersonator_id = impersonator.id;
+
+                req.extensions_mut()
+                    .insert(kustos::actix_web::User::from(impersonated.id.into_inner()));
+                req.extensions_mut().insert(impersonated);
+                req.extensions_mut().insert(Impersonation { impersonator });
+
+                let mut res = service.call(req).await?;
+
+                res.headers_mut().insert(
+                    HeaderName::from_static(IMPERSONATED_BY_HEADER),
+                    HeaderValue::from_str(&impersonator_id.to_string())?,
+                );
+
+                Ok(res)
+            }
+            .instrument(tracing::trace_span!("ImpersonationMiddleware::async::call")),
+        )
+    }
+}
+
+/// Check if the request may impersonate the user and write it to the impersonation log
+///
+/// Returns the impersonated user
+async fn check_impersonation(
+    db: &Data<Db>,
+    authz: &Authz,
+    req: &ServiceRequest,
+    impersonator: &User,
+    impersonated_id: UserId,
+) -> Result<User, ApiError> {
+    let is_allowed = authz
+        .is_user_in_role(impersonator.id.into_inner(), IMPERSONATION_ROLE)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to check the impersonation role, {}", e);
+            ApiError::internal()
+        })?;
+
+    if !is_allowed {
+        return Err(ApiError::forbidden()
+            .with_code("impersonation_not_allowed")
+            .with_message("Not allowed to impersonate other users"));
+    }
+
+    if !is_read_only_request(req.method(), req.path()) {
+        return Err(ApiError::forbidden()
+            .with_code("impersonation_read_only")
+            .with_message(
+                "Impersonated requests are restricted to viewing rooms, events and assets",
+            ));
+    }
+
+    let db = db.clone();
+    let tenant_id = impersonator.tenant_id;
+    let entry = NewImpersonationLogEntry {
+        impersonator_id: impersonator.id,
+        impersonated_id,
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        request_id: req
+            .extensions()
+            .get::<RequestId>()
+            .map(|request_id| request_id.to_string()),
+        tenant_id,
+    };
+
+    crate::block(move || -> Result<User, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let impersonated = User::get_filtered_by_tenant(&mut conn, tenant_id, impersonated_id)
+            .map_err(|_| {
+                ApiError::not_found()
+                    .with_code("unknown_impersonated_user")
+                    .with_message("The impersonated user does not exist")
+            })?;
+
+        // Never serve an impersonated request which is missing in the log
+        entry.insert(&mut conn)?;
+
+        Ok(impersonated)
+    })
+    .await?
+}
+
+/// Impersonated requests must not change anything
+fn is_read_only_request(method: &Method, path: &str) -> bool {
+    if method != Method::GET && method != Method::HEAD {
+        return false;
+    }
+
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+
+    IMPERSONATION_PATHS.iter().any(|allowed| {
+        path.strip_prefix(allowed)
+            .map(|rest| rest.is_empty() || rest.starts_with('/'))
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_only_requests() {
+        assert!(is_read_only_request(&Method::GET, "/v1/rooms"));
+        assert!(is_read_only_request(
+            &Method::GET,
+            "/v1/rooms/00000000-0000-0000-0000-000000000000/assets"
+        ));
+        assert!(is_read_only_request(&Method::HEAD, "/v1/events"));
+        assert!(is_read_only_request(&Method::GET, "/v1/users/me"));
+
+        assert!(!is_read_only_request(&Method::POST, "/v1/rooms"));
+        assert!(!is_read_only_request(
+            &Method::DELETE,
+            "/v1/events/00000000-0000-0000-0000-000000000000"
+        ));
+        assert!(!is_read_only_request(&Method::GET, "/v1/roomsx"));
+        assert!(!is_read_only_request(&Method::GET, "/v1/users/find"));
+        assert!(!is_read_only_request(&Method::GET, "/v1/contacts"));
+    }
+}
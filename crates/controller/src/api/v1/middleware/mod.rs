@@ -4,6 +4,8 @@
 
 //! Actix middleware implementations
 pub mod headers;
+pub mod impersonation;
 pub mod metrics;
+pub mod request_filter;
 pub mod service_auth;
 pub mod user_auth;
@@ -0,0 +1,394 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Middleware rejecting requests according to the `request_filter` settings
+//!
+//! Requests are rejected if the client is not in the allowlist of the scope matching the path, if they match a deny
+//! rule or if their body exceeds the maximum body size of the scope. The settings are read for every request, so
+//! changes are applied when the settings are reloaded.
+//!
+//! The client address is determined with the trusted proxies, see [`client_ip`](crate::api::client_ip). Paths are
+//! matched in the form the router matches them, so percent-encoded paths cannot bypass a scope or deny rule.
+use crate::api::client_ip::client_ip;
+use crate::api::v1::response::ApiError;
+use crate::metrics::EndpointMetrics;
+use crate::settings::{RequestDenyRule, RequestFilterScope, SharedSettings};
+use actix_http::error::PayloadError;
+use actix_http::header::{CONTENT_LENGTH, USER_AGENT};
+use actix_http::Payload;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, ResponseError};
+use futures::future::{ready, Ready};
+use futures::{Future, FutureExt, StreamExt};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct RequestFilter {
+    settings: SharedSettings,
+    metrics: Arc<EndpointMetrics>,
+}
+
+impl RequestFilter {
+    pub fn new(settings: SharedSettings, metrics: Arc<EndpointMetrics>) -> Self {
+        Self { settings, metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestFilter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestFilterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestFilterMiddleware {
+            service,
+            settings: self.settings.clone(),
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+pub struct RequestFilterMiddleware<S> {
+    service: S,
+    settings: SharedSettings,
+    metrics: Arc<EndpointMetrics>,
+}
+
+/// Reason a request was rejected, used as label of the `web.rejected_requests_count` metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rejection {
+    NotAllowed,
+    Denied,
+    BodyTooLarge,
+}
+
+impl Rejection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotAllowed => "not_allowed",
+            Self::Denied => "denied",
+            Self::BodyTooLarge => "body_too_large",
+        }
+    }
+
+    fn error(&self) -> ApiError {
+        match self {
+            Self::NotAllowed | Self::Denied => ApiError::forbidden(),
+            Self::BodyTooLarge => ApiError::payload_too_large(),
+        }
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RequestFilterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let settings = self.settings.load();
+        let filter = &settings.request_filter;
+
+        let client_ip = client_ip(req.request(), &settings.http.trusted_proxies);
+        // The path with percent-encoded characters decoded the way the router decodes them
+        let path = req.match_info().as_str();
+        let scope = matching_scope(&filter.scopes, path);
+
+        let request = Request {
+            client_ip,
+            path,
+            method: req.method().as_str(),
+            user_agent: req
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok()),
+            content_length: req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok()),
+        };
+
+        if let Some(rejection) = check(&request, scope, &filter.deny) {
+            log::debug!(
+                "Rejected request to {} from {:?}, {}",
+                path,
+                client_ip,
+                rejection.as_str()
+            );
+
+            self.metrics
+                .increment_rejected_requests_count(rejection.as_str());
+
+            let response = req.into_response(rejection.error().error_response());
+            return ready(Ok(response.map_into_right_body())).boxed_local();
+        }
+
+        // Bodies without a content length are limited while they are read
+        if let Some(max_body_size) = scope.and_then(|scope| scope.max_body_size) {
+            limit_payload(&mut req, max_body_size);
+        }
+
+        self.service
+            .call(req)
+            .map(|res| res.map(ServiceResponse::map_into_left_body))
+            .boxed_local()
+    }
+}
+
+/// Properties of a request checked by the filter
+struct Request<'r> {
+    client_ip: Option<IpAddr>,
+    path: &'r str,
+    method: &'r str,
+    user_agent: Option<&'r str>,
+    content_length: Option<usize>,
+}
+
+fn check(
+    request: &Request<'_>,
+    scope: Option<&RequestFilterScope>,
+    deny: &[RequestDenyRule],
+) -> Option<Rejection> {
+    if let Some(scope) = scope {
+        // Clients without an address, e.g. connected through a unix socket, are not in any allowlist
+        let allowed = scope.allowlist.is_empty()
+            || request
+                .client_ip
+                .map(|ip| scope.allowlist.iter().any(|net| net.contains(&ip)))
+                .unwrap_or_default();
+
+        if !allowed {
+            return Some(Rejection::NotAllowed);
+        }
+    }
+
+    if deny.iter().any(|rule| matches_rule(request, rule)) {
+        return Some(Rejection::Denied);
+    }
+
+    match (
+        scope.and_then(|scope| scope.max_body_size),
+        request.content_length,
+    ) {
+        (Some(max_body_size), Some(content_length)) if content_length > max_body_size => {
+            Some(Rejection::BodyTooLarge)
+        }
+        _ => None,
+    }
+}
+
+fn matches_rule(request: &Request<'_>, rule: &RequestDenyRule) -> bool {
+    let networks = rule.networks.is_empty()
+        || request
+            .client_ip
+            .map(|ip| rule.networks.iter().any(|net| net.contains(&ip)))
+            .unwrap_or_default();
+
+    let path = rule
+        .path
+        .as_deref()
+        .map(|prefix| has_path_prefix(request.path, prefix))
+        .unwrap_or(true);
+
+    let method = rule.methods.is_empty()
+        || rule
+            .methods
+            .iter()
+            .any(|method| method.eq_ignore_ascii_case(request.method));
+
+    let user_agent = rule
+        .user_agent
+        .as_deref()
+        .map(|needle| {
+            request
+                .user_agent
+                .map(|user_agent| user_agent.to_lowercase().contains(&needle.to_lowercase()))
+                .unwrap_or_default()
+        })
+        .unwrap_or(true);
+
+    networks && path && method && user_agent
+}
+
+/// The scope with the longest path prefix matching the path
+fn matching_scope<'s>(
+    scopes: &'s [RequestFilterScope],
+    path: &str,
+) -> Option<&'s RequestFilterScope> {
+    scopes
+        .iter()
+        .filter(|scope| has_path_prefix(path, &scope.path))
+        .max_by_key(|scope| scope.path.trim_end_matches('/').len())
+}
+
+/// Returns true if the path starts with all segments of the prefix
+fn has_path_prefix(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Fail reading the body once it exceeds `max_body_size` bytes
+fn limit_payload(req: &mut ServiceRequest, max_body_size: usize) {
+    let mut received = 0;
+
+    let payload = req.take_payload().map(move |chunk| {
+        let chunk = chunk?;
+
+        received += chunk.len();
+        if received > max_body_size {
+            return Err(PayloadError::Overflow);
+        }
+
+        Ok(chunk)
+    });
+
+    req.set_payload(Payload::Stream {
+        payload: payload.boxed_local(),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::client_ip::canonical_ip;
+    use pretty_assertions::assert_eq;
+
+    fn scope(path: &str, allowlist: &[&str], max_body_size: Option<usize>) -> RequestFilterScope {
+        RequestFilterScope {
+            path: path.into(),
+            allowlist: allowlist.iter().map(|net| net.parse().unwrap()).collect(),
+            max_body_size,
+        }
+    }
+
+    fn request<'r>(client_ip: &str, path: &'r str) -> Request<'r> {
+        Request {
+            client_ip: Some(canonical_ip(client_ip.parse().unwrap())),
+            path,
+            method: "GET",
+            user_agent: Some("Mozilla/5.0 BadBot/1.0"),
+            content_length: None,
+        }
+    }
+
+    #[test]
+    fn path_prefix() {
+        assert!(has_path_prefix("/internal", "/internal"));
+        assert!(has_path_prefix("/internal/rooms", "/internal/"));
+        assert!(!has_path_prefix("/internals", "/internal"));
+        assert!(has_path_prefix("/v1/rooms", "/"));
+    }
+
+    #[test]
+    fn percent_encoded_path_is_decoded() {
+        let req = actix_web::test::TestRequest::with_uri("/intern%61l/rooms").to_srv_request();
+
+        assert_eq!(req.match_info().as_str(), "/internal/rooms");
+
+        let scopes = [scope("/internal", &["10.0.0.0/8"], None)];
+
+        assert_eq!(
+            matching_scope(&scopes, req.match_info().as_str()).map(|scope| scope.path.as_str()),
+            Some("/internal")
+        );
+    }
+
+    #[test]
+    fn longest_scope_matches() {
+        let scopes = [
+            scope("/", &[], None),
+            scope("/internal", &["10.0.0.0/8"], None),
+        ];
+
+        assert_eq!(
+            matching_scope(&scopes, "/internal/rooms").map(|scope| scope.path.as_str()),
+            Some("/internal")
+        );
+        assert_eq!(
+            matching_scope(&scopes, "/v1/rooms").map(|scope| scope.path.as_str()),
+            Some("/")
+        );
+    }
+
+    #[test]
+    fn allowlist() {
+        let scope = scope("/internal", &["10.0.0.0/8"], None);
+
+        assert_eq!(
+            check(&request("10.1.2.3", "/internal"), Some(&scope), &[]),
+            None
+        );
+        assert_eq!(
+            check(&request("::ffff:10.1.2.3", "/internal"), Some(&scope), &[]),
+            None
+        );
+        assert_eq!(
+            check(&request("192.168.1.1", "/internal"), Some(&scope), &[]),
+            Some(Rejection::NotAllowed)
+        );
+    }
+
+    #[test]
+    fn deny_rules() {
+        let rule = RequestDenyRule {
+            networks: vec![],
+            path: Some("/v1/users".into()),
+            methods: vec!["get".into()],
+            user_agent: Some("badbot".into()),
+        };
+
+        assert_eq!(
+            check(
+                &request("10.1.2.3", "/v1/users/find"),
+                None,
+                std::slice::from_ref(&rule)
+            ),
+            Some(Rejection::Denied)
+        );
+        assert_eq!(
+            check(
+                &request("10.1.2.3", "/v1/rooms"),
+                None,
+                std::slice::from_ref(&rule)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn body_size() {
+        let scope = scope("/", &[], Some(1024));
+
+        let mut request = request("10.1.2.3", "/v1/rooms");
+        request.content_length = Some(1024);
+        assert_eq!(check(&request, Some(&scope), &[]), None);
+
+        request.content_length = Some(1025);
+        assert_eq!(
+            check(&request, Some(&scope), &[]),
+            Some(Rejection::BodyTooLarge)
+        );
+    }
+}
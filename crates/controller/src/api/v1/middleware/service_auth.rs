@@ -31,6 +31,16 @@ impl RealmRoles {
     }
 }
 
+/// Client ID of the service-account which sent the request
+#[derive(Clone)]
+pub struct ServiceClient(Rc<str>);
+
+impl ServiceClient {
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+}
+
 /// Middleware factory for [`ServiceAuthMiddleware`]
 pub struct ServiceAuth {
     oidc_ctx: Data<OidcContext>,
@@ -63,7 +73,7 @@ where
 
 /// Middleware which extracts and verifies an access-token from the request
 ///
-/// Inserts a `RealmRoles` struct into the request for other services to inspect.
+/// Inserts a `RealmRoles` and a `ServiceClient` struct into the request for other services to inspect.
 pub struct ServiceAuthMiddleware<S> {
     service: Rc<S>,
 
@@ -109,8 +119,9 @@ where
 
         Box::pin(
             async move {
-                let realm_roles = check_access_token(oidc_ctx, access_token).await?;
+                let (realm_roles, client) = check_access_token(oidc_ctx, access_token).await?;
                 req.extensions_mut().insert(realm_roles);
+                req.extensions_mut().insert(client);
                 service.call(req).await
             }
             .instrument(tracing::trace_span!("ServiceAuthMiddleware::async::call")),
@@ -122,7 +133,7 @@ where
 async fn check_access_token(
     oidc_ctx: Data<OidcContext>,
     access_token: AccessToken,
-) -> Result<RealmRoles, ApiError> {
+) -> Result<(RealmRoles, ServiceClient), ApiError> {
     let claims = match oidc_ctx.verify_access_token::<ServiceClaims>(&access_token) {
         Ok(claims) => claims,
         Err(e) => {
@@ -146,7 +157,10 @@ async fn check_access_token(
             .iter_mut()
             .for_each(|role| role.make_ascii_lowercase());
 
-        Ok(RealmRoles(realm_roles.into()))
+        Ok((
+            RealmRoles(realm_roles.into()),
+            ServiceClient(claims.azp.into()),
+        ))
     } else {
         Err(ApiError::unauthorized()
             .with_www_authenticate(AuthenticationError::AccessTokenInactive))
@@ -15,6 +15,7 @@ use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
 use controller_shared::settings::{Settings, SharedSettings, TenantAssignment};
 use core::future::ready;
 use database::Db;
+use db_storage::ldap_sessions::LdapSession;
 use db_storage::tenants::{OidcTenantId, Tenant};
 use db_storage::users::User;
 use openidconnect::AccessToken;
@@ -128,6 +129,12 @@ pub async fn check_access_token(
     oidc_ctx: Data<OidcContext>,
     access_token: AccessToken,
 ) -> Result<(Tenant, User), ApiError> {
+    if settings.ldap.is_some() {
+        if let Some(token_hash) = crate::ldap::session_token_hash(access_token.secret()) {
+            return check_ldap_session(db, token_hash).await;
+        }
+    }
+
     let (oidc_tenant_id, sub) = match oidc_ctx.verify_access_token::<UserClaims>(&access_token) {
         Ok(claims) => {
             // Get the tenant_id depending on the configured assignment
@@ -190,3 +197,76 @@ pub async fn check_access_token(
             .with_www_authenticate(AuthenticationError::AccessTokenInactive))
     }
 }
+
+/// Check the session token of a user which logged in via LDAP
+async fn check_ldap_session(db: Data<Db>, token_hash: Vec<u8>) -> Result<(Tenant, User), ApiError> {
+    crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let (_, user) =
+            LdapSession::get_user_by_token_hash(&mut conn, &token_hash)?.ok_or_else(|| {
+                ApiError::unauthorized().with_www_authenticate(AuthenticationError::SessionExpired)
+            })?;
+
+        let tenant = Tenant::get(&mut conn, user.tenant_id)?;
+
+        Ok((tenant, user))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calendar_sync::CalendarSync;
+    use crate::storage::ObjectStorage;
+    use actix_web::http::StatusCode;
+    use arc_swap::ArcSwap;
+    use db_storage::ldap_sessions::NewLdapSession;
+    use kustos::Authz;
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    #[serial]
+    async fn erased_user_ldap_session_is_rejected() {
+        let db_ctx = test_util::database::DatabaseContext::new(true).await;
+        let user = db_ctx.create_test_user(0, vec![]).unwrap();
+
+        let (_, token_hash) = crate::ldap::new_session_token().unwrap();
+
+        NewLdapSession {
+            token_hash: token_hash.clone(),
+            user_id: user.id,
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+        }
+        .insert(&mut db_ctx.db.get_conn().unwrap())
+        .unwrap();
+
+        let (_, session_user) =
+            check_ldap_session(Data::from(db_ctx.db.clone()), token_hash.clone())
+                .await
+                .unwrap();
+        assert_eq!(session_user.id, user.id);
+
+        let authz = Authz::new(db_ctx.db.clone()).await.unwrap();
+        let settings = Settings::load("../../extra/example.toml").unwrap();
+        let calendar_sync =
+            CalendarSync::new(Arc::new(ArcSwap::from_pointee(settings)), db_ctx.db.clone());
+
+        crate::gdpr::erase_user(
+            db_ctx.db.clone(),
+            &ObjectStorage::broken(),
+            &authz,
+            &calendar_sync,
+            user.id,
+        )
+        .await
+        .unwrap();
+
+        let err = check_ldap_session(Data::from(db_ctx.db.clone()), token_hash)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    }
+}
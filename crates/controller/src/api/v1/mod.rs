@@ -6,38 +6,91 @@
 //!
 //! Current Endpoints. See their respective function:
 //! - `/auth/login` ([post](auth::login))
+//! - `/auth/ldap/login` ([post](auth::ldap_login))
 //! - `/rooms` ([GET](rooms::accessible), [POST](rooms::new))
+//! - `/rooms/public` ([GET](room_directory::search))
 //! - `/rooms/{room_id}` ([GET](rooms::get), [PATCH](rooms::patch))
 //! - `/rooms/{room_id}/start` ([POST](rooms::start))
+//! - `/rooms/{room_id}/media-stats` ([GET](rooms::get_media_stats))
 //! - `/rooms/{room_id}/start_invited` ([POST](rooms::start_invited))
+//! - `/invite/challenge` ([POST](guest_challenge::new_challenge))
 //! - `/rooms/{room_id}/invites ([GET](invites::get_invites), [POST](invites::add_invite))
 //! - `/rooms/{room_id}/invites/{invite_code} ([GET](invites::get_invite), [PUT](invites::update_invite), [DELETE](invites::delete_invite)])
 //! - `/rooms/{room_id}/sip ([GET](sip_configs::get), [PUT](sip_configs::put), [DELETE](sip_configs::delete))
+//! - `/rooms/{room_id}/owners` ([GET](room_owners::get_owners))
+//! - `/rooms/{room_id}/owners/{user_id}` ([PUT](room_owners::add_owner), [DELETE](room_owners::remove_owner))
+//! - `/rooms/{room_id}/transfer_ownership` ([POST](room_owners::transfer_ownership))
+//! - `/rooms/{room_id}/media_settings` ([GET](room_media_settings::get), [PUT](room_media_settings::put), [DELETE](room_media_settings::delete))
+//! - `/rooms/{room_id}/branding` ([GET](room_branding::get), [PUT](room_branding::put), [DELETE](room_branding::delete))
+//! - `/rooms/{room_id}/directory_entry` ([GET](room_directory::get), [PUT](room_directory::put), [DELETE](room_directory::delete))
 //! - `/rooms/{room_id}/legal_votes ([GET](legal_vote::get_all_for_room))
+//! - `/rooms/{room_id}/legal_votes/scheduled ([GET](legal_vote::get_scheduled_for_room), [POST](legal_vote::new_scheduled))
+//! - `/rooms/{room_id}/legal_votes/scheduled/{scheduled_vote_id} ([DELETE](legal_vote::delete_scheduled))
 //! - `/turn` ([GET](turn::get))
+//! - `/turn/check` ([GET](turn::get_check), [POST](turn::post_check))
 //! - `/users/me`([GET](users::get_me), [PATCH](users::patch_me))
 //! - `/users/{user_id}` ([GET](users::get_user))
 //! - `/users/find` ([GET](users::find))
+//! - `/users/me/data-export` ([POST](users::data_export))
+//! - `/users/me/contacts` ([GET](contacts::get_me_contacts), [PUT](contacts::put_me_contacts))
+//! - `/users/me/calendar_links` ([GET](calendar_links::get_calendar_links))
+//! - `/users/me/calendar_links/{provider}` ([PUT](calendar_links::put_calendar_link), [DELETE](calendar_links::delete_calendar_link))
+//! - `/users/me/calendar_links/{provider}/authorize` ([GET](calendar_links::authorize))
+//! - `/users/me/polls` ([GET](poll_templates::get_library_polls), [POST](poll_templates::new_library_poll))
+//! - `/users/me/polls/{poll_id}` ([DELETE](poll_templates::delete_library_poll))
+//! - `/events/{event_id}/polls` ([GET](poll_templates::get_event_polls), [POST](poll_templates::new_event_poll))
+//! - `/events/{event_id}/polls/{poll_id}` ([DELETE](poll_templates::delete_event_poll))
+//! - `/events/{event_id}/results` ([GET](events::published_results::get_event_results), [POST](events::published_results::publish_event_result))
+//! - `/events/{event_id}/results/{result_id}` ([DELETE](events::published_results::delete_event_result))
+//! - `/events/{event_id}/results/public_token` ([DELETE](events::published_results::revoke_public_token))
+//! - `/events/{event_id}/results/public` ([GET](events::published_results::get_public_event_results))
 //! - `/legal_votes` ([GET](legal_vote::get_all))
 //! - `/legal_votes/{legal_vote_id}` ([GET](legal_vote::get_specific))
+//! - `/legal_votes/{legal_vote_id}/protocol` ([GET](legal_vote::get_protocol))
+//! - `/legal_votes/{legal_vote_id}/pdf` ([GET](legal_vote::get_protocol_pdf))
+//! - `/trash` ([GET](trash::get_trash))
+//! - `/trash/rooms/{room_id}/restore` ([POST](trash::restore_room))
+//! - `/trash/events/{event_id}/restore` ([POST](trash::restore_event))
+//! - `/statistics/rooms` ([GET](statistics::get_room_statistics))
+//! - `/room_snapshots/{room_id}` ([GET](room_snapshots::get_room_snapshot), [PUT](room_snapshots::put_room_snapshot))
+//! - `/mail_templates/{tenant_id}` ([GET](mail_templates::get_all))
+//! - `/mail_templates/{tenant_id}/{kind}` ([PUT](mail_templates::put), [DELETE](mail_templates::delete))
+//! - `/mail_templates/{tenant_id}/{kind}/preview` ([POST](mail_templates::post_preview))
 //! - `/services/call_in/start ([POST](services::call_in::start))
+//! - `/services/mail/ics_reply` ([POST](services::mail::ics_reply))
+//! - `/services/bot/start` ([POST](services::bot::start))
+//! - `/services/bot/matrix_bridges` ([GET](services::bot::get_matrix_bridges))
 
 pub use request::{CursorPaginationQuery, PagePaginationQuery};
 pub use response::{ApiResponse, DefaultApiResult};
 
 pub mod assets;
 pub mod auth;
+mod brute_force;
+pub mod calendar_links;
+pub mod contacts;
 mod cursor;
 pub mod events;
+pub mod guest_challenge;
 pub mod invites;
 pub mod legal_vote;
+pub mod mail_templates;
 pub mod middleware;
+pub mod poll_templates;
 mod request;
 pub mod response;
+pub mod room_branding;
+pub mod room_directory;
+pub mod room_media_settings;
+pub mod room_owners;
+pub mod room_snapshots;
 pub mod rooms;
 pub mod services;
 pub mod sip_configs;
+pub mod statistics;
 pub mod tariffs;
+pub mod telemetry;
+pub mod trash;
 pub mod turn;
 pub mod users;
 mod util;
@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Polls prepared before a meeting
+//!
+//! Polls can be prepared for an event or stored in the personal poll library of the current user to reuse them across
+//! meetings. Moderators launch them by their id with the `launch` command of the polls signaling module.
+use super::response::{ApiError, NoContent};
+use super::{ApiResponse, DefaultApiResult};
+use actix_web::web::{Data, Json, Path, ReqData};
+use actix_web::{delete, get, post};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::events::Event;
+use db_storage::poll_templates::{NewPollTemplate, PollTemplate, PollTemplateId};
+use db_storage::users::User;
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use types::core::{EventId, UserId};
+use validator::{Validate, ValidationError};
+
+/// A prepared poll
+#[derive(Debug, Serialize)]
+pub struct PollTemplateResource {
+    pub id: PollTemplateId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub topic: String,
+    pub live: bool,
+    pub choices: Vec<String>,
+    pub duration_secs: u64,
+}
+
+impl From<PollTemplate> for PollTemplateResource {
+    fn from(template: PollTemplate) -> Self {
+        Self {
+            id: template.id,
+            created_by: template.created_by,
+            created_at: template.created_at,
+            topic: template.topic,
+            live: template.live,
+            choices: template.choices,
+            duration_secs: template.duration_secs as u64,
+        }
+    }
+}
+
+/// Request body to prepare a poll, the limits are the same as for polls started during a meeting
+#[derive(Debug, Deserialize, Validate)]
+pub struct PostPollTemplateBody {
+    #[validate(length(min = 2, max = 100))]
+    pub topic: String,
+    pub live: bool,
+    #[validate(length(min = 2, max = 64), custom = "validate_choices")]
+    pub choices: Vec<String>,
+    #[validate(range(min = 2, max = 3600))]
+    pub duration_secs: u64,
+}
+
+fn validate_choices(choices: &[String]) -> Result<(), ValidationError> {
+    if choices
+        .iter()
+        .all(|choice| (1..=100).contains(&choice.len()))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_choice"))
+    }
+}
+
+impl PostPollTemplateBody {
+    fn into_new(self, current_user: &User, event_id: Option<EventId>) -> NewPollTemplate {
+        NewPollTemplate {
+            created_by: current_user.id,
+            event_id,
+            topic: self.topic,
+            live: self.live,
+            choices: self.choices,
+            duration_secs: self.duration_secs as i32,
+            tenant_id: current_user.tenant_id,
+        }
+    }
+}
+
+/// API Endpoint `GET /events/{event_id}/polls`
+///
+/// Returns the polls prepared for the event
+#[get("/events/{event_id}/polls")]
+pub async fn get_event_polls(
+    db: Data<Db>,
+    event_id: Path<EventId>,
+) -> DefaultApiResult<Vec<PollTemplateResource>> {
+    let event_id = event_id.into_inner();
+
+    let templates = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        // Assert that the event exists
+        let _event = Event::get(&mut conn, event_id)?;
+
+        PollTemplate::get_all_for_event(&mut conn, event_id)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(
+        templates.into_iter().map(Into::into).collect(),
+    ))
+}
+
+/// API Endpoint `POST /events/{event_id}/polls`
+///
+/// Prepare a poll for the event
+#[post("/events/{event_id}/polls")]
+pub async fn new_event_poll(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    event_id: Path<EventId>,
+    body: Json<PostPollTemplateBody>,
+) -> DefaultApiResult<PollTemplateResource> {
+    let event_id = event_id.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let template = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        conn.transaction(|conn| {
+            // Assert that the event exists
+            let _event = Event::get(conn, event_id)?;
+
+            body.into_new(&current_user, Some(event_id)).insert(conn)
+        })
+    })
+    .await??;
+
+    Ok(ApiResponse::new(template.into()))
+}
+
+/// API Endpoint `DELETE /events/{event_id}/polls/{poll_id}`
+///
+/// Remove a poll prepared for the event
+#[delete("/events/{event_id}/polls/{poll_id}")]
+pub async fn delete_event_poll(
+    db: Data<Db>,
+    path: Path<(EventId, PollTemplateId)>,
+) -> Result<NoContent, ApiError> {
+    let (event_id, poll_id) = path.into_inner();
+
+    crate::block(move || PollTemplate::delete_for_event(&mut db.get_conn()?, event_id, poll_id))
+        .await??;
+
+    Ok(NoContent)
+}
+
+/// API Endpoint `GET /users/me/polls`
+///
+/// Returns the polls of the personal library of the current user
+#[get("/users/me/polls")]
+pub async fn get_library_polls(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+) -> DefaultApiResult<Vec<PollTemplateResource>> {
+    let templates = crate::block(move || {
+        PollTemplate::get_all_for_library(&mut db.get_read_conn()?, current_user.id)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(
+        templates.into_iter().map(Into::into).collect(),
+    ))
+}
+
+/// API Endpoint `POST /users/me/polls`
+///
+/// Add a poll to the personal library of the current user
+#[post("/users/me/polls")]
+pub async fn new_library_poll(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    body: Json<PostPollTemplateBody>,
+) -> DefaultApiResult<PollTemplateResource> {
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let template = crate::block(move || {
+        body.into_new(&current_user, None)
+            .insert(&mut db.get_conn()?)
+    })
+    .await??;
+
+    Ok(ApiResponse::new(template.into()))
+}
+
+/// API Endpoint `DELETE /users/me/polls/{poll_id}`
+///
+/// Remove a poll from the personal library of the current user
+#[delete("/users/me/polls/{poll_id}")]
+pub async fn delete_library_poll(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    poll_id: Path<PollTemplateId>,
+) -> Result<NoContent, ApiError> {
+    let poll_id = poll_id.into_inner();
+
+    crate::block(move || {
+        PollTemplate::delete_from_library(&mut db.get_conn()?, current_user.id, poll_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices() {
+        assert!(validate_choices(&["yes".into(), "no".into()]).is_ok());
+        assert!(validate_choices(&["yes".into(), "".into()]).is_err());
+        assert!(validate_choices(&["yes".into(), "a".repeat(101)]).is_err());
+    }
+}
@@ -47,6 +47,8 @@ struct StandardErrorBody {
     code: Cow<'static, str>,
     // Human readable message
     message: Cow<'static, str>,
+    // Whether repeating the request later might succeed
+    retryable: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -96,6 +98,8 @@ struct ValidationErrorBody {
     code: Cow<'static, str>,
     // Human readable message
     message: Cow<'static, str>,
+    // Whether repeating the request later might succeed
+    retryable: bool,
     // A list validation errors
     errors: Vec<ValidationErrorEntry>,
 }
@@ -109,6 +113,7 @@ impl ValidationErrorBody {
         Self {
             code: code.into(),
             message: message.into(),
+            retryable: false,
             errors,
         }
     }
@@ -186,6 +191,7 @@ impl ApiError {
             body: ErrorBody::Standard(StandardErrorBody {
                 code: code.into(),
                 message: message.into(),
+                retryable: false,
             }),
         }
     }
@@ -216,6 +222,16 @@ impl ApiError {
         self
     }
 
+    /// Override whether the request can be retried, defaults to `false` for most errors
+    pub fn with_retryable(mut self, retryable: bool) -> Self {
+        match &mut self.body {
+            ErrorBody::Standard(std) => std.retryable = retryable,
+            ErrorBody::Validation(val) => val.retryable = retryable,
+        }
+
+        self
+    }
+
     /// Add an WWW Authenticate header to a response
     pub fn with_www_authenticate(mut self, authentication_error: AuthenticationError) -> Self {
         let header_value = Bearer::build()
@@ -275,6 +291,15 @@ impl ApiError {
         )
     }
 
+    /// Create a new 413 Payload Too Large error
+    pub fn payload_too_large() -> Self {
+        Self::new_standard(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            "The request body exceeds the allowed size",
+        )
+    }
+
     /// Create a new 422 Unprocessable Entity error
     ///
     /// see [`Self::unprocessable_entities()`]
@@ -306,6 +331,16 @@ impl ApiError {
         }
     }
 
+    /// Create a new 429 Too Many Requests error
+    pub fn too_many_requests() -> Self {
+        Self::new_standard(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_requests",
+            "Too many requests have been sent in a given amount of time",
+        )
+        .with_retryable(true)
+    }
+
     /// Create a new 500 Internal Server Error
     pub fn internal() -> Self {
         Self::new_standard(
@@ -319,7 +354,7 @@ impl ApiError {
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.body {
-            ErrorBody::Standard(StandardErrorBody { code, message }) => {
+            ErrorBody::Standard(StandardErrorBody { code, message, .. }) => {
                 write!(
                     f,
                     "status={}, code={}, message={}",
@@ -330,6 +365,7 @@ impl std::fmt::Display for ApiError {
                 code,
                 message,
                 errors,
+                ..
             }) => {
                 write!(
                     f,
@@ -577,6 +613,7 @@ mod test {
             {
                 "code": "validation_failed",
                 "message": "Some provided values are invalid",
+                "retryable": false,
                 "errors": [
                   {
                     "field": "another_range",
@@ -616,7 +653,8 @@ mod test {
             error.body,
             {
                 "code": "custom_code",
-                "message": "A requested resource could not be found"
+                "message": "A requested resource could not be found",
+                "retryable": false
             }
         );
     }
@@ -629,7 +667,22 @@ mod test {
             error.body,
             {
                 "code": "not_found",
-                "message": "A custom message"
+                "message": "A custom message",
+                "retryable": false
+            }
+        );
+    }
+
+    #[test]
+    fn api_error_retryable() {
+        let error = ApiError::too_many_requests();
+
+        assert_eq_json!(
+            error.body,
+            {
+                "code": "too_many_requests",
+                "message": "Too many requests have been sent in a given amount of time",
+                "retryable": true
             }
         );
     }
@@ -0,0 +1,227 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Branding of rooms
+//!
+//! White-label deployments can show their logo and colors without maintaining a fork of the frontend. The branding
+//! of the tenant is configured with the `tenants set-branding` command, owners can override it for their room. The
+//! logo of a room is uploaded as asset of the room.
+//!
+//! The branding is returned by *GET /rooms/{room_id}/branding*, which does not require authentication so it can be
+//! shown to guests before joining, and in the `join_success` message.
+use super::response::{ApiError, NoContent};
+use crate::storage::{self, ObjectStorage};
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, put};
+use database::Db;
+use db_storage::assets::{Asset, AssetScanStatus};
+use db_storage::brandings::{RoomBranding, TenantBranding};
+use db_storage::rooms::Room;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use types::core::{AssetId, RoomId};
+use validator::{Validate, ValidationError};
+
+/// Branding of a room, the values not set for the room are taken from its tenant
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct BrandingResource {
+    /// URL of the logo, logos uploaded as asset of the room are pre-signed URLs which expire after the configured
+    /// lifetime
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secondary_color: Option<String>,
+}
+
+impl BrandingResource {
+    fn is_empty(&self) -> bool {
+        self.logo_url.is_none() && self.primary_color.is_none() && self.secondary_color.is_none()
+    }
+}
+
+/// Load the branding of the room, returns None if neither the room nor its tenant have a branding
+pub(crate) async fn load_branding(
+    db: Arc<Db>,
+    storage: &ObjectStorage,
+    room: &Room,
+) -> anyhow::Result<Option<BrandingResource>> {
+    let room_id = room.id;
+    let tenant_id = room.tenant_id;
+
+    let (tenant_branding, room_branding, logo) = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_conn()?;
+
+        let tenant_branding = TenantBranding::get(&mut conn, tenant_id)?;
+        let room_branding = RoomBranding::get(&mut conn, room_id)?;
+
+        let logo = room_branding
+            .as_ref()
+            .and_then(|branding| branding.logo_asset_id)
+            .map(|asset_id| Asset::get(&mut conn, asset_id, room_id))
+            .transpose()?;
+
+        Ok((tenant_branding, room_branding, logo))
+    })
+    .await??;
+
+    let logo_url = match logo {
+        Some(logo) if logo.scan_status != AssetScanStatus::Infected => {
+            let (url, _) = storage::assets::get_asset_url(storage, &logo.id).await?;
+            Some(url)
+        }
+        _ => tenant_branding
+            .as_ref()
+            .and_then(|branding| branding.logo_url.clone()),
+    };
+
+    let (room_primary_color, room_secondary_color) = room_branding
+        .map(|branding| (branding.primary_color, branding.secondary_color))
+        .unwrap_or_default();
+    let (tenant_primary_color, tenant_secondary_color) = tenant_branding
+        .map(|branding| (branding.primary_color, branding.secondary_color))
+        .unwrap_or_default();
+
+    let branding = BrandingResource {
+        logo_url,
+        primary_color: room_primary_color.or(tenant_primary_color),
+        secondary_color: room_secondary_color.or(tenant_secondary_color),
+    };
+
+    Ok(Some(branding).filter(|branding| !branding.is_empty()))
+}
+
+/// Accept colors in the `#rrggbb` format
+pub(crate) fn validate_color(color: &str) -> Result<(), ValidationError> {
+    let valid = color
+        .strip_prefix('#')
+        .map(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .unwrap_or_default();
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_color"))
+    }
+}
+
+/// API request parameters to replace the branding of a room
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutRoomBranding {
+    /// Asset of the room containing the logo
+    pub logo_asset_id: Option<AssetId>,
+    #[validate(custom = "validate_color")]
+    pub primary_color: Option<String>,
+    #[validate(custom = "validate_color")]
+    pub secondary_color: Option<String>,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/branding*
+///
+/// Returns the branding of the room, all values are unset if neither the room nor its tenant have a branding
+#[get("/rooms/{room_id}/branding")]
+pub async fn get(
+    db: Data<Db>,
+    storage: Data<ObjectStorage>,
+    room_id: Path<RoomId>,
+) -> Result<Json<BrandingResource>, ApiError> {
+    let room_id = room_id.into_inner();
+    let db = db.into_inner();
+
+    let room = crate::block({
+        let db = db.clone();
+        move || Room::get(&mut db.get_read_conn()?, room_id)
+    })
+    .await??;
+
+    let branding = load_branding(db, &storage, &room).await?;
+
+    Ok(Json(branding.unwrap_or_default()))
+}
+
+/// API Endpoint *PUT /rooms/{room_id}/branding*
+///
+/// Replaces the branding of the room with the provided [`PutRoomBranding`], unset values are taken from the tenant
+///
+/// Returns the new branding of the room.
+#[put("/rooms/{room_id}/branding")]
+pub async fn put(
+    db: Data<Db>,
+    storage: Data<ObjectStorage>,
+    room_id: Path<RoomId>,
+    body: Json<PutRoomBranding>,
+) -> Result<Json<BrandingResource>, ApiError> {
+    let room_id = room_id.into_inner();
+    let body = body.into_inner();
+    let db = db.into_inner();
+
+    body.validate()?;
+
+    let room = crate::block({
+        let db = db.clone();
+        move || -> Result<_, ApiError> {
+            let mut conn = db.get_conn()?;
+
+            let room = Room::get(&mut conn, room_id)?;
+
+            if let Some(logo_asset_id) = body.logo_asset_id {
+                let logo = Asset::get(&mut conn, logo_asset_id, room_id)?;
+
+                if logo.scan_status == AssetScanStatus::Infected {
+                    return Err(ApiError::bad_request()
+                        .with_code("asset_infected")
+                        .with_message("The asset is infected and has been quarantined"));
+                }
+            }
+
+            RoomBranding {
+                room_id,
+                logo_asset_id: body.logo_asset_id,
+                primary_color: body.primary_color,
+                secondary_color: body.secondary_color,
+            }
+            .upsert(&mut conn)?;
+
+            Ok(room)
+        }
+    })
+    .await??;
+
+    let branding = load_branding(db, &storage, &room).await?;
+
+    Ok(Json(branding.unwrap_or_default()))
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/branding*
+///
+/// Removes the branding of the room, the branding of the tenant applies again
+#[delete("/rooms/{room_id}/branding")]
+pub async fn delete(db: Data<Db>, room_id: Path<RoomId>) -> Result<NoContent, ApiError> {
+    let room_id = room_id.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        RoomBranding::delete_by_room(&mut conn, room_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert!(validate_color("#1a2B3c").is_ok());
+        assert!(validate_color("1a2b3c").is_err());
+        assert!(validate_color("#1a2b3").is_err());
+        assert!(validate_color("#1a2b3g").is_err());
+        assert!(validate_color("#1a2b3c4d").is_err());
+    }
+}
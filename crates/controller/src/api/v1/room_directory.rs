@@ -0,0 +1,290 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Public directory of rooms
+//!
+//! Owners can list their rooms with a title, description and schedule. When the `room_directory` settings are
+//! configured, anyone can search the listed rooms with *GET /rooms/public*, e.g. on open community installations. The
+//! number of requests to the directory is limited per client.
+use super::invites::invite_is_valid;
+use super::response::{ApiError, NoContent};
+use super::{ApiResponse, PagePaginationQuery};
+use crate::api::client_ip::client_ip;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, put, HttpRequest};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::invites::Invite;
+use db_storage::room_directory::RoomDirectoryEntry;
+use db_storage::rooms::Room;
+use redis_args::ToRedisArgs;
+use serde::{Deserialize, Serialize};
+use types::core::{InviteCodeId, RoomId};
+use validator::Validate;
+
+/// Number of requests of a client to the directory in a minute
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-room_directory:client={client}:minute={minute}")]
+struct DirectoryRequests<'s> {
+    client: &'s str,
+    minute: i64,
+}
+
+/// A room listed in the directory
+#[derive(Debug, Serialize)]
+pub struct RoomDirectoryEntryResource {
+    pub room_id: RoomId,
+    pub title: String,
+    pub description: String,
+    pub schedule: Option<String>,
+    /// Invite code to join the room, not set if the invite expired or has been deactivated
+    pub invite_code: Option<InviteCodeId>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RoomDirectoryEntryResource {
+    fn new(entry: RoomDirectoryEntry, invite: Option<&Invite>) -> Self {
+        Self {
+            room_id: entry.room_id,
+            title: entry.title,
+            description: entry.description,
+            schedule: entry.schedule,
+            invite_code: invite
+                .filter(|invite| invite_is_valid(invite))
+                .map(|invite| invite.id),
+            updated_at: entry.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    /// Search string matched against the title and description of the rooms
+    #[serde(default)]
+    q: String,
+}
+
+/// API Endpoint *GET /rooms/public*
+///
+/// Returns the rooms listed in the directory matching the search, ordered by title. Returns 404 Not Found if the
+/// directory is not enabled.
+#[get("/rooms/public")]
+pub async fn search(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    request: HttpRequest,
+    search: Query<SearchQuery>,
+    pagination: Query<PagePaginationQuery>,
+) -> Result<ApiResponse<Vec<RoomDirectoryEntryResource>>, ApiError> {
+    let settings = settings.load_full();
+    let directory = settings
+        .room_directory
+        .as_ref()
+        .ok_or_else(ApiError::not_found)?;
+
+    // Clients without a known address share a limit
+    let client = client_ip(&request, &settings.http.trusted_proxies)
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+
+    let mut redis_conn = (**redis_ctx).clone();
+    let requests = increment_requests(&mut redis_conn, &client).await?;
+
+    if requests > directory.requests_per_minute {
+        return Err(ApiError::too_many_requests()
+            .with_code("rate_limited")
+            .with_message("Too many requests to the room directory, try again in a minute"));
+    }
+
+    let SearchQuery { q } = search.into_inner();
+    let PagePaginationQuery { per_page, page } = pagination.into_inner();
+
+    let (entries, entry_count) = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        RoomDirectoryEntry::search_paginated(&mut conn, &q, per_page, page)
+    })
+    .await??;
+
+    let entries = entries
+        .into_iter()
+        .map(|(entry, invite)| RoomDirectoryEntryResource::new(entry, invite.as_ref()))
+        .collect();
+
+    Ok(ApiResponse::new(entries).with_page_pagination(per_page, page, entry_count))
+}
+
+/// Increment the number of requests of the client in the current minute, returns the incremented number
+async fn increment_requests(
+    redis_conn: &mut RedisConnection,
+    client: &str,
+) -> Result<u32, ApiError> {
+    let key = DirectoryRequests {
+        client,
+        minute: Utc::now().timestamp() / 60,
+    };
+
+    let (requests,): (u32,) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, 60)
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to count requests to the room directory, {}", e);
+            ApiError::internal()
+        })?;
+
+    Ok(requests)
+}
+
+/// API request parameters to list a room in the directory
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutRoomDirectoryEntry {
+    #[validate(length(min = 1, max = 255))]
+    pub title: String,
+    #[serde(default)]
+    #[validate(length(max = 4096))]
+    pub description: String,
+    /// Free text describing when the room is used, e.g. "Every Tuesday at 18:00 CET"
+    #[validate(length(max = 255))]
+    pub schedule: Option<String>,
+    /// Invite code of the room shown to visitors of the directory
+    pub invite_code: Option<InviteCodeId>,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/directory_entry*
+///
+/// Returns the directory entry of the room, 404 Not Found if the room is not listed
+#[get("/rooms/{room_id}/directory_entry")]
+pub async fn get(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+) -> Result<Json<RoomDirectoryEntryResource>, ApiError> {
+    let room_id = room_id.into_inner();
+
+    let (entry, invite) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        // Make sure the room is not in the trash
+        Room::get(&mut conn, room_id)?;
+
+        let entry = RoomDirectoryEntry::get(&mut conn, room_id)?.ok_or_else(ApiError::not_found)?;
+        let invite = entry
+            .invite_code
+            .map(|invite_code| Invite::get(&mut conn, invite_code))
+            .transpose()?;
+
+        Ok((entry, invite))
+    })
+    .await??;
+
+    Ok(Json(RoomDirectoryEntryResource::new(
+        entry,
+        invite.as_ref(),
+    )))
+}
+
+/// API Endpoint *PUT /rooms/{room_id}/directory_entry*
+///
+/// Lists the room in the directory or replaces its entry with the provided [`PutRoomDirectoryEntry`]
+///
+/// Returns the new directory entry.
+#[put("/rooms/{room_id}/directory_entry")]
+pub async fn put(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+    body: Json<PutRoomDirectoryEntry>,
+) -> Result<Json<RoomDirectoryEntryResource>, ApiError> {
+    let room_id = room_id.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let (entry, invite) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        Room::get(&mut conn, room_id)?;
+
+        let invite = body
+            .invite_code
+            .map(|invite_code| Invite::get(&mut conn, invite_code))
+            .transpose()?;
+
+        if matches!(&invite, Some(invite) if invite.room != room_id) {
+            return Err(ApiError::bad_request()
+                .with_code("invalid_invite_code")
+                .with_message("The invite code does not belong to the room"));
+        }
+
+        let entry = RoomDirectoryEntry {
+            room_id,
+            title: body.title,
+            description: body.description,
+            schedule: body.schedule,
+            invite_code: body.invite_code,
+            updated_at: Utc::now(),
+        }
+        .upsert(&mut conn)?;
+
+        Ok((entry, invite))
+    })
+    .await??;
+
+    Ok(Json(RoomDirectoryEntryResource::new(
+        entry,
+        invite.as_ref(),
+    )))
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/directory_entry*
+///
+/// Removes the room from the directory
+#[delete("/rooms/{room_id}/directory_entry")]
+pub async fn delete(db: Data<Db>, room_id: Path<RoomId>) -> Result<NoContent, ApiError> {
+    let room_id = room_id.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        RoomDirectoryEntry::delete_by_room(&mut conn, room_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use pretty_assertions::assert_eq;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn requests_are_counted_per_client() {
+        let mut redis_conn = setup().await;
+
+        for expected in 1..=3 {
+            assert_eq!(
+                increment_requests(&mut redis_conn, "192.0.2.1")
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+
+        assert_eq!(
+            increment_requests(&mut redis_conn, "192.0.2.2")
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
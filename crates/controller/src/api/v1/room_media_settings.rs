@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Media settings of rooms
+//!
+//! Allows the owners to tune the audio level thresholds used to detect speaking participants, e.g. for rooms in noisy
+//! environments, and to enable push-to-talk for large meetings. Changes apply to media sessions published after the
+//! change.
+use super::response::{ApiError, NoContent};
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, put};
+use database::Db;
+use db_storage::room_media_settings::RoomMediaSettings;
+use db_storage::rooms::Room;
+use serde::{Deserialize, Serialize};
+use types::core::RoomId;
+use validator::Validate;
+
+/// Media settings of a room, unset values use the defaults of the media module
+#[derive(Debug, Default, Serialize)]
+pub struct RoomMediaSettingsResource {
+    pub speaker_focus_packets: Option<i64>,
+    pub speaker_focus_level: Option<i64>,
+    pub push_to_talk: bool,
+    pub push_to_talk_max_hold_secs: Option<i64>,
+}
+
+impl From<RoomMediaSettings> for RoomMediaSettingsResource {
+    fn from(settings: RoomMediaSettings) -> Self {
+        Self {
+            speaker_focus_packets: settings.speaker_focus_packets,
+            speaker_focus_level: settings.speaker_focus_level,
+            push_to_talk: settings.push_to_talk,
+            push_to_talk_max_hold_secs: settings.push_to_talk_max_hold_secs,
+        }
+    }
+}
+
+/// API request parameters to replace the media settings of a room
+#[derive(Debug, Deserialize, Validate)]
+pub struct PutRoomMediaSettings {
+    /// Number of packets with the audio level needed to detect a speaking participant, 50 packets are one second
+    #[validate(range(min = 1, max = 500))]
+    pub speaker_focus_packets: Option<i64>,
+    /// Average audio level needed per packet, from 127 (muted) to 0 (loud)
+    #[validate(range(min = 0, max = 127))]
+    pub speaker_focus_level: Option<i64>,
+    /// Only transmit the audio of non-moderators while they hold the talk button
+    #[serde(default)]
+    pub push_to_talk: bool,
+    /// Maximum time in seconds the talk button can be held before the participant is muted
+    #[validate(range(min = 1, max = 3600))]
+    pub push_to_talk_max_hold_secs: Option<i64>,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/media_settings*
+///
+/// Returns the media settings of the room
+#[get("/rooms/{room_id}/media_settings")]
+pub async fn get(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+) -> Result<Json<RoomMediaSettingsResource>, ApiError> {
+    let room_id = room_id.into_inner();
+
+    let settings = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        // Make sure the room is not in the trash
+        Room::get(&mut conn, room_id)?;
+
+        RoomMediaSettings::get(&mut conn, room_id)
+    })
+    .await??;
+
+    Ok(Json(settings.map(Into::into).unwrap_or_default()))
+}
+
+/// API Endpoint *PUT /rooms/{room_id}/media_settings*
+///
+/// Replaces the media settings of the room with the provided [`PutRoomMediaSettings`]
+///
+/// Returns the new media settings.
+#[put("/rooms/{room_id}/media_settings")]
+pub async fn put(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+    body: Json<PutRoomMediaSettings>,
+) -> Result<Json<RoomMediaSettingsResource>, ApiError> {
+    let room_id = room_id.into_inner();
+    let body = body.into_inner();
+
+    body.validate()?;
+
+    let settings = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Room::get(&mut conn, room_id)?;
+
+        RoomMediaSettings {
+            room_id,
+            speaker_focus_packets: body.speaker_focus_packets,
+            speaker_focus_level: body.speaker_focus_level,
+            push_to_talk: body.push_to_talk,
+            push_to_talk_max_hold_secs: body.push_to_talk_max_hold_secs,
+        }
+        .upsert(&mut conn)
+    })
+    .await??;
+
+    Ok(Json(settings.into()))
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/media_settings*
+///
+/// Resets the media settings of the room to the defaults
+#[delete("/rooms/{room_id}/media_settings")]
+pub async fn delete(db: Data<Db>, room_id: Path<RoomId>) -> Result<NoContent, ApiError> {
+    let room_id = room_id.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        RoomMediaSettings::delete_by_room(&mut conn, room_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
@@ -0,0 +1,280 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Owners of rooms
+//!
+//! All owners of a room can manage the room, its invites and the events in the room, and are moderators in the
+//! meeting. The primary owner is returned as `created_by` of the room, their tariff applies to the room.
+use super::events::EventPoliciesBuilderExt;
+use super::response::{ApiError, Created, NoContent};
+use super::rooms::{RoomResource, RoomsPoliciesBuilderExt};
+use super::users::PublicUserProfile;
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Json, Path, ReqData};
+use actix_web::{delete, get, post, put, Either};
+use database::Db;
+use db_storage::events::{Event, EventInvite};
+use db_storage::room_owners::{NewRoomOwner, RoomOwner};
+use db_storage::rooms::Room;
+use db_storage::users::User;
+use kustos::policies_builder::{Finished, PoliciesBuilder};
+use kustos::prelude::*;
+use serde::Deserialize;
+use types::core::{EventId, RoomId, UserId};
+
+/// Policies granting the owner access to the room and the events in the room
+fn owner_policies(
+    user_id: UserId,
+    room_id: RoomId,
+    event_ids: &[EventId],
+) -> PoliciesBuilder<Finished> {
+    let mut policies = PoliciesBuilder::new()
+        .grant_user_access(user_id)
+        .room_read_access(room_id)
+        .room_write_access(room_id);
+
+    for &event_id in event_ids {
+        policies = policies
+            .event_read_access(event_id)
+            .event_write_access(event_id);
+    }
+
+    policies.finish()
+}
+
+/// API Endpoint *GET /rooms/{room_id}/owners*
+///
+/// Returns the owners of the room in the order they have been added
+#[get("/rooms/{room_id}/owners")]
+pub async fn get_owners(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+) -> Result<Json<Vec<PublicUserProfile>>, ApiError> {
+    let settings = settings.load_full();
+    let room_id = room_id.into_inner();
+
+    let owners = crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        // Make sure the room is not in the trash
+        Room::get(&mut conn, room_id)?;
+
+        RoomOwner::get_all_for_room(&mut conn, room_id)
+    })
+    .await??;
+
+    let owners = owners
+        .into_iter()
+        .map(|user| PublicUserProfile::from_db(&settings, user))
+        .collect();
+
+    Ok(Json(owners))
+}
+
+/// API Endpoint *PUT /rooms/{room_id}/owners/{user_id}*
+///
+/// Adds the user of the same tenant to the owners of the room.
+///
+/// Returns 201 Created if the user has been added, 204 No Content if the user already is an owner.
+#[put("/rooms/{room_id}/owners/{user_id}")]
+pub async fn add_owner(
+    db: Data<Db>,
+    authz: Data<Authz>,
+    current_user: ReqData<User>,
+    path: Path<(RoomId, UserId)>,
+) -> Result<Either<Created, NoContent>, ApiError> {
+    let current_user = current_user.into_inner();
+    let (room_id, user_id) = path.into_inner();
+
+    let added = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_conn()?;
+
+        let room = Room::get(&mut conn, room_id)?;
+        let user = User::get_filtered_by_tenant(&mut conn, current_user.tenant_id, user_id)?;
+
+        let inserted = NewRoomOwner {
+            room_id: room.id,
+            user_id: user.id,
+        }
+        .insert(&mut conn)?;
+
+        if !inserted {
+            return Ok(None);
+        }
+
+        let event_ids = Event::get_all_ids_for_room(&mut conn, room.id)?;
+
+        Ok(Some(event_ids))
+    })
+    .await??;
+
+    match added {
+        Some(event_ids) => {
+            authz
+                .add_policies(owner_policies(user_id, room_id, &event_ids))
+                .await?;
+
+            Ok(Either::Left(Created))
+        }
+        None => Ok(Either::Right(NoContent)),
+    }
+}
+
+/// API Endpoint *DELETE /rooms/{room_id}/owners/{user_id}*
+///
+/// Removes the user from the owners of the room, the user loses the access granted to owners of the room. The access
+/// to events the user created or is invited to is kept.
+///
+/// The primary owner cannot be removed, the ownership has to be transferred to another user first.
+#[delete("/rooms/{room_id}/owners/{user_id}")]
+pub async fn remove_owner(
+    db: Data<Db>,
+    authz: Data<Authz>,
+    path: Path<(RoomId, UserId)>,
+) -> Result<NoContent, ApiError> {
+    let (room_id, user_id) = path.into_inner();
+
+    let policies = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let room = Room::get(&mut conn, room_id)?;
+
+        if room.created_by == user_id {
+            return Err(ApiError::bad_request()
+                .with_code("primary_owner")
+                .with_message("The primary owner of the room cannot be removed"));
+        }
+
+        if !RoomOwner::delete(&mut conn, room_id, user_id)? {
+            return Err(ApiError::not_found());
+        }
+
+        let event_ids = Event::get_all_ids_for_room(&mut conn, room_id)?;
+        let created = Event::get_all_ids_for_room_created_by(&mut conn, room_id, user_id)?;
+        let invited: Vec<EventId> = EventInvite::get_all_for_invitee(&mut conn, user_id)?
+            .into_iter()
+            .map(|invite| invite.event_id)
+            .filter(|event_id| event_ids.contains(event_id))
+            .collect();
+
+        Ok(removed_owner_policies(
+            user_id, room_id, &event_ids, &created, &invited,
+        ))
+    })
+    .await??;
+
+    authz.remove_existing_policies(policies).await?;
+
+    Ok(NoContent)
+}
+
+/// Policies granted by [`owner_policies`] which are removed from a former owner
+///
+/// The creator of an event keeps the access to it, invitees keep the read access to the event and the room.
+fn removed_owner_policies(
+    user_id: UserId,
+    room_id: RoomId,
+    event_ids: &[EventId],
+    created: &[EventId],
+    invited: &[EventId],
+) -> PoliciesBuilder<Finished> {
+    let mut policies = PoliciesBuilder::new()
+        .grant_user_access(user_id)
+        .room_write_access(room_id);
+
+    if created.is_empty() && invited.is_empty() {
+        policies = policies.room_read_access(room_id);
+    }
+
+    for event_id in event_ids {
+        if created.contains(event_id) {
+            continue;
+        }
+
+        policies = policies.event_write_access(*event_id);
+
+        if !invited.contains(event_id) {
+            policies = policies.event_read_access(*event_id);
+        }
+    }
+
+    policies.finish()
+}
+
+/// API request parameters to transfer the ownership of a room
+#[derive(Debug, Deserialize)]
+pub struct TransferOwnershipBody {
+    /// The new primary owner
+    pub user_id: UserId,
+}
+
+/// API Endpoint *POST /rooms/{room_id}/transfer_ownership*
+///
+/// Makes the user of the same tenant the primary owner of the room. Only the current primary owner can transfer the
+/// ownership, they stay an owner of the room.
+///
+/// Returns the modified [`RoomResource`]
+#[post("/rooms/{room_id}/transfer_ownership")]
+pub async fn transfer_ownership(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    authz: Data<Authz>,
+    current_user: ReqData<User>,
+    room_id: Path<RoomId>,
+    body: Json<TransferOwnershipBody>,
+) -> Result<Json<RoomResource>, ApiError> {
+    let settings = settings.load_full();
+    let current_user = current_user.into_inner();
+    let room_id = room_id.into_inner();
+    let body = body.into_inner();
+
+    let (room, new_owner, new_event_ids) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let room = Room::get(&mut conn, room_id)?;
+
+        if room.created_by != current_user.id {
+            return Err(ApiError::forbidden()
+                .with_code("not_primary_owner")
+                .with_message("Only the primary owner can transfer the ownership of the room"));
+        }
+
+        let new_owner =
+            User::get_filtered_by_tenant(&mut conn, current_user.tenant_id, body.user_id)?;
+
+        let was_owner = RoomOwner::is_owner(&mut conn, room_id, new_owner.id)?;
+
+        let room = RoomOwner::transfer(&mut conn, room_id, new_owner.id)?;
+
+        // Policies only have to be granted if the user has not been an owner before
+        let new_event_ids = if was_owner {
+            None
+        } else {
+            Some(Event::get_all_ids_for_room(&mut conn, room_id)?)
+        };
+
+        Ok((room, new_owner, new_event_ids))
+    })
+    .await??;
+
+    if let Some(event_ids) = new_event_ids {
+        authz
+            .add_policies(owner_policies(new_owner.id, room_id, &event_ids))
+            .await?;
+    }
+
+    let room_resource = RoomResource {
+        id: room.id,
+        created_by: PublicUserProfile::from_db(&settings, new_owner),
+        created_at: room.created_at,
+        password: room.password,
+        waiting_room: room.waiting_room,
+        locale: room.locale,
+        region: room.region,
+        webinar_mode: room.webinar_mode,
+    };
+
+    Ok(Json(room_resource))
+}
@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Snapshots of the redis state of rooms for administrators
+//!
+//! The endpoints are only accessible by service accounts with the `opentalk-room-snapshots` realm role.
+use super::response::error::json_error_handler;
+use super::response::{ApiError, NoContent};
+use super::services::RequiredRealmRole;
+use super::ApiResponse;
+use crate::api::signaling::prelude::*;
+use crate::api::signaling::snapshot::{self, RoomSnapshot};
+use crate::redis_wrapper::RedisConnection;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{Data, Json, JsonConfig, Path, Query};
+use actix_web::{get, put};
+use database::Db;
+use db_storage::rooms::Room;
+use serde::Deserialize;
+use types::core::RoomId;
+
+const REQUIRED_ROOM_SNAPSHOTS_ROLE: &str = "opentalk-room-snapshots";
+
+/// Snapshots of large rooms exceed the default limit of JSON request bodies
+const SNAPSHOT_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreQuery {
+    /// Restore the snapshot even if participants are inside the room
+    #[serde(default)]
+    force: bool,
+}
+
+/// API Endpoint *GET /room_snapshots/{room_id}*
+///
+/// Returns a snapshot of all redis keys of the room, the keys are assigned to the namespaces of the signaling modules
+#[get("/{room_id}")]
+pub async fn get_room_snapshot(
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    modules: Data<SignalingModules>,
+    room_id: Path<RoomId>,
+) -> Result<ApiResponse<RoomSnapshot>, ApiError> {
+    let room_id = room_id.into_inner();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    // Make sure the room exists
+    crate::block(move || Room::get(&mut db.get_read_conn()?, room_id)).await??;
+
+    let snapshot = snapshot::take(&mut redis_conn, room_id, &modules.get_module_names()).await?;
+
+    Ok(ApiResponse::new(snapshot))
+}
+
+/// API Endpoint *PUT /room_snapshots/{room_id}*
+///
+/// Replaces the redis state of the room with the snapshot, which may have been taken of another room.
+///
+/// Returns 409 Conflict if participants are inside the room and `force` is not set, or if the snapshot has been taken
+/// with other key versions of the modules than the current ones.
+#[put("/{room_id}")]
+pub async fn put_room_snapshot(
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    room_id: Path<RoomId>,
+    query: Query<RestoreQuery>,
+    body: Json<RoomSnapshot>,
+) -> Result<NoContent, ApiError> {
+    let room_id = room_id.into_inner();
+    let snapshot = body.into_inner();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    crate::block(move || Room::get(&mut db.get_read_conn()?, room_id)).await??;
+
+    let participant_count = control::storage::get_participant_count(&mut redis_conn, room_id)
+        .await?
+        .unwrap_or(0);
+
+    if participant_count > 0 && !query.force {
+        return Err(ApiError::conflict()
+            .with_code("room_active")
+            .with_message("Participants are inside the room"));
+    }
+
+    if !snapshot::has_current_key_versions(&mut redis_conn, &snapshot).await? {
+        return Err(ApiError::conflict()
+            .with_code("key_versions_mismatch")
+            .with_message("The snapshot has been taken with other key versions of the modules"));
+    }
+
+    snapshot::restore(&mut redis_conn, room_id, snapshot).await?;
+
+    Ok(NoContent)
+}
+
+pub fn services() -> impl HttpServiceFactory {
+    actix_web::web::scope("")
+        .wrap(RequiredRealmRole::new(REQUIRED_ROOM_SNAPSHOTS_ROLE))
+        .app_data(
+            JsonConfig::default()
+                .limit(SNAPSHOT_SIZE_LIMIT)
+                .error_handler(json_error_handler),
+        )
+        .service(get_room_snapshot)
+        .service(put_room_snapshot)
+}
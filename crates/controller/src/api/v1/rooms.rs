@@ -7,30 +7,42 @@
 //! The defined structs are exposed to the REST API and will be serialized/deserialized. Similar
 //! structs are defined in the Database crate [`db_storage`] for database operations.
 
+use super::brute_force;
+use super::guest_challenge::{self, ChallengeSolution};
+use super::invites::invite_is_valid;
 use super::response::error::{ApiError, ValidationErrorEntry};
 use super::response::{NoContent, CODE_INVALID_VALUE};
 use super::users::PublicUserProfile;
+use crate::api::client_ip::client_ip;
 use crate::api::signaling::prelude::*;
-use crate::api::signaling::ticket::start_or_continue_signaling_session;
+use crate::api::signaling::sharding::RoomAffinity;
+use crate::api::signaling::ticket::{
+    is_valid_resumption_token, start_or_continue_signaling_session, ClientFingerprint,
+};
 use crate::api::v1::tariffs::TariffResource;
 use crate::api::v1::{ApiResponse, PagePaginationQuery};
 use crate::api::Participant;
+use crate::i18n::Locale;
 use crate::redis_wrapper::RedisConnection;
 use crate::settings::SharedSettingsActix;
 use actix_web::web::{self, Data, Json, Path, ReqData};
-use actix_web::{delete, get, patch, post};
-use chrono::{DateTime, Utc};
-use database::Db;
+use actix_web::{delete, get, patch, post, HttpRequest};
+use chrono::{DateTime, NaiveDate, Utc};
+use database::{Db, OptionalExt};
+use db_storage::action_items::{ActionItem, ActionItemKind};
 use db_storage::invites::Invite;
+use db_storage::room_markers::RoomMarker;
+use db_storage::room_statistics::RoomStatistics;
 use db_storage::rooms::{self as db_rooms, Room};
 use db_storage::sip_configs::NewSipConfig;
 use db_storage::users::User;
 use kustos::policies_builder::{GrantingAccess, PoliciesBuilder};
 use kustos::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use types::core::{BreakoutRoomId, InviteCodeId, ResumptionToken, RoomId, TicketToken};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 /// A Room
 ///
@@ -43,6 +55,9 @@ pub struct RoomResource {
     pub created_at: DateTime<Utc>,
     pub password: Option<String>,
     pub waiting_room: bool,
+    pub locale: Option<String>,
+    pub region: Option<String>,
+    pub webinar_mode: bool,
 }
 
 /// API Endpoint *GET /rooms*
@@ -65,7 +80,7 @@ pub async fn accessible(
         .await?;
 
     let (rooms, room_count) = crate::block(move || {
-        let mut conn = db.get_conn()?;
+        let mut conn = db.get_read_conn()?;
 
         match accessible_rooms {
             kustos::AccessibleResources::All => {
@@ -86,6 +101,9 @@ pub async fn accessible(
             created_at: room.created_at,
             password: room.password,
             waiting_room: room.waiting_room,
+            locale: room.locale,
+            region: room.region,
+            webinar_mode: room.webinar_mode,
         })
         .collect::<Vec<RoomResource>>();
 
@@ -102,6 +120,21 @@ pub struct PostRoomsBody {
     pub enable_sip: bool,
     #[serde(default)]
     pub waiting_room: bool,
+    /// Language tag of the documents and messages generated for the room, e.g. `de-DE`
+    #[validate(custom = "validate_locale")]
+    pub locale: Option<String>,
+    /// Region of the media servers the room is pinned to
+    #[validate(length(min = 1, max = 64))]
+    pub region: Option<String>,
+    /// Only show the moderators to participants who are not moderators
+    #[serde(default)]
+    pub webinar_mode: bool,
+}
+
+pub(super) fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    Locale::from_str(locale)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("unsupported_locale"))
 }
 
 /// API Endpoint *POST /rooms*
@@ -132,6 +165,9 @@ pub async fn new(
             password: room_parameters.password,
             waiting_room: room_parameters.waiting_room,
             tenant_id: current_user.tenant_id,
+            locale: room_parameters.locale,
+            region: room_parameters.region,
+            webinar_mode: room_parameters.webinar_mode,
         };
 
         let room = new_room.insert(&mut conn)?;
@@ -150,6 +186,9 @@ pub async fn new(
         created_at: room.created_at,
         password: room.password,
         waiting_room: room.waiting_room,
+        locale: room.locale,
+        region: room.region,
+        webinar_mode: room.webinar_mode,
     };
 
     let policies = PoliciesBuilder::new()
@@ -171,6 +210,16 @@ pub struct PatchRoomsBody {
     pub password: Option<Option<String>>,
 
     pub waiting_room: Option<bool>,
+
+    #[validate(custom = "validate_locale")]
+    #[serde(default, deserialize_with = "super::util::deserialize_some")]
+    pub locale: Option<Option<String>>,
+
+    #[validate(length(min = 1, max = 64))]
+    #[serde(default, deserialize_with = "super::util::deserialize_some")]
+    pub region: Option<Option<String>>,
+
+    pub webinar_mode: Option<bool>,
 }
 
 /// API Endpoint *PATCH /rooms/{room_id}*
@@ -192,24 +241,39 @@ pub async fn patch(
 
     modify_room.validate()?;
 
-    let room = crate::block(move || {
+    let (room, created_by) = crate::block(move || -> database::Result<_> {
         let mut conn = db.get_conn()?;
 
         let changeset = db_rooms::UpdateRoom {
             password: modify_room.password,
             waiting_room: modify_room.waiting_room,
+            locale: modify_room.locale,
+            region: modify_room.region,
+            webinar_mode: modify_room.webinar_mode,
+        };
+
+        let room = changeset.apply(&mut conn, room_id)?;
+
+        // The room might be patched by another owner than the primary owner
+        let created_by = if room.created_by == current_user.id {
+            current_user
+        } else {
+            User::get(&mut conn, room.created_by)?
         };
 
-        changeset.apply(&mut conn, room_id)
+        Ok((room, created_by))
     })
     .await??;
 
     let room_resource = RoomResource {
         id: room.id,
-        created_by: PublicUserProfile::from_db(&settings, current_user),
+        created_by: PublicUserProfile::from_db(&settings, created_by),
         created_at: room.created_at,
         password: room.password,
         waiting_room: room.waiting_room,
+        locale: room.locale,
+        region: room.region,
+        webinar_mode: room.webinar_mode,
     };
 
     Ok(Json(room_resource))
@@ -217,26 +281,21 @@ pub async fn patch(
 
 /// API Endpoint *DELETE /rooms/{room_id}*
 ///
-/// Deletes the room and owned resources.
+/// Moves the room and its events into the trash.
+///
+/// The room can be restored from the trash until the configured grace period has passed, after which
+/// the room and all owned resources are purged.
 #[delete("/rooms/{room_id}")]
-pub async fn delete(
-    db: Data<Db>,
-    room_id: Path<RoomId>,
-    authz: Data<Authz>,
-) -> Result<NoContent, ApiError> {
+pub async fn delete(db: Data<Db>, room_id: Path<RoomId>) -> Result<NoContent, ApiError> {
     let room_id = room_id.into_inner();
 
     crate::block(move || {
         let mut conn = db.get_conn()?;
 
-        Room::delete_by_id(&mut conn, room_id)
+        Room::soft_delete_by_id(&mut conn, room_id)
     })
     .await??;
 
-    let resources = associated_resource_ids(room_id);
-
-    authz.remove_explicit_resources(resources).await?;
-
     Ok(NoContent)
 }
 
@@ -265,6 +324,9 @@ pub async fn get(
         created_at: room.created_at,
         password: room.password,
         waiting_room: room.waiting_room,
+        locale: room.locale,
+        region: room.region,
+        webinar_mode: room.webinar_mode,
     };
 
     Ok(Json(room_resource))
@@ -291,6 +353,134 @@ pub async fn get_room_tariff(
     Ok(Json(response))
 }
 
+/// API Endpoint *GET /rooms/{room_id}/media-stats*
+///
+/// Returns the statistics the signaling modules collect about the running room, keyed by the namespace of the module.
+/// The `media` module reports the media sessions of every participant, including the serving janus instance, to help
+/// support with debugging media problems while the meeting is running.
+#[get("/rooms/{room_id}/media-stats")]
+pub async fn get_media_stats(
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    modules: Data<SignalingModules>,
+    room_id: Path<RoomId>,
+) -> Result<Json<BTreeMap<&'static str, serde_json::Value>>, ApiError> {
+    let room_id = room_id.into_inner();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    // Make sure the room exists and is not in the trash
+    crate::block(move || {
+        let mut conn = db.get_read_conn()?;
+
+        Room::get(&mut conn, room_id)
+    })
+    .await??;
+
+    let stats = modules
+        .room_stats(&mut redis_conn, SignalingRoomId(room_id, None))
+        .await;
+
+    Ok(Json(stats))
+}
+
+/// Summary of the last session of a room
+#[derive(Debug, Serialize)]
+pub struct RoomSummaryResource {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Highest number of participants inside the room at the same time
+    pub peak_participants: i32,
+    /// Sum of the time every participant spent inside the room
+    pub participant_minutes: i64,
+    /// Markers set by the moderators during the session, ordered by their time
+    pub markers: Vec<RoomMarkerResource>,
+    /// Decisions and action items collected during the session, ordered by their creation time
+    pub action_items: Vec<ActionItemResource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomMarkerResource {
+    pub title: String,
+    pub marked_at: DateTime<Utc>,
+    /// The breakout room the marker was set in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakout_room_id: Option<BreakoutRoomId>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionItemResource {
+    pub kind: ActionItemKind,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    /// The breakout room the item was added in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakout_room_id: Option<BreakoutRoomId>,
+}
+
+/// API Endpoint *GET /rooms/{room_id}/summary*
+///
+/// Returns the summary of the last session of the room, once all participants have left. Returns 404 Not Found if
+/// the room has not been used yet.
+#[get("/rooms/{room_id}/summary")]
+pub async fn get_summary(
+    db: Data<Db>,
+    room_id: Path<RoomId>,
+) -> Result<Json<RoomSummaryResource>, ApiError> {
+    let room_id = room_id.into_inner();
+
+    let (statistics, markers, action_items) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        // Make sure the room exists and is not in the trash
+        Room::get(&mut conn, room_id)?;
+
+        let statistics = RoomStatistics::get_latest_for_room(&mut conn, room_id)?
+            .ok_or_else(ApiError::not_found)?;
+
+        let markers = RoomMarker::get_all_for_session(&mut conn, statistics.id)?;
+
+        let action_items = ActionItem::get_all_created_between(
+            &mut conn,
+            room_id,
+            statistics.started_at,
+            statistics.ended_at,
+        )?;
+
+        Ok((statistics, markers, action_items))
+    })
+    .await??;
+
+    Ok(Json(RoomSummaryResource {
+        started_at: statistics.started_at,
+        ended_at: statistics.ended_at,
+        peak_participants: statistics.peak_participants,
+        participant_minutes: statistics.participant_minutes,
+        markers: markers
+            .into_iter()
+            .map(|marker| RoomMarkerResource {
+                title: marker.title,
+                marked_at: marker.marked_at,
+                breakout_room_id: marker.breakout_room_id,
+            })
+            .collect(),
+        action_items: action_items
+            .into_iter()
+            .map(|item| ActionItemResource {
+                kind: item.kind,
+                text: item.text,
+                assignee: item.assignee,
+                due_date: item.due_date,
+                created_at: item.created_at,
+                breakout_room_id: item.breakout_room_id,
+            })
+            .collect(),
+    }))
+}
+
 /// The JSON body expected when making a *POST /rooms/{room_id}/start*
 #[derive(Debug, Deserialize)]
 pub struct StartRequest {
@@ -303,6 +493,9 @@ pub struct StartRequest {
 pub struct StartResponse {
     ticket: TicketToken,
     resumption: ResumptionToken,
+    /// URL of the signaling endpoint of the controller instance the room runs on, only set when sharding is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signaling_url: Option<String>,
 }
 
 #[derive(Debug)]
@@ -311,6 +504,8 @@ pub enum StartRoomError {
     NoBreakoutRooms,
     InvalidBreakoutRoomId,
     BannedFromRoom,
+    ChallengeRequired,
+    ChallengeFailed,
 }
 
 impl From<StartRoomError> for ApiError {
@@ -331,6 +526,14 @@ impl From<StartRoomError> for ApiError {
             StartRoomError::BannedFromRoom => ApiError::forbidden()
                 .with_code("banned_from_room")
                 .with_message("This user has been banned from entering this room"),
+
+            StartRoomError::ChallengeRequired => ApiError::forbidden()
+                .with_code("challenge_required")
+                .with_message("A challenge must be solved before joining the room"),
+
+            StartRoomError::ChallengeFailed => ApiError::forbidden()
+                .with_code("challenge_failed")
+                .with_message("The provided challenge solution is invalid"),
         }
     }
 }
@@ -352,14 +555,19 @@ impl From<StartRoomError> for ApiError {
 /// Returns [`StartRoomError::WrongRoomPassword`] when the provided password is wrong
 /// Returns [`StartRoomError::NoBreakoutRooms`]  when no breakout rooms are configured but were provided
 /// Returns [`StartRoomError::InvalidBreakoutRoomId`]  when the provided breakout room id is invalid     
+#[allow(clippy::too_many_arguments)]
 #[post("/rooms/{room_id}/start")]
 pub async fn start(
+    settings: SharedSettingsActix,
     db: Data<Db>,
     redis_conn: Data<RedisConnection>,
+    room_affinity: Data<RoomAffinity>,
     current_user: ReqData<User>,
     room_id: Path<RoomId>,
+    http_request: HttpRequest,
     request: Json<StartRequest>,
 ) -> Result<Json<StartResponse>, ApiError> {
+    let settings = settings.load_full();
     let request = request.into_inner();
     let room_id = room_id.into_inner();
 
@@ -395,10 +603,24 @@ pub async fn start(
         room_id,
         request.breakout_room,
         request.resumption,
+        ClientFingerprint::of_request(
+            settings.tickets.binding,
+            &http_request,
+            &settings.http.trusted_proxies,
+        ),
     )
     .await?;
 
-    Ok(Json(StartResponse { ticket, resumption }))
+    let signaling_url = room_affinity
+        .assign(&mut redis_conn, room_id)
+        .await?
+        .map(|assignment| assignment.signaling_url);
+
+    Ok(Json(StartResponse {
+        ticket,
+        resumption,
+        signaling_url,
+    }))
 }
 
 /// The JSON body expected when making a *POST /rooms/{room_id}/start_invited*
@@ -408,6 +630,8 @@ pub struct InvitedStartRequest {
     invite_code: String,
     breakout_room: Option<BreakoutRoomId>,
     resumption: Option<ResumptionToken>,
+    /// Solution of the challenge requested with *POST /invite/challenge*, if guests have to solve one
+    challenge: Option<ChallengeSolution>,
 }
 
 /// API Endpoint *POST /rooms/{room_id}/start_invited*
@@ -415,13 +639,40 @@ pub struct InvitedStartRequest {
 /// See [`start`]
 #[post("/rooms/{room_id}/start_invited")]
 pub async fn start_invited(
+    settings: SharedSettingsActix,
     db: Data<Db>,
     redis_ctx: Data<RedisConnection>,
+    room_affinity: Data<RoomAffinity>,
     room_id: Path<RoomId>,
+    http_request: HttpRequest,
     request: Json<InvitedStartRequest>,
 ) -> Result<ApiResponse<StartResponse>, ApiError> {
-    let request = request.into_inner();
+    let settings = settings.load_full();
+    let mut request = request.into_inner();
     let room_id = room_id.into_inner();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    // Guests continuing their session solved the challenge already
+    let resuming = match &request.resumption {
+        Some(resumption) => {
+            is_valid_resumption_token(&mut redis_conn, Participant::Guest, room_id, resumption)
+                .await?
+        }
+        None => false,
+    };
+
+    if !resuming {
+        let client_ip =
+            client_ip(&http_request, &settings.http.trusted_proxies).map(|ip| ip.to_string());
+
+        guest_challenge::verify(
+            settings.guest_challenge.as_ref(),
+            &mut redis_conn,
+            request.challenge.take(),
+            client_ip.as_deref(),
+        )
+        .await?;
+    }
 
     let invite_code_as_uuid = uuid::Uuid::from_str(&request.invite_code).map_err(|_| {
         ApiError::unprocessable_entities([ValidationErrorEntry::new(
@@ -431,36 +682,57 @@ pub async fn start_invited(
         )])
     })?;
 
-    let room = crate::block(move || -> Result<db_rooms::Room, ApiError> {
-        let mut conn = db.get_conn()?;
-
-        let invite = Invite::get(&mut conn, InviteCodeId::from(invite_code_as_uuid))?;
+    // Invite codes and room passwords are guessed like with *POST /invite/verify*, so the attempts are counted the
+    // same way
+    let client = brute_force::client_subject(&http_request, &settings.http.trusted_proxies);
 
-        if !invite.active {
-            return Err(ApiError::not_found());
-        }
+    if let Some(client) = &client {
+        brute_force::check(&mut redis_conn, brute_force::Kind::InviteCode, client).await?;
+    }
 
-        if invite.room != room_id {
-            return Err(ApiError::bad_request().with_message("Room id mismatch"));
-        }
+    let room = crate::block(move || -> database::Result<Option<db_rooms::Room>> {
+        let mut conn = db.get_conn()?;
 
-        let room = Room::get(&mut conn, invite.room)?;
+        let invite = Invite::get(&mut conn, InviteCodeId::from(invite_code_as_uuid)).optional()?;
 
-        Ok(room)
+        // Do not leak the existence of the invite when it is expired, inactive or belongs to another room
+        match invite {
+            Some(invite) if invite_is_valid(&invite) && invite.room == room_id => {
+                Room::get(&mut conn, invite.room).map(Some)
+            }
+            _ => Ok(None),
+        }
     })
     .await??;
 
-    if let Some(password) = &room.password {
-        if let Some(pw) = &request.password {
-            if pw != password {
-                return Err(StartRoomError::WrongRoomPassword.into());
+    let result = match room {
+        Some(room) if room.password.is_some() && room.password != request.password => {
+            Err(StartRoomError::WrongRoomPassword.into())
+        }
+        Some(room) => Ok(room),
+        None => Err(ApiError::not_found()),
+    };
+
+    let room = match result {
+        Ok(room) => room,
+        Err(e) => {
+            if let Some(client) = &client {
+                brute_force::record_failure(
+                    &mut redis_conn,
+                    &settings.brute_force_protection,
+                    brute_force::Kind::InviteCode,
+                    client,
+                )
+                .await?;
             }
-        } else {
-            return Err(StartRoomError::WrongRoomPassword.into());
+
+            return Err(e);
         }
-    }
+    };
 
-    let mut redis_conn = (**redis_ctx).clone();
+    if let Some(client) = &client {
+        brute_force::reset(&mut redis_conn, brute_force::Kind::InviteCode, client).await?;
+    }
 
     if let Some(breakout_room) = request.breakout_room {
         let config = breakout::storage::get_config(&mut redis_conn, room.id).await?;
@@ -480,10 +752,24 @@ pub async fn start_invited(
         room_id,
         request.breakout_room,
         request.resumption,
+        ClientFingerprint::of_request(
+            settings.tickets.binding,
+            &http_request,
+            &settings.http.trusted_proxies,
+        ),
     )
     .await?;
 
-    Ok(ApiResponse::new(StartResponse { ticket, resumption }))
+    let signaling_url = room_affinity
+        .assign(&mut redis_conn, room_id)
+        .await?
+        .map(|assignment| assignment.signaling_url);
+
+    Ok(ApiResponse::new(StartResponse {
+        ticket,
+        resumption,
+        signaling_url,
+    }))
 }
 
 pub trait RoomsPoliciesBuilderExt {
@@ -517,6 +803,14 @@ where
                 room_id.resource_id().with_suffix("/assets/*"),
                 [AccessMethod::Get],
             )
+            .add_resource(
+                room_id.resource_id().with_suffix("/owners"),
+                [AccessMethod::Get],
+            )
+            .add_resource(
+                room_id.resource_id().with_suffix("/summary"),
+                [AccessMethod::Get],
+            )
     }
 
     fn room_write_access(self, room_id: RoomId) -> Self {
@@ -532,6 +826,16 @@ where
             room_id.resource_id().with_suffix("/invites/*"),
             [AccessMethod::GET, AccessMethod::PUT, AccessMethod::DELETE],
         )
+        .add_resource(
+            room_id.resource_id().with_suffix("/legal_votes/scheduled"),
+            [AccessMethod::Get, AccessMethod::Post],
+        )
+        .add_resource(
+            room_id
+                .resource_id()
+                .with_suffix("/legal_votes/scheduled/*"),
+            [AccessMethod::Delete],
+        )
         .add_resource(
             room_id.resource_id().with_suffix("/assets"),
             [AccessMethod::Delete],
@@ -540,15 +844,37 @@ where
             room_id.resource_id().with_suffix("/assets/*"),
             [AccessMethod::Delete],
         )
+        .add_resource(
+            room_id.resource_id().with_suffix("/assets/uploads"),
+            [AccessMethod::Post],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/assets/uploads/*"),
+            [AccessMethod::Put, AccessMethod::Post],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/owners/*"),
+            [AccessMethod::Put, AccessMethod::Delete],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/transfer_ownership"),
+            [AccessMethod::Post],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/media_settings"),
+            [AccessMethod::Get, AccessMethod::Put, AccessMethod::Delete],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/branding"),
+            [AccessMethod::Put, AccessMethod::Delete],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/directory_entry"),
+            [AccessMethod::Get, AccessMethod::Put, AccessMethod::Delete],
+        )
+        .add_resource(
+            room_id.resource_id().with_suffix("/media-stats"),
+            [AccessMethod::Get],
+        )
     }
 }
-
-pub(crate) fn associated_resource_ids(room_id: RoomId) -> impl IntoIterator<Item = ResourceId> {
-    [
-        ResourceId::from(format!("/room/{room_id}")),
-        ResourceId::from(format!("/room/{room_id}/invites")),
-        ResourceId::from(format!("/room/{room_id}/invites/*")),
-        ResourceId::from(format!("/room/{room_id}/start")),
-        ResourceId::from(format!("/room/{room_id}/tariff")),
-    ]
-}
@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Bots are automated participants, e.g. for captioning or note-taking
+//!
+//! They join rooms without an invite on behalf of a service account and only get the signaling modules enabled in
+//! the `bots` settings.
+use crate::api::signaling::prelude::control;
+use crate::api::signaling::ticket::start_or_continue_signaling_session;
+use crate::api::signaling::SignalingRoomId;
+use crate::api::v1::response::ApiError;
+use crate::api::Participant;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettingsActix;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{Data, Json};
+use actix_web::{get, post};
+use database::Db;
+use db_storage::events::matrix_bridges::EventMatrixBridge;
+use db_storage::rooms::Room;
+use serde::{Deserialize, Serialize};
+use types::core::{BreakoutRoomId, EventId, ResumptionToken, RoomId, TicketToken};
+
+const REQUIRED_BOT_ROLE: &str = "opentalk-bot";
+
+#[derive(Debug, Deserialize)]
+pub struct BotStartBody {
+    room_id: RoomId,
+    #[serde(default)]
+    breakout_room: Option<BreakoutRoomId>,
+    #[serde(default)]
+    resumption: Option<ResumptionToken>,
+}
+
+#[derive(Serialize)]
+pub struct BotStartResponse {
+    ticket: TicketToken,
+    resumption: ResumptionToken,
+}
+
+/// API Endpoint *POST services/bot/start*
+///
+/// Returns a ticket for a bot to join the signaling of the given room
+#[post("/start")]
+pub async fn start(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    body: Json<BotStartBody>,
+) -> Result<Json<BotStartResponse>, ApiError> {
+    if settings.load().bots.is_none() {
+        return Err(ApiError::not_found());
+    }
+
+    let mut redis_conn = (**redis_ctx).clone();
+    let body = body.into_inner();
+
+    let room = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_conn()?;
+
+        Room::get(&mut conn, body.room_id)
+    })
+    .await??;
+
+    let (ticket, resumption) = start_or_continue_signaling_session(
+        &mut redis_conn,
+        Participant::Bot,
+        room.id,
+        body.breakout_room,
+        body.resumption,
+        None,
+    )
+    .await?;
+
+    Ok(Json(BotStartResponse { ticket, resumption }))
+}
+
+/// Matrix room the chat of a room is bridged to
+#[derive(Serialize)]
+pub struct MatrixBridge {
+    event_id: EventId,
+    room_id: RoomId,
+    matrix_room_id: String,
+    /// Participants other than bots are inside the room
+    ///
+    /// The bridge only joins active rooms, so it doesn't open or keep alive rooms on its own.
+    active: bool,
+}
+
+/// API Endpoint *GET services/bot/matrix_bridges*
+///
+/// Returns all configured Matrix bridges for the Matrix bridge service
+#[get("/matrix_bridges")]
+pub async fn get_matrix_bridges(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+) -> Result<Json<Vec<MatrixBridge>>, ApiError> {
+    if settings.load().bots.is_none() {
+        return Err(ApiError::not_found());
+    }
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let bridges =
+        crate::block(move || EventMatrixBridge::get_all_with_room(&mut db.get_read_conn()?))
+            .await??;
+
+    let mut response = Vec::with_capacity(bridges.len());
+
+    for (bridge, room_id) in bridges {
+        let active = control::storage::has_present_participants(
+            &mut redis_conn,
+            SignalingRoomId(room_id, None),
+        )
+        .await?;
+
+        response.push(MatrixBridge {
+            event_id: bridge.event_id,
+            room_id,
+            matrix_room_id: bridge.matrix_room_id,
+            active,
+        });
+    }
+
+    Ok(Json(response))
+}
+
+pub fn services() -> impl HttpServiceFactory {
+    actix_web::web::scope("/bot")
+        .wrap(super::RequiredRealmRole::new(REQUIRED_BOT_ROLE))
+        .service(start)
+        .service(get_matrix_bridges)
+}
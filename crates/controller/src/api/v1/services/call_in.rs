@@ -3,13 +3,16 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::api::signaling::ticket::start_or_continue_signaling_session;
+use crate::api::v1::brute_force;
+use crate::api::v1::middleware::service_auth::ServiceClient;
 use crate::api::v1::response::ApiError;
 use crate::api::Participant;
 use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettingsActix;
 use actix_web::dev::HttpServiceFactory;
 use actix_web::error::Result;
 use actix_web::post;
-use actix_web::web::{Data, Json};
+use actix_web::web::{Data, Json, ReqData};
 use database::Db;
 use db_storage::sip_configs::SipConfig;
 use serde::{Deserialize, Serialize};
@@ -33,35 +36,66 @@ pub struct CallInStartResponse {
 /// API Endpoint *POST services/call_in/start* for the call-in service
 #[post("/start")]
 pub async fn start(
+    settings: SharedSettingsActix,
     db: Data<Db>,
     redis_ctx: Data<RedisConnection>,
+    client: ReqData<ServiceClient>,
     request: Json<CallInStartRequestBody>,
 ) -> Result<Json<CallInStartResponse>, ApiError> {
+    let settings = settings.load_full();
     let mut redis_conn = (**redis_ctx).clone();
     let request = request.into_inner();
 
     request.id.validate()?;
     request.pin.validate()?;
 
-    let room_id = crate::block(move || -> Result<RoomId, ApiError> {
+    // The attempts are counted per call-in id of the authenticated call-in gateway, the caller cannot be trusted
+    let subject = format!("client={}:id={}", client.id(), request.id);
+
+    brute_force::check(&mut redis_conn, brute_force::Kind::CallIn, &subject).await?;
+
+    let room_id = crate::block(move || -> Result<Option<RoomId>, ApiError> {
         let mut conn = db.get_conn()?;
 
         if let Some(sip_config) = SipConfig::get(&mut conn, request.id)? {
             if sip_config.password == request.pin {
-                return Ok(sip_config.room);
+                return Ok(Some(sip_config.room));
             }
         }
 
-        Err(ApiError::bad_request()
-            .with_code("invalid_credentials")
-            .with_message("given call-in id & pin combination is not valid"))
+        Ok(None)
     })
     .await??;
 
-    let (ticket, resumption) =
-        start_or_continue_signaling_session(&mut redis_conn, Participant::Sip, room_id, None, None)
+    let room_id = match room_id {
+        Some(room_id) => room_id,
+        None => {
+            brute_force::record_failure(
+                &mut redis_conn,
+                &settings.brute_force_protection,
+                brute_force::Kind::CallIn,
+                &subject,
+            )
             .await?;
 
+            return Err(ApiError::bad_request()
+                .with_code("invalid_credentials")
+                .with_message("given call-in id & pin combination is not valid"));
+        }
+    };
+
+    brute_force::reset(&mut redis_conn, brute_force::Kind::CallIn, &subject).await?;
+
+    let (ticket, resumption) = start_or_continue_signaling_session(
+        &mut redis_conn,
+        Participant::Sip,
+        room_id,
+        None,
+        None,
+        None,
+    )
+    .await?;
+
     Ok(Json(CallInStartResponse { ticket, resumption }))
 }
 
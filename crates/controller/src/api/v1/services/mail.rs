@@ -0,0 +1,235 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Endpoints for the service processing the incoming mails
+//!
+//! Invitees can respond to the invite mail with their calendar application, which sends an iCalendar `METHOD:REPLY`.
+//! The `UID` of the event in the reply must start with the id of the event, e.g. `{event_id}@example.org`.
+use crate::api::v1::response::{ApiError, NoContent};
+use actix_web::dev::HttpServiceFactory;
+use actix_web::post;
+use actix_web::web::Data;
+use chrono::Utc;
+use database::Db;
+use db_storage::events::email_invites::UpdateEventEmailInvite;
+use db_storage::events::{Event, EventInviteStatus, UpdateEventInvite};
+use db_storage::users::User;
+use types::core::EventId;
+use uuid::Uuid;
+
+pub const REQUIRED_MAIL_ROLE: &str = "opentalk-mail";
+
+/// Response of an invitee parsed from an iCalendar reply
+#[derive(Debug, PartialEq, Eq)]
+struct IcsReply {
+    event_id: EventId,
+    attendee_email: String,
+    status: EventInviteStatus,
+    comment: Option<String>,
+}
+
+/// API Endpoint *POST services/mail/ics_reply* for the mail service
+///
+/// Applies the iCalendar reply of an invitee in the body to its invite
+#[post("/ics_reply")]
+pub async fn ics_reply(db: Data<Db>, body: String) -> Result<NoContent, ApiError> {
+    let reply = parse_ics_reply(&body).map_err(|message| {
+        ApiError::bad_request()
+            .with_code("invalid_ics_reply")
+            .with_message(message)
+    })?;
+
+    crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_conn()?;
+
+        let event = Event::get(&mut conn, reply.event_id)?;
+
+        if let Some(user) = User::get_by_email(&mut conn, event.tenant_id, &reply.attendee_email)? {
+            UpdateEventInvite {
+                status: reply.status,
+                comment: reply.comment,
+                responded_at: Some(Utc::now()),
+            }
+            .apply(&mut conn, user.id, event.id)?;
+        } else {
+            UpdateEventEmailInvite {
+                status: reply.status,
+                comment: reply.comment,
+                responded_at: Some(Utc::now()),
+            }
+            .apply(&mut conn, event.id, &reply.attendee_email)?;
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+/// Parse the first event of an iCalendar reply
+fn parse_ics_reply(ics: &str) -> Result<IcsReply, &'static str> {
+    // Unfold the content lines, long lines are continued on the next line starting with a space or tab
+    let ics = ics
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut is_reply = false;
+    let mut in_event = false;
+    let mut event_id = None;
+    let mut attendee = None;
+    let mut comment = None;
+
+    for line in ics.lines() {
+        let (name, params, value) = match split_content_line(line) {
+            Some(content_line) => content_line,
+            None => continue,
+        };
+
+        match name.to_ascii_uppercase().as_str() {
+            "METHOD" => is_reply = value.eq_ignore_ascii_case("REPLY"),
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => in_event = true,
+            "END" if value.eq_ignore_ascii_case("VEVENT") => break,
+            "UID" if in_event => {
+                let id = value.split('@').next().unwrap_or_default();
+                event_id = Uuid::parse_str(id).ok().map(EventId::from);
+            }
+            "ATTENDEE" if in_event => {
+                let status = params
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("PARTSTAT"))
+                    .and_then(|(_, value)| match value.to_ascii_uppercase().as_str() {
+                        "ACCEPTED" => Some(EventInviteStatus::Accepted),
+                        "TENTATIVE" => Some(EventInviteStatus::Tentative),
+                        "DECLINED" => Some(EventInviteStatus::Declined),
+                        _ => None,
+                    });
+
+                let email = value
+                    .get(..7)
+                    .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+                    .map(|_| value[7..].to_owned());
+
+                attendee = email.zip(status);
+            }
+            "COMMENT" if in_event => comment = Some(unescape_text(value)),
+            _ => {}
+        }
+    }
+
+    if !is_reply {
+        return Err("Not an iCalendar reply");
+    }
+
+    let event_id = event_id.ok_or("Missing or invalid UID of the event")?;
+    let (attendee_email, status) =
+        attendee.ok_or("Missing attendee or participation status of the attendee")?;
+
+    Ok(IcsReply {
+        event_id,
+        attendee_email,
+        status,
+        comment: comment.filter(|comment| !comment.is_empty()),
+    })
+}
+
+/// Split a content line into its name, parameters and value
+fn split_content_line(line: &str) -> Option<(&str, Vec<(&str, &str)>, &str)> {
+    // The value starts after the first colon outside of quoted parameter values
+    let mut quoted = false;
+    let value_start = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+
+    let (name_and_params, value) = (&line[..value_start], &line[value_start + 1..]);
+
+    let mut parts = name_and_params.split(';');
+    let name = parts.next()?;
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name, value.trim_matches('"')))
+        .collect();
+
+    Some((name, params, value))
+}
+
+/// Unescape a value of the TEXT type
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => {}
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    unescaped
+}
+
+pub fn services() -> impl HttpServiceFactory {
+    actix_web::web::scope("/mail")
+        .wrap(super::RequiredRealmRole::new(REQUIRED_MAIL_ROLE))
+        .service(ics_reply)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reply() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   VERSION:2.0\r\n\
+                   METHOD:REPLY\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:00000000-0000-0000-0000-000000000001@example.org\r\n\
+                   ATTENDEE;CN=\"Doe: Jane\";PARTSTAT=TENTATIVE:mailto:jane\r\n \
+                   @example.org\r\n\
+                   COMMENT:Running late\\, will join\\nafter lunch\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+
+        assert_eq!(
+            parse_ics_reply(ics),
+            Ok(IcsReply {
+                event_id: EventId::from(Uuid::from_u128(1)),
+                attendee_email: "jane@example.org".into(),
+                status: EventInviteStatus::Tentative,
+                comment: Some("Running late, will join\nafter lunch".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_replies() {
+        let request = "METHOD:REQUEST\n\
+                       BEGIN:VEVENT\n\
+                       UID:00000000-0000-0000-0000-000000000001\n\
+                       ATTENDEE;PARTSTAT=ACCEPTED:mailto:jane@example.org\n\
+                       END:VEVENT\n";
+        assert!(parse_ics_reply(request).is_err());
+
+        let needs_action = "METHOD:REPLY\n\
+                            BEGIN:VEVENT\n\
+                            UID:00000000-0000-0000-0000-000000000001\n\
+                            ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:jane@example.org\n\
+                            END:VEVENT\n";
+        assert!(parse_ics_reply(needs_action).is_err());
+    }
+}
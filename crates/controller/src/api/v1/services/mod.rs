@@ -12,16 +12,18 @@ use core::task::{Context, Poll};
 use futures::future::Either;
 use std::rc::Rc;
 
+pub mod bot;
 pub mod call_in;
+pub mod mail;
 pub mod recording;
 
 /// Middleware factory for [`RequiredRealmRoleMiddleware`]
-struct RequiredRealmRole {
+pub(super) struct RequiredRealmRole {
     role: Rc<str>,
 }
 
 impl RequiredRealmRole {
-    fn new(role: impl Into<Rc<str>>) -> Self {
+    pub(super) fn new(role: impl Into<Rc<str>>) -> Self {
         Self { role: role.into() }
     }
 }
@@ -48,7 +50,7 @@ where
 /// Checks if the request has [`RealmRoles`] and they contain a certain role
 ///
 /// If it doesn't contain the required role, it returns a [`ApiError::unauthorized`]
-struct RequiredRealmRoleMiddleware<S> {
+pub(super) struct RequiredRealmRoleMiddleware<S> {
     service: S,
     role: Rc<str>,
 }
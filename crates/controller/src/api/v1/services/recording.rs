@@ -2,30 +2,49 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::api::signaling::prelude::{breakout, control};
 use crate::api::signaling::ticket::start_or_continue_signaling_session;
+use crate::api::v1::assets::map_store_asset_error;
 use crate::api::v1::response::ApiError;
 use crate::api::v1::response::NoContent;
+use crate::api::v1::rooms::StartRoomError;
 use crate::api::Participant;
 use crate::redis_wrapper::RedisConnection;
-use crate::settings::SharedSettingsActix;
-use crate::storage::assets::save_asset;
+use crate::services::NotificationService;
+use crate::settings::{NotificationEvent, SharedSettingsActix};
+use crate::storage::assets::{get_asset_url, save_asset};
 use crate::storage::ObjectStorage;
 use actix_web::dev::HttpServiceFactory;
 use actix_web::post;
 use actix_web::web::Payload;
 use actix_web::web::Query;
 use actix_web::web::{Data, Json};
+use anyhow::Context;
+use chrono::Utc;
 use database::Db;
+use db_storage::recording_transcriptions::{
+    NewRecordingTranscription, RecordingTranscription, TranscriptionStatus,
+    UpdateRecordingTranscription,
+};
+use db_storage::room_markers;
+use db_storage::room_owners::RoomOwner;
 use db_storage::rooms::Room;
 use futures::TryStreamExt;
+use lapin::options::ExchangeDeclareOptions;
+use lapin::{BasicProperties, ExchangeKind};
+use lapin_pool::RabbitMqPool;
 use serde::{Deserialize, Serialize};
-use types::core::{ResumptionToken, RoomId, TicketToken};
+use types::core::{AssetId, BreakoutRoomId, ResumptionToken, RoomId, TicketToken, Timestamp};
+use types::signaling::NamespacedCommand;
 
 const REQUIRED_RECORDING_ROLE: &str = "opentalk-recorder";
 
 #[derive(Debug, Deserialize)]
 pub struct RecorderStartBody {
     room_id: RoomId,
+    /// Record the breakout room instead of the main room
+    #[serde(default)]
+    breakout_room: Option<BreakoutRoomId>,
 }
 
 #[derive(Serialize)]
@@ -56,10 +75,23 @@ pub async fn start(
     })
     .await??;
 
+    if let Some(breakout_room) = body.breakout_room {
+        let config = breakout::storage::get_config(&mut redis_conn, room.id).await?;
+
+        if let Some(config) = config {
+            if !config.is_valid_id(breakout_room) {
+                return Err(StartRoomError::InvalidBreakoutRoomId.into());
+            }
+        } else {
+            return Err(StartRoomError::NoBreakoutRooms.into());
+        }
+    }
+
     let (ticket, resumption) = start_or_continue_signaling_session(
         &mut redis_conn,
         Participant::Recorder,
         room.id,
+        body.breakout_room,
         None,
         None,
     )
@@ -71,18 +103,25 @@ pub async fn start(
 #[derive(Deserialize)]
 pub struct UploadRenderQuery {
     room_id: RoomId,
+    /// The breakout room of the recording, the asset is listed with the assets of the main room
+    breakout_room: Option<BreakoutRoomId>,
     filename: String,
 }
 
 #[post("/upload_render")]
 pub async fn upload_render(
+    settings: SharedSettingsActix,
     storage: Data<ObjectStorage>,
     db: Data<Db>,
+    rabbitmq_pool: Data<RabbitMqPool>,
+    notifications: Data<NotificationService>,
     query: Query<UploadRenderQuery>,
     data: Payload,
 ) -> Result<NoContent, ApiError> {
+    let settings = settings.load_full();
+
     // Assert that the room exists
-    crate::block({
+    let room = crate::block({
         let db = db.clone();
         let room_id = query.room_id;
         move || {
@@ -93,23 +132,357 @@ pub async fn upload_render(
     })
     .await??;
 
-    save_asset(
+    let asset_id = save_asset(
         &storage,
-        db.into_inner(),
+        db.clone().into_inner(),
         query.room_id,
+        query.breakout_room,
         Some("recording"),
         &query.filename,
         "recording-render",
         data.into_stream().map_err(anyhow::Error::from),
     )
+    .await
+    .map_err(map_store_asset_error)?;
+
+    // The markers set while recording are the chapters of the recording
+    crate::block({
+        let db = db.clone();
+        let room_id = query.room_id;
+        let breakout_room = query.breakout_room;
+        move || {
+            let mut conn = db.get_conn()?;
+
+            room_markers::link_to_recording(&mut conn, room_id, breakout_room, asset_id)
+        }
+    })
+    .await??;
+
+    notifications.notify(
+        NotificationEvent::RecordingAvailable,
+        query.room_id,
+        vec![("filename", query.filename.clone())],
+    );
+
+    if let Some(queue) = &settings.rabbit_mq.transcription_task_queue {
+        // The recording is stored regardless, the owners are able to download it without the transcript
+        if let Err(e) = enqueue_transcription(
+            &storage,
+            db.clone(),
+            &rabbitmq_pool,
+            queue,
+            room,
+            asset_id,
+            query.filename.clone(),
+        )
+        .await
+        {
+            log::error!("Failed to enqueue transcription of recording {asset_id}, {e:?}");
+        }
+    }
+
+    Ok(NoContent)
+}
+
+/// Task published to the transcription queue for every stored recording
+#[derive(Debug, Serialize)]
+pub struct TranscriptionTask {
+    recording_id: AssetId,
+    room_id: RoomId,
+    filename: String,
+    /// Pre-signed URL to download the recording from the object storage
+    url: String,
+    url_expires_at: Timestamp,
+    /// Locale of the room, a hint for the language spoken in the recording
+    locale: Option<String>,
+}
+
+async fn enqueue_transcription(
+    storage: &ObjectStorage,
+    db: Data<Db>,
+    rabbitmq_pool: &RabbitMqPool,
+    queue: &str,
+    room: Room,
+    recording_id: AssetId,
+    filename: String,
+) -> anyhow::Result<()> {
+    let (url, lifetime) = get_asset_url(storage, &recording_id).await?;
+    let url_expires_at = Timestamp::from(Utc::now() + chrono::Duration::from_std(lifetime)?);
+
+    let room_id = room.id;
+
+    crate::block({
+        let db = db.clone();
+        move || {
+            let mut conn = db.get_conn()?;
+
+            NewRecordingTranscription {
+                recording_id,
+                room_id,
+                status: TranscriptionStatus::Queued,
+            }
+            .insert(&mut conn)
+        }
+    })
+    .await??;
+
+    let task = TranscriptionTask {
+        recording_id,
+        room_id,
+        filename,
+        url,
+        url_expires_at,
+        locale: room.locale,
+    };
+
+    let channel = rabbitmq_pool
+        .create_channel()
+        .await
+        .context("Failed to create rabbitmq channel")?;
+
+    channel
+        .basic_publish(
+            "",
+            queue,
+            Default::default(),
+            &serde_json::to_vec(&task).context("Failed to serialize transcription task")?,
+            Default::default(),
+        )
+        .await
+        .context("Failed to publish transcription task")?;
+
+    publish_transcription_status(
+        db,
+        rabbitmq_pool,
+        room_id,
+        recording_id,
+        TranscriptionStatus::Queued,
+    )
+    .await
+}
+
+/// Send the status of the transcription to all owners of the room which are currently inside the room
+async fn publish_transcription_status(
+    db: Data<Db>,
+    rabbitmq_pool: &RabbitMqPool,
+    room_id: RoomId,
+    recording_id: AssetId,
+    status: TranscriptionStatus,
+) -> anyhow::Result<()> {
+    let owners = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        RoomOwner::get_ids_for_room(&mut conn, room_id)
+    })
+    .await??;
+
+    let channel = rabbitmq_pool
+        .create_channel()
+        .await
+        .context("Failed to create rabbitmq channel")?;
+
+    let exchange = breakout::rabbitmq::global_exchange_name(room_id);
+
+    channel
+        .exchange_declare(
+            &exchange,
+            ExchangeKind::Topic,
+            ExchangeDeclareOptions {
+                auto_delete: true,
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .await
+        .context("Failed to declare the global room exchange")?;
+
+    let message = serde_json::to_vec(&NamespacedCommand {
+        namespace: control::NAMESPACE,
+        payload: control::rabbitmq::Message::TranscriptionUpdated {
+            recording_id,
+            status,
+        },
+    })?;
+
+    for owner in owners {
+        let properties =
+            BasicProperties::default().with_timestamp(Timestamp::now().timestamp() as u64);
+
+        channel
+            .basic_publish(
+                &exchange,
+                &control::rabbitmq::room_user_routing_key(owner),
+                Default::default(),
+                &message,
+                properties,
+            )
+            .await
+            .context("Failed to publish transcription status")?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptionStatusBody {
+    recording_id: AssetId,
+    status: TranscriptionStatus,
+    /// Reason of the failure, only stored if the status is `failed`
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// API Endpoint *POST /services/recording/transcription_status*
+///
+/// Used by the transcription worker to report the progress of a transcription task. The owners of the room are
+/// notified about the new status.
+#[post("/transcription_status")]
+pub async fn transcription_status(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    rabbitmq_pool: Data<RabbitMqPool>,
+    body: Json<TranscriptionStatusBody>,
+) -> Result<NoContent, ApiError> {
+    let settings = settings.load_full();
+    if settings.rabbit_mq.transcription_task_queue.is_none() {
+        return Err(ApiError::not_found());
+    }
+
+    let body = body.into_inner();
+
+    if body.status == TranscriptionStatus::Queued {
+        return Err(ApiError::bad_request()
+            .with_code("invalid_status")
+            .with_message("The status of a transcription cannot be reset to queued"));
+    }
+
+    let error = if body.status == TranscriptionStatus::Failed {
+        body.error
+    } else {
+        None
+    };
+
+    let transcription = crate::block({
+        let db = db.clone();
+        move || {
+            let mut conn = db.get_conn()?;
+
+            // Fails with 404 Not Found if the recording is not transcribed
+            RecordingTranscription::get(&mut conn, body.recording_id)?;
+
+            UpdateRecordingTranscription {
+                status: Some(body.status),
+                transcript_asset_id: None,
+                chapters_asset_id: None,
+                error: Some(error),
+                updated_at: Utc::now(),
+            }
+            .apply(&mut conn, body.recording_id)
+        }
+    })
+    .await??;
+
+    publish_transcription_status(
+        db,
+        &rabbitmq_pool,
+        transcription.room_id,
+        transcription.recording_id,
+        transcription.status,
+    )
     .await?;
 
     Ok(NoContent)
 }
 
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptKind {
+    Transcript,
+    Chapters,
+}
+
+#[derive(Deserialize)]
+pub struct UploadTranscriptQuery {
+    recording_id: AssetId,
+    kind: TranscriptKind,
+    filename: String,
+}
+
+/// API Endpoint *POST /services/recording/upload_transcript*
+///
+/// Used by the transcription worker to upload the transcript or the chapters of a recording. The file is stored as
+/// asset of the room and linked to the recording, a previously uploaded file of the same kind is replaced.
+#[post("/upload_transcript")]
+pub async fn upload_transcript(
+    settings: SharedSettingsActix,
+    storage: Data<ObjectStorage>,
+    db: Data<Db>,
+    query: Query<UploadTranscriptQuery>,
+    data: Payload,
+) -> Result<NoContent, ApiError> {
+    let settings = settings.load_full();
+    if settings.rabbit_mq.transcription_task_queue.is_none() {
+        return Err(ApiError::not_found());
+    }
+
+    let query = query.into_inner();
+
+    let transcription = crate::block({
+        let db = db.clone();
+        let recording_id = query.recording_id;
+        move || {
+            let mut conn = db.get_conn()?;
+
+            RecordingTranscription::get(&mut conn, recording_id)
+        }
+    })
+    .await??;
+
+    let kind = match query.kind {
+        TranscriptKind::Transcript => "recording-transcript",
+        TranscriptKind::Chapters => "recording-chapters",
+    };
+
+    let asset_id = save_asset(
+        &storage,
+        db.clone().into_inner(),
+        transcription.room_id,
+        None,
+        Some("recording"),
+        &query.filename,
+        kind,
+        data.into_stream().map_err(anyhow::Error::from),
+    )
+    .await
+    .map_err(map_store_asset_error)?;
+
+    let (transcript_asset_id, chapters_asset_id) = match query.kind {
+        TranscriptKind::Transcript => (Some(Some(asset_id)), None),
+        TranscriptKind::Chapters => (None, Some(Some(asset_id))),
+    };
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        UpdateRecordingTranscription {
+            status: None,
+            transcript_asset_id,
+            chapters_asset_id,
+            error: None,
+            updated_at: Utc::now(),
+        }
+        .apply(&mut conn, transcription.recording_id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
 pub fn services() -> impl HttpServiceFactory {
     actix_web::web::scope("/recording")
         .wrap(super::RequiredRealmRole::new(REQUIRED_RECORDING_ROLE))
         .service(start)
         .service(upload_render)
+        .service(transcription_status)
+        .service(upload_transcript)
 }
@@ -0,0 +1,398 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Usage statistics for administrators
+//!
+//! The endpoints are only accessible by service accounts with the `opentalk-statistics` realm role.
+use super::response::ApiError;
+use super::services::RequiredRealmRole;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::get;
+use actix_web::web::{Data, Json, Query};
+use chrono::{DateTime, NaiveDate, Utc};
+use database::{Db, DbConnection};
+use db_storage::call_feedback::{self, CallFeedback, IssueCount, RatingCount};
+use db_storage::room_statistics::{self, ModuleUsage, RoomStatistics};
+use db_storage::tenants::{OidcTenantId, Tenant};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use types::core::{RoomId, TenantId};
+
+const REQUIRED_STATISTICS_ROLE: &str = "opentalk-statistics";
+
+#[derive(Debug, Deserialize)]
+pub struct StatisticsQuery {
+    /// OIDC id of the tenant whose statistics are requested
+    tenant: OidcTenantId,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+/// Aggregated usage of all room sessions of a tenant which started inside the requested time range
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct RoomStatisticsResource {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub sessions: i64,
+    pub participant_minutes: i64,
+    /// Highest number of participants in a single session
+    pub peak_participants: i32,
+    /// Number of sessions each module was used in
+    pub modules: BTreeMap<String, i64>,
+    pub rooms: Vec<RoomUsage>,
+}
+
+/// Aggregated usage of a single room
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct RoomUsage {
+    pub room_id: RoomId,
+    pub sessions: i64,
+    pub participant_minutes: i64,
+    pub peak_participants: i32,
+    pub last_ended_at: DateTime<Utc>,
+}
+
+impl From<room_statistics::RoomUsage> for RoomUsage {
+    fn from(usage: room_statistics::RoomUsage) -> Self {
+        Self {
+            room_id: usage.room_id,
+            sessions: usage.sessions,
+            participant_minutes: usage.participant_minutes,
+            peak_participants: usage.peak_participants,
+            last_ended_at: usage.last_ended_at,
+        }
+    }
+}
+
+/// Aggregated call feedback of a tenant which was submitted inside the requested time range
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FeedbackStatisticsResource {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub submissions: i64,
+    /// Unset if no feedback was submitted
+    pub average_rating: Option<f64>,
+    /// Number of submissions with each rating
+    pub ratings: BTreeMap<i16, i64>,
+    /// Number of submissions which reported each issue
+    pub issues: BTreeMap<String, i64>,
+    /// Feedback of every day with submissions, oldest first
+    pub days: Vec<FeedbackDay>,
+}
+
+/// Aggregated call feedback of a single day (UTC)
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FeedbackDay {
+    pub date: NaiveDate,
+    pub submissions: i64,
+    pub average_rating: f64,
+    /// Number of media connections of the submitting participants which went down
+    pub webrtc_down_count: i64,
+    /// Number of times lost packets were detected on the media connections of the submitting participants
+    pub slow_link_count: i64,
+}
+
+impl StatisticsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.from >= self.to {
+            return Err(ApiError::bad_request().with_message("`from` must be before `to`"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the id of the tenant with the given OIDC id
+fn get_tenant_id(conn: &mut DbConnection, tenant: OidcTenantId) -> Result<TenantId, ApiError> {
+    Tenant::get_by_oidc_id(conn, tenant)?
+        .map(|tenant| tenant.id)
+        .ok_or_else(|| ApiError::not_found().with_message("Unknown tenant"))
+}
+
+/// API Endpoint *GET /statistics/rooms*
+///
+/// Returns the aggregated usage of all rooms of the tenant given by the `tenant` query parameter in the time range
+/// given by the `from` and `to` query parameters
+#[get("/rooms")]
+pub async fn get_room_statistics(
+    db: Data<Db>,
+    query: Query<StatisticsQuery>,
+) -> Result<Json<RoomStatisticsResource>, ApiError> {
+    let query = query.into_inner();
+    query.validate()?;
+
+    let StatisticsQuery { tenant, from, to } = query;
+
+    let (rooms, modules) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        let tenant_id = get_tenant_id(&mut conn, tenant)?;
+
+        let rooms = RoomStatistics::get_room_usage_started_between(&mut conn, tenant_id, from, to)?;
+        let modules =
+            RoomStatistics::get_module_usage_started_between(&mut conn, tenant_id, from, to)?;
+
+        Ok((rooms, modules))
+    })
+    .await??;
+
+    Ok(Json(room_statistics_resource(from, to, rooms, modules)))
+}
+
+/// Build the resource from the usage of the rooms, which has been aggregated by the database
+fn room_statistics_resource(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    rooms: Vec<room_statistics::RoomUsage>,
+    modules: Vec<ModuleUsage>,
+) -> RoomStatisticsResource {
+    RoomStatisticsResource {
+        from,
+        to,
+        sessions: rooms.iter().map(|room| room.sessions).sum(),
+        participant_minutes: rooms.iter().map(|room| room.participant_minutes).sum(),
+        peak_participants: rooms
+            .iter()
+            .map(|room| room.peak_participants)
+            .max()
+            .unwrap_or_default(),
+        modules: modules
+            .into_iter()
+            .map(|usage| (usage.module, usage.sessions))
+            .collect(),
+        rooms: rooms.into_iter().map(RoomUsage::from).collect(),
+    }
+}
+
+/// API Endpoint *GET /statistics/feedback*
+///
+/// Returns the aggregated call feedback submitted in the rooms of the tenant given by the `tenant` query parameter in
+/// the time range given by the `from` and `to` query parameters
+#[get("/feedback")]
+pub async fn get_feedback_statistics(
+    db: Data<Db>,
+    query: Query<StatisticsQuery>,
+) -> Result<Json<FeedbackStatisticsResource>, ApiError> {
+    let query = query.into_inner();
+    query.validate()?;
+
+    let StatisticsQuery { tenant, from, to } = query;
+
+    let (days, ratings, issues) = crate::block(move || -> Result<_, ApiError> {
+        let mut conn = db.get_read_conn()?;
+
+        let tenant_id = get_tenant_id(&mut conn, tenant)?;
+
+        let days = CallFeedback::get_days_created_between(&mut conn, tenant_id, from, to)?;
+        let ratings =
+            CallFeedback::get_rating_counts_created_between(&mut conn, tenant_id, from, to)?;
+        let issues =
+            CallFeedback::get_issue_counts_created_between(&mut conn, tenant_id, from, to)?;
+
+        Ok((days, ratings, issues))
+    })
+    .await??;
+
+    Ok(Json(feedback_statistics_resource(
+        from, to, days, ratings, issues,
+    )))
+}
+
+/// Build the resource from the feedback, which has been aggregated by the database
+fn feedback_statistics_resource(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    days: Vec<call_feedback::FeedbackDay>,
+    ratings: Vec<RatingCount>,
+    issues: Vec<IssueCount>,
+) -> FeedbackStatisticsResource {
+    let submissions: i64 = days.iter().map(|day| day.submissions).sum();
+    let rating_sum: i64 = days.iter().map(|day| day.rating_sum).sum();
+
+    FeedbackStatisticsResource {
+        from,
+        to,
+        submissions,
+        average_rating: (submissions > 0).then(|| rating_sum as f64 / submissions as f64),
+        ratings: ratings
+            .into_iter()
+            .map(|count| (count.rating, count.submissions))
+            .collect(),
+        issues: issues
+            .into_iter()
+            .map(|count| (count.issue, count.submissions))
+            .collect(),
+        days: days
+            .into_iter()
+            .map(|day| FeedbackDay {
+                date: day.date,
+                submissions: day.submissions,
+                average_rating: day.rating_sum as f64 / day.submissions as f64,
+                webrtc_down_count: day.webrtc_down_count,
+                slow_link_count: day.slow_link_count,
+            })
+            .collect(),
+    }
+}
+
+pub fn services() -> impl HttpServiceFactory {
+    actix_web::web::scope("")
+        .wrap(RequiredRealmRole::new(REQUIRED_STATISTICS_ROLE))
+        .service(get_room_statistics)
+        .service(get_feedback_statistics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    fn room_usage(
+        room_id: u128,
+        sessions: i64,
+        minutes: i64,
+        peak: i32,
+    ) -> room_statistics::RoomUsage {
+        room_statistics::RoomUsage {
+            room_id: RoomId::from(Uuid::from_u128(room_id)),
+            sessions,
+            participant_minutes: minutes,
+            peak_participants: peak,
+            last_ended_at: Utc.with_ymd_and_hms(2023, 1, 1, 11, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn room_statistics_totals() {
+        let from = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 2, 1, 0, 0, 0).unwrap();
+
+        let resource = room_statistics_resource(
+            from,
+            to,
+            vec![room_usage(2, 1, 120, 8), room_usage(1, 2, 70, 5)],
+            vec![
+                ModuleUsage {
+                    module: "chat".into(),
+                    sessions: 1,
+                },
+                ModuleUsage {
+                    module: "media".into(),
+                    sessions: 3,
+                },
+            ],
+        );
+
+        assert_eq!(resource.sessions, 3);
+        assert_eq!(resource.participant_minutes, 190);
+        assert_eq!(resource.peak_participants, 8);
+        assert_eq!(
+            resource.modules,
+            BTreeMap::from([("chat".to_string(), 1), ("media".to_string(), 3)])
+        );
+
+        let usage: Vec<_> = resource
+            .rooms
+            .iter()
+            .map(|room| (room.room_id, room.sessions, room.participant_minutes))
+            .collect();
+
+        assert_eq!(
+            usage,
+            vec![
+                (RoomId::from(Uuid::from_u128(2)), 1, 120),
+                (RoomId::from(Uuid::from_u128(1)), 2, 70)
+            ]
+        );
+
+        let empty = room_statistics_resource(from, to, vec![], vec![]);
+        assert_eq!(empty.sessions, 0);
+        assert_eq!(empty.peak_participants, 0);
+    }
+
+    #[test]
+    fn feedback_statistics_totals() {
+        let from = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 2, 1, 0, 0, 0).unwrap();
+
+        let resource = feedback_statistics_resource(
+            from,
+            to,
+            vec![
+                call_feedback::FeedbackDay {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                    submissions: 2,
+                    rating_sum: 7,
+                    webrtc_down_count: 3,
+                    slow_link_count: 0,
+                },
+                call_feedback::FeedbackDay {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+                    submissions: 1,
+                    rating_sum: 4,
+                    webrtc_down_count: 1,
+                    slow_link_count: 0,
+                },
+            ],
+            vec![
+                RatingCount {
+                    rating: 2,
+                    submissions: 1,
+                },
+                RatingCount {
+                    rating: 4,
+                    submissions: 1,
+                },
+                RatingCount {
+                    rating: 5,
+                    submissions: 1,
+                },
+            ],
+            vec![
+                IssueCount {
+                    issue: "audio".into(),
+                    submissions: 2,
+                },
+                IssueCount {
+                    issue: "connection".into(),
+                    submissions: 1,
+                },
+            ],
+        );
+
+        assert_eq!(resource.submissions, 3);
+        assert_eq!(resource.average_rating, Some(11.0 / 3.0));
+        assert_eq!(resource.ratings, BTreeMap::from([(2, 1), (4, 1), (5, 1)]));
+        assert_eq!(
+            resource.issues,
+            BTreeMap::from([("audio".to_string(), 2), ("connection".to_string(), 1)])
+        );
+        assert_eq!(
+            resource.days,
+            vec![
+                FeedbackDay {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                    submissions: 2,
+                    average_rating: 3.5,
+                    webrtc_down_count: 3,
+                    slow_link_count: 0,
+                },
+                FeedbackDay {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+                    submissions: 1,
+                    average_rating: 4.0,
+                    webrtc_down_count: 1,
+                    slow_link_count: 0,
+                },
+            ]
+        );
+
+        assert_eq!(
+            feedback_statistics_resource(from, to, vec![], vec![], vec![]).average_rating,
+            None
+        );
+    }
+}
@@ -5,10 +5,11 @@
 use std::collections::{HashMap, HashSet};
 
 use db_storage::tariffs::Tariff;
+use schemars::JsonSchema;
 use serde::Serialize;
 use types::core::TariffId;
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct TariffResource {
     pub id: TariffId,
     pub name: String,
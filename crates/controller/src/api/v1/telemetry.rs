@@ -0,0 +1,255 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Ingestion of WebRTC statistics of the clients
+//!
+//! Clients periodically submit batches of snapshots taken from `RTCPeerConnection.getStats()`. They are stored to
+//! correlate the packet loss seen by the clients with the `slow_link` events of the media server. Only the configured
+//! share of the batches is stored, the endpoint is disabled when `[telemetry]` is not configured.
+use super::response::{ApiError, NoContent};
+use super::turn::{belongs_to_requester, check_access_token_or_invite};
+use crate::api::signaling::resumption::{ResumptionData, ResumptionRedisKey};
+use crate::oidc::OidcContext;
+use crate::redis_encryption::Encrypted;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Json};
+use actix_web::{post, HttpRequest};
+use database::Db;
+use db_storage::rooms::Room;
+use db_storage::webrtc_stats::{insert_batch, NewWebRtcStats};
+use either::Either;
+use kustos::prelude::*;
+use kustos::Authz;
+use rand::Rng;
+use redis::AsyncCommands;
+use serde::Deserialize;
+use types::core::{ParticipantId, ResumptionToken, RoomId, Timestamp};
+use uuid::Uuid;
+
+/// Batch of snapshots of the media connections of a participant
+#[derive(Debug, Deserialize)]
+pub struct PostWebRtcStatsBody {
+    pub room_id: RoomId,
+    /// Id of the participant in the signaling session
+    pub participant_id: Uuid,
+    /// Resumption token of the participant, proves that the participant was joined by the requester
+    pub resumption: ResumptionToken,
+    pub snapshots: Vec<WebRtcStatsSnapshot>,
+}
+
+/// Statistics of a single media connection at a point in time
+#[derive(Debug, Deserialize)]
+pub struct WebRtcStatsSnapshot {
+    /// Time the snapshot was taken
+    pub timestamp: Timestamp,
+    pub media_session_type: MediaSessionType,
+    /// Participant publishing the media, the participant itself for upstream connections
+    pub source: Uuid,
+    pub direction: LinkDirection,
+    /// Number of packets sent or received so far
+    pub packets: u64,
+    /// Number of packets lost so far
+    pub packets_lost: u64,
+    #[serde(default)]
+    pub jitter_ms: Option<f64>,
+    #[serde(default)]
+    pub round_trip_time_ms: Option<f64>,
+    #[serde(default)]
+    pub bitrate_kbps: Option<f64>,
+}
+
+/// Named like the media sessions of the media module
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaSessionType {
+    Video,
+    Screen,
+}
+
+impl MediaSessionType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Screen => "screen",
+        }
+    }
+}
+
+/// Named like the directions of the `slow_link` events of the media server
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkDirection {
+    Upstream,
+    Downstream,
+}
+
+impl LinkDirection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Upstream => "upstream",
+            Self::Downstream => "downstream",
+        }
+    }
+}
+
+impl PostWebRtcStatsBody {
+    fn into_new_stats(self) -> Vec<NewWebRtcStats> {
+        let room_id = self.room_id;
+        let participant_id = self.participant_id;
+
+        // Drop values a client could not have measured
+        let valid = |value: Option<f64>| value.filter(|value| value.is_finite() && *value >= 0.0);
+
+        self.snapshots
+            .into_iter()
+            .map(|snapshot| NewWebRtcStats {
+                recorded_at: *snapshot.timestamp,
+                room_id,
+                participant_id,
+                media_session_type: snapshot.media_session_type.as_str().into(),
+                source: snapshot.source,
+                direction: snapshot.direction.as_str().into(),
+                packets: snapshot.packets.try_into().unwrap_or(i64::MAX),
+                packets_lost: snapshot.packets_lost.try_into().unwrap_or(i64::MAX),
+                jitter_ms: valid(snapshot.jitter_ms),
+                round_trip_time_ms: valid(snapshot.round_trip_time_ms),
+                bitrate_kbps: valid(snapshot.bitrate_kbps),
+            })
+            .collect()
+    }
+}
+
+/// API Endpoint *POST /telemetry/webrtc*
+///
+/// Stores the batch of WebRTC statistics snapshots of the participant, if it is sampled. Accepts access tokens and
+/// invite codes like the *GET /turn* endpoint. Users must have access to the room, invite codes can only submit
+/// statistics of their room. The resumption token must belong to the participant and the requester.
+///
+/// Returns 404 Not Found if telemetry is not configured and 400 Bad Request if the batch is empty or too large.
+#[post("/telemetry/webrtc")]
+pub async fn post_webrtc(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    authz: Data<Authz>,
+    oidc_ctx: Data<OidcContext>,
+    req: HttpRequest,
+    body: Json<PostWebRtcStatsBody>,
+) -> Result<NoContent, ApiError> {
+    let settings = settings.load_full();
+    let body = body.into_inner();
+
+    let telemetry = settings
+        .telemetry
+        .as_ref()
+        .ok_or_else(ApiError::not_found)?;
+
+    let requester = check_access_token_or_invite(&settings, &req, db.clone(), oidc_ctx).await?;
+
+    match &requester {
+        Either::Left(user) => {
+            let start_resource = body.room_id.resource_id().with_suffix("/start");
+
+            if !authz
+                .check(user.id, start_resource, AccessMethod::Post)
+                .await?
+            {
+                return Err(ApiError::forbidden());
+            }
+        }
+        Either::Right(invite) => {
+            if invite.room != body.room_id {
+                return Err(ApiError::forbidden());
+            }
+        }
+    }
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let data: Option<Encrypted<ResumptionData>> = redis_conn
+        .get(ResumptionRedisKey(body.resumption))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get the resumption data, {}", e);
+            ApiError::internal()
+        })?;
+
+    match data {
+        Some(Encrypted(data))
+            if belongs_to_requester(&data, &requester)
+                && data.room == body.room_id
+                && data.participant_id == ParticipantId::from(body.participant_id) => {}
+        _ => return Err(ApiError::forbidden().with_code("invalid_resumption_token")),
+    }
+
+    if body.snapshots.is_empty() || body.snapshots.len() > telemetry.max_batch_size {
+        return Err(ApiError::bad_request()
+            .with_code("invalid_batch_size")
+            .with_message(format!(
+                "A batch must contain between 1 and {} snapshots",
+                telemetry.max_batch_size
+            )));
+    }
+
+    if !rand::thread_rng().gen_bool(telemetry.sample_rate.clamp(0.0, 1.0)) {
+        return Ok(NoContent);
+    }
+
+    let room_id = body.room_id;
+    let stats = body.into_new_stats();
+
+    crate::block(move || -> Result<(), ApiError> {
+        let mut conn = db.get_conn()?;
+
+        // Fails with 404 Not Found if the room does not exist
+        Room::get(&mut conn, room_id)?;
+
+        insert_batch(&mut conn, &stats)?;
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn convert_snapshots() {
+        let body: PostWebRtcStatsBody = serde_json::from_value(serde_json::json!({
+            "room_id": "00000000-0000-0000-0000-000000000001",
+            "participant_id": "00000000-0000-0000-0000-000000000002",
+            "resumption": "00000000-0000-0000-0000-000000000004",
+            "snapshots": [{
+                "timestamp": "2023-01-01T10:00:00Z",
+                "media_session_type": "screen",
+                "source": "00000000-0000-0000-0000-000000000003",
+                "direction": "downstream",
+                "packets": 1200,
+                "packets_lost": 12,
+                "jitter_ms": 4.5,
+                "round_trip_time_ms": -1.0
+            }]
+        }))
+        .unwrap();
+
+        let stats = body.into_new_stats();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].room_id, RoomId::from(Uuid::from_u128(1)));
+        assert_eq!(stats[0].participant_id, Uuid::from_u128(2));
+        assert_eq!(stats[0].media_session_type, "screen");
+        assert_eq!(stats[0].source, Uuid::from_u128(3));
+        assert_eq!(stats[0].direction, "downstream");
+        assert_eq!((stats[0].packets, stats[0].packets_lost), (1200, 12));
+        assert_eq!(stats[0].jitter_ms, Some(4.5));
+        assert_eq!(stats[0].round_trip_time_ms, None);
+        assert_eq!(stats[0].bitrate_kbps, None);
+    }
+}
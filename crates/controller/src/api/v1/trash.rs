@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Trash of deleted rooms and events
+//!
+//! Deleted rooms and events are kept in the trash for the configured grace period. Until then they can be
+//! restored by their creator, afterwards they are purged by a background task.
+use super::response::{ApiError, NoContent};
+use super::ApiResponse;
+use crate::settings::SharedSettingsActix;
+use actix_web::web::{Data, Path, ReqData};
+use actix_web::{get, post};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::events::Event;
+use db_storage::rooms::Room;
+use db_storage::users::User;
+use serde::Serialize;
+use types::core::{EventId, RoomId};
+
+/// Contents of the trash of a user
+#[derive(Debug, Serialize)]
+pub struct TrashResource {
+    pub rooms: Vec<TrashedRoom>,
+    pub events: Vec<TrashedEvent>,
+}
+
+/// A room in the trash
+///
+/// Events which have been deleted together with the room are not listed separately.
+#[derive(Debug, Serialize)]
+pub struct TrashedRoom {
+    pub id: RoomId,
+    pub deleted_at: DateTime<Utc>,
+    /// Point in time after which the room is purged and can no longer be restored
+    pub purge_at: DateTime<Utc>,
+}
+
+/// An event in the trash
+#[derive(Debug, Serialize)]
+pub struct TrashedEvent {
+    pub id: EventId,
+    pub title: String,
+    pub room: RoomId,
+    pub deleted_at: DateTime<Utc>,
+    /// Point in time after which the event is purged and can no longer be restored
+    pub purge_at: DateTime<Utc>,
+}
+
+/// API Endpoint *GET /trash*
+///
+/// Returns all rooms and events created by the current user which are in the trash
+#[get("/trash")]
+pub async fn get_trash(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    current_user: ReqData<User>,
+) -> Result<ApiResponse<TrashResource>, ApiError> {
+    let grace_period = chrono::Duration::from_std(settings.load().trash.grace_period)
+        .map_err(anyhow::Error::from)?;
+    let current_user = current_user.into_inner();
+
+    let (rooms, events) = crate::block(move || -> database::Result<_> {
+        let mut conn = db.get_read_conn()?;
+
+        let rooms = Room::get_all_deleted_for_user(&mut conn, current_user.id)?;
+        let events = Event::get_all_deleted_for_user(&mut conn, current_user.id)?;
+
+        Ok((rooms, events))
+    })
+    .await??;
+
+    let rooms = rooms
+        .into_iter()
+        .filter_map(|room| {
+            let deleted_at = room.deleted_at?;
+
+            Some(TrashedRoom {
+                id: room.id,
+                deleted_at,
+                purge_at: deleted_at + grace_period,
+            })
+        })
+        .collect();
+
+    let events = events
+        .into_iter()
+        .filter_map(|event| {
+            let deleted_at = event.deleted_at?;
+
+            Some(TrashedEvent {
+                id: event.id,
+                title: event.title,
+                room: event.room,
+                deleted_at,
+                purge_at: deleted_at + grace_period,
+            })
+        })
+        .collect();
+
+    Ok(ApiResponse::new(TrashResource { rooms, events }))
+}
+
+/// API Endpoint *POST /trash/rooms/{room_id}/restore*
+///
+/// Restores a room and the events which have been deleted together with it
+#[post("/trash/rooms/{room_id}/restore")]
+pub async fn restore_room(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    room_id: Path<RoomId>,
+) -> Result<NoContent, ApiError> {
+    let room_id = room_id.into_inner();
+    let current_user = current_user.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Room::restore(&mut conn, room_id, current_user.id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
+
+/// API Endpoint *POST /trash/events/{event_id}/restore*
+///
+/// Restores an event. Events of a room in the trash can only be restored by restoring the room.
+#[post("/trash/events/{event_id}/restore")]
+pub async fn restore_event(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+    event_id: Path<EventId>,
+) -> Result<NoContent, ApiError> {
+    let event_id = event_id.into_inner();
+    let current_user = current_user.into_inner();
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Event::restore(&mut conn, event_id, current_user.id)
+    })
+    .await??;
+
+    Ok(NoContent)
+}
@@ -3,20 +3,34 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 //! TURN related API structs and Endpoints
+//!
+//! Credentials are time-limited HMAC credentials following the TURN REST API. The username contains a pseudonym of the
+//! requesting user or invite, which allows the TURN servers to track and limit the usage per user without learning
+//! who they are.
+//!
+//! The daily quota of credentials is counted per participant. Participants pass the resumption token returned by the
+//! start endpoints, before joining a room the quota is counted per user or invite.
 use super::response::ApiError;
+use crate::api::signaling::connectivity::{self, ConnectivityCheck, ConnectivityReport};
+use crate::api::signaling::metrics::SignalingMetrics;
+use crate::api::signaling::resumption::{ResumptionData, ResumptionRedisKey};
 use crate::api::v1::middleware::user_auth::check_access_token;
 use crate::api::v1::response::error::AuthenticationError;
 use crate::api::v1::response::NoContent;
+use crate::api::Participant;
 use crate::oidc::OidcContext;
+use crate::redis_encryption::Encrypted;
+use crate::redis_wrapper::RedisConnection;
 use crate::settings::{self, SharedSettingsActix};
 use crate::settings::{Settings, TurnServer};
 use actix_http::StatusCode;
 use actix_web::http::header::Header;
 use actix_web::web::Data;
 use actix_web::web::Json;
+use actix_web::web::Query;
 use actix_web::Either as AWEither;
 use actix_web::HttpRequest;
-use actix_web::{get, ResponseError};
+use actix_web::{get, post, ResponseError};
 use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
 use arc_swap::ArcSwap;
 use database::{Db, OptionalExt};
@@ -27,10 +41,30 @@ use openidconnect::AccessToken;
 use rand::distributions::{Distribution, Uniform};
 use rand::prelude::SliceRandom;
 use rand::{CryptoRng, Rng};
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
 use ring::hmac;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
-use types::core::InviteCodeId;
+use types::core::{InviteCodeId, ResumptionToken, Timestamp};
+
+/// Time in seconds after which the counter of issued credentials of a day expires
+const ISSUED_CREDENTIALS_EXPIRY: usize = 2 * 24 * 60 * 60;
+
+/// Number of TURN credentials issued to a participant, user or invite on a day
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-turn:issued_credentials={subject}:day={day}")]
+struct IssuedCredentials<'s> {
+    subject: &'s str,
+    day: chrono::NaiveDate,
+}
+
+/// Query parameters of the `GET /turn` endpoint
+#[derive(Debug, Deserialize)]
+pub struct TurnQuery {
+    /// Resumption token of the requesting participant, the quota is counted per participant if given
+    resumption: Option<ResumptionToken>,
+}
 
 /// TURN access credentials for users.
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
@@ -65,11 +99,16 @@ pub enum IceServer {
 /// API Endpoint *GET /turn*
 ///
 /// Returns a list of ['Turn'] with HMAC-SHA1 credentials following <https://datatracker.ietf.org/doc/html/draft-uberti-behave-turn-rest-00>
+///
+/// The TURN servers are taken from the pool of the requester's region if one is configured. Returns 429 Too Many
+/// Requests if the participant exceeded the configured daily quota of credentials.
 #[get("/turn")]
 pub async fn get(
     settings: SharedSettingsActix,
     db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
     oidc_ctx: Data<OidcContext>,
+    query: Query<TurnQuery>,
     req: HttpRequest,
 ) -> Result<AWEither<Json<Vec<IceServer>>, NoContent>, ApiError> {
     let settings: &ArcSwap<Settings> = &settings;
@@ -79,30 +118,50 @@ pub async fn get(
     let stun_servers = &settings.stun;
 
     // This is a omniauth endpoint. AccessTokens and InviteCodes are allowed as Bearer tokens
-    match check_access_token_or_invite(&settings, &req, db, oidc_ctx).await? {
-        Either::Right(invite) => {
-            log::trace!(
-                "Generating new turn credentials for invite {} and servers {:?}",
-                invite.id,
-                &turn_servers
-            );
-        }
-        Either::Left(user) => {
-            log::trace!(
-                "Generating new turn credentials for user {} and servers {:?}",
-                user.id,
-                &turn_servers
-            );
-        }
-    }
+    let requester = check_access_token_or_invite(&settings, &req, db, oidc_ctx).await?;
+
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let quota_subject =
+        quota_subject(&mut redis_conn, &requester, query.into_inner().resumption).await?;
+    let subject = subject(requester);
+
+    log::trace!(
+        "Generating new turn credentials for {} and servers {:?}",
+        subject,
+        &turn_servers
+    );
+
+    let region = settings
+        .rooms
+        .region_header
+        .as_ref()
+        .and_then(|header| req.headers().get(header.as_str()))
+        .and_then(|value| value.to_str().ok());
+
     let mut ice_servers = match turn_servers {
         Some(turn_config) => {
+            if let Some(daily_quota) = turn_config.daily_quota {
+                let issued = increment_issued_credentials(&mut redis_conn, &quota_subject).await?;
+
+                if issued > daily_quota {
+                    return Err(ApiError::too_many_requests()
+                        .with_code("turn_quota_exceeded")
+                        .with_message("The daily quota of TURN credentials has been exceeded"));
+                }
+            }
+
             let expires = (chrono::Utc::now()
                 + chrono::Duration::from_std(turn_config.lifetime)
                     .map_err(|_| ApiError::internal())?)
             .timestamp();
             let mut rand_rng = ::rand::thread_rng();
-            rr_servers(&mut rand_rng, &turn_config.servers, expires)
+            rr_servers(
+                &mut rand_rng,
+                turn_config.servers_for_region(region),
+                expires,
+                &subject,
+            )
         }
         None => vec![],
     };
@@ -120,18 +179,153 @@ pub async fn get(
     Ok(AWEither::Left(Json(ice_servers)))
 }
 
-fn create_credentials<T: Rng + CryptoRng>(
-    rng: &mut T,
-    psk: &str,
-    ttl: i64,
-    uris: &[String],
-) -> IceServer {
+/// API Endpoint *POST /turn/check*
+///
+/// Evaluates the types of the ICE candidates the client gathered using the servers returned by [`get`]. The result is
+/// stored as the latest connectivity check of the user or invite and counted in the metrics.
+///
+/// Returns the evaluated [`ConnectivityCheck`]
+#[post("/turn/check")]
+pub async fn post_check(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    oidc_ctx: Data<OidcContext>,
+    metrics: Data<SignalingMetrics>,
+    req: HttpRequest,
+    body: Json<ConnectivityReport>,
+) -> Result<Json<ConnectivityCheck>, ApiError> {
+    let settings = settings.load_full();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let subject = subject(check_access_token_or_invite(&settings, &req, db, oidc_ctx).await?);
+
+    let check = ConnectivityCheck::evaluate(&body, Timestamp::now());
+
+    check.record_metrics(&metrics);
+
+    connectivity::set_last_check(&mut redis_conn, &subject, &check).await?;
+
+    Ok(Json(check))
+}
+
+/// API Endpoint *GET /turn/check*
+///
+/// Returns the latest [`ConnectivityCheck`] of the user or invite, checks expire after a day
+#[get("/turn/check")]
+pub async fn get_check(
+    settings: SharedSettingsActix,
+    db: Data<Db>,
+    redis_ctx: Data<RedisConnection>,
+    oidc_ctx: Data<OidcContext>,
+    req: HttpRequest,
+) -> Result<Json<ConnectivityCheck>, ApiError> {
+    let settings = settings.load_full();
+    let mut redis_conn = (**redis_ctx).clone();
+
+    let subject = subject(check_access_token_or_invite(&settings, &req, db, oidc_ctx).await?);
+
+    let check = connectivity::get_last_check(&mut redis_conn, &subject)
+        .await?
+        .ok_or_else(ApiError::not_found)?;
+
+    Ok(Json(check))
+}
+
+/// Returns the subject identifying the user or invite in redis keys and TURN usernames
+fn subject(requester: Either<User, Invite>) -> String {
+    match requester {
+        Either::Left(user) => format!("user={}", user.id),
+        Either::Right(invite) => format!("invite={}", invite.id),
+    }
+}
+
+/// Returns the subject the issued credentials are counted for
+///
+/// This is the participant of the resumption token if it belongs to the requester, guests sharing an invite code get
+/// a quota each this way. Without a valid resumption token the credentials are counted for the user or invite.
+async fn quota_subject(
+    redis_conn: &mut RedisConnection,
+    requester: &Either<User, Invite>,
+    resumption: Option<ResumptionToken>,
+) -> Result<String, ApiError> {
+    let fallback = match requester {
+        Either::Left(user) => format!("user={}", user.id),
+        Either::Right(invite) => format!("invite={}", invite.id),
+    };
+
+    let resumption = match resumption {
+        Some(resumption) => resumption,
+        None => return Ok(fallback),
+    };
+
+    let data: Option<Encrypted<ResumptionData>> = redis_conn
+        .get(ResumptionRedisKey(resumption))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get the resumption data, {}", e);
+            ApiError::internal()
+        })?;
+
+    match data {
+        Some(Encrypted(data)) if belongs_to_requester(&data, requester) => {
+            Ok(format!("participant={}", data.participant_id))
+        }
+        _ => Ok(fallback),
+    }
+}
+
+/// Returns true if the participant of the resumption data was joined by the requester
+pub(super) fn belongs_to_requester(
+    data: &ResumptionData,
+    requester: &Either<User, Invite>,
+) -> bool {
+    match requester {
+        Either::Left(user) => data.participant == Participant::User(user.id),
+        Either::Right(invite) => data.participant == Participant::Guest && data.room == invite.room,
+    }
+}
+
+/// Increment the number of credentials issued to the subject today, returns the incremented number
+async fn increment_issued_credentials(
+    redis_conn: &mut RedisConnection,
+    subject: &str,
+) -> Result<u32, ApiError> {
+    let key = IssuedCredentials {
+        subject,
+        day: chrono::Utc::now().date_naive(),
+    };
+
+    let (issued,): (u32,) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, ISSUED_CREDENTIALS_EXPIRY)
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to increment the issued TURN credentials, {}", e);
+            ApiError::internal()
+        })?;
+
+    Ok(issued)
+}
+
+/// Returns the pseudonym of the subject for the TURN server with the given pre shared key
+///
+/// The pseudonym is stable for a subject, but cannot be linked to the subject without the pre shared key.
+fn pseudonym(psk: &str, subject: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, psk.as_bytes());
+    let tag = hmac::sign(&key, subject.as_bytes());
+
+    base64::encode_config(&tag.as_ref()[..16], base64::URL_SAFE_NO_PAD)
+}
+
+fn create_credentials(psk: &str, ttl: i64, subject: &str, uris: &[String]) -> IceServer {
     // TODO We should invest time to add SHA265 support to coturn or our own turn server.
     let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, psk.as_bytes());
 
-    // We append 16 bytes as a base64 encoded string to the prefix `turn_random_for_privacy_` for usage as application data in our username
-    let random_part: String = base64::encode(rng.gen::<[u8; 16]>().as_ref());
-    let username = format!("{ttl}:turn_random_for_privacy_{random_part}",);
+    let username = format!("{ttl}:{}", pseudonym(psk, subject));
     let password = base64::encode(hmac::sign(&key, username.as_bytes()).as_ref());
 
     IceServer::Turn(Turn {
@@ -146,21 +340,22 @@ fn rr_servers<T: Rng + CryptoRng>(
     rng: &mut T,
     servers: &[TurnServer],
     expires: i64,
+    subject: &str,
 ) -> Vec<IceServer> {
     // Create a list of TURN responses for each configured TURN server.
     match servers.len() {
         0 => vec![],
         // When we only have one configured TURN server, return the credentials for this single one.
-        1 => vec![create_credentials(rng, &servers[0].pre_shared_key, expires, &servers[0].uris)]
+        1 => vec![create_credentials(&servers[0].pre_shared_key, expires, subject, &servers[0].uris)]
         ,
         // When we have two configured TURN servers, draw a random one and return the credentials for this drawn one.
         2 => {
             let between: Uniform<u32> = Uniform::from(0..1);
             let selected_server = between.sample(rng) as usize;
             let turn = create_credentials(
-                rng,
                 &servers[selected_server].pre_shared_key,
                 expires,
+                subject,
                 &servers[selected_server].uris,
             );
 
@@ -170,14 +365,14 @@ fn rr_servers<T: Rng + CryptoRng>(
         _ => servers
             .choose_multiple(rng, 2)
             .map(|server| {
-                create_credentials(rng, &server.pre_shared_key, expires, &server.uris)
+                create_credentials(&server.pre_shared_key, expires, subject, &server.uris)
             })
             .collect::<Vec<_>>(),
     }
 }
 
 /// Checks for a valid access_token similar to the OIDC Middleware, but also allows invite_tokens as a valid bearer token.
-async fn check_access_token_or_invite(
+pub(super) async fn check_access_token_or_invite(
     settings: &Settings,
     req: &HttpRequest,
     db: Data<Db>,
@@ -237,16 +432,34 @@ mod test {
 
     #[test]
     fn test_create_credentials() {
-        use rand::prelude::*;
-        use rand::SeedableRng;
-        let mut rng = StdRng::seed_from_u64(1234567890);
-        let credentials =
-            create_credentials(&mut rng, "PSK", 3400, &["turn:turn.turn.turn".to_owned()]);
+        let credentials = create_credentials(
+            "PSK",
+            3400,
+            "user=test",
+            &["turn:turn.turn.turn".to_owned()],
+        );
+        assert_eq!(
+            credentials,
+            IceServer::Turn(Turn {
+                username: "3400:ChFJriqqcSKrL4cv5R9CLg".to_owned(),
+                password: "tMVwEmMw5/x86jp6DXfh2eNOS8A=".to_owned(),
+                ttl: 3400.to_string(),
+                uris: vec!["turn:turn.turn.turn".to_owned()]
+            })
+        );
+
+        // Every subject gets its own pseudonym
+        let credentials = create_credentials(
+            "PSK",
+            3400,
+            "invite=test",
+            &["turn:turn.turn.turn".to_owned()],
+        );
         assert_eq!(
             credentials,
             IceServer::Turn(Turn {
-                username: "3400:turn_random_for_privacy_8VbonSpZc9GXSw9gMxaV0A==".to_owned(),
-                password: "h3R6Ob2G0+nH3oRhO2y/IuK757Y=".to_owned(),
+                username: "3400:SuxAmtwBE0BBYkEBbdPDrw".to_owned(),
+                password: "3eqCk1ozi8/QVW1ZWYMrgXsaTIU=".to_owned(),
                 ttl: 3400.to_string(),
                 uris: vec!["turn:turn.turn.turn".to_owned()]
             })
@@ -260,7 +473,7 @@ mod test {
 
         // No configured servers
         let mut rng = StdRng::seed_from_u64(1234567890);
-        assert_eq!(rr_servers(&mut rng, &[], 1200), vec![]);
+        assert_eq!(rr_servers(&mut rng, &[], 1200, "user=test"), vec![]);
 
         // One configured server
         let mut rng = StdRng::seed_from_u64(1234567890);
@@ -269,10 +482,10 @@ mod test {
             pre_shared_key: "PSK1".to_owned(),
         }];
         assert_eq!(
-            rr_servers(&mut rng, &one_server, 1200),
+            rr_servers(&mut rng, &one_server, 1200, "user=test"),
             vec![IceServer::Turn(Turn {
-                username: "1200:turn_random_for_privacy_8VbonSpZc9GXSw9gMxaV0A==".to_owned(),
-                password: "G7hjqVZX/dVAOgzzb+GeS8vEpcU=".to_owned(),
+                username: "1200:h2m01J4-lCKi0POqYAlpzA".to_owned(),
+                password: "okWkX4s7XN4Ief219TIwXF9F9SY=".to_owned(),
                 ttl: 1200.to_string(),
                 uris: vec!["turn:turn1.turn.turn".to_owned()]
             })]
@@ -291,10 +504,10 @@ mod test {
             },
         ];
         assert_eq!(
-            rr_servers(&mut rng, &two_servers, 1200),
+            rr_servers(&mut rng, &two_servers, 1200, "user=test"),
             vec![IceServer::Turn(Turn {
-                username: "1200:turn_random_for_privacy_VuidKllz0ZdLD2AzFpXQYA==".to_owned(),
-                password: "Aybo95/GPrJhWN2qqbz6yP2qEvg=".to_owned(),
+                username: "1200:h2m01J4-lCKi0POqYAlpzA".to_owned(),
+                password: "okWkX4s7XN4Ief219TIwXF9F9SY=".to_owned(),
                 ttl: 1200.to_string(),
                 uris: vec!["turn:turn1.turn.turn".to_owned()]
             })]
@@ -317,17 +530,17 @@ mod test {
             },
         ];
         assert_eq!(
-            rr_servers(&mut rng, &three_servers, 1200),
+            rr_servers(&mut rng, &three_servers, 1200, "user=test"),
             vec![
                 IceServer::Turn(Turn {
-                    username: "1200:turn_random_for_privacy_nSpZc9GXSw9gMxaV0GDahQ==".to_owned(),
-                    password: "6zfptEfCPlF3oWFtPKtlAPwjAWs=".to_owned(),
+                    username: "1200:h2m01J4-lCKi0POqYAlpzA".to_owned(),
+                    password: "okWkX4s7XN4Ief219TIwXF9F9SY=".to_owned(),
                     ttl: 1200.to_string(),
                     uris: vec!["turn:turn1.turn.turn".to_owned()]
                 }),
                 IceServer::Turn(Turn {
-                    username: "1200:turn_random_for_privacy_AYzBIYeOCHhhiwR7CQ3X1A==".to_owned(),
-                    password: "fiWX+emwV1thN/dBcZ9melA061g=".to_owned(),
+                    username: "1200:TdJ48tNAvOEW_3TsuBgdqA".to_owned(),
+                    password: "R/Xi69qhN4HkZXbk1p+OzQ3s9gU=".to_owned(),
                     ttl: 1200.to_string(),
                     uris: vec!["turn:turn3.turn.turn".to_owned()]
                 })
@@ -339,7 +552,7 @@ mod test {
         let mut second = 0;
         let mut third = 0;
         for _ in 1..5000 {
-            rr_servers(&mut rng, &three_servers, 1200)
+            rr_servers(&mut rng, &three_servers, 1200, "user=test")
                 .iter()
                 .filter_map(|e| match e {
                     IceServer::Turn(turn) => Some(turn),
@@ -10,18 +10,25 @@
 use super::response::{ApiError, NoContent};
 use crate::api::signaling::prelude::SignalingModules;
 use crate::api::v1::tariffs::TariffResource;
+use crate::gdpr;
 use crate::settings::SharedSettingsActix;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use actix_web::web::{Data, Json, Path, Query, ReqData};
-use actix_web::{get, patch, Either};
+use actix_web::{get, patch, post, Either, HttpResponse};
 use anyhow::Context;
-use controller_shared::settings::Settings;
-use database::Db;
+use chrono::Utc;
+use controller_shared::settings::{Settings, UsersFindScope};
+use database::{Db, DbConnection};
+use db_storage::events::Event;
+use db_storage::groups::count_shared_groups;
 use db_storage::tariffs::Tariff;
 use db_storage::tenants::Tenant;
 use db_storage::users::{UpdateUser, User};
 use keycloak_admin::KeycloakAdminClient;
 use serde::{Deserialize, Serialize};
-use types::core::UserId;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use types::core::{EventId, TenantId, UserId};
 use validator::Validate;
 
 /// Public user details.
@@ -217,6 +224,31 @@ pub async fn get_me_tariff(
     Ok(Json(response))
 }
 
+/// API Endpoint *POST /users/me/data-export*
+///
+/// Returns an export of all data stored about the requesting user as a downloadable JSON document.
+#[post("/users/me/data-export")]
+pub async fn data_export(
+    db: Data<Db>,
+    current_user: ReqData<User>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = current_user.into_inner();
+
+    let export = gdpr::export_user_data(db.into_inner(), current_user).await?;
+
+    let filename = format!(
+        "opentalk-data-export-{}.json",
+        export.exported_at.format("%Y-%m-%d")
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .json(export))
+}
+
 /// API Endpoint *GET /users/{user_id}*
 ///
 /// Returns [`PublicUserProfile`] of the specified user
@@ -246,6 +278,18 @@ pub struct FindQuery {
     q: String,
 }
 
+/// Maximum number of entries returned by a search in the LDAP directory
+const LDAP_SEARCH_LIMIT: i32 = 100;
+
+/// Maximum number of registered users returned by a search
+const FIND_LIMIT: usize = 5;
+
+/// Number of registered users matching a search, which are ranked to return the best [`FIND_LIMIT`] of them
+const FIND_CANDIDATES_LIMIT: i64 = 20;
+
+/// Meetings with the current user in this many past days rank found users higher
+const RECENT_MEETINGS_DAYS: i64 = 90;
+
 #[derive(Serialize)]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum UserFindResponseItem {
@@ -270,6 +314,7 @@ pub async fn find(
     kc_admin_client: Data<KeycloakAdminClient>,
     db: Data<Db>,
     current_tenant: ReqData<Tenant>,
+    current_user: ReqData<User>,
     query: Query<FindQuery>,
 ) -> Result<Json<Vec<UserFindResponseItem>>, ApiError> {
     let settings = settings.load_full();
@@ -284,14 +329,28 @@ pub async fn find(
             .with_message("query must be at least 3 characters long"));
     }
 
-    let found_users = if settings.endpoints.users_find_use_kc {
+    let found_users = if settings.endpoints.users_find_scope == UsersFindScope::ExactEmail {
+        let found_users = crate::block(move || {
+            let mut conn = db.get_read_conn()?;
+
+            User::find_by_email(&mut conn, current_tenant.id, &query.q)
+        })
+        .await??;
+
+        found_users
+            .into_iter()
+            .map(|user| {
+                UserFindResponseItem::Registered(PublicUserProfile::from_db(&settings, user))
+            })
+            .collect()
+    } else if settings.endpoints.users_find_use_kc {
         let mut found_kc_users = kc_admin_client
             .search_user(current_tenant.oidc_tenant_id.inner(), &query.q)
             .await
             .context("Failed to search for user in keycloak")?;
 
         let (db_users, kc_users) = crate::block(move || -> Result<_, ApiError> {
-            let mut conn = db.get_conn()?;
+            let mut conn = db.get_read_conn()?;
 
             let kc_subs: Vec<&str> = found_kc_users
                 .iter()
@@ -323,11 +382,63 @@ pub async fn find(
                 })
             }))
             .collect()
+    } else if let Some(ldap_settings) = &settings.ldap {
+        let mut found_entries = crate::ldap::search(ldap_settings, &query.q, LDAP_SEARCH_LIMIT)
+            .await
+            .context("Failed to search for user in the LDAP directory")?;
+
+        let (db_users, ldap_entries) = crate::block(move || -> Result<_, ApiError> {
+            let mut conn = db.get_read_conn()?;
+
+            let subjects: Vec<String> = found_entries.iter().map(|entry| entry.subject()).collect();
+            let subjects: Vec<&str> = subjects.iter().map(String::as_str).collect();
+
+            let users = User::get_all_by_oidc_subs(&mut conn, current_tenant.id, &subjects)?;
+
+            found_entries
+                .retain(|entry| !users.iter().any(|user| user.oidc_sub == entry.subject()));
+
+            Ok((users, found_entries))
+        })
+        .await??;
+
+        db_users
+            .into_iter()
+            .map(|user| {
+                UserFindResponseItem::Registered(PublicUserProfile::from_db(&settings, user))
+            })
+            .chain(ldap_entries.into_iter().map(|entry| {
+                let avatar_url =
+                    email_to_libravatar_url(&settings.avatar.libravatar_url, &entry.email);
+
+                UserFindResponseItem::Unregistered(UnregisteredUser {
+                    email: entry.email,
+                    firstname: entry.firstname,
+                    lastname: entry.lastname,
+                    avatar_url,
+                })
+            }))
+            .collect()
     } else {
-        let found_users = crate::block(move || {
-            let mut conn = db.get_conn()?;
+        let current_user_id = current_user.id;
 
-            User::find(&mut conn, current_tenant.id, &query.q)
+        let found_users = crate::block(move || {
+            let mut conn = db.get_read_conn()?;
+
+            let users = User::find_with_limit(
+                &mut conn,
+                current_tenant.id,
+                &query.q,
+                FIND_CANDIDATES_LIMIT,
+            )?;
+
+            rank_found_users(
+                &mut conn,
+                current_tenant.id,
+                current_user_id,
+                users,
+                &query.q,
+            )
         })
         .await??;
 
@@ -341,3 +452,66 @@ pub async fn find(
 
     Ok(Json(found_users))
 }
+
+/// Rank the found users and return the best [`FIND_LIMIT`] of them
+///
+/// Users whose email matches the query exactly come first, then the users sharing the most groups with the current
+/// user, then the users with the most recent meetings with the current user. Otherwise the order of the search is kept.
+fn rank_found_users(
+    conn: &mut DbConnection,
+    tenant_id: TenantId,
+    current_user_id: UserId,
+    mut users: Vec<User>,
+    query: &str,
+) -> database::Result<Vec<User>> {
+    if users.len() > 1 {
+        let user_ids: Vec<UserId> = users.iter().map(|user| user.id).collect();
+
+        let shared_groups: HashMap<UserId, i64> =
+            count_shared_groups(conn, current_user_id, &user_ids)?
+                .into_iter()
+                .collect();
+
+        let now = Utc::now();
+        let participants: Vec<UserId> = user_ids
+            .into_iter()
+            .chain(std::iter::once(current_user_id))
+            .collect();
+
+        let events = Event::get_all_blocking_for_users(
+            conn,
+            tenant_id,
+            &participants,
+            now - chrono::Duration::days(RECENT_MEETINGS_DAYS),
+            now,
+        )?;
+
+        let own_events: HashSet<EventId> = events
+            .iter()
+            .filter(|(_, user_id)| *user_id == current_user_id)
+            .map(|(event, _)| event.id)
+            .collect();
+
+        let mut recent_meetings: HashMap<UserId, usize> = HashMap::new();
+        for (event, user_id) in &events {
+            if *user_id != current_user_id && own_events.contains(&event.id) {
+                *recent_meetings.entry(*user_id).or_default() += 1;
+            }
+        }
+
+        let query = query.trim();
+
+        // Stable sort, keeps the order of the search for equally ranked users
+        users.sort_by_key(|user| {
+            (
+                Reverse(user.email.eq_ignore_ascii_case(query)),
+                Reverse(shared_groups.get(&user.id).copied().unwrap_or_default()),
+                Reverse(recent_meetings.get(&user.id).copied().unwrap_or_default()),
+            )
+        });
+    }
+
+    users.truncate(FIND_LIMIT);
+
+    Ok(users)
+}
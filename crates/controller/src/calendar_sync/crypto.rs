@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Encryption of the OAuth tokens stored in the database
+//!
+//! Tokens are encrypted with AES-256-GCM, the random nonce is stored in front of the ciphertext.
+use anyhow::{anyhow, bail, Context, Result};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+pub(crate) struct TokenCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl TokenCipher {
+    /// Creates the cipher from the base64 encoded `calendar_sync.encryption_key`
+    pub(crate) fn new(encoded_key: &str) -> Result<Self> {
+        let key = base64::decode(encoded_key).context("encryption_key is not valid base64")?;
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .map_err(|_| anyhow!("encryption_key must be 32 bytes long"))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub(crate) fn encrypt(&self, token: &str) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate nonce"))?;
+
+        let mut ciphertext = token.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt token"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        Ok(sealed)
+    }
+
+    pub(crate) fn decrypt(&self, sealed: &[u8]) -> Result<String> {
+        if sealed.len() < NONCE_LEN {
+            bail!("Encrypted token is too short");
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+
+        let mut ciphertext = ciphertext.to_vec();
+        let token = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt token"))?;
+
+        String::from_utf8(token.to_vec()).context("Decrypted token is not valid UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let cipher = TokenCipher::new(KEY).unwrap();
+
+        let sealed = cipher.encrypt("ya29.token").unwrap();
+
+        assert_ne!(&sealed[NONCE_LEN..], b"ya29.token");
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "ya29.token");
+    }
+
+    #[test]
+    fn decrypt_with_other_key() {
+        let sealed = TokenCipher::new(KEY)
+            .unwrap()
+            .encrypt("ya29.token")
+            .unwrap();
+
+        let other = TokenCipher::new("HyAdHBsaGRgXFhUUExIREA8ODQwLCgkIBwYFBAMCAQA=").unwrap();
+
+        assert!(other.decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn invalid_key_length() {
+        assert!(TokenCipher::new("AAECAwQ=").is_err());
+    }
+}
@@ -0,0 +1,654 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Synchronization of events to the Google and Microsoft calendars linked by users
+//!
+//! Users link their calendar with the OAuth authorization code flow, the tokens are stored encrypted in the
+//! database. In the configured interval, all upcoming time dependent events created by a user are pushed to the
+//! linked calendars with the invitees as attendees, and events which have been moved to the trash are removed.
+//! The responses of the attendees are pulled back into the invites of registered users.
+//!
+//! Only one controller instance of a deployment synchronizes in every interval, it holds a lease in redis which it
+//! renews while synchronizing.
+use crate::api::v1::events::DateTimeTzFromDb;
+use crate::redis_wrapper::RedisConnection;
+use crate::settings::SharedSettings;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use controller_shared::settings::CalendarSyncClient;
+use crypto::TokenCipher;
+use database::Db;
+use db_storage::calendar_links::{
+    CalendarLink, CalendarLinkEvent, CalendarProvider, NewCalendarLink, NewCalendarLinkEvent,
+    UpdateCalendarLink,
+};
+use db_storage::events::email_invites::EventEmailInvite;
+use db_storage::events::{Event, EventInvite, EventInviteStatus, UpdateEventInvite};
+use providers::CalendarEvent;
+use redis_args::ToRedisArgs;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::{DateTimeTz, EventId, UserId};
+use url::Url;
+
+mod crypto;
+mod providers;
+
+/// Access tokens are refreshed this long before they expire
+const TOKEN_EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Lease of the controller instance synchronizing the linked calendars, contains the id of the instance
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-controller:calendar_sync.lease")]
+struct SyncLease;
+
+/// Extends the lease if it is still held by the instance
+const RENEW_LEASE: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+else
+    return 0
+end
+"#;
+
+/// Acquire the lease for the duration of `ttl`, returns false if another instance holds it
+async fn acquire_lease(
+    redis_conn: &mut RedisConnection,
+    holder: &str,
+    ttl: Duration,
+) -> Result<bool> {
+    let set: Option<String> = redis::cmd("SET")
+        .arg(SyncLease)
+        .arg(holder)
+        .arg("NX")
+        .arg("PX")
+        .arg(ttl.as_millis() as u64)
+        .query_async(redis_conn)
+        .await
+        .context("Failed to SET the calendar sync lease")?;
+
+    Ok(set.is_some())
+}
+
+/// Extend the lease by `ttl`, returns false if the lease has been lost
+async fn renew_lease(
+    redis_conn: &mut RedisConnection,
+    holder: &str,
+    ttl: Duration,
+) -> Result<bool> {
+    let renewed: i64 = redis::Script::new(RENEW_LEASE)
+        .key(SyncLease)
+        .arg(holder)
+        .arg(ttl.as_millis() as u64)
+        .invoke_async(redis_conn)
+        .await
+        .context("Failed to renew the calendar sync lease")?;
+
+    Ok(renewed == 1)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalendarSyncError {
+    #[error("Calendar synchronization is not configured for this provider")]
+    NotConfigured,
+    #[error("Failed to link the calendar, {0}")]
+    Link(#[source] anyhow::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Event created by the user of a link, with the invitees as attendees
+struct SyncedEvent {
+    event: Event,
+    /// Email addresses and, for registered users, the invite status of the invitees
+    invitees: Vec<(String, Option<(UserId, EventInviteStatus)>)>,
+    /// Latest change of the event or its invitees
+    changed_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct CalendarSync {
+    settings: SharedSettings,
+    db: Arc<Db>,
+    http: reqwest::Client,
+}
+
+impl CalendarSync {
+    pub fn new(settings: SharedSettings, db: Arc<Db>) -> Self {
+        Self {
+            settings,
+            db,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn client(&self, provider: CalendarProvider) -> Option<(TokenCipher, CalendarSyncClient)> {
+        let settings = self.settings.load();
+        let calendar_sync = settings.calendar_sync.as_ref()?;
+
+        let client = match provider {
+            CalendarProvider::Google => calendar_sync.google.clone()?,
+            CalendarProvider::Microsoft => calendar_sync.microsoft.clone()?,
+        };
+
+        match TokenCipher::new(&calendar_sync.encryption_key) {
+            Ok(cipher) => Some((cipher, client)),
+            Err(e) => {
+                log::error!("Invalid calendar_sync settings, {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the URL the user has to be redirected to, to grant access to the calendar of the provider
+    pub fn authorize_url(
+        &self,
+        provider: CalendarProvider,
+        redirect_uri: &str,
+        state: &str,
+    ) -> Result<Url, CalendarSyncError> {
+        let (_, client) = self
+            .client(provider)
+            .ok_or(CalendarSyncError::NotConfigured)?;
+
+        Ok(providers::authorize_url(
+            provider,
+            &client,
+            redirect_uri,
+            state,
+        ))
+    }
+
+    /// Link the calendar of the provider to the user with the authorization code returned to the redirect URI
+    pub async fn link(
+        &self,
+        user_id: UserId,
+        provider: CalendarProvider,
+        code: &str,
+        redirect_uri: &str,
+    ) -> Result<CalendarLink, CalendarSyncError> {
+        let (cipher, client) = self
+            .client(provider)
+            .ok_or(CalendarSyncError::NotConfigured)?;
+
+        let tokens = providers::exchange_code(&self.http, provider, &client, code, redirect_uri)
+            .await
+            .map_err(CalendarSyncError::Link)?;
+
+        let refresh_token = tokens
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| CalendarSyncError::Link(anyhow!("No refresh token granted")))?;
+
+        let new_link = NewCalendarLink {
+            user_id,
+            provider,
+            access_token: cipher.encrypt(&tokens.access_token)?,
+            refresh_token: cipher.encrypt(refresh_token)?,
+            expires_at: tokens.expires_at(),
+        };
+
+        let db = self.db.clone();
+        let link = crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            new_link.upsert(&mut conn)
+        })
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(anyhow::Error::from)?;
+
+        Ok(link)
+    }
+
+    /// Revoke the tokens of the link at the provider
+    ///
+    /// Best effort, failures are only logged as the link is deleted anyway.
+    pub(crate) async fn revoke(&self, link: &CalendarLink) {
+        let (cipher, _) = match self.client(link.provider) {
+            Some(client) => client,
+            None => {
+                log::warn!(
+                    "Cannot revoke the tokens of calendar link {}, {:?} is not configured",
+                    link.id,
+                    link.provider
+                );
+                return;
+            }
+        };
+
+        let result = match cipher.decrypt(&link.refresh_token) {
+            Ok(refresh_token) => {
+                providers::revoke_token(&self.http, link.provider, &refresh_token).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(true) => log::debug!("Revoked the tokens of calendar link {}", link.id),
+            Ok(false) => log::debug!(
+                "{:?} does not support revoking the tokens of calendar link {}",
+                link.provider,
+                link.id
+            ),
+            Err(e) => log::warn!(
+                "Failed to revoke the tokens of calendar link {}, {:?}",
+                link.id,
+                e
+            ),
+        }
+    }
+
+    /// Periodically synchronize all linked calendars
+    ///
+    /// Runs until the shutdown signal is received. The lease is held for the whole interval, so the other controller
+    /// instances skip the interval in which a calendar sync has already been done.
+    pub(crate) async fn sync_task(
+        self,
+        mut redis_conn: RedisConnection,
+        interval: Duration,
+        mut shutdown: broadcast::Receiver<()>,
+    ) {
+        let holder = uuid::Uuid::new_v4().to_string();
+        let lease_time = interval;
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match acquire_lease(&mut redis_conn, &holder, lease_time).await {
+                        Ok(true) => {
+                            if let Err(e) = self.sync_all(&mut redis_conn, &holder, lease_time).await {
+                                log::error!("Failed to synchronize linked calendars, {:?}", e);
+                            }
+                        }
+                        Ok(false) => log::debug!("Calendar sync is done by another controller instance"),
+                        Err(e) => log::error!("Failed to acquire the calendar sync lease, {:?}", e),
+                    }
+                }
+                _ = shutdown.recv() => {
+                    log::debug!("Calendar sync task received shutdown signal");
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn sync_all(
+        &self,
+        redis_conn: &mut RedisConnection,
+        holder: &str,
+        lease_time: Duration,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let links = crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            CalendarLink::get_all(&mut conn)
+        })
+        .await??;
+
+        for link in links {
+            if !renew_lease(redis_conn, holder, lease_time).await? {
+                log::warn!("Lost the calendar sync lease, stopping the synchronization");
+                return Ok(());
+            }
+
+            let (link_id, provider) = (link.id, link.provider);
+
+            if let Err(e) = self.sync_link(link).await {
+                log::warn!(
+                    "Failed to synchronize calendar link {} ({:?}), {:?}",
+                    link_id,
+                    provider,
+                    e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a valid access token of the link, refreshing it if necessary
+    async fn access_token(
+        &self,
+        link: &CalendarLink,
+        cipher: &TokenCipher,
+        client: &CalendarSyncClient,
+    ) -> Result<String> {
+        if link.expires_at > Utc::now() + chrono::Duration::seconds(TOKEN_EXPIRY_MARGIN_SECS) {
+            return cipher.decrypt(&link.access_token);
+        }
+
+        let refresh_token = cipher.decrypt(&link.refresh_token)?;
+
+        let tokens = providers::refresh_token(&self.http, link.provider, client, &refresh_token)
+            .await
+            .context("Failed to refresh access token")?;
+
+        let update = UpdateCalendarLink {
+            access_token: Some(cipher.encrypt(&tokens.access_token)?),
+            refresh_token: tokens
+                .refresh_token
+                .as_deref()
+                .map(|token| cipher.encrypt(token))
+                .transpose()?,
+            expires_at: Some(tokens.expires_at()),
+            synced_at: None,
+        };
+
+        let db = self.db.clone();
+        let link_id = link.id;
+        crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            update.apply(&mut conn, link_id)
+        })
+        .await??;
+
+        Ok(tokens.access_token)
+    }
+
+    async fn sync_link(&self, link: CalendarLink) -> Result<()> {
+        let (cipher, client) = self
+            .client(link.provider)
+            .context("Provider is not configured anymore")?;
+
+        let access_token = self.access_token(&link, &cipher, &client).await?;
+
+        let db = self.db.clone();
+        let (user_id, link_id) = (link.user_id, link.id);
+        let (events, synced) = crate::block(move || -> database::Result<_> {
+            let mut conn = db.get_conn()?;
+
+            let events = Event::get_all_created_by(&mut conn, user_id)?;
+            let event_refs: Vec<&Event> = events.iter().collect();
+
+            let invites = EventInvite::get_for_events(&mut conn, &event_refs)?;
+            let email_invites = EventEmailInvite::get_for_events(&mut conn, &event_refs)?;
+
+            let events: Vec<SyncedEvent> = events
+                .into_iter()
+                .zip(invites.into_iter().zip(email_invites))
+                .map(|(event, (invites, email_invites))| {
+                    let changed_at = invites
+                        .iter()
+                        .map(|(invite, _)| invite.created_at)
+                        .chain(email_invites.iter().map(|invite| invite.created_at))
+                        .fold(event.updated_at, DateTime::max);
+
+                    let invitees = invites
+                        .into_iter()
+                        .map(|(invite, user)| (user.email, Some((user.id, invite.status))))
+                        .chain(email_invites.into_iter().map(|invite| (invite.email, None)))
+                        .collect();
+
+                    SyncedEvent {
+                        event,
+                        invitees,
+                        changed_at,
+                    }
+                })
+                .collect();
+
+            let synced: HashMap<EventId, CalendarLinkEvent> =
+                CalendarLinkEvent::get_all_for_link(&mut conn, link_id)?
+                    .into_iter()
+                    .map(|(synced, _)| (synced.event_id, synced))
+                    .collect();
+
+            Ok((events, synced))
+        })
+        .await??;
+
+        let now = Utc::now();
+
+        for synced_event in events {
+            let event_id = synced_event.event.id;
+
+            let result = self
+                .sync_event(
+                    &link,
+                    &access_token,
+                    synced_event,
+                    synced.get(&event_id),
+                    now,
+                )
+                .await;
+
+            if let Err(e) = result {
+                log::warn!(
+                    "Failed to synchronize event {} to calendar link {}, {:?}",
+                    event_id,
+                    link.id,
+                    e
+                );
+            }
+        }
+
+        let db = self.db.clone();
+        crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            UpdateCalendarLink {
+                access_token: None,
+                refresh_token: None,
+                expires_at: None,
+                synced_at: Some(now),
+            }
+            .apply(&mut conn, link_id)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    async fn sync_event(
+        &self,
+        link: &CalendarLink,
+        access_token: &str,
+        synced_event: SyncedEvent,
+        synced: Option<&CalendarLinkEvent>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let SyncedEvent {
+            event,
+            invitees,
+            changed_at,
+        } = synced_event;
+
+        if event.deleted_at.is_some() {
+            if let Some(synced) = synced {
+                providers::delete_event(
+                    &self.http,
+                    link.provider,
+                    access_token,
+                    &synced.external_id,
+                )
+                .await?;
+
+                self.forget_event(link, event.id).await?;
+            }
+
+            return Ok(());
+        }
+
+        let (starts_at, ends_at) = match (
+            DateTimeTz::starts_at_of(&event),
+            DateTimeTz::ends_at_of(&event),
+        ) {
+            (Some(starts_at), Some(ends_at)) => (starts_at, ends_at),
+            _ => return Ok(()),
+        };
+
+        // For recurring events `ends_at` is the start of the last occurrence
+        if event.ends_at.map(|ends_at| ends_at < now).unwrap_or(true) {
+            return Ok(());
+        }
+
+        let external_id = match synced {
+            Some(synced) if synced.synced_at >= changed_at => synced.external_id.clone(),
+            _ => {
+                let attendees: Vec<String> =
+                    invitees.iter().map(|(email, _)| email.clone()).collect();
+
+                let calendar_event = CalendarEvent {
+                    title: &event.title,
+                    description: &event.description,
+                    is_all_day: event.is_all_day.unwrap_or_default(),
+                    starts_at,
+                    ends_at,
+                    recurrence_pattern: event
+                        .recurrence_pattern
+                        .as_deref()
+                        .filter(|_| event.is_recurring.unwrap_or_default()),
+                    attendees: &attendees,
+                };
+
+                let payload = match providers::payload(link.provider, &calendar_event) {
+                    Some(payload) => payload,
+                    None => {
+                        log::debug!(
+                            "Event {} is not supported by {:?} calendars",
+                            event.id,
+                            link.provider
+                        );
+                        return Ok(());
+                    }
+                };
+
+                let external_id = providers::upsert_event(
+                    &self.http,
+                    link.provider,
+                    access_token,
+                    event.id,
+                    synced.map(|synced| synced.external_id.as_str()),
+                    &payload,
+                )
+                .await?;
+
+                let new_synced = NewCalendarLinkEvent {
+                    link_id: link.id,
+                    event_id: event.id,
+                    external_id: external_id.clone(),
+                    synced_at: changed_at,
+                };
+
+                let db = self.db.clone();
+                crate::block(move || {
+                    let mut conn = db.get_conn()?;
+
+                    new_synced.upsert(&mut conn)
+                })
+                .await??;
+
+                external_id
+            }
+        };
+
+        let external_event =
+            match providers::get_event(&self.http, link.provider, access_token, &external_id)
+                .await?
+            {
+                Some(external_event) => external_event,
+                None => {
+                    // Deleted in the calendar, gets recreated with the next change of the event
+                    return Ok(());
+                }
+            };
+
+        let mut updates = vec![];
+
+        for (email, status) in providers::attendee_statuses(link.provider, &external_event) {
+            let invitee = invitees
+                .iter()
+                .find(|(invitee_email, _)| invitee_email.eq_ignore_ascii_case(&email));
+
+            if let Some((_, Some((user_id, current_status)))) = invitee {
+                if *current_status != status {
+                    updates.push((*user_id, status));
+                }
+            }
+        }
+
+        if !updates.is_empty() {
+            let db = self.db.clone();
+            let event_id = event.id;
+            crate::block(move || -> database::Result<()> {
+                let mut conn = db.get_conn()?;
+
+                for (user_id, status) in updates {
+                    UpdateEventInvite {
+                        status,
+                        comment: None,
+                        responded_at: Some(Utc::now()),
+                    }
+                    .apply(&mut conn, user_id, event_id)?;
+                }
+
+                Ok(())
+            })
+            .await??;
+        }
+
+        Ok(())
+    }
+
+    async fn forget_event(&self, link: &CalendarLink, event_id: EventId) -> Result<()> {
+        let db = self.db.clone();
+        let link_id = link.id;
+
+        crate::block(move || {
+            let mut conn = db.get_conn()?;
+
+            CalendarLinkEvent::delete(&mut conn, link_id, event_id)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn lease_is_held_by_one_instance() {
+        let mut redis_conn = setup().await;
+        let ttl = Duration::from_secs(60);
+
+        assert!(acquire_lease(&mut redis_conn, "a", ttl).await.unwrap());
+        assert!(!acquire_lease(&mut redis_conn, "b", ttl).await.unwrap());
+
+        assert!(renew_lease(&mut redis_conn, "a", ttl).await.unwrap());
+        assert!(!renew_lease(&mut redis_conn, "b", ttl).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn expired_lease_is_lost() {
+        let mut redis_conn = setup().await;
+
+        assert!(
+            acquire_lease(&mut redis_conn, "a", Duration::from_millis(10))
+                .await
+                .unwrap()
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(!renew_lease(&mut redis_conn, "a", Duration::from_secs(60))
+            .await
+            .unwrap());
+        assert!(acquire_lease(&mut redis_conn, "b", Duration::from_secs(60))
+            .await
+            .unwrap());
+    }
+}
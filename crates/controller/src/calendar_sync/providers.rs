@@ -0,0 +1,585 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! OAuth flows and event APIs of the Google Calendar API and the Microsoft Graph API
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use controller_shared::settings::CalendarSyncClient;
+use db_storage::calendar_links::CalendarProvider;
+use db_storage::events::EventInviteStatus;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use types::core::{DateTimeTz, EventId};
+use url::Url;
+
+const GOOGLE_AUTHORIZE_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const GOOGLE_REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+const GOOGLE_EVENTS_URL: &str = "https://www.googleapis.com/calendar/v3/calendars/primary/events";
+const GOOGLE_SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+
+const MICROSOFT_AUTHORIZE_URL: &str =
+    "https://login.microsoftonline.com/common/oauth2/v2.0/authorize";
+const MICROSOFT_TOKEN_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/token";
+const MICROSOFT_EVENTS_URL: &str = "https://graph.microsoft.com/v1.0/me/events";
+const MICROSOFT_SCOPE: &str = "offline_access Calendars.ReadWrite";
+
+/// Event as it is pushed to a linked calendar
+pub(crate) struct CalendarEvent<'e> {
+    pub title: &'e str,
+    pub description: &'e str,
+    pub is_all_day: bool,
+    /// Start of the first occurrence
+    pub starts_at: DateTimeTz,
+    /// End of the first occurrence
+    pub ends_at: DateTimeTz,
+    /// Recurrence rules separated by newlines
+    pub recurrence_pattern: Option<&'e str>,
+    /// Email addresses of the invitees
+    pub attendees: &'e [String],
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenResponse {
+    pub access_token: String,
+    /// Not returned by all providers when refreshing a token
+    pub refresh_token: Option<String>,
+    pub expires_in: i64,
+}
+
+impl TokenResponse {
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::seconds(self.expires_in)
+    }
+}
+
+/// Returns the URL the user has to be redirected to, to grant access to the calendar
+pub(crate) fn authorize_url(
+    provider: CalendarProvider,
+    client: &CalendarSyncClient,
+    redirect_uri: &str,
+    state: &str,
+) -> Url {
+    let (url, scope) = match provider {
+        CalendarProvider::Google => (GOOGLE_AUTHORIZE_URL, GOOGLE_SCOPE),
+        CalendarProvider::Microsoft => (MICROSOFT_AUTHORIZE_URL, MICROSOFT_SCOPE),
+    };
+
+    let mut url = Url::parse(url).expect("valid url");
+
+    url.query_pairs_mut()
+        .append_pair("client_id", &client.client_id)
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("response_type", "code")
+        .append_pair("scope", scope)
+        .append_pair("state", state);
+
+    if provider == CalendarProvider::Google {
+        // Google only returns a refresh token when asked for offline access
+        url.query_pairs_mut()
+            .append_pair("access_type", "offline")
+            .append_pair("prompt", "consent");
+    }
+
+    url
+}
+
+fn token_url(provider: CalendarProvider) -> &'static str {
+    match provider {
+        CalendarProvider::Google => GOOGLE_TOKEN_URL,
+        CalendarProvider::Microsoft => MICROSOFT_TOKEN_URL,
+    }
+}
+
+async fn request_token(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    params: &[(&str, &str)],
+) -> Result<TokenResponse> {
+    let response = http
+        .post(token_url(provider))
+        .form(params)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    serde_json::from_slice(&response).context("Invalid token response")
+}
+
+/// Exchange the authorization code returned to the redirect URI for tokens
+pub(crate) async fn exchange_code(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    client: &CalendarSyncClient,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<TokenResponse> {
+    request_token(
+        http,
+        provider,
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+        ],
+    )
+    .await
+}
+
+pub(crate) async fn refresh_token(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    client: &CalendarSyncClient,
+    refresh_token: &str,
+) -> Result<TokenResponse> {
+    request_token(
+        http,
+        provider,
+        &[
+            ("grant_type", "refresh_token"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("refresh_token", refresh_token),
+        ],
+    )
+    .await
+}
+
+/// Revoke the refresh token together with the access tokens issued with it
+///
+/// Returns false if the provider does not support revoking single tokens.
+pub(crate) async fn revoke_token(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    token: &str,
+) -> Result<bool> {
+    match provider {
+        CalendarProvider::Google => {
+            http.post(GOOGLE_REVOKE_URL)
+                .form(&[("token", token)])
+                .send()
+                .await?
+                .error_for_status()?;
+
+            Ok(true)
+        }
+        // The Microsoft identity platform only revokes all sessions of a user, unused refresh tokens expire after
+        // 90 days
+        CalendarProvider::Microsoft => Ok(false),
+    }
+}
+
+/// Returns the event resource of the provider's API
+///
+/// Returns `None` if the provider does not support the event, recurring events are only supported by Google.
+pub(crate) fn payload(provider: CalendarProvider, event: &CalendarEvent) -> Option<Value> {
+    match provider {
+        CalendarProvider::Google => Some(google_payload(event)),
+        CalendarProvider::Microsoft if event.recurrence_pattern.is_none() => {
+            Some(microsoft_payload(event))
+        }
+        CalendarProvider::Microsoft => None,
+    }
+}
+
+/// All-day events start and end at midnight UTC
+fn google_time(datetime: DateTimeTz, is_all_day: bool) -> Value {
+    if is_all_day {
+        json!({ "date": datetime.datetime.format("%Y-%m-%d").to_string() })
+    } else {
+        json!({
+            "dateTime": datetime.datetime.to_rfc3339(),
+            "timeZone": datetime.timezone.to_string(),
+        })
+    }
+}
+
+fn google_payload(event: &CalendarEvent) -> Value {
+    let recurrence: Vec<&str> = event
+        .recurrence_pattern
+        .map(|pattern| pattern.lines().filter(|line| !line.is_empty()).collect())
+        .unwrap_or_default();
+
+    let attendees: Vec<Value> = event
+        .attendees
+        .iter()
+        .map(|email| json!({ "email": email }))
+        .collect();
+
+    json!({
+        "summary": event.title,
+        "description": event.description,
+        "start": google_time(event.starts_at, event.is_all_day),
+        "end": google_time(event.ends_at, event.is_all_day),
+        "recurrence": recurrence,
+        "attendees": attendees,
+    })
+}
+
+fn microsoft_payload(event: &CalendarEvent) -> Value {
+    let time = |datetime: DateTimeTz| {
+        let (local, timezone) = if event.is_all_day {
+            (datetime.datetime.naive_utc(), "UTC".to_string())
+        } else {
+            let local = datetime.datetime.with_timezone(datetime.timezone.as_ref());
+
+            (local.naive_local(), datetime.timezone.to_string())
+        };
+
+        json!({
+            "dateTime": local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timeZone": timezone,
+        })
+    };
+
+    let attendees: Vec<Value> = event
+        .attendees
+        .iter()
+        .map(|email| json!({ "emailAddress": { "address": email }, "type": "required" }))
+        .collect();
+
+    json!({
+        "subject": event.title,
+        "body": { "contentType": "text", "content": event.description },
+        "isAllDay": event.is_all_day,
+        "start": time(event.starts_at),
+        "end": time(event.ends_at),
+        "attendees": attendees,
+    })
+}
+
+/// Returns the email addresses of the attendees of an event resource of the provider with their response
+///
+/// Attendees which have not responded yet are returned as [`EventInviteStatus::Pending`], the organizer is skipped.
+pub(crate) fn attendee_statuses(
+    provider: CalendarProvider,
+    event: &Value,
+) -> Vec<(String, EventInviteStatus)> {
+    let attendees = match event["attendees"].as_array() {
+        Some(attendees) => attendees,
+        None => return vec![],
+    };
+
+    attendees
+        .iter()
+        .filter_map(|attendee| {
+            let (email, response) = match provider {
+                CalendarProvider::Google => (
+                    attendee["email"].as_str()?,
+                    attendee["responseStatus"].as_str()?,
+                ),
+                CalendarProvider::Microsoft => (
+                    attendee["emailAddress"]["address"].as_str()?,
+                    attendee["status"]["response"].as_str()?,
+                ),
+            };
+
+            let status = match response {
+                "accepted" => EventInviteStatus::Accepted,
+                "declined" => EventInviteStatus::Declined,
+                "tentative" | "tentativelyAccepted" => EventInviteStatus::Tentative,
+                "needsAction" | "notResponded" | "none" => EventInviteStatus::Pending,
+                _ => return None,
+            };
+
+            Some((email.to_owned(), status))
+        })
+        .collect()
+}
+
+fn events_url(provider: CalendarProvider) -> &'static str {
+    match provider {
+        CalendarProvider::Google => GOOGLE_EVENTS_URL,
+        CalendarProvider::Microsoft => MICROSOFT_EVENTS_URL,
+    }
+}
+
+fn event_url(provider: CalendarProvider, external_id: &str) -> Result<Url> {
+    let mut url = Url::parse(events_url(provider))?;
+
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("Invalid events url"))?
+        .push(external_id);
+
+    Ok(url)
+}
+
+/// Send a request to the event API, returns `None` if the event does not exist (anymore)
+async fn send(
+    http: &reqwest::Client,
+    method: Method,
+    url: Url,
+    access_token: &str,
+    body: Option<&Value>,
+) -> Result<Option<Value>> {
+    let mut request = http.request(method, url).bearer_auth(access_token);
+
+    if let Some(body) = body {
+        request = request
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(body)?);
+    }
+
+    let response = request.send().await?;
+
+    if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+        return Ok(None);
+    }
+
+    let response = response.error_for_status()?.bytes().await?;
+
+    if response.is_empty() {
+        return Ok(Some(Value::Null));
+    }
+
+    let value = serde_json::from_slice(&response).context("Invalid event response")?;
+
+    Ok(Some(value))
+}
+
+/// Returns the payload to create the event with, which makes the creation idempotent
+///
+/// Google events get an id derived from the id of the event, Microsoft events a transaction id. Retrying the creation
+/// after the id of the created event could not be stored therefore does not create a second event.
+fn creation_payload(provider: CalendarProvider, event_id: EventId, payload: &Value) -> Value {
+    let mut payload = payload.clone();
+
+    match provider {
+        // Google event ids consist of base32hex characters, which include the hex digits of the uuid
+        CalendarProvider::Google => payload["id"] = json!(event_id.inner().simple().to_string()),
+        CalendarProvider::Microsoft => payload["transactionId"] = json!(event_id.to_string()),
+    }
+
+    payload
+}
+
+/// Create the event in the calendar or update it if it has been created before, returns the id of the event
+pub(crate) async fn upsert_event(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    access_token: &str,
+    event_id: EventId,
+    external_id: Option<&str>,
+    payload: &Value,
+) -> Result<String> {
+    if let Some(external_id) = external_id {
+        let method = match provider {
+            CalendarProvider::Google => Method::PUT,
+            CalendarProvider::Microsoft => Method::PATCH,
+        };
+
+        let url = event_url(provider, external_id)?;
+
+        // Recreate the event if it has been deleted in the calendar
+        if send(http, method, url, access_token, Some(payload))
+            .await?
+            .is_some()
+        {
+            return Ok(external_id.to_owned());
+        }
+    }
+
+    let payload = creation_payload(provider, event_id, payload);
+
+    let response = http
+        .post(events_url(provider))
+        .bearer_auth(access_token)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&payload)?)
+        .send()
+        .await?;
+
+    if matches!(provider, CalendarProvider::Google) && response.status() == StatusCode::CONFLICT {
+        // The event has already been created by a previous attempt or has been deleted in the calendar, deleted
+        // Google events keep their id and are restored by updating them
+        let id = payload["id"]
+            .as_str()
+            .context("Missing event id")?
+            .to_owned();
+
+        let mut payload = payload;
+        payload["status"] = json!("confirmed");
+
+        send(
+            http,
+            Method::PUT,
+            event_url(provider, &id)?,
+            access_token,
+            Some(&payload),
+        )
+        .await?
+        .context("Conflicting event not found")?;
+
+        return Ok(id);
+    }
+
+    let created: Value = serde_json::from_slice(&response.error_for_status()?.bytes().await?)
+        .context("Invalid event response")?;
+
+    created["id"]
+        .as_str()
+        .map(ToOwned::to_owned)
+        .context("Created event has no id")
+}
+
+/// Returns the event resource, or `None` if it has been deleted in the calendar
+pub(crate) async fn get_event(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    access_token: &str,
+    external_id: &str,
+) -> Result<Option<Value>> {
+    let url = event_url(provider, external_id)?;
+
+    send(http, Method::GET, url, access_token, None).await
+}
+
+pub(crate) async fn delete_event(
+    http: &reqwest::Client,
+    provider: CalendarProvider,
+    access_token: &str,
+    external_id: &str,
+) -> Result<()> {
+    let url = event_url(provider, external_id)?;
+
+    send(http, Method::DELETE, url, access_token, None).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+    use pretty_assertions::assert_eq;
+
+    fn berlin(hour: u32) -> DateTimeTz {
+        DateTimeTz {
+            datetime: Utc.with_ymd_and_hms(2023, 3, 20, hour, 0, 0).unwrap(),
+            timezone: Tz::Europe__Berlin.into(),
+        }
+    }
+
+    fn event<'e>(
+        recurrence_pattern: Option<&'e str>,
+        attendees: &'e [String],
+    ) -> CalendarEvent<'e> {
+        CalendarEvent {
+            title: "Weekly",
+            description: "Team meeting",
+            is_all_day: false,
+            starts_at: berlin(9),
+            ends_at: berlin(10),
+            recurrence_pattern,
+            attendees,
+        }
+    }
+
+    #[test]
+    fn google_event() {
+        let attendees = ["alice@example.org".to_string()];
+
+        assert_eq!(
+            payload(
+                CalendarProvider::Google,
+                &event(Some("RRULE:FREQ=WEEKLY"), &attendees)
+            ),
+            Some(json!({
+                "summary": "Weekly",
+                "description": "Team meeting",
+                "start": { "dateTime": "2023-03-20T09:00:00+00:00", "timeZone": "Europe/Berlin" },
+                "end": { "dateTime": "2023-03-20T10:00:00+00:00", "timeZone": "Europe/Berlin" },
+                "recurrence": ["RRULE:FREQ=WEEKLY"],
+                "attendees": [{ "email": "alice@example.org" }],
+            }))
+        );
+    }
+
+    #[test]
+    fn microsoft_event() {
+        assert_eq!(
+            payload(CalendarProvider::Microsoft, &event(None, &[])),
+            Some(json!({
+                "subject": "Weekly",
+                "body": { "contentType": "text", "content": "Team meeting" },
+                "isAllDay": false,
+                "start": { "dateTime": "2023-03-20T10:00:00", "timeZone": "Europe/Berlin" },
+                "end": { "dateTime": "2023-03-20T11:00:00", "timeZone": "Europe/Berlin" },
+                "attendees": [],
+            }))
+        );
+
+        assert_eq!(
+            payload(
+                CalendarProvider::Microsoft,
+                &event(Some("RRULE:FREQ=WEEKLY"), &[])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn idempotent_creation() {
+        let event_id = EventId::from(uuid::Uuid::from_u128(
+            0xa1b2c3d4_0000_4000_8000_000000000001,
+        ));
+        let payload = json!({ "summary": "Weekly" });
+
+        assert_eq!(
+            creation_payload(CalendarProvider::Google, event_id, &payload),
+            json!({ "summary": "Weekly", "id": "a1b2c3d4000040008000000000000001" })
+        );
+
+        assert_eq!(
+            creation_payload(CalendarProvider::Microsoft, event_id, &payload),
+            json!({
+                "summary": "Weekly",
+                "transactionId": "a1b2c3d4-0000-4000-8000-000000000001",
+            })
+        );
+    }
+
+    #[test]
+    fn responses_of_attendees() {
+        let google = json!({
+            "attendees": [
+                { "email": "alice@example.org", "responseStatus": "accepted" },
+                { "email": "bob@example.org", "responseStatus": "needsAction" },
+            ]
+        });
+
+        assert_eq!(
+            attendee_statuses(CalendarProvider::Google, &google),
+            vec![
+                ("alice@example.org".into(), EventInviteStatus::Accepted),
+                ("bob@example.org".into(), EventInviteStatus::Pending),
+            ]
+        );
+
+        let microsoft = json!({
+            "attendees": [
+                {
+                    "emailAddress": { "address": "alice@example.org" },
+                    "status": { "response": "tentativelyAccepted" }
+                },
+                {
+                    "emailAddress": { "address": "organizer@example.org" },
+                    "status": { "response": "organizer" }
+                },
+            ]
+        });
+
+        assert_eq!(
+            attendee_statuses(CalendarProvider::Microsoft, &microsoft),
+            vec![("alice@example.org".into(), EventInviteStatus::Tentative)]
+        );
+    }
+}
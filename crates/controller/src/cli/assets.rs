@@ -0,0 +1,395 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Compares the assets in the database with the objects in the object storage
+//!
+//! The assets and the objects are both iterated in batches ordered by the asset id, so the comparison does not need
+//! to hold all of them in memory at once.
+use crate::storage::assets::asset_key;
+use crate::storage::{ObjectStorage, StoredObject};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use controller_shared::settings::Settings;
+use database::{Db, DbConnection};
+use db_storage::assets::{Asset, AssetScanStatus};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use tabled::{Style, Table, Tabled};
+use types::core::AssetId;
+use uuid::Uuid;
+
+const ASSET_PREFIX: &str = "assets/";
+
+/// Number of assets loaded from the database at once
+const ASSET_BATCH_SIZE: i64 = 1000;
+
+/// Objects younger than this are never reported as orphaned
+///
+/// Stored objects get their asset entry after they have been scanned for viruses, an upload which is still being
+/// processed must not be deleted.
+const MIN_ORPHAN_AGE_HOURS: i64 = 24;
+
+pub(crate) struct ReindexAssetsConfig {
+    pub(crate) delete_orphans: bool,
+    pub(crate) remove_missing: bool,
+}
+
+#[derive(Tabled)]
+struct FindingTableRow {
+    problem: &'static str,
+    key: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Finding {
+    /// The asset exists in the database, but not in the storage
+    Missing(AssetId),
+    /// The object in the storage has no asset in the database
+    Orphan(String),
+}
+
+/// Result of comparing the next asset with the next object
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    /// Both lists are exhausted
+    Done,
+    /// The asset and the object match, advance both
+    Match,
+    /// Advance the assets, optionally reporting the asset
+    NextAsset(Option<Finding>),
+    /// Advance the objects, optionally reporting the object
+    NextObject(Option<Finding>),
+}
+
+/// Returns the id of the asset the object belongs to, or `None` if the key is no asset key
+fn object_asset_id(object: &StoredObject) -> Option<AssetId> {
+    object
+        .key
+        .strip_prefix(ASSET_PREFIX)
+        .and_then(|id| id.parse::<Uuid>().ok())
+        .map(AssetId::from)
+}
+
+/// Compare the next asset and the next object of the two lists which are both ordered by the asset id
+fn step(
+    asset: Option<&Asset>,
+    object: Option<&StoredObject>,
+    orphaned_before: DateTime<Utc>,
+) -> Step {
+    let orphan = |object: &StoredObject| {
+        let old_enough = object
+            .last_modified
+            .map(|last_modified| last_modified < orphaned_before)
+            .unwrap_or(false);
+
+        Step::NextObject(old_enough.then(|| Finding::Orphan(object.key.clone())))
+    };
+
+    let missing = |asset: &Asset| {
+        // infected assets have been moved into quarantine
+        let missing = asset.scan_status != AssetScanStatus::Infected;
+
+        Step::NextAsset(missing.then_some(Finding::Missing(asset.id)))
+    };
+
+    match (asset, object) {
+        (None, None) => Step::Done,
+        (Some(asset), None) => missing(asset),
+        (None, Some(object)) => orphan(object),
+        (Some(asset), Some(object)) => match object_asset_id(object) {
+            // keys which are no asset keys can be handled regardless of their position
+            None => orphan(object),
+            Some(object_id) => match asset.id.cmp(&object_id) {
+                Ordering::Equal => Step::Match,
+                Ordering::Less => missing(asset),
+                Ordering::Greater => orphan(object),
+            },
+        },
+    }
+}
+
+/// Iterates all assets of the database in batches
+struct AssetBatches<'a> {
+    conn: &'a mut DbConnection,
+    batch: VecDeque<Asset>,
+    last_id: Option<AssetId>,
+    exhausted: bool,
+}
+
+impl AssetBatches<'_> {
+    fn peek(&mut self) -> Result<Option<&Asset>> {
+        if self.batch.is_empty() && !self.exhausted {
+            let batch = Asset::get_batch_after(self.conn, self.last_id, ASSET_BATCH_SIZE)?;
+
+            self.exhausted = (batch.len() as i64) < ASSET_BATCH_SIZE;
+            self.last_id = batch.last().map(|asset| asset.id).or(self.last_id);
+            self.batch = batch.into();
+        }
+
+        Ok(self.batch.front())
+    }
+
+    fn advance(&mut self) {
+        self.batch.pop_front();
+    }
+}
+
+/// Iterates all asset objects of the storage page by page
+struct ObjectPages<'a> {
+    storage: &'a ObjectStorage,
+    page: VecDeque<StoredObject>,
+    continuation_token: Option<String>,
+    exhausted: bool,
+}
+
+impl ObjectPages<'_> {
+    async fn peek(&mut self) -> Result<Option<&StoredObject>> {
+        while self.page.is_empty() && !self.exhausted {
+            let (page, continuation_token) = self
+                .storage
+                .list_objects_page(ASSET_PREFIX, self.continuation_token.take())
+                .await?;
+
+            self.exhausted = continuation_token.is_none();
+            self.continuation_token = continuation_token;
+            self.page = page.into();
+        }
+
+        Ok(self.page.front())
+    }
+
+    fn advance(&mut self) {
+        self.page.pop_front();
+    }
+}
+
+/// Implementation of the `k3k-controller reindex-assets` command
+///
+/// Reports assets which are missing in the object storage and objects which have no asset in the database.
+/// Both can optionally be cleaned up. Infected assets are skipped as they live in quarantine, objects modified within
+/// the last 24 hours are skipped as they might belong to an upload which is still being processed.
+pub(crate) async fn reindex_assets(settings: Settings, config: ReindexAssetsConfig) -> Result<()> {
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+    let storage = ObjectStorage::new(&settings.minio, None).await?;
+
+    let orphaned_before = Utc::now() - Duration::hours(MIN_ORPHAN_AGE_HOURS);
+
+    let mut assets = AssetBatches {
+        conn: &mut conn,
+        batch: VecDeque::new(),
+        last_id: None,
+        exhausted: false,
+    };
+    let mut objects = ObjectPages {
+        storage: &storage,
+        page: VecDeque::new(),
+        continuation_token: None,
+        exhausted: false,
+    };
+
+    let mut asset_count = 0usize;
+    let mut findings = Vec::new();
+
+    loop {
+        let asset = assets.peek()?;
+        let object = objects.peek().await?;
+
+        match step(asset, object, orphaned_before) {
+            Step::Done => break,
+            Step::Match => {
+                asset_count += 1;
+                assets.advance();
+                objects.advance();
+            }
+            Step::NextAsset(finding) => {
+                asset_count += 1;
+                findings.extend(finding);
+                assets.advance();
+            }
+            Step::NextObject(finding) => {
+                findings.extend(finding);
+                objects.advance();
+            }
+        }
+    }
+
+    if findings.is_empty() {
+        println!("Assets are consistent ({asset_count} assets)");
+        return Ok(());
+    }
+
+    let rows = findings.iter().map(|finding| match finding {
+        Finding::Missing(id) => FindingTableRow {
+            problem: "missing in storage",
+            key: asset_key(id),
+        },
+        Finding::Orphan(key) => FindingTableRow {
+            problem: "orphaned object",
+            key: key.clone(),
+        },
+    });
+
+    println!("{}", Table::new(rows).with(Style::ascii()));
+
+    let missing: Vec<AssetId> = findings
+        .iter()
+        .filter_map(|finding| match finding {
+            Finding::Missing(id) => Some(*id),
+            Finding::Orphan(_) => None,
+        })
+        .collect();
+
+    if config.remove_missing && !missing.is_empty() {
+        Asset::delete_by_ids(&mut conn, &missing)?;
+
+        println!("Removed {} asset(s) from the database", missing.len());
+    }
+
+    let orphans: Vec<&String> = findings
+        .iter()
+        .filter_map(|finding| match finding {
+            Finding::Missing(_) => None,
+            Finding::Orphan(key) => Some(key),
+        })
+        .collect();
+
+    if config.delete_orphans && !orphans.is_empty() {
+        for key in &orphans {
+            storage
+                .delete((*key).clone())
+                .await
+                .with_context(|| format!("Failed to delete object {key}"))?;
+        }
+
+        println!("Deleted {} orphaned object(s)", orphans.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use types::core::TenantId;
+
+    fn asset(id: u128, scan_status: AssetScanStatus) -> Asset {
+        Asset {
+            id: AssetId::from(Uuid::from_u128(id)),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            namespace: None,
+            kind: "file".into(),
+            filename: "file.pdf".into(),
+            tenant_id: TenantId::from(Uuid::nil()),
+            scan_status,
+        }
+    }
+
+    fn object(key: String, age_hours: i64) -> StoredObject {
+        StoredObject {
+            key,
+            last_modified: Some(Utc::now() - Duration::hours(age_hours)),
+        }
+    }
+
+    fn asset_object(id: u128, age_hours: i64) -> StoredObject {
+        object(asset_key(&AssetId::from(Uuid::from_u128(id))), age_hours)
+    }
+
+    /// Drive [`step`] over two complete lists
+    fn compare(assets: &[Asset], objects: &[StoredObject]) -> Vec<Finding> {
+        let orphaned_before = Utc::now() - Duration::hours(MIN_ORPHAN_AGE_HOURS);
+
+        let mut assets = assets.iter().peekable();
+        let mut objects = objects.iter().peekable();
+        let mut findings = Vec::new();
+
+        loop {
+            match step(
+                assets.peek().copied(),
+                objects.peek().copied(),
+                orphaned_before,
+            ) {
+                Step::Done => return findings,
+                Step::Match => {
+                    assets.next();
+                    objects.next();
+                }
+                Step::NextAsset(finding) => {
+                    findings.extend(finding);
+                    assets.next();
+                }
+                Step::NextObject(finding) => {
+                    findings.extend(finding);
+                    objects.next();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn consistent_assets() {
+        let assets = [
+            asset(1, AssetScanStatus::Clean),
+            asset(2, AssetScanStatus::NotScanned),
+        ];
+        let objects = [asset_object(1, 48), asset_object(2, 48)];
+
+        assert_eq!(compare(&assets, &objects), vec![]);
+    }
+
+    #[test]
+    fn missing_and_orphaned_assets() {
+        let assets = [
+            asset(1, AssetScanStatus::Clean),
+            asset(2, AssetScanStatus::Clean),
+            asset(4, AssetScanStatus::Clean),
+        ];
+        let objects = [
+            asset_object(1, 48),
+            asset_object(3, 48),
+            asset_object(4, 48),
+            asset_object(5, 48),
+        ];
+
+        assert_eq!(
+            compare(&assets, &objects),
+            vec![
+                Finding::Missing(assets[1].id),
+                Finding::Orphan(objects[1].key.clone()),
+                Finding::Orphan(objects[3].key.clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn infected_assets_are_not_missing() {
+        let assets = [asset(1, AssetScanStatus::Infected)];
+
+        assert_eq!(compare(&assets, &[]), vec![]);
+    }
+
+    #[test]
+    fn recent_objects_are_no_orphans() {
+        let objects = [asset_object(1, 1), asset_object(2, 48)];
+
+        assert_eq!(
+            compare(&[], &objects),
+            vec![Finding::Orphan(objects[1].key.clone())]
+        );
+    }
+
+    #[test]
+    fn keys_without_asset_id_are_orphans() {
+        let assets = [asset(1, AssetScanStatus::Clean)];
+        let objects = [object("assets/unknown".into(), 48), asset_object(1, 48)];
+
+        assert_eq!(
+            compare(&assets, &objects),
+            vec![Finding::Orphan("assets/unknown".into())]
+        );
+    }
+}
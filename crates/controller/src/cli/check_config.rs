@@ -0,0 +1,486 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Validation of the settings file
+//!
+//! Reports every issue found in the settings instead of failing at the first one. The report is printed as JSON so it
+//! can be consumed by deployment tooling.
+use anyhow::{bail, Context, Result};
+use config::{Config, File, FileFormat};
+use controller_shared::settings::{HttpListener, Settings, TariffAssignment, VirusScanBackend};
+use database::{Db, OptionalExt};
+use db_storage::tariffs::Tariff;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use url::Url;
+
+/// Time to wait for a connection when probing a service
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+struct Report {
+    valid: bool,
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Issue {
+    severity: Severity,
+    key: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Severity {
+    Error,
+    Warning,
+}
+
+impl Issue {
+    fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Validate the settings file and print a JSON report to stdout
+///
+/// When `probe` is set, the services referenced in the settings are checked for reachability.
+/// Returns an error if the report contains any errors.
+pub async fn check_config(config: &str, probe: bool) -> Result<()> {
+    let mut issues = vec![];
+
+    match raw_config(config) {
+        Ok(raw) => {
+            let schema = serde_json::to_value(schemars::schema_for!(Settings))
+                .context("Failed to serialize settings schema")?;
+
+            check_unknown_keys(&raw, &schema, &mut issues);
+        }
+        Err(e) => issues.push(Issue::error("", format!("{e:#}"))),
+    }
+
+    match Settings::load(config) {
+        Ok(settings) => {
+            check_settings(&settings, &mut issues);
+
+            if probe {
+                probe_services(&settings, &mut issues).await;
+            }
+        }
+        Err(e) => issues.push(Issue::error("", format!("Failed to load settings, {e}"))),
+    }
+
+    let report = Report {
+        valid: !issues.iter().any(|issue| issue.severity == Severity::Error),
+        issues,
+    };
+
+    let json = serde_json::to_string_pretty(&report).context("Failed to serialize report")?;
+    println!("{json}");
+
+    if !report.valid {
+        bail!("The settings contain errors");
+    }
+
+    Ok(())
+}
+
+/// Write the JSON schema of the settings to `output` or stdout
+pub fn export_config_schema(output: Option<&Path>) -> Result<()> {
+    let schema = schemars::schema_for!(Settings);
+
+    let json =
+        serde_json::to_string_pretty(&schema).context("Failed to serialize settings schema")?;
+
+    match output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("Failed to write settings schema to {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+/// Read the settings file without applying environment variables or deserializing it into [`Settings`]
+fn raw_config(config: &str) -> Result<Value> {
+    Config::builder()
+        .add_source(File::new(config, FileFormat::Toml))
+        .build()
+        .and_then(|config| config.try_deserialize())
+        .context("Failed to read settings file")
+}
+
+/// Add a warning for every key of `value` which is not known to the `schema`
+///
+/// Unknown top level keys may belong to settings of modules which are not part of the controller core.
+fn check_unknown_keys(value: &Value, schema: &Value, issues: &mut Vec<Issue>) {
+    let definitions = schema
+        .get("definitions")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+
+    walk(value, schema, &definitions, "", issues);
+}
+
+fn walk(
+    value: &Value,
+    schema: &Value,
+    definitions: &Map<String, Value>,
+    path: &str,
+    issues: &mut Vec<Issue>,
+) {
+    match value {
+        Value::Object(object) => {
+            // Schemas without properties are maps, their keys cannot be checked
+            let properties = match properties(schema, definitions) {
+                Some(properties) => properties,
+                None => return,
+            };
+
+            for (key, value) in object {
+                let key_path = join(path, key);
+
+                match properties.get(key) {
+                    Some(schema) => walk(value, schema, definitions, &key_path, issues),
+                    None if path.is_empty() => issues.push(Issue::warning(
+                        key_path,
+                        "Unknown setting, ignored unless used by a module",
+                    )),
+                    None => issues.push(Issue::warning(key_path, "Unknown setting, ignored")),
+                }
+            }
+        }
+        Value::Array(array) => {
+            if let Some(items) = items(schema, definitions) {
+                for (i, value) in array.iter().enumerate() {
+                    walk(value, &items, definitions, &format!("{path}[{i}]"), issues);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Resolve a `$ref` to the schema in the definitions
+fn resolve<'s>(schema: &'s Value, definitions: &'s Map<String, Value>) -> &'s Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+        .and_then(|name| definitions.get(name))
+        .unwrap_or(schema)
+}
+
+/// Subschemas of the schema, e.g. the variants of an enum or the `null` and value schemas of an `Option`
+fn subschemas(schema: &Value) -> impl Iterator<Item = &Value> {
+    ["allOf", "anyOf", "oneOf"]
+        .into_iter()
+        .filter_map(|key| schema.get(key).and_then(Value::as_array))
+        .flatten()
+}
+
+/// All properties known to the schema, `None` if the schema does not describe an object with fixed properties
+fn properties(schema: &Value, definitions: &Map<String, Value>) -> Option<Map<String, Value>> {
+    let schema = resolve(schema, definitions);
+
+    let mut found = None;
+
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        found
+            .get_or_insert_with(Map::new)
+            .extend(properties.clone());
+    }
+
+    for subschema in subschemas(schema) {
+        if let Some(properties) = properties(subschema, definitions) {
+            found.get_or_insert_with(Map::new).extend(properties);
+        }
+    }
+
+    found
+}
+
+/// The schema of the items of an array schema
+fn items(schema: &Value, definitions: &Map<String, Value>) -> Option<Value> {
+    let schema = resolve(schema, definitions);
+
+    if let Some(items) = schema.get("items") {
+        return Some(items.clone());
+    }
+
+    subschemas(schema).find_map(|subschema| items(subschema, definitions))
+}
+
+/// Checks which cannot be expressed by the types of the settings
+fn check_settings(settings: &Settings, issues: &mut Vec<Issue>) {
+    if let Some(tls) = &settings.http.tls {
+        for (key, path) in [
+            ("http.tls.certificate", &tls.certificate),
+            ("http.tls.private_key", &tls.private_key),
+        ] {
+            if let Err(e) = std::fs::File::open(path) {
+                issues.push(Issue::error(
+                    key,
+                    format!("Cannot read {}, {e}", path.display()),
+                ));
+            }
+        }
+    }
+
+    if settings.http.tls.is_some() && matches!(settings.http.listener, HttpListener::Unix { .. }) {
+        issues.push(Issue::error(
+            "http.tls",
+            "TLS is not supported when binding to a unix socket",
+        ));
+    }
+
+    if let Some(inactivity) = &settings.inactivity {
+        if inactivity.warning >= inactivity.timeout {
+            issues.push(Issue::error(
+                "inactivity.warning",
+                "The warning must be sent before the timeout",
+            ));
+        }
+    }
+
+    if let Some(error_reporting) = &settings.error_reporting {
+        if !(0.0..=1.0).contains(&error_reporting.sample_rate) {
+            issues.push(Issue::error(
+                "error_reporting.sample_rate",
+                "The sample rate must be between 0 and 1",
+            ));
+        }
+    }
+
+    if let TariffAssignment::Static { static_tariff_name } = &settings.tariffs.assignment {
+        if static_tariff_name.trim().is_empty() {
+            issues.push(Issue::error(
+                "tariffs.assignment.static_tariff_name",
+                "The static tariff name must not be empty",
+            ));
+        }
+    }
+}
+
+/// Check that the services in the settings accept connections
+async fn probe_services(settings: &Settings, issues: &mut Vec<Issue>) {
+    let mut targets = vec![
+        ("database.url".to_owned(), settings.database.url.clone()),
+        ("redis.url".to_owned(), settings.redis.url.to_string()),
+        ("rabbit_mq.url".to_owned(), settings.rabbit_mq.url.clone()),
+        (
+            "keycloak.base_url".to_owned(),
+            settings.keycloak.base_url.to_string(),
+        ),
+        ("minio.uri".to_owned(), settings.minio.uri.clone()),
+    ];
+
+    if let Some(ldap) = &settings.ldap {
+        targets.push(("ldap.url".to_owned(), ldap.url.clone()));
+    }
+    if let Some(etherpad) = &settings.etherpad {
+        targets.push(("etherpad.url".to_owned(), etherpad.url.to_string()));
+    }
+    if let Some(spacedeck) = &settings.spacedeck {
+        targets.push(("spacedeck.url".to_owned(), spacedeck.url.to_string()));
+    }
+    if let Some(moderation_api) = settings
+        .chat_filter
+        .as_ref()
+        .and_then(|chat_filter| chat_filter.moderation_api.as_ref())
+    {
+        targets.push((
+            "chat_filter.moderation_api.url".to_owned(),
+            moderation_api.url.to_string(),
+        ));
+    }
+    if let Some(error_reporting) = &settings.error_reporting {
+        targets.push((
+            "error_reporting.dsn".to_owned(),
+            error_reporting.dsn.to_string(),
+        ));
+    }
+    for (i, plugin) in settings.plugins.iter().enumerate() {
+        targets.push((format!("plugins[{i}].url"), plugin.url.to_string()));
+    }
+    for (i, notification) in settings.notifications.iter().enumerate() {
+        targets.push((
+            format!("notifications[{i}].url"),
+            notification.url.to_string(),
+        ));
+    }
+
+    for (key, target) in targets {
+        match socket_address(&target) {
+            Some(address) => {
+                if let Err(message) = probe(&address).await {
+                    issues.push(Issue::error(key, message));
+                }
+            }
+            None => issues.push(Issue::error(key, "Cannot determine host and port")),
+        }
+    }
+
+    if let Some(virus_scan) = &settings.virus_scan {
+        match &virus_scan.backend {
+            VirusScanBackend::Clamd { clamd_address } => {
+                if let Err(message) = probe(clamd_address).await {
+                    issues.push(Issue::error("virus_scan.clamd_address", message));
+                }
+            }
+            VirusScanBackend::Icap { icap_url } => match socket_address(icap_url.as_str()) {
+                Some(address) => {
+                    if let Err(message) = probe(&address).await {
+                        issues.push(Issue::error("virus_scan.icap_url", message));
+                    }
+                }
+                None => issues.push(Issue::error(
+                    "virus_scan.icap_url",
+                    "Cannot determine host and port",
+                )),
+            },
+        }
+    }
+
+    if let TariffAssignment::Static { static_tariff_name } = &settings.tariffs.assignment {
+        if let Err(e) = check_static_tariff(settings, static_tariff_name) {
+            issues.push(Issue::error(
+                "tariffs.assignment.static_tariff_name",
+                format!("{e:#}"),
+            ));
+        }
+    }
+}
+
+/// The `host:port` address of an URL, using the default port of the scheme if none is given
+fn socket_address(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+
+    let port = url.port_or_known_default().or(match url.scheme() {
+        "postgres" | "postgresql" => Some(5432),
+        "redis" | "rediss" => Some(6379),
+        "amqp" => Some(5672),
+        "amqps" => Some(5671),
+        "ldap" => Some(389),
+        "ldaps" => Some(636),
+        "icap" => Some(1344),
+        _ => None,
+    })?;
+
+    Some(format!("{host}:{port}"))
+}
+
+async fn probe(address: &str) -> Result<(), String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("Cannot connect to {address}, {e}")),
+        Err(_) => Err(format!("Timed out connecting to {address}")),
+    }
+}
+
+fn check_static_tariff(settings: &Settings, name: &str) -> Result<()> {
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+
+    if Tariff::get_by_name(&mut conn, name).optional()?.is_none() {
+        bail!("The static tariff {name:?} does not exist");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn unknown_keys(raw: Value) -> Vec<String> {
+        let schema = serde_json::to_value(schemars::schema_for!(Settings)).unwrap();
+
+        let mut issues = vec![];
+        check_unknown_keys(&raw, &schema, &mut issues);
+
+        let mut keys: Vec<String> = issues.into_iter().map(|issue| issue.key).collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn known_keys() {
+        let raw = json!({
+            "database": { "url": "postgres://localhost/k3k", "max_connections": 5 },
+            "tariffs": { "assignment": { "static": { "static_tariff_name": "Default" } } },
+            "plugins": [{ "name": "whiteboard", "url": "http://localhost" }],
+            "notifications": [{
+                "kind": "webhook",
+                "url": "http://localhost",
+                "templates": { "room_created": "{{ room_id }}" }
+            }],
+        });
+
+        assert_eq!(unknown_keys(raw), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_nested_keys() {
+        let raw = json!({
+            "database": { "url": "postgres://localhost/k3k", "max_conections": 5 },
+            "plugins": [{ "name": "whiteboard", "url": "http://localhost", "api-key": "" }],
+            "my_module": { "enabled": true },
+        });
+
+        assert_eq!(
+            unknown_keys(raw),
+            vec!["database.max_conections", "my_module", "plugins[0].api-key"]
+        );
+    }
+
+    #[test]
+    fn socket_addresses() {
+        assert_eq!(
+            socket_address("postgres://k3k:pw@db.example.org/k3k").as_deref(),
+            Some("db.example.org:5432")
+        );
+        assert_eq!(
+            socket_address("https://auth.example.org/auth").as_deref(),
+            Some("auth.example.org:443")
+        );
+        assert_eq!(
+            socket_address("redis://localhost:6380/").as_deref(),
+            Some("localhost:6380")
+        );
+        assert_eq!(
+            socket_address("icap://scanner/avscan").as_deref(),
+            Some("scanner:1344")
+        );
+        assert_eq!(socket_address("localhost"), None);
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Commands to export the events captured in rooms for replays
+use super::rooms::connect_redis;
+use crate::api::signaling::prelude::*;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use controller_shared::settings::Settings;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use types::core::RoomId;
+use uuid::Uuid;
+
+#[derive(Subcommand, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum Command {
+    /// Export the captured events of a room as JSON array, in the order they have been dispatched
+    Export {
+        /// Id of the room
+        #[clap(long)]
+        room: Uuid,
+        /// Write the events to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Delete the captured events of a room
+    Clear {
+        /// Id of the room
+        #[clap(long)]
+        room: Uuid,
+    },
+}
+
+/// Implementation of the `k3k-controller event-capture` commands
+pub(crate) async fn handle_command(settings: Settings, command: Command) -> Result<()> {
+    if let Some(encryption_key) = &settings.redis.encryption_key {
+        crate::redis_encryption::init(encryption_key).context("Invalid redis encryption key")?;
+    }
+
+    let mut redis_conn = connect_redis(&settings).await?;
+
+    match command {
+        Command::Export { room, output } => {
+            let records = capture::read(&mut redis_conn, RoomId::from(room)).await?;
+
+            match output {
+                Some(path) => {
+                    let mut file = BufWriter::new(
+                        File::create(&path).context("Failed to create the output file")?,
+                    );
+                    serde_json::to_writer_pretty(&mut file, &records)?;
+                    file.flush()?;
+
+                    eprintln!(
+                        "Exported {} captured event(s) of room {room}",
+                        records.len()
+                    );
+                }
+                None => println!("{}", serde_json::to_string_pretty(&records)?),
+            }
+        }
+        Command::Clear { room } => {
+            capture::clear(&mut redis_conn, RoomId::from(room)).await?;
+
+            println!("Deleted the captured events of room {room}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::prelude::SignalingSchemas;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Write the JSON schemas of all signaling messages to `output` or stdout
+pub fn export_schema(
+    register_schemas: impl FnOnce(&mut SignalingSchemas),
+    output: Option<&Path>,
+) -> Result<()> {
+    let mut schemas = SignalingSchemas::default();
+    register_schemas(&mut schemas);
+
+    let json = serde_json::to_string_pretty(&schemas).context("Failed to serialize schemas")?;
+
+    match output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("Failed to write schemas to {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
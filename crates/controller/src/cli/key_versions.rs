@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Commands to inspect the versions of the redis keys of the signaling modules
+use super::rooms::connect_redis;
+use crate::api::signaling::key_versions;
+use anyhow::Result;
+use clap::Subcommand;
+use controller_shared::settings::Settings;
+use tabled::{Style, Table, Tabled};
+
+#[derive(Subcommand, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum Command {
+    /// List the key versions recorded for the signaling modules
+    List,
+    /// Record the key version of a module, e.g. after rolling back a release whose migrated keys were removed
+    Set {
+        /// Namespace of the module
+        namespace: String,
+        /// Key version to record
+        version: u32,
+    },
+}
+
+#[derive(Tabled)]
+struct KeyVersionTableRow {
+    namespace: String,
+    version: u32,
+}
+
+pub(crate) async fn handle_command(settings: Settings, command: Command) -> Result<()> {
+    let mut redis_conn = connect_redis(&settings).await?;
+
+    match command {
+        Command::List => {
+            let rows: Vec<KeyVersionTableRow> = key_versions::get_all(&mut redis_conn)
+                .await?
+                .into_iter()
+                .map(|(namespace, version)| KeyVersionTableRow { namespace, version })
+                .collect();
+
+            println!("{}", Table::new(rows).with(Style::ascii()));
+        }
+        Command::Set { namespace, version } => {
+            key_versions::set(&mut redis_conn, &namespace, version).await?;
+
+            println!("Recorded key version {version} for module {namespace}");
+        }
+    }
+
+    Ok(())
+}
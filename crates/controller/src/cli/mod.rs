@@ -2,15 +2,27 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::prelude::SignalingSchemas;
 use anyhow::{Context, Result};
 use clap::{ArgAction, Parser, Subcommand};
 use controller_shared::settings::Settings;
+use std::path::PathBuf;
+use types::core::RoomId;
+use uuid::Uuid;
 
 mod acl;
+mod assets;
+mod check_config;
+mod event_capture;
+mod export_schema;
 mod fix_acl;
+mod key_versions;
 mod reload;
+mod room_snapshot;
+mod rooms;
 mod tariffs;
 mod tenants;
+mod users;
 
 #[derive(Parser, Debug, Clone)]
 #[clap(name = "k3k-controller")]
@@ -38,6 +50,7 @@ pub struct Args {
 #[clap(rename_all = "kebab_case")]
 enum SubCommand {
     /// Rebuild ACLs based on current data
+    #[clap(alias = "fix-acls")]
     FixAcl {
         /// Do not add user roles
         #[clap(long = "no-user-roles", default_value="true", action=ArgAction::SetFalse)]
@@ -63,6 +76,78 @@ enum SubCommand {
     /// Manage tariffs
     #[clap(subcommand)]
     Tariffs(tariffs::Command),
+
+    /// Manage users
+    #[clap(subcommand)]
+    Users(users::Command),
+
+    /// Export the JSON schemas of all signaling messages
+    ExportSchema {
+        /// Write the schemas to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Validate the configuration file and print a JSON report of all issues
+    CheckConfig {
+        /// Also check that the services referenced in the configuration are reachable
+        #[clap(long)]
+        probe: bool,
+    },
+
+    /// Export the JSON schema of the configuration file
+    ExportConfigSchema {
+        /// Write the schema to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// List all rooms
+    ListRooms {
+        /// Only list rooms which currently have participants
+        #[clap(long)]
+        active: bool,
+    },
+
+    /// Close a running room, all participants are sent out of the room
+    CloseRoom {
+        /// Id of the room to close
+        id: Uuid,
+    },
+
+    /// Remove the signaling state of a room from redis
+    PurgeRedis {
+        /// Id of the room to purge
+        #[clap(long)]
+        room: Uuid,
+        /// Purge the state even if participants are inside the room
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Export and import snapshots of the redis state of a room
+    #[clap(subcommand)]
+    RoomSnapshot(room_snapshot::Command),
+
+    /// Inspect the versions of the redis keys of the signaling modules
+    ///
+    /// Keys are migrated automatically during start of the controller.
+    #[clap(subcommand)]
+    RedisKeyVersions(key_versions::Command),
+
+    /// Export the events captured in rooms to replay them
+    #[clap(subcommand)]
+    EventCapture(event_capture::Command),
+
+    /// Compare the assets in the database with the objects in the storage
+    ReindexAssets {
+        /// Delete objects in the storage which have no asset in the database
+        #[clap(long)]
+        delete_orphans: bool,
+        /// Remove assets from the database which are missing in the storage
+        #[clap(long)]
+        remove_missing: bool,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -94,8 +179,9 @@ impl Args {
 
 /// Parses the CLI-Arguments into [`Args`]
 ///
-/// Also runs (optional) cli commands if necessary
-pub async fn parse_args() -> Result<Args> {
+/// Also runs (optional) cli commands if necessary. `register_schemas` adds the schemas of the signaling modules
+/// which are not part of the controller core and is only called for the `export-schema` command.
+pub async fn parse_args(register_schemas: impl FnOnce(&mut SignalingSchemas)) -> Result<Args> {
     let args = Args::parse();
 
     if args.version {
@@ -106,34 +192,23 @@ pub async fn parse_args() -> Result<Args> {
         reload::trigger_reload()?;
     }
     if let Some(sub_command) = args.cmd.clone() {
-        let settings = Settings::load(&args.config)?;
         match sub_command {
-            SubCommand::FixAcl {
-                user_roles,
-                user_groups,
-                room_creators,
-            } => {
-                let config = fix_acl::FixAclConfig {
-                    user_roles,
-                    user_groups,
-                    room_creators,
-                };
-                fix_acl::fix_acl(settings, config).await?;
+            SubCommand::ExportSchema { output } => {
+                export_schema::export_schema(register_schemas, output.as_deref())?;
             }
-            SubCommand::Acl(subcommand) => {
-                acl::acl(settings, subcommand).await?;
+            SubCommand::ExportConfigSchema { output } => {
+                check_config::export_config_schema(output.as_deref())?;
             }
-            SubCommand::MigrateDb => {
-                let result = db_storage::migrations::migrate_from_url(&settings.database.url)
-                    .await
-                    .context("Failed to migrate database")?;
-                println!("{result:?}");
+            SubCommand::CheckConfig { probe } => {
+                check_config::check_config(&args.config, probe).await?;
             }
-            SubCommand::Tenants(command) => {
-                tenants::handle_command(settings, command)?;
+            SubCommand::RoomSnapshot(command) => {
+                let settings = Settings::load(&args.config)?;
+                room_snapshot::handle_command(settings, register_schemas, command).await?;
             }
-            SubCommand::Tariffs(command) => {
-                tariffs::handle_command(settings, command)?;
+            sub_command => {
+                let settings = Settings::load(&args.config)?;
+                run_command(settings, sub_command).await?;
             }
         }
     }
@@ -141,6 +216,74 @@ pub async fn parse_args() -> Result<Args> {
     Ok(args)
 }
 
+async fn run_command(settings: Settings, sub_command: SubCommand) -> Result<()> {
+    match sub_command {
+        SubCommand::FixAcl {
+            user_roles,
+            user_groups,
+            room_creators,
+        } => {
+            let config = fix_acl::FixAclConfig {
+                user_roles,
+                user_groups,
+                room_creators,
+            };
+            fix_acl::fix_acl(settings, config).await?;
+        }
+        SubCommand::Acl(subcommand) => {
+            acl::acl(settings, subcommand).await?;
+        }
+        SubCommand::MigrateDb => {
+            let result = db_storage::migrations::migrate_from_url(&settings.database.url)
+                .await
+                .context("Failed to migrate database")?;
+            println!("{result:?}");
+        }
+        SubCommand::Tenants(command) => {
+            tenants::handle_command(settings, command)?;
+        }
+        SubCommand::Tariffs(command) => {
+            tariffs::handle_command(settings, command)?;
+        }
+        SubCommand::Users(command) => {
+            users::handle_command(settings, command).await?;
+        }
+        SubCommand::ListRooms { active } => {
+            rooms::list_rooms(settings, active).await?;
+        }
+        SubCommand::CloseRoom { id } => {
+            rooms::close_room(settings, RoomId::from(id)).await?;
+        }
+        SubCommand::PurgeRedis { room, force } => {
+            rooms::purge_redis(settings, RoomId::from(room), force).await?;
+        }
+        SubCommand::RedisKeyVersions(command) => {
+            key_versions::handle_command(settings, command).await?;
+        }
+        SubCommand::EventCapture(command) => {
+            event_capture::handle_command(settings, command).await?;
+        }
+        SubCommand::ReindexAssets {
+            delete_orphans,
+            remove_missing,
+        } => {
+            let config = assets::ReindexAssetsConfig {
+                delete_orphans,
+                remove_missing,
+            };
+            assets::reindex_assets(settings, config).await?;
+        }
+        SubCommand::ExportSchema { .. }
+        | SubCommand::ExportConfigSchema { .. }
+        | SubCommand::CheckConfig { .. }
+        | SubCommand::RoomSnapshot(_) => {
+            unreachable!("handled in parse_args")
+        }
+    }
+
+    Ok(())
+}
+
 const BUILD_INFO: [(&str, Option<&str>); 10] = [
     ("Build Timestamp", option_env!("VERGEN_BUILD_TIMESTAMP")),
     ("Build Version", option_env!("VERGEN_BUILD_SEMVER")),
@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Commands to export and import snapshots of the redis state of rooms
+use super::rooms::connect_redis;
+use crate::api::signaling::prelude::*;
+use crate::api::signaling::snapshot::{self, RoomSnapshot};
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use controller_shared::settings::Settings;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use types::core::RoomId;
+use uuid::Uuid;
+
+#[derive(Subcommand, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum Command {
+    /// Export all redis keys of a room to a JSON file
+    Export {
+        /// Id of the room to export
+        #[clap(long)]
+        room: Uuid,
+        /// Write the snapshot to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the redis keys of a room with a snapshot, which may have been taken of another room
+    Import {
+        /// Id of the room to import the snapshot into
+        #[clap(long)]
+        room: Uuid,
+        /// The snapshot file
+        input: PathBuf,
+        /// Import the snapshot even if participants are inside the room
+        #[clap(long)]
+        force: bool,
+        /// Import the snapshot even if it has been taken with other key versions of the modules
+        #[clap(long)]
+        ignore_key_versions: bool,
+    },
+}
+
+/// Implementation of the `k3k-controller room-snapshot` commands
+///
+/// `register_schemas` adds the signaling modules, the keys of the room are assigned to their namespaces.
+pub(crate) async fn handle_command(
+    settings: Settings,
+    register_schemas: impl FnOnce(&mut SignalingSchemas),
+    command: Command,
+) -> Result<()> {
+    let mut redis_conn = connect_redis(&settings).await?;
+
+    match command {
+        Command::Export { room, output } => {
+            let mut schemas = SignalingSchemas::default();
+            register_schemas(&mut schemas);
+            let namespaces: Vec<&str> = schemas.namespaces().collect();
+
+            let snapshot = snapshot::take(&mut redis_conn, RoomId::from(room), &namespaces).await?;
+
+            match output {
+                Some(path) => {
+                    let mut file = BufWriter::new(
+                        File::create(&path).context("Failed to create the snapshot file")?,
+                    );
+                    serde_json::to_writer_pretty(&mut file, &snapshot)?;
+                    file.flush()?;
+
+                    eprintln!(
+                        "Exported {} redis key(s) of room {room}",
+                        snapshot.entries.len()
+                    );
+                }
+                None => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+            }
+        }
+        Command::Import {
+            room,
+            input,
+            force,
+            ignore_key_versions,
+        } => {
+            let room_id = RoomId::from(room);
+
+            let file = File::open(&input).context("Failed to open the snapshot file")?;
+            let snapshot: RoomSnapshot = serde_json::from_reader(BufReader::new(file))
+                .context("Failed to read the snapshot file")?;
+
+            let participant_count =
+                control::storage::get_participant_count(&mut redis_conn, room_id)
+                    .await?
+                    .unwrap_or(0);
+
+            if participant_count > 0 && !force {
+                bail!(
+                    "Room {room_id} has {participant_count} participant(s), close the room first or use --force"
+                );
+            }
+
+            if !ignore_key_versions
+                && !snapshot::has_current_key_versions(&mut redis_conn, &snapshot).await?
+            {
+                bail!(
+                    "The snapshot has been taken with the key versions {:?}, use --ignore-key-versions to import it anyway",
+                    snapshot.key_versions
+                );
+            }
+
+            let restored = snapshot::restore(&mut redis_conn, room_id, snapshot).await?;
+
+            println!("Imported {restored} redis key(s) into room {room_id}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Operational commands for rooms and their signaling state
+use crate::api::signaling::prelude::{breakout, control};
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{bail, Context, Result};
+use controller_shared::settings::Settings;
+use database::Db;
+use db_storage::rooms::Room;
+use lapin::options::ExchangeDeclareOptions;
+use lapin::{BasicProperties, ExchangeKind};
+use lapin_pool::RabbitMqPool;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use tabled::{Style, Table, Tabled};
+use types::core::{RoomId, Timestamp};
+use types::signaling::NamespacedCommand;
+use uuid::Uuid;
+
+#[derive(Tabled)]
+struct RoomTableRow {
+    id: RoomId,
+    #[tabled(rename = "created by")]
+    created_by: String,
+    #[tabled(rename = "created at")]
+    created_at: String,
+    #[tabled(rename = "waiting room")]
+    waiting_room: bool,
+    participants: isize,
+}
+
+/// Implementation of the `k3k-controller list-rooms [--active]` command
+pub(crate) async fn list_rooms(settings: Settings, active: bool) -> Result<()> {
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+    let mut redis_conn = connect_redis(&settings).await?;
+
+    let participant_counts = get_participant_counts(&mut redis_conn).await?;
+
+    let rows: Vec<RoomTableRow> = Room::get_all_with_creator(&mut conn)?
+        .into_iter()
+        .map(|(room, creator)| RoomTableRow {
+            id: room.id,
+            created_by: creator.email,
+            created_at: room.created_at.to_rfc3339(),
+            waiting_room: room.waiting_room,
+            participants: participant_counts.get(&room.id).copied().unwrap_or(0),
+        })
+        .filter(|row| !active || row.participants > 0)
+        .collect();
+
+    println!("{}", Table::new(rows).with(Style::ascii()));
+
+    Ok(())
+}
+
+/// Implementation of the `k3k-controller close-room <room-id>` command
+///
+/// Tells all participants of the room (including its breakout rooms) to leave via the room's global exchange.
+/// Only the signaling state of the room is used, so rooms which have been moved to the trash can be closed as well.
+pub(crate) async fn close_room(settings: Settings, room_id: RoomId) -> Result<()> {
+    let mut redis_conn = connect_redis(&settings).await?;
+    let participant_count = control::storage::get_participant_count(&mut redis_conn, room_id)
+        .await?
+        .unwrap_or(0);
+
+    if participant_count <= 0 {
+        println!("Room {room_id} is not active");
+        return Ok(());
+    }
+
+    let rabbitmq_pool = RabbitMqPool::from_config(&settings.rabbit_mq.url, 1, 1);
+    let channel = rabbitmq_pool
+        .create_channel()
+        .await
+        .context("Failed to create rabbitmq channel")?;
+
+    let exchange = breakout::rabbitmq::global_exchange_name(room_id);
+
+    channel
+        .exchange_declare(
+            &exchange,
+            ExchangeKind::Topic,
+            ExchangeDeclareOptions {
+                auto_delete: true,
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .await
+        .context("Failed to declare the global room exchange")?;
+
+    let message = serde_json::to_vec(&NamespacedCommand {
+        namespace: control::NAMESPACE,
+        payload: control::rabbitmq::Message::CloseRoom,
+    })?;
+    let properties = BasicProperties::default().with_timestamp(Timestamp::now().timestamp() as u64);
+
+    channel
+        .basic_publish(
+            &exchange,
+            control::rabbitmq::room_all_routing_key(),
+            Default::default(),
+            &message,
+            properties,
+        )
+        .await
+        .context("Failed to publish close message")?;
+
+    rabbitmq_pool.close(0, "close-room done").await?;
+
+    println!("Closed room {room_id} with {participant_count} participant(s)");
+
+    Ok(())
+}
+
+/// Implementation of the `k3k-controller purge-redis --room <room-id> [--force]` command
+///
+/// Removes all signaling state of the room from redis. Refuses to do so while participants are inside the room,
+/// unless `force` is set.
+pub(crate) async fn purge_redis(settings: Settings, room_id: RoomId, force: bool) -> Result<()> {
+    let mut redis_conn = connect_redis(&settings).await?;
+
+    let participant_count = control::storage::get_participant_count(&mut redis_conn, room_id)
+        .await?
+        .unwrap_or(0);
+
+    if participant_count > 0 && !force {
+        bail!(
+            "Room {room_id} has {participant_count} participant(s), close the room first or use --force"
+        );
+    }
+
+    let keys = control::storage::get_room_keys(&mut redis_conn, room_id).await?;
+
+    if keys.is_empty() {
+        println!("No redis keys found for room {room_id}");
+        return Ok(());
+    }
+
+    redis_conn
+        .del::<_, ()>(&keys)
+        .await
+        .context("Failed to delete redis keys")?;
+
+    println!("Deleted {} redis key(s) of room {room_id}", keys.len());
+
+    Ok(())
+}
+
+pub(super) async fn connect_redis(settings: &Settings) -> Result<RedisConnection> {
+    let redis = redis::Client::open(settings.redis.url.clone()).context("Invalid redis url")?;
+    let redis_conn = redis::aio::ConnectionManager::new(redis)
+        .await
+        .context("Failed to create redis connection manager")?;
+
+    Ok(RedisConnection::new(redis_conn))
+}
+
+async fn scan_keys(redis_conn: &mut RedisConnection, pattern: &str) -> Result<Vec<String>> {
+    let mut iter = redis_conn
+        .scan_match::<_, String>(pattern)
+        .await
+        .context("Failed to scan redis keys")?;
+
+    let mut keys = Vec::new();
+
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+/// Returns the participant count of every room which currently has signaling state in redis
+async fn get_participant_counts(
+    redis_conn: &mut RedisConnection,
+) -> Result<HashMap<RoomId, isize>> {
+    let keys = scan_keys(redis_conn, "k3k-signaling:room=*:participant-count").await?;
+
+    let mut counts = HashMap::new();
+
+    for key in keys {
+        let room_id = key
+            .strip_prefix("k3k-signaling:room=")
+            .and_then(|key| key.strip_suffix(":participant-count"))
+            .and_then(|room_id| room_id.parse::<Uuid>().ok())
+            .map(RoomId::from);
+
+        if let Some(room_id) = room_id {
+            let count = control::storage::get_participant_count(redis_conn, room_id)
+                .await?
+                .unwrap_or(0);
+
+            counts.insert(room_id, count);
+        }
+    }
+
+    Ok(counts)
+}
@@ -2,14 +2,17 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use anyhow::{Context, Result};
+use crate::api::v1::room_branding::validate_color;
+use anyhow::{bail, Context, Result};
 use chrono::Utc;
 use clap::Subcommand;
 use controller_shared::settings::Settings;
 use database::Db;
+use db_storage::brandings::TenantBranding;
 use db_storage::tenants::{OidcTenantId, Tenant, UpdateTenant};
 use tabled::{Style, Table, Tabled};
 use types::core::TenantId;
+use url::Url;
 use uuid::Uuid;
 
 #[derive(Subcommand, Debug, Clone)]
@@ -19,6 +22,21 @@ pub enum Command {
     List,
     /// Change a tenants oidc-id
     SetOidcId { id: Uuid, new_oidc_id: String },
+    /// Set the branding shown in all rooms of a tenant, owners can override it for their rooms
+    SetBranding {
+        id: Uuid,
+        /// URL of the logo
+        #[clap(long)]
+        logo_url: Option<Url>,
+        /// Primary color in the `#rrggbb` format
+        #[clap(long)]
+        primary_color: Option<String>,
+        /// Secondary color in the `#rrggbb` format
+        #[clap(long)]
+        secondary_color: Option<String>,
+    },
+    /// Remove the branding of a tenant
+    RemoveBranding { id: Uuid },
 }
 
 pub fn handle_command(settings: Settings, command: Command) -> Result<()> {
@@ -29,6 +47,21 @@ pub fn handle_command(settings: Settings, command: Command) -> Result<()> {
             TenantId::from(id),
             OidcTenantId::from(new_oidc_id),
         ),
+        Command::SetBranding {
+            id,
+            logo_url,
+            primary_color,
+            secondary_color,
+        } => set_branding(
+            settings,
+            TenantBranding {
+                tenant_id: TenantId::from(id),
+                logo_url: logo_url.map(String::from),
+                primary_color,
+                secondary_color,
+            },
+        ),
+        Command::RemoveBranding { id } => remove_branding(settings, TenantId::from(id)),
     }
 }
 
@@ -83,3 +116,39 @@ fn set_oidc_id(settings: Settings, id: TenantId, new_oidc_id: OidcTenantId) -> R
 
     Ok(())
 }
+
+/// Implementation of the `k3k-controller tenants set-branding <tenant-id>` command
+fn set_branding(settings: Settings, branding: TenantBranding) -> Result<()> {
+    for color in [&branding.primary_color, &branding.secondary_color]
+        .into_iter()
+        .flatten()
+    {
+        if validate_color(color).is_err() {
+            bail!("Invalid color {color:?}, expected the format #rrggbb");
+        }
+    }
+
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+
+    // Make sure the tenant exists
+    let tenant = Tenant::get(&mut conn, branding.tenant_id)?;
+
+    branding.upsert(&mut conn)?;
+
+    println!("Updated branding of tenant {}", tenant.id);
+
+    Ok(())
+}
+
+/// Implementation of the `k3k-controller tenants remove-branding <tenant-id>` command
+fn remove_branding(settings: Settings, id: TenantId) -> Result<()> {
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+
+    TenantBranding::delete_by_tenant(&mut conn, id)?;
+
+    println!("Removed branding of tenant {id}");
+
+    Ok(())
+}
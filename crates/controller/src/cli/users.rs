@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::api::v1::middleware::impersonation::IMPERSONATION_ROLE;
+use crate::calendar_sync::CalendarSync;
+use crate::gdpr;
+use crate::storage::ObjectStorage;
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use clap::Subcommand;
+use controller_shared::settings::Settings;
+use database::Db;
+use db_storage::impersonation_log::ImpersonationLogEntry;
+use db_storage::users::User;
+use std::sync::Arc;
+use tabled::{Style, Table, Tabled};
+use types::core::UserId;
+use uuid::Uuid;
+
+#[derive(Subcommand, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum Command {
+    /// Erase all personal data of a user
+    ///
+    /// Deletes all rooms and events created by the user including their assets and anonymizes the user.
+    /// This cannot be undone.
+    Erase {
+        /// Id of the user to erase
+        id: Uuid,
+    },
+    /// Allow a user to impersonate the other users of their tenant, read-only
+    GrantImpersonation {
+        /// Id of the user
+        id: Uuid,
+    },
+    /// Revoke the permission to impersonate other users
+    RevokeImpersonation {
+        /// Id of the user
+        id: Uuid,
+    },
+    /// List the latest requests made while impersonating users, newest first
+    ImpersonationLog {
+        /// Only list requests impersonating this user
+        #[clap(long)]
+        user: Option<Uuid>,
+        /// Maximum number of requests to list
+        #[clap(long, default_value = "50")]
+        limit: i64,
+    },
+}
+
+pub async fn handle_command(settings: Settings, command: Command) -> Result<()> {
+    match command {
+        Command::Erase { id } => erase_user(settings, UserId::from(id)).await,
+        Command::GrantImpersonation { id } => {
+            set_impersonation(settings, UserId::from(id), true).await
+        }
+        Command::RevokeImpersonation { id } => {
+            set_impersonation(settings, UserId::from(id), false).await
+        }
+        Command::ImpersonationLog { user, limit } => {
+            impersonation_log(settings, user.map(UserId::from), limit)
+        }
+    }
+}
+
+/// Implementation of the `k3k-controller users erase <user-id>` command
+async fn erase_user(settings: Settings, user_id: UserId) -> Result<()> {
+    let db = Arc::new(Db::connect(&settings.database).context("Failed to connect to database")?);
+    let authz = kustos::Authz::new(db.clone()).await?;
+    let storage = ObjectStorage::new(&settings.minio, None).await?;
+    let calendar_sync = CalendarSync::new(Arc::new(ArcSwap::from_pointee(settings)), db.clone());
+
+    gdpr::erase_user(db, &storage, &authz, &calendar_sync, user_id).await?;
+
+    println!("Erased user {user_id}");
+
+    Ok(())
+}
+
+/// Implementation of the `k3k-controller users grant-impersonation|revoke-impersonation <user-id>` commands
+async fn set_impersonation(settings: Settings, user_id: UserId, allowed: bool) -> Result<()> {
+    let db = Arc::new(Db::connect(&settings.database).context("Failed to connect to database")?);
+    let authz = kustos::Authz::new(db.clone()).await?;
+
+    let mut conn = db.get_conn()?;
+    let user = User::get(&mut conn, user_id).context("Failed to get the user")?;
+
+    if allowed {
+        authz
+            .add_user_to_role(user.id.into_inner(), IMPERSONATION_ROLE)
+            .await?;
+
+        println!(
+            "User {user_id} can now impersonate the users of tenant {}",
+            user.tenant_id
+        );
+    } else {
+        authz
+            .remove_user_from_role(user.id.into_inner(), IMPERSONATION_ROLE)
+            .await?;
+
+        println!("User {user_id} can no longer impersonate other users");
+    }
+
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct ImpersonationLogTableRow {
+    created_at: String,
+    impersonator: UserId,
+    impersonated: UserId,
+    method: String,
+    path: String,
+    request_id: String,
+}
+
+impl ImpersonationLogTableRow {
+    fn from_entry(entry: ImpersonationLogEntry) -> Self {
+        Self {
+            created_at: entry.created_at.to_rfc3339(),
+            impersonator: entry.impersonator_id,
+            impersonated: entry.impersonated_id,
+            method: entry.method,
+            path: entry.path,
+            request_id: entry.request_id.unwrap_or_default(),
+        }
+    }
+}
+
+/// Implementation of the `k3k-controller users impersonation-log` command
+fn impersonation_log(settings: Settings, user_id: Option<UserId>, limit: i64) -> Result<()> {
+    let db = Db::connect(&settings.database).context("Failed to connect to database")?;
+    let mut conn = db.get_conn()?;
+
+    let entries = ImpersonationLogEntry::get_latest(&mut conn, user_id, limit)?;
+    let rows: Vec<ImpersonationLogTableRow> = entries
+        .into_iter()
+        .map(ImpersonationLogTableRow::from_entry)
+        .collect();
+
+    println!("{}", Table::new(rows).with(Style::psql()));
+
+    Ok(())
+}
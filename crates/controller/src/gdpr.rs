@@ -0,0 +1,411 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Export and erasure of personal data
+//!
+//! Users can download an export of all data stored about them. Administrators can erase a user, which deletes all
+//! rooms and events created by the user and anonymizes the user entry, so that remaining references (e.g. in legal
+//! vote protocols) no longer point to any personal data.
+//!
+//! Chat messages are only kept in redis for the lifetime of a meeting and are therefore not part of the export.
+//! User accounts managed by the OIDC provider must be removed there separately.
+use crate::calendar_sync::CalendarSync;
+use crate::storage::ObjectStorage;
+use crate::trash::{purge_event, purge_room};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use database::Db;
+use db_storage::assets::{Asset, AssetScanStatus};
+use db_storage::calendar_links::{CalendarLink, CalendarProvider};
+use db_storage::call_feedback::CallFeedback;
+use db_storage::contacts::ContactFavorite;
+use db_storage::events::email_invites::EventEmailInvite;
+use db_storage::events::{Event, EventFavorite, EventInvite, EventInviteStatus};
+use db_storage::groups::{remove_user_from_all_groups, Group};
+use db_storage::ldap_sessions::LdapSession;
+use db_storage::legal_votes::types::protocol::v1::{ProtocolEntry, VoteEvent};
+use db_storage::legal_votes::types::VoteOption;
+use db_storage::legal_votes::{LegalVote, LegalVoteId};
+use db_storage::poll_templates::PollTemplate;
+use db_storage::room_owners::RoomOwner;
+use db_storage::room_statistics::{self, RoomStatistics};
+use db_storage::rooms::Room;
+use db_storage::users::User;
+use diesel::Connection;
+use kustos::Authz;
+use serde::Serialize;
+use std::sync::Arc;
+use types::core::{AssetId, EventId, GroupName, RoomId, UserId};
+
+/// All data stored about a user
+#[derive(Debug, Serialize)]
+pub struct DataExport {
+    pub exported_at: DateTime<Utc>,
+    pub profile: ExportedProfile,
+    pub groups: Vec<GroupName>,
+    pub rooms: Vec<ExportedRoom>,
+    pub events: Vec<ExportedEvent>,
+    pub event_invites: Vec<ExportedEventInvite>,
+    pub legal_votes: Vec<ExportedLegalVote>,
+    pub assets: Vec<ExportedAsset>,
+    pub calendar_links: Vec<ExportedCalendarLink>,
+    pub favorite_contacts: Vec<UserId>,
+    pub room_sessions: Vec<ExportedRoomSession>,
+    pub call_feedback: Vec<ExportedCallFeedback>,
+    pub poll_library: Vec<ExportedPollTemplate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedProfile {
+    pub id: UserId,
+    pub email: String,
+    pub title: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub display_name: String,
+    pub phone: Option<String>,
+    pub language: String,
+    pub dashboard_theme: String,
+    pub conference_theme: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedRoom {
+    pub id: RoomId,
+    pub created_at: DateTime<Utc>,
+    pub waiting_room: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedEvent {
+    pub id: EventId,
+    pub room: RoomId,
+    pub title: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub recurrence_pattern: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedEventInvite {
+    pub event_id: EventId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub status: EventInviteStatus,
+    pub comment: Option<String>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+/// A legal vote the user initiated or participated in
+///
+/// Only contains the votes of the exported user, votes of other participants are omitted.
+#[derive(Debug, Serialize)]
+pub struct ExportedLegalVote {
+    pub id: LegalVoteId,
+    pub room: Option<RoomId>,
+    pub created_at: DateTime<Utc>,
+    pub initiated: bool,
+    pub name: Option<String>,
+    pub votes: Vec<VoteOption>,
+}
+
+/// Metadata of an asset in one of the rooms created by the user
+#[derive(Debug, Serialize)]
+pub struct ExportedAsset {
+    pub id: AssetId,
+    pub room: RoomId,
+    pub filename: String,
+    pub namespace: Option<String>,
+    pub kind: String,
+    pub created_at: DateTime<Utc>,
+    pub scan_status: AssetScanStatus,
+}
+
+/// A calendar the user linked, the OAuth tokens are not exported
+#[derive(Debug, Serialize)]
+pub struct ExportedCalendarLink {
+    pub provider: CalendarProvider,
+    pub created_at: DateTime<Utc>,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+/// A session of a room the user took part in
+#[derive(Debug, Serialize)]
+pub struct ExportedRoomSession {
+    pub room: RoomId,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+}
+
+/// Feedback the user submitted when leaving a room
+#[derive(Debug, Serialize)]
+pub struct ExportedCallFeedback {
+    pub room: RoomId,
+    pub created_at: DateTime<Utc>,
+    pub rating: i16,
+    pub issues: Vec<String>,
+    pub comment: Option<String>,
+}
+
+/// A poll of the personal library of the user
+#[derive(Debug, Serialize)]
+pub struct ExportedPollTemplate {
+    pub created_at: DateTime<Utc>,
+    pub topic: String,
+    pub live: bool,
+    pub choices: Vec<String>,
+    pub duration_secs: i32,
+}
+
+/// Collect all data stored about the given user
+pub(crate) async fn export_user_data(db: Arc<Db>, user: User) -> Result<DataExport> {
+    crate::block(move || -> Result<DataExport> {
+        let mut conn = db.get_conn()?;
+
+        let groups = Group::get_all_for_user(&mut conn, user.id)?;
+        let rooms = Room::get_all_created_by(&mut conn, user.id)?;
+        let events = Event::get_all_created_by(&mut conn, user.id)?;
+        let event_invites = EventInvite::get_all_for_invitee(&mut conn, user.id)?;
+        let legal_votes = LegalVote::get_all_for_participant(&mut conn, user.id)?;
+        let calendar_links = CalendarLink::get_all_for_user(&mut conn, user.id)?;
+        let favorite_contacts = ContactFavorite::get_all_for_user(&mut conn, user.id)?;
+        let room_sessions = RoomStatistics::get_all_for_participant(&mut conn, user.id)?;
+        let call_feedback = CallFeedback::get_all_for_user(&mut conn, user.id)?;
+        let poll_library = PollTemplate::get_all_for_library(&mut conn, user.id)?;
+
+        let room_ids: Vec<RoomId> = rooms.iter().map(|room| room.id).collect();
+        let assets = Asset::get_all_for_rooms(&mut conn, &room_ids)?;
+
+        let legal_votes = legal_votes
+            .into_iter()
+            .map(|legal_vote| export_legal_vote(user.id, legal_vote))
+            .collect::<Result<_>>()?;
+
+        Ok(DataExport {
+            exported_at: Utc::now(),
+            profile: ExportedProfile {
+                id: user.id,
+                email: user.email,
+                title: user.title,
+                firstname: user.firstname,
+                lastname: user.lastname,
+                display_name: user.display_name,
+                phone: user.phone,
+                language: user.language,
+                dashboard_theme: user.dashboard_theme,
+                conference_theme: user.conference_theme,
+            },
+            groups: groups.into_iter().map(|group| group.name).collect(),
+            rooms: rooms
+                .into_iter()
+                .map(|room| ExportedRoom {
+                    id: room.id,
+                    created_at: room.created_at,
+                    waiting_room: room.waiting_room,
+                    deleted_at: room.deleted_at,
+                })
+                .collect(),
+            events: events
+                .into_iter()
+                .map(|event| ExportedEvent {
+                    id: event.id,
+                    room: event.room,
+                    title: event.title,
+                    description: event.description,
+                    created_at: event.created_at,
+                    starts_at: event.starts_at,
+                    ends_at: event.ends_at,
+                    recurrence_pattern: event.recurrence_pattern,
+                    deleted_at: event.deleted_at,
+                })
+                .collect(),
+            event_invites: event_invites
+                .into_iter()
+                .map(|invite| ExportedEventInvite {
+                    event_id: invite.event_id,
+                    created_by: invite.created_by,
+                    created_at: invite.created_at,
+                    status: invite.status,
+                    comment: invite.comment,
+                    responded_at: invite.responded_at,
+                })
+                .collect(),
+            legal_votes,
+            assets: assets
+                .into_iter()
+                .map(|(asset, room)| ExportedAsset {
+                    id: asset.id,
+                    room,
+                    filename: asset.filename,
+                    namespace: asset.namespace,
+                    kind: asset.kind,
+                    created_at: asset.created_at,
+                    scan_status: asset.scan_status,
+                })
+                .collect(),
+            calendar_links: calendar_links
+                .into_iter()
+                .map(|link| ExportedCalendarLink {
+                    provider: link.provider,
+                    created_at: link.created_at,
+                    synced_at: link.synced_at,
+                })
+                .collect(),
+            favorite_contacts: favorite_contacts
+                .into_iter()
+                .map(|contact| contact.id)
+                .collect(),
+            room_sessions: room_sessions
+                .into_iter()
+                .map(|session| ExportedRoomSession {
+                    room: session.room_id,
+                    started_at: session.started_at,
+                    ended_at: session.ended_at,
+                })
+                .collect(),
+            call_feedback: call_feedback
+                .into_iter()
+                .map(|feedback| ExportedCallFeedback {
+                    room: feedback.room_id,
+                    created_at: feedback.created_at,
+                    rating: feedback.rating,
+                    issues: feedback.issues,
+                    comment: feedback.comment,
+                })
+                .collect(),
+            poll_library: poll_library
+                .into_iter()
+                .map(|template| ExportedPollTemplate {
+                    created_at: template.created_at,
+                    topic: template.topic,
+                    live: template.live,
+                    choices: template.choices,
+                    duration_secs: template.duration_secs,
+                })
+                .collect(),
+        })
+    })
+    .await?
+}
+
+fn export_legal_vote(user_id: UserId, legal_vote: LegalVote) -> Result<ExportedLegalVote> {
+    let entries: Vec<ProtocolEntry> = if legal_vote.protocol.version == 1 {
+        serde_json::from_str(legal_vote.protocol.entries.get())
+            .with_context(|| format!("Failed to parse protocol of legal vote {}", legal_vote.id))?
+    } else {
+        log::warn!(
+            "Unsupported protocol version {} of legal vote {}",
+            legal_vote.protocol.version,
+            legal_vote.id
+        );
+        Vec::new()
+    };
+
+    let mut name = None;
+    let mut votes = Vec::new();
+
+    for entry in entries {
+        match entry.event {
+            VoteEvent::Start(start) => name = Some(start.parameters.inner.name),
+            VoteEvent::Vote(vote) => {
+                if matches!(&vote.user_info, Some(user_info) if user_info.issuer == user_id) {
+                    votes.push(vote.option);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ExportedLegalVote {
+        id: legal_vote.id,
+        room: legal_vote.room,
+        created_at: legal_vote.created_at,
+        initiated: legal_vote.created_by == user_id,
+        name,
+        votes,
+    })
+}
+
+/// Erase all personal data of the given user
+///
+/// Rooms and events created by the user are purged including their assets, prepared polls and permissions. Invites,
+/// favorites, favorite contacts, session participations, the poll library, calendar links and LDAP sessions of the
+/// user are deleted, the user entry and the call feedback of the user are anonymized. At last all permissions, groups
+/// and roles of the user are removed from kustos and the tokens of the calendar links are revoked at the providers.
+pub(crate) async fn erase_user(
+    db: Arc<Db>,
+    storage: &ObjectStorage,
+    authz: &Authz,
+    calendar_sync: &CalendarSync,
+    user_id: UserId,
+) -> Result<()> {
+    let db_clone = db.clone();
+    let (user, rooms, events, calendar_links) = crate::block(move || -> database::Result<_> {
+        let mut conn = db_clone.get_conn()?;
+
+        let user = User::get(&mut conn, user_id)?;
+        let rooms: Vec<RoomId> = Room::get_all_created_by(&mut conn, user_id)?
+            .into_iter()
+            .map(|room| room.id)
+            .collect();
+
+        // Events inside of the rooms are purged together with the room
+        let events: Vec<EventId> = Event::get_all_created_by(&mut conn, user_id)?
+            .into_iter()
+            .filter(|event| !rooms.contains(&event.room))
+            .map(|event| event.id)
+            .collect();
+
+        let calendar_links = CalendarLink::get_all_for_user(&mut conn, user_id)?;
+
+        Ok((user, rooms, events, calendar_links))
+    })
+    .await??;
+
+    for room_id in rooms {
+        purge_room(db.clone(), storage, authz, room_id)
+            .await
+            .with_context(|| format!("Failed to purge room {room_id}"))?;
+    }
+
+    for event_id in events {
+        purge_event(db.clone(), authz, event_id)
+            .await
+            .with_context(|| format!("Failed to purge event {event_id}"))?;
+    }
+
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        conn.transaction(|conn| -> database::Result<()> {
+            EventInvite::delete_all_for_invitee(conn, user_id)?;
+            EventFavorite::delete_all_for_user(conn, user_id)?;
+            RoomOwner::delete_all_for_user(conn, user_id)?;
+            LdapSession::delete_all_for_user(conn, user_id)?;
+            CalendarLink::delete_all_for_user(conn, user_id)?;
+            ContactFavorite::delete_all_for_user(conn, user_id)?;
+            room_statistics::delete_participant(conn, user_id)?;
+            CallFeedback::anonymize_all_for_user(conn, user_id)?;
+            PollTemplate::delete_library(conn, user_id)?;
+            EventEmailInvite::delete_all_for_email(conn, &user.email)?;
+            remove_user_from_all_groups(conn, user_id)?;
+            User::anonymize(conn, user_id)?;
+
+            Ok(())
+        })
+    })
+    .await??;
+
+    authz.remove_user(user_id).await?;
+
+    for link in &calendar_links {
+        calendar_sync.revoke(link).await;
+    }
+
+    Ok(())
+}
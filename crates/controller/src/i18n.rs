@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Message catalog for texts generated by the controller, e.g. notifications and the names of generated documents
+//!
+//! The locale of a room is taken from its `locale` setting, falling back to `defaults.user_language`.
+//! Every catalog must contain all messages, which is enforced by matching exhaustively over [`Message`].
+use std::str::FromStr;
+
+/// Locales with a message catalog
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+}
+
+/// Key of a message in the catalogs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Notification template sent when a meeting starts
+    MeetingStarted,
+    /// Notification template sent when a recording is available
+    RecordingAvailable,
+    /// Notification template sent when a vote ended
+    VoteResult,
+    /// Prefix of the file name of protocol PDFs
+    ProtocolFilename,
+    /// Heading of the decisions appended to the protocol
+    ProtocolDecisions,
+    /// Heading of the action items appended to the protocol
+    ProtocolActionItems,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unsupported locale")]
+pub struct UnsupportedLocale;
+
+impl FromStr for Locale {
+    type Err = UnsupportedLocale;
+
+    /// Parses the primary language subtag of a language tag, e.g. `de` of `de-AT`
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Ok(Self::En),
+            "de" => Ok(Self::De),
+            "fr" => Ok(Self::Fr),
+            _ => Err(UnsupportedLocale),
+        }
+    }
+}
+
+impl Locale {
+    /// Returns the locale of a room with the given `locale` setting
+    ///
+    /// Falls back to the `default` language of the deployment if the room has none, and to English if neither
+    /// is supported.
+    pub fn resolve(locale: Option<&str>, default: &str) -> Self {
+        locale
+            .and_then(|locale| locale.parse().ok())
+            .or_else(|| default.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+        }
+    }
+
+    /// Returns the message in this locale
+    pub fn message(&self, message: Message) -> &'static str {
+        match self {
+            Self::En => en(message),
+            Self::De => de(message),
+            Self::Fr => fr(message),
+        }
+    }
+}
+
+fn en(message: Message) -> &'static str {
+    match message {
+        Message::MeetingStarted => "The meeting {title} has started",
+        Message::RecordingAvailable => "A recording of the meeting {title} is available",
+        Message::VoteResult => "The vote \"{topic}\" in the meeting {title} has ended: {results}",
+        Message::ProtocolFilename => "protocol",
+        Message::ProtocolDecisions => "Decisions",
+        Message::ProtocolActionItems => "Action items",
+    }
+}
+
+fn de(message: Message) -> &'static str {
+    match message {
+        Message::MeetingStarted => "Das Meeting {title} hat begonnen",
+        Message::RecordingAvailable => "Eine Aufzeichnung des Meetings {title} ist verfügbar",
+        Message::VoteResult => {
+            "Die Abstimmung \"{topic}\" im Meeting {title} ist beendet: {results}"
+        }
+        Message::ProtocolFilename => "protokoll",
+        Message::ProtocolDecisions => "Beschlüsse",
+        Message::ProtocolActionItems => "Aufgaben",
+    }
+}
+
+fn fr(message: Message) -> &'static str {
+    match message {
+        Message::MeetingStarted => "La réunion {title} a commencé",
+        Message::RecordingAvailable => "Un enregistrement de la réunion {title} est disponible",
+        Message::VoteResult => "Le vote « {topic} » de la réunion {title} est terminé : {results}",
+        Message::ProtocolFilename => "proces-verbal",
+        Message::ProtocolDecisions => "Décisions",
+        Message::ProtocolActionItems => "Actions à mener",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_language_tags() {
+        assert_eq!("de-AT".parse::<Locale>().unwrap(), Locale::De);
+        assert_eq!("fr_CA".parse::<Locale>().unwrap(), Locale::Fr);
+        assert_eq!("EN".parse::<Locale>().unwrap(), Locale::En);
+        assert!("es".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn resolve_locale() {
+        assert_eq!(Locale::resolve(Some("fr"), "de-DE"), Locale::Fr);
+        assert_eq!(Locale::resolve(None, "de-DE"), Locale::De);
+        assert_eq!(Locale::resolve(Some("es"), "es-ES"), Locale::En);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(Locale::De.message(Message::ProtocolFilename), "protokoll");
+        assert_eq!(Locale::En.message(Message::ProtocolFilename), "protocol");
+    }
+}
@@ -0,0 +1,357 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Authentication against and search in an LDAP directory
+//!
+//! Used for deployments where Keycloak is not federated with the directory. Users log in by binding with the DN of
+//! their entry and their password, the attributes of the entry are mapped to the fields of the user as configured in
+//! the `ldap.attributes` settings.
+use crate::oidc::IdTokenInfo;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use controller_shared::settings::{Ldap, LdapAttributes};
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use ldap3::{SearchOptions, SearchResult};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Timeout for establishing the connection to the directory server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result code of a bind with invalid credentials
+const RC_INVALID_CREDENTIALS: u32 = 49;
+
+/// Result code of a search which returned more entries than the size limit
+const RC_SIZE_LIMIT_EXCEEDED: u32 = 4;
+
+/// Prefix of the session tokens, distinguishes them from the access tokens of the OIDC provider
+const SESSION_TOKEN_PREFIX: &str = "ldap_";
+
+/// Prefix of the subjects of the users, distinguishes them from the subjects of the OIDC provider
+const SUBJECT_PREFIX: &str = "ldap:";
+
+/// Entry of a person in the directory
+#[derive(Debug)]
+pub(crate) struct DirectoryEntry {
+    pub id: String,
+    pub email: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub display_name: Option<String>,
+    pub phone: Option<String>,
+    pub groups: Vec<String>,
+}
+
+impl DirectoryEntry {
+    /// Map the attributes of the search entry, returns None if the id or email attribute is missing
+    fn from_search_entry(attributes: &LdapAttributes, entry: SearchEntry) -> Option<Self> {
+        let mut attrs: HashMap<String, Vec<String>> = entry
+            .attrs
+            .into_iter()
+            .map(|(name, values)| (name.to_lowercase(), values))
+            .collect();
+
+        let mut take_first = |name: &str| {
+            attrs
+                .remove(&name.to_lowercase())
+                .and_then(|values| values.into_iter().next())
+                .filter(|value| !value.is_empty())
+        };
+
+        let id = take_first(&attributes.id)?;
+        let email = take_first(&attributes.email)?;
+        let firstname = take_first(&attributes.firstname).unwrap_or_default();
+        let lastname = take_first(&attributes.lastname).unwrap_or_default();
+        let display_name = take_first(&attributes.display_name);
+        let phone = take_first(&attributes.phone);
+
+        let groups = attrs
+            .remove(&attributes.groups.to_lowercase())
+            .unwrap_or_default()
+            .iter()
+            .map(|group| group_name(group).to_string())
+            .collect();
+
+        Some(Self {
+            id,
+            email,
+            firstname,
+            lastname,
+            display_name,
+            phone,
+            groups,
+        })
+    }
+
+    /// Returns the subject of the user, the id is prefixed so an entry can never match a user of the OIDC provider
+    pub fn subject(&self) -> String {
+        format!("{SUBJECT_PREFIX}{}", self.id)
+    }
+
+    /// Convert the entry into the info used to create or update the user on login
+    pub fn into_id_token_info(self, issuer: String, expiration: DateTime<Utc>) -> IdTokenInfo {
+        IdTokenInfo {
+            sub: self.subject(),
+            issuer,
+            expiration,
+            email: self.email,
+            firstname: self.firstname,
+            lastname: self.lastname,
+            x_grp: self.groups,
+            phone_number: self.phone,
+            display_name: self.display_name,
+            tenant_id: None,
+            tariff_id: None,
+        }
+    }
+}
+
+/// Returns the value of the first RDN if the group is a DN, otherwise the group itself
+fn group_name(group: &str) -> &str {
+    group
+        .split(',')
+        .next()
+        .and_then(|rdn| rdn.split_once('='))
+        .map(|(_, value)| value.trim())
+        .filter(|value| !value.is_empty())
+        .unwrap_or(group)
+}
+
+fn attribute_names(attributes: &LdapAttributes) -> Vec<&str> {
+    vec![
+        attributes.id.as_str(),
+        attributes.email.as_str(),
+        attributes.firstname.as_str(),
+        attributes.lastname.as_str(),
+        attributes.display_name.as_str(),
+        attributes.phone.as_str(),
+        attributes.groups.as_str(),
+    ]
+}
+
+/// Connect to the directory server and bind with the configured search account
+async fn connect(settings: &Ldap) -> Result<ldap3::Ldap> {
+    let conn_settings = LdapConnSettings::new()
+        .set_conn_timeout(CONNECT_TIMEOUT)
+        .set_starttls(settings.starttls);
+
+    let (conn, mut ldap) = LdapConnAsync::with_settings(conn_settings, &settings.url)
+        .await
+        .context("Failed to connect to the LDAP server")?;
+
+    ldap3::drive!(conn);
+
+    let bind_dn = settings.bind_dn.as_deref().unwrap_or_default();
+    let bind_password = settings.bind_password.as_deref().unwrap_or_default();
+
+    ldap.simple_bind(bind_dn, bind_password)
+        .await
+        .and_then(|result| result.success())
+        .context("Failed to bind to the LDAP server")?;
+
+    Ok(ldap)
+}
+
+/// Search the entries matching the filter below the base DN, returns at most `limit` entries
+async fn search_entries(
+    ldap: &mut ldap3::Ldap,
+    settings: &Ldap,
+    filter: &str,
+    limit: i32,
+) -> Result<Vec<DirectoryEntry>> {
+    let SearchResult(entries, result) = ldap
+        .with_search_options(SearchOptions::new().sizelimit(limit))
+        .search(
+            &settings.base_dn,
+            Scope::Subtree,
+            filter,
+            attribute_names(&settings.attributes),
+        )
+        .await
+        .context("Failed to search the LDAP directory")?;
+
+    if result.rc != 0 && result.rc != RC_SIZE_LIMIT_EXCEEDED {
+        return Err(LdapError::from(result)).context("Failed to search the LDAP directory");
+    }
+
+    let entries = entries
+        .into_iter()
+        .map(SearchEntry::construct)
+        .filter_map(|entry| DirectoryEntry::from_search_entry(&settings.attributes, entry))
+        .collect();
+
+    Ok(entries)
+}
+
+/// Authenticate the user with their directory credentials
+///
+/// Returns None if the user does not exist or the password is wrong.
+pub(crate) async fn authenticate(
+    settings: &Ldap,
+    username: &str,
+    password: &str,
+) -> Result<Option<DirectoryEntry>> {
+    // An empty password would result in an unauthenticated bind, which succeeds on most servers
+    if username.is_empty() || password.is_empty() {
+        return Ok(None);
+    }
+
+    let mut ldap = connect(settings).await?;
+
+    let filter = settings
+        .user_filter
+        .replace("{username}", &ldap_escape(username));
+
+    let SearchResult(mut entries, result) = ldap
+        .search(
+            &settings.base_dn,
+            Scope::Subtree,
+            &filter,
+            attribute_names(&settings.attributes),
+        )
+        .await
+        .context("Failed to search the LDAP directory")?;
+
+    result
+        .success()
+        .context("Failed to search the LDAP directory")?;
+
+    // The username must identify exactly one entry
+    if entries.len() != 1 {
+        let _ = ldap.unbind().await;
+        return Ok(None);
+    }
+
+    let entry = SearchEntry::construct(entries.remove(0));
+
+    let result = ldap
+        .simple_bind(&entry.dn, password)
+        .await
+        .context("Failed to bind to the LDAP server")?;
+
+    let _ = ldap.unbind().await;
+
+    if result.rc == RC_INVALID_CREDENTIALS {
+        return Ok(None);
+    }
+
+    result
+        .success()
+        .context("Failed to bind to the LDAP server")?;
+
+    Ok(DirectoryEntry::from_search_entry(
+        &settings.attributes,
+        entry,
+    ))
+}
+
+/// Search the directory for people matching the query
+pub(crate) async fn search(
+    settings: &Ldap,
+    query: &str,
+    limit: i32,
+) -> Result<Vec<DirectoryEntry>> {
+    let mut ldap = connect(settings).await?;
+
+    let filter = settings
+        .search_filter
+        .replace("{query}", &ldap_escape(query));
+
+    let entries = search_entries(&mut ldap, settings, &filter, limit).await;
+
+    let _ = ldap.unbind().await;
+
+    entries
+}
+
+/// Create a new random session token, returns the token and its hash
+pub(crate) fn new_session_token() -> Result<(String, Vec<u8>)> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Failed to generate session token"))?;
+
+    let token = format!(
+        "{}{}",
+        SESSION_TOKEN_PREFIX,
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    );
+    let hash = digest(&SHA256, token.as_bytes()).as_ref().to_vec();
+
+    Ok((token, hash))
+}
+
+/// Returns the hash of the token if it is a session token
+pub(crate) fn session_token_hash(token: &str) -> Option<Vec<u8>> {
+    if !token.starts_with(SESSION_TOKEN_PREFIX) {
+        return None;
+    }
+
+    Some(digest(&SHA256, token.as_bytes()).as_ref().to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_tokens() {
+        let (token, hash) = new_session_token().unwrap();
+
+        assert!(token.starts_with(SESSION_TOKEN_PREFIX));
+        assert_eq!(session_token_hash(&token), Some(hash));
+        assert_eq!(session_token_hash("eyJhbGciOiJSUzI1NiJ9.e30.c2ln"), None);
+    }
+
+    #[test]
+    fn group_names() {
+        assert_eq!(
+            group_name("cn=developers,ou=groups,dc=example,dc=org"),
+            "developers"
+        );
+        assert_eq!(group_name("developers"), "developers");
+        assert_eq!(group_name("cn=,ou=groups"), "cn=,ou=groups");
+    }
+
+    #[test]
+    fn map_search_entry() {
+        let entry = SearchEntry {
+            dn: "uid=jdoe,ou=people,dc=example,dc=org".into(),
+            attrs: HashMap::from([
+                ("uid".to_string(), vec!["jdoe".to_string()]),
+                ("mail".to_string(), vec!["jdoe@example.org".to_string()]),
+                ("givenname".to_string(), vec!["John".to_string()]),
+                ("sn".to_string(), vec!["Doe".to_string()]),
+                (
+                    "memberOf".to_string(),
+                    vec!["cn=developers,ou=groups,dc=example,dc=org".to_string()],
+                ),
+            ]),
+            bin_attrs: HashMap::new(),
+        };
+
+        let entry = DirectoryEntry::from_search_entry(&LdapAttributes::default(), entry).unwrap();
+
+        assert_eq!(entry.id, "jdoe");
+        assert_eq!(entry.subject(), "ldap:jdoe");
+        assert_eq!(entry.email, "jdoe@example.org");
+        assert_eq!(entry.firstname, "John");
+        assert_eq!(entry.lastname, "Doe");
+        assert_eq!(entry.display_name, None);
+        assert_eq!(entry.groups, vec!["developers".to_string()]);
+    }
+
+    #[test]
+    fn entry_without_email_is_skipped() {
+        let entry = SearchEntry {
+            dn: "uid=jdoe,ou=people,dc=example,dc=org".into(),
+            attrs: HashMap::from([("uid".to_string(), vec!["jdoe".to_string()])]),
+            bin_attrs: HashMap::new(),
+        };
+
+        assert!(DirectoryEntry::from_search_entry(&LdapAttributes::default(), entry).is_none());
+    }
+}
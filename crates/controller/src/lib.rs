@@ -16,7 +16,7 @@
 //! }
 //!
 //! async fn run() -> Result<()> {
-//!    if let Some(controller) = Controller::create("K3K Controller Community Edition").await? {
+//!    if let Some(controller) = Controller::create("K3K Controller Community Edition", |_| {}).await? {
 //!         controller.run().await?;
 //!     }
 //!
@@ -26,14 +26,15 @@
 
 use crate::acl::check_or_create_kustos_default_permissions;
 use crate::api::v1::middleware::metrics::RequestMetrics;
+use crate::api::v1::middleware::request_filter::RequestFilter;
 use crate::api::v1::response::error::json_error_handler;
-use crate::services::MailService;
-use crate::settings::{Settings, SharedSettings};
+use crate::services::{ErrorReportingService, MailService, NotificationService};
+use crate::settings::{HttpListener, Settings, SharedSettings};
 use crate::trace::ReducedSpanBuilder;
 use actix_cors::Cors;
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer, Scope};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{bail, Context, Result};
 use arc_swap::ArcSwap;
 use breakout::BreakoutRooms;
 use database::Db;
@@ -42,8 +43,6 @@ use lapin_pool::RabbitMqPool;
 use moderation::ModerationModule;
 use oidc::OidcContext;
 use prelude::*;
-use std::fs::File;
-use std::io::BufReader;
 use std::net::Ipv6Addr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -60,20 +59,33 @@ mod api;
 pub mod api;
 
 mod acl;
+mod calendar_sync;
 mod cli;
+mod gdpr;
+pub mod i18n;
+mod ldap;
 mod metrics;
 mod oidc;
+mod redis_encryption;
 mod redis_wrapper;
 pub mod storage;
+mod systemd;
+mod telemetry;
+mod tls;
 mod trace;
 
 mod services;
 pub mod settings;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod trash;
 
 pub mod prelude {
     pub use crate::api::signaling::prelude::*;
     pub use crate::api::Participant;
+    pub use crate::redis_encryption::Encrypted;
     pub use crate::redis_wrapper::RedisConnection;
+    pub use crate::services::NotificationService;
 
     // re-export commonly used crates to reduce dependency management in module-crates
     pub use actix_web;
@@ -184,6 +196,12 @@ pub struct Controller {
 
     /// All metrics of the Application
     pub metrics: metrics::CombinedMetrics,
+
+    /// Notifies the configured chat services and webhooks about events of meetings
+    pub notifications: NotificationService,
+
+    /// Ships module errors and panics to the configured error reporting service
+    pub error_reporting: ErrorReportingService,
 }
 
 impl Controller {
@@ -193,8 +211,14 @@ impl Controller {
     /// subprogram (e.g. `--reload`) and must now exit.
     ///
     /// Otherwise it will return itself which can be modified and then run using [`Controller::run`]
-    pub async fn create(program_name: &str) -> Result<Option<Self>> {
-        let args = cli::parse_args().await?;
+    ///
+    /// `register_schemas` must add the schemas of all signaling modules which will be registered to the controller,
+    /// it is used by the `export-schema` subcommand.
+    pub async fn create(
+        program_name: &str,
+        register_schemas: impl FnOnce(&mut SignalingSchemas),
+    ) -> Result<Option<Self>> {
+        let args = cli::parse_args(register_schemas).await?;
 
         // Some args run commands by them self and thus should exit here
         if !args.controller_should_start() {
@@ -235,7 +259,8 @@ impl Controller {
         let db = Arc::new(db);
 
         // Connect to MinIO
-        let storage = Arc::new(ObjectStorage::new(&settings.minio).await?);
+        let storage =
+            Arc::new(ObjectStorage::new(&settings.minio, settings.virus_scan.as_ref()).await?);
 
         // Discover OIDC Provider
         let oidc = Arc::new(
@@ -251,16 +276,27 @@ impl Controller {
             settings.keycloak.client_secret.secret().clone(),
         )?);
 
+        if let Some(encryption_key) = &settings.redis.encryption_key {
+            redis_encryption::init(encryption_key).context("Invalid redis encryption key")?;
+        }
+
         // Build redis client. Does not check if redis is reachable.
         let redis = redis::Client::open(settings.redis.url.clone()).context("Invalid redis url")?;
         let redis_conn = redis::aio::ConnectionManager::new(redis)
             .await
             .context("Failed to create redis connection manager")?;
-        let redis_conn = RedisConnection::new(redis_conn).with_metrics(metrics.redis.clone());
+        let redis_conn = RedisConnection::new(redis_conn)
+            .with_metrics(metrics.redis.clone())
+            .with_resilience(&settings.redis);
 
         let (shutdown, _) = broadcast::channel::<()>(1);
         let (reload, _) = broadcast::channel::<()>(4);
 
+        let notifications = NotificationService::new(shared_settings.clone(), db.clone());
+
+        let error_reporting = ErrorReportingService::new(shared_settings.clone());
+        error_reporting.install_panic_hook();
+
         let mut signaling = SignalingModules::default();
 
         // Add default modules
@@ -269,6 +305,11 @@ impl Controller {
         if let Some(queue) = settings.rabbit_mq.recording_task_queue.clone() {
             signaling.add_module::<recording::Recording>(recording::RecordingParams { queue });
         }
+        if !settings.plugins.is_empty() {
+            signaling.add_module::<plugins::Plugins>(plugins::PluginsParams::new(
+                settings.plugins.clone(),
+            ));
+        }
 
         Ok(Self {
             startup_settings: settings,
@@ -284,11 +325,19 @@ impl Controller {
             reload,
             signaling,
             metrics,
+            notifications,
+            error_reporting,
         })
     }
 
     /// Runs the controller until a fatal error occurred or a shutdown is requested (e.g. SIGTERM).
     pub async fn run(self) -> Result<()> {
+        // Migrate the redis keys of the modules before accepting signaling connections
+        self.signaling
+            .migrate_keys(&mut self.redis.clone())
+            .await
+            .context("Failed to migrate redis keys")?;
+
         let signaling_modules = Arc::new(self.signaling);
 
         // Start HTTP Server
@@ -304,15 +353,18 @@ impl Controller {
             let shutdown = self.shutdown.clone();
             let shared_settings = self.shared_settings.clone();
             let redis = self.redis;
+            let notifications = Data::new(self.notifications);
+            let error_reporting = Data::new(self.error_reporting);
 
             let kc_admin_client = Data::from(self.kc_admin_client);
 
             let mail_service = Data::new(MailService::new(
                 self.shared_settings.clone(),
+                self.db.clone(),
                 self.metrics.endpoint.clone(),
                 self.rabbitmq_pool.clone(),
                 self.rabbitmq_pool.create_channel().await?,
-            ));
+            )?);
 
             // TODO(r.floren) what to do with the handle
             let (authz, _) = kustos::Authz::new_with_autoload_and_metrics(
@@ -326,6 +378,67 @@ impl Controller {
             log::info!("Making sure the default permissions are set");
             check_or_create_kustos_default_permissions(&authz).await?;
 
+            actix_rt::spawn(trash::purge_task(
+                self.shared_settings.clone(),
+                self.db.clone(),
+                self.storage.clone(),
+                authz.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            actix_rt::spawn(storage::uploads::abort_expired_uploads_task(
+                self.storage.clone(),
+                redis.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            actix_rt::spawn(telemetry::purge_task(
+                self.shared_settings.clone(),
+                self.db.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            let calendar_sync =
+                calendar_sync::CalendarSync::new(self.shared_settings.clone(), self.db.clone());
+
+            if let Some(settings) = &self.startup_settings.calendar_sync {
+                actix_rt::spawn(calendar_sync.clone().sync_task(
+                    redis.clone(),
+                    settings.interval,
+                    self.shutdown.subscribe(),
+                ));
+            }
+
+            let calendar_sync = Data::new(calendar_sync);
+
+            actix_rt::spawn(api::signaling::empty_rooms::sweeper_task(
+                redis.clone(),
+                self.db.clone(),
+                signaling_modules.clone(),
+                self.metrics.signaling.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            actix_rt::spawn(api::signaling::prewarm::prewarm_task(
+                self.shared_settings.clone(),
+                redis.clone(),
+                self.db.clone(),
+                signaling_modules.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            let room_affinity = Arc::new(api::signaling::sharding::RoomAffinity::new(
+                self.startup_settings.sharding.as_ref(),
+            ));
+
+            actix_rt::spawn(api::signaling::sharding::announce_task(
+                room_affinity.clone(),
+                redis.clone(),
+                self.shutdown.subscribe(),
+            ));
+
+            let room_affinity = Data::from(room_affinity);
+
             let authz_middleware = authz.actix_web_middleware(true).await?;
 
             let metrics = Data::new(self.metrics);
@@ -348,6 +461,10 @@ impl Controller {
                 let signaling_modules = Data::from(signaling_modules.upgrade().unwrap());
 
                 App::new()
+                    .wrap(RequestFilter::new(
+                        shared_settings.clone(),
+                        metrics.endpoint.clone(),
+                    ))
                     .wrap(api::v1::middleware::headers::Headers {})
                     .wrap(TracingLogger::<ReducedSpanBuilder>::new())
                     .wrap(cors)
@@ -358,7 +475,7 @@ impl Controller {
                     .app_data(storage)
                     .app_data(oidc_ctx.clone())
                     .app_data(kc_admin_client.clone())
-                    .app_data(authz)
+                    .app_data(authz.clone())
                     .app_data(redis)
                     .app_data(Data::new(shutdown.clone()))
                     .app_data(rabbitmq_pool.clone())
@@ -367,31 +484,94 @@ impl Controller {
                     .app_data(signaling_metrics.clone())
                     .app_data(metrics.clone())
                     .app_data(mail_service)
+                    .app_data(notifications.clone())
+                    .app_data(error_reporting.clone())
+                    .app_data(calendar_sync.clone())
+                    .app_data(room_affinity.clone())
                     .service(api::signaling::ws_service)
                     .service(metrics::metrics)
                     .service(v1_scope(
                         settings.clone(),
                         db.clone(),
                         oidc_ctx.clone(),
+                        authz,
                         acl,
                     ))
                     .service(internal_scope(settings.clone(), db, oidc_ctx))
             })
         };
 
-        let address = (Ipv6Addr::UNSPECIFIED, self.startup_settings.http.port);
+        let http = &self.startup_settings.http;
 
-        let http_server = if let Some(tls) = &self.startup_settings.http.tls {
-            let config = setup_rustls(tls).context("Failed to setup TLS context")?;
+        let tls_config = if let Some(tls) = &http.tls {
+            let resolver = Arc::new(
+                tls::CertificateResolver::new(tls).context("Failed to setup TLS context")?,
+            );
 
-            http_server.bind_rustls(address, config)
+            actix_rt::spawn(tls::reload_task(
+                resolver.clone(),
+                tls.clone(),
+                self.reload.subscribe(),
+                self.shutdown.subscribe(),
+            ));
+
+            Some(tls::server_config(resolver))
         } else {
-            http_server.bind(address)
+            None
         };
 
-        let http_server = http_server.with_context(|| {
-            format!("Failed to bind http server to {}:{}", address.0, address.1)
-        })?;
+        let http_server = match &http.listener {
+            HttpListener::Tcp => {
+                let address = (Ipv6Addr::UNSPECIFIED, http.port);
+
+                let http_server = if let Some(config) = tls_config {
+                    http_server.bind_rustls(address, config)
+                } else {
+                    http_server.bind(address)
+                };
+
+                http_server.with_context(|| {
+                    format!("Failed to bind http server to {}:{}", address.0, address.1)
+                })?
+            }
+            HttpListener::Unix { path } => {
+                if tls_config.is_some() {
+                    bail!("TLS is not supported when binding the http server to a unix socket");
+                }
+
+                http_server
+                    .bind_uds(path)
+                    .with_context(|| format!("Failed to bind http server to {path:?}"))?
+            }
+            HttpListener::Systemd => {
+                let listeners =
+                    systemd::listen_fds().context("Failed to take sockets passed by systemd")?;
+
+                if listeners.is_empty() {
+                    bail!("No sockets were passed by systemd");
+                }
+
+                let mut http_server = http_server;
+
+                for listener in listeners {
+                    http_server = match (listener, &tls_config) {
+                        (systemd::Listener::Tcp(listener), Some(config)) => {
+                            http_server.listen_rustls(listener, config.clone())
+                        }
+                        (systemd::Listener::Tcp(listener), None) => http_server.listen(listener),
+                        (systemd::Listener::Unix(_), Some(_)) => {
+                            bail!("TLS is not supported on unix sockets passed by systemd")
+                        }
+                        (systemd::Listener::Unix(listener), None) => {
+                            http_server.listen_uds(listener)
+                        }
+                    }
+                    .context("Failed to listen on socket passed by systemd")?;
+                }
+
+                http_server
+            }
+        };
 
         log::info!("Startup finished");
 
@@ -466,27 +646,63 @@ fn v1_scope(
     settings: SharedSettings,
     db: Data<Db>,
     oidc_ctx: Data<OidcContext>,
+    authz: Data<kustos::Authz>,
     acl: kustos::actix_web::KustosService,
 ) -> Scope {
     // the latest version contains the root services
     web::scope("/v1")
         .service(api::v1::auth::login)
+        .service(api::v1::auth::ldap_login)
         .service(api::v1::auth::oidc_provider)
         .service(api::v1::rooms::start_invited)
         .service(api::v1::invites::verify_invite_code)
+        .service(api::v1::guest_challenge::new_challenge)
+        .service(api::v1::room_directory::search)
+        .service(api::v1::room_branding::get)
+        .service(api::v1::events::published_results::get_public_event_results)
         .service(api::v1::turn::get)
+        .service(api::v1::turn::get_check)
+        .service(api::v1::turn::post_check)
+        .service(api::v1::telemetry::post_webrtc)
         .service(
             web::scope("/services")
                 .wrap(api::v1::middleware::service_auth::ServiceAuth::new(
                     oidc_ctx.clone(),
                 ))
+                .service(api::v1::services::bot::services())
                 .service(api::v1::services::call_in::services())
+                .service(api::v1::services::mail::services())
                 .service(api::v1::services::recording::services()),
         )
+        .service(
+            web::scope("/statistics")
+                .wrap(api::v1::middleware::service_auth::ServiceAuth::new(
+                    oidc_ctx.clone(),
+                ))
+                .service(api::v1::statistics::services()),
+        )
+        .service(
+            web::scope("/room_snapshots")
+                .wrap(api::v1::middleware::service_auth::ServiceAuth::new(
+                    oidc_ctx.clone(),
+                ))
+                .service(api::v1::room_snapshots::services()),
+        )
+        .service(
+            web::scope("/mail_templates")
+                .wrap(api::v1::middleware::service_auth::ServiceAuth::new(
+                    oidc_ctx.clone(),
+                ))
+                .service(api::v1::mail_templates::services()),
+        )
         .service(
             // empty scope to differentiate between auth endpoints
             web::scope("")
                 .wrap(acl)
+                .wrap(api::v1::middleware::impersonation::Impersonate {
+                    db: db.clone(),
+                    authz,
+                })
                 .wrap(api::v1::middleware::user_auth::OidcAuth {
                     settings,
                     db,
@@ -496,22 +712,49 @@ fn v1_scope(
                 .service(api::v1::users::patch_me)
                 .service(api::v1::users::get_me)
                 .service(api::v1::users::get_me_tariff)
+                .service(api::v1::users::data_export)
+                .service(api::v1::contacts::get_me_contacts)
+                .service(api::v1::contacts::put_me_contacts)
+                .service(api::v1::calendar_links::get_calendar_links)
+                .service(api::v1::calendar_links::authorize)
+                .service(api::v1::calendar_links::put_calendar_link)
+                .service(api::v1::calendar_links::delete_calendar_link)
                 .service(api::v1::users::get_user)
                 .service(api::v1::rooms::accessible)
                 .service(api::v1::rooms::new)
                 .service(api::v1::rooms::patch)
                 .service(api::v1::rooms::get)
                 .service(api::v1::rooms::get_room_tariff)
+                .service(api::v1::rooms::get_media_stats)
+                .service(api::v1::rooms::get_summary)
                 .service(api::v1::rooms::start)
                 .service(api::v1::rooms::delete)
+                .service(api::v1::room_owners::get_owners)
+                .service(api::v1::room_owners::add_owner)
+                .service(api::v1::room_owners::remove_owner)
+                .service(api::v1::room_owners::transfer_ownership)
+                .service(api::v1::room_media_settings::get)
+                .service(api::v1::room_media_settings::put)
+                .service(api::v1::room_media_settings::delete)
+                .service(api::v1::room_branding::put)
+                .service(api::v1::room_branding::delete)
+                .service(api::v1::room_directory::get)
+                .service(api::v1::room_directory::put)
+                .service(api::v1::room_directory::delete)
                 .service(api::v1::legal_vote::get_all)
                 .service(api::v1::legal_vote::get_all_for_room)
+                .service(api::v1::legal_vote::get_scheduled_for_room)
+                .service(api::v1::legal_vote::new_scheduled)
+                .service(api::v1::legal_vote::delete_scheduled)
                 .service(api::v1::legal_vote::get_specific)
+                .service(api::v1::legal_vote::get_protocol)
+                .service(api::v1::legal_vote::get_protocol_pdf)
                 .service(api::v1::events::new_event)
                 .service(api::v1::events::get_events)
                 .service(api::v1::events::get_event)
                 .service(api::v1::events::patch_event)
                 .service(api::v1::events::delete_event)
+                .service(api::v1::events::conflicts::check_conflicts)
                 .service(api::v1::events::favorites::add_event_to_favorites)
                 .service(api::v1::events::favorites::remove_event_from_favorites)
                 .service(api::v1::events::instances::get_event_instance)
@@ -522,6 +765,21 @@ fn v1_scope(
                 .service(api::v1::events::invites::delete_invite_to_event)
                 .service(api::v1::events::invites::accept_event_invite)
                 .service(api::v1::events::invites::decline_event_invite)
+                .service(api::v1::events::invites::get_event_attendance)
+                .service(api::v1::events::invites::remind_event_invitees)
+                .service(api::v1::events::matrix_bridge::get_matrix_bridge)
+                .service(api::v1::events::matrix_bridge::put_matrix_bridge)
+                .service(api::v1::events::matrix_bridge::delete_matrix_bridge)
+                .service(api::v1::events::published_results::get_event_results)
+                .service(api::v1::events::published_results::publish_event_result)
+                .service(api::v1::events::published_results::revoke_public_token)
+                .service(api::v1::events::published_results::delete_event_result)
+                .service(api::v1::poll_templates::get_event_polls)
+                .service(api::v1::poll_templates::new_event_poll)
+                .service(api::v1::poll_templates::delete_event_poll)
+                .service(api::v1::poll_templates::get_library_polls)
+                .service(api::v1::poll_templates::new_library_poll)
+                .service(api::v1::poll_templates::delete_library_poll)
                 .service(api::v1::sip_configs::get)
                 .service(api::v1::sip_configs::put)
                 .service(api::v1::sip_configs::delete)
@@ -532,7 +790,17 @@ fn v1_scope(
                 .service(api::v1::invites::delete_invite)
                 .service(api::v1::assets::room_assets)
                 .service(api::v1::assets::room_asset)
-                .service(api::v1::assets::delete),
+                .service(api::v1::assets::room_asset_url)
+                .service(api::v1::assets::room_asset_chapters)
+                .service(api::v1::assets::delete)
+                .service(api::v1::assets::start_upload)
+                .service(api::v1::assets::get_upload)
+                .service(api::v1::assets::put_upload_part)
+                .service(api::v1::assets::complete_upload)
+                .service(api::v1::assets::abort_upload)
+                .service(api::v1::trash::get_trash)
+                .service(api::v1::trash::restore_room)
+                .service(api::v1::trash::restore_event),
         )
 }
 
@@ -560,6 +828,8 @@ fn setup_cors() -> Cors {
         .send_wildcard()
         .allowed_header(CONTENT_TYPE)
         .allowed_header(AUTHORIZATION)
+        .allowed_header(api::v1::middleware::impersonation::IMPERSONATE_USER_HEADER)
+        .expose_headers([api::v1::middleware::impersonation::IMPERSONATED_BY_HEADER])
         .allowed_methods([
             Method::GET,
             Method::POST,
@@ -568,27 +838,3 @@ fn setup_cors() -> Cors {
             Method::DELETE,
         ])
 }
-
-fn setup_rustls(tls: &settings::HttpTls) -> Result<rustls::ServerConfig> {
-    let cert_file = File::open(&tls.certificate)
-        .with_context(|| format!("Failed to open certificate file {:?}", &tls.certificate))?;
-    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
-        .map_err(|_| anyhow!("Invalid certificate"))?;
-    let certs = certs.into_iter().map(rustls::Certificate).collect();
-
-    let private_key_file = File::open(&tls.private_key).with_context(|| {
-        format!(
-            "Failed to open pkcs8 private key file {:?}",
-            &tls.private_key
-        )
-    })?;
-    let mut key = rustls_pemfile::rsa_private_keys(&mut BufReader::new(private_key_file))
-        .map_err(|_| anyhow!("Invalid pkcs8 private key"))?;
-
-    let config = rustls::ServerConfig::builder()
-        .with_safe_defaults()
-        .with_no_client_auth()
-        .with_single_cert(certs, rustls::PrivateKey(key.remove(0)))?;
-
-    Ok(config)
-}
@@ -26,11 +26,13 @@ use prometheus::{Encoder, TextEncoder};
 use std::sync::Arc;
 
 const MAIL_TASK_KIND: Key = Key::from_static_str("mail_task_kind");
+const REASON: Key = Key::from_static_str("reason");
 
 pub struct EndpointMetrics {
     pub(crate) request_durations: Histogram<f64>,
     pub(crate) response_sizes: Histogram<u64>,
     pub(crate) issued_email_tasks_count: Counter<u64>,
+    pub(crate) rejected_requests_count: Counter<u64>,
 }
 
 impl EndpointMetrics {
@@ -41,6 +43,11 @@ impl EndpointMetrics {
             &[MAIL_TASK_KIND.string(mail_task.as_kind_str())],
         );
     }
+
+    pub fn increment_rejected_requests_count(&self, reason: &'static str) {
+        self.rejected_requests_count
+            .add(&Context::current(), 1, &[REASON.string(reason)]);
+    }
 }
 
 pub struct CombinedMetrics {
@@ -85,6 +92,9 @@ impl AggregatorSelector for OverrideAggregatorSelector {
                     0.01, 0.25, 0.5, 1.0, 2.0, 5.0,
                 ])))
             }
+            "signaling.module_event_duration_seconds" => Some(Arc::new(aggregators::histogram(&[
+                0.001, 0.005, 0.01, 0.05, 0.1, 0.5,
+            ]))),
             "sql.execution_time_seconds" => Some(Arc::new(aggregators::histogram(&[
                 0.01, 0.05, 0.1, 0.25, 0.5,
             ]))),
@@ -138,6 +148,10 @@ impl CombinedMetrics {
                 .u64_counter("web.issued_email_tasks_count")
                 .with_description("Number of issued email tasks")
                 .init(),
+            rejected_requests_count: meter
+                .u64_counter("web.rejected_requests_count")
+                .with_description("Number of requests rejected by the request filter")
+                .init(),
         });
 
         let signaling = Arc::new(SignalingMetrics {
@@ -171,6 +185,33 @@ impl CombinedMetrics {
                 .i64_up_down_counter("signaling.participants_with_video_count")
                 .with_description("Number of participants with video unmuted")
                 .init(),
+            inactivity_disconnects_count: meter
+                .u64_counter("signaling.inactivity_disconnects_count")
+                .with_description("Number of participants disconnected due to inactivity")
+                .init(),
+            connectivity_checks_count: meter
+                .u64_counter("signaling.connectivity_checks_count")
+                .with_description("Number of connectivity pre-checks reported by clients")
+                .init(),
+            module_event_duration: meter
+                .f64_histogram("signaling.module_event_duration_seconds")
+                .with_description("Time a signaling module takes to handle an event")
+                .with_unit(Unit::new("seconds"))
+                .init(),
+            module_failures_count: meter
+                .u64_counter("signaling.module_failures_count")
+                .with_description("Number of events signaling modules failed to handle")
+                .init(),
+            ws_queue_depth: meter
+                .i64_up_down_counter("signaling.ws_queue_depth")
+                .with_description(
+                    "Number of received websocket messages which are not yet handled by the runners",
+                )
+                .init(),
+            ticket_rejections_count: meter
+                .u64_counter("signaling.ticket_rejections_count")
+                .with_description("Number of rejected signaling tickets")
+                .init(),
         });
 
         let database = Arc::new(DatabaseMetrics {
@@ -17,6 +17,9 @@ pub struct ServiceClaims {
     pub iat: DateTime<Utc>,
     /// Issuer (URL to the OIDC Provider)
     pub iss: String,
+    /// Authorized party (Client ID of the service)
+    #[serde(default)]
+    pub azp: String,
     /// Keycloak realm management
     pub realm_access: RealmAccess,
 }
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Encryption of sensitive values stored in redis
+//!
+//! Values wrapped in [`Encrypted`] are encrypted with AES-256-GCM using the deployment wide `redis.encryption_key`,
+//! so a dump of the redis database does not leak the content of meetings. The random nonce and a marker are stored in
+//! front of the ciphertext.
+//!
+//! Without a configured key the values are stored in plain text. Plain text values can still be read after a key has
+//! been configured, which allows enabling the encryption while meetings are running.
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::OnceCell;
+use redis::{ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs, Value};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Marks encrypted values, the number is the version of the format
+const MARKER: &[u8] = b"k3k-enc:1:";
+
+static CIPHER: OnceCell<ValueCipher> = OnceCell::new();
+
+/// Set the key used to encrypt all [`Encrypted`] values of this process
+///
+/// Must be called once on startup before any value is written to redis.
+pub(crate) fn init(encoded_key: &str) -> Result<()> {
+    let cipher = ValueCipher::new(encoded_key)?;
+
+    if CIPHER.set(cipher).is_err() {
+        bail!("redis encryption key has already been set");
+    }
+
+    Ok(())
+}
+
+/// Wrapper which encrypts the redis args of the inner value and decrypts it when read from redis
+///
+/// # Example
+///
+/// ```ignore
+/// redis_conn.lpush(key, Encrypted(&message)).await?;
+///
+/// let messages: Vec<Encrypted<StoredMessage>> = redis_conn.lrange(key, 0, -1).await?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Encrypted<T>(pub T);
+
+impl<T> Encrypted<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: ToRedisArgs> ToRedisArgs for Encrypted<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match CIPHER.get() {
+            Some(cipher) => {
+                for arg in self.0.to_redis_args() {
+                    out.write_arg(&cipher.seal(&arg).expect("Failed to encrypt redis value"));
+                }
+            }
+            None => self.0.write_redis_args(out),
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        self.0.is_single_arg()
+    }
+}
+
+impl<T: FromRedisValue> FromRedisValue for Encrypted<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        match v {
+            Value::Data(bytes) if bytes.starts_with(MARKER) => {
+                let cipher = CIPHER.get().ok_or_else(|| {
+                    RedisError::from((
+                        ErrorKind::TypeError,
+                        "got encrypted value, but no redis encryption key is configured",
+                    ))
+                })?;
+
+                let plain = cipher.open(&bytes[MARKER.len()..]).map_err(|_| {
+                    RedisError::from((ErrorKind::TypeError, "failed to decrypt redis value"))
+                })?;
+
+                T::from_redis_value(&Value::Data(plain)).map(Self)
+            }
+            _ => T::from_redis_value(v).map(Self),
+        }
+    }
+}
+
+struct ValueCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl ValueCipher {
+    /// Creates the cipher from the base64 encoded `redis.encryption_key`
+    fn new(encoded_key: &str) -> Result<Self> {
+        let key = base64::decode(encoded_key).context("encryption_key is not valid base64")?;
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .map_err(|_| anyhow!("encryption_key must be 32 bytes long"))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Encrypt the value, returns the marker, nonce and ciphertext
+    fn seal(&self, value: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate nonce"))?;
+
+        let mut ciphertext = value.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt value"))?;
+
+        let mut sealed = MARKER.to_vec();
+        sealed.extend(nonce);
+        sealed.extend(ciphertext);
+
+        Ok(sealed)
+    }
+
+    /// Decrypt the nonce and ciphertext following the marker
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("Encrypted value is too short");
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+
+        let mut ciphertext = ciphertext.to_vec();
+        let value = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt value"))?;
+
+        Ok(value.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    #[test]
+    fn seal_and_open() {
+        let cipher = ValueCipher::new(KEY).unwrap();
+
+        let sealed = cipher.seal(b"hello room").unwrap();
+
+        assert!(sealed.starts_with(MARKER));
+        assert!(!sealed.windows(10).any(|window| window == b"hello room"));
+        assert_eq!(cipher.open(&sealed[MARKER.len()..]).unwrap(), b"hello room");
+    }
+
+    #[test]
+    fn open_with_other_key() {
+        let sealed = ValueCipher::new(KEY).unwrap().seal(b"hello room").unwrap();
+
+        let other = ValueCipher::new("HyAdHBsaGRgXFhUUExIREA8ODQwLCgkIBwYFBAMCAQA=").unwrap();
+
+        assert!(other.open(&sealed[MARKER.len()..]).is_err());
+    }
+
+    #[test]
+    fn read_plain_value() {
+        let value: Encrypted<String> =
+            FromRedisValue::from_redis_value(&Value::Data(b"hello room".to_vec())).unwrap();
+
+        assert_eq!(value.into_inner(), "hello room");
+    }
+}
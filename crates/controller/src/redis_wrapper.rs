@@ -2,15 +2,60 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use crate::settings::RedisConfig;
 use opentelemetry::metrics::Histogram;
 use opentelemetry::{Context, Key};
 use redis::aio::ConnectionLike;
-use redis::{Arg, RedisFuture};
-use std::sync::Arc;
-use std::time::Instant;
+use redis::{Arg, ErrorKind, RedisError, RedisFuture, RedisResult};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const COMMAND_KEY: Key = Key::from_static_str("command");
 
+/// Delay before the first retry of a failed command, doubled for every following retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Commands which only read data and can be retried without applying a change twice
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "DUMP",
+    "EXISTS",
+    "GET",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HSCAN",
+    "HVALS",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LRANGE",
+    "MGET",
+    "PING",
+    "PTTL",
+    "SCAN",
+    "SCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SMISMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "TTL",
+    "TYPE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZRANGE",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZSCAN",
+    "ZSCORE",
+];
+
 pub struct RedisMetrics {
     pub(crate) command_execution_time: Histogram<f64>,
 }
@@ -19,6 +64,7 @@ pub struct RedisMetrics {
 pub struct RedisConnection {
     connection_manager: redis::aio::ConnectionManager,
     metrics: Option<Arc<RedisMetrics>>,
+    resilience: Option<Arc<Resilience>>,
 }
 
 impl RedisConnection {
@@ -26,6 +72,7 @@ impl RedisConnection {
         Self {
             connection_manager,
             metrics: None,
+            resilience: None,
         }
     }
 
@@ -33,37 +80,309 @@ impl RedisConnection {
         self.metrics = Some(metrics);
         self
     }
+
+    /// Retry commands on connection failures and stop sending commands for a while after too many
+    /// consecutive failures, as configured in the [`RedisConfig`]
+    pub fn with_resilience(mut self, config: &RedisConfig) -> Self {
+        self.resilience = Some(Arc::new(Resilience {
+            max_retries: config.max_retries,
+            threshold: config.circuit_breaker_threshold,
+            cooldown: config.circuit_breaker_cooldown,
+            consecutive_failures: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+            write_buffer_size: config.write_buffer_size,
+            write_buffer: Mutex::new(VecDeque::new()),
+            flushing: AtomicBool::new(false),
+        }));
+        self
+    }
+
+    /// Execute writes whose results are not needed, buffering them while redis is unavailable
+    ///
+    /// Buffered writes are executed in order by a background task once a command succeeds again. Writes are
+    /// queued behind the buffered writes until the buffer is drained. When the buffer is full the oldest writes are
+    /// dropped, so this must only be used for writes which are still correct when applied late or not at all, e.g.
+    /// histories or statistics.
+    pub async fn write_buffered(&mut self, pipe: redis::Pipeline) -> RedisResult<()> {
+        let resilience = match &self.resilience {
+            Some(resilience) => resilience.clone(),
+            None => return pipe.query_async(self).await,
+        };
+
+        // Keep the order of the writes, earlier writes are still waiting to be executed
+        let queued =
+            resilience.has_buffered_writes() || resilience.flushing.load(Ordering::Acquire);
+
+        if !queued && resilience.check().is_ok() {
+            match pipe.query_async(self).await {
+                Err(e) if is_transient(&e) => {}
+                res => return res,
+            }
+        }
+
+        resilience.buffer_write(pipe);
+
+        if queued && resilience.check().is_ok() {
+            self.spawn_flush_write_buffer();
+        }
+
+        Ok(())
+    }
+
+    /// Execute the writes buffered by [`RedisConnection::write_buffered`] in a background task
+    ///
+    /// The caller does not wait for the buffered writes, only one task drains the buffer at a time to keep the
+    /// writes in order.
+    fn spawn_flush_write_buffer(&self) {
+        let resilience = match &self.resilience {
+            Some(resilience) => resilience.clone(),
+            None => return,
+        };
+
+        if !resilience.has_buffered_writes() || resilience.flushing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let mut connection_manager = self.connection_manager.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let drained = flush_write_buffer(&resilience, &mut connection_manager).await;
+
+                resilience.flushing.store(false, Ordering::Release);
+
+                // Writes may have been queued after the buffer was drained but before the flag was reset
+                if !drained
+                    || !resilience.has_buffered_writes()
+                    || resilience.flushing.swap(true, Ordering::AcqRel)
+                {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Fail immediately while redis is considered unavailable
+    fn check_available(&self) -> RedisResult<()> {
+        match &self.resilience {
+            Some(resilience) => resilience.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Record the result of a request, returns the delay after which the request should be retried
+    ///
+    /// Only read-only requests are retried, a failed write may have been applied before the connection broke.
+    fn retry_delay<T>(
+        &self,
+        res: &RedisResult<T>,
+        read_only: bool,
+        retries: &mut u32,
+    ) -> Option<Duration> {
+        let resilience = self.resilience.as_ref()?;
+
+        match res {
+            Ok(_) => resilience.record_success(),
+            Err(e) if is_transient(e) => {
+                if read_only && *retries < resilience.max_retries {
+                    let delay = RETRY_BASE_DELAY * 2u32.pow(*retries);
+                    *retries += 1;
+
+                    return Some(delay);
+                }
+
+                resilience.record_failure();
+            }
+            Err(_) => {}
+        }
+
+        None
+    }
+}
+
+/// Execute the buffered writes in order, returns false if a transient failure stopped the flush
+async fn flush_write_buffer(
+    resilience: &Resilience,
+    connection_manager: &mut redis::aio::ConnectionManager,
+) -> bool {
+    while let Some(pipe) = resilience.pop_buffered_write() {
+        match pipe.query_async::<_, ()>(connection_manager).await {
+            Ok(()) => {}
+            Err(e) if is_transient(&e) => {
+                resilience.requeue_buffered_write(pipe);
+                return false;
+            }
+            Err(e) => log::warn!("Failed to execute buffered redis write, {}", e),
+        }
+    }
+
+    true
+}
+
+/// Returns true if the command only reads data
+fn is_read_only(cmd: &redis::Cmd) -> bool {
+    match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => std::str::from_utf8(name)
+            .map(|name| {
+                READ_ONLY_COMMANDS
+                    .iter()
+                    .any(|command| command.eq_ignore_ascii_case(name))
+            })
+            .unwrap_or_default(),
+        _ => false,
+    }
+}
+
+/// Returns true if the error was caused by the connection to redis and the command may succeed later
+pub fn is_transient(error: &RedisError) -> bool {
+    error.is_connection_dropped() || error.is_connection_refusal() || error.is_io_error()
+}
+
+/// Returns true if the error or any of its causes is a transient redis error, see [`is_transient`]
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<RedisError>())
+        .any(is_transient)
+}
+
+/// Circuit breaker shared by all clones of a [`RedisConnection`]
+struct Resilience {
+    max_retries: u32,
+    threshold: u32,
+    cooldown: Duration,
+    consecutive_failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+    write_buffer_size: usize,
+    write_buffer: Mutex<VecDeque<redis::Pipeline>>,
+    /// Set while a task executes the buffered writes
+    flushing: AtomicBool,
+}
+
+impl Resilience {
+    /// Fail fast while the circuit is open, lets requests pass again after the cooldown
+    fn check(&self) -> RedisResult<()> {
+        let open_until = self
+            .open_until
+            .lock()
+            .expect("poisoned circuit breaker lock");
+
+        match *open_until {
+            Some(open_until) if Instant::now() < open_until => Err(RedisError::from((
+                ErrorKind::IoError,
+                "redis is unavailable, circuit breaker is open",
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_success(&self) {
+        if self.consecutive_failures.swap(0, Ordering::Relaxed) >= self.threshold {
+            log::info!("Redis is available again, closing circuit breaker");
+
+            *self
+                .open_until
+                .lock()
+                .expect("poisoned circuit breaker lock") = None;
+        }
+    }
+
+    fn buffer_write(&self, pipe: redis::Pipeline) {
+        let mut write_buffer = self
+            .write_buffer
+            .lock()
+            .expect("poisoned write buffer lock");
+
+        if write_buffer.len() >= self.write_buffer_size {
+            log::warn!("Redis write buffer is full, dropping the oldest write");
+
+            if write_buffer.pop_front().is_none() {
+                return;
+            }
+        }
+
+        write_buffer.push_back(pipe);
+    }
+
+    fn pop_buffered_write(&self) -> Option<redis::Pipeline> {
+        self.write_buffer
+            .lock()
+            .expect("poisoned write buffer lock")
+            .pop_front()
+    }
+
+    fn requeue_buffered_write(&self, pipe: redis::Pipeline) {
+        self.write_buffer
+            .lock()
+            .expect("poisoned write buffer lock")
+            .push_front(pipe);
+    }
+
+    fn has_buffered_writes(&self) -> bool {
+        !self
+            .write_buffer
+            .lock()
+            .expect("poisoned write buffer lock")
+            .is_empty()
+    }
+
+    fn record_failure(&self) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if failures >= self.threshold {
+            if failures == self.threshold {
+                log::error!(
+                    "Redis failed {} consecutive times, opening circuit breaker",
+                    failures
+                );
+            }
+
+            *self
+                .open_until
+                .lock()
+                .expect("poisoned circuit breaker lock") = Some(Instant::now() + self.cooldown);
+        }
+    }
 }
 
 impl ConnectionLike for RedisConnection {
     fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> RedisFuture<'a, redis::Value> {
-        let fut = self.connection_manager.req_packed_command(cmd);
+        Box::pin(async move {
+            self.check_available()?;
 
-        if let Some(metrics) = &self.metrics {
-            Box::pin(async move {
+            let read_only = is_read_only(cmd);
+            let mut retries = 0;
+
+            let (res, start) = loop {
                 let start = Instant::now();
+                let res = self.connection_manager.req_packed_command(cmd).await;
 
-                let res = fut.await;
+                match self.retry_delay(&res, read_only, &mut retries) {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => break (res, start),
+                }
+            };
 
-                if res.is_ok() {
-                    let command = if let Some(Arg::Simple(b)) = cmd.args_iter().next() {
-                        COMMAND_KEY.string(std::str::from_utf8(b).unwrap_or("UNKNOWN").to_owned())
-                    } else {
-                        COMMAND_KEY.string("UNKNOWN")
-                    };
+            if res.is_ok() {
+                self.spawn_flush_write_buffer();
+            }
 
-                    metrics.command_execution_time.record(
-                        &Context::current(),
-                        start.elapsed().as_secs_f64(),
-                        &[command],
-                    );
-                }
+            if let (Some(metrics), true) = (&self.metrics, res.is_ok()) {
+                let command = if let Some(Arg::Simple(b)) = cmd.args_iter().next() {
+                    COMMAND_KEY.string(std::str::from_utf8(b).unwrap_or("UNKNOWN").to_owned())
+                } else {
+                    COMMAND_KEY.string("UNKNOWN")
+                };
 
-                res
-            })
-        } else {
-            fut
-        }
+                metrics.command_execution_time.record(
+                    &Context::current(),
+                    start.elapsed().as_secs_f64(),
+                    &[command],
+                );
+            }
+
+            res
+        })
     }
 
     fn req_packed_commands<'a>(
@@ -72,32 +391,157 @@ impl ConnectionLike for RedisConnection {
         offset: usize,
         count: usize,
     ) -> RedisFuture<'a, Vec<redis::Value>> {
-        let fut = self
-            .connection_manager
-            .req_packed_commands(cmd, offset, count);
+        Box::pin(async move {
+            self.check_available()?;
 
-        if let Some(metrics) = &self.metrics {
-            Box::pin(async move {
-                let start = Instant::now();
+            let read_only = cmd.cmd_iter().all(is_read_only);
+            let mut retries = 0;
 
-                let res = fut.await;
+            let (res, start) = loop {
+                let start = Instant::now();
+                let res = self
+                    .connection_manager
+                    .req_packed_commands(cmd, offset, count)
+                    .await;
 
-                if res.is_ok() {
-                    metrics.command_execution_time.record(
-                        &Context::current(),
-                        start.elapsed().as_secs_f64(),
-                        &[COMMAND_KEY.string("MULTI")],
-                    );
+                match self.retry_delay(&res, read_only, &mut retries) {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => break (res, start),
                 }
+            };
 
-                res
-            })
-        } else {
-            fut
-        }
+            if res.is_ok() {
+                self.spawn_flush_write_buffer();
+            }
+
+            if let (Some(metrics), true) = (&self.metrics, res.is_ok()) {
+                metrics.command_execution_time.record(
+                    &Context::current(),
+                    start.elapsed().as_secs_f64(),
+                    &[COMMAND_KEY.string("MULTI")],
+                );
+            }
+
+            res
+        })
     }
 
     fn get_db(&self) -> i64 {
         self.connection_manager.get_db()
     }
 }
+
+/// Redis connection of the unit tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::RedisConnection;
+    use redis::aio::ConnectionManager;
+
+    /// Connect to the redis at `REDIS_ADDR` and remove all of its keys
+    ///
+    /// Tests using redis must be marked as `#[serial]`.
+    pub(crate) async fn setup() -> RedisConnection {
+        let redis_url =
+            std::env::var("REDIS_ADDR").unwrap_or_else(|_| "redis://0.0.0.0:6379/".to_owned());
+        let redis = redis::Client::open(redis_url).expect("Invalid redis url");
+
+        let mut mgr = ConnectionManager::new(redis).await.unwrap();
+
+        redis::cmd("FLUSHALL")
+            .query_async::<_, ()>(&mut mgr)
+            .await
+            .unwrap();
+
+        RedisConnection::new(mgr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resilience() -> Resilience {
+        Resilience {
+            max_retries: 3,
+            threshold: 2,
+            cooldown: Duration::from_secs(60),
+            consecutive_failures: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+            write_buffer_size: 2,
+            write_buffer: Mutex::new(VecDeque::new()),
+            flushing: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn circuit_breaker_opens_after_threshold() {
+        let resilience = resilience();
+
+        resilience.record_failure();
+        assert!(resilience.check().is_ok());
+
+        resilience.record_failure();
+        assert!(resilience.check().unwrap_err().is_io_error());
+    }
+
+    #[test]
+    fn circuit_breaker_closes_on_success() {
+        let resilience = resilience();
+
+        resilience.record_failure();
+        resilience.record_failure();
+        resilience.record_success();
+
+        assert_eq!(resilience.consecutive_failures.load(Ordering::Relaxed), 0);
+        assert!(resilience.check().is_ok());
+    }
+
+    #[test]
+    fn only_read_only_commands_are_retried() {
+        assert!(is_read_only(&redis::cmd("HGETALL")));
+        assert!(is_read_only(redis::cmd("get").arg("key")));
+        assert!(!is_read_only(redis::cmd("INCR").arg("key")));
+        assert!(!is_read_only(redis::cmd("EVALSHA").arg("sha")));
+
+        let read_pipe = redis::pipe().get("a").ttl("a").clone();
+        let write_pipe = redis::pipe().get("a").lpush("a", 1).clone();
+
+        assert!(read_pipe.cmd_iter().all(is_read_only));
+        assert!(!write_pipe.cmd_iter().all(is_read_only));
+    }
+
+    #[test]
+    fn write_buffer_drops_oldest_writes() {
+        let resilience = resilience();
+
+        resilience.buffer_write(redis::pipe().set("a", 1).clone());
+        resilience.buffer_write(redis::pipe().set("b", 2).clone());
+        resilience.buffer_write(redis::pipe().set("c", 3).clone());
+
+        let keys: Vec<_> = std::iter::from_fn(|| resilience.pop_buffered_write())
+            .map(
+                |pipe| match pipe.cmd_iter().next().unwrap().args_iter().nth(1) {
+                    Some(Arg::Simple(key)) => key.to_vec(),
+                    _ => panic!("missing key"),
+                },
+            )
+            .collect();
+
+        assert_eq!(keys, vec![b"b".to_vec(), b"c".to_vec()]);
+        assert!(!resilience.has_buffered_writes());
+    }
+
+    #[test]
+    fn transient_errors() {
+        let io_error = RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        let response_error = RedisError::from((ErrorKind::ResponseError, "WRONGTYPE"));
+
+        assert!(is_transient(&io_error));
+        assert!(!is_transient(&response_error));
+
+        assert!(is_transient_error(
+            &anyhow::Error::from(io_error).context("Failed to get attribute")
+        ));
+        assert!(!is_transient_error(&anyhow::Error::from(response_error)));
+    }
+}
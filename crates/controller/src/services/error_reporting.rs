@@ -0,0 +1,388 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! ErrorReportingService
+//!
+//! Ships module errors and panics to the Sentry compatible service configured in the `error_reporting` settings.
+//! Each runner is executed inside a [`scope`], which keeps track of the room, the module currently handling an event
+//! and the most recent events of the participant. Reports created inside the scope are enriched with them.
+use crate::api::signaling::SignalingRoomId;
+use anyhow::{bail, Context, Result};
+use controller_shared::settings::{ErrorReporting, SharedSettings};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::PanicInfo;
+use types::core::{ParticipantId, Timestamp};
+use url::Url;
+use uuid::Uuid;
+
+const CLIENT: &str = concat!("opentalk-controller/", env!("CARGO_PKG_VERSION"));
+
+tokio::task_local! {
+    static CONTEXT: RefCell<ReportContext>;
+}
+
+/// Context of the runner task, attached to the reports created inside of it
+struct ReportContext {
+    room: Option<SignalingRoomId>,
+    participant: Option<ParticipantId>,
+    module: Option<&'static str>,
+    breadcrumbs: VecDeque<Breadcrumb>,
+    max_breadcrumbs: usize,
+}
+
+struct Breadcrumb {
+    timestamp: Timestamp,
+    category: &'static str,
+    message: String,
+}
+
+/// Severity of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Error,
+    Fatal,
+}
+
+impl Level {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Fatal => "fatal",
+        }
+    }
+}
+
+/// Run the future inside a new report context, keeping the given number of breadcrumbs
+pub async fn scope<F>(max_breadcrumbs: usize, future: F) -> F::Output
+where
+    F: Future,
+{
+    let context = ReportContext {
+        room: None,
+        participant: None,
+        module: None,
+        breadcrumbs: VecDeque::with_capacity(max_breadcrumbs),
+        max_breadcrumbs,
+    };
+
+    CONTEXT.scope(RefCell::new(context), future).await
+}
+
+/// Set the room and participant of the current report context
+pub fn set_participant(room: SignalingRoomId, participant: ParticipantId) {
+    let _ = CONTEXT.try_with(|context| {
+        let mut context = context.borrow_mut();
+        context.room = Some(room);
+        context.participant = Some(participant);
+    });
+}
+
+/// Set the module which is currently handling an event, `None` once it is done
+pub fn set_module(module: Option<&'static str>) {
+    let _ = CONTEXT.try_with(|context| context.borrow_mut().module = module);
+}
+
+/// Add an event of the participant to the current report context, dropping the oldest one if it is full
+pub fn breadcrumb(category: &'static str, message: impl Into<String>) {
+    let _ = CONTEXT.try_with(|context| {
+        let mut context = context.borrow_mut();
+
+        if context.max_breadcrumbs == 0 {
+            return;
+        }
+
+        if context.breadcrumbs.len() == context.max_breadcrumbs {
+            context.breadcrumbs.pop_front();
+        }
+
+        context.breadcrumbs.push_back(Breadcrumb {
+            timestamp: Timestamp::now(),
+            category,
+            message: message.into(),
+        });
+    });
+}
+
+/// The endpoint and authentication of a Sentry project, parsed from its DSN
+#[derive(Debug, PartialEq, Eq)]
+struct Dsn {
+    store_url: Url,
+    public_key: String,
+}
+
+impl Dsn {
+    /// Parse a DSN like `https://<public key>@sentry.example.org/<path>/<project id>`
+    fn parse(dsn: &Url) -> Result<Self> {
+        let public_key = dsn.username();
+        if public_key.is_empty() {
+            bail!("DSN is missing the public key");
+        }
+
+        let path = dsn.path().trim_end_matches('/');
+        let (prefix, project_id) = match path.rsplit_once('/') {
+            Some((prefix, project_id)) if !project_id.is_empty() => (prefix, project_id),
+            _ => bail!("DSN is missing the project id"),
+        };
+
+        let mut store_url = dsn.clone();
+        store_url
+            .set_username("")
+            .and_then(|_| store_url.set_password(None))
+            .map_err(|_| anyhow::anyhow!("DSN cannot contain credentials"))?;
+        store_url.set_path(&format!("{prefix}/api/{project_id}/store/"));
+
+        Ok(Self {
+            store_url,
+            public_key: public_key.to_owned(),
+        })
+    }
+
+    fn auth_header(&self) -> String {
+        format!(
+            "Sentry sentry_version=7, sentry_client={CLIENT}, sentry_key={}",
+            self.public_key
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct ErrorReportingService {
+    settings: SharedSettings,
+    client: reqwest::Client,
+}
+
+impl ErrorReportingService {
+    pub fn new(settings: SharedSettings) -> Self {
+        Self {
+            settings,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Number of breadcrumbs kept for each runner, zero if error reporting is disabled
+    pub fn max_breadcrumbs(&self) -> usize {
+        self.settings
+            .load()
+            .error_reporting
+            .as_ref()
+            .map(|error_reporting| error_reporting.breadcrumbs)
+            .unwrap_or_default()
+    }
+
+    /// Report the error a module returned when handling an event
+    pub fn report_module_error(&self, namespace: &'static str, error: &anyhow::Error) {
+        self.report(
+            Level::Error,
+            format!("{:?}", error),
+            Some(namespace),
+            Map::new(),
+        );
+    }
+
+    /// Report panics in addition to the panic hook which is already installed
+    pub fn install_panic_hook(&self) {
+        let this = self.clone();
+        let previous_hook = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info| {
+            this.report_panic(info);
+
+            previous_hook(info);
+        }));
+    }
+
+    fn report_panic(&self, info: &PanicInfo<'_>) {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            (*message).to_owned()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_owned()
+        };
+
+        let mut extra = Map::new();
+        if let Some(location) = info.location() {
+            extra.insert("location".into(), Value::from(location.to_string()));
+        }
+
+        self.report(Level::Fatal, format!("panic: {message}"), None, extra);
+    }
+
+    /// Send a report in the background if error reporting is enabled and the report is sampled
+    fn report(
+        &self,
+        level: Level,
+        message: String,
+        module: Option<&'static str>,
+        extra: Map<String, Value>,
+    ) {
+        let settings = match self.settings.load().error_reporting.clone() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        if rand::random::<f64>() >= settings.sample_rate {
+            return;
+        }
+
+        // The context cannot be borrowed if the panic occurred while it was modified
+        let event = CONTEXT
+            .try_with(|context| {
+                context.try_borrow().ok().map(|context| {
+                    event(
+                        &settings,
+                        level,
+                        &message,
+                        module,
+                        extra.clone(),
+                        Some(&context),
+                    )
+                })
+            })
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| event(&settings, level, &message, module, extra, None));
+
+        // Reports created outside of a runtime, e.g. panics of the main thread, cannot be sent
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+
+        let this = self.clone();
+
+        handle.spawn(async move {
+            if let Err(e) = this.send(&settings, event).await {
+                log::warn!("Failed to send error report, {:?}", e);
+            }
+        });
+    }
+
+    async fn send(&self, settings: &ErrorReporting, event: Value) -> Result<()> {
+        let dsn = Dsn::parse(&settings.dsn).context("Invalid error reporting DSN")?;
+
+        let body = serde_json::to_vec(&event).context("Failed to serialize error report")?;
+
+        self.client
+            .post(dsn.store_url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Sentry-Auth", dsn.auth_header())
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("Error reporting service rejected the report")?;
+
+        Ok(())
+    }
+}
+
+/// Build the Sentry event of a report
+fn event(
+    settings: &ErrorReporting,
+    level: Level,
+    message: &str,
+    module: Option<&'static str>,
+    mut extra: Map<String, Value>,
+    context: Option<&ReportContext>,
+) -> Value {
+    let mut tags = Map::new();
+    let mut breadcrumbs = vec![];
+
+    if let Some(context) = context {
+        if let Some(room) = context.room {
+            tags.insert("room_id".into(), Value::from(room.to_string()));
+        }
+        if let Some(participant) = context.participant {
+            extra.insert(
+                "participant_id".into(),
+                Value::from(participant.to_string()),
+            );
+        }
+        if let Some(module) = module.or(context.module) {
+            tags.insert("module".into(), Value::from(module));
+        }
+
+        breadcrumbs = context
+            .breadcrumbs
+            .iter()
+            .map(|breadcrumb| {
+                json!({
+                    "timestamp": breadcrumb.timestamp,
+                    "category": breadcrumb.category,
+                    "message": breadcrumb.message,
+                })
+            })
+            .collect();
+    } else if let Some(module) = module {
+        tags.insert("module".into(), Value::from(module));
+    }
+
+    json!({
+        "event_id": Uuid::new_v4().simple().to_string(),
+        "timestamp": Timestamp::now(),
+        "platform": "other",
+        "level": level.as_str(),
+        "logger": "signaling",
+        "release": CLIENT,
+        "environment": settings.environment,
+        "message": { "formatted": message },
+        "tags": tags,
+        "extra": extra,
+        "breadcrumbs": { "values": breadcrumbs },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_dsn() {
+        let dsn = Dsn::parse(
+            &"https://abc123@sentry.example.org/errors/42"
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            dsn.store_url.as_str(),
+            "https://sentry.example.org/errors/api/42/store/"
+        );
+        assert_eq!(dsn.public_key, "abc123");
+    }
+
+    #[test]
+    fn parse_invalid_dsn() {
+        assert!(Dsn::parse(&"https://sentry.example.org/42".parse().unwrap()).is_err());
+        assert!(Dsn::parse(&"https://abc123@sentry.example.org/".parse().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn breadcrumbs_are_bounded() {
+        scope(2, async {
+            breadcrumb("websocket", "chat");
+            breadcrumb("rabbitmq", "media");
+            breadcrumb("websocket", "polls");
+
+            CONTEXT.with(|context| {
+                let messages: Vec<String> = context
+                    .borrow()
+                    .breadcrumbs
+                    .iter()
+                    .map(|breadcrumb| breadcrumb.message.clone())
+                    .collect();
+
+                assert_eq!(messages, vec!["media", "polls"]);
+            });
+        })
+        .await;
+    }
+}
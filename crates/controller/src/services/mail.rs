@@ -7,15 +7,22 @@
 //! Used to have a clean interface for various kinds of mails
 //! that are sent from the Web-API and possibly other connected services.
 //!
+//! Mails are sent directly with SMTP if configured, otherwise enqueued for the mail worker.
+//!
 // TODO We probably can avoid the conversion to MailTasks if no rabbit_mq_queue is set in all mail fns
+use super::mail_templates;
+use super::smtp::SmtpTransport;
 use crate::metrics::EndpointMetrics;
 use anyhow::{Context, Result};
 use controller_shared::settings::{Settings, SharedSettings};
+use database::Db;
+use db_storage::mail_templates::MailTemplate;
 use db_storage::{events::Event, rooms::Room, sip_configs::SipConfig, users::User};
 use lapin_pool::{RabbitMqChannel, RabbitMqPool};
 use mail_worker_proto::*;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use types::core::TenantId;
 
 pub struct RegisteredMailRecipient {
     pub email: String,
@@ -51,6 +58,9 @@ fn to_event(
 
     let end_time: Option<v1::Time> = event.ends_at_of_first_occurrence().map(Into::into);
 
+    // The dial-in of the room cannot be used to join an external meeting
+    let sip = sip.filter(|_| event.external_meeting_url.is_none());
+
     let call_in =
         if let Some((call_in_settings, sip_config)) = settings.call_in.as_ref().zip(sip.as_ref()) {
             Some(v1::CallIn {
@@ -72,36 +82,92 @@ fn to_event(
         room: v1::Room {
             id: *room.id.inner(),
             password: room.password,
+            locale: room.locale,
         },
         call_in,
+        external_meeting_url: event.external_meeting_url,
     }
 }
 
 #[derive(Clone)]
 pub struct MailService {
     settings: SharedSettings,
+    db: Arc<Db>,
     metrics: Arc<EndpointMetrics>,
     rabbitmq_pool: Arc<RabbitMqPool>,
     rabbitmq_channel: Arc<Mutex<RabbitMqChannel>>,
+    smtp: Option<Arc<SmtpTransport>>,
 }
 
 impl MailService {
     pub fn new(
         settings: SharedSettings,
+        db: Arc<Db>,
         metrics: Arc<EndpointMetrics>,
         rabbitmq_pool: Arc<RabbitMqPool>,
         rabbitmq_channel: RabbitMqChannel,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        let smtp = settings
+            .load()
+            .smtp
+            .as_ref()
+            .map(SmtpTransport::new)
+            .transpose()?
+            .map(Arc::new);
+
+        Ok(Self {
             settings,
+            db,
             metrics,
             rabbitmq_pool,
             rabbitmq_channel: Arc::new(Mutex::new(rabbitmq_channel)),
+            smtp,
+        })
+    }
+
+    /// Attach the mail rendered from the template of the tenant to the mail task
+    ///
+    /// Keeps the built-in template of the mail worker if the tenant has no template or it fails to render.
+    async fn apply_template(&self, tenant_id: TenantId, mail_task: &mut MailTask) {
+        let kind = mail_templates::template_kind(mail_task);
+        let db = self.db.clone();
+
+        let template = crate::block(move || -> Result<_> {
+            let mut conn = db.get_conn()?;
+
+            Ok(MailTemplate::get(&mut conn, tenant_id, kind)?)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+
+        let template = match template {
+            Ok(Some(template)) => template,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!(
+                    "Failed to load mail template, using built-in template, {}",
+                    e
+                );
+                return;
+            }
+        };
+
+        match mail_templates::render_mail(&template, mail_task) {
+            Ok(custom_mail) => mail_task.set_custom_mail(custom_mail),
+            Err(e) => log::warn!(
+                "Failed to render mail template, using built-in template, {}",
+                e
+            ),
         }
     }
 
-    async fn send_to_rabbitmq(&self, mail_task: MailTask) -> Result<()> {
-        if let Some(queue_name) = &self.settings.load().rabbit_mq.mail_task_queue {
+    async fn send_mail_task(&self, tenant_id: TenantId, mut mail_task: MailTask) -> Result<()> {
+        self.apply_template(tenant_id, &mut mail_task).await;
+
+        if let Some(smtp) = &self.smtp {
+            smtp.send(&mail_task).await?;
+        } else if let Some(queue_name) = &self.settings.load().rabbit_mq.mail_task_queue {
             let channel = {
                 let mut channel = self.rabbitmq_channel.lock().await;
 
@@ -133,7 +199,7 @@ impl MailService {
         Ok(())
     }
 
-    /// Sends a Registered Invite mail task to the rabbit mq queue, if configured.
+    /// Sends a Registered Invite mail with SMTP or as mail task to the rabbit mq queue, if configured.
     pub async fn send_registered_invite(
         &self,
         inviter: User,
@@ -143,6 +209,7 @@ impl MailService {
         invitee: User,
     ) -> Result<()> {
         let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
 
         // Create MailTask
         let mail_task = MailTask::registered_event_invite(
@@ -151,11 +218,11 @@ impl MailService {
             invitee,
         );
 
-        self.send_to_rabbitmq(mail_task).await?;
+        self.send_mail_task(tenant_id, mail_task).await?;
         Ok(())
     }
 
-    /// Sends a Unregistered Invite mail task to the rabbit mq queue, if configured.
+    /// Sends a Unregistered Invite mail with SMTP or as mail task to the rabbit mq queue, if configured.
     pub async fn send_unregistered_invite(
         &self,
         inviter: User,
@@ -165,6 +232,7 @@ impl MailService {
         invitee: keycloak_admin::users::User,
     ) -> Result<()> {
         let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
 
         // Create MailTask
         let mail_task = MailTask::unregistered_event_invite(
@@ -173,11 +241,11 @@ impl MailService {
             invitee,
         );
 
-        self.send_to_rabbitmq(mail_task).await?;
+        self.send_mail_task(tenant_id, mail_task).await?;
         Ok(())
     }
 
-    /// Sends a external Invite mail task to the rabbit mq queue, if configured.
+    /// Sends a external Invite mail with SMTP or as mail task to the rabbit mq queue, if configured.
     pub async fn send_external_invite(
         &self,
         inviter: User,
@@ -188,6 +256,7 @@ impl MailService {
         invite_code: String,
     ) -> Result<()> {
         let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
 
         // Create MailTask
         let mail_task = MailTask::external_event_invite(
@@ -197,11 +266,61 @@ impl MailService {
             invite_code,
         );
 
-        self.send_to_rabbitmq(mail_task).await?;
+        self.send_mail_task(tenant_id, mail_task).await?;
+        Ok(())
+    }
+
+    /// Sends an Invite mail again to an invitee who did not respond yet, with SMTP or as mail task to the rabbit mq
+    /// queue, if configured.
+    pub async fn send_invite_reminder(
+        &self,
+        inviter: User,
+        event: Event,
+        room: Room,
+        sip_config: Option<SipConfig>,
+        invitee: MailRecipient,
+        invite_code: String,
+    ) -> Result<()> {
+        let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
+
+        let mail_task = match invitee {
+            MailRecipient::Registered(invitee) => MailTask::registered_event_invite(
+                inviter,
+                to_event(event, room, sip_config, settings),
+                v1::RegisteredUser {
+                    email: v1::Email::new(invitee.email),
+                    title: invitee.title,
+                    first_name: invitee.first_name,
+                    last_name: invitee.last_name,
+                    language: invitee.language,
+                },
+            ),
+            MailRecipient::Unregistered(invitee) => MailTask::unregistered_event_invite(
+                inviter,
+                to_event(event, room, sip_config, settings),
+                v1::UnregisteredUser {
+                    email: v1::Email::new(invitee.email),
+                    first_name: invitee.first_name,
+                    last_name: invitee.last_name,
+                },
+            ),
+            MailRecipient::External(invitee) => MailTask::external_event_invite(
+                inviter,
+                to_event(event, room, sip_config, settings),
+                v1::ExternalUser {
+                    email: v1::Email::new(invitee.email),
+                },
+                invite_code,
+            ),
+        };
+
+        self.send_mail_task(tenant_id, mail_task).await?;
+
         Ok(())
     }
 
-    /// Sends an Event Update mail task to the rabbit mq queue, if configured.
+    /// Sends an Event Update mail with SMTP or as mail task to the rabbit mq queue, if configured.
     pub async fn send_event_update(
         &self,
         inviter: User,
@@ -212,6 +331,7 @@ impl MailService {
         invite_code: String,
     ) -> Result<()> {
         let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
 
         let mail_task = match invitee {
             MailRecipient::Registered(invitee) => MailTask::registered_event_update(
@@ -244,12 +364,12 @@ impl MailService {
             ),
         };
 
-        self.send_to_rabbitmq(mail_task).await?;
+        self.send_mail_task(tenant_id, mail_task).await?;
 
         Ok(())
     }
 
-    /// Sends an Event Cancellation mail task to the rabbit mq queue, if configured.
+    /// Sends an Event Cancellation mail with SMTP or as mail task to the rabbit mq queue, if configured.
     pub async fn send_event_cancellation(
         &self,
         inviter: User,
@@ -259,6 +379,7 @@ impl MailService {
         invitee: MailRecipient,
     ) -> Result<()> {
         let settings = &*self.settings.load();
+        let tenant_id = room.tenant_id;
 
         let mail_task = match invitee {
             MailRecipient::Registered(invitee) => MailTask::registered_event_cancellation(
@@ -290,7 +411,7 @@ impl MailService {
             ),
         };
 
-        self.send_to_rabbitmq(mail_task).await?;
+        self.send_mail_task(tenant_id, mail_task).await?;
 
         Ok(())
     }
@@ -0,0 +1,366 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Rendering of the mail templates of tenants
+//!
+//! Mails sent with SMTP use the built-in templates of [`built_in_mail`] if the tenant has no template.
+//!
+//! Templates contain variables in double curly braces, e.g. `{{event.name}}`, which are replaced with the values of
+//! the mail task. Values inserted into the body are HTML escaped, unknown variables are replaced with nothing.
+//!
+//! Available variables:
+//! - `event.name`, `event.description`, `event.rrule`
+//! - `event.start_time.time`, `event.start_time.timezone`, `event.end_time.time`, `event.end_time.timezone`
+//! - `event.room.id`, `event.room.password`
+//! - `event.external_meeting_url`, only for events taking place on an external conferencing service
+//! - `event.call_in.sip_tel`, `event.call_in.sip_id`, `event.call_in.sip_password`
+//! - `inviter.email`, `inviter.title`, `inviter.first_name`, `inviter.last_name`
+//! - `invitee.email`, and `invitee.first_name`, `invitee.last_name` for invitees which are not external
+//! - `invite_code`, only for invites and updates sent to external invitees
+use db_storage::mail_templates::{MailTemplate, MailTemplateKind};
+use mail_worker_proto::v1::{self, CustomMail};
+use mail_worker_proto::MailTask;
+use serde_json::Value;
+use std::fmt;
+use uuid::Uuid;
+
+/// Syntax error in a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    /// Byte offset of the variable containing the error
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// The kind of template used for the mail task
+pub fn template_kind(mail_task: &MailTask) -> MailTemplateKind {
+    let MailTask::V1(message) = mail_task;
+
+    match message {
+        v1::Message::RegisteredEventInvite(_)
+        | v1::Message::UnregisteredEventInvite(_)
+        | v1::Message::ExternalEventInvite(_) => MailTemplateKind::EventInvite,
+        v1::Message::RegisteredEventUpdate(_)
+        | v1::Message::UnregisteredEventUpdate(_)
+        | v1::Message::ExternalEventUpdate(_) => MailTemplateKind::EventUpdate,
+        v1::Message::RegisteredEventCancellation(_)
+        | v1::Message::UnregisteredEventCancellation(_)
+        | v1::Message::ExternalEventCancellation(_) => MailTemplateKind::EventCancellation,
+    }
+}
+
+/// Render the subject and body of the template with the values of the mail task
+pub fn render_mail(
+    template: &MailTemplate,
+    mail_task: &MailTask,
+) -> Result<CustomMail, TemplateError> {
+    let context = serde_json::to_value(mail_task).unwrap_or_default();
+
+    Ok(CustomMail {
+        subject: render(&template.subject, &context, false)?,
+        body: render(&template.body, &context, true)?,
+    })
+}
+
+/// Replace the variables of the template with the values of the context
+pub fn render(template: &str, context: &Value, escape_html: bool) -> Result<String, TemplateError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut position = 0;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+
+        let variable = &rest[start + 2..];
+        let end = variable.find("}}").ok_or(TemplateError {
+            position: position + start,
+            message: "Unclosed variable",
+        })?;
+
+        let name = variable[..end].trim();
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+
+        if !valid_name {
+            return Err(TemplateError {
+                position: position + start,
+                message: "Invalid variable name",
+            });
+        }
+
+        let value = match lookup(context, name) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+            _ => String::new(),
+        };
+
+        if escape_html {
+            push_html_escaped(&mut rendered, &value);
+        } else {
+            rendered.push_str(&value);
+        }
+
+        let consumed = start + 2 + end + 2;
+        rest = &rest[consumed..];
+        position += consumed;
+    }
+
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+/// Get the value of the variable from the context
+fn lookup<'v>(context: &'v Value, name: &str) -> Option<&'v Value> {
+    name.split('.')
+        .try_fold(context, |value, key| value.get(key))
+}
+
+/// Whether the variable has a non-empty value in the context
+fn is_set(context: &Value, name: &str) -> bool {
+    match lookup(context, name) {
+        None | Some(Value::Null) => false,
+        Some(Value::String(value)) => !value.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn push_html_escaped(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Render the mail with the built-in template, used when sending mails with SMTP
+pub fn built_in_mail(mail_task: &MailTask) -> Result<CustomMail, TemplateError> {
+    let context = serde_json::to_value(mail_task).unwrap_or_default();
+    let kind = template_kind(mail_task);
+
+    let (subject, introduction) = match kind {
+        MailTemplateKind::EventInvite => (
+            "Invitation to {{event.name}}",
+            "{{inviter.first_name}} {{inviter.last_name}} invited you to <b>{{event.name}}</b>.",
+        ),
+        MailTemplateKind::EventUpdate => (
+            "Updated: {{event.name}}",
+            "{{inviter.first_name}} {{inviter.last_name}} updated <b>{{event.name}}</b>.",
+        ),
+        MailTemplateKind::EventCancellation => (
+            "Cancelled: {{event.name}}",
+            "{{inviter.first_name}} {{inviter.last_name}} cancelled <b>{{event.name}}</b>.",
+        ),
+    };
+
+    let mut body = format!("<p>{introduction}</p>");
+
+    if is_set(&context, "event.description") {
+        body.push_str("<p>{{event.description}}</p>");
+    }
+
+    // Only list the details which are set, a cancelled event cannot be joined anymore
+    let details: &[(&str, &str)] = match kind {
+        MailTemplateKind::EventCancellation => &[],
+        MailTemplateKind::EventInvite | MailTemplateKind::EventUpdate => &[
+            (
+                "event.start_time",
+                "Start: {{event.start_time.time}} ({{event.start_time.timezone}})<br>",
+            ),
+            (
+                "event.end_time",
+                "End: {{event.end_time.time}} ({{event.end_time.timezone}})<br>",
+            ),
+            ("event.rrule", "Recurrence: {{event.rrule}}<br>"),
+            (
+                "event.external_meeting_url",
+                "Meeting link: {{event.external_meeting_url}}<br>",
+            ),
+            (
+                "event.room.password",
+                "Room password: {{event.room.password}}<br>",
+            ),
+            ("invite_code", "Invite code: {{invite_code}}<br>"),
+            (
+                "event.call_in",
+                "Dial-in: {{event.call_in.sip_tel}}, ID {{event.call_in.sip_id}}, \
+                 PIN {{event.call_in.sip_password}}<br>",
+            ),
+        ],
+    };
+
+    // The room cannot be used to join an external meeting
+    let is_external = is_set(&context, "event.external_meeting_url");
+    let is_room_detail = |name: &str| matches!(name, "event.room.password" | "invite_code");
+
+    let details: String = details
+        .iter()
+        .filter(|(name, _)| is_set(&context, name))
+        .filter(|(name, _)| !(is_external && is_room_detail(name)))
+        .map(|(_, line)| *line)
+        .collect();
+
+    if !details.is_empty() {
+        body.push_str(&format!("<p>{details}</p>"));
+    }
+
+    Ok(CustomMail {
+        subject: render(subject, &context, false)?,
+        body: render(&body, &context, true)?,
+    })
+}
+
+/// Mail task with example values, used to preview templates
+pub fn sample_mail_task(kind: MailTemplateKind) -> MailTask {
+    let inviter = v1::RegisteredUser {
+        email: "alice@example.org".into(),
+        title: "".into(),
+        first_name: "Alice".into(),
+        last_name: "Adams".into(),
+        language: "en".into(),
+    };
+
+    let invitee = v1::RegisteredUser {
+        email: "bob@example.org".into(),
+        title: "Dr.".into(),
+        first_name: "Bob".into(),
+        last_name: "Baker".into(),
+        language: "en".into(),
+    };
+
+    let start = chrono::Utc::now() + chrono::Duration::days(1);
+
+    let event = v1::Event {
+        id: Uuid::nil(),
+        name: "Weekly Meeting".into(),
+        description: "Status updates of the team".into(),
+        start_time: Some(v1::Time {
+            time: start,
+            timezone: "Europe/Berlin".into(),
+        }),
+        end_time: Some(v1::Time {
+            time: start + chrono::Duration::hours(1),
+            timezone: "Europe/Berlin".into(),
+        }),
+        rrule: Some("FREQ=WEEKLY".into()),
+        room: v1::Room {
+            id: Uuid::nil(),
+            password: Some("password".into()),
+            locale: None,
+        },
+        call_in: Some(v1::CallIn {
+            sip_tel: "+4930405051".into(),
+            sip_id: "0123456789".into(),
+            sip_password: "555555".into(),
+        }),
+        external_meeting_url: None,
+    };
+
+    match kind {
+        MailTemplateKind::EventInvite => MailTask::registered_event_invite(inviter, event, invitee),
+        MailTemplateKind::EventUpdate => MailTask::registered_event_update(inviter, event, invitee),
+        MailTemplateKind::EventCancellation => {
+            MailTask::registered_event_cancellation(inviter, event, invitee)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use types::core::TenantId;
+
+    #[test]
+    fn render_variables() {
+        let context = json!({
+            "event": {"name": "<Weekly> & more", "room": {"password": null}},
+            "count": 3,
+        });
+
+        assert_eq!(
+            render(
+                "{{ event.name }}: {{count}}{{event.room.password}}{{unknown.name}}",
+                &context,
+                false
+            ),
+            Ok("<Weekly> & more: 3".to_string())
+        );
+        assert_eq!(
+            render("<b>{{event.name}}</b>", &context, true),
+            Ok("<b>&lt;Weekly&gt; &amp; more</b>".to_string())
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            render("Hello {{name", &json!({}), false),
+            Err(TemplateError {
+                position: 6,
+                message: "Unclosed variable",
+            })
+        );
+        assert_eq!(
+            render("{{a}} {{ }}", &json!({}), false),
+            Err(TemplateError {
+                position: 6,
+                message: "Invalid variable name",
+            })
+        );
+    }
+
+    #[test]
+    fn render_sample() {
+        let template = MailTemplate {
+            tenant_id: TenantId::from(Uuid::nil()),
+            kind: MailTemplateKind::EventInvite,
+            subject: "Invitation to {{event.name}}".into(),
+            body: "Hello {{invitee.first_name}}, {{inviter.first_name}} invited you".into(),
+            updated_at: chrono::Utc::now(),
+        };
+
+        let mail_task = sample_mail_task(MailTemplateKind::EventInvite);
+        assert_eq!(template_kind(&mail_task), MailTemplateKind::EventInvite);
+
+        assert_eq!(
+            render_mail(&template, &mail_task),
+            Ok(CustomMail {
+                subject: "Invitation to Weekly Meeting".into(),
+                body: "Hello Bob, Alice invited you".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn built_in_cancellation() {
+        let mail_task = sample_mail_task(MailTemplateKind::EventCancellation);
+
+        assert_eq!(
+            built_in_mail(&mail_task),
+            Ok(CustomMail {
+                subject: "Cancelled: Weekly Meeting".into(),
+                body: "<p>Alice Adams cancelled <b>Weekly Meeting</b>.</p>\
+                       <p>Status updates of the team</p>"
+                    .into(),
+            })
+        );
+    }
+}
@@ -4,10 +4,16 @@
 
 //! Long Running Services that expose clean APIs and hide implementation details from endpoints
 //! If the amount of services grow, add another layer that bundles all services.
+pub mod error_reporting;
 mod mail;
+pub mod mail_templates;
+mod notifications;
+mod smtp;
 
+pub use error_reporting::ErrorReportingService;
 pub use mail::ExternalMailRecipient;
 pub use mail::MailRecipient;
 pub use mail::MailService;
 pub use mail::RegisteredMailRecipient;
 pub use mail::UnregisteredMailRecipient;
+pub use notifications::NotificationService;
@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! NotificationService
+//!
+//! Notifies chat services and webhooks configured in the `notifications` settings about events of meetings. Targets
+//! can be restricted to the meetings of some tenants or rooms. Messages are rendered from templates, in which `{name}` is replaced with the value of the same name. Events
+//! without a configured template use the message of the catalog in the locale of the room.
+use crate::i18n::{Locale, Message};
+use anyhow::{Context, Result};
+use controller_shared::settings::{
+    NotificationEvent, NotificationTarget, NotificationTargetKind, SharedSettings,
+};
+use database::Db;
+use db_storage::events::Event;
+use db_storage::rooms::Room;
+use db_storage::tenants::Tenant;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use types::core::RoomId;
+
+fn default_template(event: NotificationEvent, locale: Locale) -> &'static str {
+    let message = match event {
+        NotificationEvent::MeetingStarted => Message::MeetingStarted,
+        NotificationEvent::RecordingAvailable => Message::RecordingAvailable,
+        NotificationEvent::VoteResult => Message::VoteResult,
+    };
+
+    locale.message(message)
+}
+
+/// Replaces every `{name}` in the template with the value of the same name, unknown names are kept as they are
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest.find('}').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &rest[1..end])
+                .map(|(_, value)| (end, value))
+        });
+
+        match value {
+            Some((end, value)) => {
+                rendered.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+fn payload(
+    target: &NotificationTarget,
+    event: NotificationEvent,
+    locale: Locale,
+    values: &[(&str, String)],
+) -> Value {
+    let template = target
+        .templates
+        .get(&event)
+        .map(String::as_str)
+        .unwrap_or_else(|| default_template(event, locale));
+
+    let text = render(template, values);
+
+    match target.kind {
+        NotificationTargetKind::Slack | NotificationTargetKind::Mattermost => {
+            json!({ "text": text })
+        }
+        NotificationTargetKind::Webhook => {
+            let data: Map<String, Value> = values
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::from(value.as_str())))
+                .collect();
+
+            json!({ "event": event.as_str(), "text": text, "data": data })
+        }
+    }
+}
+
+/// Returns true if the target wants to be notified about the event of the room, regardless of the room's tenant
+fn is_subscribed(target: &NotificationTarget, event: NotificationEvent, room_id: RoomId) -> bool {
+    (target.events.is_empty() || target.events.contains(&event))
+        && (target.rooms.is_empty() || target.rooms.contains(room_id.inner()))
+}
+
+#[derive(Clone)]
+pub struct NotificationService {
+    settings: SharedSettings,
+    db: Arc<Db>,
+    client: reqwest::Client,
+}
+
+impl NotificationService {
+    pub fn new(settings: SharedSettings, db: Arc<Db>) -> Self {
+        Self {
+            settings,
+            db,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Notifies all targets subscribed to the event in the background
+    ///
+    /// The values `room_id` and `title` (the title of the room's event, the room id if there is none) are always
+    /// available to the templates and need not be passed.
+    pub fn notify(
+        &self,
+        event: NotificationEvent,
+        room_id: RoomId,
+        values: Vec<(&'static str, String)>,
+    ) {
+        let targets: Vec<NotificationTarget> = self
+            .settings
+            .load()
+            .notifications
+            .iter()
+            .filter(|target| is_subscribed(target, event, room_id))
+            .cloned()
+            .collect();
+
+        if targets.is_empty() {
+            return;
+        }
+
+        let this = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = this.send(targets, event, room_id, values).await {
+                log::error!("Failed to send {} notifications, {:?}", event.as_str(), e);
+            }
+        });
+    }
+
+    async fn send(
+        &self,
+        targets: Vec<NotificationTarget>,
+        event: NotificationEvent,
+        room_id: RoomId,
+        mut values: Vec<(&'static str, String)>,
+    ) -> Result<()> {
+        let (room, oidc_tenant_id, title) = crate::block({
+            let db = self.db.clone();
+
+            move || -> Result<(Room, String, Option<String>)> {
+                let mut conn = db.get_conn()?;
+
+                let room = Room::get(&mut conn, room_id)?;
+                let tenant = Tenant::get(&mut conn, room.tenant_id)?;
+
+                // Rescheduled events share the room, all of them have the same title
+                let title = match Event::get_all_ids_for_room(&mut conn, room_id)?.first() {
+                    Some(event_id) => Some(Event::get(&mut conn, *event_id)?.title),
+                    None => None,
+                };
+
+                Ok((room, tenant.oidc_tenant_id.into_inner(), title))
+            }
+        })
+        .await??;
+
+        let targets = targets
+            .into_iter()
+            .filter(|target| target.tenants.is_empty() || target.tenants.contains(&oidc_tenant_id));
+
+        let locale = Locale::resolve(
+            room.locale.as_deref(),
+            &self.settings.load().defaults.user_language,
+        );
+
+        values.push(("room_id", room_id.to_string()));
+        values.push(("title", title.unwrap_or_else(|| room_id.to_string())));
+
+        for target in targets {
+            let body = serde_json::to_vec(&payload(&target, event, locale, &values))
+                .context("Failed to serialize notification")?;
+
+            let response = self
+                .client
+                .post(target.url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            if let Err(e) = response {
+                log::warn!("Failed to notify {}, {}", target.url, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn target(kind: NotificationTargetKind) -> NotificationTarget {
+        NotificationTarget {
+            kind,
+            url: "http://localhost/hooks/notify".parse().unwrap(),
+            events: vec![],
+            tenants: vec![],
+            rooms: vec![],
+            templates: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn subscribed_targets() {
+        let room_id = RoomId::from(uuid::Uuid::from_u128(1));
+
+        let mut target = target(NotificationTargetKind::Webhook);
+        assert!(is_subscribed(
+            &target,
+            NotificationEvent::VoteResult,
+            room_id
+        ));
+
+        target.events = vec![NotificationEvent::MeetingStarted];
+        target.rooms = vec![uuid::Uuid::from_u128(2)];
+        assert!(!is_subscribed(
+            &target,
+            NotificationEvent::MeetingStarted,
+            room_id
+        ));
+
+        target.rooms.push(*room_id.inner());
+        assert!(is_subscribed(
+            &target,
+            NotificationEvent::MeetingStarted,
+            room_id
+        ));
+        assert!(!is_subscribed(
+            &target,
+            NotificationEvent::VoteResult,
+            room_id
+        ));
+    }
+
+    #[test]
+    fn render_template() {
+        let values = [("title", "Daily".to_string()), ("topic", "Lunch".into())];
+
+        assert_eq!(
+            render("{title}: {topic} {unknown} {title", &values),
+            "Daily: Lunch {unknown} {title"
+        );
+    }
+
+    #[test]
+    fn chat_payload() {
+        let mut target = target(NotificationTargetKind::Mattermost);
+        target.templates.insert(
+            NotificationEvent::MeetingStarted,
+            ":movie_camera: {title}".into(),
+        );
+
+        let values = [("title", "Daily".to_string())];
+
+        assert_eq!(
+            payload(
+                &target,
+                NotificationEvent::MeetingStarted,
+                Locale::En,
+                &values
+            ),
+            json!({ "text": ":movie_camera: Daily" })
+        );
+    }
+
+    #[test]
+    fn webhook_payload() {
+        let values = [("title", "Daily".to_string())];
+
+        assert_eq!(
+            payload(
+                &target(NotificationTargetKind::Webhook),
+                NotificationEvent::RecordingAvailable,
+                Locale::De,
+                &values
+            ),
+            json!({
+                "event": "recording_available",
+                "text": "Eine Aufzeichnung des Meetings Daily ist verfügbar",
+                "data": { "title": "Daily" }
+            })
+        );
+    }
+}
@@ -0,0 +1,60 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Sends mails directly to an SMTP server, for installations without the mail worker
+use super::mail_templates::built_in_mail;
+use anyhow::{Context, Result};
+use controller_shared::settings::Smtp;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use mail_worker_proto::MailTask;
+
+pub(super) struct SmtpTransport {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpTransport {
+    pub(super) fn new(settings: &Smtp) -> Result<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&settings.url)
+            .context("Invalid SMTP url")?
+            .build();
+
+        let from = settings
+            .from
+            .parse()
+            .context("Invalid sender of the SMTP settings")?;
+
+        Ok(Self { transport, from })
+    }
+
+    /// Send the mail of the mail task, rendered with the built-in template if the tenant has no template
+    pub(super) async fn send(&self, mail_task: &MailTask) -> Result<()> {
+        let mail = match mail_task.custom_mail() {
+            Some(mail) => mail.clone(),
+            None => built_in_mail(mail_task).context("Failed to render built-in mail template")?,
+        };
+
+        let to: Mailbox = mail_task
+            .invitee_email()
+            .parse()
+            .context("Invalid email address of the invitee")?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject)
+            .header(ContentType::TEXT_HTML)
+            .body(mail.body)
+            .context("Failed to build mail")?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send mail with SMTP")?;
+
+        Ok(())
+    }
+}
@@ -2,25 +2,49 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use super::scan::{ScanVerdict, VirusScanner};
 use super::ObjectStorage;
 use anyhow::{Context, Result};
 use aws_sdk_s3::types::ByteStream;
 use bytes::Bytes;
+use controller_shared::settings::VirusScanFailurePolicy;
 use database::Db;
-use db_storage::assets::{Asset, NewAsset};
+use db_storage::assets::{Asset, AssetScanStatus, NewAsset};
 use db_storage::rooms::Room;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use std::sync::Arc;
-use types::core::{AssetId, RoomId};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use types::core::{AssetId, BreakoutRoomId, RoomId};
 use uuid::Uuid;
 
+/// Number of chunks buffered between the upload and the virus scanner
+const SCAN_CHANNEL_CAPACITY: usize = 16;
+
+/// Returned when the virus scan found an asset to be infected
+///
+/// The asset has been moved into quarantine and its database entry is marked as [`AssetScanStatus::Infected`].
+#[derive(Debug, thiserror::Error)]
+#[error("asset {asset_id} is infected with {signature}")]
+pub struct AssetInfected {
+    pub asset_id: AssetId,
+    pub signature: String,
+}
+
 /// Save an asset in the long term storage
 ///
-/// Creates a new database entry before after the asset in the configured S3 bucket.
+/// Creates a new database entry after the asset has been stored in the configured S3 bucket. Assets created in a
+/// breakout room belong to the main room and are tagged with the id of the breakout room.
+///
+/// If a virus scanner is configured, the asset is scanned while being uploaded. Infected assets
+/// are quarantined and reported with an [`AssetInfected`] error. Assets exceeding the maximum scan
+/// size are stored unscanned.
+#[allow(clippy::too_many_arguments)]
 pub async fn save_asset(
     storage: &ObjectStorage,
     db: Arc<Db>,
     room_id: RoomId,
+    breakout_room_id: Option<BreakoutRoomId>,
     namespace: Option<&str>,
     filename: impl Into<String>,
     kind: impl Into<String>,
@@ -31,13 +55,196 @@ pub async fn save_asset(
     let kind = kind.into();
 
     let asset_id = AssetId::from(Uuid::new_v4());
+    let key = asset_key(&asset_id);
+
+    let scan_result = if let Some(scanner) = storage.virus_scanner() {
+        let (tx, rx) = mpsc::channel(SCAN_CHANNEL_CAPACITY);
+
+        // forward every uploaded chunk to the scanner
+        let data = data.then(move |chunk| {
+            let tx = tx.clone();
+
+            async move {
+                if let Ok(bytes) = &chunk {
+                    // a closed channel means the scan has already failed, the upload continues regardless
+                    let _ = tx.send(bytes.clone()).await;
+                }
+
+                chunk
+            }
+        });
+
+        let (upload_result, scan_result) =
+            futures::join!(storage.put(&key, Box::pin(data)), scanner.scan(rx));
+
+        upload_result.context("failed to upload asset file to storage")?;
+
+        Some(scan_result)
+    } else {
+        storage
+            .put(&key, data)
+            .await
+            .context("failed to upload asset file to storage")?;
+
+        None
+    };
+
+    create_scanned_asset_entry(
+        storage,
+        db,
+        room_id,
+        breakout_room_id,
+        asset_id,
+        namespace,
+        filename,
+        kind,
+        scan_result,
+    )
+    .await?;
+
+    Ok(asset_id)
+}
+
+/// Scan an object which has already been written to the object storage
+pub(crate) async fn scan_stored_object(
+    storage: &ObjectStorage,
+    scanner: &VirusScanner,
+    key: &str,
+) -> Result<ScanVerdict> {
+    let mut data = storage.get(key.into()).await?;
+
+    let (tx, rx) = mpsc::channel(SCAN_CHANNEL_CAPACITY);
+
+    let forward = async move {
+        while let Some(bytes) = data.next().await {
+            if tx.send(bytes?).await.is_err() {
+                // the scan has already failed
+                break;
+            }
+        }
 
-    // upload to s3 storage
-    storage
-        .put(&asset_key(&asset_id), data)
-        .await
-        .context("failed to upload asset file to storage")?;
+        Ok::<_, anyhow::Error>(())
+    };
 
+    let (forward_result, scan_result) = futures::join!(forward, scanner.scan(rx));
+
+    forward_result.context("failed to read object for virus scan")?;
+
+    scan_result
+}
+
+/// Create the database entry for an asset according to the result of its virus scan
+///
+/// Infected assets are moved into quarantine, their entry is created and an [`AssetInfected`] error is returned.
+/// Assets which could not be scanned are handled according to the configured [`VirusScanFailurePolicy`], assets which
+/// are too large to be scanned are always stored.
+/// `scan_result` is `None` if no virus scanner is configured.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn create_scanned_asset_entry(
+    storage: &ObjectStorage,
+    db: Arc<Db>,
+    room_id: RoomId,
+    breakout_room_id: Option<BreakoutRoomId>,
+    asset_id: AssetId,
+    namespace: Option<String>,
+    filename: String,
+    kind: String,
+    scan_result: Option<Result<ScanVerdict>>,
+) -> Result<()> {
+    let (scan_status, infection) = match scan_result {
+        None => (AssetScanStatus::NotScanned, None),
+        Some(Ok(ScanVerdict::Clean)) => (AssetScanStatus::Clean, None),
+        Some(Ok(ScanVerdict::TooLarge)) => {
+            log::info!(
+                "Asset {} exceeds the maximum scan size, storing it without scanning",
+                asset_id
+            );
+
+            (AssetScanStatus::TooLarge, None)
+        }
+        Some(Ok(ScanVerdict::Infected(signature))) => {
+            log::warn!(
+                "Asset {} is infected with {}, moving it into quarantine",
+                asset_id,
+                signature
+            );
+
+            storage
+                .rename(&asset_key(&asset_id), &quarantine_key(&asset_id))
+                .await
+                .context("failed to quarantine infected asset")?;
+
+            (AssetScanStatus::Infected, Some(signature))
+        }
+        Some(Err(e)) => {
+            let policy = storage
+                .virus_scanner()
+                .map(VirusScanner::on_failure)
+                .unwrap_or_default();
+
+            match policy {
+                VirusScanFailurePolicy::FailOpen => {
+                    log::warn!(
+                        "Failed to scan asset {}, storing it anyway: {:?}",
+                        asset_id,
+                        e
+                    );
+
+                    (AssetScanStatus::ScanFailed, None)
+                }
+                VirusScanFailurePolicy::FailClosed => {
+                    if let Err(err) = storage.delete(asset_key(&asset_id)).await {
+                        log::error!(
+                            "Failed to delete unscanned asset, leaking asset: {}, {:?}",
+                            &asset_key(&asset_id),
+                            err
+                        );
+                    }
+
+                    return Err(e.context("failed to scan asset for viruses"));
+                }
+            }
+        }
+    };
+
+    create_asset_entry(
+        storage,
+        db,
+        room_id,
+        breakout_room_id,
+        asset_id,
+        namespace,
+        filename,
+        kind,
+        scan_status,
+    )
+    .await?;
+
+    match infection {
+        Some(signature) => Err(AssetInfected {
+            asset_id,
+            signature,
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Create the database entry for an asset which has already been written to the object storage
+///
+/// Removes the object from the storage if the database entry could not be created.
+#[allow(clippy::too_many_arguments)]
+async fn create_asset_entry(
+    storage: &ObjectStorage,
+    db: Arc<Db>,
+    room_id: RoomId,
+    breakout_room_id: Option<BreakoutRoomId>,
+    asset_id: AssetId,
+    namespace: Option<String>,
+    filename: String,
+    kind: String,
+    scan_status: AssetScanStatus,
+) -> Result<()> {
     // create db entry
     let block_result = crate::block(move || {
         let mut db_conn = db.get_conn()?;
@@ -50,39 +257,35 @@ pub async fn save_asset(
             filename,
             kind,
             tenant_id: room.tenant_id,
+            scan_status,
         }
-        .insert_for_room(&mut db_conn, room_id)
+        .insert_for_room(&mut db_conn, room_id, breakout_room_id)
     })
     .await;
 
-    let mut is_error = false;
-
     // check possible errors
-    match block_result {
-        Ok(db_result) => {
-            if let Err(e) = db_result {
-                log::error!("Failed to create new asset in db: {}", e);
-                is_error = true;
-            }
+    let error = match block_result {
+        Ok(Ok(_)) => return Ok(()),
+        Ok(Err(e)) => {
+            log::error!("Failed to create new asset in db: {}", e);
+            anyhow::Error::from(e)
         }
         Err(e) => {
             log::error!("Blocking error while creating asset: {}", e);
-            is_error = true;
+            anyhow::Error::from(e)
         }
-    }
+    };
 
     // rollback s3 storage if errors occurred
-    if is_error {
-        if let Err(err) = storage.delete(asset_key(&asset_id)).await {
-            log::error!(
-                "Failed to rollback s3 asset after database error, leaking asset: {}",
-                &asset_key(&asset_id)
-            );
-            return Err(err);
-        }
+    if let Err(err) = storage.delete(asset_key(&asset_id)).await {
+        log::error!(
+            "Failed to rollback s3 asset after database error, leaking asset: {}",
+            &asset_key(&asset_id)
+        );
+        return Err(err);
     }
 
-    Ok(asset_id)
+    Err(error.context("failed to create asset in database"))
 }
 
 /// Get an asset from the object storage
@@ -90,6 +293,16 @@ pub async fn get_asset(storage: &ObjectStorage, asset_id: &AssetId) -> Result<By
     storage.get(asset_key(asset_id)).await
 }
 
+/// Get a pre-signed URL to download an asset directly from the object storage
+///
+/// Returns the URL and its lifetime
+pub async fn get_asset_url(
+    storage: &ObjectStorage,
+    asset_id: &AssetId,
+) -> Result<(String, Duration)> {
+    storage.presigned_get_url(asset_key(asset_id)).await
+}
+
 /// Delete an asset from the object storage
 pub async fn delete_asset(
     storage: &ObjectStorage,
@@ -104,9 +317,25 @@ pub async fn delete_asset(
     })
     .await??;
 
-    storage.delete(asset_key(&asset_id)).await
+    delete_asset_objects(storage, &asset_id).await
+}
+
+/// Delete the object of an asset from the object storage, including the object of an infected asset in quarantine
+///
+/// Deleting a missing key succeeds, so both keys are deleted regardless of the scan status of the asset.
+pub(crate) async fn delete_asset_objects(
+    storage: &ObjectStorage,
+    asset_id: &AssetId,
+) -> Result<()> {
+    storage.delete(asset_key(asset_id)).await?;
+    storage.delete(quarantine_key(asset_id)).await
 }
 
 pub fn asset_key(asset_id: &AssetId) -> String {
     format!("assets/{asset_id}")
 }
+
+/// Key of an infected asset which has been moved into quarantine
+pub fn quarantine_key(asset_id: &AssetId) -> String {
+    format!("quarantine/{asset_id}")
+}
@@ -4,29 +4,47 @@
 
 use anyhow::{Context, Result};
 use aws_sdk_s3::config::Builder;
+use aws_sdk_s3::error::{AbortMultipartUploadError, CompleteMultipartUploadError};
 use aws_sdk_s3::model::{CompletedMultipartUpload, CompletedPart};
-use aws_sdk_s3::types::ByteStream;
+use aws_sdk_s3::presigning::config::PresigningConfig;
+use aws_sdk_s3::types::{ByteStream, SdkError};
 use aws_sdk_s3::Client;
 use aws_sdk_s3::Credentials as AwsCred;
 use aws_sdk_s3::Endpoint;
 use bytes::Bytes;
-use controller_shared::settings::MinIO;
+use chrono::{DateTime, TimeZone, Utc};
+use controller_shared::settings::{MinIO, VirusScan};
 use futures::Stream;
 use futures::StreamExt;
+use scan::VirusScanner;
+use std::time::Duration;
 
 pub mod assets;
+pub mod scan;
+pub mod uploads;
 
 const CHUNK_SIZE: usize = 5_242_880; // 5 MebiByte (minimum for aws s3)
 
+/// An object listed with [`ObjectStorage::list_objects_page`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StoredObject {
+    pub(crate) key: String,
+    pub(crate) last_modified: Option<DateTime<Utc>>,
+}
+
 pub struct ObjectStorage {
     /// The s3 client
     client: Client,
     /// The configured bucket
     bucket: String,
+    /// Lifetime of pre-signed URLs
+    presigned_url_lifetime: Duration,
+    /// Optional virus scanner for stored assets
+    virus_scanner: Option<VirusScanner>,
 }
 
 impl ObjectStorage {
-    pub async fn new(minio: &MinIO) -> Result<Self> {
+    pub async fn new(minio: &MinIO, virus_scan: Option<&VirusScan>) -> Result<Self> {
         let credentials = AwsCred::new(
             minio.access_key.clone(),
             minio.secret_key.clone(),
@@ -55,9 +73,20 @@ impl ObjectStorage {
 
         log::info!("Using MinIO S3 bucket: {} ", minio.bucket,);
 
+        let virus_scanner = virus_scan
+            .map(VirusScanner::new)
+            .transpose()
+            .context("Invalid virus scan settings")?;
+
+        if let Some(virus_scanner) = &virus_scanner {
+            log::info!("Scanning assets using {}", virus_scanner.address());
+        }
+
         Ok(Self {
             client,
             bucket: minio.bucket.clone(),
+            presigned_url_lifetime: minio.presigned_url_lifetime,
+            virus_scanner,
         })
     }
 
@@ -81,6 +110,8 @@ impl ObjectStorage {
         Self {
             client,
             bucket: "broken".into(),
+            presigned_url_lifetime: Duration::from_secs(0),
+            virus_scanner: None,
         }
     }
 
@@ -251,6 +282,25 @@ impl ObjectStorage {
         Ok(data.body)
     }
 
+    /// Create a pre-signed URL which allows downloading the object without further authentication
+    ///
+    /// Returns the URL and its lifetime
+    pub(crate) async fn presigned_get_url(&self, key: String) -> Result<(String, Duration)> {
+        let presigned = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(
+                PresigningConfig::expires_in(self.presigned_url_lifetime)
+                    .context("invalid presigned url lifetime")?,
+            )
+            .await
+            .context("failed to create presigned url")?;
+
+        Ok((presigned.uri().to_string(), self.presigned_url_lifetime))
+    }
+
     pub(crate) async fn delete(&self, key: String) -> Result<()> {
         self.client
             .delete_object()
@@ -261,6 +311,198 @@ impl ObjectStorage {
 
         Ok(())
     }
+
+    /// List a single page of the objects starting with the given prefix, ordered by their key
+    ///
+    /// Returns the objects and the continuation token of the next page, if there is one.
+    pub(crate) async fn list_objects_page(
+        &self,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<(Vec<StoredObject>, Option<String>)> {
+        let output = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .set_continuation_token(continuation_token)
+            .send()
+            .await
+            .context("failed to list objects")?;
+
+        let objects = output
+            .contents()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|object| {
+                Some(StoredObject {
+                    key: object.key()?.into(),
+                    last_modified: object.last_modified().and_then(|last_modified| {
+                        Utc.timestamp_opt(last_modified.secs(), last_modified.subsec_nanos())
+                            .single()
+                    }),
+                })
+            })
+            .collect();
+
+        let continuation_token = if output.is_truncated() {
+            output.next_continuation_token().map(String::from)
+        } else {
+            None
+        };
+
+        Ok((objects, continuation_token))
+    }
+
+    /// Move an object to another key within the bucket
+    pub(crate) async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .copy_source(format!("{}/{}", self.bucket, from))
+            .key(to)
+            .send()
+            .await
+            .context("failed to copy object")?;
+
+        self.delete(from.into()).await
+    }
+
+    pub(crate) fn virus_scanner(&self) -> Option<&VirusScanner> {
+        self.virus_scanner.as_ref()
+    }
+
+    /// Start a new multipart upload for the given key
+    ///
+    /// Returns the upload id assigned by S3
+    pub(crate) async fn create_multipart_upload(&self, key: &str) -> Result<String> {
+        let output = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .context("failed to create multipart upload")?;
+
+        output
+            .upload_id
+            .context("no upload_id in create_multipart_upload response")
+    }
+
+    /// Upload a single part of a multipart upload
+    ///
+    /// Returns the etag of the uploaded part which is required to complete the upload
+    pub(crate) async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        let part = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .content_length(data.len() as i64)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .context("failed to upload part")?;
+
+        part.e_tag()
+            .map(ToOwned::to_owned)
+            .context("missing etag in upload_part response")
+    }
+
+    /// Complete a multipart upload from the given `(part_number, etag)` pairs
+    ///
+    /// The parts must be sorted by their part number.
+    pub(crate) async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> Result<()> {
+        let parts = parts
+            .into_iter()
+            .map(|(part_number, e_tag)| {
+                CompletedPart::builder()
+                    .e_tag(e_tag)
+                    .part_number(part_number)
+                    .build()
+            })
+            .collect();
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .context("failed to complete multipart upload")?;
+
+        Ok(())
+    }
+
+    /// Abort a multipart upload and discard all already uploaded parts
+    pub(crate) async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        self.client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .context("failed to abort multipart upload")?;
+
+        Ok(())
+    }
+}
+
+/// S3 error codes of multipart upload requests which fail again when retried
+const PERMANENT_MULTIPART_UPLOAD_ERRORS: &[&str] = &[
+    "EntityTooSmall",
+    "InvalidPart",
+    "InvalidPartOrder",
+    "NoSuchUpload",
+];
+
+/// Returns the S3 error code of a failed multipart upload completion or abort
+pub(crate) fn multipart_upload_error_code(error: &anyhow::Error) -> Option<&str> {
+    error.chain().find_map(|cause| {
+        if let Some(SdkError::ServiceError(context)) =
+            cause.downcast_ref::<SdkError<CompleteMultipartUploadError>>()
+        {
+            return context.err().code();
+        }
+
+        if let Some(SdkError::ServiceError(context)) =
+            cause.downcast_ref::<SdkError<AbortMultipartUploadError>>()
+        {
+            return context.err().code();
+        }
+
+        None
+    })
+}
+
+/// Returns true if a multipart upload request failed in a way which cannot be fixed by retrying it
+///
+/// Other failures, e.g. timeouts or unavailable storage, leave the multipart upload intact.
+pub(crate) fn is_permanent_multipart_upload_error(error: &anyhow::Error) -> bool {
+    multipart_upload_error_code(error).map_or(false, |code| {
+        PERMANENT_MULTIPART_UPLOAD_ERRORS.contains(&code)
+    })
 }
 
 struct MultipartUploadContext {
@@ -0,0 +1,386 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Virus scanning of assets
+//!
+//! The data is streamed to either a ClamAV daemon using its `INSTREAM` command or to an ICAP server (RFC 3507) using
+//! `RESPMOD` requests. Data exceeding the configured maximum size is not scanned.
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use controller_shared::settings::{VirusScan, VirusScanBackend, VirusScanFailurePolicy};
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use url::Url;
+
+/// clamd rejects chunks larger than its `StreamMaxLength`, keep them reasonably small
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Port of ICAP servers if the URL does not contain one
+const ICAP_DEFAULT_PORT: u16 = 1344;
+
+/// Maximum size of the headers of an ICAP response
+const MAX_ICAP_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// Outcome of a successful scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Contains the name of the found signature
+    Infected(String),
+    /// The data exceeded the maximum scan size and has not been scanned
+    TooLarge,
+}
+
+enum Backend {
+    Clamd {
+        address: String,
+    },
+    Icap {
+        /// `host:port` of the ICAP server
+        address: String,
+        /// The `icap://` URL of the service
+        url: Url,
+    },
+}
+
+pub(crate) struct VirusScanner {
+    backend: Backend,
+    timeout: Duration,
+    on_failure: VirusScanFailurePolicy,
+    max_size: u64,
+}
+
+impl VirusScanner {
+    pub(crate) fn new(settings: &VirusScan) -> Result<Self> {
+        let backend = match &settings.backend {
+            VirusScanBackend::Clamd { clamd_address } => Backend::Clamd {
+                address: clamd_address.clone(),
+            },
+            VirusScanBackend::Icap { icap_url } => {
+                if icap_url.scheme() != "icap" {
+                    bail!("ICAP URL must use the icap:// scheme");
+                }
+
+                let host = icap_url.host_str().context("ICAP URL has no host")?;
+                let port = icap_url.port().unwrap_or(ICAP_DEFAULT_PORT);
+
+                Backend::Icap {
+                    address: format!("{host}:{port}"),
+                    url: icap_url.clone(),
+                }
+            }
+        };
+
+        Ok(Self {
+            backend,
+            timeout: settings.timeout,
+            on_failure: settings.on_failure,
+            max_size: settings.max_size,
+        })
+    }
+
+    pub(crate) fn on_failure(&self) -> VirusScanFailurePolicy {
+        self.on_failure
+    }
+
+    /// Address of the scanner, for logging
+    pub(crate) fn address(&self) -> &str {
+        match &self.backend {
+            Backend::Clamd { address } => address,
+            Backend::Icap { url, .. } => url.as_str(),
+        }
+    }
+
+    /// Scan all data received from the channel until all senders are dropped
+    ///
+    /// The receiver is drained even if the scan fails, so senders never block on a failed scan.
+    pub(crate) async fn scan(&self, mut data: mpsc::Receiver<Bytes>) -> Result<ScanVerdict> {
+        let result = self.scan_inner(&mut data).await;
+
+        data.close();
+
+        result
+    }
+
+    /// The timeout only applies to the communication with the scanner, waiting for the data to scan is not limited
+    /// since it depends on the upload speed of the client.
+    async fn scan_inner(&self, data: &mut mpsc::Receiver<Bytes>) -> Result<ScanVerdict> {
+        let address = match &self.backend {
+            Backend::Clamd { address } | Backend::Icap { address, .. } => address,
+        };
+
+        let mut stream = self
+            .timeout(async {
+                TcpStream::connect(address)
+                    .await
+                    .context("failed to connect to the virus scanner")
+            })
+            .await?;
+
+        self.timeout(async {
+            stream
+                .write_all(&self.request_head())
+                .await
+                .context("failed to send the scan request")
+        })
+        .await?;
+
+        let mut size = 0;
+
+        while let Some(bytes) = data.recv().await {
+            size += bytes.len() as u64;
+
+            if size > self.max_size {
+                // The scanner rejects streams exceeding its limit, skip the scan instead of failing it
+                return Ok(ScanVerdict::TooLarge);
+            }
+
+            self.timeout(async {
+                for chunk in bytes.chunks(MAX_CHUNK_SIZE) {
+                    self.write_chunk(&mut stream, chunk).await?;
+                }
+
+                Ok::<_, anyhow::Error>(())
+            })
+            .await?;
+        }
+
+        self.timeout(async {
+            self.finish(&mut stream)
+                .await
+                .context("failed to read the scan response")
+        })
+        .await
+    }
+
+    /// The command preceding the data
+    fn request_head(&self) -> Vec<u8> {
+        match &self.backend {
+            Backend::Clamd { .. } => b"zINSTREAM\0".to_vec(),
+            Backend::Icap { url, .. } => {
+                // The data is sent as body of an empty HTTP response
+                let http_head = "HTTP/1.1 200 OK\r\n\r\n";
+
+                format!(
+                    "RESPMOD {url} ICAP/1.0\r\n\
+                     Host: {host}\r\n\
+                     Allow: 204\r\n\
+                     Encapsulated: res-hdr=0, res-body={body_offset}\r\n\
+                     \r\n\
+                     {http_head}",
+                    host = url.host_str().unwrap_or_default(),
+                    body_offset = http_head.len(),
+                )
+                .into_bytes()
+            }
+        }
+    }
+
+    async fn write_chunk(&self, stream: &mut TcpStream, chunk: &[u8]) -> Result<()> {
+        match &self.backend {
+            Backend::Clamd { .. } => {
+                stream.write_u32(chunk.len() as u32).await?;
+                stream.write_all(chunk).await?;
+            }
+            Backend::Icap { .. } => {
+                stream
+                    .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                    .await?;
+                stream.write_all(chunk).await?;
+                stream.write_all(b"\r\n").await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Terminate the stream and read the verdict
+    async fn finish(&self, stream: &mut TcpStream) -> Result<ScanVerdict> {
+        match &self.backend {
+            Backend::Clamd { .. } => {
+                // a zero length chunk terminates the stream
+                stream.write_u32(0).await?;
+                stream.flush().await?;
+
+                let mut response = Vec::new();
+                stream.read_to_end(&mut response).await?;
+
+                parse_response(&response)
+            }
+            Backend::Icap { .. } => {
+                stream.write_all(b"0\r\n\r\n").await?;
+                stream.flush().await?;
+
+                // Only the ICAP headers are of interest, the server may send a modified response after them
+                let mut response = Vec::new();
+                let mut buf = [0; 4096];
+
+                while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+                    if response.len() > MAX_ICAP_RESPONSE_SIZE {
+                        bail!("ICAP response headers are too large");
+                    }
+
+                    let read = stream.read(&mut buf).await?;
+
+                    if read == 0 {
+                        break;
+                    }
+
+                    response.extend_from_slice(&buf[..read]);
+                }
+
+                parse_icap_response(&response)
+            }
+        }
+    }
+
+    async fn timeout<T>(&self, f: impl Future<Output = Result<T>>) -> Result<T> {
+        match tokio::time::timeout(self.timeout, f).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("virus scan timed out")),
+        }
+    }
+}
+
+/// Parse a response of clamd, e.g. `stream: OK` or `stream: Eicar-Signature FOUND`
+fn parse_response(response: &[u8]) -> Result<ScanVerdict> {
+    let response = String::from_utf8_lossy(response);
+    let response = response.trim_end_matches(['\0', '\n']);
+
+    let result = match response.strip_prefix("stream: ") {
+        Some(result) => result,
+        None => bail!("unexpected clamd response: {response}"),
+    };
+
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.into()))
+    } else {
+        bail!("clamd failed to scan the stream: {result}")
+    }
+}
+
+/// Parse the headers of an ICAP response
+///
+/// `204 No Content` means the data is unmodified and therefore clean. Servers which found a virus answer with `200 OK`
+/// and report the signature in the `X-Infection-Found` (`Type=0; Resolution=2; Threat=<name>;`) or `X-Virus-ID`
+/// header.
+fn parse_icap_response(response: &[u8]) -> Result<ScanVerdict> {
+    let response = String::from_utf8_lossy(response);
+    let mut lines = response.split("\r\n");
+
+    let status_line = lines.next().unwrap_or_default();
+    let status = match status_line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [version, status, ..] if version.starts_with("ICAP/") => *status,
+        _ => bail!("unexpected ICAP response: {status_line}"),
+    };
+
+    let headers: Vec<(String, &str)> = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| *value)
+    };
+
+    match status {
+        "204" => Ok(ScanVerdict::Clean),
+        "200" => {
+            if let Some(infection) = header("x-infection-found") {
+                let threat = infection
+                    .split(';')
+                    .filter_map(|field| field.trim().strip_prefix("Threat="))
+                    .next()
+                    .unwrap_or(infection);
+
+                Ok(ScanVerdict::Infected(threat.into()))
+            } else if let Some(virus) = header("x-virus-id") {
+                Ok(ScanVerdict::Infected(virus.into()))
+            } else {
+                // The server returned the unmodified data instead of 204
+                Ok(ScanVerdict::Clean)
+            }
+        }
+        _ => bail!("ICAP server failed to scan the data: {status_line}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_clamd_responses() {
+        assert_eq!(parse_response(b"stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_response(b"stream: Eicar-Signature FOUND\0").unwrap(),
+            ScanVerdict::Infected("Eicar-Signature".into())
+        );
+        assert!(parse_response(b"INSTREAM size limit exceeded. ERROR\0").is_err());
+        assert!(parse_response(b"stream: Can't allocate memory ERROR\0").is_err());
+    }
+
+    #[test]
+    fn parse_icap_responses() {
+        assert_eq!(
+            parse_icap_response(b"ICAP/1.0 204 No Content\r\nISTag: \"abc\"\r\n\r\n").unwrap(),
+            ScanVerdict::Clean
+        );
+        assert_eq!(
+            parse_icap_response(
+                b"ICAP/1.0 200 OK\r\nX-Infection-Found: Type=0; Resolution=2; Threat=Eicar-Test-Signature;\r\n\r\n"
+            )
+            .unwrap(),
+            ScanVerdict::Infected("Eicar-Test-Signature".into())
+        );
+        assert_eq!(
+            parse_icap_response(b"ICAP/1.0 200 OK\r\nX-Virus-ID: Eicar\r\n\r\n").unwrap(),
+            ScanVerdict::Infected("Eicar".into())
+        );
+        assert!(parse_icap_response(b"ICAP/1.0 500 Server Error\r\n\r\n").is_err());
+        assert!(parse_icap_response(b"HTTP/1.1 200 OK\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn icap_request_head() {
+        let scanner = VirusScanner::new(&VirusScan {
+            backend: VirusScanBackend::Icap {
+                icap_url: "icap://scanner/avscan".parse().unwrap(),
+            },
+            timeout: Duration::from_secs(1),
+            on_failure: VirusScanFailurePolicy::FailClosed,
+            max_size: 1024,
+        })
+        .unwrap();
+
+        assert_eq!(scanner.address(), "icap://scanner/avscan");
+        assert_eq!(
+            String::from_utf8(scanner.request_head()).unwrap(),
+            "RESPMOD icap://scanner/avscan ICAP/1.0\r\n\
+             Host: scanner\r\n\
+             Allow: 204\r\n\
+             Encapsulated: res-hdr=0, res-body=19\r\n\
+             \r\n\
+             HTTP/1.1 200 OK\r\n\r\n"
+        );
+        assert!(matches!(
+            scanner.backend,
+            Backend::Icap { ref address, .. } if address == "scanner:1344"
+        ));
+    }
+}
@@ -0,0 +1,564 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Resumable multipart uploads of assets
+//!
+//! An upload is backed by a S3 multipart upload. The upload session and the parts which have already
+//! been uploaded are tracked in redis, which allows a client to query the state of an interrupted upload
+//! and continue where it left off.
+//!
+//! The upload session is identified by the id of the asset that is created when the upload is completed.
+//!
+//! Multipart uploads which are neither completed nor aborted by the client are aborted by the
+//! [`abort_expired_uploads_task`] once their session expired, so S3 does not keep their parts forever.
+use super::assets::{asset_key, create_scanned_asset_entry, scan_stored_object};
+use super::{
+    is_permanent_multipart_upload_error, multipart_upload_error_code, ObjectStorage, CHUNK_SIZE,
+};
+use crate::redis_wrapper::RedisConnection;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use database::Db;
+use redis::AsyncCommands;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::{AssetId, RoomId, Timestamp, UserId};
+use uuid::Uuid;
+
+/// The minimum size of every part except the last one (enforced by S3)
+pub const MIN_PART_SIZE: usize = CHUNK_SIZE;
+
+/// The maximum size of a single part accepted by the controller
+pub const MAX_PART_SIZE: usize = 64 * 1024 * 1024;
+
+/// The maximum part number (enforced by S3)
+pub const MAX_PART_NUMBER: i32 = 10_000;
+
+/// Time in seconds an upload session stays valid after it was created or a part was uploaded
+const UPLOAD_SESSION_EXPIRY: usize = 24 * 60 * 60;
+
+/// Interval in which expired uploads are aborted
+const ABORT_EXPIRED_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Sorted set of all pending uploads, scored by the unix timestamp their session expires at
+///
+/// Outlives the upload sessions, so the multipart uploads of expired sessions can still be aborted.
+const PENDING_UPLOADS: &str = "k3k-controller:asset-uploads";
+
+/// Typed redis key for an [`UploadSession`]
+#[derive(Debug, Copy, Clone, ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-controller:asset-upload={asset_id}")]
+struct UploadSessionKey {
+    asset_id: AssetId,
+}
+
+/// Hash of all uploaded parts of an upload, mapping the part number to the etag of the part
+#[derive(Debug, Copy, Clone, ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-controller:asset-upload={asset_id}:parts")]
+struct UploadPartsKey {
+    asset_id: AssetId,
+}
+
+/// Data stored behind the [`UploadSessionKey`]
+#[derive(Debug, Clone, Serialize, Deserialize, ToRedisArgs, FromRedisValue)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct UploadSession {
+    pub asset_id: AssetId,
+    pub room_id: RoomId,
+    pub created_by: UserId,
+    pub namespace: Option<String>,
+    pub filename: String,
+    pub kind: String,
+    pub expires_at: Timestamp,
+    /// The id of the S3 multipart upload, never exposed to the client
+    s3_upload_id: String,
+}
+
+/// Member of the [`PENDING_UPLOADS`] sorted set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToRedisArgs, FromRedisValue)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+struct PendingUpload {
+    asset_id: AssetId,
+    s3_upload_id: String,
+}
+
+impl From<&UploadSession> for PendingUpload {
+    fn from(session: &UploadSession) -> Self {
+        Self {
+            asset_id: session.asset_id,
+            s3_upload_id: session.s3_upload_id.clone(),
+        }
+    }
+}
+
+/// A part that has been uploaded to an [`UploadSession`]
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadedPart {
+    pub part_number: i32,
+    pub e_tag: String,
+}
+
+fn expires_at() -> Timestamp {
+    Timestamp::from(chrono::Utc::now() + chrono::Duration::seconds(UPLOAD_SESSION_EXPIRY as i64))
+}
+
+/// Start a new resumable upload for an asset of the given room
+pub async fn start_upload(
+    storage: &ObjectStorage,
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    created_by: UserId,
+    namespace: Option<String>,
+    filename: String,
+    kind: String,
+) -> Result<UploadSession> {
+    let asset_id = AssetId::from(Uuid::new_v4());
+
+    let s3_upload_id = storage
+        .create_multipart_upload(&asset_key(&asset_id))
+        .await?;
+
+    let session = UploadSession {
+        asset_id,
+        room_id,
+        created_by,
+        namespace,
+        filename,
+        kind,
+        expires_at: expires_at(),
+        s3_upload_id,
+    };
+
+    let res: Result<()> = redis::pipe()
+        .atomic()
+        .set_ex(
+            UploadSessionKey { asset_id },
+            &session,
+            UPLOAD_SESSION_EXPIRY,
+        )
+        .ignore()
+        .zadd(
+            PENDING_UPLOADS,
+            PendingUpload::from(&session),
+            session.expires_at.timestamp(),
+        )
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .context("Failed to SET upload session");
+
+    if let Err(e) = res {
+        // do not leak the multipart upload
+        if let Err(e) = storage
+            .abort_multipart_upload(&asset_key(&asset_id), &session.s3_upload_id)
+            .await
+        {
+            log::error!(
+                "Failed to abort multipart upload after redis error, {:?}",
+                e
+            );
+        }
+
+        return Err(e);
+    }
+
+    Ok(session)
+}
+
+/// Get the upload session for the given asset id
+///
+/// Returns `None` if the session does not exist, has expired, belongs to another room or has been
+/// started by another user.
+pub async fn get_upload(
+    redis_conn: &mut RedisConnection,
+    room_id: RoomId,
+    user_id: UserId,
+    asset_id: AssetId,
+) -> Result<Option<UploadSession>> {
+    let session: Option<UploadSession> = redis_conn
+        .get(UploadSessionKey { asset_id })
+        .await
+        .context("Failed to GET upload session")?;
+
+    Ok(session.filter(|session| session.room_id == room_id && session.created_by == user_id))
+}
+
+/// Get all parts which have already been uploaded for the given session, sorted by their part number
+pub async fn get_uploaded_parts(
+    redis_conn: &mut RedisConnection,
+    session: &UploadSession,
+) -> Result<Vec<UploadedPart>> {
+    let parts: HashMap<i32, String> = redis_conn
+        .hgetall(UploadPartsKey {
+            asset_id: session.asset_id,
+        })
+        .await
+        .context("Failed to HGETALL uploaded parts")?;
+
+    let mut parts: Vec<UploadedPart> = parts
+        .into_iter()
+        .map(|(part_number, e_tag)| UploadedPart { part_number, e_tag })
+        .collect();
+
+    parts.sort_by_key(|part| part.part_number);
+
+    Ok(parts)
+}
+
+/// Upload a part of the upload session
+///
+/// Uploading a part with an already existing part number replaces the previous part.
+/// Every upload refreshes the expiry of the session.
+pub async fn upload_part(
+    storage: &ObjectStorage,
+    redis_conn: &mut RedisConnection,
+    session: &mut UploadSession,
+    part_number: i32,
+    data: Bytes,
+) -> Result<UploadedPart> {
+    let e_tag = storage
+        .upload_part(
+            &asset_key(&session.asset_id),
+            &session.s3_upload_id,
+            part_number,
+            data,
+        )
+        .await?;
+
+    session.expires_at = expires_at();
+
+    redis::pipe()
+        .atomic()
+        .hset(
+            UploadPartsKey {
+                asset_id: session.asset_id,
+            },
+            part_number,
+            &e_tag,
+        )
+        .ignore()
+        .expire(
+            UploadPartsKey {
+                asset_id: session.asset_id,
+            },
+            UPLOAD_SESSION_EXPIRY,
+        )
+        .ignore()
+        .set_ex(
+            UploadSessionKey {
+                asset_id: session.asset_id,
+            },
+            &*session,
+            UPLOAD_SESSION_EXPIRY,
+        )
+        .ignore()
+        .zadd(
+            PENDING_UPLOADS,
+            PendingUpload::from(&*session),
+            session.expires_at.timestamp(),
+        )
+        .ignore()
+        .query_async::<_, ()>(redis_conn)
+        .await
+        .context("Failed to store uploaded part")?;
+
+    Ok(UploadedPart { part_number, e_tag })
+}
+
+/// Complete the upload session and create the asset from all uploaded parts
+///
+/// The assembled asset is passed through the virus scanner if one is configured. If S3 fails to assemble
+/// the parts, the multipart upload is aborted and the session is removed.
+pub async fn complete_upload(
+    storage: &ObjectStorage,
+    db: Arc<Db>,
+    redis_conn: &mut RedisConnection,
+    session: UploadSession,
+) -> Result<AssetId> {
+    let parts = get_uploaded_parts(redis_conn, &session)
+        .await?
+        .into_iter()
+        .map(|part| (part.part_number, part.e_tag))
+        .collect();
+
+    let res = storage
+        .complete_multipart_upload(&asset_key(&session.asset_id), &session.s3_upload_id, parts)
+        .await;
+
+    if let Err(e) = res {
+        // Keep the session on transient failures, so the client can retry the completion
+        if is_permanent_multipart_upload_error(&e) {
+            if let Err(e) = abort_upload(storage, redis_conn, session).await {
+                log::error!(
+                    "Failed to abort multipart upload after failed completion, {:?}",
+                    e
+                );
+            }
+        }
+
+        return Err(e);
+    }
+
+    delete_upload_session(redis_conn, &session).await?;
+
+    let scan_result = match storage.virus_scanner() {
+        Some(scanner) => {
+            Some(scan_stored_object(storage, scanner, &asset_key(&session.asset_id)).await)
+        }
+        None => None,
+    };
+
+    create_scanned_asset_entry(
+        storage,
+        db,
+        session.room_id,
+        None,
+        session.asset_id,
+        session.namespace,
+        session.filename,
+        session.kind,
+        scan_result,
+    )
+    .await?;
+
+    Ok(session.asset_id)
+}
+
+/// Abort the upload session and discard all uploaded parts
+///
+/// The session is kept if the multipart upload could not be aborted, unless it does not exist anymore.
+pub async fn abort_upload(
+    storage: &ObjectStorage,
+    redis_conn: &mut RedisConnection,
+    session: UploadSession,
+) -> Result<()> {
+    abort_multipart_upload(storage, &session.asset_id, &session.s3_upload_id).await?;
+
+    delete_upload_session(redis_conn, &session).await
+}
+
+/// Abort the multipart upload of an asset, succeeds if the multipart upload is already gone
+async fn abort_multipart_upload(
+    storage: &ObjectStorage,
+    asset_id: &AssetId,
+    s3_upload_id: &str,
+) -> Result<()> {
+    match storage
+        .abort_multipart_upload(&asset_key(asset_id), s3_upload_id)
+        .await
+    {
+        Err(e) if multipart_upload_error_code(&e) == Some("NoSuchUpload") => Ok(()),
+        res => res,
+    }
+}
+
+async fn delete_upload_session(
+    redis_conn: &mut RedisConnection,
+    session: &UploadSession,
+) -> Result<()> {
+    let asset_id = session.asset_id;
+
+    redis::pipe()
+        .atomic()
+        .del(UploadSessionKey { asset_id })
+        .ignore()
+        .del(UploadPartsKey { asset_id })
+        .ignore()
+        .zrem(PENDING_UPLOADS, PendingUpload::from(session))
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .context("Failed to DEL upload session")
+}
+
+/// Get all pending uploads whose session expired at the given point in time
+///
+/// Uploads whose session has been refreshed in the meantime are skipped.
+async fn get_expired_uploads(
+    redis_conn: &mut RedisConnection,
+    now: Timestamp,
+) -> Result<Vec<PendingUpload>> {
+    let candidates: Vec<PendingUpload> = redis_conn
+        .zrangebyscore(PENDING_UPLOADS, "-inf", now.timestamp())
+        .await
+        .context("Failed to ZRANGEBYSCORE pending uploads")?;
+
+    let mut expired = Vec::with_capacity(candidates.len());
+
+    for upload in candidates {
+        let session_exists: bool = redis_conn
+            .exists(UploadSessionKey {
+                asset_id: upload.asset_id,
+            })
+            .await
+            .context("Failed to check upload session")?;
+
+        if !session_exists {
+            expired.push(upload);
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Abort the multipart uploads of all expired upload sessions
+async fn abort_expired_uploads(
+    storage: &ObjectStorage,
+    redis_conn: &mut RedisConnection,
+) -> Result<()> {
+    for upload in get_expired_uploads(redis_conn, Timestamp::now()).await? {
+        if let Err(e) =
+            abort_multipart_upload(storage, &upload.asset_id, &upload.s3_upload_id).await
+        {
+            // Keep the pending upload to retry on the next run, S3 would keep the parts forever otherwise
+            log::warn!(
+                "Failed to abort expired multipart upload of asset {}, {:?}",
+                upload.asset_id,
+                e
+            );
+            continue;
+        }
+
+        redis::pipe()
+            .atomic()
+            .del(UploadPartsKey {
+                asset_id: upload.asset_id,
+            })
+            .ignore()
+            .zrem(PENDING_UPLOADS, &upload)
+            .ignore()
+            .query_async(redis_conn)
+            .await
+            .context("Failed to remove expired upload")?;
+    }
+
+    Ok(())
+}
+
+/// Periodically abort the multipart uploads of expired upload sessions
+///
+/// Runs until the shutdown signal is received.
+pub(crate) async fn abort_expired_uploads_task(
+    storage: Arc<ObjectStorage>,
+    mut redis_conn: RedisConnection,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(ABORT_EXPIRED_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(e) = abort_expired_uploads(&storage, &mut redis_conn).await {
+                    log::error!("Failed to abort expired uploads, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Expired uploads task received shutdown signal");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redis_wrapper::testing::setup;
+    use serial_test::serial;
+
+    const ROOM: RoomId = RoomId::from(Uuid::nil());
+    const USER: UserId = UserId::from(Uuid::nil());
+
+    fn session(asset_id: AssetId) -> UploadSession {
+        UploadSession {
+            asset_id,
+            room_id: ROOM,
+            created_by: USER,
+            namespace: None,
+            filename: "recording.mp4".into(),
+            kind: "recording".into(),
+            expires_at: expires_at(),
+            s3_upload_id: format!("upload-{asset_id}"),
+        }
+    }
+
+    async fn store_session(redis_conn: &mut RedisConnection, session: &UploadSession) {
+        redis_conn
+            .set_ex::<_, _, ()>(
+                UploadSessionKey {
+                    asset_id: session.asset_id,
+                },
+                session,
+                UPLOAD_SESSION_EXPIRY,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn upload_is_only_visible_to_its_creator() {
+        let mut redis_conn = setup().await;
+
+        let asset_id = AssetId::from(Uuid::from_u128(1));
+        store_session(&mut redis_conn, &session(asset_id)).await;
+
+        let own = get_upload(&mut redis_conn, ROOM, USER, asset_id)
+            .await
+            .unwrap();
+        assert_eq!(own.map(|session| session.asset_id), Some(asset_id));
+
+        let other_user = UserId::from(Uuid::from_u128(2));
+        let foreign = get_upload(&mut redis_conn, ROOM, other_user, asset_id)
+            .await
+            .unwrap();
+        assert!(foreign.is_none());
+
+        let other_room = RoomId::from(Uuid::from_u128(3));
+        let foreign = get_upload(&mut redis_conn, other_room, USER, asset_id)
+            .await
+            .unwrap();
+        assert!(foreign.is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn expired_uploads_without_session() {
+        let mut redis_conn = setup().await;
+
+        let expired = session(AssetId::from(Uuid::from_u128(1)));
+        let refreshed = session(AssetId::from(Uuid::from_u128(2)));
+        let running = session(AssetId::from(Uuid::from_u128(3)));
+
+        // the session of `refreshed` was extended after the score has been read
+        store_session(&mut redis_conn, &refreshed).await;
+
+        let past = Timestamp::now().timestamp() - 10;
+        let future = Timestamp::now().timestamp() + 10;
+
+        redis_conn
+            .zadd_multiple::<_, _, _, ()>(
+                PENDING_UPLOADS,
+                &[
+                    (past, PendingUpload::from(&expired)),
+                    (past, PendingUpload::from(&refreshed)),
+                    (future, PendingUpload::from(&running)),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let uploads = get_expired_uploads(&mut redis_conn, Timestamp::now())
+            .await
+            .unwrap();
+
+        assert_eq!(uploads, vec![PendingUpload::from(&expired)]);
+    }
+}
@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Sockets passed by systemd socket activation
+//!
+//! Implements the protocol of `sd_listen_fds(3)`: systemd passes the sockets as the file descriptors starting at 3 and
+//! sets `LISTEN_PID` to the pid of the service and `LISTEN_FDS` to the number of sockets.
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::sys::socket::{getsockname, AddressFamily, SockaddrStorage};
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+
+/// First file descriptor passed by systemd
+const LISTEN_FDS_START: RawFd = 3;
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Take the listening sockets passed by systemd
+///
+/// The environment variables are removed, so the sockets are not passed on to child processes.
+pub fn listen_fds() -> Result<Vec<Listener>> {
+    let pid = std::env::var("LISTEN_PID").context(
+        "LISTEN_PID is not set, the controller was not started by systemd socket activation",
+    )?;
+    let fds = std::env::var("LISTEN_FDS").context("LISTEN_FDS is not set")?;
+
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    let pid: u32 = pid.parse().context("LISTEN_PID is not a valid pid")?;
+    if pid != std::process::id() {
+        bail!("The sockets passed by systemd are meant for process {pid}");
+    }
+
+    let fds: RawFd = fds
+        .parse()
+        .context("LISTEN_FDS is not a valid number of sockets")?;
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + fds)
+        .map(listener)
+        .collect()
+}
+
+fn listener(fd: RawFd) -> Result<Listener> {
+    fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+        .with_context(|| format!("Failed to set close-on-exec flag of socket {fd}"))?;
+
+    let address = getsockname::<SockaddrStorage>(fd)
+        .with_context(|| format!("Failed to get address of socket {fd}"))?;
+
+    // Safety: systemd passes the ownership of the sockets to this process, each of them is only converted once
+    let listener = match address.family() {
+        Some(AddressFamily::Inet | AddressFamily::Inet6) => {
+            Listener::Tcp(unsafe { TcpListener::from_raw_fd(fd) })
+        }
+        Some(AddressFamily::Unix) => Listener::Unix(unsafe { UnixListener::from_raw_fd(fd) }),
+        family => bail!("Socket {fd} has the unsupported address family {family:?}"),
+    };
+
+    Ok(listener)
+}
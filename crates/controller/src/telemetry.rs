@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Background task deleting the WebRTC statistics of the clients after the configured retention
+use crate::settings::SharedSettings;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use database::Db;
+use db_storage::webrtc_stats::WebRtcStats;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Interval in which expired statistics are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically delete the statistics which are older than the retention
+///
+/// Runs until the shutdown signal is received, does nothing while telemetry is not configured.
+pub(crate) async fn purge_task(
+    settings: SharedSettings,
+    db: Arc<Db>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let retention = match &settings.load().telemetry {
+                    Some(telemetry) => telemetry.retention,
+                    None => continue,
+                };
+
+                if let Err(e) = purge(db.clone(), retention).await {
+                    log::error!("Failed to purge WebRTC statistics, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Telemetry purge task received shutdown signal");
+                return;
+            }
+        }
+    }
+}
+
+async fn purge(db: Arc<Db>, retention: Duration) -> Result<()> {
+    let received_before = Utc::now()
+        - chrono::Duration::from_std(retention).context("invalid telemetry retention")?;
+
+    let deleted = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        WebRtcStats::delete_received_before(&mut conn, received_before)
+    })
+    .await??;
+
+    log::debug!("Purged {} WebRTC statistics snapshots", deleted);
+
+    Ok(())
+}
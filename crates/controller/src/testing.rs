@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Support for running the signaling endpoint in end-to-end tests
+//!
+//! The [`SignalingTestServer`] serves only the signaling websocket endpoint and therefore does not require an OIDC
+//! provider or an object storage. Postgres, redis and RabbitMQ are still required as the signaling runner relies on
+//! them.
+use crate::api;
+use crate::api::signaling::ticket::start_or_continue_signaling_session;
+use crate::metrics::CombinedMetrics;
+use crate::prelude::*;
+use crate::settings::Settings;
+use crate::storage::ObjectStorage;
+use actix_web::dev::ServerHandle;
+use actix_web::web::Data;
+use actix_web::{App, HttpServer};
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use database::Db;
+use kustos::Authz;
+use lapin_pool::RabbitMqPool;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing_actix_web::TracingLogger;
+use types::core::{RoomId, TicketToken, UserId};
+
+/// HTTP server serving the signaling endpoint on a random local port
+pub struct SignalingTestServer {
+    address: SocketAddr,
+    handle: ServerHandle,
+    redis: RedisConnection,
+    shutdown: broadcast::Sender<()>,
+}
+
+impl SignalingTestServer {
+    /// Start the server with the given signaling modules
+    ///
+    /// The object storage is a placeholder which errors on every access.
+    pub async fn start(
+        settings: Settings,
+        db: Arc<Db>,
+        authz: Authz,
+        redis: RedisConnection,
+        rabbitmq_pool: Arc<RabbitMqPool>,
+        shutdown: broadcast::Sender<()>,
+        signaling: SignalingModules,
+    ) -> Result<Self> {
+        let settings = Data::new(ArcSwap::from_pointee(settings));
+        let signaling = Data::new(signaling);
+        let signaling_metrics = Data::from(CombinedMetrics::init().signaling);
+        let storage = Data::new(ObjectStorage::broken());
+        let notifications = Data::new(NotificationService::new(
+            settings.clone().into_inner(),
+            db.clone(),
+        ));
+        let db = Data::from(db);
+        let authz = Data::new(authz);
+        let rabbitmq_pool = Data::from(rabbitmq_pool);
+
+        let http_server = {
+            let redis = Data::new(redis.clone());
+            let shutdown = Data::new(shutdown.clone());
+
+            HttpServer::new(move || {
+                App::new()
+                    .wrap(TracingLogger::default())
+                    .app_data(settings.clone())
+                    .app_data(db.clone())
+                    .app_data(storage.clone())
+                    .app_data(notifications.clone())
+                    .app_data(authz.clone())
+                    .app_data(redis.clone())
+                    .app_data(shutdown.clone())
+                    .app_data(rabbitmq_pool.clone())
+                    .app_data(signaling.clone())
+                    .app_data(SignalingProtocols::data())
+                    .app_data(signaling_metrics.clone())
+                    .service(api::signaling::ws_service)
+            })
+        };
+
+        let http_server = http_server
+            .workers(1)
+            .bind((Ipv4Addr::LOCALHOST, 0))
+            .context("Failed to bind signaling test server")?;
+
+        let address = http_server
+            .addrs()
+            .first()
+            .copied()
+            .context("Signaling test server is not bound to any address")?;
+
+        let http_server = http_server.disable_signals().run();
+        let handle = http_server.handle();
+
+        actix_rt::spawn(http_server);
+
+        Ok(Self {
+            address,
+            handle,
+            redis,
+            shutdown,
+        })
+    }
+
+    /// Returns the address the server is listening on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the websocket URL of the signaling endpoint
+    pub fn signaling_url(&self) -> String {
+        format!("ws://{}/signaling", self.address)
+    }
+
+    /// Create a ticket for the given participant to connect to the signaling endpoint
+    pub async fn create_ticket(
+        &self,
+        participant: Participant<UserId>,
+        room: RoomId,
+    ) -> Result<TicketToken> {
+        let mut redis = self.redis.clone();
+
+        let (ticket, _) =
+            start_or_continue_signaling_session(&mut redis, participant, room, None, None, None)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create signaling ticket, {}", e))?;
+
+        Ok(ticket)
+    }
+
+    /// Send the shutdown signal to all runners and stop the server
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+
+        self.handle.stop(true).await;
+    }
+}
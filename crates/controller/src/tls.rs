@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! TLS setup of the HTTP server
+//!
+//! The certificate and private key files are checked for changes in the configured interval and when the reload
+//! signal is received. Changed files are loaded without restarting the server, so certificate renewals do not require
+//! any downtime. Established connections keep using the certificate they were created with. A private key which does
+//! not belong to the certificate is rejected, e.g. when only one of the files has been replaced yet.
+use crate::settings::HttpTls;
+use anyhow::{anyhow, bail, Context, Result};
+use arc_swap::ArcSwap;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{CertifiedKey, SigningKey};
+use rustls::{Certificate, PrivateKey, ServerConfig, SignatureScheme};
+use rustls_pemfile::Item;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Signature schemes used to check that the private key belongs to the certificate
+const KEY_CHECK_SCHEMES: &[(SignatureScheme, &webpki::SignatureAlgorithm)] = &[
+    (SignatureScheme::ED25519, &webpki::ED25519),
+    (
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        &webpki::ECDSA_P256_SHA256,
+    ),
+    (
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        &webpki::ECDSA_P384_SHA384,
+    ),
+    (
+        SignatureScheme::RSA_PSS_SHA256,
+        &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    ),
+];
+
+/// Serves the most recently loaded certificate
+pub struct CertificateResolver {
+    certified_key: ArcSwap<CertifiedKey>,
+}
+
+impl CertificateResolver {
+    pub fn new(tls: &HttpTls) -> Result<Self> {
+        Ok(Self {
+            certified_key: ArcSwap::from_pointee(load_certified_key(tls)?),
+        })
+    }
+
+    fn reload(&self, tls: &HttpTls) -> Result<()> {
+        self.certified_key.store(Arc::new(load_certified_key(tls)?));
+
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key.load_full())
+    }
+}
+
+/// Create the rustls config of the HTTP server
+///
+/// The ALPN protocols are not set here, actix-http prepends `h2` and `http/1.1` to them when binding the server, so
+/// HTTP/2 is negotiated with all clients supporting it.
+pub fn server_config(resolver: Arc<CertificateResolver>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver)
+}
+
+/// Reload the certificate whenever the files changed or the reload signal was received
+///
+/// Failing to load the files keeps the current certificate in use, they are loaded again with the next change.
+pub async fn reload_task(
+    resolver: Arc<CertificateResolver>,
+    tls: HttpTls,
+    mut reload: broadcast::Receiver<()>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = interval(tls.reload_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut loaded = modified(&tls);
+
+    loop {
+        let force = tokio::select! {
+            _ = interval.tick() => false,
+            res = reload.recv() => match res {
+                Ok(()) | Err(RecvError::Lagged(_)) => true,
+                Err(RecvError::Closed) => return,
+            },
+            _ = shutdown.recv() => {
+                log::debug!("TLS reload task received shutdown signal");
+                return;
+            }
+        };
+
+        let current = modified(&tls);
+
+        if !force && current == loaded {
+            continue;
+        }
+
+        match resolver.reload(&tls) {
+            Ok(()) => {
+                log::info!("Reloaded TLS certificate from {:?}", tls.certificate);
+                loaded = current;
+            }
+            Err(e) => log::warn!(
+                "Failed to reload TLS certificate, keeping the current one, {:?}",
+                e
+            ),
+        }
+    }
+}
+
+/// Modification times of the certificate and private key, `None` if either cannot be read
+fn modified(tls: &HttpTls) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+
+    Some((
+        modified(&tls.certificate).ok()?,
+        modified(&tls.private_key).ok()?,
+    ))
+}
+
+fn load_certified_key(tls: &HttpTls) -> Result<CertifiedKey> {
+    let cert_file = File::open(&tls.certificate)
+        .with_context(|| format!("Failed to open certificate file {:?}", &tls.certificate))?;
+    let certs: Vec<Certificate> = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .map_err(|_| anyhow!("Invalid certificate"))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    if certs.is_empty() {
+        bail!("No certificate found in {:?}", &tls.certificate);
+    }
+
+    let private_key_file = File::open(&tls.private_key)
+        .with_context(|| format!("Failed to open private key file {:?}", &tls.private_key))?;
+    let key = rustls_pemfile::read_all(&mut BufReader::new(private_key_file))
+        .map_err(|_| anyhow!("Invalid private key"))?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .with_context(|| format!("No private key found in {:?}", &tls.private_key))?;
+
+    let key = rustls::sign::any_supported_type(&key)
+        .map_err(|_| anyhow!("Unsupported private key type"))?;
+
+    check_key_matches(&certs[0], key.as_ref())?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// Check that the private key belongs to the certificate by verifying a signature created with it
+fn check_key_matches(cert: &Certificate, key: &dyn SigningKey) -> Result<()> {
+    const MESSAGE: &[u8] = b"k3k-controller TLS key check";
+
+    let schemes: Vec<SignatureScheme> = KEY_CHECK_SCHEMES
+        .iter()
+        .map(|(scheme, _)| *scheme)
+        .collect();
+
+    let signer = key
+        .choose_scheme(&schemes)
+        .context("Unsupported private key type")?;
+
+    let algorithm = KEY_CHECK_SCHEMES
+        .iter()
+        .find(|(scheme, _)| *scheme == signer.scheme())
+        .map(|(_, algorithm)| *algorithm)
+        .context("Unsupported private key type")?;
+
+    let signature = signer
+        .sign(MESSAGE)
+        .map_err(|e| anyhow!("Failed to sign with the private key, {}", e))?;
+
+    webpki::EndEntityCert::try_from(cert.0.as_slice())
+        .map_err(|e| anyhow!("Invalid certificate, {:?}", e))?
+        .verify_signature(algorithm, MESSAGE, &signature)
+        .map_err(|_| anyhow!("The private key does not belong to the certificate"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn tls(cert: &str, key: &str) -> HttpTls {
+        let dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls"));
+
+        HttpTls {
+            certificate: dir.join(cert),
+            private_key: dir.join(key),
+            reload_interval: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn matching_key_is_loaded() {
+        assert!(load_certified_key(&tls("cert1.pem", "key1.pem")).is_ok());
+        assert!(load_certified_key(&tls("cert2.pem", "key2.pem")).is_ok());
+    }
+
+    #[test]
+    fn mismatching_key_is_rejected() {
+        assert!(load_certified_key(&tls("cert1.pem", "key2.pem")).is_err());
+        assert!(CertificateResolver::new(&tls("cert2.pem", "key1.pem")).is_err());
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_certificate() {
+        let resolver = CertificateResolver::new(&tls("cert1.pem", "key1.pem")).unwrap();
+        let loaded = resolver.certified_key.load_full();
+
+        assert!(resolver.reload(&tls("cert2.pem", "key1.pem")).is_err());
+        assert!(Arc::ptr_eq(&loaded, &resolver.certified_key.load_full()));
+
+        resolver.reload(&tls("cert2.pem", "key2.pem")).unwrap();
+        assert!(!Arc::ptr_eq(&loaded, &resolver.certified_key.load_full()));
+    }
+}
@@ -0,0 +1,166 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Background task purging rooms and events from the trash
+//!
+//! Rooms and events are soft deleted and stay in the trash until the configured grace period has passed.
+//! Purging removes the database rows, the assets of purged rooms from the object storage and all
+//! explicit kustos policies of the purged resources.
+use crate::api::internal::rooms::associated_room_resource_ids;
+use crate::api::v1::events::associated_resource_ids as associated_event_resource_ids;
+use crate::settings::SharedSettings;
+use crate::storage::assets::delete_asset_objects;
+use crate::storage::ObjectStorage;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use database::Db;
+use db_storage::assets::Asset;
+use db_storage::events::Event;
+use db_storage::legal_votes::LegalVote;
+use db_storage::rooms::Room;
+use db_storage::sip_configs::SipConfig;
+use diesel::Connection;
+use kustos::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use types::core::{EventId, RoomId};
+
+/// Interval in which the trash is checked for rooms and events to purge
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically purge all rooms and events whose grace period in the trash has passed
+///
+/// Runs until the shutdown signal is received.
+pub(crate) async fn purge_task(
+    settings: SharedSettings,
+    db: Arc<Db>,
+    storage: Arc<ObjectStorage>,
+    authz: Authz,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let grace_period = settings.load().trash.grace_period;
+
+                if let Err(e) = purge(&db, &storage, &authz, grace_period).await {
+                    log::error!("Failed to purge the trash, {:?}", e);
+                }
+            }
+            _ = shutdown.recv() => {
+                log::debug!("Trash purge task received shutdown signal");
+                return;
+            }
+        }
+    }
+}
+
+async fn purge(
+    db: &Arc<Db>,
+    storage: &ObjectStorage,
+    authz: &Authz,
+    grace_period: Duration,
+) -> Result<()> {
+    let deleted_before = Utc::now()
+        - chrono::Duration::from_std(grace_period).context("invalid trash grace period")?;
+
+    let db_clone = db.clone();
+    let room_ids = crate::block(move || {
+        let mut conn = db_clone.get_conn()?;
+
+        Room::get_all_ids_deleted_before(&mut conn, deleted_before)
+    })
+    .await??;
+
+    for room_id in room_ids {
+        purge_room(db.clone(), storage, authz, room_id)
+            .await
+            .with_context(|| format!("Failed to purge room {room_id}"))?;
+    }
+
+    // Events of purged rooms are already gone at this point
+    let db_clone = db.clone();
+    let event_ids = crate::block(move || {
+        let mut conn = db_clone.get_conn()?;
+
+        Event::get_all_ids_deleted_before(&mut conn, deleted_before)
+    })
+    .await??;
+
+    for event_id in event_ids {
+        purge_event(db.clone(), authz, event_id)
+            .await
+            .with_context(|| format!("Failed to purge event {event_id}"))?;
+    }
+
+    Ok(())
+}
+
+/// Purge a room including its events, legal votes, sip config and assets
+pub(crate) async fn purge_room(
+    db: Arc<Db>,
+    storage: &ObjectStorage,
+    authz: &Authz,
+    room_id: RoomId,
+) -> Result<()> {
+    let (events, legal_votes, assets) = crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        conn.transaction(|conn| -> database::Result<_> {
+            let events = Event::get_all_ids_for_room(conn, room_id)?;
+            let legal_votes = LegalVote::get_all_ids_for_room(conn, room_id)?;
+            let assets = Asset::get_all_ids_for_room(conn, room_id)?;
+
+            LegalVote::delete_by_room(conn, room_id)?;
+            Event::delete_all_for_room(conn, room_id)?;
+            SipConfig::delete_by_room(conn, room_id)?;
+            Asset::delete_by_ids(conn, &assets)?;
+            Room::delete_by_id(conn, room_id)?;
+
+            Ok((events, legal_votes, assets))
+        })
+    })
+    .await??;
+
+    for asset_id in assets {
+        delete_asset_objects(storage, &asset_id).await?;
+    }
+
+    let resources: Vec<_> = events
+        .iter()
+        .flat_map(|&event_id| associated_event_resource_ids(event_id))
+        .chain(
+            legal_votes
+                .iter()
+                .map(|legal_vote| legal_vote.resource_id()),
+        )
+        .chain(associated_room_resource_ids(room_id))
+        .collect();
+
+    authz.remove_explicit_resources(resources).await?;
+
+    log::debug!("Purged room {} from the trash", room_id);
+
+    Ok(())
+}
+
+pub(crate) async fn purge_event(db: Arc<Db>, authz: &Authz, event_id: EventId) -> Result<()> {
+    crate::block(move || {
+        let mut conn = db.get_conn()?;
+
+        Event::delete_by_id(&mut conn, event_id)
+    })
+    .await??;
+
+    authz
+        .remove_explicit_resources(associated_event_resource_ids(event_id))
+        .await?;
+
+    log::debug!("Purged event {} from the trash", event_id);
+
+    Ok(())
+}
@@ -5,31 +5,68 @@
 use crate::metrics::{DatabaseMetrics, MetricsConnection};
 use crate::{DatabaseError, DbConnection};
 use controller_shared::settings;
-use diesel::r2d2::ConnectionManager;
-use diesel::{r2d2, PgConnection};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sql_types::{Double, Nullable};
+use diesel::{r2d2, sql_query, PgConnection, QueryResult, QueryableByName, RunQueryDsl};
 use opentelemetry::Context;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
+/// How long the result of a replication lag check is reused, unusable replicas are skipped for this duration
+const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time to wait for a replica connection before trying the next replica
+const REPLICA_CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Db container that uses a connection pool to hand out connections
 ///
 /// Uses an r2d2 connection pool to manage multiple established connections.
+/// Read-only queries can be routed to replicas of the database using [`Db::get_read_conn`].
 pub struct Db {
     metrics: Option<Arc<DatabaseMetrics>>,
     pool: DbPool,
+    replicas: Vec<Replica>,
+    max_replica_lag: Duration,
+    next_replica: AtomicUsize,
 }
 
 impl Db {
     /// Creates a new Db instance from the specified database settings.
     #[tracing::instrument(skip(db_settings))]
     pub fn connect(db_settings: &settings::Database) -> crate::Result<Self> {
-        Self::connect_url(
+        let mut db = Self::connect_url(
             &db_settings.url,
             db_settings.max_connections,
             Some(db_settings.min_idle_connections),
-        )
+        )?;
+
+        db.max_replica_lag = db_settings.max_replica_lag;
+
+        for replica_url in &db_settings.replica_urls {
+            let manager = ConnectionManager::<PgConnection>::new(replica_url);
+
+            // Do not wait for the replica to become available, an unavailable replica is skipped when
+            // handing out connections
+            let pool = diesel::r2d2::Pool::builder()
+                .max_size(db_settings.max_connections)
+                .min_idle(Some(db_settings.min_idle_connections))
+                .connection_timeout(Duration::from_secs(10))
+                .build_unchecked(manager);
+
+            db.replicas.push(Replica {
+                pool,
+                last_check: Mutex::new(None),
+            });
+        }
+
+        if !db.replicas.is_empty() {
+            log::info!("Using {} database replica(s)", db.replicas.len());
+        }
+
+        Ok(db)
     }
 
     /// Creates a new Db instance from the specified database url.
@@ -49,6 +86,9 @@ impl Db {
         Ok(Self {
             metrics: None,
             pool,
+            replicas: vec![],
+            max_replica_lag: Duration::ZERO,
+            next_replica: AtomicUsize::new(0),
         })
     }
 
@@ -94,4 +134,136 @@ impl Db {
             }
         }
     }
+
+    /// Returns an established connection for read-only queries
+    ///
+    /// The configured replicas are used in a round-robin fashion. Replicas which are unavailable or lag behind
+    /// the primary by more than the configured maximum are skipped until they are checked again. If no replica is usable (or none are
+    /// configured) a connection to the primary is returned.
+    ///
+    /// Only use this for queries which tolerate slightly outdated data.
+    pub fn get_read_conn(&self) -> crate::Result<DbConnection> {
+        if !self.replicas.is_empty() {
+            let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+
+            for i in 0..self.replicas.len() {
+                let replica = &self.replicas[(start + i) % self.replicas.len()];
+
+                if let Some(conn) = replica.get_conn(self.max_replica_lag) {
+                    return Ok(MetricsConnection {
+                        metrics: self.metrics.clone(),
+                        conn,
+                    });
+                }
+            }
+
+            log::warn!("No database replica is usable, falling back to the primary database");
+        }
+
+        self.get_conn()
+    }
+}
+
+/// A read-only replica of the primary database
+struct Replica {
+    pool: DbPool,
+    /// Time of the last check of the replica and whether it was usable
+    ///
+    /// Covers both the replication lag and the availability of the replica, so an unreachable replica does
+    /// not delay every read by the connection timeout.
+    last_check: Mutex<Option<(Instant, bool)>>,
+}
+
+impl Replica {
+    /// Returns a connection if the replica is reachable and its replication lag is acceptable
+    fn get_conn(
+        &self,
+        max_lag: Duration,
+    ) -> Option<PooledConnection<ConnectionManager<PgConnection>>> {
+        let last_check = *self
+            .last_check
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let recently_checked = match last_check {
+            Some((checked_at, usable)) if checked_at.elapsed() < LAG_CHECK_INTERVAL => {
+                if !usable {
+                    return None;
+                }
+
+                true
+            }
+            _ => false,
+        };
+
+        let mut conn = match self.pool.get_timeout(REPLICA_CONNECTION_TIMEOUT) {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Unable to get connection to database replica, {}", e);
+                self.set_last_check(false);
+                return None;
+            }
+        };
+
+        if recently_checked {
+            return Some(conn);
+        }
+
+        let usable = match replication_lag(&mut conn) {
+            Ok(Some(lag)) => {
+                if lag > max_lag {
+                    log::warn!("Database replica is lagging behind by {:?}", lag);
+                }
+
+                lag <= max_lag
+            }
+            Ok(None) => {
+                log::warn!("Database replica is not replicating from the primary database");
+                false
+            }
+            Err(e) => {
+                log::warn!("Failed to check replication lag of database replica, {}", e);
+                false
+            }
+        };
+
+        self.set_last_check(usable);
+
+        usable.then_some(conn)
+    }
+
+    fn set_last_check(&self, usable: bool) {
+        *self
+            .last_check
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some((Instant::now(), usable));
+    }
+}
+
+#[derive(QueryableByName)]
+struct ReplicationLag {
+    #[diesel(sql_type = Nullable<Double>)]
+    lag: Option<f64>,
+}
+
+/// Query the replication lag of a replica
+///
+/// A replica which is streaming from the primary and has replayed all received WAL is considered up to date,
+/// even if the last replayed transaction is old. A replica which lost the connection to the primary cannot know
+/// whether it is up to date, its lag is the age of the last replayed transaction. Returns `None` if the replica
+/// has never replayed a transaction while not streaming.
+fn replication_lag(conn: &mut PgConnection) -> QueryResult<Option<Duration>> {
+    let replication_lag: ReplicationLag = sql_query(
+        "SELECT (CASE \
+         WHEN NOT pg_is_in_recovery() THEN 0 \
+         WHEN NOT EXISTS (SELECT 1 FROM pg_stat_wal_receiver WHERE status = 'streaming') \
+         THEN EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) \
+         WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+         ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0) END)::float8 AS lag",
+    )
+    .get_result(conn)?;
+
+    Ok(replication_lag
+        .lag
+        .map(|lag| Duration::from_secs_f64(lag.max(0.0))))
 }
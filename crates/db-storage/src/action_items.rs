@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Decisions and action items of meetings
+//!
+//! Collected by the participants of a meeting in the action items module and persisted when the room is destroyed.
+use crate::schema::action_items;
+use chrono::{DateTime, NaiveDate, Utc};
+use database::{DbConnection, Result};
+use diesel::deserialize::FromSql;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use types::core::{BreakoutRoomId, RoomId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    ActionItemId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+sql_enum!(
+    #[derive(PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    ActionItemKind,
+    "action_item_kind",
+    ActionItemKindType,
+    {
+        Decision = b"decision",
+        ActionItem = b"action_item",
+    }
+);
+
+/// Diesel action_items model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = action_items)]
+pub struct ActionItem {
+    pub id: ActionItemId,
+    pub room_id: RoomId,
+    pub breakout_room_id: Option<BreakoutRoomId>,
+    pub kind: ActionItemKind,
+    pub text: String,
+    /// Free text, the assignee is not required to be a participant of the meeting
+    pub assignee: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ActionItem {
+    /// Get the items of the room and its breakout rooms which were created in the given period
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_created_between(
+        conn: &mut DbConnection,
+        room_id: RoomId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Self>> {
+        let query = action_items::table
+            .filter(action_items::room_id.eq(room_id))
+            .filter(action_items::created_at.between(from, to))
+            .order_by(action_items::created_at.asc());
+
+        let items = query.load(conn)?;
+
+        Ok(items)
+    }
+}
+
+/// Action item insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = action_items)]
+pub struct NewActionItem {
+    /// The id assigned by the signaling module
+    pub id: ActionItemId,
+    pub room_id: RoomId,
+    pub breakout_room_id: Option<BreakoutRoomId>,
+    pub kind: ActionItemKind,
+    pub text: String,
+    pub assignee: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Insert a batch of items
+///
+/// Items which have already been persisted are skipped.
+#[tracing::instrument(err, skip_all)]
+pub fn insert_batch(conn: &mut DbConnection, items: &[NewActionItem]) -> Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    diesel::insert_into(action_items::table)
+        .values(items)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
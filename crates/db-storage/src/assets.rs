@@ -8,6 +8,10 @@ use chrono::{DateTime, Utc};
 use database::DbConnection;
 use database::Paginate;
 use database::Result;
+use diesel::deserialize::FromSql;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
 use diesel::BoolExpressionMethods;
 use diesel::Connection;
 use diesel::Insertable;
@@ -15,7 +19,24 @@ use diesel::JoinOnDsl;
 use diesel::RunQueryDsl;
 use diesel::{ExpressionMethods, QueryDsl};
 use diesel::{Identifiable, Queryable};
-use types::core::{AssetId, RoomId, TenantId};
+use serde::Serialize;
+use std::io::Write;
+use types::core::{AssetId, BreakoutRoomId, RoomId, TenantId};
+
+sql_enum!(
+    #[derive(PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "snake_case")]
+    AssetScanStatus,
+    "asset_scan_status",
+    AssetScanStatusType,
+    {
+        NotScanned = b"not_scanned",
+        Clean = b"clean",
+        Infected = b"infected",
+        ScanFailed = b"scan_failed",
+        TooLarge = b"too_large",
+    }
+);
 
 /// Diesel resource struct
 #[derive(Debug, Clone, Queryable, Identifiable)]
@@ -27,6 +48,7 @@ pub struct Asset {
     pub kind: String,
     pub filename: String,
     pub tenant_id: TenantId,
+    pub scan_status: AssetScanStatus,
 }
 
 impl Asset {
@@ -59,17 +81,18 @@ impl Asset {
 
         Ok(assets)
     }
+    /// Get the assets of the room alongside the breakout room they have been created in
     #[tracing::instrument(err, skip_all)]
     pub fn get_all_for_room_paginated(
         conn: &mut DbConnection,
         room_id: RoomId,
         limit: i64,
         page: i64,
-    ) -> Result<(Vec<Self>, i64)> {
+    ) -> Result<(Vec<(Self, Option<BreakoutRoomId>)>, i64)> {
         let query = assets::table
             .inner_join(room_assets::table.on(room_assets::asset_id.eq(assets::id)))
             .filter(room_assets::room_id.eq(room_id))
-            .select(assets::all_columns)
+            .select((assets::all_columns, room_assets::breakout_room_id))
             .paginate_by(limit, page);
 
         let resources_with_total = query.load_and_count(conn)?;
@@ -95,6 +118,47 @@ impl Asset {
         Ok(resources_with_total)
     }
 
+    /// Get all assets of the given rooms alongside the id of the room they belong to
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_rooms(
+        conn: &mut DbConnection,
+        room_ids: &[RoomId],
+    ) -> Result<Vec<(Self, RoomId)>> {
+        let query = assets::table
+            .inner_join(room_assets::table.on(room_assets::asset_id.eq(assets::id)))
+            .filter(room_assets::room_id.eq_any(room_ids))
+            .select((assets::all_columns, room_assets::room_id))
+            .order_by(assets::created_at.asc());
+
+        let assets = query.load(conn)?;
+
+        Ok(assets)
+    }
+
+    /// Get up to `limit` assets ordered by their id, starting after the given id
+    ///
+    /// Used to iterate over all assets without loading them at once.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_batch_after(
+        conn: &mut DbConnection,
+        after: Option<AssetId>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let mut query = assets::table
+            .select(assets::all_columns)
+            .order_by(assets::id.asc())
+            .limit(limit)
+            .into_boxed();
+
+        if let Some(after) = after {
+            query = query.filter(assets::id.gt(after));
+        }
+
+        let assets = query.load(conn)?;
+
+        Ok(assets)
+    }
+
     #[tracing::instrument(err, skip_all)]
     pub fn get_all_paginated(
         conn: &mut DbConnection,
@@ -145,6 +209,7 @@ impl Asset {
 pub struct RoomAsset {
     pub room_id: RoomId,
     pub asset_id: AssetId,
+    pub breakout_room_id: Option<BreakoutRoomId>,
 }
 
 #[derive(Debug, Insertable)]
@@ -155,17 +220,27 @@ pub struct NewAsset {
     pub kind: String,
     pub filename: String,
     pub tenant_id: TenantId,
+    pub scan_status: AssetScanStatus,
 }
 
 impl NewAsset {
+    /// Insert the asset and associate it with the room
+    ///
+    /// Assets created inside a breakout room belong to the main room, the breakout room is recorded alongside.
     #[tracing::instrument(err, skip_all)]
-    pub fn insert_for_room(self, conn: &mut DbConnection, room_id: RoomId) -> Result<Asset> {
+    pub fn insert_for_room(
+        self,
+        conn: &mut DbConnection,
+        room_id: RoomId,
+        breakout_room_id: Option<BreakoutRoomId>,
+    ) -> Result<Asset> {
         conn.transaction(|conn| {
             let asset: Asset = self.insert_into(assets::table).get_result(conn)?;
 
             RoomAsset {
                 room_id,
                 asset_id: asset.id,
+                breakout_room_id,
             }
             .insert_into(room_assets::table)
             .execute(conn)?;
@@ -0,0 +1,111 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Branding of tenants and rooms
+//!
+//! The branding of a tenant applies to all rooms of the tenant. Each value of the branding of a room overrides the
+//! value of the tenant.
+use crate::schema::{room_brandings, tenant_brandings};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{AssetId, RoomId, TenantId};
+
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = tenant_brandings, primary_key(tenant_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct TenantBranding {
+    pub tenant_id: TenantId,
+    /// URL of the logo, usually served by the web server of the frontend
+    pub logo_url: Option<String>,
+    /// Color in the `#rrggbb` format
+    pub primary_color: Option<String>,
+    /// Color in the `#rrggbb` format
+    pub secondary_color: Option<String>,
+}
+
+impl TenantBranding {
+    /// Get the branding of the tenant, returns None if the tenant has no branding
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, tenant_id: TenantId) -> Result<Option<TenantBranding>> {
+        let query = tenant_brandings::table.filter(tenant_brandings::tenant_id.eq(tenant_id));
+
+        let branding = query.get_result(conn).optional()?;
+
+        Ok(branding)
+    }
+
+    /// Insert or replace the branding of the tenant
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<TenantBranding> {
+        let query = diesel::insert_into(tenant_brandings::table)
+            .values(&self)
+            .on_conflict(tenant_brandings::tenant_id)
+            .do_update()
+            .set(&self);
+
+        let branding = query.get_result(conn)?;
+
+        Ok(branding)
+    }
+
+    /// Delete the branding of the tenant
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_tenant(conn: &mut DbConnection, tenant_id: TenantId) -> Result<()> {
+        diesel::delete(tenant_brandings::table)
+            .filter(tenant_brandings::tenant_id.eq(tenant_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = room_brandings, primary_key(room_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct RoomBranding {
+    pub room_id: RoomId,
+    /// Asset of the room containing the logo
+    pub logo_asset_id: Option<AssetId>,
+    /// Color in the `#rrggbb` format
+    pub primary_color: Option<String>,
+    /// Color in the `#rrggbb` format
+    pub secondary_color: Option<String>,
+}
+
+impl RoomBranding {
+    /// Get the branding of the room, returns None if the room has no branding
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, room_id: RoomId) -> Result<Option<RoomBranding>> {
+        let query = room_brandings::table.filter(room_brandings::room_id.eq(room_id));
+
+        let branding = query.get_result(conn).optional()?;
+
+        Ok(branding)
+    }
+
+    /// Insert or replace the branding of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<RoomBranding> {
+        let query = diesel::insert_into(room_brandings::table)
+            .values(&self)
+            .on_conflict(room_brandings::room_id)
+            .do_update()
+            .set(&self);
+
+        let branding = query.get_result(conn)?;
+
+        Ok(branding)
+    }
+
+    /// Delete the branding of the room, the branding of the tenant applies again
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_room(conn: &mut DbConnection, room_id: RoomId) -> Result<()> {
+        diesel::delete(room_brandings::table)
+            .filter(room_brandings::room_id.eq(room_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
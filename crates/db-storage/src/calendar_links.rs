@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! External calendars linked by users, which their events are synchronized to
+//!
+//! The OAuth tokens of a link are stored encrypted, the encryption is up to the caller.
+use crate::events::Event;
+use crate::schema::{calendar_link_events, calendar_links, events};
+use crate::users::User;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::deserialize::FromSql;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use types::core::{EventId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)] CalendarLinkId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+sql_enum!(
+    #[derive(PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    CalendarProvider,
+    "calendar_provider",
+    CalendarProviderType,
+    {
+        Google = b"google",
+        Microsoft = b"microsoft",
+    }
+);
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[diesel(table_name = calendar_links)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+pub struct CalendarLink {
+    pub id: CalendarLinkId,
+    pub user_id: UserId,
+    pub provider: CalendarProvider,
+    /// Encrypted OAuth access token
+    pub access_token: Vec<u8>,
+    /// Encrypted OAuth refresh token
+    pub refresh_token: Vec<u8>,
+    /// Expiry of the access token
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last successful synchronization
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+impl CalendarLink {
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all(conn: &mut DbConnection) -> Result<Vec<Self>> {
+        let query = calendar_links::table.order_by(calendar_links::created_at.asc());
+
+        let links = query.load(conn)?;
+
+        Ok(links)
+    }
+
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Self>> {
+        let query = calendar_links::table
+            .filter(calendar_links::user_id.eq(user_id))
+            .order_by(calendar_links::created_at.asc());
+
+        let links = query.load(conn)?;
+
+        Ok(links)
+    }
+
+    /// Deletes the link of the user to the provider
+    ///
+    /// Returns true if something was deleted
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        provider: CalendarProvider,
+    ) -> Result<bool> {
+        let lines_changes = diesel::delete(calendar_links::table)
+            .filter(calendar_links::user_id.eq(user_id))
+            .filter(calendar_links::provider.eq(provider))
+            .execute(conn)?;
+
+        Ok(lines_changes > 0)
+    }
+
+    /// Deletes all links of the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(calendar_links::table)
+            .filter(calendar_links::user_id.eq(user_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = calendar_links)]
+pub struct NewCalendarLink {
+    pub user_id: UserId,
+    pub provider: CalendarProvider,
+    pub access_token: Vec<u8>,
+    pub refresh_token: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewCalendarLink {
+    /// Insert the link, replacing the tokens of an existing link of the user to the provider
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<CalendarLink> {
+        let query = self
+            .insert_into(calendar_links::table)
+            .on_conflict((calendar_links::user_id, calendar_links::provider))
+            .do_update()
+            .set((
+                calendar_links::access_token.eq(excluded(calendar_links::access_token)),
+                calendar_links::refresh_token.eq(excluded(calendar_links::refresh_token)),
+                calendar_links::expires_at.eq(excluded(calendar_links::expires_at)),
+            ));
+
+        let link = query.get_result(conn)?;
+
+        Ok(link)
+    }
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = calendar_links)]
+pub struct UpdateCalendarLink {
+    pub access_token: Option<Vec<u8>>,
+    pub refresh_token: Option<Vec<u8>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+impl UpdateCalendarLink {
+    #[tracing::instrument(err, skip_all)]
+    pub fn apply(self, conn: &mut DbConnection, link_id: CalendarLinkId) -> Result<CalendarLink> {
+        let query = diesel::update(calendar_links::table)
+            .filter(calendar_links::id.eq(link_id))
+            .set(self)
+            .returning(calendar_links::all_columns);
+
+        let link = query.get_result(conn)?;
+
+        Ok(link)
+    }
+}
+
+/// Event which has been synchronized to a linked calendar
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[diesel(table_name = calendar_link_events)]
+#[diesel(primary_key(link_id, event_id))]
+#[diesel(belongs_to(CalendarLink, foreign_key = link_id))]
+#[diesel(belongs_to(Event))]
+pub struct CalendarLinkEvent {
+    pub link_id: CalendarLinkId,
+    pub event_id: EventId,
+    /// Id of the event in the linked calendar
+    pub external_id: String,
+    /// Latest change of the event or its invites when it was synchronized
+    pub synced_at: DateTime<Utc>,
+}
+
+impl CalendarLinkEvent {
+    /// Get all synchronized events of the link together with the event, including events in the trash
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_link(
+        conn: &mut DbConnection,
+        link_id: CalendarLinkId,
+    ) -> Result<Vec<(Self, Event)>> {
+        let query = calendar_link_events::table
+            .inner_join(events::table)
+            .filter(calendar_link_events::link_id.eq(link_id))
+            .select((calendar_link_events::all_columns, events::all_columns));
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete(
+        conn: &mut DbConnection,
+        link_id: CalendarLinkId,
+        event_id: EventId,
+    ) -> Result<()> {
+        diesel::delete(calendar_link_events::table)
+            .filter(calendar_link_events::link_id.eq(link_id))
+            .filter(calendar_link_events::event_id.eq(event_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = calendar_link_events)]
+pub struct NewCalendarLinkEvent {
+    pub link_id: CalendarLinkId,
+    pub event_id: EventId,
+    pub external_id: String,
+    pub synced_at: DateTime<Utc>,
+}
+
+impl NewCalendarLinkEvent {
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<CalendarLinkEvent> {
+        let query = self
+            .insert_into(calendar_link_events::table)
+            .on_conflict((
+                calendar_link_events::link_id,
+                calendar_link_events::event_id,
+            ))
+            .do_update()
+            .set((
+                calendar_link_events::external_id.eq(excluded(calendar_link_events::external_id)),
+                calendar_link_events::synced_at.eq(excluded(calendar_link_events::synced_at)),
+            ));
+
+        let event = query.get_result(conn)?;
+
+        Ok(event)
+    }
+}
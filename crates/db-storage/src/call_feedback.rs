@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Feedback of participants on the quality of their calls
+//!
+//! Participants can rate the call when leaving a room. The feedback is stored together with the context of the call,
+//! like the number of participants and the problems of the media connections, to track the perceived quality over time.
+use crate::schema::call_feedback;
+use chrono::{DateTime, NaiveDate, Utc};
+use database::{DbConnection, Result};
+use diesel::sql_types::{BigInt, Date, SmallInt, Text, Timestamptz};
+use diesel::{
+    ExpressionMethods, Identifiable, Insertable, QueryDsl, Queryable, QueryableByName, RunQueryDsl,
+};
+use types::core::{RoomId, TenantId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    CallFeedbackId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Diesel call_feedback model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = call_feedback)]
+pub struct CallFeedback {
+    pub id: CallFeedbackId,
+    pub room_id: RoomId,
+    pub tenant_id: TenantId,
+    /// Unset for guests and participants which are no longer registered
+    pub user_id: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+    /// Star rating between 1 and 5
+    pub rating: i16,
+    /// Tags of the issues the participant experienced, e.g. `audio`
+    pub issues: Vec<String>,
+    pub comment: Option<String>,
+    /// Number of participants inside the room when the feedback was submitted
+    pub participants: i32,
+    /// Time the participant spent inside the room
+    pub duration_secs: i64,
+    /// Number of media connections of the participant which went down
+    pub webrtc_down_count: i32,
+    /// Number of times the media server detected lost packets on the media connections of the participant
+    pub slow_link_count: i32,
+}
+
+/// Call feedback of a single day (UTC), aggregated over all submissions of that day
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct FeedbackDay {
+    #[diesel(sql_type = Date)]
+    pub date: NaiveDate,
+    #[diesel(sql_type = BigInt)]
+    pub submissions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub rating_sum: i64,
+    #[diesel(sql_type = BigInt)]
+    pub webrtc_down_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub slow_link_count: i64,
+}
+
+/// Number of submissions with a rating
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct RatingCount {
+    #[diesel(sql_type = SmallInt)]
+    pub rating: i16,
+    #[diesel(sql_type = BigInt)]
+    pub submissions: i64,
+}
+
+/// Number of submissions which reported an issue
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct IssueCount {
+    #[diesel(sql_type = Text)]
+    pub issue: String,
+    #[diesel(sql_type = BigInt)]
+    pub submissions: i64,
+}
+
+impl CallFeedback {
+    /// Get all feedback submitted by the user, the oldest feedback comes first
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Self>> {
+        let query = call_feedback::table
+            .filter(call_feedback::user_id.eq(user_id))
+            .order_by(call_feedback::created_at.asc());
+
+        let feedback = query.load(conn)?;
+
+        Ok(feedback)
+    }
+
+    /// Detach all feedback of the user from the user and delete the comments
+    ///
+    /// The ratings and the context of the calls are kept for the aggregated statistics.
+    #[tracing::instrument(err, skip_all)]
+    pub fn anonymize_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::update(call_feedback::table)
+            .filter(call_feedback::user_id.eq(user_id))
+            .set((
+                call_feedback::user_id.eq(None::<UserId>),
+                call_feedback::comment.eq(None::<String>),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Get the feedback submitted in the rooms of the tenant inside the given time range, aggregated per day
+    ///
+    /// Days without submissions are omitted, the oldest day comes first.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_days_created_between(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<FeedbackDay>> {
+        let query = diesel::sql_query(
+            "SELECT (created_at AT TIME ZONE 'UTC')::DATE AS date, \
+                COUNT(*) AS submissions, \
+                SUM(rating)::BIGINT AS rating_sum, \
+                SUM(webrtc_down_count)::BIGINT AS webrtc_down_count, \
+                SUM(slow_link_count)::BIGINT AS slow_link_count \
+            FROM call_feedback \
+            WHERE tenant_id = $1 AND created_at >= $2 AND created_at < $3 \
+            GROUP BY date \
+            ORDER BY date",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to);
+
+        let days = query.load(conn)?;
+
+        Ok(days)
+    }
+
+    /// Get the number of submissions in the rooms of the tenant inside the given time range, per rating
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_rating_counts_created_between(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<RatingCount>> {
+        let query = diesel::sql_query(
+            "SELECT rating, COUNT(*) AS submissions \
+            FROM call_feedback \
+            WHERE tenant_id = $1 AND created_at >= $2 AND created_at < $3 \
+            GROUP BY rating \
+            ORDER BY rating",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to);
+
+        let ratings = query.load(conn)?;
+
+        Ok(ratings)
+    }
+
+    /// Get the number of submissions in the rooms of the tenant inside the given time range, per reported issue
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_issue_counts_created_between(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<IssueCount>> {
+        let query = diesel::sql_query(
+            "SELECT issue, COUNT(*) AS submissions \
+            FROM call_feedback, UNNEST(issues) AS issue \
+            WHERE tenant_id = $1 AND created_at >= $2 AND created_at < $3 \
+            GROUP BY issue \
+            ORDER BY issue",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to);
+
+        let issues = query.load(conn)?;
+
+        Ok(issues)
+    }
+}
+
+/// Call feedback insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = call_feedback)]
+pub struct NewCallFeedback {
+    pub room_id: RoomId,
+    pub tenant_id: TenantId,
+    pub user_id: Option<UserId>,
+    pub rating: i16,
+    pub issues: Vec<String>,
+    pub comment: Option<String>,
+    pub participants: i32,
+    pub duration_secs: i64,
+    pub webrtc_down_count: i32,
+    pub slow_link_count: i32,
+}
+
+impl NewCallFeedback {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<CallFeedback> {
+        let query = self.insert_into(call_feedback::table);
+
+        let feedback = query.get_result(conn)?;
+
+        Ok(feedback)
+    }
+}
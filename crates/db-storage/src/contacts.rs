@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Users marked as favorite contacts by other users
+use crate::schema::{contact_favorites, users};
+use crate::users::User;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::UserId;
+
+#[derive(Debug, Clone, Queryable)]
+pub struct ContactFavorite {
+    pub user_id: UserId,
+    pub contact_id: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ContactFavorite {
+    /// Get all favorite contacts of the user in the order they have been added
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<User>> {
+        let query = contact_favorites::table
+            .inner_join(users::table.on(users::id.eq(contact_favorites::contact_id)))
+            .filter(contact_favorites::user_id.eq(user_id))
+            .order_by(contact_favorites::created_at.asc())
+            .then_order_by(users::id)
+            .select(users::all_columns);
+
+        let contacts = query.load(conn)?;
+
+        Ok(contacts)
+    }
+
+    /// Replace the favorite contacts of the user
+    ///
+    /// Contacts which were favorites before keep their position, new favorites are appended in the given order.
+    #[tracing::instrument(err, skip_all)]
+    pub fn replace_for_user(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        contact_ids: &[UserId],
+    ) -> Result<()> {
+        conn.transaction(|conn| {
+            diesel::delete(contact_favorites::table)
+                .filter(contact_favorites::user_id.eq(user_id))
+                .filter(contact_favorites::contact_id.ne_all(contact_ids))
+                .execute(conn)?;
+
+            // Every row gets its own creation time, rows inserted with the same time would lose their order
+            let now = Utc::now();
+
+            let new_favorites: Vec<NewContactFavorite> = contact_ids
+                .iter()
+                .enumerate()
+                .map(|(position, &contact_id)| NewContactFavorite {
+                    user_id,
+                    contact_id,
+                    created_at: now + chrono::Duration::microseconds(position as i64),
+                })
+                .collect();
+
+            if !new_favorites.is_empty() {
+                diesel::insert_into(contact_favorites::table)
+                    .values(new_favorites)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Delete the favorite contacts of the user and remove the user from the favorites of all other users
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(contact_favorites::table)
+            .filter(
+                contact_favorites::user_id
+                    .eq(user_id)
+                    .or(contact_favorites::contact_id.eq(user_id)),
+            )
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = contact_favorites)]
+pub struct NewContactFavorite {
+    pub user_id: UserId,
+    pub contact_id: UserId,
+    pub created_at: DateTime<Utc>,
+}
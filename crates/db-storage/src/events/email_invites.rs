@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use super::{Event, NewEventInvite};
+use super::{Event, EventInviteStatus, NewEventInvite};
 use crate::schema::{event_email_invites, event_invites, events};
 use crate::users::User;
 use chrono::{DateTime, Utc};
@@ -49,6 +49,10 @@ pub struct EventEmailInvite {
     pub email: String,
     pub created_by: UserId,
     pub created_at: DateTime<Utc>,
+    pub status: EventInviteStatus,
+    /// Comment of the invitee to the response, shown to the organizer
+    pub comment: Option<String>,
+    pub responded_at: Option<DateTime<Utc>>,
 }
 
 impl EventEmailInvite {
@@ -121,4 +125,47 @@ impl EventEmailInvite {
 
         Ok(invites)
     }
+
+    /// Delete all email invites sent to the given email address
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_email(conn: &mut DbConnection, email: &str) -> Result<()> {
+        diesel::delete(event_email_invites::table)
+            .filter(event_email_invites::email.eq(email))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = event_email_invites)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpdateEventEmailInvite {
+    pub status: EventInviteStatus,
+    pub comment: Option<String>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+impl UpdateEventEmailInvite {
+    /// Apply the update to the invite sent to `email`
+    #[tracing::instrument(err, skip_all)]
+    pub fn apply(
+        self,
+        conn: &mut DbConnection,
+        event_id: EventId,
+        email: &str,
+    ) -> Result<EventEmailInvite> {
+        let query = diesel::update(event_email_invites::table)
+            .filter(
+                event_email_invites::event_id
+                    .eq(event_id)
+                    .and(event_email_invites::email.eq(email)),
+            )
+            .set(self)
+            .returning(event_email_invites::all_columns);
+
+        let event_email_invite = query.get_result(conn)?;
+
+        Ok(event_email_invite)
+    }
 }
@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Matrix rooms the global chat of an event's room is bridged to
+use super::Event;
+use crate::schema::{event_matrix_bridges, events};
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{EventId, RoomId, UserId};
+
+#[derive(Debug, Clone, Associations, Identifiable, Queryable)]
+#[diesel(table_name = event_matrix_bridges)]
+#[diesel(primary_key(event_id))]
+#[diesel(belongs_to(Event))]
+pub struct EventMatrixBridge {
+    pub event_id: EventId,
+    /// Id of the Matrix room, e.g. `!abcdef:matrix.org`
+    pub matrix_room_id: String,
+    pub updated_by: UserId,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EventMatrixBridge {
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, event_id: EventId) -> Result<Option<Self>> {
+        let bridge = event_matrix_bridges::table
+            .filter(event_matrix_bridges::event_id.eq(event_id))
+            .first(conn)
+            .optional()?;
+
+        Ok(bridge)
+    }
+
+    /// Get all bridges of events which are not deleted, together with the room of the event
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_with_room(conn: &mut DbConnection) -> Result<Vec<(Self, RoomId)>> {
+        let query = event_matrix_bridges::table
+            .inner_join(events::table)
+            .filter(events::deleted_at.is_null())
+            .select((event_matrix_bridges::all_columns, events::room))
+            .order_by(event_matrix_bridges::event_id);
+
+        let bridges = query.load(conn)?;
+
+        Ok(bridges)
+    }
+
+    /// Deletes the bridge of the given event
+    ///
+    /// Returns true if something was deleted
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_event_id(conn: &mut DbConnection, event_id: EventId) -> Result<bool> {
+        let lines_changes = diesel::delete(event_matrix_bridges::table)
+            .filter(event_matrix_bridges::event_id.eq(event_id))
+            .execute(conn)?;
+
+        Ok(lines_changes > 0)
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = event_matrix_bridges)]
+pub struct NewEventMatrixBridge {
+    pub event_id: EventId,
+    pub matrix_room_id: String,
+    pub updated_by: UserId,
+}
+
+impl NewEventMatrixBridge {
+    /// Insert the bridge, replacing an existing bridge of the event
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<EventMatrixBridge> {
+        let query = self
+            .insert_into(event_matrix_bridges::table)
+            .on_conflict(event_matrix_bridges::event_id)
+            .do_update()
+            .set((
+                event_matrix_bridges::matrix_room_id
+                    .eq(excluded(event_matrix_bridges::matrix_room_id)),
+                event_matrix_bridges::updated_by.eq(excluded(event_matrix_bridges::updated_by)),
+                event_matrix_bridges::updated_at.eq(diesel::dsl::now),
+            ));
+
+        let bridge = query.get_result(conn)?;
+
+        Ok(bridge)
+    }
+}
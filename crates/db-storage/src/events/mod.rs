@@ -4,7 +4,8 @@
 
 use crate::rooms::Room;
 use crate::schema::{
-    event_exceptions, event_favorites, event_invites, events, rooms, sip_configs, users,
+    event_exceptions, event_favorites, event_invites, events, room_owners, rooms, sip_configs,
+    users,
 };
 use crate::sip_configs::SipConfig;
 use crate::users::User;
@@ -13,15 +14,16 @@ use chrono::{DateTime, Utc};
 use chrono_tz::Tz;
 use database::{DatabaseError, DbConnection, Paginate, Result};
 use diesel::deserialize::FromSql;
+use diesel::dsl::sql;
 use diesel::expression::AsExpression;
 use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::sql_types::{Nullable, Record, Timestamptz, Uuid};
+use diesel::sql_types::{Bool, Nullable, Record, Timestamptz, Uuid};
 use diesel::{
     BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods,
     OptionalExtension, PgSortExpressionMethods, QueryDsl, Queryable, RunQueryDsl,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::io::Write;
 use std::str::FromStr;
 use types::core::{EventId, RoomId, TenantId, TimeZone, UserId};
@@ -35,6 +37,8 @@ types::diesel_newtype! {
 }
 
 pub mod email_invites;
+pub mod matrix_bridges;
+pub mod published_results;
 
 #[derive(Debug, Clone, Queryable, Identifiable, Associations, PartialEq, Eq)]
 #[diesel(table_name = events)]
@@ -77,8 +81,21 @@ pub struct Event {
     pub is_adhoc: bool,
 
     pub tenant_id: TenantId,
+
+    /// Set when the event has been moved to the trash
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    /// URL of a meeting on an external conferencing service, e.g. Jitsi or Teams, to be used instead of the room
+    pub external_meeting_url: Option<String>,
 }
 
+/// SQL condition matching events which end after the bound datetime
+///
+/// `ends_at` of recurring events contains the start of the last occurrence, so the occurrence duration is added.
+const ENDS_AFTER: &str = "(CASE WHEN events.is_recurring \
+    THEN events.ends_at + COALESCE(events.duration_secs, 0) * INTERVAL '1 second' \
+    ELSE events.ends_at END) > ";
+
 impl Event {
     /// Returns the ends_at value of the first occurrence of the event
     pub fn ends_at_of_first_occurrence(&self) -> Option<(DateTime<Utc>, TimeZone)> {
@@ -127,7 +144,9 @@ impl GetEventsCursor {
 impl Event {
     #[tracing::instrument(err, skip_all)]
     pub fn get(conn: &mut DbConnection, event_id: EventId) -> Result<Event> {
-        let query = events::table.filter(events::id.eq(event_id));
+        let query = events::table
+            .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null());
 
         let event = query.first(conn)?;
 
@@ -161,7 +180,8 @@ impl Event {
                 sip_configs::all_columns.nullable(),
                 event_favorites::user_id.nullable().is_not_null(),
             ))
-            .filter(events::id.eq(event_id));
+            .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null());
 
         let (event, invite, room, sip_config, is_favorite) = query.first(conn)?;
 
@@ -182,7 +202,8 @@ impl Event {
                 rooms::all_columns,
                 sip_configs::all_columns.nullable(),
             ))
-            .filter(events::id.eq(event_id));
+            .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null());
 
         let (event, room, sip_config) = query.first(conn)?;
 
@@ -213,9 +234,14 @@ impl Event {
         )>,
     > {
         // Filter applied to all events which validates that the event is either created by
-        // the given user or a invite to the event exists for the user
+        // the given user, is in a room owned by the user or a invite to the event exists for the user
+        let owned_rooms = room_owners::table
+            .select(room_owners::room_id)
+            .filter(room_owners::user_id.eq(user.id));
+
         let event_related_to_user_id = events::created_by
             .eq(user.id)
+            .or(events::room.eq_any(owned_rooms))
             .or(event_invites::invitee.eq(user.id));
 
         // Create query which select events and joins into the room of the event
@@ -240,6 +266,7 @@ impl Event {
                 event_favorites::user_id.nullable().is_not_null(),
             ))
             .filter(events::tenant_id.eq(user.tenant_id))
+            .filter(events::deleted_at.is_null())
             .filter(event_related_to_user_id)
             .order_by(events::starts_at.nullable().asc().nulls_first())
             .then_order_by(events::created_at.asc())
@@ -367,6 +394,100 @@ impl Event {
         Ok(())
     }
 
+    /// Move an event to the trash
+    #[tracing::instrument(err, skip_all)]
+    pub fn soft_delete_by_id(conn: &mut DbConnection, event_id: EventId) -> Result<()> {
+        let deleted_events = diesel::update(events::table)
+            .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null())
+            .set(events::deleted_at.eq(Utc::now()))
+            .execute(conn)?;
+
+        if deleted_events == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Restore an event created by the given user from the trash
+    ///
+    /// Events which have been moved to the trash together with their room can only be restored by restoring the room.
+    #[tracing::instrument(err, skip_all)]
+    pub fn restore(
+        conn: &mut DbConnection,
+        event_id: EventId,
+        created_by: UserId,
+    ) -> Result<Event> {
+        conn.transaction(|conn| {
+            let room_deleted_at: Option<DateTime<Utc>> = events::table
+                .inner_join(rooms::table)
+                .select(rooms::deleted_at)
+                .filter(events::id.eq(event_id))
+                .filter(events::created_by.eq(created_by))
+                .filter(events::deleted_at.is_not_null())
+                .first(conn)?;
+
+            if room_deleted_at.is_some() {
+                return Err(DatabaseError::NotFound);
+            }
+
+            let event = diesel::update(events::table)
+                .filter(events::id.eq(event_id))
+                .set(events::deleted_at.eq(None::<DateTime<Utc>>))
+                .get_result(conn)?;
+
+            Ok(event)
+        })
+    }
+
+    /// Returns all [`Event`]s in the trash created by the given user, excluding events which have been moved to the
+    /// trash together with their room
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_deleted_for_user(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<Vec<Event>> {
+        let query = events::table
+            .inner_join(rooms::table)
+            .select(events::all_columns)
+            .filter(events::created_by.eq(user_id))
+            .filter(events::deleted_at.is_not_null())
+            .filter(rooms::deleted_at.is_null())
+            .order_by(events::deleted_at.desc());
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
+    /// Returns the ids of all [`Event`]s which have been moved to the trash before the given point in time
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_ids_deleted_before(
+        conn: &mut DbConnection,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<EventId>> {
+        let query = events::table
+            .select(events::id)
+            .filter(events::deleted_at.lt(deleted_before));
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
+    /// Returns all [`Event`]s created by the given user, including events in the trash
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_created_by(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Event>> {
+        let query = events::table
+            .filter(events::created_by.eq(user_id))
+            .order_by(events::created_at.asc());
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
     /// Returns all [`Event`]s in the given [`RoomId`].
     ///
     /// This is needed because when rescheduling an Event from time x onwards, we create a new Event and both reference the room.
@@ -381,6 +502,109 @@ impl Event {
         Ok(events)
     }
 
+    /// Returns the ids of all events in the room which have been created by the given user
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_ids_for_room_created_by(
+        conn: &mut DbConnection,
+        room_id: RoomId,
+        user_id: UserId,
+    ) -> Result<Vec<EventId>> {
+        let query = events::table
+            .select(events::id)
+            .filter(events::room.eq(room_id))
+            .filter(events::created_by.eq(user_id));
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
+    /// Returns all time dependent [`Event`]s which block the time of the given users between `time_min` and `time_max`
+    ///
+    /// An event blocks the time of its creator and of all invitees which have not declined the invite. Every event is
+    /// returned together with the blocked user, once for each of the given users taking part in it.
+    ///
+    /// As `ends_at` of recurring events contains the start of the last occurrence, the duration of an occurrence is
+    /// added to it to find the series whose last occurrence still overlaps with `time_min`.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_blocking_for_users(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        user_ids: &[UserId],
+        time_min: DateTime<Utc>,
+        time_max: DateTime<Utc>,
+    ) -> Result<Vec<(Event, UserId)>> {
+        let created: Vec<Event> = events::table
+            .filter(events::tenant_id.eq(tenant_id))
+            .filter(events::deleted_at.is_null())
+            .filter(events::created_by.eq_any(user_ids))
+            .filter(events::starts_at.lt(time_max))
+            .filter(sql::<Bool>(ENDS_AFTER).bind::<Timestamptz, _>(time_min))
+            .load(conn)?;
+
+        let invited: Vec<(Event, UserId)> = events::table
+            .inner_join(event_invites::table.on(event_invites::event_id.eq(events::id)))
+            .select((events::all_columns, event_invites::invitee))
+            .filter(events::tenant_id.eq(tenant_id))
+            .filter(events::deleted_at.is_null())
+            .filter(event_invites::invitee.eq_any(user_ids))
+            .filter(event_invites::status.ne(EventInviteStatus::Declined))
+            .filter(events::starts_at.lt(time_max))
+            .filter(sql::<Bool>(ENDS_AFTER).bind::<Timestamptz, _>(time_min))
+            .load(conn)?;
+
+        let events = created
+            .into_iter()
+            .map(|event| {
+                let created_by = event.created_by;
+                (event, created_by)
+            })
+            .chain(invited)
+            .collect();
+
+        Ok(events)
+    }
+
+    /// Returns all time dependent [`Event`]s in the given room between `time_min` and `time_max`
+    ///
+    /// See [`Event::get_all_blocking_for_users`] for the handling of recurring events.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_room_between(
+        conn: &mut DbConnection,
+        room_id: RoomId,
+        time_min: DateTime<Utc>,
+        time_max: DateTime<Utc>,
+    ) -> Result<Vec<Event>> {
+        let query = events::table
+            .filter(events::room.eq(room_id))
+            .filter(events::deleted_at.is_null())
+            .filter(events::starts_at.lt(time_max))
+            .filter(sql::<Bool>(ENDS_AFTER).bind::<Timestamptz, _>(time_min));
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
+    /// Returns all time dependent [`Event`]s of all rooms between `time_min` and `time_max`
+    ///
+    /// See [`Event::get_all_blocking_for_users`] for the handling of recurring events.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_between(
+        conn: &mut DbConnection,
+        time_min: DateTime<Utc>,
+        time_max: DateTime<Utc>,
+    ) -> Result<Vec<Event>> {
+        let query = events::table
+            .filter(events::deleted_at.is_null())
+            .filter(events::starts_at.lt(time_max))
+            .filter(sql::<Bool>(ENDS_AFTER).bind::<Timestamptz, _>(time_min));
+
+        let events = query.load(conn)?;
+
+        Ok(events)
+    }
+
     /// Deletes all [`Event`]s in a given [`RoomId`]
     ///
     /// Fastpath for deleting multiple events in room
@@ -413,6 +637,7 @@ pub struct NewEvent {
     pub recurrence_pattern: Option<String>,
     pub is_adhoc: bool,
     pub tenant_id: TenantId,
+    pub external_meeting_url: Option<String>,
 }
 
 impl NewEvent {
@@ -443,6 +668,7 @@ pub struct UpdateEvent {
     pub is_recurring: Option<Option<bool>>,
     pub recurrence_pattern: Option<Option<String>>,
     pub is_adhoc: Option<bool>,
+    pub external_meeting_url: Option<Option<String>>,
 }
 
 impl UpdateEvent {
@@ -450,6 +676,7 @@ impl UpdateEvent {
     pub fn apply(self, conn: &mut DbConnection, event_id: EventId) -> Result<Event> {
         let query = diesel::update(events::table)
             .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null())
             .set(self)
             .returning(events::all_columns);
 
@@ -602,7 +829,7 @@ impl UpdateEventException {
 }
 
 sql_enum!(
-    #[derive(Serialize, PartialEq, Eq)]
+    #[derive(Serialize, Deserialize, PartialEq, Eq)]
     #[serde(rename_all = "snake_case")]
     EventInviteStatus,
     "event_invite_status",
@@ -640,6 +867,9 @@ pub struct EventInvite {
     pub created_by: UserId,
     pub created_at: DateTime<Utc>,
     pub status: EventInviteStatus,
+    /// Comment of the invitee to the response, shown to the organizer
+    pub comment: Option<String>,
+    pub responded_at: Option<DateTime<Utc>>,
 }
 
 impl EventInvite {
@@ -705,17 +935,46 @@ impl EventInvite {
         conn: &mut DbConnection,
         user_id: UserId,
     ) -> Result<Vec<EventInvite>> {
-        let query = event_invites::table.filter(
-            event_invites::invitee
-                .eq(user_id)
-                .and(event_invites::status.eq(EventInviteStatus::Pending)),
-        );
+        let query = event_invites::table
+            .inner_join(events::table)
+            .select(event_invites::all_columns)
+            .filter(
+                event_invites::invitee
+                    .eq(user_id)
+                    .and(event_invites::status.eq(EventInviteStatus::Pending)),
+            )
+            .filter(events::deleted_at.is_null());
 
         let event_invites = query.load(conn)?;
 
         Ok(event_invites)
     }
 
+    /// Returns all invites of the given user, regardless of their status
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_invitee(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<Vec<EventInvite>> {
+        let query = event_invites::table
+            .filter(event_invites::invitee.eq(user_id))
+            .order_by(event_invites::created_at.asc());
+
+        let event_invites = query.load(conn)?;
+
+        Ok(event_invites)
+    }
+
+    /// Delete all invites of the given user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_invitee(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(event_invites::table)
+            .filter(event_invites::invitee.eq(user_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
     #[tracing::instrument(err, skip_all)]
     pub fn delete_by_invitee(
         conn: &mut DbConnection,
@@ -768,8 +1027,11 @@ impl NewEventInvite {
 
 #[derive(AsChangeset)]
 #[diesel(table_name = event_invites)]
+#[diesel(treat_none_as_null = true)]
 pub struct UpdateEventInvite {
     pub status: EventInviteStatus,
+    pub comment: Option<String>,
+    pub responded_at: Option<DateTime<Utc>>,
 }
 
 impl UpdateEventInvite {
@@ -833,6 +1095,16 @@ impl EventFavorite {
 
         Ok(lines_changes > 0)
     }
+
+    /// Deletes all EventFavorite entries of the given user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(event_favorites::table)
+            .filter(event_favorites::user_id.eq(user_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
 }
 
 #[derive(Insertable)]
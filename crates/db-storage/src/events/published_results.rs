@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Final results of polls and legal votes published to the page of an event
+//!
+//! Only the tally is published, the results are readable by everyone holding the public link of the event. The link
+//! contains a signed nonce of the event, replacing the nonce revokes all links issued before.
+use super::Event;
+use crate::legal_votes::LegalVoteId;
+use crate::schema::{event_published_results, event_results_tokens};
+use crate::utils::Jsonb;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use types::core::{EventId, TenantId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    PublishedResultId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Vote count of a single option
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultEntry {
+    pub option: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Associations, Identifiable, Queryable)]
+#[diesel(table_name = event_published_results)]
+#[diesel(belongs_to(Event))]
+pub struct EventPublishedResult {
+    pub id: PublishedResultId,
+    pub event_id: EventId,
+    pub published_by: UserId,
+    pub published_at: DateTime<Utc>,
+    /// Topic of the poll or name of the legal vote
+    pub title: String,
+    pub results: Jsonb<Vec<ResultEntry>>,
+    /// Set if the results are of a legal vote, unset for polls
+    pub legal_vote_id: Option<LegalVoteId>,
+    pub tenant_id: TenantId,
+}
+
+impl EventPublishedResult {
+    /// Get the results published to the event, in the order they were published
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_event(conn: &mut DbConnection, event_id: EventId) -> Result<Vec<Self>> {
+        let query = event_published_results::table
+            .filter(event_published_results::event_id.eq(event_id))
+            .order_by(event_published_results::published_at.asc());
+
+        let results = query.load(conn)?;
+
+        Ok(results)
+    }
+
+    /// Delete the published result with the given id of the event
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_id(
+        conn: &mut DbConnection,
+        event_id: EventId,
+        id: PublishedResultId,
+    ) -> Result<()> {
+        let query = diesel::delete(
+            event_published_results::table
+                .filter(event_published_results::id.eq(id))
+                .filter(event_published_results::event_id.eq(event_id)),
+        );
+
+        let deleted = query.execute(conn)?;
+
+        if deleted == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = event_published_results)]
+pub struct NewEventPublishedResult {
+    pub event_id: EventId,
+    pub published_by: UserId,
+    pub title: String,
+    pub results: Jsonb<Vec<ResultEntry>>,
+    pub legal_vote_id: Option<LegalVoteId>,
+    pub tenant_id: TenantId,
+}
+
+impl NewEventPublishedResult {
+    /// Insert the result, returns `None` if the legal vote has already been published to the event
+    #[tracing::instrument(err, skip_all)]
+    pub fn try_insert(self, conn: &mut DbConnection) -> Result<Option<EventPublishedResult>> {
+        let query = self
+            .insert_into(event_published_results::table)
+            .on_conflict_do_nothing();
+
+        let result = query.get_result(conn).optional()?;
+
+        Ok(result)
+    }
+}
+
+/// Nonce of the public link to the results of an event
+#[derive(Debug, Clone, Associations, Identifiable, Queryable)]
+#[diesel(table_name = event_results_tokens)]
+#[diesel(primary_key(event_id))]
+#[diesel(belongs_to(Event))]
+pub struct EventResultsToken {
+    pub event_id: EventId,
+    pub nonce: uuid::Uuid,
+    pub rotated_at: DateTime<Utc>,
+}
+
+impl EventResultsToken {
+    /// Get the nonce of the event, returns `None` if no public link has been issued yet
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, event_id: EventId) -> Result<Option<Self>> {
+        let query = event_results_tokens::table.filter(event_results_tokens::event_id.eq(event_id));
+
+        let token = query.get_result(conn).optional()?;
+
+        Ok(token)
+    }
+
+    /// Get the nonce of the event, creates it when the first public link is issued
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_or_create(conn: &mut DbConnection, event_id: EventId) -> Result<Self> {
+        diesel::insert_into(event_results_tokens::table)
+            .values((
+                event_results_tokens::event_id.eq(event_id),
+                event_results_tokens::nonce.eq(uuid::Uuid::new_v4()),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        let query = event_results_tokens::table.filter(event_results_tokens::event_id.eq(event_id));
+
+        let token = query.get_result(conn)?;
+
+        Ok(token)
+    }
+
+    /// Replace the nonce of the event, which revokes all public links issued before
+    #[tracing::instrument(err, skip_all)]
+    pub fn rotate(conn: &mut DbConnection, event_id: EventId) -> Result<Self> {
+        let query = diesel::insert_into(event_results_tokens::table)
+            .values((
+                event_results_tokens::event_id.eq(event_id),
+                event_results_tokens::nonce.eq(uuid::Uuid::new_v4()),
+                event_results_tokens::rotated_at.eq(Utc::now()),
+            ))
+            .on_conflict(event_results_tokens::event_id)
+            .do_update()
+            .set((
+                event_results_tokens::nonce.eq(excluded(event_results_tokens::nonce)),
+                event_results_tokens::rotated_at.eq(excluded(event_results_tokens::rotated_at)),
+            ));
+
+        let token = query.get_result(conn)?;
+
+        Ok(token)
+    }
+}
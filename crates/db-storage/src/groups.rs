@@ -35,6 +35,35 @@ impl Group {
         Ok(groups)
     }
 }
+
+/// Returns the number of groups each of the other users shares with the user
+///
+/// Users without shared groups are omitted.
+#[tracing::instrument(err, skip_all)]
+pub fn count_shared_groups(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    other_user_ids: &[UserId],
+) -> Result<Vec<(UserId, i64)>> {
+    let group_ids: Vec<GroupId> = user_groups::table
+        .filter(user_groups::user_id.eq(user_id))
+        .select(user_groups::group_id)
+        .load(conn)?;
+
+    if group_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let query = user_groups::table
+        .filter(user_groups::user_id.eq_any(other_user_ids))
+        .filter(user_groups::group_id.eq_any(group_ids))
+        .group_by(user_groups::user_id)
+        .select((user_groups::user_id, diesel::dsl::count_star()));
+
+    let counts = query.load(conn)?;
+
+    Ok(counts)
+}
 #[derive(Debug, Insertable)]
 #[diesel(table_name = groups)]
 pub struct NewGroup<'a> {
@@ -149,6 +178,15 @@ pub fn insert_user_into_groups(
     Ok(())
 }
 
+#[tracing::instrument(err, skip_all)]
+pub fn remove_user_from_all_groups(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+    diesel::delete(user_groups::table)
+        .filter(user_groups::user_id.eq(user_id))
+        .execute(conn)?;
+
+    Ok(())
+}
+
 #[tracing::instrument(err, skip_all)]
 pub fn remove_user_from_groups(
     conn: &mut DbConnection,
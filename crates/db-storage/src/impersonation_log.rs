@@ -0,0 +1,76 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Audit log of the requests made while impersonating a user
+//!
+//! The users are not referenced by the table, entries are kept when either user is deleted.
+use crate::schema::impersonation_log;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{TenantId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    ImpersonationLogId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = impersonation_log)]
+pub struct ImpersonationLogEntry {
+    pub id: ImpersonationLogId,
+    pub impersonator_id: UserId,
+    pub impersonated_id: UserId,
+    pub method: String,
+    pub path: String,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub tenant_id: TenantId,
+}
+
+impl ImpersonationLogEntry {
+    /// Get the latest entries, newest first, optionally only of requests impersonating the given user
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_latest(
+        conn: &mut DbConnection,
+        impersonated_id: Option<UserId>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let mut query = impersonation_log::table
+            .order_by(impersonation_log::created_at.desc())
+            .limit(limit)
+            .into_boxed();
+
+        if let Some(impersonated_id) = impersonated_id {
+            query = query.filter(impersonation_log::impersonated_id.eq(impersonated_id));
+        }
+
+        let entries = query.load(conn)?;
+
+        Ok(entries)
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = impersonation_log)]
+pub struct NewImpersonationLogEntry {
+    pub impersonator_id: UserId,
+    pub impersonated_id: UserId,
+    pub method: String,
+    pub path: String,
+    pub request_id: Option<String>,
+    pub tenant_id: TenantId,
+}
+
+impl NewImpersonationLogEntry {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<ImpersonationLogEntry> {
+        let query = self.insert_into(impersonation_log::table);
+
+        let entry = query.get_result(conn)?;
+
+        Ok(entry)
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Sessions of users which authenticated against the LDAP directory
+//!
+//! Only a hash of the session token is stored, hashing is up to the caller.
+use crate::schema::{ldap_sessions, users};
+use crate::users::User;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::UserId;
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[diesel(table_name = ldap_sessions)]
+#[diesel(primary_key(token_hash))]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+pub struct LdapSession {
+    pub token_hash: Vec<u8>,
+    pub user_id: UserId,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl LdapSession {
+    /// Get the user of the session with the given token hash, if the session has not expired yet
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_user_by_token_hash(
+        conn: &mut DbConnection,
+        token_hash: &[u8],
+    ) -> Result<Option<(Self, User)>> {
+        let query = ldap_sessions::table
+            .inner_join(users::table)
+            .filter(ldap_sessions::token_hash.eq(token_hash))
+            .filter(ldap_sessions::expires_at.gt(Utc::now()))
+            .select((ldap_sessions::all_columns, users::all_columns));
+
+        let session = query.first(conn).optional()?;
+
+        Ok(session)
+    }
+
+    /// Delete all expired sessions of the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_expired_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(ldap_sessions::table)
+            .filter(ldap_sessions::user_id.eq(user_id))
+            .filter(ldap_sessions::expires_at.le(Utc::now()))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Delete all sessions of the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(ldap_sessions::table)
+            .filter(ldap_sessions::user_id.eq(user_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = ldap_sessions)]
+pub struct NewLdapSession {
+    pub token_hash: Vec<u8>,
+    pub user_id: UserId,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewLdapSession {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<LdapSession> {
+        let query = self.insert_into(ldap_sessions::table);
+
+        let session = query.get_result(conn)?;
+
+        Ok(session)
+    }
+}
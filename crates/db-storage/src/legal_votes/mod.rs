@@ -4,13 +4,14 @@
 
 use self::types::protocol::{NewProtocol, Protocol};
 use crate::schema::legal_votes;
-use ::types::core::{RoomId, TenantId, UserId};
+use ::types::core::{AssetId, RoomId, TenantId, UserId};
 use chrono::{DateTime, Utc};
 use database::{DatabaseError, DbConnection, Paginate, Result};
 use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind;
 use diesel::{ExpressionMethods, Identifiable, QueryDsl, Queryable, RunQueryDsl};
 
+pub mod scheduled;
 pub mod types;
 
 ::types::diesel_newtype! {
@@ -35,6 +36,8 @@ pub struct LegalVote {
     pub room: Option<RoomId>,
     pub protocol: Protocol,
     pub tenant_id: TenantId,
+    /// The PDF of the protocol, stored as asset of the room
+    pub protocol_asset_id: Option<AssetId>,
 }
 
 impl LegalVote {
@@ -132,6 +135,32 @@ impl LegalVote {
         Ok(legal_votes_with_total)
     }
 
+    /// Get all `LegalVotes` which were created by or contain a vote of the given user
+    ///
+    /// Votes are only attributed to a user if the legal vote was not hidden.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_participant(
+        conn: &mut DbConnection,
+        user_id: UserId,
+    ) -> Result<Vec<LegalVote>> {
+        let voted = serde_json::json!({
+            "entries": [{ "event": { "event": "vote", "issuer": user_id } }]
+        });
+
+        let query = legal_votes::table
+            .filter(
+                legal_votes::created_by.eq(user_id).or(
+                    diesel::dsl::sql::<diesel::sql_types::Bool>("legal_votes.protocol @> ")
+                        .bind::<diesel::sql_types::Jsonb, _>(voted),
+                ),
+            )
+            .order_by(legal_votes::created_at.asc());
+
+        let legal_votes = query.load(conn)?;
+
+        Ok(legal_votes)
+    }
+
     /// Delete all `LegalVotes` for room
     #[tracing::instrument(err, skip_all)]
     pub fn delete_by_room(conn: &mut DbConnection, room_id: RoomId) -> Result<()> {
@@ -214,3 +243,18 @@ pub fn set_protocol(
 
     Ok(())
 }
+
+/// Link the PDF of the protocol, which has been stored as asset of the room, to the legal vote
+#[tracing::instrument(err, skip_all)]
+pub fn set_protocol_asset(
+    conn: &mut DbConnection,
+    legal_vote_id: LegalVoteId,
+    asset_id: AssetId,
+) -> Result<()> {
+    let query = diesel::update(legal_votes::table.filter(legal_votes::id.eq(&legal_vote_id)))
+        .set(legal_votes::protocol_asset_id.eq(asset_id));
+
+    query.execute(conn)?;
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Legal votes which are prepared before a meeting and started by id during the meeting
+use super::types::ScheduledParameters;
+use super::LegalVoteId;
+use crate::schema::scheduled_legal_votes;
+use crate::utils::Jsonb;
+use ::types::core::{RoomId, TenantId, UserId};
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, Identifiable, QueryDsl, Queryable, RunQueryDsl};
+
+::types::diesel_newtype! {
+    #[derive(Copy)]
+    ScheduledLegalVoteId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Diesel scheduled_legal_votes model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct ScheduledLegalVote {
+    pub id: ScheduledLegalVoteId,
+    pub room_id: RoomId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// Reference to the agenda item of the meeting this vote belongs to
+    pub agenda_item: Option<String>,
+    pub parameters: Jsonb<ScheduledParameters>,
+    /// The legal vote which was started from this scheduled vote
+    pub legal_vote_id: Option<LegalVoteId>,
+    pub tenant_id: TenantId,
+}
+
+impl ScheduledLegalVote {
+    /// Get the scheduled vote with the given id inside the given room
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, room_id: RoomId, id: ScheduledLegalVoteId) -> Result<Self> {
+        let query = scheduled_legal_votes::table
+            .filter(scheduled_legal_votes::id.eq(id))
+            .filter(scheduled_legal_votes::room_id.eq(room_id));
+
+        let scheduled_vote = query.get_result(conn)?;
+
+        Ok(scheduled_vote)
+    }
+
+    /// Get all scheduled votes of the given room, in the order they were created
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_room(conn: &mut DbConnection, room_id: RoomId) -> Result<Vec<Self>> {
+        let query = scheduled_legal_votes::table
+            .filter(scheduled_legal_votes::room_id.eq(room_id))
+            .order_by(scheduled_legal_votes::created_at.asc());
+
+        let scheduled_votes = query.load(conn)?;
+
+        Ok(scheduled_votes)
+    }
+
+    /// Link the scheduled vote to the legal vote it was started as
+    #[tracing::instrument(err, skip_all)]
+    pub fn set_legal_vote(
+        conn: &mut DbConnection,
+        id: ScheduledLegalVoteId,
+        legal_vote_id: LegalVoteId,
+    ) -> Result<()> {
+        let query =
+            diesel::update(scheduled_legal_votes::table.filter(scheduled_legal_votes::id.eq(id)))
+                .set(scheduled_legal_votes::legal_vote_id.eq(legal_vote_id));
+
+        query.execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Delete the scheduled vote with the given id inside the given room
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_id(
+        conn: &mut DbConnection,
+        room_id: RoomId,
+        id: ScheduledLegalVoteId,
+    ) -> Result<()> {
+        let query = diesel::delete(
+            scheduled_legal_votes::table
+                .filter(scheduled_legal_votes::id.eq(id))
+                .filter(scheduled_legal_votes::room_id.eq(room_id)),
+        );
+
+        let deleted = query.execute(conn)?;
+
+        if deleted == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Scheduled legal vote insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = scheduled_legal_votes)]
+pub struct NewScheduledLegalVote {
+    pub room_id: RoomId,
+    pub created_by: UserId,
+    pub agenda_item: Option<String>,
+    pub parameters: Jsonb<ScheduledParameters>,
+    pub tenant_id: TenantId,
+}
+
+impl NewScheduledLegalVote {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<ScheduledLegalVote> {
+        let query = self.insert_into(scheduled_legal_votes::table);
+
+        let scheduled_vote = query.get_result(conn)?;
+
+        Ok(scheduled_vote)
+    }
+}
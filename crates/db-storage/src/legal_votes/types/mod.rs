@@ -50,6 +50,52 @@ pub struct Parameters {
     pub token: Option<Token>,
 }
 
+/// Percentages of the duration of a timed vote which are left when the participants are reminded of the deadline
+pub const REMINDER_PERCENTAGES: [u8; 2] = [50, 10];
+
+impl Parameters {
+    /// The time the vote expires, unset if the vote has no duration
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.inner
+            .duration
+            .map(|duration| self.start_time + chrono::Duration::seconds(duration as i64))
+    }
+
+    /// The reminders of the remaining time of a timed vote, in the order they are due
+    ///
+    /// Empty if the vote has no duration.
+    pub fn reminders(&self) -> Vec<Reminder> {
+        let (duration, deadline) = match (self.inner.duration, self.deadline()) {
+            (Some(duration), Some(deadline)) => (duration, deadline),
+            _ => return vec![],
+        };
+
+        REMINDER_PERCENTAGES
+            .iter()
+            .map(|&remaining_percent| {
+                let remaining_secs = duration * u64::from(remaining_percent) / 100;
+
+                Reminder {
+                    remaining_percent,
+                    remaining_secs,
+                    due: deadline - chrono::Duration::seconds(remaining_secs as i64),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A reminder of the remaining time of a timed vote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    /// Percentage of the duration which is left
+    pub remaining_percent: u8,
+    /// Seconds left until the deadline
+    pub remaining_secs: u64,
+    /// The time the reminder is due
+    pub due: DateTime<Utc>,
+}
+
 /// Kinds of votes
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToRedisArgs, FromRedisValue,
@@ -131,6 +177,53 @@ pub struct UserParameters {
     pub timezone: Option<chrono_tz::Tz>,
 }
 
+/// The parameters of a vote which is scheduled before the meeting
+///
+/// Equal to the [`UserParameters`] except for the allowed participants, which are only known once the vote gets started.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Deserialize, Validate)]
+pub struct ScheduledParameters {
+    /// The kind of vote
+    pub kind: VoteKind,
+    /// The name of the vote
+    #[validate(length(max = 150))]
+    pub name: String,
+    /// A Subtitle for the vote
+    #[validate(length(max = 255))]
+    pub subtitle: Option<String>,
+    /// The topic that will be voted on
+    #[validate(length(max = 500))]
+    pub topic: Option<String>,
+    /// Indicates that the `Abstain` vote option is enabled
+    pub enable_abstain: bool,
+    /// The vote will automatically stop when every participant voted
+    pub auto_close: bool,
+    /// The vote will stop when the duration (in seconds) has passed
+    #[validate(range(min = 5))]
+    pub duration: Option<u64>,
+    /// A PDF document will be created when the vote is over
+    pub create_pdf: bool,
+    /// An optional timezone, defaults to UTC.
+    pub timezone: Option<chrono_tz::Tz>,
+}
+
+impl ScheduledParameters {
+    /// Create the [`UserParameters`] to start the scheduled vote with the given participants
+    pub fn into_user_parameters(self, allowed_participants: Vec<ParticipantId>) -> UserParameters {
+        UserParameters {
+            kind: self.kind,
+            name: self.name,
+            subtitle: self.subtitle,
+            topic: self.topic,
+            allowed_participants,
+            enable_abstain: self.enable_abstain,
+            auto_close: self.auto_close,
+            duration: self.duration,
+            create_pdf: self.create_pdf,
+            timezone: self.timezone,
+        }
+    }
+}
+
 /// Final vote results
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "results")]
@@ -405,4 +498,52 @@ mod test {
         assert!(create_pdf);
         assert_eq!(None, timezone);
     }
+
+    #[test]
+    fn deadline_and_reminders_of_timed_vote() {
+        let mut params = Parameters {
+            initiator_id: ParticipantId::nil(),
+            legal_vote_id: LegalVoteId::from(Uuid::nil()),
+            start_time: Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap(),
+            max_votes: 2,
+            token: None,
+            inner: UserParameters {
+                name: "Timed Vote".into(),
+                kind: VoteKind::RollCall,
+                subtitle: None,
+                topic: None,
+                allowed_participants: vec![ParticipantId::nil()],
+                enable_abstain: false,
+                auto_close: false,
+                duration: Some(60),
+                create_pdf: true,
+                timezone: None,
+            },
+        };
+
+        assert_eq!(
+            params.deadline(),
+            Some(Utc.with_ymd_and_hms(1970, 1, 1, 0, 1, 0).unwrap())
+        );
+        assert_eq!(
+            params.reminders(),
+            vec![
+                Reminder {
+                    remaining_percent: 50,
+                    remaining_secs: 30,
+                    due: Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 30).unwrap(),
+                },
+                Reminder {
+                    remaining_percent: 10,
+                    remaining_secs: 6,
+                    due: Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 54).unwrap(),
+                },
+            ]
+        );
+
+        params.inner.duration = None;
+
+        assert_eq!(params.deadline(), None);
+        assert!(params.reminders().is_empty());
+    }
 }
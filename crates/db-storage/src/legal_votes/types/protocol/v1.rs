@@ -54,6 +54,8 @@ pub enum VoteEvent {
     FinalResults(FinalResults),
     /// The vote has been canceled
     Cancel(Cancel),
+    /// A participant delegated their vote to another participant
+    Delegation(Delegation),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -85,6 +87,11 @@ pub struct Vote {
     pub token: Token,
     /// The chosen vote option
     pub option: VoteOption,
+    /// User id of the proxy that cast the vote on behalf of the voting user
+    ///
+    /// Is `None` if the user voted themselves or the vote is hidden
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<UserId>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -106,3 +113,14 @@ pub struct Cancel {
     #[serde(flatten)]
     pub reason: CancelReason,
 }
+
+/// A delegation of a users vote to a proxy
+///
+/// Delegations can be chained, the protocol contains one entry per delegation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delegation {
+    /// The user that delegated their vote
+    pub delegator: UserId,
+    /// The user that casts the vote on behalf of the delegator
+    pub proxy: UserId,
+}
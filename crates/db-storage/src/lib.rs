@@ -33,18 +33,34 @@ use diesel::sql_types::Text;
 mod macros;
 mod schema;
 
+pub mod action_items;
 pub mod assets;
+pub mod brandings;
+pub mod calendar_links;
+pub mod call_feedback;
+pub mod contacts;
 pub mod events;
 pub mod groups;
+pub mod impersonation_log;
 pub mod invites;
+pub mod ldap_sessions;
 pub mod legal_votes;
+pub mod mail_templates;
 pub mod migrations;
+pub mod poll_templates;
+pub mod recording_transcriptions;
+pub mod room_directory;
+pub mod room_markers;
+pub mod room_media_settings;
+pub mod room_owners;
+pub mod room_statistics;
 pub mod rooms;
 pub mod sip_configs;
 pub mod tariffs;
 pub mod tenants;
 pub mod users;
 pub mod utils;
+pub mod webrtc_stats;
 
 sql_function!(fn lower(x: Text) -> Text);
 sql_function!(fn levenshtein(x: Text, y: Text) -> Integer);
@@ -52,7 +68,12 @@ sql_function!(fn soundex(x: Text) -> Text);
 
 // SQL types reexport for schema.rs
 pub mod sql_types {
+    pub use super::action_items::ActionItemKindType as Action_item_kind;
+    pub use super::assets::AssetScanStatusType as Asset_scan_status;
+    pub use super::calendar_links::CalendarProviderType as Calendar_provider;
     pub use super::events::EventExceptionKindType as Event_exception_kind;
     pub use super::events::EventInviteStatusType as Event_invite_status;
+    pub use super::mail_templates::MailTemplateKindType as Mail_template_kind;
+    pub use super::recording_transcriptions::TranscriptionStatusType as Transcription_status;
     pub use diesel::sql_types::*;
 }
@@ -0,0 +1,104 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Mail templates of tenants
+//!
+//! Replace the built-in templates of the mail worker for the mails sent to the invitees of events of a tenant.
+use crate::schema::mail_templates;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::deserialize::FromSql;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use types::core::TenantId;
+
+sql_enum!(
+    #[derive(PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    MailTemplateKind,
+    "mail_template_kind",
+    MailTemplateKindType,
+    {
+        EventInvite = b"event_invite",
+        EventUpdate = b"event_update",
+        EventCancellation = b"event_cancellation",
+    }
+);
+
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = mail_templates, primary_key(tenant_id, kind))]
+pub struct MailTemplate {
+    pub tenant_id: TenantId,
+    pub kind: MailTemplateKind,
+    pub subject: String,
+    /// HTML body of the mail
+    pub body: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MailTemplate {
+    /// Get the template of the tenant, returns None if the tenant uses the built-in template
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        kind: MailTemplateKind,
+    ) -> Result<Option<MailTemplate>> {
+        let query = mail_templates::table
+            .filter(mail_templates::tenant_id.eq(tenant_id))
+            .filter(mail_templates::kind.eq(kind));
+
+        let template = query.get_result(conn).optional()?;
+
+        Ok(template)
+    }
+
+    /// Get all templates of the tenant
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_tenant(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+    ) -> Result<Vec<MailTemplate>> {
+        let query = mail_templates::table
+            .filter(mail_templates::tenant_id.eq(tenant_id))
+            .order_by(mail_templates::kind);
+
+        let templates = query.load(conn)?;
+
+        Ok(templates)
+    }
+
+    /// Insert or replace the template
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<MailTemplate> {
+        let query = diesel::insert_into(mail_templates::table)
+            .values(&self)
+            .on_conflict((mail_templates::tenant_id, mail_templates::kind))
+            .do_update()
+            .set(&self);
+
+        let template = query.get_result(conn)?;
+
+        Ok(template)
+    }
+
+    /// Delete the template, the tenant uses the built-in template again
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        kind: MailTemplateKind,
+    ) -> Result<()> {
+        diesel::delete(mail_templates::table)
+            .filter(mail_templates::tenant_id.eq(tenant_id))
+            .filter(mail_templates::kind.eq(kind))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+CREATE TYPE asset_scan_status AS ENUM ('not_scanned', 'clean', 'infected', 'scan_failed');
+
+ALTER TABLE assets ADD COLUMN scan_status asset_scan_status NOT NULL DEFAULT 'not_scanned';
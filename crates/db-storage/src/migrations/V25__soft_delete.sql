@@ -0,0 +1,5 @@
+ALTER TABLE rooms ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE events ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX rooms_deleted_at_idx ON rooms(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX events_deleted_at_idx ON events(deleted_at) WHERE deleted_at IS NOT NULL;
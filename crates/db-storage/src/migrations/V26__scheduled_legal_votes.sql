@@ -0,0 +1,12 @@
+CREATE TABLE scheduled_legal_votes(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    created_by UUID REFERENCES users(id) NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    agenda_item TEXT,
+    parameters JSONB NOT NULL,
+    legal_vote_id UUID REFERENCES legal_votes(id) ON DELETE SET NULL,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL
+);
+
+CREATE INDEX scheduled_legal_votes_room_id_idx ON scheduled_legal_votes(room_id);
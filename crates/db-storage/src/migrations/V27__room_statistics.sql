@@ -0,0 +1,13 @@
+CREATE TABLE room_statistics(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ NOT NULL,
+    peak_participants INTEGER NOT NULL,
+    participant_minutes BIGINT NOT NULL,
+    modules TEXT[] NOT NULL
+);
+
+CREATE INDEX room_statistics_room_id_idx ON room_statistics(room_id);
+CREATE INDEX room_statistics_started_at_idx ON room_statistics(started_at);
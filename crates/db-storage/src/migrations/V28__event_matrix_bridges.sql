@@ -0,0 +1,6 @@
+CREATE TABLE event_matrix_bridges(
+    event_id UUID PRIMARY KEY REFERENCES events(id) ON DELETE CASCADE,
+    matrix_room_id TEXT NOT NULL,
+    updated_by UUID REFERENCES users(id) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
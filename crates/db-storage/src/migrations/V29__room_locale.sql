@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN locale VARCHAR(35);
@@ -0,0 +1,21 @@
+CREATE TYPE calendar_provider AS ENUM ('google', 'microsoft');
+
+CREATE TABLE calendar_links(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    provider calendar_provider NOT NULL,
+    access_token BYTEA NOT NULL,
+    refresh_token BYTEA NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    synced_at TIMESTAMPTZ,
+    UNIQUE (user_id, provider)
+);
+
+CREATE TABLE calendar_link_events(
+    link_id UUID REFERENCES calendar_links(id) ON DELETE CASCADE NOT NULL,
+    event_id UUID REFERENCES events(id) ON DELETE CASCADE NOT NULL,
+    external_id TEXT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (link_id, event_id)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE ldap_sessions(
+    token_hash BYTEA PRIMARY KEY,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ON ldap_sessions(user_id);
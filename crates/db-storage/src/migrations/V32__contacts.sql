@@ -0,0 +1,14 @@
+CREATE TABLE room_statistics_participants(
+    room_statistics_id UUID REFERENCES room_statistics(id) ON DELETE CASCADE NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    PRIMARY KEY (room_statistics_id, user_id)
+);
+
+CREATE INDEX ON room_statistics_participants(user_id);
+
+CREATE TABLE contact_favorites(
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    contact_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    PRIMARY KEY (user_id, contact_id)
+);
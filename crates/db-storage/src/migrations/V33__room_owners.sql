@@ -0,0 +1,11 @@
+CREATE TABLE room_owners(
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    PRIMARY KEY (room_id, user_id)
+);
+
+CREATE INDEX ON room_owners(user_id);
+
+INSERT INTO room_owners (room_id, user_id, created_at)
+SELECT id, created_by, created_at FROM rooms;
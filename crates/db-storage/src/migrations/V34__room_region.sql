@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN region VARCHAR(64);
@@ -0,0 +1,5 @@
+CREATE TABLE room_media_settings(
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    speaker_focus_packets BIGINT,
+    speaker_focus_level BIGINT
+);
@@ -0,0 +1 @@
+ALTER TABLE room_assets ADD COLUMN breakout_room_id UUID;
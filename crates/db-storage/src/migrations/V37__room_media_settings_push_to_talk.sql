@@ -0,0 +1,3 @@
+ALTER TABLE room_media_settings
+    ADD COLUMN push_to_talk BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN push_to_talk_max_hold_secs BIGINT;
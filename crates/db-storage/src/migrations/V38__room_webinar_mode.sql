@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN webinar_mode BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+CREATE TABLE room_directory_entries(
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    schedule VARCHAR(255),
+    invite_code UUID REFERENCES invites(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
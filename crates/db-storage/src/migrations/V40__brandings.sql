@@ -0,0 +1,13 @@
+CREATE TABLE tenant_brandings(
+    tenant_id UUID PRIMARY KEY REFERENCES tenants(id) ON DELETE CASCADE,
+    logo_url TEXT,
+    primary_color VARCHAR(7),
+    secondary_color VARCHAR(7)
+);
+
+CREATE TABLE room_brandings(
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    logo_asset_id UUID REFERENCES assets(id) ON DELETE SET NULL,
+    primary_color VARCHAR(7),
+    secondary_color VARCHAR(7)
+);
@@ -0,0 +1,10 @@
+CREATE TYPE mail_template_kind AS ENUM ('event_invite', 'event_update', 'event_cancellation');
+
+CREATE TABLE mail_templates(
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    kind mail_template_kind NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (tenant_id, kind)
+);
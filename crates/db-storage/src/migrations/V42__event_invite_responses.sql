@@ -0,0 +1,23 @@
+-- Responses of invitees, the comment is shown to the organizer
+ALTER TABLE event_invites
+    ADD COLUMN comment TEXT,
+    ADD COLUMN responded_at TIMESTAMPTZ;
+
+-- Invitees without an account can respond to the invite mail with an ICS reply
+ALTER TABLE event_email_invites
+    ADD COLUMN status event_invite_status NOT NULL DEFAULT 'pending',
+    ADD COLUMN comment TEXT,
+    ADD COLUMN responded_at TIMESTAMPTZ;
+
+-- Grant the access to the new endpoints of existing events to everyone with access to the related endpoints
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/invites/remind'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/invites$', '/attendance'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/invites' AND v2 = 'GET'
+ON CONFLICT DO NOTHING;
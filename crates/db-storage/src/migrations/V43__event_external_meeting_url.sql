@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN external_meeting_url TEXT;
@@ -0,0 +1,16 @@
+CREATE TABLE call_feedback(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    issues TEXT[] NOT NULL,
+    comment TEXT,
+    participants INTEGER NOT NULL,
+    duration_secs BIGINT NOT NULL,
+    webrtc_down_count INTEGER NOT NULL,
+    slow_link_count INTEGER NOT NULL
+);
+
+CREATE INDEX call_feedback_created_at_idx ON call_feedback(created_at);
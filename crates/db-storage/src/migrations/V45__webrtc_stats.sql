@@ -0,0 +1,19 @@
+CREATE TABLE webrtc_stats(
+    id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    participant_id UUID NOT NULL,
+    media_session_type VARCHAR(16) NOT NULL,
+    source UUID NOT NULL,
+    direction VARCHAR(16) NOT NULL,
+    packets BIGINT NOT NULL,
+    packets_lost BIGINT NOT NULL,
+    jitter_ms DOUBLE PRECISION,
+    round_trip_time_ms DOUBLE PRECISION,
+    bitrate_kbps DOUBLE PRECISION
+);
+
+-- Snapshots are appended in the order they are recorded, a BRIN index keeps time range queries cheap
+CREATE INDEX webrtc_stats_recorded_at_idx ON webrtc_stats USING BRIN(recorded_at);
+CREATE INDEX webrtc_stats_participant_id_idx ON webrtc_stats(participant_id);
@@ -0,0 +1,14 @@
+CREATE TYPE transcription_status AS ENUM ('queued', 'processing', 'done', 'failed');
+
+CREATE TABLE recording_transcriptions(
+    recording_id UUID PRIMARY KEY REFERENCES assets(id) ON DELETE CASCADE,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    status transcription_status NOT NULL,
+    transcript_asset_id UUID REFERENCES assets(id) ON DELETE SET NULL,
+    chapters_asset_id UUID REFERENCES assets(id) ON DELETE SET NULL,
+    error TEXT,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT now() NOT NULL
+);
+
+CREATE INDEX recording_transcriptions_room_id_idx ON recording_transcriptions(room_id);
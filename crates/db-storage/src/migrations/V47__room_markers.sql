@@ -0,0 +1,21 @@
+-- Chapter markers set by moderators during meetings
+CREATE TABLE room_markers(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    breakout_room_id UUID,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    title TEXT NOT NULL,
+    marked_at TIMESTAMPTZ NOT NULL,
+    recording_id UUID REFERENCES assets(id) ON DELETE SET NULL,
+    room_statistics_id UUID REFERENCES room_statistics(id) ON DELETE SET NULL
+);
+
+CREATE INDEX room_markers_room_id_idx ON room_markers(room_id, marked_at);
+CREATE INDEX room_markers_recording_id_idx ON room_markers(recording_id);
+
+-- Grant the access to the summary of existing rooms to everyone with read access to the room
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/tariff$', '/summary'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/rooms/%/tariff'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,15 @@
+-- Decisions and action items collected during meetings
+CREATE TYPE action_item_kind AS ENUM ('decision', 'action_item');
+
+CREATE TABLE action_items(
+    id UUID PRIMARY KEY,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE NOT NULL,
+    breakout_room_id UUID,
+    kind action_item_kind NOT NULL,
+    text TEXT NOT NULL,
+    assignee TEXT,
+    due_date DATE,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX action_items_room_id_idx ON action_items(room_id, created_at);
@@ -0,0 +1,34 @@
+-- Polls prepared before a meeting, either for an event or in the personal library of a user
+CREATE TABLE poll_templates(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_by UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    event_id UUID REFERENCES events(id) ON DELETE CASCADE,
+    topic TEXT NOT NULL,
+    live BOOLEAN NOT NULL,
+    choices TEXT[] NOT NULL,
+    duration_secs INTEGER NOT NULL,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL
+);
+
+CREATE INDEX poll_templates_event_id_idx ON poll_templates(event_id);
+CREATE INDEX poll_templates_created_by_idx ON poll_templates(created_by) WHERE event_id IS NULL;
+
+-- Grant the access to the new endpoints of existing events to everyone who can edit the event
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/polls'), 'GET', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/polls'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/polls/*'), 'DELETE', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,15 @@
+-- The PDF of the protocol which has been stored as asset of the room when the vote ended
+ALTER TABLE legal_votes ADD COLUMN protocol_asset_id UUID REFERENCES assets(id) ON DELETE SET NULL;
+
+-- Grant the access to the protocol downloads to everyone who can read the legal vote
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/protocol', v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/legal_votes/[^/]+$' AND v2 = 'GET'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/pdf', v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/legal_votes/[^/]+$' AND v2 = 'GET'
+ON CONFLICT DO NOTHING;
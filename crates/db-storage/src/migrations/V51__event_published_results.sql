@@ -0,0 +1,33 @@
+-- Final results of polls and legal votes published to the page of an event
+CREATE TABLE event_published_results(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id UUID REFERENCES events(id) ON DELETE CASCADE NOT NULL,
+    published_by UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    published_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    title TEXT NOT NULL,
+    results JSONB NOT NULL,
+    legal_vote_id UUID REFERENCES legal_votes(id) ON DELETE CASCADE,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL,
+    UNIQUE (event_id, legal_vote_id)
+);
+
+CREATE INDEX event_published_results_event_id_idx ON event_published_results(event_id);
+
+-- Grant the access to the new endpoints of existing events to everyone who can edit the event
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/results'), 'GET', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/results'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/results/*'), 'DELETE', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,16 @@
+-- Requests of administrators viewing the data of other users for support purposes
+--
+-- The users are not referenced, the entries must outlive the deletion of both users.
+CREATE TABLE impersonation_log(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    impersonator_id UUID NOT NULL,
+    impersonated_id UUID NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    request_id TEXT,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL,
+    tenant_id UUID REFERENCES tenants(id) NOT NULL
+);
+
+CREATE INDEX impersonation_log_impersonated_id_idx ON impersonation_log(impersonated_id);
+CREATE INDEX impersonation_log_created_at_idx ON impersonation_log(created_at);
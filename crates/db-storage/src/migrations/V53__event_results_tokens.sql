@@ -0,0 +1,8 @@
+-- Nonce signed into the public link to the results published to an event
+--
+-- Replacing the nonce revokes all public links issued before.
+CREATE TABLE event_results_tokens(
+    event_id UUID PRIMARY KEY REFERENCES events(id) ON DELETE CASCADE,
+    nonce UUID NOT NULL,
+    rotated_at TIMESTAMPTZ DEFAULT now() NOT NULL
+);
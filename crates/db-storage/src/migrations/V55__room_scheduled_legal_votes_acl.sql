@@ -0,0 +1,12 @@
+-- Grant the access to the scheduled legal votes of existing rooms to everyone with write access to the room
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/legal_votes/scheduled', 'GET|POST', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/rooms/[^/]+$' AND v2 LIKE '%PUT%'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/legal_votes/scheduled/*', 'DELETE', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/rooms/[^/]+$' AND v2 LIKE '%PUT%'
+ON CONFLICT DO NOTHING;
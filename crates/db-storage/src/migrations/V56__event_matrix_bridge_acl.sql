@@ -0,0 +1,12 @@
+-- Grant the access to the matrix bridge of existing events to everyone who can read or edit the event
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/invites$', '/matrix_bridge'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/invites' AND v2 = 'GET'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/reschedule$', '/matrix_bridge'), 'PUT|DELETE', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/events/%/reschedule'
+ON CONFLICT DO NOTHING;
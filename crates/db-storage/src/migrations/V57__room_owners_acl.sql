@@ -0,0 +1,18 @@
+-- Grant the access to the owners of existing rooms to everyone with read or write access to the room
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, regexp_replace(v1, '/tariff$', '/owners'), v2, v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 LIKE '/rooms/%/tariff'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/owners/*', 'PUT|DELETE', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/rooms/[^/]+$' AND v2 LIKE '%PUT%'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO casbin_rule (ptype, v0, v1, v2, v3, v4, v5)
+SELECT ptype, v0, v1 || '/transfer_ownership', 'POST', v3, v4, v5
+FROM casbin_rule
+WHERE ptype = 'p' AND v1 ~ '^/rooms/[^/]+$' AND v2 LIKE '%PUT%'
+ON CONFLICT DO NOTHING;
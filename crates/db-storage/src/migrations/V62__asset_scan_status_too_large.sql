@@ -0,0 +1,2 @@
+-- Assets exceeding the maximum size of the virus scanner are stored without being scanned
+ALTER TYPE asset_scan_status ADD VALUE 'too_large';
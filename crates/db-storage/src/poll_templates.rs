@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Polls which are prepared before a meeting
+//!
+//! A poll template either belongs to an event, or to the personal library of its creator if it has no event. Templates
+//! are launched by their id during the meeting.
+use crate::schema::poll_templates;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::{ExpressionMethods, Identifiable, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{EventId, TenantId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    PollTemplateId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Diesel poll_templates model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct PollTemplate {
+    pub id: PollTemplateId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// The event the poll is prepared for, unset for polls of the personal library
+    pub event_id: Option<EventId>,
+    pub topic: String,
+    pub live: bool,
+    pub choices: Vec<String>,
+    pub duration_secs: i32,
+    pub tenant_id: TenantId,
+}
+
+impl PollTemplate {
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, id: PollTemplateId) -> Result<Self> {
+        let query = poll_templates::table.filter(poll_templates::id.eq(id));
+
+        let template = query.get_result(conn)?;
+
+        Ok(template)
+    }
+
+    /// Get the polls prepared for the event, in the order they were created
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_event(conn: &mut DbConnection, event_id: EventId) -> Result<Vec<Self>> {
+        let query = poll_templates::table
+            .filter(poll_templates::event_id.eq(event_id))
+            .order_by(poll_templates::created_at.asc());
+
+        let templates = query.load(conn)?;
+
+        Ok(templates)
+    }
+
+    /// Get the polls of the personal library of the user, in the order they were created
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_library(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Self>> {
+        let query = poll_templates::table
+            .filter(poll_templates::created_by.eq(user_id))
+            .filter(poll_templates::event_id.is_null())
+            .order_by(poll_templates::created_at.asc());
+
+        let templates = query.load(conn)?;
+
+        Ok(templates)
+    }
+
+    /// Delete the poll with the given id prepared for the event
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_for_event(
+        conn: &mut DbConnection,
+        event_id: EventId,
+        id: PollTemplateId,
+    ) -> Result<()> {
+        let query = diesel::delete(
+            poll_templates::table
+                .filter(poll_templates::id.eq(id))
+                .filter(poll_templates::event_id.eq(event_id)),
+        );
+
+        let deleted = query.execute(conn)?;
+
+        if deleted == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+
+    /// Delete the poll with the given id from the personal library of the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_from_library(
+        conn: &mut DbConnection,
+        user_id: UserId,
+        id: PollTemplateId,
+    ) -> Result<()> {
+        let query = diesel::delete(
+            poll_templates::table
+                .filter(poll_templates::id.eq(id))
+                .filter(poll_templates::created_by.eq(user_id))
+                .filter(poll_templates::event_id.is_null()),
+        );
+
+        let deleted = query.execute(conn)?;
+
+        if deleted == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+
+    /// Delete all polls of the personal library of the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_library(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(
+            poll_templates::table
+                .filter(poll_templates::created_by.eq(user_id))
+                .filter(poll_templates::event_id.is_null()),
+        )
+        .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+/// Poll template insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = poll_templates)]
+pub struct NewPollTemplate {
+    pub created_by: UserId,
+    pub event_id: Option<EventId>,
+    pub topic: String,
+    pub live: bool,
+    pub choices: Vec<String>,
+    pub duration_secs: i32,
+    pub tenant_id: TenantId,
+}
+
+impl NewPollTemplate {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<PollTemplate> {
+        let query = self.insert_into(poll_templates::table);
+
+        let template = query.get_result(conn)?;
+
+        Ok(template)
+    }
+}
@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Post-processing of recordings
+//!
+//! Tracks the recordings which have been handed to the transcription worker. The transcript and the chapters created
+//! by the worker are stored as assets of the room and linked to the recording they belong to.
+use crate::schema::recording_transcriptions;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::deserialize::FromSql;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use types::core::{AssetId, RoomId};
+
+sql_enum!(
+    #[derive(PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    TranscriptionStatus,
+    "transcription_status",
+    TranscriptionStatusType,
+    {
+        Queued = b"queued",
+        Processing = b"processing",
+        Done = b"done",
+        Failed = b"failed",
+    }
+);
+
+/// Diesel recording_transcriptions model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = recording_transcriptions, primary_key(recording_id))]
+pub struct RecordingTranscription {
+    /// The asset of the recording
+    pub recording_id: AssetId,
+    pub room_id: RoomId,
+    pub status: TranscriptionStatus,
+    pub transcript_asset_id: Option<AssetId>,
+    pub chapters_asset_id: Option<AssetId>,
+    /// Reason reported by the worker if the transcription failed
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RecordingTranscription {
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, recording_id: AssetId) -> Result<Self> {
+        let query = recording_transcriptions::table
+            .filter(recording_transcriptions::recording_id.eq(recording_id));
+
+        let transcription = query.get_result(conn)?;
+
+        Ok(transcription)
+    }
+
+    /// Get the transcriptions of all recordings of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_room(conn: &mut DbConnection, room_id: RoomId) -> Result<Vec<Self>> {
+        let query = recording_transcriptions::table
+            .filter(recording_transcriptions::room_id.eq(room_id))
+            .order_by(recording_transcriptions::created_at.asc());
+
+        let transcriptions = query.load(conn)?;
+
+        Ok(transcriptions)
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = recording_transcriptions)]
+pub struct NewRecordingTranscription {
+    pub recording_id: AssetId,
+    pub room_id: RoomId,
+    pub status: TranscriptionStatus,
+}
+
+impl NewRecordingTranscription {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<RecordingTranscription> {
+        let query = self.insert_into(recording_transcriptions::table);
+
+        let transcription = query.get_result(conn)?;
+
+        Ok(transcription)
+    }
+}
+
+/// Diesel recording_transcriptions struct for updates
+///
+/// Is used in update queries. None fields will be ignored on update queries
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = recording_transcriptions)]
+pub struct UpdateRecordingTranscription {
+    pub status: Option<TranscriptionStatus>,
+    pub transcript_asset_id: Option<Option<AssetId>>,
+    pub chapters_asset_id: Option<Option<AssetId>>,
+    pub error: Option<Option<String>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UpdateRecordingTranscription {
+    #[tracing::instrument(err, skip_all)]
+    pub fn apply(
+        self,
+        conn: &mut DbConnection,
+        recording_id: AssetId,
+    ) -> Result<RecordingTranscription> {
+        let target = recording_transcriptions::table
+            .filter(recording_transcriptions::recording_id.eq(recording_id));
+
+        let transcription = diesel::update(target).set(self).get_result(conn)?;
+
+        Ok(transcription)
+    }
+}
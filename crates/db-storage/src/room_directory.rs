@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Entries of the public room directory
+//!
+//! Owners can list their room in the directory, which can be browsed by anyone when the directory is enabled.
+use crate::invites::Invite;
+use crate::lower;
+use crate::schema::{invites, room_directory_entries, rooms};
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Paginate, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{InviteCodeId, RoomId};
+
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = room_directory_entries, primary_key(room_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct RoomDirectoryEntry {
+    pub room_id: RoomId,
+    pub title: String,
+    pub description: String,
+    /// Free text describing when the room is used, e.g. "Every Tuesday at 18:00 CET"
+    pub schedule: Option<String>,
+    /// Invite code guests can use to join the room
+    pub invite_code: Option<InviteCodeId>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RoomDirectoryEntry {
+    /// Get the directory entry of the room, returns None if the room is not listed
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, room_id: RoomId) -> Result<Option<RoomDirectoryEntry>> {
+        let query =
+            room_directory_entries::table.filter(room_directory_entries::room_id.eq(room_id));
+
+        let entry = query.get_result(conn).optional()?;
+
+        Ok(entry)
+    }
+
+    /// Search the entries of rooms which are not in the trash, paginated
+    ///
+    /// Matches the search string against the title and description, an empty search string returns all entries.
+    /// Each entry is returned with its invite.
+    #[tracing::instrument(err, skip_all)]
+    pub fn search_paginated(
+        conn: &mut DbConnection,
+        search_str: &str,
+        limit: i64,
+        page: i64,
+    ) -> Result<(Vec<(RoomDirectoryEntry, Option<Invite>)>, i64)> {
+        // Remove all existing % in the search string to avoid manipulation of the LIKE query
+        let search_str = search_str.replace('%', "").trim().to_lowercase();
+        let like_query = format!("%{search_str}%");
+
+        let query = room_directory_entries::table
+            .inner_join(rooms::table)
+            .left_join(invites::table)
+            .select((
+                room_directory_entries::all_columns,
+                invites::all_columns.nullable(),
+            ))
+            .filter(rooms::deleted_at.is_null())
+            .filter(
+                lower(room_directory_entries::title)
+                    .like(&like_query)
+                    .or(lower(room_directory_entries::description).like(&like_query)),
+            )
+            .order_by((
+                room_directory_entries::title.asc(),
+                room_directory_entries::room_id.asc(),
+            ))
+            .paginate_by(limit, page);
+
+        let entries_with_total = query.load_and_count(conn)?;
+
+        Ok(entries_with_total)
+    }
+
+    /// Insert or replace the directory entry of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<RoomDirectoryEntry> {
+        let query = diesel::insert_into(room_directory_entries::table)
+            .values(&self)
+            .on_conflict(room_directory_entries::room_id)
+            .do_update()
+            .set(&self);
+
+        let entry = query.get_result(conn)?;
+
+        Ok(entry)
+    }
+
+    /// Remove the room from the directory
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_room(conn: &mut DbConnection, room_id: RoomId) -> Result<()> {
+        diesel::delete(room_directory_entries::table)
+            .filter(room_directory_entries::room_id.eq(room_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
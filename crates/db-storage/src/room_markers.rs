@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Chapter markers of meetings
+//!
+//! Moderators mark the beginning of a new topic during a meeting. The markers are linked to the recording of the room
+//! once it has been uploaded and to the statistics of the session when the room gets destroyed.
+use crate::room_statistics::RoomStatisticsId;
+use crate::schema::room_markers;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::{ExpressionMethods, Identifiable, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{AssetId, BreakoutRoomId, RoomId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    RoomMarkerId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Diesel room_markers model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = room_markers)]
+pub struct RoomMarker {
+    pub id: RoomMarkerId,
+    pub room_id: RoomId,
+    pub breakout_room_id: Option<BreakoutRoomId>,
+    /// Unset if the moderator is no longer registered
+    pub created_by: Option<UserId>,
+    pub title: String,
+    pub marked_at: DateTime<Utc>,
+    /// The recording the marker is a chapter of
+    pub recording_id: Option<AssetId>,
+    /// The session the marker was set in, unset while the session is running
+    pub room_statistics_id: Option<RoomStatisticsId>,
+}
+
+impl RoomMarker {
+    /// Get the markers of the running session of the room, including the markers of its breakout rooms
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_running_session(
+        conn: &mut DbConnection,
+        room_id: RoomId,
+    ) -> Result<Vec<Self>> {
+        let query = room_markers::table
+            .filter(room_markers::room_id.eq(room_id))
+            .filter(room_markers::room_statistics_id.is_null())
+            .order_by(room_markers::marked_at.asc());
+
+        let markers = query.load(conn)?;
+
+        Ok(markers)
+    }
+
+    /// Get the chapters of the recording
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_recording(
+        conn: &mut DbConnection,
+        recording_id: AssetId,
+    ) -> Result<Vec<Self>> {
+        let query = room_markers::table
+            .filter(room_markers::recording_id.eq(recording_id))
+            .order_by(room_markers::marked_at.asc());
+
+        let markers = query.load(conn)?;
+
+        Ok(markers)
+    }
+
+    /// Get the markers set in the session
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_session(
+        conn: &mut DbConnection,
+        room_statistics_id: RoomStatisticsId,
+    ) -> Result<Vec<Self>> {
+        let query = room_markers::table
+            .filter(room_markers::room_statistics_id.eq(room_statistics_id))
+            .order_by(room_markers::marked_at.asc());
+
+        let markers = query.load(conn)?;
+
+        Ok(markers)
+    }
+}
+
+/// Link the markers of the (breakout) room which are not part of a recording yet to the recording
+///
+/// Returns the number of linked markers
+#[tracing::instrument(err, skip_all)]
+pub fn link_to_recording(
+    conn: &mut DbConnection,
+    room_id: RoomId,
+    breakout_room_id: Option<BreakoutRoomId>,
+    recording_id: AssetId,
+) -> Result<usize> {
+    let target = room_markers::table
+        .filter(room_markers::room_id.eq(room_id))
+        .filter(room_markers::recording_id.is_null());
+
+    let linked = if let Some(breakout_room_id) = breakout_room_id {
+        diesel::update(target.filter(room_markers::breakout_room_id.eq(breakout_room_id)))
+            .set(room_markers::recording_id.eq(recording_id))
+            .execute(conn)?
+    } else {
+        diesel::update(target.filter(room_markers::breakout_room_id.is_null()))
+            .set(room_markers::recording_id.eq(recording_id))
+            .execute(conn)?
+    };
+
+    Ok(linked)
+}
+
+/// Link the markers of the running session of the room to the statistics of the session
+#[tracing::instrument(err, skip_all)]
+pub fn link_to_session(
+    conn: &mut DbConnection,
+    room_id: RoomId,
+    room_statistics_id: RoomStatisticsId,
+) -> Result<()> {
+    let target = room_markers::table
+        .filter(room_markers::room_id.eq(room_id))
+        .filter(room_markers::room_statistics_id.is_null());
+
+    diesel::update(target)
+        .set(room_markers::room_statistics_id.eq(room_statistics_id))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Room marker insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = room_markers)]
+pub struct NewRoomMarker {
+    pub room_id: RoomId,
+    pub breakout_room_id: Option<BreakoutRoomId>,
+    pub created_by: Option<UserId>,
+    pub title: String,
+    pub marked_at: DateTime<Utc>,
+}
+
+impl NewRoomMarker {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<RoomMarker> {
+        let query = self.insert_into(room_markers::table);
+
+        let marker = query.get_result(conn)?;
+
+        Ok(marker)
+    }
+}
@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Media settings of rooms
+//!
+//! Overrides the audio level thresholds the media module configures for the publishers of a room. Unset values fall
+//! back to the values configured for the media module. Also contains the push-to-talk policy of the room.
+use crate::schema::room_media_settings;
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::RoomId;
+
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = room_media_settings, primary_key(room_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct RoomMediaSettings {
+    pub room_id: RoomId,
+    /// Number of packets with the audio level needed to detect a speaking participant
+    pub speaker_focus_packets: Option<i64>,
+    /// Average audio level needed per packet, from 127 (muted) to 0 (loud)
+    pub speaker_focus_level: Option<i64>,
+    /// Only transmit the audio of non-moderators while they hold the talk button
+    pub push_to_talk: bool,
+    /// Maximum time in seconds the talk button can be held before the participant is muted
+    pub push_to_talk_max_hold_secs: Option<i64>,
+}
+
+impl RoomMediaSettings {
+    /// Get the media settings of the room, returns None if the room uses the defaults
+    #[tracing::instrument(err, skip_all)]
+    pub fn get(conn: &mut DbConnection, room_id: RoomId) -> Result<Option<RoomMediaSettings>> {
+        let query = room_media_settings::table.filter(room_media_settings::room_id.eq(room_id));
+
+        let settings = query.get_result(conn).optional()?;
+
+        Ok(settings)
+    }
+
+    /// Insert or replace the media settings of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn upsert(self, conn: &mut DbConnection) -> Result<RoomMediaSettings> {
+        let query = diesel::insert_into(room_media_settings::table)
+            .values(&self)
+            .on_conflict(room_media_settings::room_id)
+            .do_update()
+            .set(&self);
+
+        let settings = query.get_result(conn)?;
+
+        Ok(settings)
+    }
+
+    /// Delete the media settings of the room, resetting it to the defaults
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_by_room(conn: &mut DbConnection, room_id: RoomId) -> Result<()> {
+        diesel::delete(room_media_settings::table)
+            .filter(room_media_settings::room_id.eq(room_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Owners of rooms
+//!
+//! Every room has at least one owner, the creator of the room is added as owner when the room is inserted. The
+//! `created_by` field of the room refers to the primary owner, whose tariff applies to the room.
+use crate::rooms::Room;
+use crate::schema::{room_owners, rooms, users};
+use crate::users::User;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use types::core::{RoomId, UserId};
+
+#[derive(Debug, Clone, Queryable)]
+pub struct RoomOwner {
+    pub room_id: RoomId,
+    pub user_id: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RoomOwner {
+    /// Get all owners of the room in the order they have been added
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_room(conn: &mut DbConnection, room_id: RoomId) -> Result<Vec<User>> {
+        let query = room_owners::table
+            .inner_join(users::table)
+            .filter(room_owners::room_id.eq(room_id))
+            .order_by(room_owners::created_at.asc())
+            .then_order_by(users::id)
+            .select(users::all_columns);
+
+        let owners = query.load(conn)?;
+
+        Ok(owners)
+    }
+
+    /// Get the ids of all owners of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_ids_for_room(conn: &mut DbConnection, room_id: RoomId) -> Result<Vec<UserId>> {
+        let query = room_owners::table
+            .select(room_owners::user_id)
+            .filter(room_owners::room_id.eq(room_id));
+
+        let owner_ids = query.load(conn)?;
+
+        Ok(owner_ids)
+    }
+
+    /// Get the ids of all rooms owned by the user
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_room_ids_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<RoomId>> {
+        let query = room_owners::table
+            .select(room_owners::room_id)
+            .filter(room_owners::user_id.eq(user_id));
+
+        let room_ids = query.load(conn)?;
+
+        Ok(room_ids)
+    }
+
+    /// Returns true if the user is an owner of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn is_owner(conn: &mut DbConnection, room_id: RoomId, user_id: UserId) -> Result<bool> {
+        let query = diesel::dsl::select(diesel::dsl::exists(
+            room_owners::table
+                .filter(room_owners::room_id.eq(room_id))
+                .filter(room_owners::user_id.eq(user_id)),
+        ));
+
+        let is_owner = query.get_result(conn)?;
+
+        Ok(is_owner)
+    }
+
+    /// Remove the user from the owners of the room
+    ///
+    /// Returns false if the user was not an owner of the room.
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete(conn: &mut DbConnection, room_id: RoomId, user_id: UserId) -> Result<bool> {
+        let deleted = diesel::delete(room_owners::table)
+            .filter(room_owners::room_id.eq(room_id))
+            .filter(room_owners::user_id.eq(user_id))
+            .execute(conn)?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Remove the user from the owners of all rooms
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_all_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+        diesel::delete(room_owners::table)
+            .filter(room_owners::user_id.eq(user_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Make the user the primary owner of the room
+    ///
+    /// The user is added to the owners if they are not an owner yet, the previous primary owner stays an owner.
+    #[tracing::instrument(err, skip_all)]
+    pub fn transfer(conn: &mut DbConnection, room_id: RoomId, user_id: UserId) -> Result<Room> {
+        conn.transaction(|conn| {
+            NewRoomOwner { room_id, user_id }.insert(conn)?;
+
+            let room = diesel::update(rooms::table.filter(rooms::id.eq(room_id)))
+                .set(rooms::created_by.eq(user_id))
+                .get_result(conn)?;
+
+            Ok(room)
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = room_owners)]
+pub struct NewRoomOwner {
+    pub room_id: RoomId,
+    pub user_id: UserId,
+}
+
+impl NewRoomOwner {
+    /// Add the owner, returns false if the user already is an owner of the room
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<bool> {
+        let inserted = self
+            .insert_into(room_owners::table)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(inserted > 0)
+    }
+}
@@ -0,0 +1,260 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Usage statistics of past room sessions
+//!
+//! A session starts when the first participant enters a room and ends when the room gets destroyed.
+use crate::schema::{room_statistics, room_statistics_participants, users};
+use crate::users::User;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::sql_types::{BigInt, Integer, Text, Timestamptz};
+use diesel::{
+    ExpressionMethods, Identifiable, Insertable, OptionalExtension, QueryDsl, Queryable,
+    QueryableByName, RunQueryDsl,
+};
+use std::collections::HashMap;
+use types::core::{RoomId, TenantId, UserId};
+
+types::diesel_newtype! {
+    #[derive(Copy)]
+    RoomStatisticsId(uuid::Uuid) => diesel::sql_types::Uuid
+}
+
+/// Diesel room_statistics model
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = room_statistics)]
+pub struct RoomStatistics {
+    pub id: RoomStatisticsId,
+    pub room_id: RoomId,
+    pub tenant_id: TenantId,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Highest number of participants inside the room at the same time, including breakout and waiting rooms
+    pub peak_participants: i32,
+    /// Sum of the time every participant spent inside the room
+    pub participant_minutes: i64,
+    /// Namespaces of the signaling modules used in the session
+    pub modules: Vec<String>,
+}
+
+/// Usage of a single room, aggregated over its sessions
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct RoomUsage {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub room_id: RoomId,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub participant_minutes: i64,
+    #[diesel(sql_type = Integer)]
+    pub peak_participants: i32,
+    #[diesel(sql_type = Timestamptz)]
+    pub last_ended_at: DateTime<Utc>,
+}
+
+/// Number of sessions a signaling module was used in
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct ModuleUsage {
+    #[diesel(sql_type = Text)]
+    pub module: String,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+}
+
+impl RoomStatistics {
+    /// Get the usage of every room of the tenant, aggregated over the sessions which started inside the given time
+    /// range
+    ///
+    /// The rooms with the most participant minutes come first.
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_room_usage_started_between(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<RoomUsage>> {
+        let query = diesel::sql_query(
+            "SELECT room_id, \
+                COUNT(*) AS sessions, \
+                SUM(participant_minutes)::BIGINT AS participant_minutes, \
+                MAX(peak_participants) AS peak_participants, \
+                MAX(ended_at) AS last_ended_at \
+            FROM room_statistics \
+            WHERE tenant_id = $1 AND started_at >= $2 AND started_at < $3 \
+            GROUP BY room_id \
+            ORDER BY participant_minutes DESC, room_id",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to);
+
+        let usage = query.load(conn)?;
+
+        Ok(usage)
+    }
+
+    /// Get the number of sessions of the tenant which started inside the given time range, per signaling module
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_module_usage_started_between(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ModuleUsage>> {
+        let query = diesel::sql_query(
+            "SELECT module, COUNT(*) AS sessions \
+            FROM room_statistics, UNNEST(modules) AS module \
+            WHERE tenant_id = $1 AND started_at >= $2 AND started_at < $3 \
+            GROUP BY module \
+            ORDER BY module",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to);
+
+        let usage = query.load(conn)?;
+
+        Ok(usage)
+    }
+
+    /// Get the statistics of the last session of the room, returns None if the room has never been used
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_latest_for_room(conn: &mut DbConnection, room_id: RoomId) -> Result<Option<Self>> {
+        let query = room_statistics::table
+            .filter(room_statistics::room_id.eq(room_id))
+            .order_by(room_statistics::ended_at.desc());
+
+        let statistics = query.first(conn).optional()?;
+
+        Ok(statistics)
+    }
+
+    /// Get the statistics of all sessions the user took part in, the latest sessions come first
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_participant(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Self>> {
+        let query = room_statistics::table
+            .inner_join(room_statistics_participants::table)
+            .filter(room_statistics_participants::user_id.eq(user_id))
+            .order_by(room_statistics::ended_at.desc())
+            .select(room_statistics::all_columns);
+
+        let statistics = query.load(conn)?;
+
+        Ok(statistics)
+    }
+}
+
+/// Returns the users which took part in the same sessions as the given user, which ended after `since`
+///
+/// Every user is returned together with the end of the latest of these sessions, the users who were met most recently
+/// come first.
+#[tracing::instrument(err, skip_all)]
+pub fn get_recently_met_users(
+    conn: &mut DbConnection,
+    user_id: UserId,
+    since: DateTime<Utc>,
+) -> Result<Vec<(User, DateTime<Utc>)>> {
+    let session_ids: Vec<RoomStatisticsId> = room_statistics_participants::table
+        .inner_join(room_statistics::table)
+        .filter(room_statistics_participants::user_id.eq(user_id))
+        .filter(room_statistics::ended_at.gt(since))
+        .select(room_statistics::id)
+        .load(conn)?;
+
+    if session_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let participants: Vec<(User, DateTime<Utc>)> = room_statistics_participants::table
+        .inner_join(room_statistics::table)
+        .inner_join(users::table)
+        .filter(room_statistics_participants::room_statistics_id.eq_any(session_ids))
+        .filter(room_statistics_participants::user_id.ne(user_id))
+        .select((users::all_columns, room_statistics::ended_at))
+        .load(conn)?;
+
+    let mut last_met: HashMap<UserId, (User, DateTime<Utc>)> = HashMap::new();
+
+    for (user, ended_at) in participants {
+        let entry = last_met.entry(user.id).or_insert((user, ended_at));
+        entry.1 = entry.1.max(ended_at);
+    }
+
+    let mut users: Vec<(User, DateTime<Utc>)> = last_met.into_values().collect();
+    users.sort_by(|(user_a, met_a), (user_b, met_b)| {
+        met_b.cmp(met_a).then_with(|| user_a.id.cmp(&user_b.id))
+    });
+
+    Ok(users)
+}
+
+/// Room statistics insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = room_statistics)]
+pub struct NewRoomStatistics {
+    pub room_id: RoomId,
+    pub tenant_id: TenantId,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub peak_participants: i32,
+    pub participant_minutes: i64,
+    pub modules: Vec<String>,
+}
+
+impl NewRoomStatistics {
+    #[tracing::instrument(err, skip_all)]
+    pub fn insert(self, conn: &mut DbConnection) -> Result<RoomStatistics> {
+        let query = self.insert_into(room_statistics::table);
+
+        let statistics = query.get_result(conn)?;
+
+        Ok(statistics)
+    }
+}
+
+/// A registered user which took part in a session
+#[derive(Debug, Insertable)]
+#[diesel(table_name = room_statistics_participants)]
+pub struct NewRoomStatisticsParticipant {
+    pub room_statistics_id: RoomStatisticsId,
+    pub user_id: UserId,
+}
+
+/// Record the registered users which took part in the session
+#[tracing::instrument(err, skip_all)]
+pub fn insert_participants(
+    conn: &mut DbConnection,
+    room_statistics_id: RoomStatisticsId,
+    user_ids: &[UserId],
+) -> Result<()> {
+    let participants: Vec<NewRoomStatisticsParticipant> = user_ids
+        .iter()
+        .map(|&user_id| NewRoomStatisticsParticipant {
+            room_statistics_id,
+            user_id,
+        })
+        .collect();
+
+    if participants.is_empty() {
+        return Ok(());
+    }
+
+    diesel::insert_into(room_statistics_participants::table)
+        .values(participants)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Remove the user from the participants of all sessions
+#[tracing::instrument(err, skip_all)]
+pub fn delete_participant(conn: &mut DbConnection, user_id: UserId) -> Result<()> {
+    diesel::delete(room_statistics_participants::table)
+        .filter(room_statistics_participants::user_id.eq(user_id))
+        .execute(conn)?;
+
+    Ok(())
+}
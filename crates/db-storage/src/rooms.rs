@@ -4,13 +4,15 @@
 
 //! Contains the room specific database structs and queries
 use crate::diesel::RunQueryDsl;
+use crate::room_owners::NewRoomOwner;
+use crate::schema::events;
 use crate::schema::rooms;
 use crate::schema::users;
 use crate::tariffs::Tariff;
 use crate::users::User;
 use chrono::{DateTime, Utc};
 use database::DbConnection;
-use database::{Paginate, Result};
+use database::{DatabaseError, Paginate, Result};
 use diesel::prelude::*;
 use diesel::{ExpressionMethods, QueryDsl};
 use diesel::{Identifiable, Queryable};
@@ -32,13 +34,23 @@ pub struct Room {
     pub password: Option<String>,
     pub waiting_room: bool,
     pub tenant_id: TenantId,
+    /// Set when the room has been moved to the trash
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Language tag of the documents and messages generated for the room
+    pub locale: Option<String>,
+    /// Region of the media servers the room is pinned to
+    pub region: Option<String>,
+    /// Participants who are not moderators only see the moderators of the room
+    pub webinar_mode: bool,
 }
 
 impl Room {
     /// Select a room using the given id
     #[tracing::instrument(err, skip_all)]
     pub fn get(conn: &mut DbConnection, id: RoomId) -> Result<Self> {
-        let query = rooms::table.filter(rooms::id.eq(id));
+        let query = rooms::table
+            .filter(rooms::id.eq(id))
+            .filter(rooms::deleted_at.is_null());
 
         let room: Room = query.get_result(conn)?;
 
@@ -50,6 +62,7 @@ impl Room {
     pub fn get_with_user(conn: &mut DbConnection, id: RoomId) -> Result<(Self, User)> {
         let query = rooms::table
             .filter(rooms::id.eq(id))
+            .filter(rooms::deleted_at.is_null())
             .inner_join(users::table);
 
         let result: (Room, User) = query.get_result(conn)?;
@@ -61,6 +74,7 @@ impl Room {
     #[tracing::instrument(err, skip_all)]
     pub fn get_all_with_creator(conn: &mut DbConnection) -> Result<Vec<(Room, User)>> {
         let query = rooms::table
+            .filter(rooms::deleted_at.is_null())
             .order_by(rooms::id.desc())
             .inner_join(users::table);
 
@@ -79,6 +93,7 @@ impl Room {
         let query = rooms::table
             .inner_join(users::table)
             .select((rooms::all_columns, users::all_columns))
+            .filter(rooms::deleted_at.is_null())
             .order_by(rooms::id.desc())
             .paginate_by(limit, page);
 
@@ -99,6 +114,7 @@ impl Room {
             .inner_join(users::table)
             .select((rooms::all_columns, users::all_columns))
             .filter(rooms::id.eq_any(ids))
+            .filter(rooms::deleted_at.is_null())
             .order_by(rooms::id.desc())
             .paginate_by(limit, page);
 
@@ -107,6 +123,18 @@ impl Room {
         Ok(rooms_with_total)
     }
 
+    /// Select all rooms created by the given user, including rooms in the trash
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_created_by(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Room>> {
+        let query = rooms::table
+            .filter(rooms::created_by.eq(user_id))
+            .order_by(rooms::created_at.asc());
+
+        let rooms = query.load(conn)?;
+
+        Ok(rooms)
+    }
+
     /// Get the room's tariff
     #[tracing::instrument(err, skip_all)]
     pub fn get_tariff(&self, conn: &mut DbConnection) -> Result<Tariff> {
@@ -128,6 +156,92 @@ impl Room {
     pub fn delete(self, conn: &mut DbConnection) -> Result<()> {
         Self::delete_by_id(conn, self.id)
     }
+
+    /// Move a room and all events in the room to the trash
+    #[tracing::instrument(err, skip_all)]
+    pub fn soft_delete_by_id(conn: &mut DbConnection, room_id: RoomId) -> Result<()> {
+        conn.transaction(|conn| {
+            let now = Utc::now();
+
+            let deleted_rooms = diesel::update(
+                rooms::table
+                    .filter(rooms::id.eq(room_id))
+                    .filter(rooms::deleted_at.is_null()),
+            )
+            .set(rooms::deleted_at.eq(now))
+            .execute(conn)?;
+
+            if deleted_rooms == 0 {
+                return Err(DatabaseError::NotFound);
+            }
+
+            diesel::update(
+                events::table
+                    .filter(events::room.eq(room_id))
+                    .filter(events::deleted_at.is_null()),
+            )
+            .set(events::deleted_at.eq(now))
+            .execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    /// Restore a room created by the given user from the trash
+    ///
+    /// Events which have been moved to the trash together with the room are restored as well.
+    #[tracing::instrument(err, skip_all)]
+    pub fn restore(conn: &mut DbConnection, room_id: RoomId, created_by: UserId) -> Result<Room> {
+        conn.transaction(|conn| {
+            let room: Room = rooms::table
+                .filter(rooms::id.eq(room_id))
+                .filter(rooms::created_by.eq(created_by))
+                .filter(rooms::deleted_at.is_not_null())
+                .get_result(conn)?;
+
+            diesel::update(
+                events::table
+                    .filter(events::room.eq(room_id))
+                    .filter(events::deleted_at.eq(room.deleted_at)),
+            )
+            .set(events::deleted_at.eq(None::<DateTime<Utc>>))
+            .execute(conn)?;
+
+            let room = diesel::update(rooms::table.filter(rooms::id.eq(room_id)))
+                .set(rooms::deleted_at.eq(None::<DateTime<Utc>>))
+                .get_result(conn)?;
+
+            Ok(room)
+        })
+    }
+
+    /// Select all rooms in the trash created by the given user
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_deleted_for_user(conn: &mut DbConnection, user_id: UserId) -> Result<Vec<Room>> {
+        let query = rooms::table
+            .filter(rooms::created_by.eq(user_id))
+            .filter(rooms::deleted_at.is_not_null())
+            .order_by(rooms::deleted_at.desc());
+
+        let rooms = query.load(conn)?;
+
+        Ok(rooms)
+    }
+
+    /// Select the ids of all rooms which have been moved to the trash before the given point in time
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_ids_deleted_before(
+        conn: &mut DbConnection,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<RoomId>> {
+        let query = rooms::table
+            .select(rooms::id)
+            .filter(rooms::deleted_at.lt(deleted_before));
+
+        let room_ids = query.load(conn)?;
+
+        Ok(room_ids)
+    }
 }
 
 /// Diesel insertable room struct
@@ -140,14 +254,26 @@ pub struct NewRoom {
     pub password: Option<String>,
     pub waiting_room: bool,
     pub tenant_id: TenantId,
+    pub locale: Option<String>,
+    pub region: Option<String>,
+    pub webinar_mode: bool,
 }
 
 impl NewRoom {
+    /// Insert the room and add its creator to the owners of the room
     #[tracing::instrument(err, skip_all)]
     pub fn insert(self, conn: &mut DbConnection) -> Result<Room> {
-        let room = self.insert_into(rooms::table).get_result(conn)?;
+        conn.transaction(|conn| {
+            let room: Room = self.insert_into(rooms::table).get_result(conn)?;
 
-        Ok(room)
+            NewRoomOwner {
+                room_id: room.id,
+                user_id: room.created_by,
+            }
+            .insert(conn)?;
+
+            Ok(room)
+        })
     }
 }
 
@@ -159,12 +285,17 @@ impl NewRoom {
 pub struct UpdateRoom {
     pub password: Option<Option<String>>,
     pub waiting_room: Option<bool>,
+    pub locale: Option<Option<String>>,
+    pub region: Option<Option<String>>,
+    pub webinar_mode: Option<bool>,
 }
 
 impl UpdateRoom {
     #[tracing::instrument(err, skip_all)]
     pub fn apply(self, conn: &mut DbConnection, room_id: RoomId) -> Result<Room> {
-        let target = rooms::table.filter(rooms::id.eq(&room_id));
+        let target = rooms::table
+            .filter(rooms::id.eq(&room_id))
+            .filter(rooms::deleted_at.is_null());
         let room = diesel::update(target).set(self).get_result(conn)?;
 
         Ok(room)
@@ -1,3 +1,18 @@
+table! {
+    use crate::sql_types::*;
+
+    action_items (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        breakout_room_id -> Nullable<Uuid>,
+        kind -> Action_item_kind,
+        text -> Text,
+        assignee -> Nullable<Text>,
+        due_date -> Nullable<Date>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     use crate::sql_types::*;
 
@@ -9,6 +24,52 @@ table! {
         kind -> Varchar,
         filename -> Varchar,
         tenant_id -> Uuid,
+        scan_status -> Asset_scan_status,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    calendar_link_events (link_id, event_id) {
+        link_id -> Uuid,
+        event_id -> Uuid,
+        external_id -> Text,
+        synced_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    calendar_links (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Calendar_provider,
+        access_token -> Bytea,
+        refresh_token -> Bytea,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        synced_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    call_feedback (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        tenant_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        rating -> Int2,
+        issues -> Array<Text>,
+        comment -> Nullable<Text>,
+        participants -> Int4,
+        duration_secs -> Int8,
+        webrtc_down_count -> Int4,
+        slow_link_count -> Int4,
     }
 }
 
@@ -27,6 +88,16 @@ table! {
     }
 }
 
+table! {
+    use crate::sql_types::*;
+
+    contact_favorites (user_id, contact_id) {
+        user_id -> Uuid,
+        contact_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     use crate::sql_types::*;
 
@@ -35,6 +106,9 @@ table! {
         email -> Varchar,
         created_by -> Uuid,
         created_at -> Timestamptz,
+        status -> Event_invite_status,
+        comment -> Nullable<Text>,
+        responded_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -78,6 +152,44 @@ table! {
         created_by -> Uuid,
         created_at -> Timestamptz,
         status -> Event_invite_status,
+        comment -> Nullable<Text>,
+        responded_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    event_matrix_bridges (event_id) {
+        event_id -> Uuid,
+        matrix_room_id -> Text,
+        updated_by -> Uuid,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    event_published_results (id) {
+        id -> Uuid,
+        event_id -> Uuid,
+        published_by -> Uuid,
+        published_at -> Timestamptz,
+        title -> Text,
+        results -> Jsonb,
+        legal_vote_id -> Nullable<Uuid>,
+        tenant_id -> Uuid,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    event_results_tokens (event_id) {
+        event_id -> Uuid,
+        nonce -> Uuid,
+        rotated_at -> Timestamptz,
     }
 }
 
@@ -105,6 +217,8 @@ table! {
         recurrence_pattern -> Nullable<Varchar>,
         is_adhoc -> Bool,
         tenant_id -> Uuid,
+        deleted_at -> Nullable<Timestamptz>,
+        external_meeting_url -> Nullable<Text>,
     }
 }
 
@@ -128,6 +242,21 @@ table! {
     }
 }
 
+table! {
+    use crate::sql_types::*;
+
+    impersonation_log (id) {
+        id -> Uuid,
+        impersonator_id -> Uuid,
+        impersonated_id -> Uuid,
+        method -> Text,
+        path -> Text,
+        request_id -> Nullable<Text>,
+        created_at -> Timestamptz,
+        tenant_id -> Uuid,
+    }
+}
+
 table! {
     use crate::sql_types::*;
 
@@ -144,6 +273,17 @@ table! {
     }
 }
 
+table! {
+    use crate::sql_types::*;
+
+    ldap_sessions (token_hash) {
+        token_hash -> Bytea,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
 table! {
     use crate::sql_types::*;
 
@@ -155,6 +295,50 @@ table! {
         room -> Nullable<Uuid>,
         protocol -> Jsonb,
         tenant_id -> Uuid,
+        protocol_asset_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    mail_templates (tenant_id, kind) {
+        tenant_id -> Uuid,
+        kind -> Mail_template_kind,
+        subject -> Text,
+        body -> Text,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    poll_templates (id) {
+        id -> Uuid,
+        created_by -> Uuid,
+        created_at -> Timestamptz,
+        event_id -> Nullable<Uuid>,
+        topic -> Text,
+        live -> Bool,
+        choices -> Array<Text>,
+        duration_secs -> Int4,
+        tenant_id -> Uuid,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    recording_transcriptions (recording_id) {
+        recording_id -> Uuid,
+        room_id -> Uuid,
+        status -> Transcription_status,
+        transcript_asset_id -> Nullable<Uuid>,
+        chapters_asset_id -> Nullable<Uuid>,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -175,6 +359,92 @@ table! {
     room_assets (room_id, asset_id) {
         room_id -> Uuid,
         asset_id -> Uuid,
+        breakout_room_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_brandings (room_id) {
+        room_id -> Uuid,
+        logo_asset_id -> Nullable<Uuid>,
+        primary_color -> Nullable<Varchar>,
+        secondary_color -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_directory_entries (room_id) {
+        room_id -> Uuid,
+        title -> Varchar,
+        description -> Text,
+        schedule -> Nullable<Varchar>,
+        invite_code -> Nullable<Uuid>,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_markers (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        breakout_room_id -> Nullable<Uuid>,
+        created_by -> Nullable<Uuid>,
+        title -> Text,
+        marked_at -> Timestamptz,
+        recording_id -> Nullable<Uuid>,
+        room_statistics_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_media_settings (room_id) {
+        room_id -> Uuid,
+        speaker_focus_packets -> Nullable<Int8>,
+        speaker_focus_level -> Nullable<Int8>,
+        push_to_talk -> Bool,
+        push_to_talk_max_hold_secs -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_owners (room_id, user_id) {
+        room_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_statistics (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        tenant_id -> Uuid,
+        started_at -> Timestamptz,
+        ended_at -> Timestamptz,
+        peak_participants -> Int4,
+        participant_minutes -> Int8,
+        modules -> Array<Text>,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    room_statistics_participants (room_statistics_id, user_id) {
+        room_statistics_id -> Uuid,
+        user_id -> Uuid,
     }
 }
 
@@ -189,6 +459,25 @@ table! {
         password -> Nullable<Varchar>,
         waiting_room -> Bool,
         tenant_id -> Uuid,
+        deleted_at -> Nullable<Timestamptz>,
+        locale -> Nullable<Varchar>,
+        region -> Nullable<Varchar>,
+        webinar_mode -> Bool,
+    }
+}
+
+table! {
+    use crate::sql_types::*;
+
+    scheduled_legal_votes (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        created_by -> Uuid,
+        created_at -> Timestamptz,
+        agenda_item -> Nullable<Text>,
+        parameters -> Jsonb,
+        legal_vote_id -> Nullable<Uuid>,
+        tenant_id -> Uuid,
     }
 }
 
@@ -217,6 +506,17 @@ table! {
     }
 }
 
+table! {
+    use crate::sql_types::*;
+
+    tenant_brandings (tenant_id) {
+        tenant_id -> Uuid,
+        logo_url -> Nullable<Text>,
+        primary_color -> Nullable<Varchar>,
+        secondary_color -> Nullable<Varchar>,
+    }
+}
+
 table! {
     use crate::sql_types::*;
 
@@ -259,7 +559,34 @@ table! {
     }
 }
 
+table! {
+    use crate::sql_types::*;
+
+    webrtc_stats (id) {
+        id -> Int8,
+        recorded_at -> Timestamptz,
+        received_at -> Timestamptz,
+        room_id -> Uuid,
+        participant_id -> Uuid,
+        media_session_type -> Varchar,
+        source -> Uuid,
+        direction -> Varchar,
+        packets -> Int8,
+        packets_lost -> Int8,
+        jitter_ms -> Nullable<Float8>,
+        round_trip_time_ms -> Nullable<Float8>,
+        bitrate_kbps -> Nullable<Float8>,
+    }
+}
+
+joinable!(action_items -> rooms (room_id));
 joinable!(assets -> tenants (tenant_id));
+joinable!(calendar_link_events -> calendar_links (link_id));
+joinable!(calendar_link_events -> events (event_id));
+joinable!(calendar_links -> users (user_id));
+joinable!(call_feedback -> rooms (room_id));
+joinable!(call_feedback -> tenants (tenant_id));
+joinable!(call_feedback -> users (user_id));
 joinable!(event_email_invites -> events (event_id));
 joinable!(event_email_invites -> users (created_by));
 joinable!(event_exceptions -> events (event_id));
@@ -267,42 +594,101 @@ joinable!(event_exceptions -> users (created_by));
 joinable!(event_favorites -> events (event_id));
 joinable!(event_favorites -> users (user_id));
 joinable!(event_invites -> events (event_id));
+joinable!(event_matrix_bridges -> events (event_id));
+joinable!(event_matrix_bridges -> users (updated_by));
+joinable!(event_published_results -> events (event_id));
+joinable!(event_published_results -> legal_votes (legal_vote_id));
+joinable!(event_published_results -> tenants (tenant_id));
+joinable!(event_published_results -> users (published_by));
+joinable!(event_results_tokens -> events (event_id));
 joinable!(events -> rooms (room));
 joinable!(events -> tenants (tenant_id));
 joinable!(external_tariffs -> tariffs (tariff_id));
 joinable!(groups -> tenants (tenant_id));
+joinable!(impersonation_log -> tenants (tenant_id));
 joinable!(invites -> rooms (room));
+joinable!(ldap_sessions -> users (user_id));
+joinable!(legal_votes -> assets (protocol_asset_id));
 joinable!(legal_votes -> rooms (room));
 joinable!(legal_votes -> tenants (tenant_id));
 joinable!(legal_votes -> users (created_by));
+joinable!(mail_templates -> tenants (tenant_id));
+joinable!(poll_templates -> events (event_id));
+joinable!(poll_templates -> tenants (tenant_id));
+joinable!(poll_templates -> users (created_by));
+joinable!(recording_transcriptions -> rooms (room_id));
 joinable!(room_assets -> assets (asset_id));
 joinable!(room_assets -> rooms (room_id));
+joinable!(room_brandings -> assets (logo_asset_id));
+joinable!(room_brandings -> rooms (room_id));
+joinable!(room_directory_entries -> invites (invite_code));
+joinable!(room_directory_entries -> rooms (room_id));
+joinable!(room_markers -> assets (recording_id));
+joinable!(room_markers -> room_statistics (room_statistics_id));
+joinable!(room_markers -> rooms (room_id));
+joinable!(room_markers -> users (created_by));
+joinable!(room_media_settings -> rooms (room_id));
+joinable!(room_owners -> rooms (room_id));
+joinable!(room_owners -> users (user_id));
+joinable!(room_statistics -> rooms (room_id));
+joinable!(room_statistics -> tenants (tenant_id));
+joinable!(room_statistics_participants -> room_statistics (room_statistics_id));
+joinable!(room_statistics_participants -> users (user_id));
 joinable!(rooms -> tenants (tenant_id));
 joinable!(rooms -> users (created_by));
+joinable!(scheduled_legal_votes -> legal_votes (legal_vote_id));
+joinable!(scheduled_legal_votes -> rooms (room_id));
+joinable!(scheduled_legal_votes -> tenants (tenant_id));
+joinable!(scheduled_legal_votes -> users (created_by));
 joinable!(sip_configs -> rooms (room));
+joinable!(tenant_brandings -> tenants (tenant_id));
 joinable!(user_groups -> groups (group_id));
 joinable!(user_groups -> users (user_id));
 joinable!(users -> tariffs (tariff_id));
 joinable!(users -> tenants (tenant_id));
+joinable!(webrtc_stats -> rooms (room_id));
 
 allow_tables_to_appear_in_same_query!(
+    action_items,
     assets,
+    calendar_link_events,
+    calendar_links,
+    call_feedback,
     casbin_rule,
+    contact_favorites,
     event_email_invites,
     event_exceptions,
     event_favorites,
     event_invites,
+    event_matrix_bridges,
+    event_published_results,
+    event_results_tokens,
     events,
     external_tariffs,
     groups,
+    impersonation_log,
     invites,
+    ldap_sessions,
     legal_votes,
+    mail_templates,
+    poll_templates,
+    recording_transcriptions,
     refinery_schema_history,
     room_assets,
+    room_brandings,
+    room_directory_entries,
+    room_markers,
+    room_media_settings,
+    room_owners,
+    room_statistics,
+    room_statistics_participants,
     rooms,
+    scheduled_legal_votes,
     sip_configs,
     tariffs,
+    tenant_brandings,
     tenants,
     user_groups,
     users,
+    webrtc_stats,
 );
@@ -15,6 +15,9 @@ use diesel::{
 use std::fmt;
 use types::core::{TariffId, TenantId, UserId};
 
+/// Display name of users whose personal data has been erased
+pub const ANONYMIZED_DISPLAY_NAME: &str = "Deleted user";
+
 types::diesel_newtype! {
     #[derive(Copy)]
     SerialUserId(i64) => diesel::sql_types::BigInt
@@ -218,6 +221,19 @@ impl User {
         conn: &mut DbConnection,
         tenant_id: TenantId,
         search_str: &str,
+    ) -> Result<Vec<User>> {
+        Self::find_with_limit(conn, tenant_id, search_str, 5)
+    }
+
+    /// Find at most `limit` users by search string, see [`User::find`]
+    ///
+    /// Search strings of at least 4 characters also match names with a typo in the display_name, firstname or lastname.
+    #[tracing::instrument(err, skip_all)]
+    pub fn find_with_limit(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        search_str: &str,
+        limit: i64,
     ) -> Result<Vec<User>> {
         // IMPORTANT: lowercase it to match the index of the db and
         // remove all existing % in name and to avoid manipulation of the LIKE query.
@@ -229,6 +245,8 @@ impl User {
 
         let like_query = format!("%{search_str}%");
 
+        let max_typos = max_typos(&search_str);
+
         let lower_display_name = lower(users::display_name);
 
         let lower_first_lastname = lower(users::firstname.concat(" ").concat(users::lastname));
@@ -254,18 +272,71 @@ impl User {
                         .or(soundex(lower_first_lastname)
                             .eq(soundex(&search_str))
                             // only take SOUNDEX results with a levenshtein score of lower than 5
-                            .and(levenshtein(lower_first_lastname, &search_str).lt(5))),
+                            .and(levenshtein(lower_first_lastname, &search_str).lt(5)))
+                        //
+                        // Then names with a few typos
+                        .or(levenshtein(lower_display_name, &search_str).le(max_typos))
+                        .or(levenshtein(lower(users::firstname), &search_str).le(max_typos))
+                        .or(levenshtein(lower(users::lastname), &search_str).le(max_typos)),
                 ),
             )
             .order_by(levenshtein(lower_display_name, &search_str))
             .then_order_by(levenshtein(lower_first_lastname, &search_str))
             .then_order_by(users::id)
-            .limit(5)
+            .limit(limit)
             .load(conn)?;
 
         Ok(matches)
     }
+
+    /// Get all users of the tenant whose email matches the given email, ignoring the case
+    #[tracing::instrument(err, skip_all)]
+    pub fn find_by_email(
+        conn: &mut DbConnection,
+        tenant_id: TenantId,
+        email: &str,
+    ) -> Result<Vec<User>> {
+        let users = users::table
+            .filter(users::tenant_id.eq(tenant_id))
+            .filter(lower(users::email).eq(email.trim().to_lowercase()))
+            .order_by(users::id)
+            .load(conn)?;
+
+        Ok(users)
+    }
+
+    /// Remove all personal data from the user entry
+    ///
+    /// The entry itself is kept, as it is still referenced by other entries, e.g. invites or legal votes.
+    /// The OIDC subject is replaced as well, so a later login with the same account creates a new user.
+    #[tracing::instrument(err, skip_all)]
+    pub fn anonymize(conn: &mut DbConnection, user_id: UserId) -> Result<User> {
+        let query = diesel::update(users::table.filter(users::id.eq(user_id))).set((
+            users::oidc_sub.eq(format!("erased:{user_id}")),
+            users::email.eq(format!("erased-{user_id}@invalid")),
+            users::title.eq(""),
+            users::firstname.eq(""),
+            users::lastname.eq(""),
+            users::display_name.eq(ANONYMIZED_DISPLAY_NAME),
+            users::phone.eq(None::<String>),
+            users::id_token_exp.eq(0),
+        ));
+
+        let user = query.get_result(conn)?;
+
+        Ok(user)
+    }
 }
+
+/// Number of typos tolerated when matching names against the search string
+fn max_typos(search_str: &str) -> i32 {
+    match search_str.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
 /// Diesel insertable user struct
 ///
 /// Represents fields that have to be provided on user insertion.
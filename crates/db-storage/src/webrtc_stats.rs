@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! WebRTC statistics reported by the clients
+//!
+//! Snapshots of the media connections are only appended and deleted after the configured retention, they can be
+//! correlated with the media problems the media server detected for the same participant.
+use crate::schema::webrtc_stats;
+use chrono::{DateTime, Utc};
+use database::{DbConnection, Result};
+use diesel::{ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use types::core::RoomId;
+use uuid::Uuid;
+
+/// Diesel webrtc_stats model
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = webrtc_stats)]
+pub struct WebRtcStats {
+    pub id: i64,
+    /// Time the client took the snapshot
+    pub recorded_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+    pub room_id: RoomId,
+    pub participant_id: Uuid,
+    /// `video` or `screen`
+    pub media_session_type: String,
+    /// Participant publishing the media
+    pub source: Uuid,
+    /// `upstream` or `downstream`
+    pub direction: String,
+    /// Number of packets sent or received so far
+    pub packets: i64,
+    pub packets_lost: i64,
+    pub jitter_ms: Option<f64>,
+    pub round_trip_time_ms: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+}
+
+impl WebRtcStats {
+    /// Get all snapshots of the participant, oldest first
+    #[tracing::instrument(err, skip_all)]
+    pub fn get_all_for_participant(
+        conn: &mut DbConnection,
+        participant_id: Uuid,
+    ) -> Result<Vec<Self>> {
+        let query = webrtc_stats::table
+            .filter(webrtc_stats::participant_id.eq(participant_id))
+            .order_by(webrtc_stats::recorded_at.asc());
+
+        let stats = query.load(conn)?;
+
+        Ok(stats)
+    }
+
+    /// Delete all snapshots which were received before the given time, returns the number of deleted snapshots
+    #[tracing::instrument(err, skip_all)]
+    pub fn delete_received_before(conn: &mut DbConnection, before: DateTime<Utc>) -> Result<usize> {
+        let query =
+            diesel::delete(webrtc_stats::table).filter(webrtc_stats::received_at.lt(before));
+
+        let deleted = query.execute(conn)?;
+
+        Ok(deleted)
+    }
+}
+
+/// WebRTC statistics insert values
+#[derive(Debug, Insertable)]
+#[diesel(table_name = webrtc_stats)]
+pub struct NewWebRtcStats {
+    pub recorded_at: DateTime<Utc>,
+    pub room_id: RoomId,
+    pub participant_id: Uuid,
+    pub media_session_type: String,
+    pub source: Uuid,
+    pub direction: String,
+    pub packets: i64,
+    pub packets_lost: i64,
+    pub jitter_ms: Option<f64>,
+    pub round_trip_time_ms: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+}
+
+/// Insert a batch of snapshots
+#[tracing::instrument(err, skip_all)]
+pub fn insert_batch(conn: &mut DbConnection, stats: &[NewWebRtcStats]) -> Result<()> {
+    if stats.is_empty() {
+        return Ok(());
+    }
+
+    diesel::insert_into(webrtc_stats::table)
+        .values(stats)
+        .execute(conn)?;
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::common::make_user;
+use chrono::{Datelike, TimeZone, Utc};
+use database::DbConnection;
+use k3k_db_storage::contacts::ContactFavorite;
+use k3k_db_storage::room_statistics::{
+    get_recently_met_users, insert_participants, NewRoomStatistics,
+};
+use k3k_db_storage::rooms::NewRoom;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+use types::core::UserId;
+
+mod common;
+
+fn favorite_ids(conn: &mut DbConnection, user_id: UserId) -> Vec<UserId> {
+    ContactFavorite::get_all_for_user(conn, user_id)
+        .unwrap()
+        .into_iter()
+        .map(|user| user.id)
+        .collect()
+}
+
+#[tokio::test]
+#[serial]
+async fn favorites_keep_their_order() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let alice = make_user(&mut conn, "Alice", "Adams", "Alice Adams");
+    let bob = make_user(&mut conn, "Bob", "Baker", "Bob Baker");
+    let carol = make_user(&mut conn, "Carol", "Clark", "Carol Clark");
+
+    // Insert in the reverse order of the ids, so the order can't be the one of the ids
+    let mut ids = vec![alice.id, bob.id, carol.id];
+    ids.sort_unstable();
+    ids.reverse();
+
+    ContactFavorite::replace_for_user(&mut conn, user.id, &ids).unwrap();
+
+    assert_eq!(favorite_ids(&mut conn, user.id), ids);
+
+    // Kept favorites keep their position
+    ContactFavorite::replace_for_user(&mut conn, user.id, &[ids[2], ids[0]]).unwrap();
+
+    assert_eq!(favorite_ids(&mut conn, user.id), vec![ids[0], ids[2]]);
+
+    // New favorites are appended
+    ContactFavorite::replace_for_user(&mut conn, user.id, &[ids[1], ids[2], ids[0]]).unwrap();
+
+    assert_eq!(
+        favorite_ids(&mut conn, user.id),
+        vec![ids[0], ids[2], ids[1]]
+    );
+
+    // The favorites of other users are not touched
+    assert!(favorite_ids(&mut conn, alice.id).is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn recently_met_users() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let alice = make_user(&mut conn, "Alice", "Adams", "Alice Adams");
+    let bob = make_user(&mut conn, "Bob", "Baker", "Bob Baker");
+    let carol = make_user(&mut conn, "Carol", "Clark", "Carol Clark");
+
+    let room = NewRoom {
+        created_by: user.id,
+        password: None,
+        waiting_room: false,
+        tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
+    }
+    .insert(&mut conn)
+    .unwrap();
+
+    let mut session = |day: u32, user_ids: &[UserId]| {
+        let statistics = NewRoomStatistics {
+            room_id: room.id,
+            tenant_id: room.tenant_id,
+            started_at: Utc.with_ymd_and_hms(2023, 1, day, 10, 0, 0).unwrap(),
+            ended_at: Utc.with_ymd_and_hms(2023, 1, day, 11, 0, 0).unwrap(),
+            peak_participants: user_ids.len() as i32,
+            participant_minutes: 60,
+            modules: vec![],
+        }
+        .insert(&mut conn)
+        .unwrap();
+
+        insert_participants(&mut conn, statistics.id, user_ids).unwrap();
+    };
+
+    // before the checked time range
+    session(1, &[user.id, carol.id]);
+    session(5, &[user.id, alice.id, bob.id]);
+    session(6, &[user.id, bob.id]);
+    // without the user
+    session(7, &[alice.id, carol.id]);
+
+    let met: Vec<(UserId, u32)> = get_recently_met_users(
+        &mut conn,
+        user.id,
+        Utc.with_ymd_and_hms(2023, 1, 2, 0, 0, 0).unwrap(),
+    )
+    .unwrap()
+    .into_iter()
+    .map(|(user, met_at)| (user.id, met_at.day()))
+    .collect();
+
+    assert_eq!(met, vec![(bob.id, 6), (alice.id, 5)]);
+}
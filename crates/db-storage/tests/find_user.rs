@@ -58,4 +58,13 @@ async fn test() {
     let users = User::find(&mut conn, tenant_id, "Schpecktre").unwrap();
     assert_eq!(users.len(), 1);
     assert_eq!(users[0].firstname, "Aileen");
+
+    // Typos in the lastname
+    let users = User::find(&mut conn, tenant_id, "Rutherfrod").unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].firstname, "Laura");
+
+    let users = User::find_by_email(&mut conn, tenant_id, &users[0].email.to_uppercase()).unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].firstname, "Laura");
 }
@@ -10,6 +10,7 @@ use k3k_db_storage::events::{
     Event, EventInvite, EventInviteStatus, GetEventsCursor, NewEvent, NewEventInvite,
     UpdateEventInvite,
 };
+use k3k_db_storage::room_owners::{NewRoomOwner, RoomOwner};
 use k3k_db_storage::rooms::NewRoom;
 use k3k_db_storage::tenants::{get_or_create_tenant_by_oidc_id, OidcTenantId};
 use pretty_assertions::assert_eq;
@@ -44,6 +45,7 @@ fn make_event(
         recurrence_pattern: None,
         is_adhoc,
         tenant_id: tenant.id,
+        external_meeting_url: None,
     }
     .insert(conn)
     .unwrap()
@@ -55,7 +57,11 @@ fn update_invite_status(
     event_id: EventId,
     new_status: EventInviteStatus,
 ) {
-    let changeset = UpdateEventInvite { status: new_status };
+    let changeset = UpdateEventInvite {
+        status: new_status,
+        comment: None,
+        responded_at: Some(Utc::now()),
+    };
 
     changeset.apply(conn, user_id, event_id).unwrap();
 }
@@ -74,6 +80,9 @@ async fn test() {
         password: None,
         waiting_room: false,
         tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -269,6 +278,9 @@ async fn get_events_invite_filter() {
         password: None,
         waiting_room: false,
         tenant_id: inviter.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -490,6 +502,9 @@ async fn get_event_invites() {
         password: None,
         waiting_room: false,
         tenant_id: ferdinand.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -566,6 +581,9 @@ async fn get_event_adhoc() {
         password: None,
         waiting_room: false,
         tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -647,6 +665,9 @@ async fn get_event_time_independent() {
         password: None,
         waiting_room: false,
         tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -730,6 +751,9 @@ async fn get_event_min_max_time() {
         password: None,
         waiting_room: false,
         tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
     }
     .insert(&mut conn)
     .unwrap();
@@ -751,6 +775,7 @@ async fn get_event_min_max_time() {
         recurrence_pattern: None,
         is_adhoc: false,
         tenant_id: user.tenant_id,
+        external_meeting_url: None,
     }
     .insert(&mut conn)
     .unwrap();
@@ -772,6 +797,7 @@ async fn get_event_min_max_time() {
         recurrence_pattern: None,
         is_adhoc: false,
         tenant_id: user.tenant_id,
+        external_meeting_url: None,
     }
     .insert(&mut conn)
     .unwrap();
@@ -1021,3 +1047,68 @@ async fn get_event_min_max_time() {
         assert_eq!(events[0].0, event2);
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn get_events_of_owned_rooms() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let creator = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let co_owner = make_user(&mut conn, "Another", "Tester", "Another Tester");
+
+    let room = NewRoom {
+        created_by: creator.id,
+        password: None,
+        waiting_room: false,
+        tenant_id: creator.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
+    }
+    .insert(&mut conn)
+    .unwrap();
+
+    let event = make_event(&mut conn, creator.id, room.id, Some(1), false);
+
+    let get_events = |conn: &mut DbConnection| {
+        Event::get_all_for_user_paginated(
+            conn,
+            &co_owner,
+            false,
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            10,
+        )
+        .unwrap()
+    };
+
+    assert!(get_events(&mut conn).is_empty());
+
+    let added = NewRoomOwner {
+        room_id: room.id,
+        user_id: co_owner.id,
+    }
+    .insert(&mut conn)
+    .unwrap();
+    assert!(added);
+
+    let events = get_events(&mut conn);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0, event);
+    assert!(events[0].1.is_none());
+
+    assert_eq!(
+        RoomOwner::get_ids_for_room(&mut conn, room.id)
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(RoomOwner::delete(&mut conn, room.id, co_owner.id).unwrap());
+    assert!(get_events(&mut conn).is_empty());
+}
@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::common::make_user;
+use chrono::Utc;
+use database::DbConnection;
+use k3k_db_storage::room_directory::RoomDirectoryEntry;
+use k3k_db_storage::rooms::{NewRoom, Room};
+use k3k_db_storage::users::User;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+use types::core::RoomId;
+
+mod common;
+
+fn make_room(conn: &mut DbConnection, user: &User) -> Room {
+    NewRoom {
+        created_by: user.id,
+        password: None,
+        waiting_room: false,
+        tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
+    }
+    .insert(conn)
+    .unwrap()
+}
+
+fn list_room(conn: &mut DbConnection, room_id: RoomId, title: &str, description: &str) {
+    RoomDirectoryEntry {
+        room_id,
+        title: title.into(),
+        description: description.into(),
+        schedule: None,
+        invite_code: None,
+        updated_at: Utc::now(),
+    }
+    .upsert(conn)
+    .unwrap();
+}
+
+fn search(conn: &mut DbConnection, search_str: &str) -> (Vec<String>, i64) {
+    let (entries, count) = RoomDirectoryEntry::search_paginated(conn, search_str, 10, 1).unwrap();
+
+    let titles = entries.into_iter().map(|(entry, _)| entry.title).collect();
+
+    (titles, count)
+}
+
+#[tokio::test]
+#[serial]
+async fn search_matches_title_and_description() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let chess = make_room(&mut conn, &user);
+    let knitting = make_room(&mut conn, &user);
+    let unlisted = make_room(&mut conn, &user);
+
+    list_room(&mut conn, chess.id, "Chess Club", "Weekly games");
+    list_room(&mut conn, knitting.id, "Knitting", "Bring your own wool");
+
+    assert_eq!(
+        search(&mut conn, ""),
+        (vec!["Chess Club".to_owned(), "Knitting".to_owned()], 2)
+    );
+    assert_eq!(
+        search(&mut conn, "chess"),
+        (vec!["Chess Club".to_owned()], 1)
+    );
+    assert_eq!(search(&mut conn, "WOOL"), (vec!["Knitting".to_owned()], 1));
+    // Wildcards in the search string are ignored
+    assert_eq!(search(&mut conn, "%"), search(&mut conn, ""));
+
+    assert!(RoomDirectoryEntry::get(&mut conn, unlisted.id)
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn upsert_replaces_and_trashed_rooms_are_hidden() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let room = make_room(&mut conn, &user);
+
+    list_room(&mut conn, room.id, "Chess Club", "Weekly games");
+    list_room(&mut conn, room.id, "Chess Club", "Daily games");
+
+    assert_eq!(
+        RoomDirectoryEntry::get(&mut conn, room.id)
+            .unwrap()
+            .unwrap()
+            .description,
+        "Daily games"
+    );
+    assert_eq!(search(&mut conn, ""), (vec!["Chess Club".to_owned()], 1));
+
+    Room::soft_delete_by_id(&mut conn, room.id).unwrap();
+
+    assert_eq!(search(&mut conn, ""), (vec![], 0));
+
+    RoomDirectoryEntry::delete_by_room(&mut conn, room.id).unwrap();
+
+    assert!(RoomDirectoryEntry::get(&mut conn, room.id)
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,231 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::common::make_user;
+use chrono::{DateTime, TimeZone, Utc};
+use database::DbConnection;
+use k3k_db_storage::call_feedback::{CallFeedback, FeedbackDay, IssueCount, NewCallFeedback};
+use k3k_db_storage::room_statistics::{ModuleUsage, NewRoomStatistics, RoomStatistics};
+use k3k_db_storage::rooms::{NewRoom, Room};
+use k3k_db_storage::tenants::{get_or_create_tenant_by_oidc_id, OidcTenantId};
+use k3k_db_storage::users::User;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+use types::core::TenantId;
+
+mod common;
+
+fn make_room(conn: &mut DbConnection, user: &User, tenant_id: TenantId) -> Room {
+    NewRoom {
+        created_by: user.id,
+        password: None,
+        waiting_room: false,
+        tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
+    }
+    .insert(conn)
+    .unwrap()
+}
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 1, day, hour, 0, 0).unwrap()
+}
+
+fn insert_session(
+    conn: &mut DbConnection,
+    room: &Room,
+    started_at: DateTime<Utc>,
+    participant_minutes: i64,
+    peak_participants: i32,
+    modules: &[&str],
+) {
+    NewRoomStatistics {
+        room_id: room.id,
+        tenant_id: room.tenant_id,
+        started_at,
+        ended_at: started_at + chrono::Duration::hours(1),
+        peak_participants,
+        participant_minutes,
+        modules: modules.iter().map(|module| module.to_string()).collect(),
+    }
+    .insert(conn)
+    .unwrap();
+}
+
+fn insert_feedback(conn: &mut DbConnection, room: &Room, rating: i16, issues: &[&str]) {
+    NewCallFeedback {
+        room_id: room.id,
+        tenant_id: room.tenant_id,
+        user_id: None,
+        rating,
+        issues: issues.iter().map(|issue| issue.to_string()).collect(),
+        comment: None,
+        participants: 2,
+        duration_secs: 600,
+        webrtc_down_count: 1,
+        slow_link_count: 2,
+    }
+    .insert(conn)
+    .unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn room_usage_is_aggregated_per_tenant() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let other_tenant =
+        get_or_create_tenant_by_oidc_id(&mut conn, &OidcTenantId::from("other".to_owned()))
+            .unwrap();
+
+    let room_a = make_room(&mut conn, &user, user.tenant_id);
+    let room_b = make_room(&mut conn, &user, user.tenant_id);
+    let other_room = make_room(&mut conn, &user, other_tenant.id);
+
+    insert_session(&mut conn, &room_a, at(1, 10), 30, 3, &["media", "chat"]);
+    insert_session(&mut conn, &room_a, at(2, 10), 40, 5, &["media", "polls"]);
+    insert_session(&mut conn, &room_b, at(3, 10), 120, 8, &["media"]);
+    // outside of the time range
+    insert_session(&mut conn, &room_b, at(20, 10), 500, 20, &["media"]);
+    // other tenant
+    insert_session(&mut conn, &other_room, at(1, 10), 1000, 50, &["media"]);
+
+    let usage = RoomStatistics::get_room_usage_started_between(
+        &mut conn,
+        user.tenant_id,
+        at(1, 0),
+        at(10, 0),
+    )
+    .unwrap();
+
+    let usage: Vec<_> = usage
+        .into_iter()
+        .map(|room| {
+            (
+                room.room_id,
+                room.sessions,
+                room.participant_minutes,
+                room.peak_participants,
+                room.last_ended_at,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        usage,
+        vec![
+            (room_b.id, 1, 120, 8, at(3, 11)),
+            (room_a.id, 2, 70, 5, at(2, 11)),
+        ]
+    );
+
+    let modules = RoomStatistics::get_module_usage_started_between(
+        &mut conn,
+        user.tenant_id,
+        at(1, 0),
+        at(10, 0),
+    )
+    .unwrap();
+
+    assert_eq!(
+        modules,
+        vec![
+            ModuleUsage {
+                module: "chat".into(),
+                sessions: 1
+            },
+            ModuleUsage {
+                module: "media".into(),
+                sessions: 3
+            },
+            ModuleUsage {
+                module: "polls".into(),
+                sessions: 1
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn feedback_is_aggregated_per_tenant() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let other_tenant =
+        get_or_create_tenant_by_oidc_id(&mut conn, &OidcTenantId::from("other".to_owned()))
+            .unwrap();
+
+    let room = make_room(&mut conn, &user, user.tenant_id);
+    let other_room = make_room(&mut conn, &user, other_tenant.id);
+
+    insert_feedback(&mut conn, &room, 5, &[]);
+    insert_feedback(&mut conn, &room, 2, &["audio", "connection"]);
+    insert_feedback(&mut conn, &other_room, 1, &["audio"]);
+
+    let now = Utc::now();
+    let from = now - chrono::Duration::hours(1);
+    let to = now + chrono::Duration::hours(1);
+
+    let days = CallFeedback::get_days_created_between(&mut conn, user.tenant_id, from, to).unwrap();
+
+    // the feedback has been created just now, the test might run across midnight
+    let submissions: i64 = days.iter().map(|day| day.submissions).sum();
+    let rating_sum: i64 = days.iter().map(|day| day.rating_sum).sum();
+    assert_eq!((submissions, rating_sum), (2, 7));
+
+    if let [day] = days.as_slice() {
+        assert_eq!(
+            day,
+            &FeedbackDay {
+                date: now.date_naive(),
+                submissions: 2,
+                rating_sum: 7,
+                webrtc_down_count: 2,
+                slow_link_count: 4,
+            }
+        );
+    }
+
+    let ratings: Vec<_> =
+        CallFeedback::get_rating_counts_created_between(&mut conn, user.tenant_id, from, to)
+            .unwrap()
+            .into_iter()
+            .map(|count| (count.rating, count.submissions))
+            .collect();
+    assert_eq!(ratings, vec![(2, 1), (5, 1)]);
+
+    let issues =
+        CallFeedback::get_issue_counts_created_between(&mut conn, user.tenant_id, from, to)
+            .unwrap();
+    assert_eq!(
+        issues,
+        vec![
+            IssueCount {
+                issue: "audio".into(),
+                submissions: 1
+            },
+            IssueCount {
+                issue: "connection".into(),
+                submissions: 1
+            },
+        ]
+    );
+
+    assert!(CallFeedback::get_days_created_between(
+        &mut conn,
+        user.tenant_id,
+        to,
+        to + (to - from)
+    )
+    .unwrap()
+    .is_empty());
+}
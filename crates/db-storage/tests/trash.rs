@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::common::make_user;
+use database::{DatabaseError, DbConnection};
+use k3k_db_storage::events::{Event, NewEvent};
+use k3k_db_storage::rooms::{NewRoom, Room};
+use k3k_db_storage::users::User;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+use types::core::{RoomId, UserId};
+
+mod common;
+
+fn make_room(conn: &mut DbConnection, user: &User) -> Room {
+    NewRoom {
+        created_by: user.id,
+        password: None,
+        waiting_room: false,
+        tenant_id: user.tenant_id,
+        locale: None,
+        region: None,
+        webinar_mode: false,
+    }
+    .insert(conn)
+    .unwrap()
+}
+
+fn make_event(conn: &mut DbConnection, user: &User, room_id: RoomId) -> Event {
+    NewEvent {
+        title: "Test Event".into(),
+        description: "Test Event".into(),
+        room: room_id,
+        created_by: user.id,
+        updated_by: user.id,
+        is_time_independent: true,
+        is_all_day: Some(false),
+        starts_at: None,
+        starts_at_tz: None,
+        ends_at: None,
+        ends_at_tz: None,
+        duration_secs: None,
+        is_recurring: Some(false),
+        recurrence_pattern: None,
+        is_adhoc: false,
+        tenant_id: user.tenant_id,
+        external_meeting_url: None,
+    }
+    .insert(conn)
+    .unwrap()
+}
+
+fn deleted_room_ids(conn: &mut DbConnection, user_id: UserId) -> Vec<RoomId> {
+    Room::get_all_deleted_for_user(conn, user_id)
+        .unwrap()
+        .into_iter()
+        .map(|room| room.id)
+        .collect()
+}
+
+#[tokio::test]
+#[serial]
+async fn soft_delete_and_restore_room() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let room = make_room(&mut conn, &user);
+    let event = make_event(&mut conn, &user, room.id);
+
+    Room::soft_delete_by_id(&mut conn, room.id).unwrap();
+
+    // the room and its events are hidden, but listed in the trash of the creator
+    assert!(matches!(
+        Room::get(&mut conn, room.id),
+        Err(DatabaseError::NotFound)
+    ));
+    assert!(matches!(
+        Event::get(&mut conn, event.id),
+        Err(DatabaseError::NotFound)
+    ));
+    assert_eq!(deleted_room_ids(&mut conn, user.id), vec![room.id]);
+
+    // events moved to the trash together with their room are only restored with the room
+    assert!(Event::get_all_deleted_for_user(&mut conn, user.id)
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        Event::restore(&mut conn, event.id, user.id),
+        Err(DatabaseError::NotFound)
+    ));
+
+    // deleting the room again fails
+    assert!(matches!(
+        Room::soft_delete_by_id(&mut conn, room.id),
+        Err(DatabaseError::NotFound)
+    ));
+
+    // other users cannot restore the room
+    let other_user = make_user(&mut conn, "Other", "Tester", "Other Tester");
+    assert!(matches!(
+        Room::restore(&mut conn, room.id, other_user.id),
+        Err(DatabaseError::NotFound)
+    ));
+
+    let restored = Room::restore(&mut conn, room.id, user.id).unwrap();
+    assert_eq!(restored.deleted_at, None);
+
+    assert_eq!(Room::get(&mut conn, room.id).unwrap().id, room.id);
+    assert_eq!(Event::get(&mut conn, event.id).unwrap().id, event.id);
+    assert!(deleted_room_ids(&mut conn, user.id).is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn restoring_room_keeps_events_deleted_before() {
+    let db_ctx = test_util::database::DatabaseContext::new(true).await;
+
+    let mut conn = db_ctx.db.get_conn().unwrap();
+
+    let user = make_user(&mut conn, "Test", "Tester", "Test Tester");
+    let room = make_room(&mut conn, &user);
+    let deleted_event = make_event(&mut conn, &user, room.id);
+    let event = make_event(&mut conn, &user, room.id);
+
+    Event::soft_delete_by_id(&mut conn, deleted_event.id).unwrap();
+    Room::soft_delete_by_id(&mut conn, room.id).unwrap();
+    Room::restore(&mut conn, room.id, user.id).unwrap();
+
+    assert_eq!(Event::get(&mut conn, event.id).unwrap().id, event.id);
+    assert!(matches!(
+        Event::get(&mut conn, deleted_event.id),
+        Err(DatabaseError::NotFound)
+    ));
+
+    let deleted_events: Vec<_> = Event::get_all_deleted_for_user(&mut conn, user.id)
+        .unwrap()
+        .into_iter()
+        .map(|event| event.id)
+        .collect();
+    assert_eq!(deleted_events, vec![deleted_event.id]);
+
+    let restored = Event::restore(&mut conn, deleted_event.id, user.id).unwrap();
+    assert_eq!(restored.deleted_at, None);
+}
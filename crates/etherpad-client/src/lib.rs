@@ -184,6 +184,24 @@ impl EtherpadClient {
         Ok(())
     }
 
+    /// Appends the text to the end of the pad
+    pub async fn append_text(&self, pad_id: &str, text: &str) -> Result<()> {
+        let mut url = self.base_url.join("api/1.2.13/appendText")?;
+
+        url.query_pairs_mut()
+            .append_pair("apikey", &self.api_key)
+            .append_pair("padID", pad_id)
+            .append_pair("text", text);
+
+        let response = self.client.get(url).send().await?;
+
+        verify_etherpad_response(response)
+            .await
+            .context("Failed to call etherpad endpoint 'appendText'")?;
+
+        Ok(())
+    }
+
     pub async fn get_readonly_pad_id(&self, pad_id: &str) -> Result<String> {
         let mut url = self.base_url.join("api/1/getReadOnlyID")?;
 
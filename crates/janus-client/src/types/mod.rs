@@ -257,6 +257,7 @@ impl From<u64> for StreamTypes {
 
 /// A candidate for ICE/SDP trickle
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrickleCandidate {
     #[serde(rename = "sdpMLineIndex")]
     pub sdp_m_line_index: u64,
@@ -8,10 +8,20 @@ use types::core::ParticipantId;
 #[derive(Default)]
 pub struct FocusDetection {
     queue: VecDeque<ParticipantId>,
+    /// Participants spotlighted by a moderator, they take precedence over all other speakers
+    spotlights: Vec<ParticipantId>,
     last_focus: Option<ParticipantId>,
 }
 
 impl FocusDetection {
+    pub fn set_spotlights(
+        &mut self,
+        spotlights: Vec<ParticipantId>,
+    ) -> Option<Option<ParticipantId>> {
+        self.spotlights = spotlights;
+        self.get_focussed_update()
+    }
+
     pub fn on_started_talking(&mut self, id: ParticipantId) -> Option<Option<ParticipantId>> {
         self.remove(id);
         self.queue.push_back(id);
@@ -30,7 +40,17 @@ impl FocusDetection {
     }
 
     fn get_focussed_update(&mut self) -> Option<Option<ParticipantId>> {
-        let focus = self.queue.front().copied();
+        // The longest talking spotlighted participant is focussed, the first spotlighted participant while none of
+        // them is talking
+        let focus = if self.spotlights.is_empty() {
+            self.queue.front().copied()
+        } else {
+            self.queue
+                .iter()
+                .find(|id| self.spotlights.contains(id))
+                .or_else(|| self.spotlights.first())
+                .copied()
+        };
 
         if focus != self.last_focus {
             self.last_focus = focus;
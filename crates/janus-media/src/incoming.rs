@@ -4,11 +4,13 @@
 
 use crate::mcu::MediaSessionType;
 use crate::MediaSessionState;
+use controller::prelude::connectivity::ConnectivityReport;
 use janus_client::TrickleCandidate;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::ParticipantId;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action")]
 pub enum Message {
     /// The participant successfully established a stream
@@ -29,6 +31,26 @@ pub enum Message {
     #[serde(rename = "moderator_mute")]
     ModeratorMute(RequestMute),
 
+    /// A moderators request to mute all non-moderators
+    #[serde(rename = "moderator_mute_all")]
+    ModeratorMuteAll(RequestMuteAll),
+
+    /// A moderators request to disable the video of all non-moderators
+    #[serde(rename = "moderator_disable_all_video")]
+    ModeratorDisableAllVideo(RequestMuteAll),
+
+    /// A moderators request to allow non-moderators to unmute their media again
+    #[serde(rename = "unlock_media")]
+    UnlockMedia(UnlockMedia),
+
+    /// The participant pressed the talk button in a push-to-talk room
+    #[serde(rename = "push_to_talk_start")]
+    PushToTalkStart,
+
+    /// The participant released the talk button
+    #[serde(rename = "push_to_talk_stop")]
+    PushToTalkStop,
+
     /// SDP offer
     #[serde(rename = "publish")]
     Publish(TargetedSdp),
@@ -64,15 +86,35 @@ pub enum Message {
     /// SDP request to configure subscription
     #[serde(rename = "configure")]
     Configure(TargetConfigure),
+
+    /// Report of the ICE candidates gathered in a connectivity pre-check
+    #[serde(rename = "connectivity_report")]
+    ConnectivityReport(ConnectivityReport),
+
+    /// Request control over the screen shared by the target
+    #[serde(rename = "request_remote_control")]
+    RequestRemoteControl(RemoteControlTarget),
+
+    /// Grant the target control over the own shared screen
+    #[serde(rename = "grant_remote_control")]
+    GrantRemoteControl(RemoteControlTarget),
+
+    /// Deny the request of the target to control the own shared screen
+    #[serde(rename = "deny_remote_control")]
+    DenyRemoteControl(RemoteControlTarget),
+
+    /// Revoke the remote control of the screen shared by the target
+    #[serde(rename = "revoke_remote_control")]
+    RevokeRemoteControl(RemoteControlTarget),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct AssociatedMediaSession {
     /// The stream type that has been published
     pub media_session_type: MediaSessionType,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct MediaSessionInfo {
     /// The stream type that has been published
     pub media_session_type: MediaSessionType,
@@ -84,7 +126,7 @@ pub struct MediaSessionInfo {
 /// Request a number of participants to mute themselves
 ///
 /// May only be processed if the issuer is a moderator
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct RequestMute {
     /// Participants that shall be muted
     pub targets: Vec<ParticipantId>,
@@ -92,7 +134,30 @@ pub struct RequestMute {
     pub force: bool,
 }
 
-#[derive(Debug, Deserialize)]
+/// Request all non-moderators to mute themselves
+///
+/// May only be processed if the issuer is a moderator
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RequestMuteAll {
+    /// Force mute the participants
+    pub force: bool,
+    /// Prevent the participants from unmuting themselves until unlocked, implies `force`
+    #[serde(default)]
+    pub lock: bool,
+}
+
+/// Unlock the media locked by [`RequestMuteAll`]
+///
+/// May only be processed if the issuer is a moderator
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UnlockMedia {
+    #[serde(default)]
+    pub audio: bool,
+    #[serde(default)]
+    pub video: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct TargetedSdp {
     /// The payload of the sdp message
     pub sdp: String,
@@ -102,7 +167,7 @@ pub struct TargetedSdp {
     pub target: Target,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct TargetedCandidate {
     /// The payload of the sdp message
     pub candidate: TrickleCandidate,
@@ -112,7 +177,7 @@ pub struct TargetedCandidate {
     pub target: Target,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
 pub struct Target {
     /// The target of this message.
     ///
@@ -123,7 +188,7 @@ pub struct Target {
     pub media_session_type: MediaSessionType,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Clone, Copy, JsonSchema)]
 pub struct TargetSubscribe {
     /// The target of the subscription
     #[serde(flatten)]
@@ -136,13 +201,19 @@ pub struct TargetSubscribe {
 }
 
 /// Give a list of participants write access to the protocol
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParticipantSelection {
     /// The targeted participants
     pub participant_ids: Vec<ParticipantId>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Copy, JsonSchema)]
+pub struct RemoteControlTarget {
+    /// The participant sharing the screen, or the requesting participant when granting or denying
+    pub target: ParticipantId,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct TargetConfigure {
     /// The target of this configure
     #[serde(flatten)]
@@ -154,7 +225,7 @@ pub struct TargetConfigure {
     pub configuration: SubscriberConfiguration,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct SubscriberConfiguration {
     /// Video Feed
     ///
@@ -294,6 +365,73 @@ mod test {
         }
     }
 
+    #[test]
+    fn moderator_mute_all() {
+        let json = r#"
+        {
+            "action": "moderator_mute_all",
+            "force": false,
+            "lock": true
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::ModeratorMuteAll(RequestMuteAll { force, lock }) = msg {
+            assert!(!force);
+            assert!(lock);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn moderator_disable_all_video() {
+        let json = r#"
+        {
+            "action": "moderator_disable_all_video",
+            "force": true
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::ModeratorDisableAllVideo(RequestMuteAll { force, lock }) = msg {
+            assert!(force);
+            assert!(!lock);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn unlock_media() {
+        let json = r#"
+        {
+            "action": "unlock_media",
+            "video": true
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::UnlockMedia(UnlockMedia { audio, video }) = msg {
+            assert!(!audio);
+            assert!(video);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn push_to_talk() {
+        let msg: Message = serde_json::from_str(r#"{"action": "push_to_talk_start"}"#).unwrap();
+        assert!(matches!(msg, Message::PushToTalkStart));
+
+        let msg: Message = serde_json::from_str(r#"{"action": "push_to_talk_stop"}"#).unwrap();
+        assert!(matches!(msg, Message::PushToTalkStop));
+    }
+
     #[test]
     fn offer() {
         let json = r#"
@@ -489,4 +627,49 @@ mod test {
             panic!()
         }
     }
+
+    #[test]
+    fn connectivity_report() {
+        use controller::prelude::connectivity::CandidateType;
+
+        let json = r#"
+        {
+            "action": "connectivity_report",
+            "candidate_types": ["host", "srflx", "relay"]
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::ConnectivityReport(ConnectivityReport { candidate_types }) = msg {
+            assert_eq!(
+                candidate_types,
+                vec![
+                    CandidateType::Host,
+                    CandidateType::Srflx,
+                    CandidateType::Relay
+                ]
+            );
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn grant_remote_control() {
+        let json = r#"
+        {
+            "action": "grant_remote_control",
+            "target": "00000000-0000-0000-0000-000000000000"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::GrantRemoteControl(RemoteControlTarget { target }) = msg {
+            assert_eq!(target, ParticipantId::nil());
+        } else {
+            panic!()
+        }
+    }
 }
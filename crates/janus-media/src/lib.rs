@@ -8,26 +8,35 @@
 //!
 //! Handles media related messages and manages their respective forwarding to janus-gateway via rabbitmq.
 use anyhow::{bail, Context, Result};
+use controller::prelude::connection_history::ConnectionEventKind;
 use controller::prelude::*;
 use controller::settings::SharedSettings;
 use controller::Controller;
+use db_storage::room_media_settings::RoomMediaSettings;
 use focus::FocusDetection;
-use incoming::{RequestMute, TargetConfigure};
+use futures::stream::once;
+use futures::FutureExt;
+use incoming::{RemoteControlTarget, RequestMute, RequestMuteAll, TargetConfigure, UnlockMedia};
 use janus_client::TrickleCandidate;
 use mcu::McuPool;
 use mcu::PublishConfiguration;
 use mcu::{
-    LinkDirection, MediaSessionKey, MediaSessionType, Request, Response, TrickleMessage,
-    WebRtcEvent,
+    LinkDirection, MediaSessionKey, MediaSessionType, Request, Response, SpeakerFocus,
+    TrickleMessage, WebRtcEvent,
 };
 use outgoing::Link;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sessions::MediaSessions;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::time::sleep;
 use tokio_stream::wrappers::ReceiverStream;
-use types::core::ParticipantId;
+use tokio_stream::StreamExt;
+use types::core::{ParticipantId, Timestamp};
 
 mod focus;
 mod incoming;
@@ -36,8 +45,12 @@ mod outgoing;
 mod rabbitmq;
 mod sessions;
 mod settings;
+mod stats;
 mod storage;
 
+/// Maximum time the talk button can be held in push-to-talk rooms, if not configured for the room
+const DEFAULT_PUSH_TO_TALK_MAX_HOLD: Duration = Duration::from_secs(60);
+
 pub struct Media {
     id: ParticipantId,
     room: SignalingRoomId,
@@ -45,9 +58,32 @@ pub struct Media {
     mcu: Arc<McuPool>,
     media: MediaSessions,
 
+    /// Region of the media servers the room is pinned to
+    pinned_region: Option<String>,
+
+    /// Audio level thresholds configured in the media settings of the room
+    speaker_focus: SpeakerFocus,
+
+    /// Subject the connectivity checks of the participant are stored for
+    connectivity_subject: String,
+
+    /// Maximum time the talk button can be held, if push-to-talk is enabled for the room
+    push_to_talk: Option<Duration>,
+
+    /// Only moderators and panelists can publish media in rooms in webinar mode
+    webinar_mode: bool,
+
+    /// The participant holds the talk button
+    talking: bool,
+
+    /// Number of times the talk button has been pressed, used to identify the expiry of the current hold
+    talk_holds: u64,
+
     state: State,
 
     focus_detection: FocusDetection,
+
+    i_am_the_recorder: bool,
 }
 
 type State = HashMap<MediaSessionType, MediaSessionState>;
@@ -62,14 +98,91 @@ pub struct PeerFrontendData {
 #[derive(Serialize)]
 pub struct FrontendData {
     is_presenter: bool,
+    locks: MediaLocks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    push_to_talk: Option<PushToTalkPolicy>,
+}
+
+/// Push-to-talk policy of the room, only applies to non-moderators
+#[derive(Serialize)]
+pub struct PushToTalkPolicy {
+    max_hold_secs: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MediaEvent {
+    WebRtc(MediaSessionKey, WebRtcEvent),
+    /// The talk button has been held for the maximum duration
+    PushToTalkExpired(u64),
+    /// The participant has been promoted to a panelist or demoted to an attendee
+    PanelistStatusChanged(bool),
+    /// A moderator changed the spotlighted participants of the room
+    SpotlightsChanged(Vec<ParticipantId>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
 pub struct MediaSessionState {
     pub video: bool,
     pub audio: bool,
 }
 
+/// Media of the room which only moderators can unmute
+///
+/// Locks apply to the camera and microphone, the screen share is controlled by the presenter role.
+#[derive(
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    JsonSchema,
+    ToRedisArgs,
+    FromRedisValue,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct MediaLocks {
+    pub audio: bool,
+    pub video: bool,
+}
+
+impl MediaLocks {
+    /// Returns if the state unmutes any locked media
+    fn is_violated_by(&self, state: MediaSessionState) -> bool {
+        (self.audio && state.audio) || (self.video && state.video)
+    }
+
+    /// Mute all locked media of the state
+    fn enforce(&self, state: MediaSessionState) -> MediaSessionState {
+        MediaSessionState {
+            video: state.video && !self.video,
+            audio: state.audio && !self.audio,
+        }
+    }
+}
+
+/// Entry of the log of the remote controls granted in the room
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToRedisArgs, FromRedisValue)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct RemoteControlEvent {
+    pub timestamp: Timestamp,
+    pub sharer: ParticipantId,
+    pub controller: ParticipantId,
+    #[serde(flatten)]
+    pub kind: RemoteControlEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RemoteControlEventKind {
+    Granted,
+    Denied,
+    Revoked { revoked_by: Option<ParticipantId> },
+}
+
 fn process_metrics_for_media_session_state(
     ctx: &ModuleContext<'_, Media>,
     session_type: &MediaSessionType,
@@ -100,13 +213,15 @@ fn process_metrics_for_media_session_state(
 impl SignalingModule for Media {
     const NAMESPACE: &'static str = "media";
 
+    const CACHEABLE_PEER_FRONTEND_DATA: bool = true;
+
     type Params = Arc<McuPool>;
 
     type Incoming = incoming::Message;
     type Outgoing = outgoing::Message;
     type RabbitMqMessage = rabbitmq::Message;
 
-    type ExtEvent = (MediaSessionKey, WebRtcEvent);
+    type ExtEvent = MediaEvent;
 
     type FrontendData = FrontendData;
     type PeerFrontendData = PeerFrontendData;
@@ -124,19 +239,80 @@ impl SignalingModule for Media {
         let room = ctx.room_id();
 
         storage::set_state(ctx.redis_conn(), room, id, &state).await?;
-        ctx.add_event_stream(ReceiverStream::new(janus_events));
+        ctx.add_event_stream(
+            ReceiverStream::new(janus_events)
+                .map(|(media_session_key, event)| MediaEvent::WebRtc(media_session_key, event)),
+        );
+
+        if let Some(region) = ctx.region().map(ToOwned::to_owned) {
+            storage::set_region(ctx.redis_conn(), room, id, &region).await?;
+        }
+
+        ctx.subscribe(|changed: moderation::PanelistStatusChanged| {
+            MediaEvent::PanelistStatusChanged(changed.is_panelist)
+        });
+        ctx.subscribe(|changed: moderation::SpotlightsChanged| {
+            MediaEvent::SpotlightsChanged(changed.spotlights)
+        });
+
+        let spotlights = moderation::storage::get_spotlights(ctx.redis_conn(), room).await?;
+        let mut focus_detection = FocusDetection::default();
+        focus_detection.set_spotlights(spotlights);
 
         if !screen_share_requires_permission(&mcu.shared_settings) {
             storage::set_presenter(ctx.redis_conn(), room, id).await?;
         }
 
+        let db = ctx.db().clone();
+        let room_id = ctx.room().id;
+
+        let media_settings = controller::block(move || {
+            let mut conn = db.get_conn()?;
+
+            RoomMediaSettings::get(&mut conn, room_id)
+        })
+        .await?
+        .context("Failed to get the media settings of the room")?;
+
+        let speaker_focus = media_settings
+            .as_ref()
+            .map(|settings| SpeakerFocus {
+                packets: settings.speaker_focus_packets,
+                level: settings.speaker_focus_level,
+            })
+            .unwrap_or_default();
+
+        let push_to_talk =
+            media_settings
+                .filter(|settings| settings.push_to_talk)
+                .map(|settings| {
+                    settings
+                        .push_to_talk_max_hold_secs
+                        .map(|secs| Duration::from_secs(secs.max(1) as u64))
+                        .unwrap_or(DEFAULT_PUSH_TO_TALK_MAX_HOLD)
+                });
+
+        // Checks of users are shared with the REST API, so the latest check is found independent of how it was made
+        let connectivity_subject = match ctx.participant() {
+            Participant::User(user) => format!("user={}", user.id),
+            _ => format!("participant={id}"),
+        };
+
         Ok(Some(Self {
             id,
             room,
             mcu: mcu.clone(),
-            media: MediaSessions::new(ctx.participant_id(), media_sender),
+            media: MediaSessions::new(ctx.participant_id(), room, media_sender),
+            pinned_region: ctx.room().region.clone(),
+            speaker_focus,
+            connectivity_subject,
+            push_to_talk,
+            webinar_mode: ctx.room().webinar_mode,
+            talking: false,
+            talk_holds: 0,
             state,
-            focus_detection: Default::default(),
+            focus_detection,
+            i_am_the_recorder: matches!(ctx.participant(), Participant::Recorder),
         }))
     }
 
@@ -146,7 +322,26 @@ impl SignalingModule for Media {
         event: Event<'_, Self>,
     ) -> Result<()> {
         match event {
-            Event::WsMessage(incoming::Message::PublishComplete(info)) => {
+            Event::WsMessage(incoming::Message::PublishComplete(mut info)) => {
+                if let Some(locks) = self
+                    .violated_locks(&mut ctx, info.media_session_type, info.media_session_state)
+                    .await?
+                {
+                    // Keep the publisher, but do not forward the locked media
+                    info.media_session_state = locks.enforce(info.media_session_state);
+
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::MediaLocked.into(),
+                    ));
+                }
+
+                // The microphone is only transmitted while the talk button is held
+                if info.media_session_type == MediaSessionType::Video
+                    && self.push_to_talk_applies(&ctx)
+                {
+                    info.media_session_state.audio &= self.talking;
+                }
+
                 let previous_session_state = self.state.get(&info.media_session_type);
 
                 process_metrics_for_media_session_state(
@@ -176,7 +371,29 @@ impl SignalingModule for Media {
                     && ctx.role() != Role::Moderator
                     && !storage::is_presenter(ctx.redis_conn(), self.room, self.id).await?
                 {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::PermissionDenied));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::PermissionDenied.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if self
+                    .violated_locks(&mut ctx, info.media_session_type, info.media_session_state)
+                    .await?
+                    .is_some()
+                {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::MediaLocked.into(),
+                    ));
+                    return Ok(());
+                }
+
+                if info.media_session_type == MediaSessionType::Video
+                    && info.media_session_state.audio
+                    && !self.talking
+                    && self.push_to_talk_applies(&ctx)
+                {
+                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::PushToTalk.into()));
                     return Ok(());
                 }
 
@@ -211,10 +428,35 @@ impl SignalingModule for Media {
             Event::WsMessage(incoming::Message::ModeratorMute(moderator_mute)) => {
                 self.handle_moderator_mute(&mut ctx, moderator_mute).await?;
             }
+            Event::WsMessage(incoming::Message::ModeratorMuteAll(request)) => {
+                self.handle_moderator_mute_all(&mut ctx, request, false)
+                    .await?;
+            }
+            Event::WsMessage(incoming::Message::ModeratorDisableAllVideo(request)) => {
+                self.handle_moderator_mute_all(&mut ctx, request, true)
+                    .await?;
+            }
+            Event::WsMessage(incoming::Message::UnlockMedia(unlock)) => {
+                self.handle_unlock_media(&mut ctx, unlock).await?;
+            }
+            Event::WsMessage(incoming::Message::PushToTalkStart) => {
+                self.handle_push_to_talk_start(&mut ctx).await?;
+            }
+            Event::WsMessage(incoming::Message::PushToTalkStop) => {
+                if self.talking {
+                    self.talking = false;
+
+                    self.set_audio(&mut ctx, false).await?;
+                }
+            }
             Event::WsMessage(incoming::Message::Unpublish(assoc)) => {
                 self.media.remove_publisher(assoc.media_session_type).await;
                 let previous_session_state = self.state.remove(&assoc.media_session_type);
 
+                if assoc.media_session_type == MediaSessionType::Screen {
+                    self.revoke_remote_control(&mut ctx, self.id, None).await?;
+                }
+
                 process_metrics_for_media_session_state(
                     &ctx,
                     &assoc.media_session_type,
@@ -232,11 +474,21 @@ impl SignalingModule for Media {
                 ctx.invalidate_data();
             }
             Event::WsMessage(incoming::Message::Publish(targeted)) => {
+                if self.is_attendee(&mut ctx).await? {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::PermissionDenied.into(),
+                    ));
+
+                    return Ok(());
+                }
+
                 if targeted.target.media_session_type == MediaSessionType::Screen
                     && ctx.role() != Role::Moderator
                     && !storage::is_presenter(ctx.redis_conn(), self.room, self.id).await?
                 {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::PermissionDenied));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::PermissionDenied.into(),
+                    ));
 
                     return Ok(());
                 }
@@ -255,7 +507,9 @@ impl SignalingModule for Media {
                         targeted.target,
                         e
                     );
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidSdpOffer));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidSdpOffer.into(),
+                    ));
                 }
             }
             Event::WsMessage(incoming::Message::SdpAnswer(targeted)) => {
@@ -268,7 +522,9 @@ impl SignalingModule for Media {
                     .await
                 {
                     log::error!("Failed to handle sdp answer {:?}, {:?}", targeted.target, e);
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::HandleSdpAnswer));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::HandleSdpAnswer.into(),
+                    ));
                 }
             }
             Event::WsMessage(incoming::Message::SdpCandidate(targeted)) => {
@@ -285,7 +541,9 @@ impl SignalingModule for Media {
                         targeted.target,
                         e
                     );
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidCandidate));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidCandidate.into(),
+                    ));
                 }
             }
             Event::WsMessage(incoming::Message::SdpEndOfCandidates(target)) => {
@@ -299,7 +557,7 @@ impl SignalingModule for Media {
                         e
                     );
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidEndOfCandidates,
+                        outgoing::Error::InvalidEndOfCandidates.into(),
                     ));
                 }
             }
@@ -323,7 +581,7 @@ impl SignalingModule for Media {
                         e
                     );
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidRequestOffer(subscribe.target.into()),
+                        outgoing::Error::InvalidRequestOffer(subscribe.target.into()).into(),
                     ));
                 }
             }
@@ -331,7 +589,7 @@ impl SignalingModule for Media {
                 if let Err(e) = self.handle_sdp_re_request_offer(&mut ctx, target).await {
                     log::error!("Failed to handle resubscribe {:?}, {:?}", target, e);
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidRequestOffer(target.into()),
+                        outgoing::Error::InvalidRequestOffer(target.into()).into(),
                     ));
                 }
             }
@@ -340,14 +598,16 @@ impl SignalingModule for Media {
                 if let Err(e) = self.handle_configure(configure).await {
                     log::error!("Failed to handle configure request {:?}", e);
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidConfigureRequest(target.into()),
+                        outgoing::Error::InvalidConfigureRequest(target.into()).into(),
                     ));
                 }
             }
 
             Event::WsMessage(incoming::Message::GrantPresenterRole(selection)) => {
                 if ctx.role() != Role::Moderator {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::PermissionDenied));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::PermissionDenied.into(),
+                    ));
 
                     return Ok(());
                 }
@@ -360,7 +620,9 @@ impl SignalingModule for Media {
             }
             Event::WsMessage(incoming::Message::RevokePresenterRole(selection)) => {
                 if ctx.role() != Role::Moderator {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::PermissionDenied));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::PermissionDenied.into(),
+                    ));
 
                     return Ok(());
                 }
@@ -371,26 +633,161 @@ impl SignalingModule for Media {
                     rabbitmq::Message::PresenterRevoked(selection),
                 )
             }
+            Event::WsMessage(incoming::Message::ConnectivityReport(report)) => {
+                let check = connectivity::ConnectivityCheck::evaluate(&report, ctx.timestamp());
+
+                if let Some(metrics) = ctx.metrics() {
+                    check.record_metrics(metrics);
+                }
+
+                connectivity::set_last_check(ctx.redis_conn(), &self.connectivity_subject, &check)
+                    .await?;
+
+                ctx.ws_send(outgoing::Message::ConnectivityResult(check));
+            }
+
+            Event::WsMessage(incoming::Message::RequestRemoteControl(target)) => {
+                self.handle_request_remote_control(&mut ctx, target).await?;
+            }
+            Event::WsMessage(incoming::Message::GrantRemoteControl(target)) => {
+                self.handle_grant_remote_control(&mut ctx, target).await?;
+            }
+            Event::WsMessage(incoming::Message::DenyRemoteControl(target)) => {
+                self.handle_deny_remote_control(&mut ctx, target).await?;
+            }
+            Event::WsMessage(incoming::Message::RevokeRemoteControl(target)) => {
+                self.handle_revoke_remote_control(&mut ctx, target).await?;
+            }
+
+            Event::Ext(MediaEvent::PanelistStatusChanged(is_panelist)) => {
+                if is_panelist || ctx.role() == Role::Moderator || self.state.is_empty() {
+                    return Ok(());
+                }
+
+                // Attendees cannot publish, stop all media sessions of the demoted panelist
+                for media_session_type in self.state.keys() {
+                    self.media.remove_publisher(*media_session_type).await;
+                }
+
+                self.state.clear();
+                self.talking = false;
+
+                storage::set_state(ctx.redis_conn(), self.room, self.id, &self.state)
+                    .await
+                    .context("Failed to set state attribute in storage")?;
+
+                ctx.invalidate_data();
+            }
+            Event::Ext(MediaEvent::SpotlightsChanged(spotlights)) => {
+                if let Some(focus) = self.focus_detection.set_spotlights(spotlights) {
+                    self.publish_speaker_overlay(&mut ctx, focus).await?;
 
-            Event::Ext((media_session_key, message)) => match message {
+                    ctx.ws_send(outgoing::Message::FocusUpdate(outgoing::FocusUpdate {
+                        focus,
+                    }));
+                }
+            }
+            Event::Ext(MediaEvent::PushToTalkExpired(hold)) => {
+                if self.talking && hold == self.talk_holds {
+                    self.talking = false;
+
+                    self.set_audio(&mut ctx, false).await?;
+
+                    ctx.ws_send(outgoing::Message::PushToTalkExpired);
+                }
+            }
+            Event::Ext(MediaEvent::WebRtc(media_session_key, message)) => match message {
                 WebRtcEvent::AssociatedMcuDied => {
+                    self.record_connection_event(
+                        &mut ctx,
+                        ConnectionEventKind::WebRtcDown {
+                            media_session_type: media_session_key.1.as_type_str().into(),
+                            source: media_session_key.0,
+                            reason: Some("media server disconnected".into()),
+                        },
+                    )
+                    .await;
+
                     self.remove_broken_media_session(&mut ctx, media_session_key)
                         .await?;
                     ctx.ws_send(outgoing::Message::WebRtcDown(media_session_key.into()))
                 }
                 WebRtcEvent::WebRtcUp => {
+                    self.update_handle_stats(&mut ctx, media_session_key, |stats| {
+                        stats.webrtc_up = true
+                    })
+                    .await;
+
                     ctx.ws_send(outgoing::Message::WebRtcUp(media_session_key.into()))
                 }
                 WebRtcEvent::Media(media) => {
+                    self.update_handle_stats(&mut ctx, media_session_key, |stats| {
+                        match media.kind.as_str() {
+                            "audio" => stats.receiving_audio = Some(media.receiving),
+                            "video" => stats.receiving_video = Some(media.receiving),
+                            _ => {}
+                        }
+                    })
+                    .await;
+
                     ctx.ws_send(outgoing::Message::Media((media_session_key, media).into()))
                 }
-                WebRtcEvent::WebRtcDown => {
+                WebRtcEvent::WebRtcDown(reason) => {
+                    self.update_handle_stats(&mut ctx, media_session_key, |stats| {
+                        stats.webrtc_up = false
+                    })
+                    .await;
+
+                    self.record_connection_event(
+                        &mut ctx,
+                        ConnectionEventKind::WebRtcDown {
+                            media_session_type: media_session_key.1.as_type_str().into(),
+                            source: media_session_key.0,
+                            reason,
+                        },
+                    )
+                    .await;
+
                     ctx.ws_send(outgoing::Message::WebRtcDown(media_session_key.into()));
 
                     self.gracefully_remove_media_session(&mut ctx, media_session_key)
                         .await?;
                 }
-                WebRtcEvent::SlowLink(link_direction) => {
+                WebRtcEvent::SlowLink(link_direction, lost) => {
+                    self.update_handle_stats(&mut ctx, media_session_key, |stats| {
+                        stats.slow_links += 1;
+                        stats.lost_packets += lost;
+                    })
+                    .await;
+
+                    // Only used for the room stats, the slow link must be signaled regardless
+                    if let Err(e) = storage::add_lost_packets(
+                        ctx.redis_conn(),
+                        self.room,
+                        self.id,
+                        link_direction,
+                        lost,
+                    )
+                    .await
+                    {
+                        log::warn!("Failed to count lost packets of {}, {:?}", self.id, e);
+                    }
+
+                    self.record_connection_event(
+                        &mut ctx,
+                        ConnectionEventKind::SlowLink {
+                            media_session_type: media_session_key.1.as_type_str().into(),
+                            source: media_session_key.0,
+                            direction: match link_direction {
+                                LinkDirection::Upstream => "upstream",
+                                LinkDirection::Downstream => "downstream",
+                            }
+                            .into(),
+                            lost,
+                        },
+                    )
+                    .await;
+
                     let direction = match link_direction {
                         LinkDirection::Upstream => outgoing::LinkDirection::Upstream,
                         LinkDirection::Downstream => outgoing::LinkDirection::Downstream,
@@ -428,6 +825,8 @@ impl SignalingModule for Media {
             },
             Event::RabbitMq(rabbitmq::Message::StartedTalking(id)) => {
                 if let Some(focus) = self.focus_detection.on_started_talking(id) {
+                    self.publish_speaker_overlay(&mut ctx, focus).await?;
+
                     ctx.ws_send(outgoing::Message::FocusUpdate(outgoing::FocusUpdate {
                         focus,
                     }));
@@ -435,6 +834,8 @@ impl SignalingModule for Media {
             }
             Event::RabbitMq(rabbitmq::Message::StoppedTalking(id)) => {
                 if let Some(focus) = self.focus_detection.on_stopped_talking(id) {
+                    self.publish_speaker_overlay(&mut ctx, focus).await?;
+
                     ctx.ws_send(outgoing::Message::FocusUpdate(outgoing::FocusUpdate {
                         focus,
                     }));
@@ -443,6 +844,23 @@ impl SignalingModule for Media {
             Event::RabbitMq(rabbitmq::Message::RequestMute(request_mute)) => {
                 ctx.ws_send(outgoing::Message::RequestMute(request_mute));
             }
+            Event::RabbitMq(rabbitmq::Message::MuteAll(request_mute)) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::RequestMute(request_mute));
+                }
+            }
+            Event::RabbitMq(rabbitmq::Message::DisableAllVideo(request_mute)) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::RequestDisableVideo(request_mute));
+                }
+            }
+            Event::RabbitMq(rabbitmq::Message::LocksUpdated(locks)) => {
+                if ctx.role() != Role::Moderator {
+                    self.enforce_locks(&mut ctx, locks).await?;
+                }
+
+                ctx.ws_send(outgoing::Message::LocksUpdated(locks));
+            }
             Event::RabbitMq(rabbitmq::Message::PresenterGranted(selection)) => {
                 if !selection.participant_ids.contains(&self.id) {
                     return Ok(());
@@ -481,6 +899,8 @@ impl SignalingModule for Media {
                     storage::set_state(ctx.redis_conn(), self.room, self.id, &self.state)
                         .await
                         .context("Failed to set state attribute in storage")?;
+
+                    self.revoke_remote_control(&mut ctx, self.id, None).await?;
                 }
 
                 ctx.ws_send(outgoing::Message::PresenterRevoked);
@@ -488,6 +908,19 @@ impl SignalingModule for Media {
                 ctx.invalidate_data();
             }
 
+            Event::RabbitMq(rabbitmq::Message::RemoteControlRequested(request)) => {
+                ctx.ws_send(outgoing::Message::RemoteControlRequested(request));
+            }
+            Event::RabbitMq(rabbitmq::Message::RemoteControlDenied(denied)) => {
+                ctx.ws_send(outgoing::Message::RemoteControlDenied(denied));
+            }
+            Event::RabbitMq(rabbitmq::Message::RemoteControlGranted(remote_control)) => {
+                ctx.ws_send(outgoing::Message::RemoteControlGranted(remote_control));
+            }
+            Event::RabbitMq(rabbitmq::Message::RemoteControlRevoked(revoked)) => {
+                ctx.ws_send(outgoing::Message::RemoteControlRevoked(revoked));
+            }
+
             Event::ParticipantJoined(id, evt_state) => {
                 let state = storage::get_state(ctx.redis_conn(), self.room, id)
                     .await
@@ -510,6 +943,8 @@ impl SignalingModule for Media {
                     if let Some(video_state) = state.get(&MediaSessionType::Video) {
                         if !video_state.audio {
                             if let Some(focus) = self.focus_detection.on_stopped_talking(id) {
+                                self.publish_speaker_overlay(&mut ctx, focus).await?;
+
                                 ctx.ws_send(outgoing::Message::FocusUpdate(
                                     outgoing::FocusUpdate { focus },
                                 ));
@@ -532,8 +967,17 @@ impl SignalingModule for Media {
             Event::ParticipantLeft(id) => {
                 self.media.remove_subscribers(id).await;
 
+                // The remote control of the own screen ends with the controlling participant
+                if storage::get_remote_control(ctx.redis_conn(), self.room, self.id).await?
+                    == Some(id)
+                {
+                    self.revoke_remote_control(&mut ctx, self.id, None).await?;
+                }
+
                 // Unfocus leaving participants
                 if let Some(focus) = self.focus_detection.on_stopped_talking(id) {
+                    self.publish_speaker_overlay(&mut ctx, focus).await?;
+
                     ctx.ws_send(outgoing::Message::FocusUpdate(outgoing::FocusUpdate {
                         focus,
                     }));
@@ -561,9 +1005,27 @@ impl SignalingModule for Media {
                 let is_presenter =
                     storage::is_presenter(ctx.redis_conn(), self.room, self.id).await?;
 
-                *frontend_data = Some(FrontendData { is_presenter })
+                let locks = storage::get_locks(ctx.redis_conn(), self.room).await?;
+
+                let push_to_talk = self.push_to_talk.map(|max_hold| PushToTalkPolicy {
+                    max_hold_secs: max_hold.as_secs(),
+                });
+
+                *frontend_data = Some(FrontendData {
+                    is_presenter,
+                    locks,
+                    push_to_talk,
+                })
             }
             Event::Leaving => {
+                if let Err(e) = self.revoke_remote_control(&mut ctx, self.id, None).await {
+                    log::error!(
+                        "Media module for {} failed to revoke the remote control of its screen, {}",
+                        self.id,
+                        e
+                    );
+                }
+
                 if let Err(e) = storage::del_state(ctx.redis_conn(), self.room, self.id).await {
                     log::error!(
                         "Media module for {} failed to remove its state data from redis, {}",
@@ -572,6 +1034,24 @@ impl SignalingModule for Media {
                     );
                 }
 
+                if let Err(e) = storage::del_region(ctx.redis_conn(), self.room, self.id).await {
+                    log::error!(
+                        "Media module for {} failed to remove its region from redis, {}",
+                        self.id,
+                        e
+                    );
+                }
+
+                if let Err(e) =
+                    storage::delete_handle_stats(ctx.redis_conn(), self.room, self.id).await
+                {
+                    log::error!(
+                        "Media module for {} failed to remove its handle stats from redis, {}",
+                        self.id,
+                        e
+                    );
+                }
+
                 // Spawn destroying all the handles as it doesn't need to be synchronized
                 // and should not block the leaving process
                 tokio::task::spawn_local(self.media.destroy());
@@ -590,11 +1070,92 @@ impl SignalingModule for Media {
                     e
                 );
             }
+
+            if let Err(e) = storage::delete_regions_key(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Media module failed to remove regions key on room destroy, {}",
+                    e
+                );
+            }
+
+            if let Err(e) = storage::delete_lost_packets_key(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Media module failed to remove lost packets key on room destroy, {}",
+                    e
+                );
+            }
+
+            if let Err(e) = storage::delete_locks_key(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Media module failed to remove locks key on room destroy, {}",
+                    e
+                );
+            }
+
+            if let Err(e) = storage::delete_remote_controls_key(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Media module failed to remove remote controls key on room destroy, {}",
+                    e
+                );
+            }
+
+            if let Err(e) =
+                storage::delete_remote_control_log_key(ctx.redis_conn(), self.room).await
+            {
+                log::error!(
+                    "Media module failed to remove remote control log key on room destroy, {}",
+                    e
+                );
+            }
         }
     }
+
+    async fn room_stats(
+        _mcu: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<Option<serde_json::Value>> {
+        let stats = stats::collect(redis_conn, room).await?;
+
+        Ok(Some(serde_json::to_value(stats)?))
+    }
 }
 
 impl Media {
+    /// Publish the participant in focus to the recording, if this is the recorder participant
+    async fn publish_speaker_overlay(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        focus: Option<ParticipantId>,
+    ) -> Result<()> {
+        if !self.i_am_the_recorder {
+            return Ok(());
+        }
+
+        let display_name = match focus {
+            Some(participant_id) => {
+                control::storage::get_attribute(
+                    ctx.redis_conn(),
+                    self.room,
+                    participant_id,
+                    "display_name",
+                )
+                .await?
+            }
+            None => None,
+        };
+
+        recording::publish_overlay(
+            ctx,
+            self.room,
+            recording::Overlay::Speaker(recording::overlay::Speaker {
+                participant_id: focus,
+                display_name,
+            }),
+        )
+        .await
+    }
+
     /// Send mute requests to the targeted participants
     ///
     /// Fails if the issuing user is not a moderator.
@@ -604,7 +1165,9 @@ impl Media {
         moderator_mute: RequestMute,
     ) -> Result<()> {
         if ctx.role() != Role::Moderator {
-            ctx.ws_send(outgoing::Message::Error(outgoing::Error::PermissionDenied));
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::PermissionDenied.into(),
+            ));
 
             return Ok(());
         }
@@ -632,6 +1195,427 @@ impl Media {
         Ok(())
     }
 
+    /// Request all non-moderators to mute their audio, or to disable their video if `video` is set
+    ///
+    /// Locks the media if requested. Fails if the issuing user is not a moderator.
+    async fn handle_moderator_mute_all(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        request: RequestMuteAll,
+        video: bool,
+    ) -> Result<()> {
+        if ctx.role() != Role::Moderator {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::PermissionDenied.into(),
+            ));
+
+            return Ok(());
+        }
+
+        if request.lock {
+            let mut locks = storage::get_locks(ctx.redis_conn(), self.room).await?;
+
+            if video {
+                locks.video = true;
+            } else {
+                locks.audio = true;
+            }
+
+            self.update_locks(ctx, locks).await?;
+        }
+
+        let request_mute = rabbitmq::RequestMute {
+            issuer: self.id,
+            force: request.force || request.lock,
+        };
+
+        let message = if video {
+            rabbitmq::Message::DisableAllVideo(request_mute)
+        } else {
+            rabbitmq::Message::MuteAll(request_mute)
+        };
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            message,
+        );
+
+        Ok(())
+    }
+
+    /// Allow the non-moderators to unmute the media again
+    ///
+    /// Fails if the issuing user is not a moderator.
+    async fn handle_unlock_media(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        unlock: UnlockMedia,
+    ) -> Result<()> {
+        if ctx.role() != Role::Moderator {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::PermissionDenied.into(),
+            ));
+
+            return Ok(());
+        }
+
+        let mut locks = storage::get_locks(ctx.redis_conn(), self.room).await?;
+
+        if unlock.audio {
+            locks.audio = false;
+        }
+
+        if unlock.video {
+            locks.video = false;
+        }
+
+        self.update_locks(ctx, locks).await
+    }
+
+    /// Store the locks and notify all participants in the room
+    async fn update_locks(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        locks: MediaLocks,
+    ) -> Result<()> {
+        storage::set_locks(ctx.redis_conn(), self.room, locks).await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::LocksUpdated(locks),
+        );
+
+        Ok(())
+    }
+
+    /// Returns the locks of the room if the state of the media session unmutes locked media
+    ///
+    /// Moderators are not affected by the locks.
+    async fn violated_locks(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        media_session_type: MediaSessionType,
+        state: MediaSessionState,
+    ) -> Result<Option<MediaLocks>> {
+        if media_session_type != MediaSessionType::Video || ctx.role() == Role::Moderator {
+            return Ok(None);
+        }
+
+        let locks = storage::get_locks(ctx.redis_conn(), self.room).await?;
+
+        Ok(Some(locks).filter(|locks| locks.is_violated_by(state)))
+    }
+
+    /// Mute the locked media of the published camera and microphone
+    async fn enforce_locks(
+        &mut self,
+        ctx: &mut ModuleContext<'_, Self>,
+        locks: MediaLocks,
+    ) -> Result<()> {
+        if locks.audio {
+            self.talking = false;
+        }
+
+        let state = match self.state.get(&MediaSessionType::Video) {
+            Some(state) if locks.is_violated_by(*state) => locks.enforce(*state),
+            _ => return Ok(()),
+        };
+
+        self.replace_video_state(ctx, state).await
+    }
+
+    /// Forward the request to control the shared screen to the participant sharing it
+    async fn handle_request_remote_control(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        target: RemoteControlTarget,
+    ) -> Result<()> {
+        let sharer = target.target;
+
+        let is_sharing_screen = storage::get_state(ctx.redis_conn(), self.room, sharer)
+            .await?
+            .map(|state| state.contains_key(&MediaSessionType::Screen))
+            .unwrap_or_default();
+
+        if sharer == self.id || !is_sharing_screen {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::NotSharingScreen.into(),
+            ));
+
+            return Ok(());
+        }
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_participant_routing_key(sharer),
+            rabbitmq::Message::RemoteControlRequested(rabbitmq::RemoteControlRequest {
+                requester: self.id,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Grant the target control over the own shared screen
+    ///
+    /// The input events are sent peer-to-peer, the grant only tells all participants who controls the screen.
+    async fn handle_grant_remote_control(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        target: RemoteControlTarget,
+    ) -> Result<()> {
+        if !self.state.contains_key(&MediaSessionType::Screen) {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::NotSharingScreen.into(),
+            ));
+
+            return Ok(());
+        }
+
+        let room_participants =
+            control::storage::get_all_participants(ctx.redis_conn(), self.room).await?;
+
+        if target.target == self.id || !room_participants.contains(&target.target) {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::PermissionDenied.into(),
+            ));
+
+            return Ok(());
+        }
+
+        if !storage::set_remote_control(ctx.redis_conn(), self.room, self.id, target.target).await?
+        {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::RemoteControlActive.into(),
+            ));
+
+            return Ok(());
+        }
+
+        self.log_remote_control_event(ctx, self.id, target.target, RemoteControlEventKind::Granted)
+            .await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::RemoteControlGranted(rabbitmq::RemoteControl {
+                sharer: self.id,
+                controller: target.target,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Tell the target that its request to control the own shared screen has been denied
+    async fn handle_deny_remote_control(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        target: RemoteControlTarget,
+    ) -> Result<()> {
+        if !self.state.contains_key(&MediaSessionType::Screen) {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::NotSharingScreen.into(),
+            ));
+
+            return Ok(());
+        }
+
+        self.log_remote_control_event(ctx, self.id, target.target, RemoteControlEventKind::Denied)
+            .await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_participant_routing_key(target.target),
+            rabbitmq::Message::RemoteControlDenied(rabbitmq::RemoteControlDenied {
+                sharer: self.id,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Revoke the remote control of the screen shared by the target
+    ///
+    /// Can be done by the participant sharing the screen, the participant controlling it and moderators.
+    async fn handle_revoke_remote_control(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        target: RemoteControlTarget,
+    ) -> Result<()> {
+        let sharer = target.target;
+
+        let controller =
+            match storage::get_remote_control(ctx.redis_conn(), self.room, sharer).await? {
+                Some(controller) => controller,
+                None => {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::NoRemoteControl.into(),
+                    ));
+
+                    return Ok(());
+                }
+            };
+
+        if sharer != self.id && controller != self.id && ctx.role() != Role::Moderator {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::PermissionDenied.into(),
+            ));
+
+            return Ok(());
+        }
+
+        self.revoke_remote_control(ctx, sharer, Some(self.id))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revoke the remote control of the screen shared by `sharer` if there is one
+    ///
+    /// `revoked_by` is `None` if the remote control ended because the screen share or the controller is gone.
+    async fn revoke_remote_control(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        sharer: ParticipantId,
+        revoked_by: Option<ParticipantId>,
+    ) -> Result<()> {
+        let controller =
+            match storage::take_remote_control(ctx.redis_conn(), self.room, sharer).await? {
+                Some(controller) => controller,
+                None => return Ok(()),
+            };
+
+        self.log_remote_control_event(
+            ctx,
+            sharer,
+            controller,
+            RemoteControlEventKind::Revoked { revoked_by },
+        )
+        .await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::RemoteControlRevoked(rabbitmq::RemoteControlRevoked {
+                remote_control: rabbitmq::RemoteControl { sharer, controller },
+                revoked_by,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Add the event to the remote control log of the room
+    async fn log_remote_control_event(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        sharer: ParticipantId,
+        controller: ParticipantId,
+        kind: RemoteControlEventKind,
+    ) -> Result<()> {
+        let event = RemoteControlEvent {
+            timestamp: ctx.timestamp(),
+            sharer,
+            controller,
+            kind,
+        };
+
+        storage::add_remote_control_event(ctx.redis_conn(), self.room, &event).await
+    }
+
+    /// Returns true if the participant is an attendee of a room in webinar mode, which cannot publish any media
+    async fn is_attendee(&self, ctx: &mut ModuleContext<'_, Self>) -> Result<bool> {
+        if !self.webinar_mode || ctx.role() == Role::Moderator {
+            return Ok(false);
+        }
+
+        let is_panelist =
+            moderation::storage::is_panelist(ctx.redis_conn(), self.room.room_id(), self.id)
+                .await?;
+
+        Ok(!is_panelist)
+    }
+
+    /// Press the talk button, transmitting the microphone until released or the maximum hold duration elapsed
+    async fn handle_push_to_talk_start(&mut self, ctx: &mut ModuleContext<'_, Self>) -> Result<()> {
+        let max_hold = match self.push_to_talk {
+            Some(max_hold) => max_hold,
+            None => {
+                ctx.ws_send(outgoing::Message::Error(
+                    outgoing::Error::PushToTalkDisabled.into(),
+                ));
+
+                return Ok(());
+            }
+        };
+
+        // Moderators can always talk
+        if ctx.role() == Role::Moderator || self.talking {
+            return Ok(());
+        }
+
+        if storage::get_locks(ctx.redis_conn(), self.room).await?.audio {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::MediaLocked.into(),
+            ));
+
+            return Ok(());
+        }
+
+        self.talking = true;
+        self.talk_holds += 1;
+
+        let hold = self.talk_holds;
+        ctx.add_event_stream(once(
+            sleep(max_hold).map(move |_| MediaEvent::PushToTalkExpired(hold)),
+        ));
+
+        self.set_audio(ctx, true).await
+    }
+
+    /// Returns if the microphone of the participant is only transmitted while the talk button is held
+    fn push_to_talk_applies(&self, ctx: &ModuleContext<'_, Self>) -> bool {
+        self.push_to_talk.is_some() && ctx.role() != Role::Moderator
+    }
+
+    /// Mute or unmute the published microphone on behalf of the participant
+    async fn set_audio(&mut self, ctx: &mut ModuleContext<'_, Self>, audio: bool) -> Result<()> {
+        let state = match self.state.get(&MediaSessionType::Video) {
+            Some(state) if state.audio != audio => MediaSessionState { audio, ..*state },
+            _ => return Ok(()),
+        };
+
+        self.replace_video_state(ctx, state).await
+    }
+
+    /// Replace the state of the published camera and microphone and configure the publisher accordingly
+    async fn replace_video_state(
+        &mut self,
+        ctx: &mut ModuleContext<'_, Self>,
+        state: MediaSessionState,
+    ) -> Result<()> {
+        let previous_state = self.state.insert(MediaSessionType::Video, state);
+
+        process_metrics_for_media_session_state(
+            ctx,
+            &MediaSessionType::Video,
+            &previous_state,
+            &state,
+        );
+
+        storage::set_state(ctx.redis_conn(), self.room, self.id, &self.state)
+            .await
+            .context("Failed to set state attribute in storage")?;
+
+        ctx.invalidate_data();
+
+        self.handle_publish_state(MediaSessionType::Video, state)
+            .await
+    }
+
     /// Gracefully removes the media session that is associated with the provided MediaSessionKey
     ///
     /// Send detach and destroy messages to janus in order to remove a media session gracefully.
@@ -689,6 +1673,37 @@ impl Media {
         Ok(())
     }
 
+    /// Add the event to the connection history of the participant, failures are only logged
+    async fn record_connection_event(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        kind: ConnectionEventKind,
+    ) {
+        if let Err(e) = connection_history::record(ctx.redis_conn(), self.id, kind).await {
+            log::warn!("Failed to record connection event of {}, {:?}", self.id, e);
+        }
+    }
+
+    /// Update the stats of the handle of the media session, which are only used for the room stats
+    async fn update_handle_stats(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        media_session_key: MediaSessionKey,
+        update: impl FnOnce(&mut storage::HandleStats),
+    ) {
+        if let Err(e) = storage::update_handle_stats(
+            ctx.redis_conn(),
+            self.room,
+            self.id,
+            media_session_key,
+            update,
+        )
+        .await
+        {
+            log::warn!("Failed to update the handle stats of {}, {:?}", self.id, e);
+        }
+    }
+
     #[tracing::instrument(level = "debug", skip(self, ctx, offer))]
     async fn handle_sdp_offer(
         &mut self,
@@ -702,8 +1717,19 @@ impl Media {
             let publisher = if let Some(publisher) = self.media.get_publisher(media_session_type) {
                 publisher
             } else {
+                // Rooms not pinned to a region use the media servers close to most of their participants
+                let region = match &self.pinned_region {
+                    Some(region) => Some(region.clone()),
+                    None => storage::get_majority_region(ctx.redis_conn(), self.room).await?,
+                };
+
                 self.media
-                    .create_publisher(&self.mcu, media_session_type)
+                    .create_publisher(
+                        &self.mcu,
+                        media_session_type,
+                        region.as_deref(),
+                        self.speaker_focus,
+                    )
                     .await?
             };
 
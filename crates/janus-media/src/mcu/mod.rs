@@ -16,10 +16,11 @@ use janus_client::types::{SdpAnswer, SdpOffer};
 use janus_client::{ClientId, JanusMessage, JsepType, RoomId as JanusRoomId, TrickleCandidate};
 use lapin_pool::{RabbitMqChannel, RabbitMqPool};
 use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
 use serde::{Deserialize, Serialize};
 use std::borrow::{Borrow, Cow};
 use std::cmp::min;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::future::Future;
 use std::hash::{Hash, Hasher};
@@ -31,6 +32,8 @@ use tokio::time::{interval_at, sleep, Instant, MissedTickBehavior};
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
+// The types crate is shadowed by the types module below
+use ::types::core::ParticipantId;
 
 mod types;
 
@@ -47,10 +50,67 @@ const PUBLISHER_INFO: &str = "k3k-signaling:mcu:publishers";
 /// busy mcu for a new publisher.
 const MCU_LOAD: &str = "k3k-signaling:mcu:load";
 
+/// Redis key of the subscriptions of a participant
+///
+/// Maps the media session keys the participant is subscribed to onto a [`SubscriptionInfo`]. This information is
+/// used for the room stats.
+#[derive(ToRedisArgs)]
+#[to_redis_args(
+    fmt = "k3k-signaling:room={room}:participant={subscriber}:namespace=media:subscriptions"
+)]
+struct Subscriptions {
+    room: SignalingRoomId,
+    subscriber: ParticipantId,
+}
+
+/// Time in seconds after which the subscriptions of a participant expire, in case they are not removed when the
+/// subscribers are destroyed, e.g. because the controller crashed
+const SUBSCRIPTIONS_EXPIRY: usize = 24 * 60 * 60;
+
 #[derive(Debug, Serialize, Deserialize)]
-struct PublisherInfo<'i> {
-    room_id: JanusRoomId,
-    mcu_id: Cow<'i, str>,
+pub(crate) struct PublisherInfo<'i> {
+    pub room_id: JanusRoomId,
+    pub mcu_id: Cow<'i, str>,
+    /// Bitrate cap of the janus room, missing for publishers created by older controllers
+    #[serde(default)]
+    pub bitrate: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SubscriptionInfo {
+    pub participant_id: ParticipantId,
+    pub media_session_type: MediaSessionType,
+}
+
+/// Get the info about the janus room and instance serving the publisher
+pub(crate) async fn get_publisher_info(
+    redis: &mut RedisConnection,
+    media_session_key: MediaSessionKey,
+) -> Result<Option<PublisherInfo<'static>>> {
+    let json: Option<String> = redis
+        .hget(PUBLISHER_INFO, media_session_key.to_string())
+        .await
+        .context("Failed to get publisher info")?;
+
+    json.map(|json| serde_json::from_str(&json).context("Failed to deserialize publisher info"))
+        .transpose()
+}
+
+/// Get the media sessions the participant is subscribed to
+pub(crate) async fn get_subscriptions(
+    redis: &mut RedisConnection,
+    room: SignalingRoomId,
+    subscriber: ParticipantId,
+) -> Result<Vec<SubscriptionInfo>> {
+    let subscriptions: HashMap<String, String> = redis
+        .hgetall(Subscriptions { room, subscriber })
+        .await
+        .context("Failed to get subscriptions")?;
+
+    subscriptions
+        .values()
+        .map(|json| serde_json::from_str(json).context("Failed to deserialize subscription info"))
+        .collect()
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -80,7 +140,8 @@ impl From<&settings::Connection> for McuId {
 
 /// Pool of one or more configured `McuClient`s
 ///
-/// Distributes new publishers to a available Mcu with the least amount of subscribers
+/// Distributes new publishers to a available Mcu with the least amount of subscribers, preferring the Mcus of the
+/// requested region
 pub struct McuPool {
     // Clients shared with the global receive task which sends keep-alive messages
     // and removes clients of vanished  janus instances
@@ -243,39 +304,57 @@ impl McuPool {
         Ok(())
     }
 
+    /// Choose the least busy mcu, preferring the mcus of the given region
+    ///
+    /// Falls back to the mcus of other regions if no mcu of the region is available.
     async fn choose_client<'guard>(
         &self,
         redis: &mut RedisConnection,
         clients: &'guard RwLockReadGuard<'guard, HashSet<McuClient>>,
+        region: Option<&str>,
     ) -> Result<&'guard McuClient> {
         // Get all mcu's in order lowest to highest
         let ids: Vec<String> = redis.zrangebyscore(MCU_LOAD, "-inf", "+inf").await?;
 
-        // choose the first available mcu
-        for id in ids {
-            if let Some(client) = clients.get(id.as_str()) {
+        let available: Vec<&McuClient> = ids
+            .iter()
+            .filter_map(|id| clients.get(id.as_str()))
+            .collect();
+
+        if let Some(region) = region {
+            if let Some(client) = available
+                .iter()
+                .find(|client| client.config.region.as_deref() == Some(region))
+                .copied()
+            {
                 return Ok(client);
             }
         }
 
-        bail!("Failed to choose client")
+        // choose the first available mcu
+        match available.first().copied() {
+            Some(client) => Ok(client),
+            None => bail!("Failed to choose client"),
+        }
     }
 
     pub async fn new_publisher(
         &self,
         event_sink: mpsc::Sender<(MediaSessionKey, WebRtcEvent)>,
         media_session_key: MediaSessionKey,
+        region: Option<&str>,
+        speaker_focus: SpeakerFocus,
     ) -> Result<JanusPublisher> {
         let mut redis = self.redis.clone();
 
         let clients = self.clients.read().await;
         let client = self
-            .choose_client(&mut redis, &clients)
+            .choose_client(&mut redis, &clients, region)
             .await
             .context("Failed to choose McuClient")?;
 
-        let (handle, room_id) = self
-            .create_publisher_handle(client, media_session_key)
+        let (handle, room_id, bitrate) = self
+            .create_publisher_handle(client, media_session_key, speaker_focus)
             .await
             .context("Failed to get or create publisher handle")?;
 
@@ -284,6 +363,7 @@ impl McuPool {
         let info = serde_json::to_string(&PublisherInfo {
             room_id,
             mcu_id: Cow::Borrowed(client.id.0.as_ref()),
+            bitrate: Some(bitrate),
         })
         .context("Failed to serialize publisher info")?;
 
@@ -315,7 +395,8 @@ impl McuPool {
         &self,
         client: &McuClient,
         media_session_key: MediaSessionKey,
-    ) -> Result<(janus_client::Handle, JanusRoomId)> {
+        speaker_focus: SpeakerFocus,
+    ) -> Result<(janus_client::Handle, JanusRoomId, u64)> {
         let handle = client
             .session
             .attach_to_plugin(janus_client::JanusPlugin::VideoRoom)
@@ -341,8 +422,12 @@ impl McuPool {
             bitrate_cap: Some(true),
             audiolevel_event: Some(true),
             audiolevel_ext: Some(true),
-            audio_active_packets: Some(settings.speaker_focus_packets),
-            audio_level_average: Some(settings.speaker_focus_level),
+            audio_active_packets: Some(
+                speaker_focus
+                    .packets
+                    .unwrap_or(settings.speaker_focus_packets),
+            ),
+            audio_level_average: Some(speaker_focus.level.unwrap_or(settings.speaker_focus_level)),
             ..Default::default()
         };
 
@@ -378,7 +463,7 @@ impl McuPool {
                     room_id
                 );
 
-                Ok((handle, room_id))
+                Ok((handle, room_id, bitrate))
             }
             janus_client::incoming::VideoRoomPluginDataJoined::Err(e) => {
                 bail!("Failed to join videoroom, got error response: {}", e);
@@ -389,6 +474,8 @@ impl McuPool {
     pub async fn new_subscriber(
         &self,
         event_sink: mpsc::Sender<(MediaSessionKey, WebRtcEvent)>,
+        room: SignalingRoomId,
+        subscriber: ParticipantId,
         media_session_key: MediaSessionKey,
     ) -> Result<JanusSubscriber> {
         let mut redis = self.redis.clone();
@@ -419,6 +506,26 @@ impl McuPool {
             .await
             .context("Failed to increment subscriber count")?;
 
+        let subscription = serde_json::to_string(&SubscriptionInfo {
+            participant_id: media_session_key.0,
+            media_session_type: media_session_key.1,
+        })
+        .context("Failed to serialize subscription info")?;
+
+        redis::pipe()
+            .atomic()
+            .hset(
+                Subscriptions { room, subscriber },
+                media_session_key.to_string(),
+                subscription,
+            )
+            .ignore()
+            .expire(Subscriptions { room, subscriber }, SUBSCRIPTIONS_EXPIRY)
+            .ignore()
+            .query_async::<_, ()>(&mut redis)
+            .await
+            .context("Failed to set subscription info")?;
+
         let (destroy, destroy_sig) = oneshot::channel();
 
         tokio::spawn(JanusSubscriber::run(
@@ -433,6 +540,8 @@ impl McuPool {
             handle: handle.clone(),
             room_id: info.room_id,
             mcu_id: client.id.clone(),
+            room,
+            subscriber,
             media_session_key,
             redis,
             destroy,
@@ -923,7 +1032,7 @@ impl JanusPublisher {
                     if let Ok(shutdown_signal) = shutdown_signal {
                         match shutdown_signal {
                             ShutdownSignal::Graceful => {
-                                let _ = event_sink.send((media_session_key, WebRtcEvent::WebRtcDown)).await;
+                                let _ = event_sink.send((media_session_key, WebRtcEvent::WebRtcDown(None))).await;
                             }
                             ShutdownSignal::AlreadyDisconnected => {
                                 let _ = event_sink.send((media_session_key, WebRtcEvent::AssociatedMcuDied)).await;
@@ -963,6 +1072,8 @@ pub struct JanusSubscriber {
     handle: janus_client::Handle,
     room_id: JanusRoomId,
     mcu_id: McuId,
+    room: SignalingRoomId,
+    subscriber: ParticipantId,
     media_session_key: MediaSessionKey,
     redis: RedisConnection,
     destroy: oneshot::Sender<()>,
@@ -1021,6 +1132,17 @@ impl JanusSubscriber {
             .await
             .context("Failed to decrease subscriber count")?;
 
+        self.redis
+            .hdel::<_, _, ()>(
+                Subscriptions {
+                    room: self.room,
+                    subscriber: self.subscriber,
+                },
+                self.media_session_key.to_string(),
+            )
+            .await
+            .context("Failed to delete subscription info")?;
+
         detach_result.map_err(From::from)
     }
 
@@ -1109,7 +1231,7 @@ impl JanusSubscriber {
                     if let Ok(shutdown_signal) = shutdown_signal {
                         match shutdown_signal {
                             ShutdownSignal::Graceful => {
-                                let _ = event_sink.send((media_session_key, WebRtcEvent::WebRtcDown)).await;
+                                let _ = event_sink.send((media_session_key, WebRtcEvent::WebRtcDown(None))).await;
                             }
                             ShutdownSignal::AlreadyDisconnected => {
                                 let _ = event_sink.send((media_session_key, WebRtcEvent::AssociatedMcuDied)).await;
@@ -1222,15 +1344,18 @@ async fn forward_janus_message(
                 }
             }
         }
-        janus_client::JanusMessage::Hangup(_) => {
+        janus_client::JanusMessage::Hangup(event) => {
             event_sink
-                .send((media_session_key, WebRtcEvent::WebRtcDown))
+                .send((
+                    media_session_key,
+                    WebRtcEvent::WebRtcDown(Some(event.reason)),
+                ))
                 .await?;
             return Ok(());
         }
         janus_client::JanusMessage::Detached(_) => {
             event_sink
-                .send((media_session_key, WebRtcEvent::WebRtcDown))
+                .send((media_session_key, WebRtcEvent::WebRtcDown(None)))
                 .await?;
             return Ok(());
         }
@@ -1254,9 +1379,9 @@ async fn forward_janus_message(
         }
         janus_client::JanusMessage::SlowLink(event) => {
             let slow_link = if event.uplink {
-                WebRtcEvent::SlowLink(LinkDirection::Upstream)
+                WebRtcEvent::SlowLink(LinkDirection::Upstream, event.lost)
             } else {
-                WebRtcEvent::SlowLink(LinkDirection::Downstream)
+                WebRtcEvent::SlowLink(LinkDirection::Downstream, event.lost)
             };
 
             event_sink.send((media_session_key, slow_link)).await?;
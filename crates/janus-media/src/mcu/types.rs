@@ -6,6 +6,7 @@ use crate::incoming::SubscriberConfiguration;
 use anyhow::anyhow;
 use controller::prelude::*;
 use janus_client::{Jsep, TrickleCandidate};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use types::core::ParticipantId;
@@ -28,6 +29,13 @@ pub struct PublishConfiguration {
     pub audio: bool,
 }
 
+/// Audio level thresholds of a room, overriding the configured `speaker_focus_packets` and `speaker_focus_level`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpeakerFocus {
+    pub packets: Option<i64>,
+    pub level: Option<i64>,
+}
+
 #[derive(Debug)]
 pub enum Response {
     SdpAnswer(Jsep),
@@ -39,9 +47,11 @@ pub enum Response {
 #[derive(Debug)]
 pub enum WebRtcEvent {
     WebRtcUp,
-    WebRtcDown,
+    /// The webrtc connection went down, contains the reason if janus hung up the connection
+    WebRtcDown(Option<String>),
     Media(Media),
-    SlowLink(LinkDirection),
+    /// Janus detected a slow link, contains the number of lost packets
+    SlowLink(LinkDirection, u64),
     Trickle(TrickleMessage),
     AssociatedMcuDied,
     StartedTalking,
@@ -63,7 +73,7 @@ impl From<janus_client::incoming::Media> for Media {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum LinkDirection {
     Upstream,
     Downstream,
@@ -76,7 +86,7 @@ pub enum TrickleMessage {
 }
 
 /// The type of media session
-#[derive(Hash, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Hash, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 pub enum MediaSessionType {
     #[serde(rename = "video")]
     Video,
@@ -5,11 +5,15 @@
 use crate::incoming::Target;
 use crate::mcu::{self, MediaSessionKey, MediaSessionType};
 use crate::rabbitmq;
+use crate::MediaLocks;
+use controller::prelude::connectivity::ConnectivityCheck;
 use janus_client::TrickleCandidate;
+use schemars::JsonSchema;
 use serde::Serialize;
 use types::core::ParticipantId;
+use types::signaling::{ErrorEnvelope, ModuleError};
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message")]
 pub enum Message {
     /// SDP Offer, renegotiate publish
@@ -50,18 +54,49 @@ pub enum Message {
     #[serde(rename = "request_mute")]
     RequestMute(rabbitmq::RequestMute),
 
+    #[serde(rename = "request_disable_video")]
+    RequestDisableVideo(rabbitmq::RequestMute),
+
+    /// The media locks of the room changed
+    #[serde(rename = "locks_updated")]
+    LocksUpdated(MediaLocks),
+
+    /// The talk button has been held for too long, the microphone has been muted
+    #[serde(rename = "push_to_talk_expired")]
+    PushToTalkExpired,
+
     #[serde(rename = "presenter_granted")]
     PresenterGranted,
 
     #[serde(rename = "presenter_revoked")]
     PresenterRevoked,
 
+    /// Result of the connectivity pre-check reported by the participant
+    #[serde(rename = "connectivity_result")]
+    ConnectivityResult(ConnectivityCheck),
+
+    /// A participant requests control over the own shared screen
+    #[serde(rename = "remote_control_requested")]
+    RemoteControlRequested(rabbitmq::RemoteControlRequest),
+
+    /// The participant sharing the screen denied the remote control request
+    #[serde(rename = "remote_control_denied")]
+    RemoteControlDenied(rabbitmq::RemoteControlDenied),
+
+    /// A participant has been granted control over a shared screen
+    #[serde(rename = "remote_control_granted")]
+    RemoteControlGranted(rabbitmq::RemoteControl),
+
+    /// The control over a shared screen has been revoked
+    #[serde(rename = "remote_control_revoked")]
+    RemoteControlRevoked(rabbitmq::RemoteControlRevoked),
+
     /// Contains a error about what request failed. See [`Error`]
     #[serde(rename = "error")]
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Sdp {
     /// The payload of the sdp message
     pub sdp: String,
@@ -70,7 +105,7 @@ pub struct Sdp {
     pub source: Source,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct SdpCandidate {
     /// The payload of the sdp message
     pub candidate: TrickleCandidate,
@@ -79,7 +114,7 @@ pub struct SdpCandidate {
     pub source: Source,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Source {
     /// The source of this message
     pub source: ParticipantId,
@@ -106,7 +141,7 @@ impl From<Target> for Source {
     }
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Media {
     #[serde(flatten)]
     pub source: Source,
@@ -124,27 +159,27 @@ impl From<(MediaSessionKey, mcu::Media)> for Media {
     }
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum LinkDirection {
     Upstream,
     Downstream,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Link {
     pub direction: LinkDirection,
     #[serde(flatten)]
     pub source: Source,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct FocusUpdate {
     pub focus: Option<ParticipantId>,
 }
 
 /// Represents a error of the janus media module
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "error")]
 pub enum Error {
     InvalidSdpOffer,
@@ -154,6 +189,45 @@ pub enum Error {
     InvalidRequestOffer(Source),
     InvalidConfigureRequest(Source),
     PermissionDenied,
+    /// The media has been locked by a moderator
+    MediaLocked,
+    /// The microphone can only be unmuted by holding the talk button
+    PushToTalk,
+    PushToTalkDisabled,
+    /// The participant is not sharing a screen
+    NotSharingScreen,
+    /// The shared screen is already controlled by another participant
+    RemoteControlActive,
+    /// The shared screen is not controlled by anyone
+    NoRemoteControl,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "media";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InvalidSdpOffer => "The SDP offer could not be handled",
+            Self::HandleSdpAnswer => "The SDP answer could not be handled",
+            Self::InvalidCandidate => "The ICE candidate could not be handled",
+            Self::InvalidEndOfCandidates => "The end of ICE candidates could not be handled",
+            Self::InvalidRequestOffer(_) => "The offer for the media session could not be created",
+            Self::InvalidConfigureRequest(_) => "The media session could not be configured",
+            Self::PermissionDenied => "Insufficient permissions for the operation",
+            Self::MediaLocked => "The media has been locked by a moderator",
+            Self::PushToTalk => "The microphone can only be unmuted by holding the talk button",
+            Self::PushToTalkDisabled => "Push-to-talk is disabled in the room",
+            Self::NotSharingScreen => "The participant is not sharing a screen",
+            Self::RemoteControlActive => {
+                "The shared screen is already controlled by another participant"
+            }
+            Self::NoRemoteControl => "The shared screen is not controlled by anyone",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(self, Self::InvalidRequestOffer(_))
+    }
 }
 
 #[cfg(test)]
@@ -329,6 +403,40 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_request_disable_video() {
+        let request_disable_video = Message::RequestDisableVideo(RequestMute {
+            issuer: ParticipantId::nil(),
+            force: true,
+        });
+
+        assert_eq_json!(
+            request_disable_video,
+            {
+                "message": "request_disable_video",
+                "issuer": "00000000-0000-0000-0000-000000000000",
+                "force": true
+            }
+        );
+    }
+
+    #[test]
+    fn test_locks_updated() {
+        let locks_updated = Message::LocksUpdated(MediaLocks {
+            audio: true,
+            video: false,
+        });
+
+        assert_eq_json!(
+            locks_updated,
+            {
+                "message": "locks_updated",
+                "audio": true,
+                "video": false
+            }
+        );
+    }
+
     #[test]
     fn test_errors() {
         let errors_and_expected = vec![
@@ -370,6 +478,12 @@ mod test {
                     "media_session_type": "video"
                 }),
             ),
+            (Error::MediaLocked, json!({"error": "media_locked"})),
+            (Error::PushToTalk, json!({"error": "push_to_talk"})),
+            (
+                Error::PushToTalkDisabled,
+                json!({"error": "push_to_talk_disabled"}),
+            ),
         ];
 
         for (error, expected) in errors_and_expected {
@@ -379,6 +493,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn error_envelope() {
+        let error = Message::Error(
+            Error::InvalidRequestOffer(Source {
+                source: ParticipantId::nil(),
+                media_session_type: MediaSessionType::Video,
+            })
+            .into(),
+        );
+
+        assert_eq_json!(
+            error,
+            {
+                "message": "error",
+                "module": "media",
+                "error": "invalid_request_offer",
+                "source": "00000000-0000-0000-0000-000000000000",
+                "media_session_type": "video",
+                "text": "The offer for the media session could not be created",
+                "retryable": true
+            }
+        );
+    }
+
     #[test]
     fn presenter_granted() {
         let presenter_granted = Message::PresenterGranted;
@@ -402,4 +540,66 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn remote_control_granted() {
+        let remote_control_granted = Message::RemoteControlGranted(rabbitmq::RemoteControl {
+            sharer: ParticipantId::nil(),
+            controller: ParticipantId::from_u128(1),
+        });
+
+        assert_eq_json!(
+            remote_control_granted,
+            {
+                "message": "remote_control_granted",
+                "sharer": "00000000-0000-0000-0000-000000000000",
+                "controller": "00000000-0000-0000-0000-000000000001"
+            }
+        );
+    }
+
+    #[test]
+    fn remote_control_revoked() {
+        let remote_control_revoked =
+            Message::RemoteControlRevoked(rabbitmq::RemoteControlRevoked {
+                remote_control: rabbitmq::RemoteControl {
+                    sharer: ParticipantId::nil(),
+                    controller: ParticipantId::from_u128(1),
+                },
+                revoked_by: None,
+            });
+
+        assert_eq_json!(
+            remote_control_revoked,
+            {
+                "message": "remote_control_revoked",
+                "sharer": "00000000-0000-0000-0000-000000000000",
+                "controller": "00000000-0000-0000-0000-000000000001"
+            }
+        );
+    }
+
+    #[test]
+    fn connectivity_result() {
+        use controller::prelude::connectivity::ConnectivityOutcome;
+        use types::core::Timestamp;
+
+        let connectivity_result = Message::ConnectivityResult(ConnectivityCheck {
+            outcome: ConnectivityOutcome::Relayed,
+            stun_reachable: false,
+            turn_reachable: true,
+            checked_at: Timestamp::unix_epoch(),
+        });
+
+        assert_eq_json!(
+            connectivity_result,
+            {
+                "message": "connectivity_result",
+                "outcome": "relayed",
+                "stun_reachable": false,
+                "turn_reachable": true,
+                "checked_at": "1970-01-01T00:00:00Z"
+            }
+        );
+    }
 }
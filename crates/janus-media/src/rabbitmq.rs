@@ -2,24 +2,66 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::ParticipantId;
 
 use crate::incoming::ParticipantSelection;
+use crate::MediaLocks;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
     StartedTalking(ParticipantId),
     StoppedTalking(ParticipantId),
     RequestMute(RequestMute),
+    /// Mute request to all non-moderators in the room
+    MuteAll(RequestMute),
+    /// Request to all non-moderators in the room to disable their video
+    DisableAllVideo(RequestMute),
+    LocksUpdated(MediaLocks),
     PresenterGranted(ParticipantSelection),
     PresenterRevoked(ParticipantSelection),
+    /// Sent to the participant sharing the screen
+    RemoteControlRequested(RemoteControlRequest),
+    /// Sent to the participant which requested the remote control
+    RemoteControlDenied(RemoteControlDenied),
+    RemoteControlGranted(RemoteControl),
+    RemoteControlRevoked(RemoteControlRevoked),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct RequestMute {
     /// The issuer of the mute request
     pub issuer: ParticipantId,
     /// Flag to determine if the mute shall be forced or not
     pub force: bool,
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct RemoteControlRequest {
+    /// The participant requesting control over the shared screen
+    pub requester: ParticipantId,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct RemoteControlDenied {
+    /// The participant sharing the screen
+    pub sharer: ParticipantId,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct RemoteControl {
+    /// The participant sharing the screen
+    pub sharer: ParticipantId,
+    /// The participant controlling the shared screen
+    pub controller: ParticipantId,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct RemoteControlRevoked {
+    #[serde(flatten)]
+    pub remote_control: RemoteControl,
+    /// The participant which revoked the remote control, missing if the screen share or the controller is gone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_by: Option<ParticipantId>,
+}
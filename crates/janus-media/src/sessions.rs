@@ -3,7 +3,8 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::mcu::{
-    JanusPublisher, JanusSubscriber, McuPool, MediaSessionKey, MediaSessionType, WebRtcEvent,
+    JanusPublisher, JanusSubscriber, McuPool, MediaSessionKey, MediaSessionType, SpeakerFocus,
+    WebRtcEvent,
 };
 use crate::MediaSessionState;
 use anyhow::{ensure, Result};
@@ -15,6 +16,7 @@ use types::core::ParticipantId;
 
 pub struct MediaSessions {
     id: ParticipantId,
+    room: SignalingRoomId,
 
     // All publishers that belong to the participant
     publishers: HashMap<MediaSessionType, JanusPublisher>,
@@ -27,22 +29,31 @@ pub struct MediaSessions {
 }
 
 impl MediaSessions {
-    pub fn new(id: ParticipantId, sender: mpsc::Sender<(MediaSessionKey, WebRtcEvent)>) -> Self {
+    pub fn new(
+        id: ParticipantId,
+        room: SignalingRoomId,
+        sender: mpsc::Sender<(MediaSessionKey, WebRtcEvent)>,
+    ) -> Self {
         Self {
             id,
+            room,
             publishers: Default::default(),
             subscribers: Default::default(),
             sender,
         }
     }
 
-    /// Creates a new [JanusPublisher] for this stream
+    /// Creates a new [JanusPublisher] for this stream, preferably on a mcu of the given region
+    ///
+    /// The audio level thresholds of the publisher are overridden by the given [SpeakerFocus].
     ///
     /// The created [JanusPublisher] is stored and a reference is returned.
     pub async fn create_publisher(
         &mut self,
         mcu_client: &McuPool,
         media_session_type: MediaSessionType,
+        region: Option<&str>,
+        speaker_focus: SpeakerFocus,
     ) -> Result<&JanusPublisher> {
         ensure!(
             !self.publishers.contains_key(&media_session_type),
@@ -53,6 +64,8 @@ impl MediaSessions {
             .new_publisher(
                 self.sender.clone(),
                 MediaSessionKey(self.id, media_session_type),
+                region,
+                speaker_focus,
             )
             .await?;
 
@@ -87,6 +100,8 @@ impl MediaSessions {
         let subscriber = mcu_client
             .new_subscriber(
                 self.sender.clone(),
+                self.room,
+                self.id,
                 MediaSessionKey(participant, media_session_type),
             )
             .await?;
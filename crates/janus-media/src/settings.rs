@@ -57,6 +57,9 @@ pub struct Connection {
     pub exchange: String,
     #[serde(default = "default_from_janus_routing_key")]
     pub from_routing_key: String,
+    /// Region the janus instance is located in
+    #[serde(default)]
+    pub region: Option<String>,
 }
 
 const fn default_max_video_bitrate() -> u64 {
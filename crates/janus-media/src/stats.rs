@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Statistics about the media sessions of a running room
+//!
+//! Assembled from the media state stored in redis, to let support inspect which participant publishes and receives
+//! which media, and on which janus instance, without joining the room.
+use crate::mcu::{self, MediaSessionKey, MediaSessionType};
+use crate::storage::{self, HandleStats};
+use crate::RemoteControlEvent;
+use anyhow::Result;
+use controller::prelude::*;
+use serde::Serialize;
+use types::core::ParticipantId;
+
+#[derive(Debug, Serialize)]
+pub struct RoomMediaStats {
+    pub participants: Vec<ParticipantMediaStats>,
+    /// Remote controls of shared screens granted, denied and revoked in the room, oldest first
+    pub remote_control_log: Vec<RemoteControlEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParticipantMediaStats {
+    pub participant_id: ParticipantId,
+    pub region: Option<String>,
+    pub publishers: Vec<PublisherStats>,
+    pub subscriptions: Vec<SubscriptionStats>,
+    pub lost_packets: LostPacketsStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublisherStats {
+    pub media_session_type: MediaSessionType,
+    pub audio: bool,
+    pub video: bool,
+    /// Janus instance serving the publisher, missing if the publisher has not been created yet
+    pub janus_instance: Option<String>,
+    pub janus_room: Option<u64>,
+    /// Bitrate cap of the janus room in bits per second
+    pub bitrate: Option<u64>,
+    /// State of the janus handle, missing if janus has not reported any event of the handle yet
+    pub handle: Option<HandleStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionStats {
+    pub participant_id: ParticipantId,
+    pub media_session_type: MediaSessionType,
+    /// State of the janus handle, missing if janus has not reported any event of the handle yet
+    pub handle: Option<HandleStats>,
+}
+
+/// Packets lost since the participant joined, as reported by the slow link events of janus
+#[derive(Debug, Default, Serialize)]
+pub struct LostPacketsStats {
+    pub upstream: u64,
+    pub downstream: u64,
+}
+
+/// Collect the media stats of all participants in the room
+pub async fn collect(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<RoomMediaStats> {
+    let participant_ids = control::storage::get_all_participants(redis_conn, room).await?;
+
+    let mut participants = Vec::with_capacity(participant_ids.len());
+
+    for participant_id in participant_ids {
+        participants.push(collect_participant(redis_conn, room, participant_id).await?);
+    }
+
+    participants.sort_by_key(|participant| participant.participant_id);
+
+    let remote_control_log = storage::get_remote_control_events(redis_conn, room).await?;
+
+    Ok(RoomMediaStats {
+        participants,
+        remote_control_log,
+    })
+}
+
+async fn collect_participant(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant_id: ParticipantId,
+) -> Result<ParticipantMediaStats> {
+    let state = storage::get_state(redis_conn, room, participant_id)
+        .await?
+        .unwrap_or_default();
+
+    let mut handles = storage::get_handle_stats(redis_conn, room, participant_id).await?;
+
+    let mut publishers = Vec::with_capacity(state.len());
+
+    for (media_session_type, session_state) in state {
+        let media_session_key = MediaSessionKey(participant_id, media_session_type);
+
+        let info = mcu::get_publisher_info(redis_conn, media_session_key).await?;
+
+        publishers.push(PublisherStats {
+            media_session_type,
+            audio: session_state.audio,
+            video: session_state.video,
+            janus_instance: info.as_ref().map(|info| info.mcu_id.to_string()),
+            janus_room: info.as_ref().map(|info| info.room_id.into()),
+            bitrate: info.and_then(|info| info.bitrate),
+            handle: handles.remove(&media_session_key.to_string()),
+        });
+    }
+
+    publishers.sort_by_key(|publisher| u64::from(publisher.media_session_type));
+
+    let mut subscriptions: Vec<_> = mcu::get_subscriptions(redis_conn, room, participant_id)
+        .await?
+        .into_iter()
+        .map(|subscription| SubscriptionStats {
+            participant_id: subscription.participant_id,
+            media_session_type: subscription.media_session_type,
+            handle: handles.remove(
+                &MediaSessionKey(subscription.participant_id, subscription.media_session_type)
+                    .to_string(),
+            ),
+        })
+        .collect();
+
+    subscriptions.sort_by_key(|subscription| {
+        (
+            subscription.participant_id,
+            u64::from(subscription.media_session_type),
+        )
+    });
+
+    let region = storage::get_region(redis_conn, room, participant_id).await?;
+
+    let (upstream, downstream) =
+        storage::get_lost_packets(redis_conn, room, participant_id).await?;
+
+    Ok(ParticipantMediaStats {
+        participant_id,
+        region,
+        publishers,
+        subscriptions,
+        lost_packets: LostPacketsStats {
+            upstream: upstream.unwrap_or_default(),
+            downstream: downstream.unwrap_or_default(),
+        },
+    })
+}
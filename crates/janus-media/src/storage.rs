@@ -2,11 +2,14 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use super::State;
+use super::{MediaLocks, RemoteControlEvent, State};
+use crate::mcu::{LinkDirection, MediaSessionKey};
 use anyhow::{Context, Result};
 use controller::prelude::*;
 use redis::AsyncCommands;
 use redis_args::ToRedisArgs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use types::core::ParticipantId;
 
 /// Data related to a module inside a participant
@@ -115,3 +118,480 @@ pub async fn delete_presenter_key(
 
     Ok(())
 }
+
+/// Regions of the participants in the room, as determined by the load balancer
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:namespace=media:regions")]
+struct ParticipantRegions {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_region(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    region: &str,
+) -> Result<()> {
+    redis_conn
+        .hset(ParticipantRegions { room }, participant, region)
+        .await
+        .context("Failed to set participant region")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn del_region(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .hdel(ParticipantRegions { room }, participant)
+        .await
+        .context("Failed to delete participant region")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_regions_key(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(ParticipantRegions { room })
+        .await
+        .context("Failed to delete participant regions")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_region(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<Option<String>> {
+    redis_conn
+        .hget(ParticipantRegions { room }, participant)
+        .await
+        .context("Failed to get participant region")
+}
+
+/// Returns the region most participants of the room are located in
+///
+/// Ties are resolved by choosing the alphabetically first region, so all participants choose the same region.
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_majority_region(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Option<String>> {
+    let regions: Vec<String> = redis_conn
+        .hvals(ParticipantRegions { room })
+        .await
+        .context("Failed to get participant regions")?;
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+
+    for region in regions {
+        *counts.entry(region).or_default() += 1;
+    }
+
+    let mut majority: Option<(String, usize)> = None;
+
+    for (region, count) in counts {
+        if majority.as_ref().map_or(true, |(_, max)| count > *max) {
+            majority = Some((region, count));
+        }
+    }
+
+    Ok(majority.map(|(region, _)| region))
+}
+
+/// Number of packets lost on the links of the participants, as reported by the slow link events of janus
+///
+/// The fields are `{participant}:upstream` and `{participant}:downstream`.
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:namespace=media:lost_packets")]
+struct LostPackets {
+    room: SignalingRoomId,
+}
+
+fn lost_packets_field(participant: ParticipantId, direction: LinkDirection) -> String {
+    match direction {
+        LinkDirection::Upstream => format!("{participant}:upstream"),
+        LinkDirection::Downstream => format!("{participant}:downstream"),
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_lost_packets(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    direction: LinkDirection,
+    lost: u64,
+) -> Result<()> {
+    redis_conn
+        .hincr(
+            LostPackets { room },
+            lost_packets_field(participant, direction),
+            lost,
+        )
+        .await
+        .context("Failed to add lost packets")
+}
+
+/// Returns the number of upstream and downstream packets the participant lost
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_lost_packets(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<(Option<u64>, Option<u64>)> {
+    redis_conn
+        .hget(
+            LostPackets { room },
+            &[
+                lost_packets_field(participant, LinkDirection::Upstream),
+                lost_packets_field(participant, LinkDirection::Downstream),
+            ],
+        )
+        .await
+        .context("Failed to get lost packets")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_lost_packets_key(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(LostPackets { room })
+        .await
+        .context("Failed to delete lost packets")
+}
+
+/// Media of the room which non-moderators are not allowed to unmute, see [`MediaLocks`]
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:namespace=media:locks")]
+struct Locks {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_locks(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    locks: MediaLocks,
+) -> Result<()> {
+    redis_conn
+        .set(Locks { room }, locks)
+        .await
+        .context("Failed to set media locks")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_locks(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<MediaLocks> {
+    let locks: Option<MediaLocks> = redis_conn
+        .get(Locks { room })
+        .await
+        .context("Failed to get media locks")?;
+
+    Ok(locks.unwrap_or_default())
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_locks_key(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(Locks { room })
+        .await
+        .context("Failed to delete media locks")
+}
+
+/// Remote controls of shared screens, mapping the participant sharing the screen to the participant controlling it
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:namespace=media:remote_controls")]
+struct RemoteControls {
+    room: SignalingRoomId,
+}
+
+/// Set the controller of the screen shared by `sharer`, returns false if the screen is already controlled
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn set_remote_control(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    sharer: ParticipantId,
+    controller: ParticipantId,
+) -> Result<bool> {
+    redis_conn
+        .hset_nx(RemoteControls { room }, sharer, controller)
+        .await
+        .context("Failed to set remote control")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_remote_control(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    sharer: ParticipantId,
+) -> Result<Option<ParticipantId>> {
+    redis_conn
+        .hget(RemoteControls { room }, sharer)
+        .await
+        .context("Failed to get remote control")
+}
+
+/// Remove the remote control of the screen shared by `sharer`, returns the participant which controlled it
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn take_remote_control(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    sharer: ParticipantId,
+) -> Result<Option<ParticipantId>> {
+    let (controller, _): (Option<ParticipantId>, i64) = redis::pipe()
+        .atomic()
+        .hget(RemoteControls { room }, sharer)
+        .hdel(RemoteControls { room }, sharer)
+        .query_async(redis_conn)
+        .await
+        .context("Failed to take remote control")?;
+
+    Ok(controller)
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_remote_controls_key(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RemoteControls { room })
+        .await
+        .context("Failed to delete remote controls")
+}
+
+/// Log of the granted, denied and revoked remote controls of the room, oldest first
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:namespace=media:remote_control_log")]
+struct RemoteControlLog {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn add_remote_control_event(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    event: &RemoteControlEvent,
+) -> Result<()> {
+    redis_conn
+        .rpush(RemoteControlLog { room }, event)
+        .await
+        .context("Failed to add remote control event")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_remote_control_events(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<Vec<RemoteControlEvent>> {
+    redis_conn
+        .lrange(RemoteControlLog { room }, 0, -1)
+        .await
+        .context("Failed to get remote control events")
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_remote_control_log_key(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<()> {
+    redis_conn
+        .del(RemoteControlLog { room })
+        .await
+        .context("Failed to delete remote control log")
+}
+
+/// State of the janus handles of a participant, as reported by the events of janus
+///
+/// Maps the media session keys of the publishers and subscriptions of the participant onto a json [`HandleStats`].
+#[derive(ToRedisArgs)]
+#[to_redis_args(
+    fmt = "k3k-signaling:room={room}:participant={participant}:namespace=media:handle_stats"
+)]
+struct HandleStatsKey {
+    room: SignalingRoomId,
+    participant: ParticipantId,
+}
+
+/// Time in seconds after which the handle stats of a participant expire, in case they are not removed when the
+/// participant leaves, e.g. because the controller crashed
+const HANDLE_STATS_EXPIRY: usize = 24 * 60 * 60;
+
+/// State of a janus handle
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandleStats {
+    /// The webrtc connection of the handle is up
+    pub webrtc_up: bool,
+    /// Janus receives audio on the handle, missing until janus reports it
+    pub receiving_audio: Option<bool>,
+    /// Janus receives video on the handle, missing until janus reports it
+    pub receiving_video: Option<bool>,
+    /// Number of slow link events of the handle
+    pub slow_links: u64,
+    /// Packets lost on the handle according to the slow link events
+    pub lost_packets: u64,
+}
+
+/// Update the stats of the handle of the media session, only called by the runner of the participant
+#[tracing::instrument(level = "debug", skip(redis_conn, update))]
+pub async fn update_handle_stats(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+    media_session_key: MediaSessionKey,
+    update: impl FnOnce(&mut HandleStats),
+) -> Result<()> {
+    let key = HandleStatsKey { room, participant };
+    let field = media_session_key.to_string();
+
+    let json: Option<String> = redis_conn
+        .hget(&key, &field)
+        .await
+        .context("Failed to get handle stats")?;
+
+    let mut stats: HandleStats = json
+        .map(|json| serde_json::from_str(&json))
+        .transpose()
+        .context("Failed to deserialize handle stats")?
+        .unwrap_or_default();
+
+    update(&mut stats);
+
+    let json = serde_json::to_string(&stats).context("Failed to serialize handle stats")?;
+
+    redis::pipe()
+        .atomic()
+        .hset(&key, field, json)
+        .ignore()
+        .expire(&key, HANDLE_STATS_EXPIRY)
+        .ignore()
+        .query_async(redis_conn)
+        .await
+        .context("Failed to set handle stats")
+}
+
+/// Returns the stats of the handles of the participant, keyed by the media session key
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn get_handle_stats(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<HashMap<String, HandleStats>> {
+    let stats: HashMap<String, String> = redis_conn
+        .hgetall(HandleStatsKey { room, participant })
+        .await
+        .context("Failed to get handle stats")?;
+
+    stats
+        .into_iter()
+        .map(|(key, json)| {
+            let stats =
+                serde_json::from_str(&json).context("Failed to deserialize handle stats")?;
+
+            Ok((key, stats))
+        })
+        .collect()
+}
+
+/// Delete the stats of all handles of the participant
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub async fn delete_handle_stats(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    participant: ParticipantId,
+) -> Result<()> {
+    redis_conn
+        .del(HandleStatsKey { room, participant })
+        .await
+        .context("Failed to delete handle stats")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mcu::MediaSessionType;
+    use pretty_assertions::assert_eq;
+    use serial_test::serial;
+    use test_util::redis::setup;
+    use types::core::RoomId;
+
+    const ROOM: SignalingRoomId = SignalingRoomId::new_test(RoomId::from(uuid::Uuid::nil()));
+    const ALICE: ParticipantId = ParticipantId::from_u128(0xbadcafe);
+    const BOB: ParticipantId = ParticipantId::from_u128(0xdeadbeef);
+
+    #[tokio::test]
+    #[serial]
+    async fn handle_stats_accumulate() {
+        let mut redis_conn = setup().await;
+
+        let publisher = MediaSessionKey(ALICE, MediaSessionType::Video);
+        let subscription = MediaSessionKey(BOB, MediaSessionType::Video);
+
+        update_handle_stats(&mut redis_conn, ROOM, ALICE, publisher, |stats| {
+            stats.webrtc_up = true
+        })
+        .await
+        .unwrap();
+
+        for lost in [3, 4] {
+            update_handle_stats(&mut redis_conn, ROOM, ALICE, subscription, |stats| {
+                stats.slow_links += 1;
+                stats.lost_packets += lost;
+            })
+            .await
+            .unwrap();
+        }
+
+        let stats = get_handle_stats(&mut redis_conn, ROOM, ALICE)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stats,
+            HashMap::from([
+                (
+                    publisher.to_string(),
+                    HandleStats {
+                        webrtc_up: true,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    subscription.to_string(),
+                    HandleStats {
+                        slow_links: 2,
+                        lost_packets: 7,
+                        ..Default::default()
+                    }
+                ),
+            ])
+        );
+
+        // The handle stats belong to the room and expire
+        let key = "k3k-signaling:room=00000000-0000-0000-0000-000000000000:participant=00000000-0000-0000-0000-00000badcafe:namespace=media:handle_stats";
+        let ttl: i64 = redis_conn.ttl(key).await.unwrap();
+        assert!(ttl > 0 && ttl <= HANDLE_STATS_EXPIRY as i64);
+
+        delete_handle_stats(&mut redis_conn, ROOM, ALICE)
+            .await
+            .unwrap();
+
+        assert!(get_handle_stats(&mut redis_conn, ROOM, ALICE)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
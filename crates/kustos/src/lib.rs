@@ -213,6 +213,24 @@ impl Authz {
         Ok(())
     }
 
+    /// Removes the policies which exist and skips the missing ones, returns the number of removed policies
+    ///
+    /// Unlike [`Authz::remove_policies`] this removes the existing policies when some of the policies are missing.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn remove_existing_policies(&self, policies: impl ToCasbinMultiple) -> Result<usize> {
+        let mut inner = self.inner.write().await;
+
+        let mut amount = 0;
+
+        for policy in policies.to_casbin_policies() {
+            if inner.remove_policy(policy).await? {
+                amount += 1;
+            }
+        }
+
+        Ok(amount)
+    }
+
     /// Grants the group access to the resources with access
     ///
     /// This takes multiple resources with access at once.
@@ -554,6 +572,22 @@ impl Authz {
         Ok(amount)
     }
 
+    /// Removes all permissions of the user and removes the user from all groups and roles
+    #[tracing::instrument(level = "debug", skip(self, user))]
+    pub async fn remove_user<S>(&self, user: S) -> Result<()>
+    where
+        S: Into<PolicyUser>,
+    {
+        let user = user.into().to_casbin_string();
+
+        let mut inner = self.inner.write().await;
+
+        inner.remove_filtered_policy(0, vec![user.clone()]).await?;
+        inner.remove_filtered_grouping_policy(0, vec![user]).await?;
+
+        Ok(())
+    }
+
     /// Removes access for user
     #[tracing::instrument(level = "debug", skip(self, user, resource, access))]
     pub async fn remove_user_permission<S, R, A>(
@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::Stage;
+use controller::prelude::Role;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Incoming websocket messages
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum Message {
+    /// Replace the stage layout of the room
+    SetLayout(Stage),
+    /// Set the lowest role which is allowed to change the layout, moderators only
+    SetEditRole(SetEditRole),
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetEditRole {
+    pub edit_role: Role,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::View;
+    use controller::prelude::*;
+    use pretty_assertions::assert_eq;
+    use types::core::ParticipantId;
+
+    #[test]
+    fn set_layout() {
+        let json = r#"
+        {
+            "action": "set_layout",
+            "view": "speaker",
+            "pinned_screen_share": "00000000-0000-0000-0000-000000000001"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SetLayout(Stage {
+            view,
+            pinned_screen_share,
+        }) = msg
+        {
+            assert_eq!(view, View::Speaker);
+            assert_eq!(pinned_screen_share, Some(ParticipantId::from_u128(1)));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn set_edit_role() {
+        let json = r#"
+        {
+            "action": "set_edit_role",
+            "edit_role": "user"
+        }
+        "#;
+
+        let msg: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::SetEditRole(SetEditRole { edit_role }) = msg {
+            assert_eq!(edit_role, Role::User);
+        } else {
+            panic!()
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! # Layout Module
+//!
+//! ## Functionality
+//!
+//! Synchronizes the stage layout of the room (the view and the pinned screen share) to all participants. By default
+//! only moderators can change the layout, they can allow users or everyone to change it as well.
+//!
+//! The recorder participant publishes the layout as overlay, so the recording shows the same stage as the clients.
+//! The overlay contains the participants spotlighted with the moderation module.
+use anyhow::Result;
+use controller::prelude::*;
+use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use types::core::ParticipantId;
+
+pub mod incoming;
+pub mod outgoing;
+pub mod rabbitmq;
+mod storage;
+
+/// Arrangement of the participants on the stage
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum View {
+    #[default]
+    Grid,
+    Speaker,
+    /// The screen share fills the stage, the speaker is shown on top of it
+    PictureInPicture,
+}
+
+impl View {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Grid => "grid",
+            Self::Speaker => "speaker",
+            Self::PictureInPicture => "picture_in_picture",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct Stage {
+    pub view: View,
+    /// The participant whose screen share is pinned to the stage
+    #[serde(default)]
+    pub pinned_screen_share: Option<ParticipantId>,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToRedisArgs, FromRedisValue, JsonSchema,
+)]
+#[to_redis_args(serde)]
+#[from_redis_value(serde)]
+pub struct LayoutState {
+    #[serde(flatten)]
+    pub stage: Stage,
+    /// The lowest role which is allowed to change the layout
+    pub edit_role: Role,
+}
+
+impl Default for LayoutState {
+    fn default() -> Self {
+        Self {
+            stage: Stage::default(),
+            edit_role: Role::Moderator,
+        }
+    }
+}
+
+impl LayoutState {
+    fn may_edit(&self, role: Role) -> bool {
+        match self.edit_role {
+            Role::Guest => true,
+            Role::User => role != Role::Guest,
+            Role::Moderator => role == Role::Moderator,
+        }
+    }
+}
+
+pub struct Layout {
+    room: SignalingRoomId,
+    i_am_the_recorder: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl SignalingModule for Layout {
+    const NAMESPACE: &'static str = "layout";
+
+    type Params = ();
+
+    type Incoming = incoming::Message;
+    type Outgoing = outgoing::Message;
+    type RabbitMqMessage = rabbitmq::Message;
+
+    type ExtEvent = moderation::SpotlightsChanged;
+
+    type FrontendData = LayoutState;
+    type PeerFrontendData = ();
+
+    async fn init(
+        mut ctx: InitContext<'_, Self>,
+        _params: &Self::Params,
+        _protocol: &'static str,
+    ) -> Result<Option<Self>> {
+        ctx.subscribe(|changed: moderation::SpotlightsChanged| changed);
+
+        Ok(Some(Self {
+            room: ctx.room_id(),
+            i_am_the_recorder: matches!(ctx.participant(), Participant::Recorder),
+        }))
+    }
+
+    async fn on_event(
+        &mut self,
+        mut ctx: ModuleContext<'_, Self>,
+        event: Event<'_, Self>,
+    ) -> Result<()> {
+        match event {
+            Event::Joined {
+                control_data: _,
+                frontend_data,
+                participants: _,
+            } => {
+                let state = storage::get(ctx.redis_conn(), self.room).await?;
+
+                if self.i_am_the_recorder {
+                    let spotlights =
+                        moderation::storage::get_spotlights(ctx.redis_conn(), self.room).await?;
+
+                    self.publish_overlay(&mut ctx, &state.stage, spotlights)
+                        .await?;
+                }
+
+                *frontend_data = Some(state);
+            }
+            Event::WsMessage(msg) => self.on_ws_message(&mut ctx, msg).await?,
+            Event::RabbitMq(rabbitmq::Message::Updated(state)) => {
+                if self.i_am_the_recorder {
+                    let spotlights =
+                        moderation::storage::get_spotlights(ctx.redis_conn(), self.room).await?;
+
+                    self.publish_overlay(&mut ctx, &state.stage, spotlights)
+                        .await?;
+                }
+
+                ctx.ws_send(outgoing::Message::Updated(state));
+            }
+            Event::Ext(moderation::SpotlightsChanged { spotlights }) => {
+                if self.i_am_the_recorder {
+                    let state = storage::get(ctx.redis_conn(), self.room).await?;
+
+                    self.publish_overlay(&mut ctx, &state.stage, spotlights)
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn on_destroy(self, mut ctx: DestroyContext<'_>) {
+        if ctx.destroy_room() {
+            if let Err(e) = storage::del(ctx.redis_conn(), self.room).await {
+                log::error!(
+                    "Failed to remove layout of room {} from redis: {:?}",
+                    self.room,
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl Layout {
+    async fn on_ws_message(
+        &mut self,
+        ctx: &mut ModuleContext<'_, Self>,
+        msg: incoming::Message,
+    ) -> Result<()> {
+        let mut state = storage::get(ctx.redis_conn(), self.room).await?;
+
+        match msg {
+            incoming::Message::SetLayout(stage) => {
+                if !state.may_edit(ctx.role()) {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                let unknown_participant = match stage.pinned_screen_share {
+                    Some(participant) => {
+                        !control::storage::participants_contains(
+                            ctx.redis_conn(),
+                            self.room,
+                            participant,
+                        )
+                        .await?
+                    }
+                    None => false,
+                };
+
+                if unknown_participant {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::UnknownParticipant.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                state.stage = stage;
+            }
+            incoming::Message::SetEditRole(incoming::SetEditRole { edit_role }) => {
+                if ctx.role() != Role::Moderator {
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InsufficientPermissions.into(),
+                    ));
+
+                    return Ok(());
+                }
+
+                state.edit_role = edit_role;
+            }
+        }
+
+        storage::set(ctx.redis_conn(), self.room, &state).await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::Updated(state),
+        );
+
+        Ok(())
+    }
+
+    /// Publish the stage and the spotlighted participants to the recording
+    async fn publish_overlay(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        stage: &Stage,
+        spotlights: Vec<ParticipantId>,
+    ) -> Result<()> {
+        recording::publish_overlay(
+            ctx,
+            self.room,
+            recording::Overlay::Layout(recording::overlay::Layout {
+                view: stage.view.as_str().into(),
+                spotlights,
+                pinned_screen_share: stage.pinned_screen_share,
+            }),
+        )
+        .await
+    }
+}
+
+pub fn register(controller: &mut controller::Controller) {
+    controller.signaling.add_module::<Layout>(());
+}
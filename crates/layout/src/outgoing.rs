@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::LayoutState;
+use schemars::JsonSchema;
+use serde::Serialize;
+use types::signaling::{ErrorEnvelope, ModuleError};
+
+/// Outgoing websocket messages
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "message")]
+pub enum Message {
+    /// The layout or the role required to change it has been updated
+    Updated(LayoutState),
+    /// An error occurred
+    Error(ErrorEnvelope<Error>),
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "error")]
+pub enum Error {
+    InsufficientPermissions,
+    UnknownParticipant,
+}
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "layout";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::UnknownParticipant => "The participant is not inside the room",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Stage, View};
+    use controller::prelude::*;
+    use test_util::assert_eq_json;
+    use types::core::ParticipantId;
+
+    #[test]
+    fn updated() {
+        let updated = Message::Updated(LayoutState {
+            stage: Stage {
+                view: View::PictureInPicture,
+                pinned_screen_share: Some(ParticipantId::nil()),
+            },
+            edit_role: Role::Moderator,
+        });
+
+        assert_eq_json!(
+            updated,
+            {
+                "message": "updated",
+                "view": "picture_in_picture",
+                "pinned_screen_share": "00000000-0000-0000-0000-000000000000",
+                "edit_role": "moderator"
+            }
+        );
+    }
+}
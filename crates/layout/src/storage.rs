@@ -0,0 +1,49 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::LayoutState;
+use anyhow::{Context, Result};
+use controller::prelude::*;
+use redis::AsyncCommands;
+use redis_args::ToRedisArgs;
+
+/// The stage layout of the room and the role required to change it
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:layout")]
+struct LayoutKey {
+    room: SignalingRoomId,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn get(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<LayoutState> {
+    let state: Option<LayoutState> = redis_conn
+        .get(LayoutKey { room })
+        .await
+        .with_context(|| format!("Failed to get layout, room={room}"))?;
+
+    Ok(state.unwrap_or_default())
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn, state))]
+pub(super) async fn set(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    state: &LayoutState,
+) -> Result<()> {
+    redis_conn
+        .set(LayoutKey { room }, state)
+        .await
+        .with_context(|| format!("Failed to set layout, room={room}"))
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn del(redis_conn: &mut RedisConnection, room: SignalingRoomId) -> Result<()> {
+    redis_conn
+        .del(LayoutKey { room })
+        .await
+        .with_context(|| format!("Failed to delete layout, room={room}"))
+}
@@ -30,6 +30,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -46,6 +47,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -66,6 +68,7 @@ impl MailTask {
             invitee: invitee.into(),
             event: event.into(),
             inviter: inviter.into(),
+            custom_mail: None,
             invite_code,
         }))
     }
@@ -82,6 +85,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -98,6 +102,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -118,6 +123,7 @@ impl MailTask {
             invitee: invitee.into(),
             event: event.into(),
             inviter: inviter.into(),
+            custom_mail: None,
             invite_code,
         }))
     }
@@ -134,6 +140,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -150,6 +157,7 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
@@ -166,10 +174,73 @@ impl MailTask {
                 invitee: invitee.into(),
                 event: event.into(),
                 inviter: inviter.into(),
+                custom_mail: None,
             },
         ))
     }
 
+    /// Replace the built-in template of the mail worker with the mail rendered from the template of the tenant
+    pub fn set_custom_mail(&mut self, custom: v1::CustomMail) {
+        let MailTask::V1(message) = self;
+
+        let custom_mail = match message {
+            // Invites
+            v1::Message::RegisteredEventInvite(message) => &mut message.custom_mail,
+            v1::Message::UnregisteredEventInvite(message) => &mut message.custom_mail,
+            v1::Message::ExternalEventInvite(message) => &mut message.custom_mail,
+            // Updates
+            v1::Message::RegisteredEventUpdate(message) => &mut message.custom_mail,
+            v1::Message::UnregisteredEventUpdate(message) => &mut message.custom_mail,
+            v1::Message::ExternalEventUpdate(message) => &mut message.custom_mail,
+            // Cancellations
+            v1::Message::RegisteredEventCancellation(message) => &mut message.custom_mail,
+            v1::Message::UnregisteredEventCancellation(message) => &mut message.custom_mail,
+            v1::Message::ExternalEventCancellation(message) => &mut message.custom_mail,
+        };
+
+        *custom_mail = Some(custom);
+    }
+
+    /// The mail rendered from the template of the tenant, if set
+    pub fn custom_mail(&self) -> Option<&v1::CustomMail> {
+        let MailTask::V1(message) = self;
+
+        match message {
+            // Invites
+            v1::Message::RegisteredEventInvite(message) => message.custom_mail.as_ref(),
+            v1::Message::UnregisteredEventInvite(message) => message.custom_mail.as_ref(),
+            v1::Message::ExternalEventInvite(message) => message.custom_mail.as_ref(),
+            // Updates
+            v1::Message::RegisteredEventUpdate(message) => message.custom_mail.as_ref(),
+            v1::Message::UnregisteredEventUpdate(message) => message.custom_mail.as_ref(),
+            v1::Message::ExternalEventUpdate(message) => message.custom_mail.as_ref(),
+            // Cancellations
+            v1::Message::RegisteredEventCancellation(message) => message.custom_mail.as_ref(),
+            v1::Message::UnregisteredEventCancellation(message) => message.custom_mail.as_ref(),
+            v1::Message::ExternalEventCancellation(message) => message.custom_mail.as_ref(),
+        }
+    }
+
+    /// The email address the mail is sent to
+    pub fn invitee_email(&self) -> &str {
+        let MailTask::V1(message) = self;
+
+        match message {
+            // Invites
+            v1::Message::RegisteredEventInvite(message) => message.invitee.email.as_ref(),
+            v1::Message::UnregisteredEventInvite(message) => message.invitee.email.as_ref(),
+            v1::Message::ExternalEventInvite(message) => message.invitee.email.as_ref(),
+            // Updates
+            v1::Message::RegisteredEventUpdate(message) => message.invitee.email.as_ref(),
+            v1::Message::UnregisteredEventUpdate(message) => message.invitee.email.as_ref(),
+            v1::Message::ExternalEventUpdate(message) => message.invitee.email.as_ref(),
+            // Cancellations
+            v1::Message::RegisteredEventCancellation(message) => message.invitee.email.as_ref(),
+            v1::Message::UnregisteredEventCancellation(message) => message.invitee.email.as_ref(),
+            v1::Message::ExternalEventCancellation(message) => message.invitee.email.as_ref(),
+        }
+    }
+
     pub fn as_kind_str(&self) -> &'static str {
         match self {
             MailTask::V1(message) => match message {
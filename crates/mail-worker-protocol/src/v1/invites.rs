@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use super::{Event, ExternalUser, RegisteredUser, UnregisteredUser};
+use super::{CustomMail, Event, ExternalUser, RegisteredUser, UnregisteredUser};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -10,6 +10,8 @@ pub struct RegisteredEventInvite {
     pub invitee: RegisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -17,6 +19,8 @@ pub struct UnregisteredEventInvite {
     pub invitee: UnregisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -25,6 +29,8 @@ pub struct ExternalEventInvite {
     pub event: Event,
     pub inviter: RegisteredUser,
     pub invite_code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -32,6 +38,8 @@ pub struct RegisteredEventUpdate {
     pub invitee: RegisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -39,6 +47,8 @@ pub struct UnregisteredEventUpdate {
     pub invitee: UnregisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -47,6 +57,8 @@ pub struct ExternalEventUpdate {
     pub event: Event,
     pub inviter: RegisteredUser,
     pub invite_code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -54,6 +66,8 @@ pub struct RegisteredEventCancellation {
     pub invitee: RegisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -61,6 +75,8 @@ pub struct UnregisteredEventCancellation {
     pub invitee: UnregisteredUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -68,4 +84,6 @@ pub struct ExternalEventCancellation {
     pub invitee: ExternalUser,
     pub event: Event,
     pub inviter: RegisteredUser,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_mail: Option<CustomMail>,
 }
@@ -86,12 +86,22 @@ pub struct Event {
     pub description: String,
     pub room: Room,
     pub call_in: Option<CallIn>,
+    /// URL of a meeting on an external conferencing service, invitees join it instead of the room
+    ///
+    /// The ICS file of the event must use this URL as location instead of the link to the room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_meeting_url: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
 pub struct Room {
     pub id: Uuid,
     pub password: Option<String>,
+    /// Language tag of the documents and messages generated for the room
+    ///
+    /// Used for the mails of invitees without a language of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -101,6 +111,14 @@ pub struct CallIn {
     pub sip_password: String,
 }
 
+/// Mail rendered from the template of the tenant, replaces the built-in template of the mail worker
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub struct CustomMail {
+    pub subject: String,
+    /// HTML body of the mail
+    pub body: String,
+}
+
 /// The different kinds of MailTasks that are currently supported
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 #[cfg_attr(any(test, feature = "client"), derive(Serialize))]
@@ -161,12 +179,14 @@ mod test {
                 room: Room {
                     id: Uuid::from_u128(0),
                     password: Some("password123".into()),
+                    locale: None,
                 },
                 call_in: Some(CallIn {
                     sip_tel: "+497652917".into(),
                     sip_id: "2".into(),
                     sip_password: "987".into(),
                 }),
+                external_meeting_url: None,
             },
             invitee: RegisteredUser {
                 email: "lastname@example.org".into(),
@@ -175,6 +195,7 @@ mod test {
                 last_name: "LastName".into(),
                 language: "de".into(),
             },
+            custom_mail: None,
         }));
 
         assert_eq!(
@@ -237,12 +258,14 @@ mod test {
                 room: Room {
                     id: Uuid::from_u128(0),
                     password: None,
+                    locale: None,
                 },
                 call_in: Some(CallIn {
                     sip_tel: "+497652917".into(),
                     sip_id: "2".into(),
                     sip_password: "987".into(),
                 }),
+                external_meeting_url: None,
             },
             invitee: RegisteredUser {
                 email: "lastname@example.org".into(),
@@ -251,6 +274,7 @@ mod test {
                 last_name: "LastName".into(),
                 language: "de".into(),
             },
+            custom_mail: None,
         }));
 
         assert_eq!(
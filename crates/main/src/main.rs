@@ -11,7 +11,12 @@ async fn main() {
 }
 
 async fn run() -> Result<()> {
-    if let Some(mut controller) = Controller::create("K3K Controller Community Edition").await? {
+    if let Some(mut controller) = Controller::create(
+        "K3K Controller Community Edition",
+        community_modules::register_schemas,
+    )
+    .await?
+    {
         community_modules::register(&mut controller).await?;
         controller.run().await?;
     }
@@ -0,0 +1,380 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Bridge of a single OpenTalk room
+//!
+//! The bridge joins the room as bot participant. Messages sent to the global chat are forwarded to the Matrix room
+//! by the ghost of the sending participant, Matrix messages are sent to the global chat by the bridge itself,
+//! prefixed with the display name of the Matrix user.
+//!
+//! The bridge leaves the room when it gets closed or only bots are left inside, so it never keeps a room alive. It
+//! doesn't reconnect on its own, the bridges are started again by the bridge manager once participants are inside
+//! the room.
+use crate::controller::{ControllerClient, MatrixBridge};
+use crate::matrix::{MatrixClient, MatrixMessage, Registration};
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+/// Websocket subprotocol spoken by the bridge
+const SIGNALING_PROTOCOL: &str = "k3k-signaling-json-v1.0";
+
+/// Participant of the room as seen by the bridge
+#[derive(Debug, PartialEq, Eq)]
+struct Participant {
+    id: Uuid,
+    display_name: String,
+    /// Services like bots and the recorder
+    is_service: bool,
+}
+
+/// Event of the signaling relevant to the bridge
+#[derive(Debug, PartialEq, Eq)]
+enum SignalingEvent {
+    JoinSuccess {
+        id: Uuid,
+        participants: Vec<Participant>,
+    },
+    Joined(Participant),
+    Left {
+        id: Uuid,
+    },
+    ChatMessage {
+        source: Uuid,
+        content: String,
+    },
+    RoomClosed,
+}
+
+fn participant(participant: &Value) -> Option<Participant> {
+    let control = &participant["control"];
+
+    Some(Participant {
+        id: participant["id"].as_str()?.parse().ok()?,
+        display_name: control["display_name"].as_str()?.to_owned(),
+        is_service: matches!(
+            control["participation_kind"].as_str(),
+            Some("bot" | "recorder")
+        ),
+    })
+}
+
+fn parse_event(text: &str) -> Option<SignalingEvent> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    let payload = &message["payload"];
+
+    match (message["namespace"].as_str()?, payload["message"].as_str()?) {
+        ("control", "join_success") => Some(SignalingEvent::JoinSuccess {
+            id: payload["id"].as_str()?.parse().ok()?,
+            participants: payload["participants"]
+                .as_array()?
+                .iter()
+                .filter_map(participant)
+                .collect(),
+        }),
+        ("control", "joined") => Some(SignalingEvent::Joined(participant(payload)?)),
+        ("control", "left") => Some(SignalingEvent::Left {
+            id: payload["id"].as_str()?.parse().ok()?,
+        }),
+        ("control", "room_closed") => Some(SignalingEvent::RoomClosed),
+        // Private and group messages are not bridged
+        ("chat", "message_sent") if payload["scope"] == "global" => {
+            Some(SignalingEvent::ChatMessage {
+                source: payload["source"].as_str()?.parse().ok()?,
+                content: payload["content"].as_str()?.to_owned(),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn namespaced(namespace: &str, payload: Value) -> Message {
+    Message::Text(json!({ "namespace": namespace, "payload": payload }).to_string())
+}
+
+pub struct Bridge {
+    pub controller: Arc<ControllerClient>,
+    pub matrix: Arc<MatrixClient>,
+    pub config: MatrixBridge,
+}
+
+impl Bridge {
+    /// Bridge the messages of the registered Matrix room until the bridge leaves the room
+    pub async fn run(self, mut registration: Registration) {
+        match self.connect(&mut registration.receiver).await {
+            Ok(()) => log::info!("Bridge of event {} left the room", self.config.event_id),
+            Err(e) => log::warn!("Bridge of event {} failed, {:?}", self.config.event_id, e),
+        }
+    }
+
+    /// Join the room and bridge messages until the connection gets closed, the room gets closed or only services are
+    /// left inside the room
+    async fn connect(
+        &self,
+        from_matrix: &mut mpsc::UnboundedReceiver<MatrixMessage>,
+    ) -> Result<()> {
+        let start = self.controller.start(self.config.room_id).await?;
+
+        let mut request = self
+            .controller
+            .settings()
+            .signaling_url
+            .as_str()
+            .into_client_request()?;
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_str(&format!("{SIGNALING_PROTOCOL}, ticket#{}", start.ticket))?,
+        );
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("Failed to connect to the signaling endpoint")?;
+
+        socket
+            .send(namespaced(
+                "control",
+                json!({
+                    "action": "join",
+                    "display_name": self.controller.settings().display_name,
+                }),
+            ))
+            .await?;
+
+        let mut own_id = None;
+        let mut participants = HashMap::new();
+        let mut present = HashSet::new();
+        let mut ghosts = HashSet::new();
+
+        loop {
+            tokio::select! {
+                message = socket.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(e.into()),
+                    };
+
+                    match parse_event(&text) {
+                        Some(SignalingEvent::JoinSuccess { id, participants: joined }) => {
+                            own_id = Some(id);
+
+                            for participant in joined {
+                                track(&mut participants, &mut present, participant);
+                            }
+
+                            if present.is_empty() {
+                                break;
+                            }
+                        }
+                        Some(SignalingEvent::Joined(participant)) => {
+                            track(&mut participants, &mut present, participant);
+                        }
+                        Some(SignalingEvent::Left { id }) => {
+                            participants.remove(&id);
+                            present.remove(&id);
+
+                            if present.is_empty() {
+                                break;
+                            }
+
+                            if ghosts.remove(&id) {
+                                if let Err(e) = self.matrix.leave_ghost(&self.config.matrix_room_id, id).await {
+                                    log::warn!("Failed to remove ghost of {} from matrix room, {:?}", id, e);
+                                }
+                            }
+                        }
+                        Some(SignalingEvent::ChatMessage { source, content }) => {
+                            if Some(source) == own_id {
+                                continue;
+                            }
+
+                            self.forward_to_matrix(&mut ghosts, &participants, source, &content).await;
+                        }
+                        Some(SignalingEvent::RoomClosed) => break,
+                        None => {}
+                    }
+                }
+                Some(message) = from_matrix.recv() => {
+                    socket
+                        .send(namespaced(
+                            "chat",
+                            json!({
+                                "action": "send_message",
+                                "scope": "global",
+                                "content": format!("{}: {}", message.sender, message.body),
+                            }),
+                        ))
+                        .await?;
+                }
+            }
+        }
+
+        // Leave the room without waiting for the bridge's runner to time out
+        socket.close(None).await?;
+
+        for id in ghosts {
+            if let Err(e) = self
+                .matrix
+                .leave_ghost(&self.config.matrix_room_id, id)
+                .await
+            {
+                log::warn!("Failed to remove ghost of {} from matrix room, {:?}", id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn forward_to_matrix(
+        &self,
+        ghosts: &mut HashSet<Uuid>,
+        participants: &HashMap<Uuid, String>,
+        source: Uuid,
+        content: &str,
+    ) {
+        let room_id = &self.config.matrix_room_id;
+
+        if !ghosts.contains(&source) {
+            let display_name = participants
+                .get(&source)
+                .map(String::as_str)
+                .unwrap_or("OpenTalk");
+
+            if let Err(e) = self.matrix.setup_ghost(room_id, source, display_name).await {
+                log::warn!("Failed to set up ghost of {}, {:?}", source, e);
+                return;
+            }
+
+            ghosts.insert(source);
+        }
+
+        if let Err(e) = self.matrix.send_message(room_id, source, content).await {
+            log::warn!("Failed to send message to matrix room {}, {:?}", room_id, e);
+        }
+    }
+}
+
+/// Remember the participant, services don't keep the bridge inside the room
+fn track(
+    participants: &mut HashMap<Uuid, String>,
+    present: &mut HashSet<Uuid>,
+    participant: Participant,
+) {
+    if !participant.is_service {
+        present.insert(participant.id);
+    }
+
+    participants.insert(participant.id, participant.display_name);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const ALICE: &str = "00000000-0000-0000-0000-000000000001";
+    const BOT: &str = "00000000-0000-0000-0000-000000000002";
+
+    #[test]
+    fn join_success() {
+        let event = parse_event(
+            &json!({
+                "namespace": "control",
+                "timestamp": "2023-01-01T00:00:00Z",
+                "payload": {
+                    "message": "join_success",
+                    "id": "00000000-0000-0000-0000-000000000000",
+                    "display_name": "Matrix",
+                    "role": "guest",
+                    "participants": [
+                        {
+                            "id": ALICE,
+                            "control": { "display_name": "Alice", "participation_kind": "user" }
+                        },
+                        {
+                            "id": BOT,
+                            "control": { "display_name": "Recorder", "participation_kind": "recorder" }
+                        }
+                    ]
+                }
+            })
+            .to_string(),
+        );
+
+        assert_eq!(
+            event,
+            Some(SignalingEvent::JoinSuccess {
+                id: Uuid::nil(),
+                participants: vec![
+                    Participant {
+                        id: ALICE.parse().unwrap(),
+                        display_name: "Alice".into(),
+                        is_service: false,
+                    },
+                    Participant {
+                        id: BOT.parse().unwrap(),
+                        display_name: "Recorder".into(),
+                        is_service: true,
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn global_chat_message() {
+        let event = parse_event(
+            &json!({
+                "namespace": "chat",
+                "timestamp": "2023-01-01T00:00:00Z",
+                "payload": {
+                    "message": "message_sent",
+                    "id": "00000000-0000-0000-0000-000000000000",
+                    "source": ALICE,
+                    "content": "Hello",
+                    "scope": "global"
+                }
+            })
+            .to_string(),
+        );
+
+        assert_eq!(
+            event,
+            Some(SignalingEvent::ChatMessage {
+                source: ALICE.parse().unwrap(),
+                content: "Hello".into()
+            })
+        );
+    }
+
+    #[test]
+    fn private_chat_message() {
+        let event = parse_event(
+            &json!({
+                "namespace": "chat",
+                "timestamp": "2023-01-01T00:00:00Z",
+                "payload": {
+                    "message": "message_sent",
+                    "id": "00000000-0000-0000-0000-000000000000",
+                    "source": ALICE,
+                    "content": "Hello",
+                    "scope": "private",
+                    "target": "00000000-0000-0000-0000-000000000000"
+                }
+            })
+            .to_string(),
+        );
+
+        assert_eq!(event, None);
+    }
+}
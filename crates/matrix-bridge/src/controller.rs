@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Client of the controller's REST API, authenticated as service account
+use crate::settings::{Controller, Oidc};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Tokens are refreshed this long before they expire
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Bridge of an event's room
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MatrixBridge {
+    pub event_id: Uuid,
+    pub room_id: Uuid,
+    pub matrix_room_id: String,
+}
+
+/// Bridge as returned by `GET /services/bot/matrix_bridges`
+#[derive(Debug, Deserialize)]
+pub struct MatrixBridgeState {
+    #[serde(flatten)]
+    pub bridge: MatrixBridge,
+    /// Participants other than bots are inside the room
+    pub active: bool,
+}
+
+#[derive(Serialize)]
+struct StartBody {
+    room_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct StartResponse {
+    pub ticket: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+pub struct ControllerClient {
+    http: reqwest::Client,
+    controller: Controller,
+    oidc: Oidc,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl ControllerClient {
+    pub fn new(http: reqwest::Client, controller: Controller, oidc: Oidc) -> Self {
+        Self {
+            http,
+            controller,
+            oidc,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn settings(&self) -> &Controller {
+        &self.controller
+    }
+
+    /// Returns the access token of the service account, requesting a new one if the current expires soon
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+
+        if let Some((access_token, expires_at)) = &*token {
+            if Instant::now() + TOKEN_EXPIRY_MARGIN < *expires_at {
+                return Ok(access_token.clone());
+            }
+        }
+
+        let response: TokenResponse = self
+            .http
+            .post(self.oidc.token_url.clone())
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", &self.oidc.client_id),
+                ("client_secret", &self.oidc.client_secret),
+            ])
+            .send()
+            .await
+            .context("Failed to request access token")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid token response")?;
+
+        let expires_at = Instant::now() + Duration::from_secs(response.expires_in);
+        *token = Some((response.access_token.clone(), expires_at));
+
+        Ok(response.access_token)
+    }
+
+    /// Get all configured bridges
+    pub async fn matrix_bridges(&self) -> Result<Vec<MatrixBridgeState>> {
+        let url = self
+            .controller
+            .api_url
+            .join("services/bot/matrix_bridges")?;
+
+        let bridges = self
+            .http
+            .get(url)
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid matrix bridges response")?;
+
+        Ok(bridges)
+    }
+
+    /// Request a ticket to join the room as bot
+    pub async fn start(&self, room_id: Uuid) -> Result<StartResponse> {
+        let url = self.controller.api_url.join("services/bot/start")?;
+
+        let response = self
+            .http
+            .post(url)
+            .bearer_auth(self.access_token().await?)
+            .json(&StartBody { room_id })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid start response")?;
+
+        Ok(response)
+    }
+}
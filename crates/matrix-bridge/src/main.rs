@@ -0,0 +1,144 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Bridge between the global chat of OpenTalk rooms and Matrix rooms
+//!
+//! Bridges are configured per event with the `/events/{event_id}/matrix_bridge` endpoint of the controller. The
+//! bridge periodically fetches all configured bridges using a service account with the `opentalk-bot` realm role,
+//! joins the rooms of the events as bot participant while participants are inside and acts as Matrix application
+//! service.
+//!
+//! Usage: `k3k-matrix-bridge [config.toml]`
+use anyhow::{Context, Result};
+use bridge::Bridge;
+use controller::{ControllerClient, MatrixBridge};
+use matrix::{MatrixClient, Rooms};
+use settings::Settings;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+mod bridge;
+mod controller;
+mod matrix;
+mod settings;
+
+#[actix_web::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let config_file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "config.toml".into());
+    let settings = Settings::load(&config_file).context("Failed to load settings")?;
+
+    let http = reqwest::Client::new();
+    let controller = Arc::new(ControllerClient::new(
+        http.clone(),
+        settings.controller,
+        settings.oidc,
+    ));
+    let matrix = Arc::new(MatrixClient::new(http, settings.matrix.clone()));
+    let rooms = Arc::new(Rooms::default());
+
+    let server = {
+        let matrix_settings = actix_web::web::Data::new(settings.matrix.clone());
+        let matrix = actix_web::web::Data::from(matrix.clone());
+        let rooms = actix_web::web::Data::from(rooms.clone());
+
+        actix_web::HttpServer::new(move || {
+            actix_web::App::new()
+                .app_data(matrix_settings.clone())
+                .app_data(matrix.clone())
+                .app_data(rooms.clone())
+                .service(matrix::transactions)
+        })
+        .bind(&settings.matrix.listen_address)
+        .context("Failed to bind application service listener")?
+        .run()
+    };
+
+    tokio::select! {
+        result = server => result.context("Application service listener failed"),
+        _ = manage_bridges(controller, matrix, rooms) => Ok(()),
+    }
+}
+
+/// Start and stop the bridges according to the configuration of the controller
+async fn manage_bridges(
+    controller: Arc<ControllerClient>,
+    matrix: Arc<MatrixClient>,
+    rooms: Arc<Rooms>,
+) {
+    let mut running: HashMap<Uuid, (MatrixBridge, JoinHandle<()>)> = HashMap::new();
+    let mut interval = tokio::time::interval(controller.settings().refresh_interval);
+
+    loop {
+        interval.tick().await;
+
+        let bridges = match controller.matrix_bridges().await {
+            Ok(bridges) => bridges,
+            Err(e) => {
+                log::error!("Failed to get matrix bridges, {:?}", e);
+                continue;
+            }
+        };
+
+        // Forget bridges which left their room, stop bridges which have been removed or changed
+        running.retain(|event_id, (config, task)| {
+            if task.is_finished() {
+                return false;
+            }
+
+            let keep = bridges.iter().any(|state| state.bridge == *config);
+
+            if !keep {
+                log::info!("Stopping bridge of event {}", event_id);
+                task.abort();
+            }
+
+            keep
+        });
+
+        for state in bridges {
+            let config = state.bridge;
+
+            // Bridges only join rooms with participants, so they never open a room on their own
+            if !state.active || running.contains_key(&config.event_id) {
+                continue;
+            }
+
+            // A stopped bridge unregisters when its task is dropped, which might not have happened yet
+            let registration = match rooms.register(config.matrix_room_id.clone()) {
+                Some(registration) => registration,
+                None => {
+                    log::warn!(
+                        "Not starting bridge of event {}, matrix room {} is already bridged",
+                        config.event_id,
+                        config.matrix_room_id
+                    );
+                    continue;
+                }
+            };
+
+            log::info!(
+                "Starting bridge of event {} to {}",
+                config.event_id,
+                config.matrix_room_id
+            );
+
+            let bridge = Bridge {
+                controller: controller.clone(),
+                matrix: matrix.clone(),
+                config: config.clone(),
+            };
+
+            running.insert(
+                config.event_id,
+                (config, tokio::spawn(bridge.run(registration))),
+            );
+        }
+    }
+}
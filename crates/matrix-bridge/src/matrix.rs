@@ -0,0 +1,432 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Matrix application service
+//!
+//! Participants of OpenTalk rooms are represented by ghost users `@{ghost_prefix}{participant_id}:{server_name}`,
+//! which are registered and controlled by the application service. Messages of Matrix users are received as
+//! transactions from the homeserver.
+use crate::settings::Matrix;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{put, HttpRequest, HttpResponse};
+use anyhow::{Context, Result};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use url::Url;
+use uuid::Uuid;
+
+/// Number of transaction ids remembered to detect retried transactions
+const SEEN_TRANSACTIONS: usize = 64;
+
+/// Text message of a Matrix user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixMessage {
+    pub sender: String,
+    pub body: String,
+}
+
+pub struct MatrixClient {
+    http: reqwest::Client,
+    settings: Matrix,
+}
+
+impl MatrixClient {
+    pub fn new(http: reqwest::Client, settings: Matrix) -> Self {
+        Self { http, settings }
+    }
+
+    /// Returns the user id of the ghost representing the given participant
+    pub fn ghost_user_id(&self, participant_id: Uuid) -> String {
+        format!(
+            "@{}{}:{}",
+            self.settings.ghost_prefix, participant_id, self.settings.server_name
+        )
+    }
+
+    /// Returns true if the user is a ghost of this bridge
+    pub fn is_ghost(&self, user_id: &str) -> bool {
+        user_id
+            .strip_prefix('@')
+            .map(|localpart| localpart.starts_with(&self.settings.ghost_prefix))
+            .unwrap_or_default()
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        path: &[&str],
+        user_id: Option<&str>,
+    ) -> Result<RequestBuilder> {
+        let mut url: Url = self.settings.homeserver_url.join("_matrix/client/v3/")?;
+
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid homeserver url"))?
+            .pop_if_empty()
+            .extend(path);
+
+        if let Some(user_id) = user_id {
+            url.query_pairs_mut().append_pair("user_id", user_id);
+        }
+
+        Ok(self
+            .http
+            .request(method, url)
+            .bearer_auth(&self.settings.as_token))
+    }
+
+    /// Register the ghost of the participant, join it to the room and set its display name
+    pub async fn setup_ghost(
+        &self,
+        room_id: &str,
+        participant_id: Uuid,
+        display_name: &str,
+    ) -> Result<()> {
+        let user_id = self.ghost_user_id(participant_id);
+        let localpart = format!("{}{}", self.settings.ghost_prefix, participant_id);
+
+        let response = self
+            .request(Method::POST, &["register"], None)?
+            .json(&json!({ "type": "m.login.application_service", "username": localpart }))
+            .send()
+            .await
+            .context("Failed to register ghost")?;
+
+        // The ghost is already registered if the bridge has seen the participant before
+        if response.status() != StatusCode::BAD_REQUEST {
+            response.error_for_status()?;
+        }
+
+        self.request(Method::POST, &["join", room_id], Some(&user_id))?
+            .json(&json!({}))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to join ghost")?;
+
+        self.request(
+            Method::PUT,
+            &["profile", &user_id, "displayname"],
+            Some(&user_id),
+        )?
+        .json(&json!({ "displayname": display_name }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to set display name of ghost")?;
+
+        Ok(())
+    }
+
+    /// Make the ghost of the participant leave the room
+    pub async fn leave_ghost(&self, room_id: &str, participant_id: Uuid) -> Result<()> {
+        let user_id = self.ghost_user_id(participant_id);
+
+        self.request(Method::POST, &["rooms", room_id, "leave"], Some(&user_id))?
+            .json(&json!({}))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Send a text message as the ghost of the participant
+    pub async fn send_message(
+        &self,
+        room_id: &str,
+        participant_id: Uuid,
+        body: &str,
+    ) -> Result<()> {
+        let user_id = self.ghost_user_id(participant_id);
+        let txn_id = Uuid::new_v4().to_string();
+
+        self.request(
+            Method::PUT,
+            &["rooms", room_id, "send", "m.room.message", &txn_id],
+            Some(&user_id),
+        )?
+        .json(&json!({ "msgtype": "m.text", "body": body }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Returns the display name of a Matrix user, falling back to the user id
+    pub async fn display_name(&self, user_id: &str) -> String {
+        #[derive(Deserialize)]
+        struct DisplayName {
+            displayname: Option<String>,
+        }
+
+        let display_name = async {
+            let response: DisplayName = self
+                .request(Method::GET, &["profile", user_id, "displayname"], None)?
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            anyhow::Ok(response.displayname)
+        };
+
+        match display_name.await {
+            Ok(Some(display_name)) => display_name,
+            Ok(None) => user_id.to_owned(),
+            Err(e) => {
+                log::debug!("Failed to get display name of {}, {:?}", user_id, e);
+                user_id.to_owned()
+            }
+        }
+    }
+}
+
+/// Senders of Matrix messages to the bridges, by Matrix room id
+#[derive(Default)]
+pub struct Rooms {
+    senders: Mutex<HashMap<String, mpsc::UnboundedSender<MatrixMessage>>>,
+    seen_transactions: Mutex<VecDeque<String>>,
+}
+
+/// Receiver of the messages of a Matrix room, unregisters itself when dropped
+pub struct Registration {
+    rooms: Arc<Rooms>,
+    room_id: String,
+    pub receiver: mpsc::UnboundedReceiver<MatrixMessage>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.rooms.senders.lock().unwrap().remove(&self.room_id);
+    }
+}
+
+impl Rooms {
+    /// Receive the messages of the given Matrix room
+    ///
+    /// Returns `None` if the room is already registered, each Matrix room can only be bridged to a single OpenTalk room
+    /// at a time.
+    pub fn register(self: &Arc<Self>, room_id: String) -> Option<Registration> {
+        let mut senders = self.senders.lock().unwrap();
+
+        if senders.contains_key(&room_id) {
+            return None;
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        senders.insert(room_id.clone(), sender);
+
+        Some(Registration {
+            rooms: self.clone(),
+            room_id,
+            receiver,
+        })
+    }
+
+    /// Returns false if the transaction has already been processed
+    fn first_seen(&self, txn_id: &str) -> bool {
+        let mut seen = self.seen_transactions.lock().unwrap();
+
+        if seen.iter().any(|seen| seen == txn_id) {
+            return false;
+        }
+
+        if seen.len() == SEEN_TRANSACTIONS {
+            seen.pop_front();
+        }
+
+        seen.push_back(txn_id.to_owned());
+
+        true
+    }
+
+    fn dispatch(&self, room_id: &str, message: MatrixMessage) {
+        if let Some(sender) = self.senders.lock().unwrap().get(room_id) {
+            let _ = sender.send(message);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transaction {
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    room_id: String,
+    sender: String,
+    #[serde(default)]
+    content: Value,
+}
+
+impl RoomEvent {
+    /// Returns the text of `m.room.message` events with a textual msgtype
+    fn text(&self) -> Option<&str> {
+        if self.kind != "m.room.message" {
+            return None;
+        }
+
+        match self.content["msgtype"].as_str()? {
+            "m.text" | "m.notice" | "m.emote" => self.content["body"].as_str(),
+            _ => None,
+        }
+    }
+}
+
+fn authorized(request: &HttpRequest, hs_token: &str) -> bool {
+    let header = request
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // Older homeservers send the token as query parameter
+    let query = url::form_urlencoded::parse(request.query_string().as_bytes())
+        .find(|(key, _)| key == "access_token")
+        .map(|(_, value)| value.into_owned());
+
+    header == Some(hs_token) || query.as_deref() == Some(hs_token)
+}
+
+/// Application service endpoint *PUT /_matrix/app/v1/transactions/{txn_id}*
+#[put("/_matrix/app/v1/transactions/{txn_id}")]
+pub async fn transactions(
+    request: HttpRequest,
+    settings: Data<Matrix>,
+    matrix: Data<MatrixClient>,
+    rooms: Data<Rooms>,
+    txn_id: Path<String>,
+    transaction: Json<Transaction>,
+) -> HttpResponse {
+    if !authorized(&request, &settings.hs_token) {
+        return HttpResponse::Forbidden().json(json!({ "errcode": "M_FORBIDDEN" }));
+    }
+
+    if !rooms.first_seen(&txn_id) {
+        return HttpResponse::Ok().json(json!({}));
+    }
+
+    for event in transaction.into_inner().events {
+        // Ignore the messages sent by the bridge itself
+        if matrix.is_ghost(&event.sender) {
+            continue;
+        }
+
+        if let Some(body) = event.text() {
+            let message = MatrixMessage {
+                sender: matrix.display_name(&event.sender).await,
+                body: body.to_owned(),
+            };
+
+            rooms.dispatch(&event.room_id, message);
+        }
+    }
+
+    HttpResponse::Ok().json(json!({}))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn client() -> MatrixClient {
+        MatrixClient::new(
+            reqwest::Client::new(),
+            Matrix {
+                homeserver_url: "http://localhost:8008/".parse().unwrap(),
+                server_name: "example.org".into(),
+                as_token: "as_token".into(),
+                hs_token: "hs_token".into(),
+                ghost_prefix: "opentalk_".into(),
+                listen_address: "0.0.0.0:9009".into(),
+            },
+        )
+    }
+
+    #[test]
+    fn ghosts() {
+        let client = client();
+
+        let ghost = client.ghost_user_id(Uuid::nil());
+
+        assert_eq!(
+            ghost,
+            "@opentalk_00000000-0000-0000-0000-000000000000:example.org"
+        );
+        assert!(client.is_ghost(&ghost));
+        assert!(!client.is_ghost("@alice:example.org"));
+    }
+
+    #[test]
+    fn text_of_events() {
+        let transaction: Transaction = serde_json::from_value(json!({
+            "events": [
+                {
+                    "type": "m.room.message",
+                    "room_id": "!room:example.org",
+                    "sender": "@alice:example.org",
+                    "content": { "msgtype": "m.text", "body": "Hello" }
+                },
+                {
+                    "type": "m.room.message",
+                    "room_id": "!room:example.org",
+                    "sender": "@alice:example.org",
+                    "content": { "msgtype": "m.image", "body": "cat.png" }
+                },
+                {
+                    "type": "m.room.member",
+                    "room_id": "!room:example.org",
+                    "sender": "@alice:example.org",
+                    "content": { "membership": "join" }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let texts: Vec<_> = transaction.events.iter().map(RoomEvent::text).collect();
+
+        assert_eq!(texts, vec![Some("Hello"), None, None]);
+    }
+
+    #[test]
+    fn duplicate_registration() {
+        let rooms = Arc::new(Rooms::default());
+
+        let old = rooms.register("!room:example.org".into()).unwrap();
+        assert!(rooms.register("!room:example.org".into()).is_none());
+        drop(old);
+
+        let mut new = rooms.register("!room:example.org".into()).unwrap();
+
+        rooms.dispatch(
+            "!room:example.org",
+            MatrixMessage {
+                sender: "Alice".into(),
+                body: "Hello".into(),
+            },
+        );
+
+        assert_eq!(new.receiver.try_recv().unwrap().body, "Hello");
+    }
+
+    #[test]
+    fn retried_transactions() {
+        let rooms = Rooms::default();
+
+        assert!(rooms.first_seen("1"));
+        assert!(rooms.first_seen("2"));
+        assert!(!rooms.first_seen("1"));
+    }
+}
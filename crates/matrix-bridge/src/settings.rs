@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Settings of the bridge
+//!
+//! Loaded from a TOML file, fields can be overwritten with environment variables with the prefix
+//! `K3K_MATRIX_BRIDGE_`. Nested fields are separated by two underscores, e.g. `K3K_MATRIX_BRIDGE_OIDC__CLIENT_SECRET`.
+use config::{Config, ConfigError, Environment, File, FileFormat};
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+use url::Url;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Settings {
+    pub controller: Controller,
+    pub oidc: Oidc,
+    pub matrix: Matrix,
+}
+
+impl Settings {
+    pub fn load(file_name: &str) -> Result<Self, ConfigError> {
+        Config::builder()
+            .add_source(File::new(file_name, FileFormat::Toml))
+            .add_source(
+                Environment::with_prefix("K3K_MATRIX_BRIDGE")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
+            .build()?
+            .try_deserialize()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Controller {
+    /// Base URL of the v1 REST API, e.g. `https://controller.example.org/v1/`
+    pub api_url: Url,
+    /// URL of the signaling endpoint, e.g. `wss://controller.example.org/signaling`
+    pub signaling_url: Url,
+    /// Time in seconds between refreshes of the configured bridges
+    #[serde(
+        deserialize_with = "duration_from_secs",
+        default = "default_refresh_interval"
+    )]
+    pub refresh_interval: Duration,
+    /// Display name of the bridge inside rooms
+    #[serde(default = "default_display_name")]
+    pub display_name: String,
+}
+
+fn default_refresh_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_display_name() -> String {
+    "Matrix".into()
+}
+
+/// Client credentials of the service account, which requires the `opentalk-bot` realm role
+#[derive(Debug, Clone, Deserialize)]
+pub struct Oidc {
+    pub token_url: Url,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Application service registration at the homeserver
+#[derive(Debug, Clone, Deserialize)]
+pub struct Matrix {
+    pub homeserver_url: Url,
+    pub server_name: String,
+    pub as_token: String,
+    pub hs_token: String,
+    /// Prefix of the localparts of users representing OpenTalk participants
+    #[serde(default = "default_ghost_prefix")]
+    pub ghost_prefix: String,
+    /// Address the homeserver sends transactions to
+    pub listen_address: String,
+}
+
+fn default_ghost_prefix() -> String {
+    "opentalk_".into()
+}
+
+fn duration_from_secs<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let duration: u64 = Deserialize::deserialize(deserializer)?;
+
+    Ok(Duration::from_secs(duration))
+}
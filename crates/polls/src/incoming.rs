@@ -2,34 +2,56 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use crate::{ChoiceId, PollId};
+use crate::{AutoExtend, ChoiceId, PollId};
+use controller::prelude::uuid::Uuid;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Message {
     Start(Start),
+    Launch(Launch),
     Vote(Vote),
     Finish(Finish),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Start {
     pub topic: String,
     pub live: bool,
     pub choices: Vec<String>,
     #[serde(with = "super::duration_secs")]
+    #[schemars(with = "u64")]
     pub duration: Duration,
+    /// Extend the poll once if too few participants voted
+    #[serde(default)]
+    pub auto_extend: Option<AutoExtend>,
+    /// Publish the final results to the events of the room, only available to registered users
+    #[serde(default)]
+    pub publish_results: bool,
+    /// Send the final results to the chat services and webhooks subscribed to `vote_result` notifications
+    #[serde(default)]
+    pub notify_results: bool,
 }
 
-#[derive(Debug, Deserialize)]
+/// Start a poll which has been prepared for an event of the room or in the poll library of the participant
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Launch {
+    pub template_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Vote {
     pub poll_id: PollId,
     pub choice_id: ChoiceId,
+    /// Key chosen by the client to safely retry the vote, a retried vote with the same key is not rejected
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Finish {
     pub id: PollId,
 }
@@ -39,7 +61,6 @@ mod test {
     use super::*;
     use controller::prelude::*;
     use pretty_assertions::assert_eq;
-    use uuid::Uuid;
 
     #[test]
     fn start() {
@@ -60,12 +81,67 @@ mod test {
             live,
             choices,
             duration,
+            auto_extend,
+            publish_results,
+            notify_results,
         }) = message
         {
             assert_eq!(topic, "abc");
             assert!(live);
             assert_eq!(choices, vec!["a", "b", "c"]);
             assert_eq!(duration, Duration::from_secs(30));
+            assert_eq!(auto_extend, None);
+            assert!(!publish_results);
+            assert!(!notify_results);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn start_with_auto_extend() {
+        let json = r#"
+        {
+            "action": "start",
+            "topic": "abc",
+            "live": false,
+            "choices": ["a", "b"],
+            "duration": 30,
+            "auto_extend": {
+                "min_participation": 50,
+                "duration": 15
+            }
+        }
+        "#;
+
+        let message: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Start(Start { auto_extend, .. }) = message {
+            assert_eq!(
+                auto_extend,
+                Some(AutoExtend {
+                    min_participation: 50,
+                    duration: Duration::from_secs(15),
+                })
+            );
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn launch() {
+        let json = r#"
+        {
+            "action": "launch",
+            "template_id": "00000000-0000-0000-0000-000000000000"
+        }
+        "#;
+
+        let message: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Launch(Launch { template_id }) = message {
+            assert_eq!(template_id, Uuid::nil());
         } else {
             panic!()
         }
@@ -83,9 +159,38 @@ mod test {
 
         let message: Message = serde_json::from_str(json).unwrap();
 
-        if let Message::Vote(Vote { poll_id, choice_id }) = message {
+        if let Message::Vote(Vote {
+            poll_id,
+            choice_id,
+            idempotency_key,
+        }) = message
+        {
             assert_eq!(poll_id, PollId(Uuid::nil()));
             assert_eq!(choice_id, ChoiceId(321));
+            assert_eq!(idempotency_key, None);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn vote_with_idempotency_key() {
+        let json = r#"
+        {
+            "action": "vote",
+            "poll_id": "00000000-0000-0000-0000-000000000000",
+            "choice_id": 321,
+            "idempotency_key": "f3b2c1"
+         }
+        "#;
+
+        let message: Message = serde_json::from_str(json).unwrap();
+
+        if let Message::Vote(Vote {
+            idempotency_key, ..
+        }) = message
+        {
+            assert_eq!(idempotency_key.as_deref(), Some("f3b2c1"));
         } else {
             panic!()
         }
@@ -5,16 +5,24 @@
 use anyhow::Result;
 use chrono::Utc;
 use controller::prelude::*;
+use controller::settings::NotificationEvent;
+use database::{Db, OptionalExt};
+use db_storage::events::published_results::{NewEventPublishedResult, ResultEntry};
+use db_storage::poll_templates::{PollTemplate, PollTemplateId};
+use db_storage::rooms::Room;
+use db_storage::utils::Jsonb;
 use futures::stream::once;
 use futures::FutureExt;
 use redis::{self, FromRedisValue, RedisResult};
 use redis_args::{FromRedisValue, ToRedisArgs};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::{from_utf8, FromStr};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
-use types::core::Timestamp;
+use types::core::{ParticipationKind, Timestamp, UserId};
 use uuid::Uuid;
 
 pub mod incoming;
@@ -26,14 +34,20 @@ pub struct ExpiredEvent(PollId);
 
 pub struct Polls {
     room: SignalingRoomId,
+    db: Arc<Db>,
+    /// Set for registered users, which can launch polls of their library and publish results to events
+    user_id: Option<UserId>,
+    i_am_the_recorder: bool,
     config: Option<Config>,
+    /// Not available in tests
+    notifications: Option<NotificationService>,
 }
 
 #[async_trait::async_trait(?Send)]
 impl SignalingModule for Polls {
     const NAMESPACE: &'static str = "polls";
 
-    type Params = ();
+    type Params = Option<NotificationService>;
 
     type Incoming = incoming::Message;
     type Outgoing = outgoing::Message;
@@ -46,12 +60,21 @@ impl SignalingModule for Polls {
 
     async fn init(
         ctx: InitContext<'_, Self>,
-        _: &Self::Params,
+        notifications: &Self::Params,
         _: &'static str,
     ) -> Result<Option<Self>> {
+        let user_id = match ctx.participant() {
+            Participant::User(user) => Some(user.id),
+            _ => None,
+        };
+
         Ok(Some(Self {
             room: ctx.room_id(),
+            db: ctx.db().clone(),
+            user_id,
+            i_am_the_recorder: matches!(ctx.participant(), Participant::Recorder),
             config: None,
+            notifications: notifications.clone(),
         }))
     }
 
@@ -89,9 +112,31 @@ impl SignalingModule for Polls {
             Event::RabbitMq(msg) => self.on_rabbitmq_message(ctx, msg).await,
             Event::Ext(ExpiredEvent(id)) => {
                 if let Some(config) = self.config.as_ref().filter(|config| config.id == id) {
+                    if let Some(auto_extend) = config.auto_extend.filter(|_| !config.extended) {
+                        // The participation is checked once for everyone, the result is published to all participants
+                        if storage::set_participation_checked(ctx.redis_conn(), self.room, id)
+                            .await?
+                        {
+                            let config = config.clone();
+
+                            self.extend_or_finish(&mut ctx, config, auto_extend).await?;
+                        }
+
+                        return Ok(());
+                    }
+
                     let results =
                         storage::poll_results(ctx.redis_conn(), self.room, config).await?;
 
+                    self.publish_overlay(&mut ctx, config, &results, true)
+                        .await?;
+
+                    // The poll expires for every participant, only notify once
+                    if storage::set_notified(ctx.redis_conn(), self.room, id).await? {
+                        self.notify_result(config, &results);
+                        self.publish_results(config, &results).await?;
+                    }
+
                     ctx.ws_send(outgoing::Message::Done(outgoing::Results { id, results }));
                 }
 
@@ -137,129 +182,112 @@ impl Polls {
         msg: incoming::Message,
     ) -> Result<()> {
         match msg {
-            incoming::Message::Start(incoming::Start {
-                topic,
-                live,
-                choices,
-                duration,
-            }) => {
+            incoming::Message::Start(start) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
-                    ));
-
-                    return Ok(());
-                }
-
-                if self.is_running() {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::StillRunning));
-
-                    return Ok(());
-                }
-
-                // TODO(k.balt): Minimal duration 2 secs for tests but thats unreasonably low real world applications
-                let min = Duration::from_secs(2);
-                let max = Duration::from_secs(3600);
-
-                if duration > max || duration < min {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidDuration));
-
-                    return Ok(());
-                }
-
-                if !matches!(topic.len(), 2..=100) {
-                    ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidTopicLength,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
 
                     return Ok(());
                 }
 
-                if !matches!(choices.len(), 2..=64) {
+                self.start(&mut ctx, start).await
+            }
+            incoming::Message::Launch(incoming::Launch { template_id }) => {
+                if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidChoiceCount,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
 
                     return Ok(());
                 }
 
-                if choices
-                    .iter()
-                    .any(|content| !matches!(content.len(), 1..=100))
-                {
-                    ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidChoiceDescription,
-                    ));
+                let template = match self.get_template(template_id).await? {
+                    Some(template) => template,
+                    None => {
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::InvalidTemplateId.into(),
+                        ));
 
-                    return Ok(());
-                }
-
-                let choices = choices
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, content)| Choice {
-                        id: ChoiceId(i as u32),
-                        content,
-                    })
-                    .collect();
-
-                let config = Config {
-                    id: PollId(Uuid::new_v4()),
-                    topic,
-                    live,
-                    choices,
-                    started: ctx.timestamp(),
-                    duration,
-                    voted: false,
+                        return Ok(());
+                    }
                 };
 
-                let set = storage::set_config(ctx.redis_conn(), self.room, &config).await?;
-
-                if !set {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::StillRunning));
-
-                    return Ok(());
-                }
-
-                storage::list_add(ctx.redis_conn(), self.room, config.id).await?;
-
-                ctx.rabbitmq_publish(
-                    control::rabbitmq::current_room_exchange_name(self.room),
-                    control::rabbitmq::room_all_routing_key().into(),
-                    rabbitmq::Message::Started(config),
-                );
-
-                Ok(())
+                self.start(
+                    &mut ctx,
+                    incoming::Start {
+                        topic: template.topic,
+                        live: template.live,
+                        choices: template.choices,
+                        duration: Duration::from_secs(template.duration_secs as u64),
+                        auto_extend: None,
+                        publish_results: false,
+                        notify_results: false,
+                    },
+                )
+                .await
             }
-            incoming::Message::Vote(incoming::Vote { poll_id, choice_id }) => {
+            incoming::Message::Vote(incoming::Vote {
+                poll_id,
+                choice_id,
+                idempotency_key,
+            }) => {
                 if let Some(config) = self
                     .config
                     .as_mut()
                     .filter(|config| config.id == poll_id && !config.is_expired())
                 {
-                    if config.voted {
-                        ctx.ws_send(outgoing::Message::Error(outgoing::Error::VotedAlready));
+                    // Retried votes are checked against the stored idempotency key
+                    if config.voted && idempotency_key.is_none() {
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::VotedAlready.into(),
+                        ));
 
                         return Ok(());
                     }
 
                     if config.choices.iter().any(|choice| choice.id == choice_id) {
-                        storage::vote(ctx.redis_conn(), self.room, config.id, choice_id).await?;
-
-                        config.voted = true;
-
-                        if config.live {
-                            ctx.rabbitmq_publish(
-                                control::rabbitmq::current_room_exchange_name(self.room),
-                                control::rabbitmq::room_all_routing_key().into(),
-                                rabbitmq::Message::Update(poll_id),
-                            );
+                        let result = storage::vote(
+                            ctx.redis_conn(),
+                            self.room,
+                            config.id,
+                            ctx.participant_id(),
+                            choice_id,
+                            idempotency_key.as_deref(),
+                        )
+                        .await?;
+
+                        match result {
+                            storage::VoteResult::Voted => {
+                                config.voted = true;
+
+                                if config.live {
+                                    ctx.rabbitmq_publish(
+                                        control::rabbitmq::current_room_exchange_name(self.room),
+                                        control::rabbitmq::room_all_routing_key().into(),
+                                        rabbitmq::Message::Update(poll_id),
+                                    );
+                                }
+                            }
+                            // The original vote has been counted, behave as if it succeeded again
+                            storage::VoteResult::Replayed => config.voted = true,
+                            storage::VoteResult::VotedAlready => {
+                                config.voted = true;
+
+                                ctx.ws_send(outgoing::Message::Error(
+                                    outgoing::Error::VotedAlready.into(),
+                                ));
+                            }
                         }
                     } else {
-                        ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidChoiceId));
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::InvalidChoiceId.into(),
+                        ));
                     }
                 } else {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidPollId));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidPollId.into(),
+                    ));
                 }
 
                 Ok(())
@@ -267,28 +295,37 @@ impl Polls {
             incoming::Message::Finish(finish) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
 
                     return Ok(());
                 }
 
-                if self
+                if let Some(config) = self
                     .config
                     .as_ref()
                     .filter(|config| config.id == finish.id && !config.is_expired())
-                    .is_some()
                 {
                     // Delete config from redis to stop vote
                     storage::del_config(ctx.redis_conn(), self.room).await?;
 
+                    if storage::set_notified(ctx.redis_conn(), self.room, config.id).await? {
+                        let results =
+                            storage::poll_results(ctx.redis_conn(), self.room, config).await?;
+
+                        self.notify_result(config, &results);
+                        self.publish_results(config, &results).await?;
+                    }
+
                     ctx.rabbitmq_publish(
                         control::rabbitmq::current_room_exchange_name(self.room),
                         control::rabbitmq::room_all_routing_key().into(),
                         rabbitmq::Message::Finish(finish.id),
                     );
                 } else {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::InvalidPollId));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::InvalidPollId.into(),
+                    ));
                 }
 
                 Ok(())
@@ -296,6 +333,216 @@ impl Polls {
         }
     }
 
+    /// Validate the poll and start it for all participants of the room
+    async fn start(
+        &mut self,
+        ctx: &mut ModuleContext<'_, Self>,
+        incoming::Start {
+            topic,
+            live,
+            choices,
+            duration,
+            auto_extend,
+            publish_results,
+            notify_results,
+        }: incoming::Start,
+    ) -> Result<()> {
+        if self.is_running() {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::StillRunning.into(),
+            ));
+
+            return Ok(());
+        }
+
+        // TODO(k.balt): Minimal duration 2 secs for tests but thats unreasonably low real world applications
+        let min = Duration::from_secs(2);
+        let max = Duration::from_secs(3600);
+
+        if duration > max || duration < min {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::InvalidDuration.into(),
+            ));
+
+            return Ok(());
+        }
+
+        if let Some(auto_extend) = &auto_extend {
+            if auto_extend.duration > max || auto_extend.duration < min {
+                ctx.ws_send(outgoing::Message::Error(
+                    outgoing::Error::InvalidDuration.into(),
+                ));
+
+                return Ok(());
+            }
+
+            if !matches!(auto_extend.min_participation, 1..=100) {
+                ctx.ws_send(outgoing::Message::Error(
+                    outgoing::Error::InvalidMinParticipation.into(),
+                ));
+
+                return Ok(());
+            }
+        }
+
+        // Results are published in the name of the registered user who started the poll
+        let publisher = match (publish_results, self.user_id) {
+            (false, _) => None,
+            (true, Some(user_id)) => Some(user_id),
+            (true, None) => {
+                ctx.ws_send(outgoing::Message::Error(
+                    outgoing::Error::InsufficientPermissions.into(),
+                ));
+
+                return Ok(());
+            }
+        };
+
+        if !matches!(topic.len(), 2..=100) {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::InvalidTopicLength.into(),
+            ));
+
+            return Ok(());
+        }
+
+        if !matches!(choices.len(), 2..=64) {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::InvalidChoiceCount.into(),
+            ));
+
+            return Ok(());
+        }
+
+        if choices
+            .iter()
+            .any(|content| !matches!(content.len(), 1..=100))
+        {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::InvalidChoiceDescription.into(),
+            ));
+
+            return Ok(());
+        }
+
+        let choices = choices
+            .into_iter()
+            .enumerate()
+            .map(|(i, content)| Choice {
+                id: ChoiceId(i as u32),
+                content,
+            })
+            .collect();
+
+        let config = Config {
+            id: PollId(Uuid::new_v4()),
+            topic,
+            live,
+            choices,
+            started: ctx.timestamp(),
+            duration,
+            auto_extend,
+            extended: false,
+            publisher,
+            notify_results,
+            voted: false,
+        };
+
+        let set = storage::set_config(ctx.redis_conn(), self.room, &config, duration).await?;
+
+        if !set {
+            ctx.ws_send(outgoing::Message::Error(
+                outgoing::Error::StillRunning.into(),
+            ));
+
+            return Ok(());
+        }
+
+        storage::list_add(ctx.redis_conn(), self.room, config.id).await?;
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::Started(config),
+        );
+
+        Ok(())
+    }
+
+    /// Extend the expired poll if too few of the eligible participants voted, finish it for everyone otherwise
+    async fn extend_or_finish(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        mut config: Config,
+        auto_extend: AutoExtend,
+    ) -> Result<()> {
+        let voters = storage::voter_count(ctx.redis_conn(), self.room, config.id).await?;
+        let eligible = eligible_participant_count(ctx.redis_conn(), self.room).await?;
+
+        if voters * 100 < eligible * usize::from(auto_extend.min_participation) {
+            config.duration += auto_extend.duration;
+            config.extended = true;
+
+            // Fails if another poll has been started in the meantime
+            if storage::set_config(ctx.redis_conn(), self.room, &config, auto_extend.duration)
+                .await?
+            {
+                ctx.rabbitmq_publish(
+                    control::rabbitmq::current_room_exchange_name(self.room),
+                    control::rabbitmq::room_all_routing_key().into(),
+                    rabbitmq::Message::Extended(config),
+                );
+
+                return Ok(());
+            }
+        }
+
+        if storage::set_notified(ctx.redis_conn(), self.room, config.id).await? {
+            let results = storage::poll_results(ctx.redis_conn(), self.room, &config).await?;
+
+            self.notify_result(&config, &results);
+            self.publish_results(&config, &results).await?;
+        }
+
+        ctx.rabbitmq_publish(
+            control::rabbitmq::current_room_exchange_name(self.room),
+            control::rabbitmq::room_all_routing_key().into(),
+            rabbitmq::Message::Finish(config.id),
+        );
+
+        Ok(())
+    }
+
+    /// Get the prepared poll, if it has been prepared for an event of the room or is in the library of the participant
+    async fn get_template(&self, template_id: Uuid) -> Result<Option<PollTemplate>> {
+        let db = self.db.clone();
+        let room_id = self.room.room_id();
+        let user_id = self.user_id;
+
+        let template = controller::block(move || -> database::Result<_> {
+            let mut conn = db.get_conn()?;
+
+            let template =
+                match PollTemplate::get(&mut conn, PollTemplateId::from(template_id)).optional()? {
+                    Some(template) => template,
+                    None => return Ok(None),
+                };
+
+            let accessible = match template.event_id {
+                Some(event_id) => {
+                    db_storage::events::Event::get_all_ids_for_room(&mut conn, room_id)?
+                        .contains(&event_id)
+                }
+                None => Some(template.created_by) == user_id,
+            };
+
+            Ok(accessible.then_some(template))
+        })
+        .await??;
+
+        Ok(template)
+    }
+
     async fn on_rabbitmq_message(
         &mut self,
         mut ctx: ModuleContext<'_, Self>,
@@ -312,12 +559,35 @@ impl Polls {
                         live: config.live,
                         choices: config.choices.clone(),
                         duration: config.duration,
+                        auto_extend: config.auto_extend,
                     }),
                     config.started,
                 );
 
                 ctx.add_event_stream(once(sleep(config.duration).map(move |_| ExpiredEvent(id))));
 
+                self.publish_overlay(&mut ctx, &config, &[], false).await?;
+
+                self.config = Some(config);
+
+                Ok(())
+            }
+            rabbitmq::Message::Extended(mut config) => {
+                let id = config.id;
+
+                if let Some(current) = self.config.as_ref().filter(|current| current.id == id) {
+                    config.voted = current.voted;
+                }
+
+                if let Some(remaining) = config.remaining() {
+                    ctx.add_event_stream(once(sleep(remaining).map(move |_| ExpiredEvent(id))));
+                }
+
+                ctx.ws_send(outgoing::Message::Extended(outgoing::Extended {
+                    id,
+                    deadline: config.deadline(),
+                }));
+
                 self.config = Some(config);
 
                 Ok(())
@@ -327,6 +597,9 @@ impl Polls {
                     let results =
                         storage::poll_results(ctx.redis_conn(), self.room, config).await?;
 
+                    self.publish_overlay(&mut ctx, config, &results, false)
+                        .await?;
+
                     ctx.ws_send(outgoing::Message::LiveUpdate(outgoing::Results {
                         id,
                         results,
@@ -340,6 +613,9 @@ impl Polls {
                     let results =
                         storage::poll_results(ctx.redis_conn(), self.room, &config).await?;
 
+                    self.publish_overlay(&mut ctx, &config, &results, true)
+                        .await?;
+
                     ctx.ws_send(outgoing::Message::Done(outgoing::Results { id, results }));
                 }
 
@@ -347,9 +623,128 @@ impl Polls {
             }
         }
     }
+
+    /// Notify the configured chat services and webhooks about the results of the finished poll
+    ///
+    /// Only done if the moderator requested it when starting the poll.
+    fn notify_result(&self, config: &Config, results: &[outgoing::Item]) {
+        if !config.notify_results {
+            return;
+        }
+
+        if let Some(notifications) = &self.notifications {
+            let results = config
+                .choices
+                .iter()
+                .map(|choice| {
+                    let count = results
+                        .iter()
+                        .find(|item| item.id == choice.id)
+                        .map(|item| item.count)
+                        .unwrap_or_default();
+
+                    format!("{}: {}", choice.content, count)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            notifications.notify(
+                NotificationEvent::VoteResult,
+                self.room.room_id(),
+                vec![("topic", config.topic.clone()), ("results", results)],
+            );
+        }
+    }
+
+    /// Publish the final results of the poll to the events of the room, if requested when starting the poll
+    async fn publish_results(&self, config: &Config, results: &[outgoing::Item]) -> Result<()> {
+        let published_by = match config.publisher {
+            Some(published_by) => published_by,
+            None => return Ok(()),
+        };
+
+        let db = self.db.clone();
+        let room_id = self.room.room_id();
+        let title = config.topic.clone();
+        let results = config
+            .choices
+            .iter()
+            .map(|choice| ResultEntry {
+                option: choice.content.clone(),
+                count: results
+                    .iter()
+                    .find(|item| item.id == choice.id)
+                    .map(|item| u64::from(item.count))
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        controller::block(move || -> database::Result<()> {
+            let mut conn = db.get_conn()?;
+
+            let tenant_id = Room::get(&mut conn, room_id)?.tenant_id;
+
+            for event_id in db_storage::events::Event::get_all_ids_for_room(&mut conn, room_id)? {
+                NewEventPublishedResult {
+                    event_id,
+                    published_by,
+                    title: title.clone(),
+                    results: Jsonb(results.clone()),
+                    legal_vote_id: None,
+                    tenant_id,
+                }
+                .try_insert(&mut conn)?;
+            }
+
+            Ok(())
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Publish the current results of the poll to the recording, if this is the recorder participant
+    async fn publish_overlay(
+        &self,
+        ctx: &mut ModuleContext<'_, Self>,
+        config: &Config,
+        results: &[outgoing::Item],
+        finished: bool,
+    ) -> Result<()> {
+        if !self.i_am_the_recorder {
+            return Ok(());
+        }
+
+        let choices = config
+            .choices
+            .iter()
+            .map(|choice| recording::overlay::PollChoice {
+                content: choice.content.clone(),
+                count: results
+                    .iter()
+                    .find(|item| item.id == choice.id)
+                    .map(|item| item.count)
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        recording::publish_overlay(
+            ctx,
+            self.room,
+            recording::Overlay::PollResults(recording::overlay::PollResults {
+                id: config.id.0,
+                topic: config.topic.clone(),
+                choices,
+                finished,
+            }),
+        )
+        .await
+    }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToRedisArgs)]
+#[derive(
+    Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToRedisArgs, JsonSchema,
+)]
 #[to_redis_args(fmt)]
 pub struct PollId(pub Uuid);
 
@@ -378,7 +773,7 @@ impl fmt::Display for PollId {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
 pub struct ChoiceId(pub u32);
 
 impl FromRedisValue for ChoiceId {
@@ -387,7 +782,7 @@ impl FromRedisValue for ChoiceId {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Choice {
     pub id: ChoiceId,
     pub content: String,
@@ -404,6 +799,17 @@ pub struct Config {
     started: Timestamp,
     #[serde(with = "duration_secs")]
     duration: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto_extend: Option<AutoExtend>,
+    /// Set once the poll has been extended, it is only extended once
+    #[serde(default)]
+    extended: bool,
+    /// User in whose name the final results are published to the events of the room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publisher: Option<UserId>,
+    /// Send the final results to the subscribed chat services and webhooks
+    #[serde(default)]
+    notify_results: bool,
 
     // skip flag, not serialized into redis and always false when reading from it
     // Indicates if the user of the module has already voted for this config
@@ -411,17 +817,31 @@ pub struct Config {
     voted: bool,
 }
 
+/// Extend the poll once if too few participants voted when it expires
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct AutoExtend {
+    /// Percentage of the eligible participants which must have voted
+    pub min_participation: u8,
+    /// Duration the poll is extended by
+    #[serde(with = "duration_secs")]
+    #[schemars(with = "u64")]
+    pub duration: Duration,
+}
+
 impl Config {
-    fn remaining(&self) -> Option<Duration> {
+    fn deadline(&self) -> Timestamp {
         let duration = chrono::Duration::from_std(self.duration)
             .expect("duration as secs should never be larger than i64::MAX");
 
-        let expire = (*self.started) + duration;
+        Timestamp::from(*self.started + duration)
+    }
+
+    fn remaining(&self) -> Option<Duration> {
         let now = Utc::now();
 
         // difference will be negative duration if expired.
         // Conversion to std duration will fail -> returning None
-        (expire - now).to_std().ok()
+        (*self.deadline() - now).to_std().ok()
     }
 
     fn is_expired(&self) -> bool {
@@ -429,6 +849,38 @@ impl Config {
     }
 }
 
+/// Count the participants in the room which can vote, i.e. users and guests which have not left
+async fn eligible_participant_count(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+) -> Result<usize> {
+    let participants = control::storage::get_all_participants(redis_conn, room).await?;
+
+    let kinds: Vec<Option<ParticipationKind>> =
+        control::storage::get_attribute_for_participants(redis_conn, room, "kind", &participants)
+            .await?;
+    let left_at: Vec<Option<Timestamp>> = control::storage::get_attribute_for_participants(
+        redis_conn,
+        room,
+        "left_at",
+        &participants,
+    )
+    .await?;
+
+    let count = kinds
+        .into_iter()
+        .zip(left_at)
+        .filter(|(kind, left_at)| {
+            matches!(
+                kind,
+                Some(ParticipationKind::User | ParticipationKind::Guest)
+            ) && left_at.is_none()
+        })
+        .count();
+
+    Ok(count)
+}
+
 mod duration_secs {
     use serde::{Deserialize, Deserializer, Serializer};
     use std::time::Duration;
@@ -449,5 +901,7 @@ mod duration_secs {
 }
 
 pub fn register(controller: &mut controller::Controller) {
-    controller.signaling.add_module::<Polls>(());
+    controller
+        .signaling
+        .add_module::<Polls>(Some(controller.notifications.clone()));
 }
@@ -2,42 +2,57 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use crate::{Choice, ChoiceId, PollId};
+use crate::{AutoExtend, Choice, ChoiceId, PollId};
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::time::Duration;
+use types::core::Timestamp;
+use types::signaling::{ErrorEnvelope, ModuleError};
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "message", rename_all = "snake_case")]
 pub enum Message {
     Started(Started),
+    Extended(Extended),
     LiveUpdate(Results),
     Done(Results),
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Started {
     pub id: PollId,
     pub topic: String,
     pub live: bool,
     pub choices: Vec<Choice>,
     #[serde(with = "super::duration_secs")]
+    #[schemars(with = "u64")]
     pub duration: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_extend: Option<AutoExtend>,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+/// The poll has been extended because too few participants voted
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
+pub struct Extended {
+    pub id: PollId,
+    /// The new end of the poll
+    pub deadline: Timestamp,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Results {
     pub id: PollId,
     pub results: Vec<Item>,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 pub struct Item {
     pub id: ChoiceId,
     pub count: u32,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "error")]
 pub enum Error {
     InsufficientPermissions,
@@ -47,10 +62,36 @@ pub enum Error {
     InvalidChoiceDescription,
     InvalidDuration,
     InvalidTopicLength,
+    InvalidTemplateId,
+    InvalidMinParticipation,
     VotedAlready,
     StillRunning,
 }
 
+impl ModuleError for Error {
+    const MODULE: &'static str = "polls";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::InvalidChoiceCount => "The poll has an invalid number of choices",
+            Self::InvalidPollId => "The poll does not exist",
+            Self::InvalidChoiceId => "The choice does not exist",
+            Self::InvalidChoiceDescription => "The description of a choice is invalid",
+            Self::InvalidDuration => "The duration of the poll is invalid",
+            Self::InvalidTopicLength => "The length of the topic is invalid",
+            Self::InvalidTemplateId => "The prepared poll does not exist",
+            Self::InvalidMinParticipation => "The minimum participation is invalid",
+            Self::VotedAlready => "The participant has already voted",
+            Self::StillRunning => "Another poll is still running",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(self, Self::StillRunning)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -75,6 +116,7 @@ mod test {
                 },
             ],
             duration: Duration::from_millis(10000),
+            auto_extend: None,
         });
 
         assert_eq_json!(
@@ -99,6 +141,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn extended() {
+        let extended = Message::Extended(Extended {
+            id: PollId(Uuid::nil()),
+            deadline: Timestamp::unix_epoch(),
+        });
+
+        assert_eq_json!(
+          extended,
+          {
+              "message": "extended",
+              "id": "00000000-0000-0000-0000-000000000000",
+              "deadline": "1970-01-01T00:00:00Z"
+          }
+        );
+    }
+
     #[test]
     fn live_update() {
         let live_update = Message::LiveUpdate(Results {
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
     Started(Config),
+    Extended(Config),
     Update(PollId),
     Finish(PollId),
 }
@@ -9,6 +9,8 @@ use controller::prelude::*;
 use redis::AsyncCommands;
 use redis_args::ToRedisArgs;
 use std::collections::HashMap;
+use std::time::Duration;
+use types::core::ParticipantId;
 
 /// Key to the current poll config
 #[derive(ToRedisArgs)]
@@ -28,18 +30,19 @@ pub(super) async fn get_config(
         .context("failed to get current config")
 }
 
-/// Set the current config if one doesn't already exist returns true if set was successful
+/// Set the current config which expires after `expire` if one doesn't already exist returns true if set was successful
 #[tracing::instrument(level = "debug", skip(redis_conn))]
 pub(super) async fn set_config(
     redis_conn: &mut RedisConnection,
     room: SignalingRoomId,
     config: &Config,
+    expire: Duration,
 ) -> Result<bool> {
     let value: redis::Value = redis::cmd("SET")
         .arg(PollConfig { room })
         .arg(config)
         .arg("EX")
-        .arg(config.duration.as_secs())
+        .arg(expire.as_secs())
         .arg("NX")
         .query_async(redis_conn)
         .await
@@ -71,37 +74,169 @@ struct PollResults {
     poll: PollId,
 }
 
+/// Key to the hash of participants which voted in the poll, mapped to the idempotency key of their vote
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:poll={poll}:voters")]
+struct PollVoters {
+    room: SignalingRoomId,
+    poll: PollId,
+}
+
+/// Key which is set once the results of the poll have been sent to the notification targets
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:poll={poll}:notified")]
+struct PollNotified {
+    room: SignalingRoomId,
+    poll: PollId,
+}
+
+/// Key which is set once the participation has been checked for a poll which can be extended
+#[derive(ToRedisArgs)]
+#[to_redis_args(fmt = "k3k-signaling:room={room}:poll={poll}:participation_checked")]
+struct PollParticipationChecked {
+    room: SignalingRoomId,
+    poll: PollId,
+}
+
 pub(super) async fn del_results(
     redis_conn: &mut RedisConnection,
     room: SignalingRoomId,
     poll_id: PollId,
 ) -> Result<()> {
-    redis_conn
-        .del(PollResults {
+    redis::cmd("DEL")
+        .arg(PollResults {
+            room,
+            poll: poll_id,
+        })
+        .arg(PollNotified {
             room,
             poll: poll_id,
         })
+        .arg(PollVoters {
+            room,
+            poll: poll_id,
+        })
+        .arg(PollParticipationChecked {
+            room,
+            poll: poll_id,
+        })
+        .query_async(redis_conn)
         .await
         .context("failed to delete results")
 }
 
-pub(super) async fn vote(
+/// Mark the poll as notified, returns true if it hasn't been marked before
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn set_notified(
     redis_conn: &mut RedisConnection,
     room: SignalingRoomId,
     poll_id: PollId,
-    choice_id: ChoiceId,
-) -> Result<()> {
+) -> Result<bool> {
     redis_conn
-        .zincr(
-            PollResults {
+        .set_nx(
+            PollNotified {
                 room,
                 poll: poll_id,
             },
-            choice_id.0,
-            1,
+            true,
         )
         .await
-        .context("failed to cast vote")
+        .context("failed to set poll notified")
+}
+
+/// Mark the participation of the expired poll as checked, returns true if it hasn't been marked before
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn set_participation_checked(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    poll_id: PollId,
+) -> Result<bool> {
+    redis_conn
+        .set_nx(
+            PollParticipationChecked {
+                room,
+                poll: poll_id,
+            },
+            true,
+        )
+        .await
+        .context("failed to set poll participation checked")
+}
+
+/// Get the number of participants which voted in the poll
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn voter_count(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    poll_id: PollId,
+) -> Result<usize> {
+    redis_conn
+        .hlen(PollVoters {
+            room,
+            poll: poll_id,
+        })
+        .await
+        .context("failed to get voter count")
+}
+
+/// Records the voter and increments the count of the choice, unless the participant already voted
+///
+/// Returns `replayed` if the previous vote was cast with the same non-empty idempotency key.
+const VOTE: &str = r#"
+local previous = redis.call("HGET", KEYS[1], ARGV[1])
+if previous then
+    if ARGV[3] ~= "" and previous == ARGV[3] then
+        return "replayed"
+    end
+    return "voted_already"
+end
+redis.call("HSET", KEYS[1], ARGV[1], ARGV[3])
+redis.call("ZINCRBY", KEYS[2], 1, ARGV[2])
+return "voted"
+"#;
+
+/// Outcome of [`vote`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum VoteResult {
+    /// The vote has been counted
+    Voted,
+    /// The vote has already been counted with the same idempotency key
+    Replayed,
+    /// The participant already voted before
+    VotedAlready,
+}
+
+#[tracing::instrument(level = "debug", skip(redis_conn))]
+pub(super) async fn vote(
+    redis_conn: &mut RedisConnection,
+    room: SignalingRoomId,
+    poll_id: PollId,
+    participant: ParticipantId,
+    choice_id: ChoiceId,
+    idempotency_key: Option<&str>,
+) -> Result<VoteResult> {
+    let result: String = redis::Script::new(VOTE)
+        .key(PollVoters {
+            room,
+            poll: poll_id,
+        })
+        .key(PollResults {
+            room,
+            poll: poll_id,
+        })
+        .arg(participant)
+        .arg(choice_id.0)
+        .arg(idempotency_key.unwrap_or_default())
+        .invoke_async(redis_conn)
+        .await
+        .context("failed to cast vote")?;
+
+    match result.as_str() {
+        "voted" => Ok(VoteResult::Voted),
+        "replayed" => Ok(VoteResult::Replayed),
+        "voted_already" => Ok(VoteResult::VotedAlready),
+        _ => bail!("got invalid result from vote script: {:?}", result),
+    }
 }
 
 async fn results(
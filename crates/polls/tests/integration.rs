@@ -8,13 +8,21 @@ use pretty_assertions::assert_eq;
 use serial_test::serial;
 use std::time::Duration;
 use test_util::*;
+use types::signaling::ErrorEnvelope;
 
-async fn start_poll(module_tester: &mut ModuleTester<Polls>, live_poll: bool) -> outgoing::Started {
+async fn start_poll(
+    module_tester: &mut ModuleTester<Polls>,
+    live_poll: bool,
+    auto_extend: Option<AutoExtend>,
+) -> outgoing::Started {
     let start = incoming::Message::Start(incoming::Start {
         topic: "polling".into(),
         live: live_poll,
         choices: vec!["yes".into(), "no".into()],
         duration: Duration::from_secs(2),
+        auto_extend,
+        publish_results: false,
+        notify_results: false,
     });
 
     module_tester
@@ -39,6 +47,7 @@ async fn start_poll(module_tester: &mut ModuleTester<Polls>, live_poll: bool) ->
         live,
         choices,
         duration,
+        auto_extend: started_auto_extend,
     })) = started1
     {
         assert_eq!(topic, "polling");
@@ -57,6 +66,7 @@ async fn start_poll(module_tester: &mut ModuleTester<Polls>, live_poll: bool) ->
             ]
         );
         assert_eq!(duration.as_millis(), 2000);
+        assert_eq!(started_auto_extend, auto_extend);
 
         outgoing::Started {
             id,
@@ -64,6 +74,7 @@ async fn start_poll(module_tester: &mut ModuleTester<Polls>, live_poll: bool) ->
             live,
             choices,
             duration,
+            auto_extend,
         }
     } else {
         panic!("unexpected {started1:?}")
@@ -75,9 +86,9 @@ async fn start_poll(module_tester: &mut ModuleTester<Polls>, live_poll: bool) ->
 async fn full_poll_with_2sec_duration() {
     let test_ctx = TestContext::new().await;
 
-    let (mut module_tester, _user1, _user2) = common::setup_users::<Polls>(&test_ctx, ()).await;
+    let (mut module_tester, _user1, _user2) = common::setup_users::<Polls>(&test_ctx, None).await;
 
-    let started = start_poll(&mut module_tester, true).await;
+    let started = start_poll(&mut module_tester, true, None).await;
 
     // User 1 vote yes
     module_tester
@@ -86,6 +97,7 @@ async fn full_poll_with_2sec_duration() {
             incoming::Message::Vote(incoming::Vote {
                 poll_id: started.id,
                 choice_id: ChoiceId(0),
+                idempotency_key: None,
             }),
         )
         .unwrap();
@@ -133,6 +145,7 @@ async fn full_poll_with_2sec_duration() {
             incoming::Message::Vote(incoming::Vote {
                 poll_id: started.id,
                 choice_id: ChoiceId(1),
+                idempotency_key: None,
             }),
         )
         .unwrap();
@@ -180,6 +193,7 @@ async fn full_poll_with_2sec_duration() {
             incoming::Message::Vote(incoming::Vote {
                 poll_id: started.id,
                 choice_id: ChoiceId(0),
+                idempotency_key: None,
             }),
         )
         .unwrap();
@@ -189,8 +203,10 @@ async fn full_poll_with_2sec_duration() {
         .await
         .unwrap();
 
-    if let WsMessageOutgoing::Module(outgoing::Message::Error(outgoing::Error::VotedAlready)) =
-        error
+    if let WsMessageOutgoing::Module(outgoing::Message::Error(ErrorEnvelope {
+        error: outgoing::Error::VotedAlready,
+        ..
+    })) = error
     {
         // OK
     } else {
@@ -237,3 +253,212 @@ async fn full_poll_with_2sec_duration() {
 
     module_tester.shutdown().await.unwrap()
 }
+
+#[actix_rt::test]
+#[serial]
+async fn poll_extended_once_with_low_participation() {
+    let test_ctx = TestContext::new().await;
+
+    let (mut module_tester, _user1, _user2) = common::setup_users::<Polls>(&test_ctx, None).await;
+
+    let started = start_poll(
+        &mut module_tester,
+        false,
+        Some(AutoExtend {
+            min_participation: 100,
+            duration: Duration::from_secs(2),
+        }),
+    )
+    .await;
+
+    // Only user 1 votes, the participation of 50% is too low
+    module_tester
+        .send_ws_message(
+            &USER_1.participant_id,
+            incoming::Message::Vote(incoming::Vote {
+                poll_id: started.id,
+                choice_id: ChoiceId(0),
+                idempotency_key: None,
+            }),
+        )
+        .unwrap();
+
+    let extended1 = module_tester
+        .receive_ws_message_override_timeout(&USER_1.participant_id, Duration::from_secs(3))
+        .await
+        .unwrap();
+
+    let extended2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(extended1, extended2);
+
+    if let WsMessageOutgoing::Module(outgoing::Message::Extended(outgoing::Extended {
+        id, ..
+    })) = extended1
+    {
+        assert_eq!(id, started.id);
+    } else {
+        panic!("unexpected {extended1:?}")
+    }
+
+    // The poll is only extended once and finishes even though the participation is still too low
+    let done1 = module_tester
+        .receive_ws_message_override_timeout(&USER_1.participant_id, Duration::from_secs(3))
+        .await
+        .unwrap();
+
+    if let WsMessageOutgoing::Module(outgoing::Message::Done(outgoing::Results { id, results })) =
+        &done1
+    {
+        assert_eq!(*id, started.id);
+        assert_eq!(
+            results,
+            &[
+                outgoing::Item {
+                    id: ChoiceId(0),
+                    count: 1,
+                },
+                outgoing::Item {
+                    id: ChoiceId(1),
+                    count: 0,
+                }
+            ]
+        );
+    } else {
+        panic!("unexpected {done1:?}")
+    }
+
+    let done2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(done1, done2);
+
+    module_tester.shutdown().await.unwrap()
+}
+
+#[actix_rt::test]
+#[serial]
+async fn replayed_vote_is_counted_once() {
+    let test_ctx = TestContext::new().await;
+
+    let (mut module_tester, _user1, _user2) = common::setup_users::<Polls>(&test_ctx, None).await;
+
+    let started = start_poll(&mut module_tester, true, None).await;
+
+    let vote = || {
+        incoming::Message::Vote(incoming::Vote {
+            poll_id: started.id,
+            choice_id: ChoiceId(0),
+            idempotency_key: Some("f3b2c1".into()),
+        })
+    };
+
+    // User 1 votes yes
+    module_tester
+        .send_ws_message(&USER_1.participant_id, vote())
+        .unwrap();
+
+    let update1 = module_tester
+        .receive_ws_message(&USER_1.participant_id)
+        .await
+        .unwrap();
+
+    let update2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(update1, update2);
+
+    // User 1 retries the vote, e.g. after a reconnect, which is neither counted nor answered with an error
+    module_tester
+        .send_ws_message(&USER_1.participant_id, vote())
+        .unwrap();
+
+    // User 2 votes no, the next update must only contain one vote for each choice
+    module_tester
+        .send_ws_message(
+            &USER_2.participant_id,
+            incoming::Message::Vote(incoming::Vote {
+                poll_id: started.id,
+                choice_id: ChoiceId(1),
+                idempotency_key: None,
+            }),
+        )
+        .unwrap();
+
+    let update1 = module_tester
+        .receive_ws_message(&USER_1.participant_id)
+        .await
+        .unwrap();
+
+    let update2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(update1, update2);
+
+    if let WsMessageOutgoing::Module(outgoing::Message::LiveUpdate(outgoing::Results {
+        id,
+        results,
+    })) = update1
+    {
+        assert_eq!(id, started.id);
+        assert_eq!(
+            results,
+            &[
+                outgoing::Item {
+                    id: ChoiceId(0),
+                    count: 1,
+                },
+                outgoing::Item {
+                    id: ChoiceId(1),
+                    count: 1
+                }
+            ]
+        );
+    } else {
+        panic!("unexpected {update1:?}")
+    }
+
+    let done1 = module_tester
+        .receive_ws_message_override_timeout(&USER_1.participant_id, Duration::from_secs(3))
+        .await
+        .unwrap();
+
+    if let WsMessageOutgoing::Module(outgoing::Message::Done(outgoing::Results { id, results })) =
+        &done1
+    {
+        assert_eq!(*id, started.id);
+        assert_eq!(
+            results,
+            &[
+                outgoing::Item {
+                    id: ChoiceId(0),
+                    count: 1,
+                },
+                outgoing::Item {
+                    id: ChoiceId(1),
+                    count: 1,
+                }
+            ]
+        );
+    } else {
+        panic!("unexpected {done1:?}")
+    }
+
+    let done2 = module_tester
+        .receive_ws_message(&USER_2.participant_id)
+        .await
+        .unwrap();
+
+    assert_eq!(done1, done2);
+
+    module_tester.shutdown().await.unwrap()
+}
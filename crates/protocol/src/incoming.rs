@@ -2,10 +2,11 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 use types::core::ParticipantId;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "action")]
 pub enum Message {
     SelectWriter(ParticipantSelection),
@@ -15,7 +16,7 @@ pub enum Message {
 }
 
 /// Give a list of participants write access to the protocol
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ParticipantSelection {
     /// The targeted participants
@@ -4,6 +4,7 @@
 
 use crate::storage::init::InitState;
 use anyhow::Result;
+use controller::i18n::{Locale, Message};
 use controller::prelude::anyhow::Context;
 use controller::prelude::chrono::{Duration, Utc};
 use controller::prelude::control::storage::{get_all_participants, get_attribute};
@@ -48,14 +49,23 @@ struct Protocol {
     etherpad: EtherpadClient,
     participant_id: ParticipantId,
     room_id: SignalingRoomId,
+    /// Locale of the generated PDFs
+    locale: Locale,
     db: Arc<Db>,
     storage: Arc<ObjectStorage>,
 }
 
+#[derive(Clone)]
+pub struct ProtocolParams {
+    etherpad: controller_shared::settings::Etherpad,
+    /// Language of the generated documents if the room has no locale set
+    default_language: String,
+}
+
 #[async_trait::async_trait(?Send)]
 impl SignalingModule for Protocol {
     const NAMESPACE: &'static str = "protocol";
-    type Params = controller_shared::settings::Etherpad;
+    type Params = ProtocolParams;
     type Incoming = incoming::Message;
     type Outgoing = outgoing::Message;
     type RabbitMqMessage = rabbitmq::Event;
@@ -68,12 +78,14 @@ impl SignalingModule for Protocol {
         params: &Self::Params,
         _protocol: &'static str,
     ) -> Result<Option<Self>> {
-        let etherpad = EtherpadClient::new(params.url.clone(), params.api_key.clone());
+        let etherpad =
+            EtherpadClient::new(params.etherpad.url.clone(), params.etherpad.api_key.clone());
 
         Ok(Some(Self {
             etherpad,
             participant_id: ctx.participant_id(),
             room_id: ctx.room_id(),
+            locale: Locale::resolve(ctx.room().locale.as_deref(), &params.default_language),
             db: ctx.db().clone(),
             storage: ctx.storage().clone(),
         }))
@@ -144,7 +156,7 @@ impl SignalingModule for Protocol {
 
     async fn on_destroy(self, mut ctx: DestroyContext<'_>) {
         if ctx.destroy_room() {
-            if let Err(e) = self.cleanup_etherpad(ctx.redis_conn()).await {
+            if let Err(e) = cleanup_etherpad(&self.etherpad, ctx.redis_conn(), self.room_id).await {
                 log::error!(
                     "Failed to cleanup etherpad for room {} in redis: {}",
                     self.room_id,
@@ -161,6 +173,42 @@ impl SignalingModule for Protocol {
             }
         }
     }
+
+    async fn cleanup_empty_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        let etherpad =
+            EtherpadClient::new(params.etherpad.url.clone(), params.etherpad.api_key.clone());
+
+        cleanup_etherpad(&etherpad, redis_conn, room).await
+    }
+
+    async fn prepare_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        if storage::init::try_start_init(redis_conn, room)
+            .await?
+            .is_some()
+        {
+            // Already initialized or currently being initialized
+            return Ok(());
+        }
+
+        let etherpad =
+            EtherpadClient::new(params.etherpad.url.clone(), params.etherpad.api_key.clone());
+
+        if let Err(e) = init_etherpad(&etherpad, redis_conn, room).await {
+            storage::init::del(redis_conn, room).await?;
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
 }
 
 impl Protocol {
@@ -173,7 +221,7 @@ impl Protocol {
             incoming::Message::SelectWriter(selection) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
 
                     return Ok(());
@@ -181,7 +229,7 @@ impl Protocol {
 
                 if !self.verify_selection(ctx.redis_conn(), &selection).await? {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidParticipantSelection,
+                        outgoing::Error::InvalidParticipantSelection.into(),
                     ));
                 }
 
@@ -199,7 +247,7 @@ impl Protocol {
                             storage::init::InitState::Initializing => {
                                 // Some other instance is currently initializing the etherpad
                                 ctx.ws_send(outgoing::Message::Error(
-                                    outgoing::Error::CurrentlyInitializing,
+                                    outgoing::Error::CurrentlyInitializing.into(),
                                 ));
                                 return Ok(());
                             }
@@ -208,13 +256,15 @@ impl Protocol {
                     }
                     None => {
                         // No init state was set before -> Initialize the etherpad in this module instance
-                        if let Err(e) = self.init_etherpad(redis_conn).await {
+                        if let Err(e) =
+                            init_etherpad(&self.etherpad, redis_conn, self.room_id).await
+                        {
                             log::error!("Failed to init etherpad for room {}, {}", self.room_id, e);
 
                             storage::init::del(redis_conn, self.room_id).await?;
 
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::FailedInitialization,
+                                outgoing::Error::FailedInitialization.into(),
                             ));
 
                             return Ok(());
@@ -247,7 +297,7 @@ impl Protocol {
             incoming::Message::DeselectWriter(selection) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
 
                     return Ok(());
@@ -257,7 +307,7 @@ impl Protocol {
                     Some(state) => match state {
                         InitState::Initializing => {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::CurrentlyInitializing,
+                                outgoing::Error::CurrentlyInitializing.into(),
                             ));
 
                             return Ok(());
@@ -265,7 +315,9 @@ impl Protocol {
                         InitState::Initialized => (),
                     },
                     None => {
-                        ctx.ws_send(outgoing::Message::Error(outgoing::Error::NotInitialized));
+                        ctx.ws_send(outgoing::Message::Error(
+                            outgoing::Error::NotInitialized.into(),
+                        ));
 
                         return Ok(());
                     }
@@ -273,7 +325,7 @@ impl Protocol {
 
                 if !self.verify_selection(ctx.redis_conn(), &selection).await? {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InvalidParticipantSelection,
+                        outgoing::Error::InvalidParticipantSelection.into(),
                     ));
 
                     return Ok(());
@@ -307,7 +359,7 @@ impl Protocol {
             incoming::Message::GeneratePdf => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -316,7 +368,9 @@ impl Protocol {
                     storage::init::get(ctx.redis_conn(), self.room_id).await?,
                     Some(InitState::Initialized)
                 ) {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::NotInitialized));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::NotInitialized.into(),
+                    ));
                     return Ok(());
                 }
 
@@ -330,17 +384,33 @@ impl Protocol {
 
                     let pad_id = format!("{group_id}${PAD_NAME}");
 
+                    // Decisions and action items collected since the last export are appended to the pad
+                    if let Some(text) = action_items::take_protocol_text(
+                        ctx.redis_conn(),
+                        self.room_id,
+                        self.locale,
+                    )
+                    .await?
+                    {
+                        self.etherpad.append_text(&pad_id, &text).await?;
+                    }
+
                     let data = self
                         .etherpad
                         .download_pdf(&session_info.session_id, &pad_id)
                         .await?
                         .map_err(Into::into);
 
-                    let filename = format!("protocol_{}.pdf", ctx.timestamp().to_rfc3339());
+                    let filename = format!(
+                        "{}_{}.pdf",
+                        self.locale.message(Message::ProtocolFilename),
+                        ctx.timestamp().to_rfc3339()
+                    );
                     let asset_id = save_asset(
                         &self.storage,
                         self.db.clone(),
                         self.room_id.room_id(),
+                        self.room_id.breakout_room_id(),
                         Some(Self::NAMESPACE),
                         &filename,
                         "protocol_pdf",
@@ -354,7 +424,9 @@ impl Protocol {
                         rabbitmq::Event::PdfAsset(PdfAsset { filename, asset_id }),
                     );
                 } else {
-                    ctx.ws_send(outgoing::Message::Error(outgoing::Error::NotInitialized));
+                    ctx.ws_send(outgoing::Message::Error(
+                        outgoing::Error::NotInitialized.into(),
+                    ));
                     return Ok(());
                 }
             }
@@ -390,25 +462,6 @@ impl Protocol {
         Ok(())
     }
 
-    /// Initializes the etherpad-group and -pad for this room
-    async fn init_etherpad(&self, redis_conn: &mut RedisConnection) -> Result<()> {
-        let group_id = self
-            .etherpad
-            .create_group_for(self.room_id.to_string())
-            .await?;
-
-        self.etherpad
-            .create_group_pad(&group_id, PAD_NAME, None)
-            .await?;
-
-        storage::group::set(redis_conn, self.room_id, &group_id).await?;
-
-        // flag this room as initialized
-        storage::init::set_initialized(redis_conn, self.room_id).await?;
-
-        Ok(())
-    }
-
     /// Creates a new etherpad author for the participant
     ///
     /// Returns the generated author id
@@ -546,37 +599,60 @@ impl Protocol {
             .iter()
             .all(|target| room_participants.contains(target)))
     }
+}
 
-    /// Removes the room related pad and group from etherpad
-    async fn cleanup_etherpad(&self, redis_conn: &mut RedisConnection) -> Result<()> {
-        let init_state = storage::init::get(redis_conn, self.room_id).await?;
+/// Initializes the etherpad-group and -pad for the room
+async fn init_etherpad(
+    etherpad: &EtherpadClient,
+    redis_conn: &mut RedisConnection,
+    room_id: SignalingRoomId,
+) -> Result<()> {
+    let group_id = etherpad.create_group_for(room_id.to_string()).await?;
 
-        if init_state.is_none() {
-            // Nothing to cleanup
-            return Ok(());
-        }
+    etherpad.create_group_pad(&group_id, PAD_NAME, None).await?;
 
-        let group_id = storage::group::get(redis_conn, self.room_id)
-            .await?
-            .unwrap();
+    storage::group::set(redis_conn, room_id, &group_id).await?;
 
-        let pad_id = format!("{group_id}${PAD_NAME}");
+    // flag this room as initialized
+    storage::init::set_initialized(redis_conn, room_id).await?;
 
-        self.etherpad.delete_pad(&pad_id).await?;
+    Ok(())
+}
 
-        // invalidate all sessions by deleting the group
-        self.etherpad.delete_group(&group_id).await?;
+/// Removes the room related pad and group from etherpad
+async fn cleanup_etherpad(
+    etherpad: &EtherpadClient,
+    redis_conn: &mut RedisConnection,
+    room_id: SignalingRoomId,
+) -> Result<()> {
+    let init_state = storage::init::get(redis_conn, room_id).await?;
 
-        Ok(())
+    if init_state.is_none() {
+        // Nothing to cleanup
+        return Ok(());
     }
+
+    let group_id = storage::group::get(redis_conn, room_id).await?.unwrap();
+
+    let pad_id = format!("{group_id}${PAD_NAME}");
+
+    etherpad.delete_pad(&pad_id).await?;
+
+    // invalidate all sessions by deleting the group
+    etherpad.delete_group(&group_id).await?;
+
+    Ok(())
 }
 
 pub fn register(controller: &mut controller::Controller) {
-    let etherpad = controller.shared_settings.load_full().etherpad.clone();
+    let settings = controller.shared_settings.load_full();
 
-    match etherpad {
+    match settings.etherpad.clone() {
         Some(etherpad) => {
-            controller.signaling.add_module::<Protocol>(etherpad);
+            controller.signaling.add_module::<Protocol>(ProtocolParams {
+                etherpad,
+                default_language: settings.defaults.user_language.clone(),
+            });
         }
         None => {
             log::warn!("Skipping the Protocol module as no etherpad is specified in the config")
@@ -2,10 +2,12 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::AssetId;
+use types::signaling::{ErrorEnvelope, ModuleError};
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "message")]
 pub enum Message {
     /// An access url containing a write session
@@ -13,22 +15,22 @@ pub enum Message {
     /// An access url containing a readonly session
     ReadUrl(AccessUrl),
     PdfAsset(PdfAsset),
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct AccessUrl {
     pub url: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct PdfAsset {
     pub filename: String,
     pub asset_id: AssetId,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "error")]
 pub enum Error {
     /// The requesting user has insufficient permissions for the operation
@@ -43,6 +45,27 @@ pub enum Error {
     NotInitialized,
 }
 
+impl ModuleError for Error {
+    const MODULE: &'static str = "protocol";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::InvalidParticipantSelection => "The selection contains invalid participant ids",
+            Self::CurrentlyInitializing => "The protocol is currently being initialized",
+            Self::FailedInitialization => "The protocol could not be initialized",
+            Self::NotInitialized => "The protocol has not been initialized yet",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::CurrentlyInitializing | Self::FailedInitialization
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -87,9 +110,15 @@ mod test {
 
     #[test]
     fn insufficient_permissions() {
-        let expected = json!({"message": "error", "error": "insufficient_permissions"});
+        let expected = json!({
+            "message": "error",
+            "module": "protocol",
+            "error": "insufficient_permissions",
+            "text": "Insufficient permissions for the operation",
+            "retryable": false
+        });
 
-        let message = Message::Error(Error::InsufficientPermissions);
+        let message = Message::Error(ErrorEnvelope::from(Error::InsufficientPermissions));
 
         let actual = serde_json::to_value(&message).unwrap();
 
@@ -98,9 +127,15 @@ mod test {
 
     #[test]
     fn currently_initialization() {
-        let expected = json!({"message": "error", "error": "failed_initialization"});
+        let expected = json!({
+            "message": "error",
+            "module": "protocol",
+            "error": "failed_initialization",
+            "text": "The protocol could not be initialized",
+            "retryable": true
+        });
 
-        let message = Message::Error(Error::FailedInitialization);
+        let message = Message::Error(ErrorEnvelope::from(Error::FailedInitialization));
 
         let actual = serde_json::to_value(&message).unwrap();
 
@@ -109,9 +144,15 @@ mod test {
 
     #[test]
     fn failed_initializing() {
-        let expected = json!({"message": "error", "error": "currently_initializing"});
+        let expected = json!({
+            "message": "error",
+            "module": "protocol",
+            "error": "currently_initializing",
+            "text": "The protocol is currently being initialized",
+            "retryable": true
+        });
 
-        let message = Message::Error(Error::CurrentlyInitializing);
+        let message = Message::Error(ErrorEnvelope::from(Error::CurrentlyInitializing));
 
         let actual = serde_json::to_value(&message).unwrap();
 
@@ -120,9 +161,15 @@ mod test {
 
     #[test]
     fn invalid_participant_selection() {
-        let expected = json!({"message": "error", "error": "invalid_participant_selection"});
+        let expected = json!({
+            "message": "error",
+            "module": "protocol",
+            "error": "invalid_participant_selection",
+            "text": "The selection contains invalid participant ids",
+            "retryable": false
+        });
 
-        let message = Message::Error(Error::InvalidParticipantSelection);
+        let message = Message::Error(ErrorEnvelope::from(Error::InvalidParticipantSelection));
 
         let actual = serde_json::to_value(&message).unwrap();
 
@@ -0,0 +1,275 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! End-to-end test harness for signaling modules
+//!
+//! In contrast to the [`ModuleTester`](controller::prelude::ModuleTester), the [`TestHarness`] runs the actual
+//! signaling endpoint of the controller and connects simulated participants via websockets. This covers the whole
+//! path from the websocket over the runner and RabbitMQ to the modules and back.
+//!
+//! The harness requires running Postgres, redis and RabbitMQ instances. They are configured with the environment
+//! variables `POSTGRES_BASE_URL`, `REDIS_ADDR` and `AMQP_ADDR`. The `docker-compose.yml` next to this crate starts
+//! them with matching defaults.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use controller::prelude::*;
+//! # use k3k_test_harness::TestHarness;
+//! # async fn scenario() -> anyhow::Result<()> {
+//! let mut modules = SignalingModules::default();
+//! modules.add_module::<moderation::ModerationModule>(());
+//!
+//! let harness = TestHarness::start(modules).await?;
+//!
+//! let mut alice = harness.join(1, "Alice").await?;
+//! let bob = harness.join(2, "Bob").await?;
+//!
+//! let joined = alice.expect_message("control", "joined").await?;
+//! assert_eq!(joined["id"], bob.id().to_string());
+//!
+//! harness.stop().await;
+//! # Ok(())
+//! # }
+//! ```
+use anyhow::{bail, Context, Result};
+use controller::prelude::*;
+use controller::settings::Settings;
+use controller::testing::SignalingTestServer;
+use db_storage::rooms::Room;
+use db_storage::users::User;
+use futures::{SinkExt, StreamExt};
+use lapin_pool::RabbitMqPool;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use test_util::TestContext;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use types::core::{ParticipantId, UserId};
+
+/// Settings file used for the signaling test server
+const CONFIG_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml");
+
+/// Websocket subprotocol spoken by the signaling endpoint
+const SIGNALING_PROTOCOL: &str = "k3k-signaling-json-v1.0";
+
+/// Time to wait for a websocket message before failing
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running signaling endpoint with a single room participants can join
+pub struct TestHarness {
+    ctx: TestContext,
+    server: SignalingTestServer,
+    owner: User,
+    room: Room,
+    rabbitmq_pool: Arc<RabbitMqPool>,
+}
+
+impl TestHarness {
+    /// Start the signaling endpoint with the given modules
+    ///
+    /// Creates a fresh database and flushes redis. The room is owned by the test user `1`, which therefore joins
+    /// as moderator.
+    pub async fn start(modules: SignalingModules) -> Result<Self> {
+        let ctx = TestContext::new().await;
+
+        let settings = Settings::load(CONFIG_FILE).context("Failed to load harness settings")?;
+
+        let amqp_addr =
+            std::env::var("AMQP_ADDR").unwrap_or_else(|_| "amqp://localhost:5672".to_owned());
+        let rabbitmq_pool = RabbitMqPool::from_config(&amqp_addr, 1, 100);
+
+        let owner = ctx.db_ctx.create_test_user(1, vec![])?;
+        let room = ctx
+            .db_ctx
+            .create_test_room(test_util::ROOM_ID, owner.id, false)?;
+
+        let server = SignalingTestServer::start(
+            settings,
+            ctx.db_ctx.db.clone(),
+            (*ctx.authz).clone(),
+            ctx.redis_conn.clone(),
+            rabbitmq_pool.clone(),
+            ctx.shutdown.clone(),
+            modules,
+        )
+        .await?;
+
+        Ok(Self {
+            ctx,
+            server,
+            owner,
+            room,
+            rabbitmq_pool,
+        })
+    }
+
+    /// Returns the room participants join
+    pub fn room(&self) -> &Room {
+        &self.room
+    }
+
+    /// Connect the test user `n` to the room and join with the given display name
+    ///
+    /// Test user `1` is the owner of the room, every other test user is created on join and can therefore only join
+    /// once. Returns once the `join_success` message has been received.
+    pub async fn join(&self, n: u32, display_name: &str) -> Result<TestParticipant> {
+        let user = if n == 1 {
+            self.owner.id
+        } else {
+            self.ctx.db_ctx.create_test_user(n, vec![])?.id
+        };
+
+        let mut participant = self.connect(Participant::User(user)).await?;
+
+        participant
+            .send(
+                "control",
+                json!({ "action": "join", "display_name": display_name }),
+            )
+            .await?;
+
+        let join_success = participant
+            .expect_message("control", "join_success")
+            .await?;
+
+        participant.id = serde_json::from_value(join_success["id"].clone())
+            .context("join_success is missing the participant id")?;
+        participant.join_success = join_success;
+
+        Ok(participant)
+    }
+
+    /// Connect the participant to the signaling endpoint without joining the room
+    ///
+    /// Allows to test joins which are not successful. The participant id is only known after a successful join and
+    /// is therefore nil.
+    pub async fn connect(&self, participant: Participant<UserId>) -> Result<TestParticipant> {
+        let ticket = self.server.create_ticket(participant, self.room.id).await?;
+
+        let mut request = self.server.signaling_url().into_client_request()?;
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_str(&format!("{SIGNALING_PROTOCOL}, ticket#{}", ticket.as_str()))?,
+        );
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("Failed to connect to the signaling endpoint")?;
+
+        Ok(TestParticipant {
+            id: ParticipantId::nil(),
+            socket,
+            join_success: Value::Null,
+        })
+    }
+
+    /// Stop the signaling endpoint and close all RabbitMQ connections
+    pub async fn stop(self) {
+        self.server.stop().await;
+
+        if let Err(e) = self.rabbitmq_pool.close(0, "test finished").await {
+            log::warn!("Failed to close RabbitMQ connections, {}", e);
+        }
+    }
+}
+
+/// A message received by a [`TestParticipant`]
+#[derive(Debug, Clone)]
+pub struct ReceivedMessage {
+    pub namespace: String,
+    pub payload: Value,
+}
+
+/// A participant connected to the signaling endpoint of a [`TestHarness`]
+pub struct TestParticipant {
+    id: ParticipantId,
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    join_success: Value,
+}
+
+impl TestParticipant {
+    /// Returns the participant id assigned by the controller
+    pub fn id(&self) -> ParticipantId {
+        self.id
+    }
+
+    /// Returns the payload of the `join_success` message
+    pub fn join_success(&self) -> &Value {
+        &self.join_success
+    }
+
+    /// Send a command to the module with the given namespace
+    pub async fn send(&mut self, namespace: &str, payload: Value) -> Result<()> {
+        let command = json!({ "namespace": namespace, "payload": payload });
+
+        self.socket
+            .send(Message::Text(command.to_string()))
+            .await
+            .context("Failed to send websocket message")
+    }
+
+    /// Receive the next signaling message
+    ///
+    /// Fails if no message has been received within 5 seconds or the websocket has been closed.
+    pub async fn receive(&mut self) -> Result<ReceivedMessage> {
+        loop {
+            let message = tokio::time::timeout(RECEIVE_TIMEOUT, self.socket.next())
+                .await
+                .context("Timed out waiting for a websocket message")?;
+
+            let text = match message {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Close(frame))) => bail!("Websocket closed, {:?}", frame),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e).context("Failed to receive websocket message"),
+                None => bail!("Websocket stream ended"),
+            };
+
+            let mut event: Value = serde_json::from_str(&text).context("Received invalid JSON")?;
+
+            let namespace = match event["namespace"].as_str() {
+                Some(namespace) => namespace.to_owned(),
+                None => bail!("Received message without namespace: {}", text),
+            };
+
+            return Ok(ReceivedMessage {
+                namespace,
+                payload: event["payload"].take(),
+            });
+        }
+    }
+
+    /// Receive the next message and assert that it is the given message of the given namespace
+    ///
+    /// Returns the payload of the message.
+    pub async fn expect_message(&mut self, namespace: &str, message: &str) -> Result<Value> {
+        let received = self.receive().await?;
+
+        if received.namespace != namespace || received.payload["message"] != message {
+            bail!(
+                "Expected {}/{}, got {}/{}",
+                namespace,
+                message,
+                received.namespace,
+                received.payload
+            );
+        }
+
+        Ok(received.payload)
+    }
+
+    /// Close the websocket connection, which makes the participant leave the room
+    pub async fn leave(mut self) -> Result<()> {
+        self.socket
+            .close(None)
+            .await
+            .context("Failed to close websocket")
+    }
+}
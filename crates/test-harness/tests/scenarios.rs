@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use controller::prelude::*;
+use k3k_chat::Chat;
+use k3k_polls::Polls;
+use k3k_test_harness::{TestHarness, TestParticipant};
+use k3k_timer::Timer;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use serial_test::serial;
+
+async fn start_harness() -> TestHarness {
+    let mut modules = SignalingModules::default();
+    modules.add_module::<Chat>(Default::default());
+    modules.add_module::<Polls>(None);
+    modules.add_module::<Timer>(());
+
+    TestHarness::start(modules).await.unwrap()
+}
+
+/// Join the room with a moderator and a regular user
+async fn join_both(harness: &TestHarness) -> (TestParticipant, TestParticipant) {
+    let mut moderator = harness.join(1, "Moderator").await.unwrap();
+    assert_eq!(moderator.join_success()["role"], "moderator");
+    assert_eq!(moderator.join_success()["participants"], json!([]));
+
+    let user = harness.join(2, "User").await.unwrap();
+    assert_eq!(user.join_success()["role"], "user");
+    assert_eq!(
+        user.join_success()["participants"][0]["id"],
+        moderator.id().to_string()
+    );
+
+    let joined = moderator.expect_message("control", "joined").await.unwrap();
+    assert_eq!(joined["id"], user.id().to_string());
+
+    (moderator, user)
+}
+
+#[actix_rt::test]
+#[serial]
+async fn join_and_leave() {
+    let harness = start_harness().await;
+    let (mut moderator, user) = join_both(&harness).await;
+
+    let user_id = user.id();
+    user.leave().await.unwrap();
+
+    let left = moderator.expect_message("control", "left").await.unwrap();
+    assert_eq!(left["id"], user_id.to_string());
+
+    harness.stop().await;
+}
+
+#[actix_rt::test]
+#[serial]
+async fn chat_message() {
+    let harness = start_harness().await;
+    let (mut moderator, mut user) = join_both(&harness).await;
+
+    let user_id = user.id().to_string();
+
+    user.send(
+        "chat",
+        json!({ "action": "send_message", "scope": "global", "content": "Hello all!" }),
+    )
+    .await
+    .unwrap();
+
+    for participant in [&mut moderator, &mut user] {
+        let message_sent = participant
+            .expect_message("chat", "message_sent")
+            .await
+            .unwrap();
+
+        assert_eq!(message_sent["source"], user_id);
+        assert_eq!(message_sent["content"], "Hello all!");
+        assert_eq!(message_sent["scope"], "global");
+    }
+
+    harness.stop().await;
+}
+
+#[actix_rt::test]
+#[serial]
+async fn poll_vote() {
+    let harness = start_harness().await;
+    let (mut moderator, mut user) = join_both(&harness).await;
+
+    moderator
+        .send(
+            "polls",
+            json!({
+                "action": "start",
+                "topic": "Lunch?",
+                "live": true,
+                "choices": ["yes", "no"],
+                "duration": 30
+            }),
+        )
+        .await
+        .unwrap();
+
+    let started = moderator.expect_message("polls", "started").await.unwrap();
+    assert_eq!(
+        user.expect_message("polls", "started").await.unwrap(),
+        started
+    );
+
+    let poll_id = started["id"].clone();
+
+    user.send(
+        "polls",
+        json!({ "action": "vote", "poll_id": poll_id, "choice_id": 0 }),
+    )
+    .await
+    .unwrap();
+
+    for participant in [&mut moderator, &mut user] {
+        let live_update = participant
+            .expect_message("polls", "live_update")
+            .await
+            .unwrap();
+
+        assert_eq!(live_update["id"], poll_id);
+        assert_eq!(
+            live_update["results"],
+            json!([{ "id": 0, "count": 1 }, { "id": 1, "count": 0 }])
+        );
+    }
+
+    // Voting twice is rejected
+    user.send(
+        "polls",
+        json!({ "action": "vote", "poll_id": poll_id, "choice_id": 1 }),
+    )
+    .await
+    .unwrap();
+
+    let error = user.expect_message("polls", "error").await.unwrap();
+    assert_eq!(error["error"], "voted_already");
+
+    harness.stop().await;
+}
+
+#[actix_rt::test]
+#[serial]
+async fn timer_start_stop() {
+    let harness = start_harness().await;
+    let (mut moderator, mut user) = join_both(&harness).await;
+
+    moderator
+        .send(
+            "timer",
+            json!({ "action": "start", "kind": "stopwatch", "title": "Break" }),
+        )
+        .await
+        .unwrap();
+
+    let started = moderator.expect_message("timer", "started").await.unwrap();
+    assert_eq!(started["kind"], "stopwatch");
+    assert_eq!(started["title"], "Break");
+    assert_eq!(
+        user.expect_message("timer", "started").await.unwrap(),
+        started
+    );
+
+    // Only moderators may stop the timer
+    user.send(
+        "timer",
+        json!({ "action": "stop", "timer_id": started["timer_id"] }),
+    )
+    .await
+    .unwrap();
+
+    let error = user.expect_message("timer", "error").await.unwrap();
+    assert_eq!(error["error"], "insufficient_permissions");
+
+    let moderator_id = moderator.id().to_string();
+
+    moderator
+        .send(
+            "timer",
+            json!({ "action": "stop", "timer_id": started["timer_id"] }),
+        )
+        .await
+        .unwrap();
+
+    for participant in [&mut moderator, &mut user] {
+        let stopped = participant
+            .expect_message("timer", "stopped")
+            .await
+            .unwrap();
+
+        assert_eq!(stopped["timer_id"], started["timer_id"]);
+        assert_eq!(stopped["kind"], "by_moderator");
+        assert_eq!(stopped["participant_id"], moderator_id);
+    }
+
+    harness.stop().await;
+}
+
+#[actix_rt::test]
+#[serial]
+async fn locked_room() {
+    let mut modules = SignalingModules::default();
+    modules.add_module::<moderation::ModerationModule>(());
+
+    let harness = TestHarness::start(modules).await.unwrap();
+    let (mut moderator, _user) = join_both(&harness).await;
+
+    moderator
+        .send("moderation", json!({ "action": "lock_room" }))
+        .await
+        .unwrap();
+
+    let locked = moderator
+        .expect_message("moderation", "room_locked")
+        .await
+        .unwrap();
+    assert_eq!(locked["issued_by"], moderator.id().to_string());
+
+    // New participants cannot join the locked room
+    let mut guest = harness.connect(Participant::Guest).await.unwrap();
+    guest
+        .send(
+            "control",
+            json!({ "action": "join", "display_name": "Guest" }),
+        )
+        .await
+        .unwrap();
+
+    let blocked = guest
+        .expect_message("control", "join_blocked")
+        .await
+        .unwrap();
+    assert_eq!(blocked["reason"], "room_locked");
+
+    // Moderators get back into the room without resumption
+    moderator.leave().await.unwrap();
+
+    let moderator = harness.join(1, "Moderator").await.unwrap();
+    assert_eq!(moderator.join_success()["role"], "moderator");
+
+    harness.stop().await;
+}
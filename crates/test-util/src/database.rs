@@ -116,6 +116,9 @@ impl DatabaseContext {
             password: None,
             waiting_room,
             tenant_id: tenant.id,
+            locale: None,
+            region: None,
+            webinar_mode: false,
         };
 
         let room = new_room.insert(&mut conn)?;
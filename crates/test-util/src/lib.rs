@@ -12,7 +12,7 @@ pub use common::{TestContext, TestUser, ROOM_ID, USERS, USER_1, USER_2};
 #[cfg(feature = "controller")]
 pub mod common;
 
-#[cfg(feature = "controller")]
+#[cfg(feature = "redis")]
 pub mod redis;
 
 #[cfg(feature = "database")]
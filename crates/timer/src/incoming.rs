@@ -3,10 +3,11 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::TimerId;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 /// Incoming websocket messages
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "action")]
 pub enum Message {
     /// Start a new timer
@@ -18,7 +19,7 @@ pub enum Message {
 }
 
 /// The different timer variations
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum Kind {
     /// The timer continues to run until a moderator stops it.
@@ -28,7 +29,7 @@ pub enum Kind {
 }
 
 /// Start a new timer
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Start {
     /// The timer kind
     #[serde(flatten)]
@@ -43,7 +44,7 @@ pub struct Start {
 }
 
 /// Stop a running timer
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Stop {
     /// The timer id
     pub timer_id: TimerId,
@@ -52,7 +53,7 @@ pub struct Stop {
 }
 
 /// Update the ready status
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct UpdateReadyStatus {
     /// The timer id
     pub timer_id: TimerId,
@@ -14,10 +14,12 @@ use controller::prelude::tokio::time::sleep;
 use controller::prelude::uuid::Uuid;
 use controller::prelude::Event;
 use controller::prelude::{
-    async_trait, control, InitContext, ModuleContext, Role, SignalingModule, SignalingRoomId,
+    async_trait, control, recording, InitContext, ModuleContext, Participant, Role,
+    SignalingModule, SignalingRoomId,
 };
 use outgoing::StopKind;
 use redis_args::ToRedisArgs;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt;
@@ -31,7 +33,9 @@ pub mod outgoing;
 pub mod rabbitmq;
 mod storage;
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToRedisArgs)]
+#[derive(
+    Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToRedisArgs, JsonSchema,
+)]
 #[to_redis_args(fmt)]
 pub struct TimerId(pub Uuid);
 
@@ -68,6 +72,7 @@ pub struct ExpiredEvent {
 pub struct Timer {
     pub room_id: SignalingRoomId,
     pub participant_id: ParticipantId,
+    i_am_the_recorder: bool,
 }
 
 #[async_trait::async_trait(?Send)]
@@ -96,6 +101,7 @@ impl SignalingModule for Timer {
         Ok(Some(Self {
             room_id: ctx.room_id(),
             participant_id: ctx.participant_id(),
+            i_am_the_recorder: matches!(ctx.participant(), Participant::Recorder),
         }))
     }
 
@@ -187,7 +193,7 @@ impl Timer {
             incoming::Message::Start(start) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -203,7 +209,7 @@ impl Timer {
                             Ok(duration) => duration,
                             Err(_) => {
                                 ctx.ws_send(outgoing::Message::Error(
-                                    outgoing::Error::InvalidDuration,
+                                    outgoing::Error::InvalidDuration.into(),
                                 ));
 
                                 return Ok(());
@@ -217,7 +223,7 @@ impl Timer {
                             None => {
                                 log::error!("DateTime overflow in timer module");
                                 ctx.ws_send(outgoing::Message::Error(
-                                    outgoing::Error::InvalidDuration,
+                                    outgoing::Error::InvalidDuration.into(),
                                 ));
 
                                 return Ok(());
@@ -241,7 +247,7 @@ impl Timer {
                     .await?
                 {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::TimerAlreadyRunning,
+                        outgoing::Error::TimerAlreadyRunning.into(),
                     ));
                     return Ok(());
                 }
@@ -264,7 +270,7 @@ impl Timer {
             incoming::Message::Stop(stop) => {
                 if ctx.role() != Role::Moderator {
                     ctx.ws_send(outgoing::Message::Error(
-                        outgoing::Error::InsufficientPermissions,
+                        outgoing::Error::InsufficientPermissions.into(),
                     ));
                     return Ok(());
                 }
@@ -337,6 +343,25 @@ impl Timer {
                     ));
                 }
 
+                if self.i_am_the_recorder {
+                    let ends_at = match started.kind {
+                        outgoing::Kind::Countdown { ends_at } => Some(ends_at),
+                        outgoing::Kind::Stopwatch => None,
+                    };
+
+                    recording::publish_overlay(
+                        ctx,
+                        self.room_id,
+                        recording::Overlay::Timer(recording::overlay::Timer {
+                            id: started.timer_id.0,
+                            started_at: started.started_at,
+                            ends_at,
+                            title: started.title.clone(),
+                        }),
+                    )
+                    .await?;
+                }
+
                 ctx.ws_send(outgoing::Message::Started(started));
             }
             rabbitmq::Event::Stop(stopped) => {
@@ -344,6 +369,17 @@ impl Timer {
                 storage::ready_status::delete(ctx.redis_conn(), self.room_id, self.participant_id)
                     .await?;
 
+                if self.i_am_the_recorder {
+                    recording::publish_overlay(
+                        ctx,
+                        self.room_id,
+                        recording::Overlay::TimerStopped {
+                            id: stopped.timer_id.0,
+                        },
+                    )
+                    .await?;
+                }
+
                 ctx.ws_send(outgoing::Message::Stopped(stopped));
             }
             rabbitmq::Event::UpdateReadyStatus(update_ready_status) => {
@@ -3,11 +3,13 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use crate::TimerId;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::{ParticipantId, Timestamp};
+use types::signaling::{ErrorEnvelope, ModuleError};
 
 /// Outgoing websocket messages
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "message")]
 pub enum Message {
     /// A timer has been started
@@ -17,11 +19,11 @@ pub enum Message {
     /// A participant updated its ready status
     UpdatedReadyStatus(UpdatedReadyStatus),
     /// An error occurred
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
 /// The different timer variations
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum Kind {
     /// The timer continues to run until a moderator stops it.
@@ -31,7 +33,7 @@ pub enum Kind {
 }
 
 /// A timer has been started
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Started {
     /// The timer id
     pub timer_id: TimerId,
@@ -51,7 +53,7 @@ pub struct Started {
 }
 
 /// The current timer has been stopped
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Stopped {
     /// The timer id
     pub timer_id: TimerId,
@@ -64,7 +66,7 @@ pub struct Stopped {
 }
 
 /// The stop reason
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "kind", content = "participant_id")]
 pub enum StopKind {
     /// The timer has been stopped by a moderator
@@ -74,7 +76,7 @@ pub enum StopKind {
 }
 
 /// Update the ready status
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct UpdatedReadyStatus {
     /// The timer id that the update is for
     pub timer_id: TimerId,
@@ -84,7 +86,7 @@ pub struct UpdatedReadyStatus {
     pub status: bool,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "error")]
 pub enum Error {
     /// An invalid timer duration has been configured
@@ -95,6 +97,22 @@ pub enum Error {
     TimerAlreadyRunning,
 }
 
+impl ModuleError for Error {
+    const MODULE: &'static str = "timer";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InvalidDuration => "The duration of the timer is invalid",
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::TimerAlreadyRunning => "A timer is already running",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(self, Self::TimerAlreadyRunning)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::time::SystemTime;
@@ -195,12 +213,15 @@ mod test {
 
     #[test]
     fn error_insufficient_permission() {
-        let stopped = Message::Error(Error::InsufficientPermissions);
+        let stopped = Message::Error(Error::InsufficientPermissions.into());
 
         assert_eq_json!(stopped,
         {
             "message": "error",
+            "module": "timer",
             "error": "insufficient_permissions",
+            "text": "Insufficient permissions for the operation",
+            "retryable": false,
         });
     }
 }
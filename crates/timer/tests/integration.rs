@@ -19,6 +19,7 @@ use test_util::USER_1;
 use test_util::USER_2;
 use test_util::{common, TestContext};
 use types::core::Timestamp;
+use types::signaling::ErrorEnvelope;
 
 /// Helps to compare expected timestamps.
 #[derive(Debug)]
@@ -447,9 +448,10 @@ async fn timer_already_active() {
         .send_ws_message(&USER_1.participant_id, start)
         .unwrap();
 
-    if let WsMessageOutgoing::Module(outgoing::Message::Error(
-        outgoing::Error::TimerAlreadyRunning,
-    )) = module_tester
+    if let WsMessageOutgoing::Module(outgoing::Message::Error(ErrorEnvelope {
+        error: outgoing::Error::TimerAlreadyRunning,
+        ..
+    })) = module_tester
         .receive_ws_message(&USER_1.participant_id)
         .await
         .unwrap()
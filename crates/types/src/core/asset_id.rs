@@ -3,5 +3,7 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 crate::diesel_newtype! {
-    #[derive(Copy)] AssetId(uuid::Uuid) => diesel::sql_types::Uuid, "diesel::sql_types::Uuid"
+    #[derive(Copy)]
+    #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+    AssetId(uuid::Uuid) => diesel::sql_types::Uuid, "diesel::sql_types::Uuid"
 }
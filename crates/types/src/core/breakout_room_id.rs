@@ -8,7 +8,9 @@ use crate::imports::*;
 
 /// The id of a breakout room
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "diesel", derive(FromSqlRow, AsExpression), diesel(sql_type = diesel::sql_types::Uuid))]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "redis", derive(ToRedisArgs), to_redis_args(fmt = "{}"))]
 pub struct BreakoutRoomId(Uuid);
 
@@ -41,3 +43,28 @@ impl std::fmt::Display for BreakoutRoomId {
         self.0.fmt(f)
     }
 }
+
+#[cfg(feature = "diesel")]
+mod diesel_traits {
+    use super::*;
+
+    use diesel::{
+        backend::RawValue,
+        deserialize::{self, FromSql},
+        pg::Pg,
+        serialize::{self, Output, ToSql},
+        sql_types,
+    };
+
+    impl ToSql<sql_types::Uuid, Pg> for BreakoutRoomId {
+        fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+            <Uuid as ToSql<sql_types::Uuid, Pg>>::to_sql(&self.0, out)
+        }
+    }
+
+    impl FromSql<sql_types::Uuid, Pg> for BreakoutRoomId {
+        fn from_sql(bytes: RawValue<Pg>) -> deserialize::Result<Self> {
+            <Uuid as FromSql<sql_types::Uuid, Pg>>::from_sql(bytes).map(Self)
+        }
+    }
+}
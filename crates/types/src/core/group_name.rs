@@ -11,6 +11,7 @@ crate::diesel_newtype! {
         to_redis_args(fmt = "{0}"),
         from_redis_value(FromStr)
     )]
+    #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
     GroupName(String) => diesel::sql_types::Text
 }
 
@@ -15,6 +15,7 @@ use crate::imports::*;
 /// it is used to store all participant related data and relations.
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "redis", derive(ToRedisArgs), to_redis_args(fmt))]
 pub struct ParticipantId(Uuid);
 
@@ -26,6 +26,7 @@ use crate::imports::*;
     derive(Deserialize, Serialize),
     serde(rename_all = "snake_case")
 )]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[cfg_attr(
     feature = "redis",
     derive(ToRedisArgs, FromRedisValue),
@@ -49,6 +50,10 @@ pub enum ParticipationKind {
     /// Recorder participation kind is used for a participant joining as a
     /// recording service.
     Recorder,
+
+    /// Bot participation kind is used for automated participants joining on
+    /// behalf of an external service, e.g. for captioning or note-taking.
+    Bot,
 }
 
 impl ParticipationKind {
@@ -71,6 +76,7 @@ mod tests {
         assert_eq!(ParticipationKind::User.as_ref(), "user");
         assert_eq!(ParticipationKind::Sip.as_ref(), "sip");
         assert_eq!(ParticipationKind::Recorder.as_ref(), "recorder");
+        assert_eq!(ParticipationKind::Bot.as_ref(), "bot");
     }
 
     #[test]
@@ -91,6 +97,10 @@ mod tests {
             ParticipationKind::from_str("recorder"),
             Ok(ParticipationKind::Recorder)
         );
+        assert_eq!(
+            ParticipationKind::from_str("bot"),
+            Ok(ParticipationKind::Bot)
+        );
     }
 
     #[test]
@@ -99,5 +109,6 @@ mod tests {
         assert!(ParticipationKind::User.is_visible());
         assert!(ParticipationKind::Sip.is_visible());
         assert!(!ParticipationKind::Recorder.is_visible());
+        assert!(ParticipationKind::Bot.is_visible());
     }
 }
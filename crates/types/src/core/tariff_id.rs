@@ -4,6 +4,7 @@
 
 crate::diesel_newtype! {
     #[derive(Copy)]
+    #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
     TariffId(uuid::Uuid) => diesel::sql_types::Uuid
 }
 
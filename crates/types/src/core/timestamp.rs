@@ -13,6 +13,7 @@ use crate::imports::*;
 /// The values are stores as unix timestamps in redis.
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Timestamp(DateTime<Utc>);
 
 impl Timestamp {
@@ -26,6 +26,7 @@
 //!
 //! Depends on:
 //! - `diesel`
+//! - `json-schema`
 //! - `redis`
 //! - `kustos`
 //! - `serde`
@@ -45,6 +46,14 @@
 //! Depends on:
 //! - `serde`
 //!
+//! ## `json-schema`
+//!
+//! Derives [`schemars::JsonSchema`] for the types used in signaling messages, so
+//! JSON schemas of the signaling API can be generated.
+//!
+//! Depends on:
+//! - `serde`
+//!
 //! ## `redis`
 //!
 //! Implements [Redis](https://docs.rs/redis/) `ToRedisArgs` and `FromRedisValue`
@@ -5,6 +5,8 @@
 //! This module contains types that are used by the signaling communication
 //! (typically through websockets)
 
+mod error;
 mod namespaced;
 
+pub use error::{ErrorEnvelope, ModuleError};
 pub use namespaced::{NamespacedCommand, NamespacedEvent};
@@ -0,0 +1,53 @@
+// SPDX-FileCopyrightText: OpenTalk GmbH <mail@opentalk.eu>
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use crate::imports::*;
+use std::borrow::Cow;
+
+/// An error a signaling module reports to the client.
+///
+/// Implemented by the error codes of every module, so the errors can be sent
+/// inside an [`ErrorEnvelope`].
+pub trait ModuleError {
+    /// Name of the module reporting the error
+    const MODULE: &'static str;
+
+    /// Human readable description of the error
+    fn text(&self) -> &'static str;
+
+    /// Whether repeating the failed request later might succeed
+    fn retryable(&self) -> bool {
+        false
+    }
+}
+
+/// The envelope of an error sent to the client.
+///
+/// Contains the machine readable error code of a module in the `error` field, together
+/// with the reporting module, a human readable text and whether the request can be retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ErrorEnvelope<E> {
+    /// Name of the module reporting the error
+    pub module: Cow<'static, str>,
+    /// The machine readable error code
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub error: E,
+    /// Human readable description of the error
+    pub text: Cow<'static, str>,
+    /// Whether repeating the failed request later might succeed
+    pub retryable: bool,
+}
+
+impl<E: ModuleError> From<E> for ErrorEnvelope<E> {
+    fn from(error: E) -> Self {
+        Self {
+            module: Cow::Borrowed(E::MODULE),
+            text: Cow::Borrowed(error.text()),
+            retryable: error.retryable(),
+            error,
+        }
+    }
+}
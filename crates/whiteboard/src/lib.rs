@@ -118,9 +118,6 @@ impl SignalingModule for Whiteboard {
                             log::error!("Whiteboard module received `Initialized` but spacedeck was not initialized");
                         }
                     }
-                    rabbitmq::Event::PdfAsset(pdf_asset) => {
-                        ctx.ws_send(outgoing::Message::PdfAsset(pdf_asset));
-                    }
                 }
                 Ok(())
             }
@@ -130,7 +127,7 @@ impl SignalingModule for Whiteboard {
                     incoming::Message::Initialize => {
                         if ctx.role() != Role::Moderator {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InsufficientPermissions,
+                                outgoing::Error::InsufficientPermissions.into(),
                             ));
                             return Ok(());
                         }
@@ -145,7 +142,7 @@ impl SignalingModule for Whiteboard {
                             self.cleanup(ctx.redis_conn()).await?;
 
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InitializationFailed,
+                                outgoing::Error::InitializationFailed.into(),
                             ));
                         }
                     }
@@ -153,7 +150,7 @@ impl SignalingModule for Whiteboard {
                     incoming::Message::GeneratePdf => {
                         if ctx.role() != Role::Moderator {
                             ctx.ws_send(outgoing::Message::Error(
-                                outgoing::Error::InsufficientPermissions,
+                                outgoing::Error::InsufficientPermissions.into(),
                             ));
                             return Ok(());
                         }
@@ -190,6 +187,7 @@ impl SignalingModule for Whiteboard {
                     &self.storage,
                     self.db.clone(),
                     self.room_id.room_id(),
+                    self.room_id.breakout_room_id(),
                     Some(Self::NAMESPACE),
                     &filename,
                     "whiteboard_pdf",
@@ -197,10 +195,11 @@ impl SignalingModule for Whiteboard {
                 )
                 .await?;
 
-                ctx.rabbitmq_publish(
+                // Sent unchanged to everyone in the room, serialize it only once
+                ctx.rabbitmq_publish_shared(
                     control::rabbitmq::current_room_exchange_name(self.room_id),
                     control::rabbitmq::room_all_routing_key().into(),
-                    rabbitmq::Event::PdfAsset(PdfAsset { filename, asset_id }),
+                    outgoing::Message::PdfAsset(PdfAsset { filename, asset_id }),
                 );
 
                 Ok(())
@@ -220,7 +219,7 @@ impl SignalingModule for Whiteboard {
         // and we hold the r3dlock in the destroy context.
 
         if ctx.destroy_room() {
-            if let Err(err) = self.cleanup(ctx.redis_conn()).await {
+            if let Err(err) = cleanup(&self.client, ctx.redis_conn(), self.room_id).await {
                 log::error!(
                     "Failed to cleanup spacedeck for room `{}`: {}",
                     self.room_id,
@@ -229,6 +228,37 @@ impl SignalingModule for Whiteboard {
             }
         }
     }
+
+    async fn cleanup_empty_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        let client = SpacedeckClient::new(params.url.clone(), params.api_key.clone());
+
+        cleanup(&client, redis_conn, room).await
+    }
+
+    async fn prepare_room(
+        params: &Self::Params,
+        redis_conn: &mut RedisConnection,
+        room: SignalingRoomId,
+    ) -> Result<()> {
+        if state::try_start_init(redis_conn, room).await?.is_some() {
+            // Already initialized or currently being initialized
+            return Ok(());
+        }
+
+        let client = SpacedeckClient::new(params.url.clone(), params.api_key.clone());
+
+        if let Err(e) = init_space(&client, redis_conn, room).await {
+            state::del(redis_conn, room).await?;
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
 }
 
 impl Whiteboard {
@@ -240,30 +270,14 @@ impl Whiteboard {
         match state::try_start_init(ctx.redis_conn(), self.room_id).await? {
             Some(state) => match state {
                 InitState::Initializing => ctx.ws_send(outgoing::Message::Error(
-                    outgoing::Error::CurrentlyInitializing,
+                    outgoing::Error::CurrentlyInitializing.into(),
                 )),
                 InitState::Initialized(_) => ctx.ws_send(outgoing::Message::Error(
-                    outgoing::Error::AlreadyInitialized,
+                    outgoing::Error::AlreadyInitialized.into(),
                 )),
             },
             None => {
-                let response = self
-                    .client
-                    .create_space(&self.room_id.to_string(), None)
-                    .await?;
-
-                let url = self.client.base_url.join(&format!(
-                    "s/{hash}-{slug}",
-                    hash = response.edit_hash,
-                    slug = response.edit_slug
-                ))?;
-
-                let space_info = SpaceInfo {
-                    id: response.id,
-                    url,
-                };
-
-                state::set_initialized(ctx.redis_conn(), self.room_id, space_info).await?;
+                init_space(&self.client, ctx.redis_conn(), self.room_id).await?;
 
                 ctx.rabbitmq_publish(
                     control::rabbitmq::current_room_exchange_name(self.room_id),
@@ -274,21 +288,48 @@ impl Whiteboard {
         }
         Ok(())
     }
+}
 
-    async fn cleanup(&self, redis_conn: &mut RedisConnection) -> Result<()> {
-        let state = match state::get(redis_conn, self.room_id).await? {
-            Some(state) => state,
-            None => return Ok(()),
-        };
+/// Creates the spacedeck space of the room and marks the room as initialized
+async fn init_space(
+    client: &SpacedeckClient,
+    redis_conn: &mut RedisConnection,
+    room_id: SignalingRoomId,
+) -> Result<()> {
+    let response = client.create_space(&room_id.to_string(), None).await?;
 
-        state::del(redis_conn, self.room_id).await?;
+    let url = client.base_url.join(&format!(
+        "s/{hash}-{slug}",
+        hash = response.edit_hash,
+        slug = response.edit_slug
+    ))?;
 
-        if let InitState::Initialized(space_info) = state {
-            self.client.delete_space(&space_info.id).await?;
-        }
+    let space_info = SpaceInfo {
+        id: response.id,
+        url,
+    };
 
-        Ok(())
+    state::set_initialized(redis_conn, room_id, space_info).await
+}
+
+/// Removes the room related space from spacedeck
+async fn cleanup(
+    client: &SpacedeckClient,
+    redis_conn: &mut RedisConnection,
+    room_id: SignalingRoomId,
+) -> Result<()> {
+    let state = match state::get(redis_conn, room_id).await? {
+        Some(state) => state,
+        None => return Ok(()),
+    };
+
+    state::del(redis_conn, room_id).await?;
+
+    if let InitState::Initialized(space_info) = state {
+        client.delete_space(&space_info.id).await?;
     }
+
+    Ok(())
 }
 
 pub fn register(controller: &mut controller::Controller) {
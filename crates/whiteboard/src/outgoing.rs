@@ -2,30 +2,32 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use types::core::AssetId;
+use types::signaling::{ErrorEnvelope, ModuleError};
 use url::Url;
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "message")]
 pub enum Message {
     SpaceUrl(AccessUrl),
     PdfAsset(PdfAsset),
-    Error(Error),
+    Error(ErrorEnvelope<Error>),
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 pub struct AccessUrl {
     pub url: Url,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct PdfAsset {
     pub filename: String,
     pub asset_id: AssetId,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "error")]
 pub enum Error {
     /// The requesting user has insufficient permissions for the operation
@@ -37,3 +39,23 @@ pub enum Error {
     /// Spacedeck is already initialized
     AlreadyInitialized,
 }
+
+impl ModuleError for Error {
+    const MODULE: &'static str = "whiteboard";
+
+    fn text(&self) -> &'static str {
+        match self {
+            Self::InsufficientPermissions => "Insufficient permissions for the operation",
+            Self::CurrentlyInitializing => "The whiteboard is currently being initialized",
+            Self::InitializationFailed => "The whiteboard could not be initialized",
+            Self::AlreadyInitialized => "The whiteboard has already been initialized",
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::CurrentlyInitializing | Self::InitializationFailed
+        )
+    }
+}